@@ -0,0 +1,195 @@
+//! 基准测试的最小框架，用法和输出仿照 criterion，不依赖外部 crate
+//!
+//! `cargo bench` 运行时（参数中有 --bench）每个基准先预热，再采集若干个样本，报告单次运行时间的
+//! [最小 中位数 最大] 和按元素计的吞吐量。结果保存在 target/tinykv-bench/<baseline>.tsv，
+//! 下次运行时先与保存的结果比较，打印中位数的变化。不带 --bench 运行时（`cargo test --benches`）
+//! 每个基准只执行一次，用来检查它们能跑通。
+//!
+//! 参数：`[FILTER] [--save-baseline NAME] [--baseline NAME]`
+//! - FILTER：只运行名称包含它的基准
+//! - --save-baseline NAME：与 NAME 比较后用本次结果覆盖 NAME，默认 NAME 为 base
+//! - --baseline NAME：只与 NAME 比较，不保存
+//!
+//! 比较一个改动前后的性能：改动前 `cargo bench -- --save-baseline before`，
+//! 改动后 `cargo bench -- --baseline before`。
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// 预热至少运行这么久
+const WARMUP_TIME: Duration = Duration::from_millis(500);
+// 采样阶段的目标总时长，样本数按预热时的单次时间估算
+const MEASUREMENT_TIME: Duration = Duration::from_secs(3);
+const MIN_SAMPLES: usize = 5;
+const MAX_SAMPLES: usize = 50;
+// 变化在这个比例以内时视为噪声
+const NOISE_THRESHOLD: f64 = 0.02;
+
+/// 一个基准的结果，中位数用于与基线比较
+struct Record {
+    name: String,
+    median: Duration,
+    elements: u64,
+}
+
+pub struct Harness {
+    filter: Option<String>,
+    /// 为 false 时每个基准只运行一次
+    measure: bool,
+    baseline: HashMap<String, Duration>,
+    baseline_name: String,
+    save: bool,
+    records: Vec<Record>,
+}
+
+impl Harness {
+    /// 按命令行参数创建；未知的以 - 开头的参数（cargo 传入的 --bench 之外）被忽略
+    pub fn from_args() -> Self {
+        let mut filter = None;
+        let mut measure = false;
+        let mut baseline_name = "base".to_string();
+        let mut save = true;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bench" => measure = true,
+                "--save-baseline" => baseline_name = args.next().expect("--save-baseline requires a name"),
+                "--baseline" => {
+                    baseline_name = args.next().expect("--baseline requires a name");
+                    save = false;
+                }
+                other if other.starts_with('-') => {}
+                other => filter = Some(other.to_string()),
+            }
+        }
+        let baseline = if measure { load_baseline(&baseline_path(&baseline_name)) } else { HashMap::new() };
+        Harness { filter, measure, baseline, baseline_name, save, records: Vec::new() }
+    }
+
+    /// 测量 routine，每次运行处理 elements 个元素
+    pub fn bench(&mut self, name: &str, elements: u64, mut routine: impl FnMut()) {
+        self.bench_with_setup(name, elements, || (), |_| routine());
+    }
+
+    /// 每次运行前调用 setup 准备输入，只计 routine 的时间；输入在计时结束后才丢弃
+    pub fn bench_with_setup<S>(&mut self, name: &str, elements: u64, mut setup: impl FnMut() -> S, mut routine: impl FnMut(&mut S)) {
+        if self.filter.as_ref().is_some_and(|f| !name.contains(f.as_str())) {
+            return;
+        }
+        let mut run = || {
+            let mut input = setup();
+            let started = Instant::now();
+            routine(&mut input);
+            let elapsed = started.elapsed();
+            drop(input);
+            elapsed
+        };
+        if !self.measure {
+            run();
+            println!("{} ... ok", name);
+            return;
+        }
+
+        let warmup_started = Instant::now();
+        let (mut warmup_runs, mut warmup_total) = (0u32, Duration::ZERO);
+        while warmup_started.elapsed() < WARMUP_TIME {
+            warmup_total += run();
+            warmup_runs += 1;
+        }
+        let estimate = warmup_total / warmup_runs;
+        let samples = (MEASUREMENT_TIME.as_secs_f64() / estimate.as_secs_f64().max(1e-9)) as usize;
+
+        let mut times: Vec<Duration> = (0..samples.clamp(MIN_SAMPLES, MAX_SAMPLES)).map(|_| run()).collect();
+        times.sort();
+        let median = times[times.len() / 2];
+        println!(
+            "{:<32} time:   [{} {} {}]",
+            name,
+            format_time(times[0]),
+            format_time(median),
+            format_time(times[times.len() - 1])
+        );
+        println!("{:<32} thrpt:  {}", "", format_throughput(elements, median));
+        if let Some(&base) = self.baseline.get(name) {
+            let change = median.as_secs_f64() / base.as_secs_f64().max(1e-12) - 1.0;
+            let verdict = match change {
+                c if c.abs() <= NOISE_THRESHOLD => "no change",
+                c if c < 0.0 => "improved",
+                _ => "regressed",
+            };
+            println!(
+                "{:<32} change: {:+.2}% vs {} ({}, {})",
+                "",
+                change * 100.0,
+                self.baseline_name,
+                format_time(base),
+                verdict
+            );
+        }
+        self.records.push(Record { name: name.to_string(), median, elements });
+    }
+
+    /// 保存本次的结果作为基线
+    pub fn finish(self) {
+        if !self.measure || !self.save || self.records.is_empty() {
+            return;
+        }
+        // 保留基线中本次没有运行（被 FILTER 过滤掉）的基准
+        let mut lines: Vec<String> = self
+            .baseline
+            .iter()
+            .filter(|(name, _)| !self.records.iter().any(|r| &r.name == *name))
+            .map(|(name, median)| format!("{}\t{}", name, median.as_nanos()))
+            .collect();
+        lines.extend(self.records.iter().map(|r| format!("{}\t{}\t{}", r.name, r.median.as_nanos(), r.elements)));
+        lines.sort();
+        let path = baseline_path(&self.baseline_name);
+        let saved = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(&path, lines.join("\n") + "\n"));
+        match saved {
+            Ok(()) => println!("saved baseline '{}' to {}", self.baseline_name, path.display()),
+            Err(e) => eprintln!("failed to save baseline {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn baseline_path(name: &str) -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"));
+    target.join("tinykv-bench").join(format!("{}.tsv", name))
+}
+
+// 每行为 名称 \t 中位数纳秒 [\t 元素数]，无法解析的行被忽略
+fn load_baseline(path: &PathBuf) -> HashMap<String, Duration> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next()?;
+            let nanos: u64 = fields.next()?.parse().ok()?;
+            Some((name.to_string(), Duration::from_nanos(nanos)))
+        })
+        .collect()
+}
+
+fn format_time(d: Duration) -> String {
+    let ns = d.as_nanos() as f64;
+    match ns {
+        n if n < 1e3 => format!("{:.2} ns", n),
+        n if n < 1e6 => format!("{:.2} µs", n / 1e3),
+        n if n < 1e9 => format!("{:.2} ms", n / 1e6),
+        n => format!("{:.2} s", n / 1e9),
+    }
+}
+
+fn format_throughput(elements: u64, per_run: Duration) -> String {
+    let rate = elements as f64 / per_run.as_secs_f64().max(1e-12);
+    match rate {
+        r if r < 1e3 => format!("{:.2} elem/s", r),
+        r if r < 1e6 => format!("{:.2} Kelem/s", r / 1e3),
+        r if r < 1e9 => format!("{:.2} Melem/s", r / 1e6),
+        r => format!("{:.2} Gelem/s", r / 1e9),
+    }
+}
@@ -0,0 +1,147 @@
+//! 存储层的基准测试：`cargo bench --bench storage [-- FILTER]`
+//!
+//! 数据集由 testing::datagen 按固定种子生成，并发负载直接调用 RawKeyValueApi，不经过网络。
+//! 参数和基线比较见 harness 模块。
+
+mod harness;
+
+use harness::Harness;
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::Command;
+use tinykv_rs::storage::StandaloneStorage;
+use tinykv_rs::testing::datagen::{key, value, Dataset, Rng};
+use tinykv_rs::testing::temp_dir;
+
+use std::sync::Arc;
+use std::thread;
+
+const CF: &str = "bench";
+const SEED: u64 = 42;
+const VALUE_SIZE: usize = 100;
+// 每次运行写入、读取或加载的条目数
+const COUNT: u64 = 10_000;
+// scan 基准每次运行的扫描次数
+const SCANS: usize = 100;
+
+fn loaded_storage(dataset: Dataset) -> StandaloneStorage {
+    let storage = StandaloneStorage::in_memory();
+    dataset.load_into(&storage, CF).expect("load dataset");
+    storage
+}
+
+fn bench_put_get(h: &mut Harness, dataset: Dataset) {
+    h.bench_with_setup(
+        "put/single",
+        dataset.count,
+        || (StandaloneStorage::in_memory(), dataset.batches(CF, 1)),
+        |(storage, batches)| {
+            for batch in batches.drain(..) {
+                storage.write(batch).unwrap();
+            }
+        },
+    );
+
+    let storage = loaded_storage(dataset);
+    let keys: Vec<Vec<u8>> = dataset.sample(SEED, dataset.count as usize).into_iter().map(key).collect();
+    h.bench("get/single", dataset.count, || {
+        for k in &keys {
+            assert!(storage.reader().unwrap().get_cf(CF, k).unwrap().is_some());
+        }
+    });
+}
+
+fn bench_batches(h: &mut Harness, dataset: Dataset) {
+    for size in [10, 100, 1000] {
+        h.bench_with_setup(
+            &format!("write/batch-{}", size),
+            dataset.count,
+            || (StandaloneStorage::in_memory(), dataset.batches(CF, size)),
+            |(storage, batches)| {
+                for batch in batches.drain(..) {
+                    storage.write(batch).unwrap();
+                }
+            },
+        );
+    }
+}
+
+fn bench_scans(h: &mut Harness, dataset: Dataset) {
+    let storage = loaded_storage(dataset);
+    for width in [10, 100, 1000] {
+        // 起点留出 width 个键，每次扫描都返回整宽
+        let starts: Vec<Vec<u8>> = Dataset::new(dataset.count - width as u64, 0).sample(SEED, SCANS).into_iter().map(key).collect();
+        h.bench(&format!("scan/width-{}", width), (SCANS * width) as u64, || {
+            let reader = storage.reader().unwrap();
+            for start in &starts {
+                assert_eq!(reader.scan_cf(CF, start, None, width, None).unwrap().len(), width);
+            }
+        });
+    }
+}
+
+fn bench_flush_load(h: &mut Harness, dataset: Dataset) {
+    h.bench_with_setup(
+        &format!("flush/{}", dataset.count),
+        dataset.count,
+        || {
+            let dir = temp_dir("bench_flush");
+            let storage = StandaloneStorage::open(&dir).expect("open storage");
+            dataset.load_into(&storage, CF).expect("load dataset");
+            // 先丢弃存储再删除目录
+            (storage, dir)
+        },
+        |(storage, _)| {
+            storage.flush().unwrap();
+        },
+    );
+
+    let dir = temp_dir("bench_load");
+    {
+        let storage = StandaloneStorage::open(&dir).expect("open storage");
+        dataset.load_into(&storage, CF).expect("load dataset");
+        storage.flush().expect("flush dataset");
+    }
+    h.bench(&format!("load/{}", dataset.count), dataset.count, || {
+        let storage = StandaloneStorage::open(&dir).unwrap();
+        assert_eq!(storage.total_keys(), dataset.count as usize);
+    });
+}
+
+// 每个线程使用自己的会话，按 read:write 的比例随机 Get 或 Put 数据集中的键
+fn bench_mixed(h: &mut Harness, dataset: Dataset, threads: usize, read: u64, write: u64) {
+    let api = RawKeyValueApi::new(Arc::new(loaded_storage(dataset)));
+    let name = format!("api/mixed-{}-{}/threads-{}", read, write, threads);
+    h.bench(&name, dataset.count, || {
+        thread::scope(|scope| {
+            for t in 0..threads {
+                let api = &api;
+                let share = dataset.count / threads as u64 + u64::from((t as u64) < dataset.count % threads as u64);
+                scope.spawn(move || {
+                    let mut session = api.new_session();
+                    let mut rng = Rng::new(SEED + t as u64);
+                    for _ in 0..share {
+                        let i = rng.below(dataset.count);
+                        let cmd = match rng.below(read + write) < read {
+                            true => Command::Get { cf: CF.to_string(), key: key(i) },
+                            false => Command::Put { cf: CF.to_string(), key: key(i), value: value(i, dataset.value_size) },
+                        };
+                        api.handle_command(&mut session, cmd);
+                    }
+                });
+            }
+        });
+    });
+}
+
+fn main() {
+    let mut h = Harness::from_args();
+    let dataset = Dataset::new(COUNT, VALUE_SIZE);
+    bench_put_get(&mut h, dataset);
+    bench_batches(&mut h, dataset);
+    bench_scans(&mut h, dataset);
+    bench_flush_load(&mut h, dataset);
+    for threads in [1, 4] {
+        bench_mixed(&mut h, dataset, threads, 80, 20);
+    }
+    h.finish();
+}
@@ -0,0 +1,25 @@
+use std::path::Path;
+use std::process::Command;
+
+// 把构建时的 git 提交写入 TINYKV_GIT_HASH，供 protocol::GIT_HASH 使用；
+// 环境中已经设置时直接沿用，不在 git 仓库中或没有 git 时不设置
+fn main() {
+    println!("cargo:rerun-if-env-changed=TINYKV_GIT_HASH");
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    if std::env::var_os("TINYKV_GIT_HASH").is_some() {
+        return;
+    }
+    let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output();
+    if let Ok(output) = output
+        && output.status.success()
+    {
+        let hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !hash.is_empty() {
+            println!("cargo:rustc-env=TINYKV_GIT_HASH={}", hash);
+        }
+    }
+}
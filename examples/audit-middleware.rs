@@ -0,0 +1,51 @@
+//! 中间件示例：记录慢命令，并禁止删除 audit 列族中的键
+//! cargo run --example audit-middleware
+
+use tinykv_rs::client::KvClient;
+use tinykv_rs::common::{Command, Response};
+use tinykv_rs::server::{ConnContext, KvServer, Middleware, ServerConfig, ShutdownOptions};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug)]
+struct Audit {
+    slow: Duration,
+}
+
+impl Middleware for Audit {
+    fn before(&self, ctx: &ConnContext, cmd: &Command) -> Result<(), Box<Response>> {
+        match cmd {
+            Command::Delete { cf, .. } if cf == "audit" => {
+                eprintln!("[audit] connection {} tried to delete from audit", ctx.conn_id);
+                Err(Box::new(Response::Error("Forbidden: audit entries are append-only".to_string())))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn after(&self, ctx: &ConnContext, cmd: &Command, _response: &Response, elapsed: Duration) {
+        if elapsed >= self.slow {
+            eprintln!("[audit] slow {} on connection {} ({}): {:?}", cmd.kind(), ctx.conn_id, ctx.peer_addr, elapsed);
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = ServerConfig {
+        middlewares: vec![Arc::new(Audit { slow: Duration::from_millis(10) })],
+        ..ServerConfig::default()
+    };
+    let handle = KvServer::with_config(config)?.start_background("127.0.0.1:0")?;
+    let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+    client.put("audit", "login:alice", "2024-01-01T00:00:00Z")?;
+    match client.delete("audit", "login:alice") {
+        Ok(()) => println!("delete succeeded"),
+        Err(e) => println!("delete rejected: {}", e),
+    }
+    println!("entry: {:?}", client.get("audit", "login:alice")?);
+
+    handle.shutdown(ShutdownOptions { flush: false, ..ShutdownOptions::default() })?;
+    Ok(())
+}
@@ -1,3 +1,6 @@
+//! 客户端示例，需要先启动服务器：
+//! cargo run --bin kv-server，然后 cargo run --example kv-client-demo
+
 use tinykv_rs::client;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -111,6 +114,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("✓ 获取不存在的键: {:?}", non_existent);
     println!("✓ 返回 None 表示键不存在\n");
 
+    // ===== 示例 9: 版本历史 =====
+    println!("【示例 9】版本历史");
+    println!("{}", "-".repeat(50));
+
+    client.put("default", "counter", "1")?;
+    client.put("default", "counter", "2")?;
+    match client.history("default", "counter", 10) {
+        Ok(versions) => {
+            println!("✓ History: cf=default, key=counter");
+            for v in &versions {
+                let value = v.value.as_ref().map(|b| String::from_utf8_lossy(&b.0).to_string());
+                println!("  - v{} @ {}ms: {:?}", v.version, v.timestamp_ms, value);
+            }
+        }
+        Err(e) => println!("✗ History 不可用: {}", e),
+    }
+    println!();
+
     println!("=== 所有示例执行完成 ===");
 
     Ok(())
@@ -165,7 +186,7 @@ mod tests {
         }
 
         let results = client.scan("default", "key0", Some("key3"), 10)?;
-        assert!(results.len() > 0);
+        assert!(!results.is_empty());
 
         Ok(())
     }
@@ -0,0 +1,27 @@
+//! tracing 示例：安装 tracing_subscriber 的 fmt 输出，打印每个连接和命令的 span
+//! cargo run --example tracing-spans --features tracing
+//!
+//! span 关闭时输出一行，其中带有 cmd、cf、key_len、status、lock_wait_us 和 exec_us 等字段
+
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::{KvServer, ServerConfig, ShutdownOptions};
+
+use tracing_subscriber::fmt::format::FmtSpan;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_span_events(FmtSpan::CLOSE).with_target(false).init();
+
+    let handle = KvServer::with_config(ServerConfig::default())?.start_background("127.0.0.1:0")?;
+    let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+    client.put("users", "u1", "Alice")?;
+    println!("get: {:?}", client.get("users", "u1")?);
+    println!("scan: {:?}", client.scan("users", "", None, 10)?);
+    if let Err(e) = client.create_cf("users", None) {
+        println!("create_cf: {}", e);
+    }
+    drop(client);
+
+    handle.shutdown(ShutdownOptions { flush: false, ..ShutdownOptions::default() })?;
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! 服务器的访问日志
+//!
+//! 每个请求回复之后输出一行 Info 级别的日志，记录命令和结果，便于把错误与请求对应起来。
+//! 字段的名称和顺序是稳定的，以后只会在末尾增加字段：
+//!
+//! | 字段 | 含义 |
+//! |------|------|
+//! | conn | 连接 ID，与 Clients 列表一致 |
+//! | seq | 连接上的第几个请求，从 1 开始 |
+//! | cmd | 命令类型，见 Command::kind |
+//! | cf | 命令作用的列族，多个时以逗号分隔；不针对列族的命令没有 |
+//! | key_len | 命令中所有键的字节数之和；不带键的命令没有 |
+//! | status | ok 或 error |
+//! | code | 错误的错误码（见 protocol::error_code）；成功或没有错误码时没有 |
+//! | duration_us | 从读到请求到回复写完的微秒数 |
+//! | resp_bytes | 回复的字节数，分帧发送时为所有帧之和 |
+//!
+//! 文本格式（LogFormat::Text）以 `INFO access` 开头，之后是空格分隔的 `名称=值`，没有的字段写作 `-`，
+//! 列族名按 protocol::display_bytes 转义，其中的空格写作 `\x20`：
+//!
+//! `INFO access conn=7 seq=3 cmd=Put cf=users key_len=2 status=ok code=- duration_us=85 resp_bytes=11`
+//!
+//! JSON 格式（LogFormat::Json）每行一个对象，另有 `"level":"info"` 和 `"msg":"access"`，没有的字段为 null。
+//! 两种格式都不包含键和值的内容。
+
+use crate::protocol::{self, Command, Response};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 日志的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format '{}', expected text|json", other)),
+        }
+    }
+}
+
+/// 日志行的去处，默认为标准输出；测试可以换成收集日志行的实现
+pub trait LogSink: Send + Sync + fmt::Debug {
+    /// 写入一行日志，不含换行
+    fn write_line(&self, line: &str);
+}
+
+/// 把日志行打印到标准输出
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write_line(&self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// 请求的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Error,
+}
+
+/// 访问日志中的一行，字段见模块文档
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRecord {
+    pub conn: u64,
+    pub seq: u64,
+    pub cmd: String,
+    pub cf: Option<String>,
+    pub key_len: Option<usize>,
+    pub status: Status,
+    pub code: Option<String>,
+    pub duration_us: u64,
+    pub resp_bytes: u64,
+}
+
+impl AccessRecord {
+    /// cmd 为中间件看到的命令（已替换默认列族），response 为分帧前的完整响应
+    pub fn new(conn: u64, seq: u64, cmd: &Command, response: &Response, duration: Duration, resp_bytes: u64) -> Self {
+        // 去重，保留第一次出现的顺序
+        let mut cfs: Vec<&str> = Vec::new();
+        for cf in cmd.cfs() {
+            if !cfs.contains(&cf) {
+                cfs.push(cf);
+            }
+        }
+        let keys = cmd.keys();
+        let (status, code) = match response {
+            Response::Error(message) => (Status::Error, protocol::error_code(message).map(str::to_string)),
+            _ => (Status::Ok, None),
+        };
+        AccessRecord {
+            conn,
+            seq,
+            cmd: cmd.kind().to_string(),
+            cf: (!cfs.is_empty()).then(|| cfs.join(",")),
+            key_len: (!keys.is_empty()).then(|| keys.iter().map(|k| k.len()).sum()),
+            status,
+            code,
+            duration_us: duration.as_micros() as u64,
+            resp_bytes,
+        }
+    }
+
+    /// 按 format 渲染为一行
+    pub fn to_line(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => {
+                let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+                format!(
+                    "INFO access conn={} seq={} cmd={} cf={} key_len={} status={} code={} duration_us={} resp_bytes={}",
+                    self.conn,
+                    self.seq,
+                    self.cmd,
+                    or_dash(self.cf.as_deref().map(escape)),
+                    or_dash(self.key_len.map(|n| n.to_string())),
+                    match self.status {
+                        Status::Ok => "ok",
+                        Status::Error => "error",
+                    },
+                    or_dash(self.code.clone()),
+                    self.duration_us,
+                    self.resp_bytes
+                )
+            }
+            LogFormat::Json => {
+                #[derive(Serialize)]
+                struct Line<'a> {
+                    level: &'static str,
+                    msg: &'static str,
+                    #[serde(flatten)]
+                    record: &'a AccessRecord,
+                }
+                serde_json::to_string(&Line { level: "info", msg: "access", record: self }).unwrap_or_default()
+            }
+        }
+    }
+}
+
+// 文本格式中的值不能含空白，列族名可以是任意字符串
+fn escape(value: &str) -> String {
+    protocol::display_bytes(value.as_bytes()).replace(' ', "\\x20")
+}
+
+/// 服务器的访问日志：格式和去处
+#[derive(Debug, Clone)]
+pub struct AccessLog {
+    format: LogFormat,
+    sink: Arc<dyn LogSink>,
+}
+
+impl AccessLog {
+    pub fn new(format: LogFormat, sink: Arc<dyn LogSink>) -> Self {
+        AccessLog { format, sink }
+    }
+
+    pub fn record(&self, record: &AccessRecord) {
+        self.sink.write_line(&record.to_line(self.format));
+    }
+}
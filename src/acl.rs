@@ -0,0 +1,182 @@
+//! 按列族划分的访问控制
+//!
+//! ServerConfig::principals 中的每个主体有自己的令牌（只保存 SHA-256）和一组按列族名通配
+//! 的权限。连接用 Command::Auth 认证后按主体的权限执行命令；配置了主体时，没有认证的连接
+//! 不能访问任何列族。通过 AdminAuth 的连接不受限制。
+//!
+//! 通配符 `*` 匹配任意长度的字符，`?` 匹配一个字符，只与列族名匹配，不区分数据库。
+
+use crate::protocol::Command;
+use crate::sha256;
+
+use serde::{Deserialize, Serialize};
+
+/// 匹配的列族上允许的操作
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CfPermission {
+    pub cf_glob: String,
+    #[serde(default)]
+    pub read: bool,
+    #[serde(default)]
+    pub write: bool,
+    /// 管理命令；不指定列族的管理命令（Flush、Compact 等）需要 cf_glob 为 "*" 的授权
+    #[serde(default)]
+    pub admin: bool,
+}
+
+impl CfPermission {
+    pub fn new(cf_glob: &str, read: bool, write: bool, admin: bool) -> Self {
+        CfPermission { cf_glob: cf_glob.to_string(), read, write, admin }
+    }
+
+    fn grants(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Read => self.read,
+            Permission::Write => self.write,
+            Permission::Admin => self.admin,
+        }
+    }
+}
+
+/// 服务端配置的主体
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Principal {
+    pub name: String,
+    /// 令牌的 SHA-256（十六进制），见 hash_token
+    pub token_sha256: String,
+    pub permissions: Vec<CfPermission>,
+}
+
+impl Principal {
+    pub fn new(name: &str, token: &str, permissions: Vec<CfPermission>) -> Self {
+        Principal { name: name.to_string(), token_sha256: hash_token(token), permissions }
+    }
+
+    /// 主体在 cf 上是否有 permission
+    pub fn allows(&self, cf: &str, permission: Permission) -> bool {
+        allows(&self.permissions, cf, permission)
+    }
+}
+
+/// 命令需要的权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Admin => "admin",
+        }
+    }
+
+    /// 命令需要的权限：管理命令需要 admin，修改类命令需要 write，其余需要 read
+    pub fn required(cmd: &Command) -> Self {
+        if cmd.requires_admin() {
+            Permission::Admin
+        } else if cmd.is_read_only() {
+            Permission::Read
+        } else {
+            Permission::Write
+        }
+    }
+}
+
+/// Info 中报告的当前连接的有效权限
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectivePermissions {
+    /// 认证的主体名；通过 AdminAuth 或未启用认证时为 "admin"，未认证时为 "anonymous"
+    pub principal: String,
+    /// 不受列族权限限制
+    pub unrestricted: bool,
+    pub permissions: Vec<CfPermission>,
+}
+
+/// 令牌的 SHA-256（十六进制），用于 Principal::token_sha256
+pub fn hash_token(token: &str) -> String {
+    sha256::hex_digest(token.as_bytes())
+}
+
+/// 检查 principal（None 表示未认证的连接）能否执行 cmd，cmd 中的列族必须已经替换了默认列族；
+/// 不允许时返回 PermissionDenied 错误，说明缺少的权限
+pub fn check(principal: Option<&Principal>, cmd: &Command) -> Result<(), String> {
+    let (name, permissions) = match principal {
+        Some(p) => (p.name.as_str(), p.permissions.as_slice()),
+        None => ("anonymous", &[][..]),
+    };
+    let permission = Permission::required(cmd);
+    let denied = |what: String| Err(format!("PermissionDenied: {} lacks {} on {}", name, permission.as_str(), what));
+    match scope(cmd) {
+        Scope::Connection => Ok(()),
+        Scope::AllCfs => {
+            let granted = permissions.iter().any(|p| p.cf_glob == "*" && p.grants(permission));
+            if granted { Ok(()) } else { denied("all column families".to_string()) }
+        }
+        Scope::Cfs(cfs) => match cfs.into_iter().find(|cf| !allows(permissions, cf, permission)) {
+            Some(cf) => denied(format!("cf {}", cf)),
+            None => Ok(()),
+        },
+    }
+}
+
+/// 命令作用的列族范围
+enum Scope<'a> {
+    /// 只影响连接本身或不涉及列族中的数据
+    Connection,
+    /// 当前数据库的所有列族，或整个存储
+    AllCfs,
+    Cfs(Vec<&'a str>),
+}
+
+fn scope(cmd: &Command) -> Scope<'_> {
+    match cmd {
+        Command::Idempotent { cmd, .. } => scope(cmd),
+        // 跨列族读取或作用于整个存储的命令
+        Command::ScanAll { .. }
+        | Command::HotKeys { .. }
+        | Command::Verify { cf: None }
+        | Command::ScanTrash { cf: None, .. }
+        | Command::Sample { cf: None, .. } => Scope::AllCfs,
+        // 频道不属于任何列族，发布需要所有列族的写权限
+        Command::Publish { .. } => Scope::AllCfs,
+        _ if cmd.requires_admin() && cmd.cfs().is_empty() => Scope::AllCfs,
+        _ => {
+            let cfs = cmd.cfs();
+            if cfs.is_empty() { Scope::Connection } else { Scope::Cfs(cfs) }
+        }
+    }
+}
+
+fn allows(permissions: &[CfPermission], cf: &str, permission: Permission) -> bool {
+    permissions.iter().any(|p| p.grants(permission) && glob_match(&p.cf_glob, cf))
+}
+
+/// name 是否匹配 pattern，`*` 匹配任意长度的字符，`?` 匹配一个字符
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // 最近一个 * 的位置，以及它当前匹配到的 name 位置
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // 让 * 多吞一个字符后重试
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,1537 @@
+//! 服务端的命令处理：会话状态、键编码和 RawKeyValueApi
+
+use crate::acl::{self, EffectivePermissions};
+use crate::audit;
+use crate::clients;
+use crate::deadline::{Deadline, DeadlineWriter};
+use crate::errorlog::ErrorCategory;
+use crate::group_commit::{GroupCommitStats, GroupCommitter};
+use crate::histogram::HistogramSet;
+use crate::hotkeys;
+use crate::idempotency::{Claim, IdempotencyCache, IdempotencyStats};
+use crate::oplog;
+use crate::pubsub::{self, PubSub};
+use crate::rotation::LogFileStats;
+use crate::runtime_config::{ConfigEntry, RuntimeConfig};
+use crate::migration;
+use crate::protocol::{
+    entry_wire_size, stable_hash, validate_cf_name, validate_db_name, validate_key, BatchMode, Bytes, CfCursor, CfEntry, CfInfo, Command, DbInfo, Modify,
+    Response, ServerInfo, ServerLimits, ValueFilter, Version, Warning, DB_SEPARATOR, DEFAULT_CF, DEFAULT_DB, FEATURES, GIT_HASH, OPT_IN_FEATURES,
+    PROTOCOL_VERSION, SERVER_VERSION,
+};
+use crate::server;
+use crate::storage;
+use crate::trace;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::ops::Bound;
+
+/// 值的大小达到响应大小上限的这个百分比时，写入附带 ValueNearLimit 警告
+pub const VALUE_NEAR_LIMIT_PERCENT: usize = 90;
+
+/// raw_scan_limited 的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanPage {
+    pub pairs: storage::KvPairs,
+    /// 提前结束时第一个未返回的键（包含），读完范围或凑够 limit 时为 None
+    pub next: Option<Vec<u8>>,
+    /// 因检查的条目数达到 ServerConfig::max_scan_examined 而提前结束
+    pub examined_limit_reached: bool,
+}
+
+/// 每个连接的会话状态
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// 当前选择的数据库
+    pub db: String,
+    /// 是否允许执行管理命令，见 Command::requires_admin
+    pub is_admin: bool,
+    /// 协商后的协议版本，客户端没有发送 Hello 时为 None
+    pub protocol_version: Option<u32>,
+    /// 协商后的特性
+    pub features: Vec<String>,
+    /// UseCf 设置的默认列族
+    pub default_cf: Option<String>,
+    /// 通过 Auth 认证的主体，按它的列族权限执行命令；is_admin 为 true 时不检查
+    pub principal: Option<Arc<acl::Principal>>,
+    /// 当前请求的上下文，handle_command 开始时重置
+    pub request: RequestContext,
+}
+
+/// 单个请求执行期间收集的信息
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    /// 协商了 envelope 时随响应返回的警告
+    pub warnings: Vec<Warning>,
+    /// 客户端放弃等待的时刻，扫描等长操作在检查点上检查
+    pub deadline: Deadline,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            db: DEFAULT_DB.to_string(),
+            is_admin: true,
+            protocol_version: None,
+            features: Vec::new(),
+            default_cf: None,
+            principal: None,
+            request: RequestContext::default(),
+        }
+    }
+}
+
+impl Session {
+    /// 是否协商了指定的特性
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    // 只有协商了 envelope 的连接收集警告，其他连接收不到它们
+    fn warn(&mut self, warning: Warning) {
+        if self.has_feature("envelope") {
+            self.request.warnings.push(warning);
+        }
+    }
+
+    /// 把命令中为空的列族名替换为会话的默认列族，没有用 UseCf 设置时替换为 DEFAULT_CF
+    pub fn apply_default_cf(&self, cmd: &mut Command) {
+        let default = self.default_cf.as_deref().unwrap_or(DEFAULT_CF);
+        for cf in cmd.cfs_mut() {
+            if cf.is_empty() {
+                *cf = default.to_string();
+            }
+        }
+    }
+}
+
+// 可以删除的数据库在存储中的键前缀
+fn drop_db_prefix(name: &str) -> Result<String, String> {
+    validate_db_name(name)?;
+    if name == DEFAULT_DB {
+        return Err("Cannot drop the default database".to_string());
+    }
+    Ok(format!("{}{}", name, DB_SEPARATOR))
+}
+
+// 比较令牌时不因首个不同字节而提前返回，避免泄露时序信息
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// 把会话中的列族名映射为存储中的列族名
+fn scoped_cf(db: &str, cf: &str) -> Result<String, String> {
+    validate_cf_name(cf)?;
+    if db == DEFAULT_DB {
+        Ok(cf.to_string())
+    } else {
+        Ok(format!("{}{}{}", db, DB_SEPARATOR, cf))
+    }
+}
+
+// 把存储中的列族名拆分为 (数据库, 列族)
+fn split_scoped_cf(scoped: &str) -> (&str, &str) {
+    match scoped.split_once(DB_SEPARATOR) {
+        Some((db, cf)) => (db, cf),
+        None => (DEFAULT_DB, scoped),
+    }
+}
+
+// 修改数据的命令；刷盘、整理等维护命令不受持久化降级影响，Flush 本身就是恢复的手段。
+// Publish 只把消息推给订阅者，不写存储
+fn writes_data(cmd: &Command) -> bool {
+    !cmd.is_read_only()
+        && !matches!(
+            cmd,
+            Command::Flush
+                | Command::Compact
+                | Command::ResetStats
+                | Command::SetConfig { .. }
+                | Command::KillClient { .. }
+                | Command::Shutdown { .. }
+                | Command::Publish { .. }
+        )
+}
+
+
+/// 存储中的编码键：列族名、KEY_TERMINATOR、原始键
+///
+/// UTF-8 中不会出现 0xFF，所以任意列族名和任意原始键都能无歧义地解码；
+/// 同一列族的键在编码后连续，并按原始键的字节序排列。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EncodedKey(Vec<u8>);
+
+/// 列族名之后的结束字节
+const KEY_TERMINATOR: u8 = 0xFF;
+
+/// 有响应大小上限时每次从存储读取的条目数，避免为很大的 limit 一次读出整个范围
+const SCAN_CHUNK: usize = 256;
+
+/// 有截止时间的扫描每检查这么多条目就检查一次是否已过期
+const DEADLINE_CHECK_EXAMINED: usize = 4096;
+
+impl EncodedKey {
+    pub fn encode(cf: &str, key: &[u8]) -> Self {
+        let mut encoded = Vec::with_capacity(cf.len() + 1 + key.len());
+        encoded.extend_from_slice(cf.as_bytes());
+        encoded.push(KEY_TERMINATOR);
+        encoded.extend_from_slice(key);
+        EncodedKey(encoded)
+    }
+
+    /// 列族键空间的起点，即 encode(cf, b"")
+    pub fn cf_prefix(cf: &str) -> Self {
+        Self::encode(cf, b"")
+    }
+
+    /// 列族键空间的排他上界：列族中的键都小于它，其他列族的键都不在 [cf_prefix, cf_end) 中；
+    /// 空列族名的键空间没有上界
+    pub fn cf_end(cf: &str) -> Option<Self> {
+        // 列族名的字节都小于 0xFF，末字节加一即可越过 cf 加结束字节开头的所有键
+        let mut end = cf.as_bytes().to_vec();
+        let last = end.pop()?;
+        end.push(last + 1);
+        Some(EncodedKey(end))
+    }
+
+    /// 解码为 (列族, 原始键)
+    pub fn decode(&self) -> Option<(&str, &[u8])> {
+        Self::decode_bytes(&self.0)
+    }
+
+    /// 编码键的稳定哈希，见 protocol::stable_hash
+    pub fn stable_hash(&self) -> u64 {
+        stable_hash(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// 解码存储内部保存的编码字节
+    pub(crate) fn decode_bytes(encoded: &[u8]) -> Option<(&str, &[u8])> {
+        let end = encoded.iter().position(|&b| b == KEY_TERMINATOR)?;
+        let cf = std::str::from_utf8(&encoded[..end]).ok()?;
+        Some((cf, &encoded[end + 1..]))
+    }
+
+    /// 编码字节属于 cf 时返回其中的原始键
+    pub(crate) fn key_in_cf<'a>(cf: &str, encoded: &'a [u8]) -> Option<&'a [u8]> {
+        encoded.strip_prefix(cf.as_bytes())?.strip_prefix(&[KEY_TERMINATOR])
+    }
+}
+
+// 原始键值API
+pub struct RawKeyValueApi {
+    storage: Arc<storage::StandaloneStorage>,
+    config: Arc<server::ServerConfig>,
+    // 未开启热点统计时为 None，请求路径上没有额外开销
+    hot_keys: Option<hotkeys::HotKeyTracker>,
+    // 服务器登记的连接，单独使用 API 时为空
+    clients: Arc<clients::ClientRegistry>,
+    // 开启组提交时的提交线程句柄
+    committer: Option<GroupCommitter>,
+    // 按命令类型统计的处理耗时（微秒）
+    latency: HistogramSet,
+    // 服务器配置了审计日志时用于 AuditVerify
+    audit: Option<Arc<audit::AuditLog>>,
+    // 最近执行过的幂等写请求的响应
+    idempotency: IdempotencyCache,
+    // 发布/订阅频道，订阅由服务器在连接上建立
+    pubsub: Arc<PubSub>,
+    // 服务器开始监听的地址，见 ServerInfo
+    listen_addrs: Mutex<Vec<String>>,
+    started_at_ms: u64,
+    // 可以用 SetConfig 修改的配置项，请求路径上读取它而不是 config
+    runtime: Arc<RuntimeConfig>,
+}
+
+impl RawKeyValueApi {
+    pub fn new(storage: Arc<storage::StandaloneStorage>) -> Self {
+        Self::with_config(storage, Arc::new(server::ServerConfig::default()))
+    }
+
+    pub fn with_config(storage: Arc<storage::StandaloneStorage>, config: Arc<server::ServerConfig>) -> Self {
+        let hot_keys = config.hot_key_sample_every.map(hotkeys::HotKeyTracker::new);
+        let committer = config.group_commit.clone().map(|c| GroupCommitter::start(Arc::clone(&storage), c));
+        let idempotency = IdempotencyCache::new(config.idempotency.clone());
+        let pubsub = Arc::new(PubSub::new(config.pubsub_queue_capacity.unwrap_or(pubsub::DEFAULT_QUEUE_CAPACITY)));
+        let started_at_ms = storage.clock().now_ms();
+        let runtime = Arc::new(RuntimeConfig::new(&config, Arc::clone(&storage)));
+        RawKeyValueApi {
+            storage,
+            config,
+            hot_keys,
+            clients: Arc::default(),
+            committer,
+            latency: HistogramSet::default(),
+            audit: None,
+            idempotency,
+            pubsub,
+            listen_addrs: Mutex::default(),
+            started_at_ms,
+            runtime,
+        }
+    }
+
+    /// 由 AuditVerify 校验的审计日志，记录由服务器的中间件链完成
+    pub fn with_audit_log(mut self, log: Arc<audit::AuditLog>) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// 发布/订阅频道
+    pub fn pubsub(&self) -> &Arc<PubSub> {
+        &self.pubsub
+    }
+
+    /// 组提交的累计统计，未开启组提交时为 None
+    pub fn group_commit_stats(&self) -> Option<GroupCommitStats> {
+        self.committer.as_ref().map(GroupCommitter::stats)
+    }
+
+    /// 写入一批修改，开启组提交时与其他连接的写请求合并提交
+    fn write(&self, batch: Vec<Modify>) -> Result<(), String> {
+        batch.iter().try_for_each(|op| validate_key(&op.key))?;
+        match &self.committer {
+            Some(committer) => committer.write(batch),
+            None => self.storage.write(batch),
+        }
+    }
+
+    pub fn clients(&self) -> &Arc<clients::ClientRegistry> {
+        &self.clients
+    }
+
+    /// 为新连接创建会话；未配置管理令牌和主体时所有连接都具有管理权限
+    pub fn new_session(&self) -> Session {
+        Session {
+            is_admin: self.config.admin_token.is_none() && self.config.principals.is_empty(),
+            ..Session::default()
+        }
+    }
+
+    pub fn raw_get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        validate_key(key)?;
+        let reader = self.storage.reader()?;
+        reader.get_cf(cf, key)
+    }
+
+    pub fn raw_put(&self, cf: String, key: Vec<u8>, value: Vec<u8>) -> Result<(), String> {
+        let modify = Modify::new_put(cf, key, value);
+        self.write(vec![modify])
+    }
+
+    /// 开启回收站时删除的值移到回收站
+    pub fn raw_delete(&self, cf: String, key: Vec<u8>) -> Result<(), String> {
+        validate_key(&key)?;
+        if self.config.trash_retention.is_some() {
+            return self.storage.soft_delete(&cf, &key);
+        }
+        let modify = Modify::new_delete(cf, key);
+        self.write(vec![modify])
+    }
+
+    pub fn raw_restore_key(&self, cf: &str, key: &[u8], overwrite: bool) -> Result<(), String> {
+        validate_key(key)?;
+        if self.config.trash_retention.is_none() {
+            return Err("Trash is not enabled".to_string());
+        }
+        self.storage.restore_key(cf, key, overwrite)
+    }
+
+    /// 数据库 db 回收站中的条目，cf 为存储中的列族名；返回的列族名不带数据库前缀
+    pub fn raw_scan_trash(&self, db: &str, cf: Option<&str>, limit: usize) -> Result<Vec<storage::TrashEntry>, String> {
+        let mut entries = Vec::new();
+        for mut entry in self.storage.trash_entries(cf)? {
+            let (entry_db, entry_cf) = split_scoped_cf(&entry.cf);
+            if entry_db != db {
+                continue;
+            }
+            if entries.len() == limit {
+                break;
+            }
+            entry.cf = entry_cf.to_string();
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// 从数据库 db 中抽取至多 count 个键；scoped_cf 为已加上数据库前缀的列族，None 时从 db 的所有列族中抽取
+    pub fn raw_sample(&self, db: &str, scoped_cf: Option<String>, count: usize) -> Result<Vec<storage::KeySample>, String> {
+        let cfs = match scoped_cf {
+            Some(cf) => vec![cf],
+            None => self
+                .storage
+                .cf_created()?
+                .into_iter()
+                .map(|(scoped, _)| scoped)
+                .filter(|scoped| split_scoped_cf(scoped).0 == db)
+                .collect(),
+        };
+        let mut samples = self.storage.sample_keys(&cfs, count)?;
+        for sample in &mut samples {
+            sample.cf = split_scoped_cf(&sample.cf).1.to_string();
+        }
+        Ok(samples)
+    }
+
+    pub fn raw_purge_trash(&self, all: bool) -> Result<usize, String> {
+        self.storage.purge_trash(self.purge_retention(all)?)
+    }
+
+    /// raw_purge_trash 的试运行
+    pub fn raw_purge_trash_report(&self, all: bool) -> Result<storage::DeletionReport, String> {
+        self.storage.purge_trash_report(self.purge_retention(all)?)
+    }
+
+    // all 时清空回收站（None），否则按配置的保留期清理
+    fn purge_retention(&self, all: bool) -> Result<Option<Duration>, String> {
+        match (all, self.config.trash_retention) {
+            (true, _) => Ok(None),
+            (false, Some(retention)) => Ok(Some(retention)),
+            (false, None) => Err("Trash is not enabled".to_string()),
+        }
+    }
+
+    /// 按 (列族, 键) 顺序扫描数据库 db 的所有列族，返回结果和下一页的起点
+    pub fn raw_scan_all(
+        &self,
+        db: &str,
+        start: Option<(&str, &[u8])>,
+        limit: usize,
+    ) -> Result<(Vec<CfEntry>, Option<CfCursor>), String> {
+        self.raw_scan_all_bounded(db, start, limit, None).map(|(entries, next, _)| (entries, next))
+    }
+
+    /// 同 raw_scan_all，但条目累计超过 max_bytes 时提前结束这一页，第三项表示是否因此截断
+    pub fn raw_scan_all_bounded(
+        &self,
+        db: &str,
+        start: Option<(&str, &[u8])>,
+        limit: usize,
+        max_bytes: Option<usize>,
+    ) -> Result<(Vec<CfEntry>, Option<CfCursor>, bool), String> {
+        self.scan_all_page(db, start, limit, max_bytes, Deadline::NONE)
+    }
+
+    // raw_scan_all_bounded 的实现，每读 DEADLINE_CHECK_EXAMINED 个条目检查一次截止时间
+    fn scan_all_page(
+        &self,
+        db: &str,
+        start: Option<(&str, &[u8])>,
+        limit: usize,
+        max_bytes: Option<usize>,
+        deadline: Deadline,
+    ) -> Result<(Vec<CfEntry>, Option<CfCursor>, bool), String> {
+        let reader = self.storage.reader()?;
+        let mut cfs: Vec<(String, String)> = reader
+            .column_families()?
+            .into_iter()
+            .filter_map(|scoped| {
+                let (cf_db, cf) = split_scoped_cf(&scoped);
+                (cf_db == db).then(|| (cf.to_string(), scoped.clone()))
+            })
+            .collect();
+        // 编码后的键序与列族名顺序不一定相同（如 "a" 与 "aB"），按列族名重新排序
+        cfs.sort();
+
+        let mut entries = Vec::new();
+        let (mut used, mut read) = (0, 0usize);
+        for (cf, scoped) in cfs {
+            let from: &[u8] = match start {
+                Some((start_cf, _)) if cf.as_str() < start_cf => continue,
+                Some((start_cf, start_key)) if cf == start_cf => start_key,
+                _ => b"",
+            };
+            for (key, value) in reader.iter_cf(&scoped, from, None)? {
+                read += 1;
+                if read.is_multiple_of(DEADLINE_CHECK_EXAMINED) {
+                    deadline.check("ScanAll")?;
+                }
+                if entries.len() == limit {
+                    return Ok((entries, Some((cf, key)), false));
+                }
+                // 列族名不需要转义，序列化后多出引号和逗号
+                used += entry_wire_size(&key, &value) + cf.len() + 3;
+                if max_bytes.is_some_and(|max| used > max) && !entries.is_empty() {
+                    return Ok((entries, Some((cf, key)), true));
+                }
+                entries.push((cf.clone(), key, value));
+            }
+        }
+        Ok((entries, None, false))
+    }
+
+    pub fn raw_get_del(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        validate_key(key)?;
+        self.storage.get_del(cf, key)
+    }
+
+    pub fn raw_expire(&self, cf: &str, key: &[u8], ttl_ms: u64) -> Result<(), String> {
+        validate_key(key)?;
+        self.storage.expire(cf, key, Duration::from_millis(ttl_ms))
+    }
+
+    /// 剩余生存时间（毫秒）
+    pub fn raw_ttl(&self, cf: &str, key: &[u8]) -> Result<Option<u64>, String> {
+        validate_key(key)?;
+        Ok(self.storage.ttl(cf, key)?.map(|ttl| ttl.as_millis() as u64))
+    }
+
+    pub fn raw_get_set(&self, cf: &str, key: &[u8], value: Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+        validate_key(key)?;
+        self.storage.get_set(cf, key, value)
+    }
+
+    pub fn raw_rename(&self, cf: &str, old_key: &[u8], new_key: &[u8], overwrite: bool) -> Result<(), String> {
+        validate_key(old_key)?;
+        validate_key(new_key)?;
+        self.storage.rename(cf, old_key, new_key, overwrite)
+    }
+
+    pub fn raw_copy(&self, cf: &str, src_key: &[u8], dst_key: &[u8], overwrite: bool) -> Result<(), String> {
+        validate_key(src_key)?;
+        validate_key(dst_key)?;
+        self.storage.copy(cf, src_key, dst_key, overwrite)
+    }
+
+    pub fn raw_find_by_value(&self, cf: &str, value: &[u8], limit: usize) -> Result<Vec<Vec<u8>>, String> {
+        let reader = self.storage.reader()?;
+        reader.find_by_value_cf(cf, value, limit)
+    }
+
+    pub fn raw_create_cf(&self, cf: &str, options: Option<storage::CfOptions>) -> Result<(), String> {
+        self.storage.create_cf(cf, options)
+    }
+
+    pub fn raw_set_cf_quota(&self, cf: &str, max_keys: Option<usize>, max_bytes: Option<usize>) -> Result<(), String> {
+        self.storage.set_cf_quota(cf, max_keys, max_bytes)
+    }
+
+    pub fn raw_lock_acquire(&self, name: &str, ttl_ms: u64) -> Result<Option<u64>, String> {
+        self.storage.lock_acquire(name, ttl_ms)
+    }
+
+    pub fn raw_lock_release(&self, name: &str, token: u64) -> Result<(), String> {
+        self.storage.lock_release(name, token)
+    }
+
+    pub fn raw_lock_renew(&self, name: &str, token: u64, ttl_ms: u64) -> Result<(), String> {
+        self.storage.lock_renew(name, token, ttl_ms)
+    }
+
+    pub fn raw_scan(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<storage::KvPairs, String> {
+        self.raw_scan_filtered(cf, start_key, end_key, limit, None)
+    }
+
+    /// 带值过滤的范围扫描，limit 按匹配的条目计数
+    pub fn raw_scan_filtered(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: Option<&[u8]>,
+        limit: usize,
+        filter: Option<&ValueFilter>,
+    ) -> Result<storage::KvPairs, String> {
+        let reader = self.storage.reader()?;
+        reader.scan_cf(cf, start_key, end_key, limit, filter)
+    }
+
+    /// 按任意边界扫描，limit 按匹配的条目计数
+    pub fn raw_scan_range(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&ValueFilter>,
+    ) -> Result<storage::KvPairs, String> {
+        let reader = self.storage.reader()?;
+        reader.scan_range_cf(cf, start, end, limit, filter)
+    }
+
+    /// 同 raw_scan_range，但条目累计超过 max_bytes 时提前结束，并返回第一个未返回的键
+    /// 第一条总是返回，否则单个超过上限的条目会让分页无法前进；max_bytes 为 None 时不限制
+    pub fn raw_scan_bounded(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&ValueFilter>,
+        max_bytes: Option<usize>,
+    ) -> Result<(storage::KvPairs, Option<Vec<u8>>), String> {
+        let page = self.raw_scan_limited(cf, start, end, limit, filter, max_bytes, None)?;
+        Ok((page.pairs, page.next))
+    }
+
+    /// 同 raw_scan_bounded，另外最多检查 max_examined 个条目；达到上限时提前结束，
+    /// next 为下一个未检查的键，examined_limit_reached 为 true
+    #[allow(clippy::too_many_arguments)]
+    pub fn raw_scan_limited(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&ValueFilter>,
+        max_bytes: Option<usize>,
+        max_examined: Option<usize>,
+    ) -> Result<ScanPage, String> {
+        self.scan_page(cf, start, end, limit, filter, max_bytes, max_examined, Deadline::NONE)
+    }
+
+    // raw_scan_limited 的实现；有截止时间时分段扫描，每段最多检查 DEADLINE_CHECK_EXAMINED 个条目，
+    // 段与段之间检查是否过期，过期时放弃这次扫描
+    #[allow(clippy::too_many_arguments)]
+    fn scan_page(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&ValueFilter>,
+        max_bytes: Option<usize>,
+        max_examined: Option<usize>,
+        deadline: Deadline,
+    ) -> Result<ScanPage, String> {
+        let mut page = ScanPage::default();
+        if max_bytes.is_none() && max_examined.is_none() && !deadline.is_set() {
+            page.pairs = self.raw_scan_range(cf, start, end, limit, filter)?;
+            return Ok(page);
+        }
+        let reader = self.storage.reader()?;
+        let (mut used, mut examined) = (0, 0);
+        let max_examined = max_examined.unwrap_or(usize::MAX);
+        // 上一段因检查数达到本段的上限而停下时的续扫位置（包含）
+        let mut resume: Option<Vec<u8>> = None;
+        while page.pairs.len() < limit {
+            deadline.check("Scan")?;
+            let from = match (&resume, page.pairs.last()) {
+                (Some(key), _) => Bound::Included(key.as_slice()),
+                (None, Some((key, _))) => Bound::Excluded(key.as_slice()),
+                (None, None) => start,
+            };
+            let wanted = match max_bytes.is_some() || deadline.is_set() {
+                true => (limit - page.pairs.len()).min(SCAN_CHUNK),
+                false => limit - page.pairs.len(),
+            };
+            let budget = match deadline.is_set() {
+                true => (max_examined - examined).min(DEADLINE_CHECK_EXAMINED),
+                false => max_examined - examined,
+            };
+            let chunk = reader.scan_examined_cf(cf, from, end, wanted, filter, budget)?;
+            examined += chunk.examined;
+            let exhausted = chunk.pairs.len() < wanted;
+            for (key, value) in chunk.pairs {
+                used += entry_wire_size(&key, &value);
+                if max_bytes.is_some_and(|max| used > max) && !page.pairs.is_empty() {
+                    page.next = Some(key);
+                    return Ok(page);
+                }
+                page.pairs.push((key, value));
+            }
+            match chunk.next {
+                Some(next) if examined >= max_examined => {
+                    page.next = Some(next);
+                    page.examined_limit_reached = true;
+                    break;
+                }
+                Some(next) => resume = Some(next),
+                None if exhausted => break,
+                None => resume = None,
+            }
+        }
+        Ok(page)
+    }
+
+    pub fn raw_get_version(&self, cf: &str, key: &[u8], version: u64) -> Result<Option<Vec<u8>>, String> {
+        validate_key(key)?;
+        let reader = self.storage.reader()?;
+        reader.get_version_cf(cf, key, version)
+    }
+
+    pub fn raw_history(&self, cf: &str, key: &[u8], limit: usize) -> Result<Vec<Version>, String> {
+        validate_key(key)?;
+        let reader = self.storage.reader()?;
+        reader.history_cf(cf, key, limit)
+    }
+
+    /// 列出所有包含数据的数据库（默认数据库总会列出）
+    pub fn list_dbs(&self) -> Result<Vec<DbInfo>, String> {
+        let mut dbs: Vec<DbInfo> = vec![DbInfo {
+            name: DEFAULT_DB.to_string(),
+            total_keys: 0,
+            column_families: 0,
+        }];
+
+        for (scoped, count) in self.storage.cf_stats()? {
+            let (db, _) = split_scoped_cf(&scoped);
+            let info = match dbs.iter_mut().find(|d| d.name == db) {
+                Some(info) => info,
+                None => {
+                    dbs.push(DbInfo { name: db.to_string(), total_keys: 0, column_families: 0 });
+                    dbs.last_mut().unwrap()
+                }
+            };
+            info.total_keys += count;
+            info.column_families += 1;
+        }
+
+        dbs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(dbs)
+    }
+
+    /// 删除数据库中的所有数据，默认数据库不能删除
+    pub fn drop_db(&self, name: &str) -> Result<usize, String> {
+        let prefix = drop_db_prefix(name)?;
+        if self.config.trash_retention.is_some() {
+            return self.storage.trash_prefix(prefix.as_bytes());
+        }
+        self.storage.delete_prefix(prefix.as_bytes())
+    }
+
+    /// drop_db 的试运行；开启回收站时这些条目会被移到回收站
+    /// 报告中的列族名不带数据库前缀
+    pub fn drop_db_report(&self, name: &str) -> Result<storage::DeletionReport, String> {
+        let prefix = drop_db_prefix(name)?;
+        let mut report = self.storage.delete_prefix_report(prefix.as_bytes())?;
+        for (cf, _) in report.first.iter_mut().chain(report.last.iter_mut()) {
+            cf.drain(..prefix.len());
+        }
+        Ok(report)
+    }
+
+    /// 整个存储的备份文件按行拆开，见 StandaloneStorage::backup_to
+    pub fn backup_lines(&self) -> Result<Vec<String>, String> {
+        self.backup_lines_within(Deadline::NONE)
+    }
+
+    // 每写出一行检查一次截止时间
+    fn backup_lines_within(&self, deadline: Deadline) -> Result<Vec<String>, String> {
+        let mut archive = Vec::new();
+        self.storage
+            .backup_to(DeadlineWriter::new(&mut archive, deadline, "Backup"))
+            .map_err(|e| deadline.check("Backup").err().unwrap_or(e))?;
+        let archive = String::from_utf8(archive).map_err(|e| format!("Failed to encode archive: {}", e))?;
+        Ok(archive.lines().map(str::to_string).collect())
+    }
+
+    /// 用 backup_lines 返回的各行替换整个存储，返回恢复的键数
+    pub fn restore_lines(&self, lines: &[String], force: bool) -> Result<usize, String> {
+        let mut archive = lines.join("\n");
+        archive.push('\n');
+        self.storage.restore_from(archive.as_bytes(), force)
+    }
+
+    /// 在服务器上写出备份文件；先写到临时文件再重命名，失败时不留下不完整的备份
+    pub fn backup_to_file(&self, path: &str) -> Result<usize, String> {
+        self.backup_to_file_within(path, Deadline::NONE)
+    }
+
+    // 每写出一行检查一次截止时间，过期时同样删除临时文件
+    fn backup_to_file_within(&self, path: &str, deadline: Deadline) -> Result<usize, String> {
+        let tmp_path = format!("{}.tmp", path);
+        let file = std::fs::File::create(&tmp_path).map_err(|e| format!("Failed to create {}: {}", tmp_path, e))?;
+        let writer = DeadlineWriter::new(std::io::BufWriter::new(file), deadline, "BackupToFile");
+        let result = self.storage.backup_to(writer)
+            .map_err(|e| deadline.check("BackupToFile").err().unwrap_or(e))
+            .and_then(|count| std::fs::rename(&tmp_path, path).map(|_| count).map_err(|e| format!("Failed to rename file: {}", e)));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        result
+    }
+
+    /// 用服务器上的备份文件替换整个存储，返回恢复的键数
+    pub fn restore_from_file(&self, path: &str, force: bool) -> Result<usize, String> {
+        let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        self.storage.restore_from(file, force)
+    }
+
+    // Info 中的追加文件统计，纯内存存储没有 wal
+    fn log_stats(&self) -> Result<Vec<LogFileStats>, String> {
+        let mut logs = Vec::new();
+        if self.storage.path().is_some() {
+            logs.push(self.storage.wal_stats()?);
+        }
+        if let Some(audit) = &self.audit {
+            logs.push(audit.stats()?);
+        }
+        if let Some(oplog) = self.storage.oplog() {
+            logs.push(oplog.stats()?);
+        }
+        Ok(logs)
+    }
+
+    // 当前数据库的统计信息以及所有数据库的概况；with_cfs 为 false 时不列出列族
+    fn info(&self, session: &Session, with_cfs: bool) -> Result<Response, String> {
+        let mut total_keys = 0;
+        let mut column_families = Vec::new();
+        let usage: HashMap<String, storage::CfUsage> = self.storage.cf_usage()?.into_iter().collect();
+        for (scoped, cf_usage) in &usage {
+            let (db, cf) = split_scoped_cf(scoped);
+            if db == session.db {
+                total_keys += cf_usage.keys;
+                if with_cfs {
+                    column_families.push(cf.to_string());
+                }
+            }
+        }
+        // 默认列族即使还没有数据也列出
+        if with_cfs && !column_families.iter().any(|cf| cf == DEFAULT_CF) {
+            column_families.push(DEFAULT_CF.to_string());
+        }
+        column_families.sort();
+
+        let mut cf_count = 0;
+        let mut cf_info = Vec::new();
+        for (scoped, created_at_ms) in self.storage.cf_created()? {
+            if split_scoped_cf(&scoped).0 == session.db {
+                cf_count += 1;
+                if with_cfs {
+                    cf_info.push(self.cf_info(&scoped, created_at_ms, &usage)?);
+                }
+            }
+        }
+        // 还没有创建的默认列族也列出，但不计入 cf_count
+        if with_cfs && !cf_info.iter().any(|cf| cf.name == DEFAULT_CF) {
+            cf_info.push(self.cf_info(&scoped_cf(&session.db, DEFAULT_CF)?, 0, &usage)?);
+            cf_info.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        Ok(Response::Info {
+            total_keys,
+            column_families,
+            cf_info,
+            databases: self.list_dbs()?,
+            durability: self.storage.durability(),
+            memory_bytes: self.storage.memory_usage()?,
+            evicted_keys: self.storage.evicted_keys()?,
+            compression: Box::new(self.storage.compression_stats()?),
+            flush: Box::new(self.storage.flush_info()?),
+            latency: self.latency.summaries().into_iter().collect(),
+            cf_count,
+            errors_total: self.storage.error_log().total(),
+            maintenance: self.storage.maintenance_status().map(Box::new),
+            compaction: Box::new(self.storage.compaction_info()?),
+            idempotency: Box::new(self.idempotency_stats()),
+            spill: Box::new(self.storage.spill_stats()?),
+            logs: self.log_stats()?,
+            locks: Box::new(self.storage.lock_stats()),
+            connections_reaped: self.clients.reaped(),
+            connections_rejected: self.clients.rejected(),
+            persistence: Box::new(self.storage.persistence_status()),
+            permissions: Some(Box::new(self.effective_permissions(session))),
+            recovery: self.storage.take_recovery_report()?.map(Box::new),
+            load: Some(self.storage.load_status()?).filter(|s| !s.loaded).map(Box::new),
+        })
+    }
+
+    // 当前数据库中名称在 start_after 之后的至多 limit 个列族，以及下一页的 start_after；
+    // limit 为 0 时一页永远是空的，按游标遍历的调用方会误以为没有列族，因此直接拒绝
+    fn list_cfs(
+        &self,
+        session: &Session,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<CfInfo>, Option<String>), String> {
+        if limit == 0 {
+            return Err("InvalidLimit: ListCfs limit must be at least 1".to_string());
+        }
+        let usage: HashMap<String, storage::CfUsage> = self.storage.cf_usage()?.into_iter().collect();
+        let mut cfs = Vec::new();
+        // cf_created 按带数据库前缀的名称排序，同一数据库内即按列族名排序
+        for (scoped, created_at_ms) in self.storage.cf_created()? {
+            let (db, cf) = split_scoped_cf(&scoped);
+            if db != session.db || start_after.is_some_and(|after| cf <= after) {
+                continue;
+            }
+            if cfs.len() == limit {
+                let next = cfs.last().map(|c: &CfInfo| c.name.clone());
+                return Ok((cfs, next));
+            }
+            cfs.push(self.cf_info(&scoped, created_at_ms, &usage)?);
+        }
+        Ok((cfs, None))
+    }
+
+    fn cf_info(&self, scoped: &str, created_at_ms: u64, usage: &HashMap<String, storage::CfUsage>) -> Result<CfInfo, String> {
+        let cf_usage = usage.get(scoped).copied().unwrap_or_default();
+        let options = self.storage.cf_options(scoped)?;
+        Ok(CfInfo {
+            name: split_scoped_cf(scoped).1.to_string(),
+            keys: cf_usage.keys,
+            created_at_ms,
+            bytes: cf_usage.bytes,
+            logical_bytes: cf_usage.logical_bytes,
+            max_keys: options.max_keys,
+            max_bytes: options.max_bytes,
+        })
+    }
+
+    /// 执行命令并按命令类型记录耗时
+    pub fn handle_command(&self, session: &mut Session, cmd: Command) -> Response {
+        self.handle_command_with_deadline(session, cmd, Deadline::NONE)
+    }
+
+    /// 同 handle_command，已过 deadline 时不执行命令，扫描等长操作在检查点上过期时放弃；
+    /// 两种情况都返回 DeadlineExceeded 错误
+    pub fn handle_command_with_deadline(&self, session: &mut Session, cmd: Command, deadline: Deadline) -> Response {
+        let started = Instant::now();
+        let kind = cmd.kind();
+        let warn_threshold = self.runtime.get().lock_wait_warn_threshold;
+        let measure_lock_wait = warn_threshold.is_some() || trace::ENABLED;
+        if measure_lock_wait {
+            // 清掉之前在这个线程上累计的等待
+            storage::take_thread_lock_wait();
+        }
+        session.request = RequestContext { deadline, ..RequestContext::default() };
+        // 记下写命令涉及的尚不存在的列族，执行成功后它们已被隐式创建
+        let missing_cfs = match session.has_feature("envelope") && !cmd.is_read_only() {
+            true => self.missing_cfs(session, &cmd),
+            false => Vec::new(),
+        };
+        let degraded = writes_data(&cmd).then(|| self.storage.persistence_status()).filter(|status| status.degraded);
+        // oplog 在提交写入的线程上记录主体，命名与审计日志一致
+        let _principal = self.storage.oplog().is_some().then(|| {
+            let principal = match &session.principal {
+                _ if session.is_admin => Some("admin".to_string()),
+                principal => principal.as_ref().map(|p| p.name.clone()),
+            };
+            oplog::enter_principal(principal)
+        });
+        let response = match (&degraded, deadline.check(kind)) {
+            (_, Err(e)) => Response::Error(e),
+            (Some(status), _) if self.runtime.get().fail_writes_when_degraded => Response::Error(format!(
+                "PersistenceDegraded: writes are rejected until a flush succeeds: {}",
+                status.last_error.as_deref().unwrap_or("unknown error")
+            )),
+            (_, Ok(())) => self.execute(session, cmd),
+        };
+        if !matches!(response, Response::Error(_) | Response::BatchError(_)) {
+            for cf in missing_cfs {
+                if self.storage.cf_exists(&cf).unwrap_or(false) {
+                    session.warn(Warning::CfAutoCreated { cf: split_scoped_cf(&cf).1.to_string() });
+                }
+            }
+            if let Some(status) = degraded {
+                session.warn(Warning::PersistenceDegraded { error: status.last_error.unwrap_or_default() });
+            }
+        }
+        let elapsed = started.elapsed();
+        self.latency.record_duration(kind, elapsed);
+        if measure_lock_wait {
+            let waited = storage::take_thread_lock_wait();
+            trace::record_storage(waited, elapsed);
+            if let Some(threshold) = warn_threshold
+                && waited > threshold
+            {
+                eprintln!("Lock wait: {} waited {}us for the storage lock", kind, waited.as_micros());
+            }
+        }
+        if let Response::Error(e) = &response
+            && (e.starts_with("OutOfMemoryBudget") || e.starts_with("QuotaExceeded") || e.starts_with("PersistenceDegraded"))
+        {
+            self.storage.error_log().record(ErrorCategory::Rejected, format!("{}: {}", kind, e));
+        }
+        response
+    }
+
+    // 值的编码大小达到 max_response_bytes 的 VALUE_NEAR_LIMIT_PERCENT% 时返回警告
+    fn value_near_limit(&self, cf: &str, key: &[u8], value: &[u8]) -> Option<Warning> {
+        let limit = self.runtime.get().max_response_bytes?;
+        let size = entry_wire_size(key, value);
+        (size * 100 >= limit * VALUE_NEAR_LIMIT_PERCENT)
+            .then(|| Warning::ValueNearLimit { cf: split_scoped_cf(cf).1.to_string(), size, limit })
+    }
+
+    // 命令中尚不存在的列族（存储中的名称）；CreateCf 显式创建列族，不算在内
+    fn missing_cfs(&self, session: &Session, cmd: &Command) -> Vec<String> {
+        if matches!(cmd, Command::CreateCf { .. }) {
+            return Vec::new();
+        }
+        let default = session.default_cf.as_deref().unwrap_or(DEFAULT_CF);
+        let mut missing: Vec<String> = cmd
+            .cfs()
+            .into_iter()
+            .filter_map(|cf| scoped_cf(&session.db, if cf.is_empty() { default } else { cf }).ok())
+            .filter(|cf| !self.storage.cf_exists(cf).unwrap_or(true))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    // 把命令中的列族解析为当前数据库下的名称
+    // 管理令牌之外，配置了主体时按列族权限检查；cmd 中的列族已经替换了默认列族
+    fn authorize(&self, session: &Session, cmd: &Command) -> Result<(), String> {
+        if session.is_admin {
+            return Ok(());
+        }
+        match &session.principal {
+            Some(principal) => acl::check(Some(principal), cmd),
+            None if cmd.requires_admin() => Err("admin required".to_string()),
+            None if self.config.principals.is_empty() => Ok(()),
+            None => acl::check(None, cmd),
+        }
+    }
+
+    // Info 中报告的当前连接的权限
+    fn effective_permissions(&self, session: &Session) -> EffectivePermissions {
+        match &session.principal {
+            _ if session.is_admin => EffectivePermissions { principal: "admin".to_string(), unrestricted: true, permissions: Vec::new() },
+            Some(principal) => EffectivePermissions {
+                principal: principal.name.clone(),
+                unrestricted: false,
+                permissions: principal.permissions.clone(),
+            },
+            None => EffectivePermissions {
+                principal: "anonymous".to_string(),
+                // 只配置了管理令牌时，未认证的连接可以访问所有列族
+                unrestricted: self.config.principals.is_empty(),
+                permissions: Vec::new(),
+            },
+        }
+    }
+
+    fn resolve_cfs(&self, session: &Session, cmd: &mut Command) -> Result<(), String> {
+        // 严格模式下写命令只能作用于已创建的列族
+        let must_exist = self.runtime.get().strict_cf_mode && !cmd.is_read_only() && !matches!(cmd, Command::CreateCf { .. });
+        for cf in cmd.cfs_mut() {
+            self.resolve_cf(session, cf, must_exist)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_cf(&self, session: &Session, cf: &mut String, must_exist: bool) -> Result<(), String> {
+        *cf = scoped_cf(&session.db, cf)?;
+        if must_exist && !self.storage.cf_exists(cf)? {
+            return Err(format!("UnknownCf: {}", split_scoped_cf(cf).1));
+        }
+        Ok(())
+    }
+
+    fn execute_batch(&self, session: &Session, mut ops: Vec<Modify>, mode: BatchMode) -> Response {
+        let invalid: Vec<Option<String>> = ops
+            .iter_mut()
+            .map(|op| {
+                validate_key(&op.key)
+                    .and_then(|_| self.resolve_cf(session, &mut op.cf, self.runtime.get().strict_cf_mode))
+                    .err()
+            })
+            .collect();
+        match mode {
+            BatchMode::Atomic => {
+                let errors: Vec<(usize, String)> =
+                    invalid.into_iter().enumerate().filter_map(|(i, e)| e.map(|e| (i, e))).collect();
+                if !errors.is_empty() {
+                    return Response::BatchError(errors);
+                }
+                match self.write(ops) {
+                    Ok(_) => Response::Ok,
+                    Err(e) => Response::Error(e),
+                }
+            }
+            BatchMode::BestEffort => {
+                let mut results: Vec<Result<(), String>> = invalid.into_iter().map(|e| e.map_or(Ok(()), Err)).collect();
+                let valid: Vec<(usize, Modify)> = ops.into_iter().enumerate().filter(|(i, _)| results[*i].is_ok()).collect();
+                // 先整体提交；因配额或内存预算被拒绝时逐条提交，找出被拒绝的操作
+                if self.write(valid.iter().map(|(_, op)| op.clone()).collect()).is_err() {
+                    for (i, op) in valid {
+                        results[i] = self.write(vec![op]);
+                    }
+                }
+                Response::BatchResults(results)
+            }
+        }
+    }
+
+    /// 创建时使用的服务器配置；其中的动态配置项可能已被 SetConfig 修改，当前值见 runtime_config
+    pub fn config(&self) -> &server::ServerConfig {
+        &self.config
+    }
+
+    /// 运行时可以修改的配置项
+    pub fn runtime_config(&self) -> &Arc<RuntimeConfig> {
+        &self.runtime
+    }
+
+    /// 记录服务器开始监听的地址
+    pub(crate) fn add_listen_addr(&self, addr: String) {
+        if let Ok(mut addrs) = self.listen_addrs.lock() {
+            addrs.push(addr);
+        }
+    }
+
+    /// 按当前配置生成的服务器信息，见 Command::ServerInfo
+    pub fn server_info(&self) -> ServerInfo {
+        let config = &self.config;
+        let runtime = self.runtime.get();
+        let options = &config.storage_options;
+        let features = [
+            ("admin-auth", config.admin_token.is_some()),
+            ("acl", !config.principals.is_empty()),
+            ("audit-log", config.audit_log.is_some()),
+            ("group-commit", config.group_commit.is_some()),
+            ("strict-cf", runtime.strict_cf_mode),
+            ("trash", config.trash_retention.is_some()),
+            ("hot-keys", config.hot_key_sample_every.is_some()),
+            ("tcp-keepalive", config.tcp_keepalive.is_some()),
+            ("fail-writes-when-degraded", runtime.fail_writes_when_degraded),
+            ("auto-compaction", options.compaction.is_some()),
+            ("lazy-load", options.lazy_load.is_some()),
+            ("compression", options.compress_threshold.is_some()),
+            ("spill", options.spill_threshold.is_some()),
+        ];
+        ServerInfo {
+            version: SERVER_VERSION.to_string(),
+            git_hash: GIT_HASH.map(str::to_string),
+            protocol_version: PROTOCOL_VERSION,
+            format_version: migration::FORMAT_VERSION,
+            pid: std::process::id(),
+            started_at_ms: self.started_at_ms,
+            listen_addrs: self.listen_addrs.lock().map(|addrs| addrs.clone()).unwrap_or_default(),
+            data_path: self.storage.path().map(|p| p.display().to_string()),
+            durability: self.storage.durability(),
+            background_flush: options.flush_policy.is_some(),
+            features: features.iter().filter(|(_, on)| *on).map(|(name, _)| name.to_string()).collect(),
+            protocol_features: FEATURES.iter().chain(OPT_IN_FEATURES).map(|f| f.to_string()).collect(),
+            limits: ServerLimits {
+                max_memory_bytes: options.max_memory_bytes,
+                max_response_bytes: runtime.max_response_bytes,
+                max_scan_examined: runtime.max_scan_examined,
+                response_chunk_bytes: runtime.response_chunk_bytes,
+                segment_max_bytes: options.segment_max_bytes,
+                idle_timeout_ms: runtime.idle_timeout.map(|d| d.as_millis() as u64),
+                max_blocked_read_ms: config.max_blocked_read.map(|d| d.as_millis() as u64),
+                pubsub_queue_capacity: config.pubsub_queue_capacity.unwrap_or(pubsub::DEFAULT_QUEUE_CAPACITY),
+                max_connections_per_ip: config.max_connections_per_ip,
+            },
+        }
+    }
+
+    /// 幂等写请求的缓存统计
+    pub fn idempotency_stats(&self) -> IdempotencyStats {
+        self.idempotency.stats(self.storage.clock().now_ms())
+    }
+
+    // 同一个 (token, key) 只执行一次；失败的请求不缓存，重试时重新执行
+    fn execute_idempotent(&self, session: &mut Session, token: u64, key: u64, cmd: Command) -> Response {
+        if cmd.is_read_only() || matches!(cmd, Command::Idempotent { .. } | Command::Shutdown { .. }) {
+            return Response::Error(format!("Idempotent cannot wrap {}", cmd.kind()));
+        }
+        let clock = self.storage.clock();
+        match self.idempotency.begin(token, key, clock.now_ms()) {
+            Claim::Replay(response) => *response,
+            Claim::InProgress => {
+                Response::Error(format!("InProgress: request {} of token {} is still being executed", key, token))
+            }
+            Claim::New => {
+                let response = self.execute(session, cmd);
+                match &response {
+                    Response::Error(_) => self.idempotency.abandon(token, key),
+                    response => self.idempotency.finish(token, key, response, clock.now_ms()),
+                }
+                response
+            }
+        }
+    }
+
+    fn execute(&self, session: &mut Session, mut cmd: Command) -> Response {
+        session.apply_default_cf(&mut cmd);
+        if let Err(e) = self.authorize(session, &cmd) {
+            return Response::Error(e);
+        }
+        // Batch 逐条校验键和列族，以便报告每个无效操作；幂等请求在执行被包装的命令时校验
+        if !matches!(cmd, Command::Batch { .. } | Command::Idempotent { .. })
+            && let Err(e) = cmd.keys().into_iter().try_for_each(validate_key).and_then(|_| self.resolve_cfs(session, &mut cmd))
+        {
+            return Response::Error(e);
+        }
+
+        match cmd {
+            Command::Get { cf, key } => {
+                if let Some(tracker) = &self.hot_keys {
+                    tracker.record(&cf, &key, hotkeys::Access::Read);
+                }
+                match self.raw_get(&cf, &key) {
+                    Ok(None) if session.features.iter().any(|f| f == "not-found") => Response::NotFound,
+                    Ok(value) => Response::Value(value.map(Bytes)),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Put { cf, key, value } => {
+                if let Some(tracker) = &self.hot_keys {
+                    tracker.record(&cf, &key, hotkeys::Access::Write);
+                }
+                let near_limit = self.value_near_limit(&cf, &key, &value);
+                match self.raw_put(cf, key, value) {
+                    Ok(_) => {
+                        near_limit.into_iter().for_each(|warning| session.warn(warning));
+                        Response::Ok
+                    }
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Delete { cf, key } => {
+                match self.raw_delete(cf, key) {
+                    Ok(_) => Response::Ok,
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::GetDel { cf, key } => {
+                match self.raw_get_del(&cf, &key) {
+                    Ok(old) => Response::Value(old.map(Bytes)),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::GetSet { cf, key, value } => {
+                match self.raw_get_set(&cf, &key, value) {
+                    Ok(old) => Response::Value(old.map(Bytes)),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Rename { cf, old_key, new_key, overwrite } => {
+                match self.raw_rename(&cf, &old_key, &new_key, overwrite) {
+                    Ok(_) => Response::Ok,
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Copy { cf, src_key, dst_key, overwrite } => {
+                match self.raw_copy(&cf, &src_key, &dst_key, overwrite) {
+                    Ok(_) => Response::Ok,
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Expire { cf, key, ttl_ms } => match self.raw_expire(&cf, &key, ttl_ms) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::Ttl { cf, key } => match self.raw_ttl(&cf, &key) {
+                Ok(ttl_ms) => Response::Ttl(ttl_ms),
+                Err(e) => Response::Error(e),
+            },
+            // cf 已按会话的数据库解析，哈希的是存储中实际的编码键
+            Command::KeyHash { cf, key } => Response::KeyHash(EncodedKey::encode(&cf, &key).stable_hash()),
+            Command::FindByValue { cf, value, limit } => {
+                match self.raw_find_by_value(&cf, &value, limit) {
+                    Ok(keys) => Response::Keys(keys.into_iter().map(Bytes).collect()),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::CreateCf { cf, options } => match self.raw_create_cf(&cf, options) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::SetCfQuota { cf, max_keys, max_bytes } => match self.raw_set_cf_quota(&cf, max_keys, max_bytes) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::LockAcquire { name, ttl_ms } => match self.raw_lock_acquire(&name, ttl_ms) {
+                Ok(token) => Response::LockToken(token),
+                Err(e) => Response::Error(e),
+            },
+            Command::LockRelease { name, token } => match self.raw_lock_release(&name, token) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::LockRenew { name, token, ttl_ms } => match self.raw_lock_renew(&name, token, ttl_ms) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::Scan { cf, start_key, end_key, limit, filter, start_bound, end_bound } => {
+                let start = match &start_bound {
+                    Some(bound) => bound.as_bound(),
+                    None => Bound::Included(start_key.as_slice()),
+                };
+                let end = match &end_bound {
+                    Some(bound) => bound.as_bound(),
+                    None => end_key.as_deref().map_or(Bound::Unbounded, Bound::Excluded),
+                };
+                let runtime = self.runtime.get();
+                let (max_bytes, max_examined) = (runtime.max_response_bytes, runtime.max_scan_examined);
+                let to_bytes = |values: storage::KvPairs| values.into_iter().map(|(k, v)| (Bytes(k), Bytes(v))).collect();
+                let deadline = session.request.deadline;
+                match self.scan_page(&cf, start, end, limit, filter.as_ref(), max_bytes, max_examined, deadline) {
+                    Ok(ScanPage { pairs, next: None, .. }) => Response::Values(to_bytes(pairs)),
+                    Ok(ScanPage { pairs, next: Some(next), examined_limit_reached })
+                        if session.features.iter().any(|f| f == "truncation") =>
+                    {
+                        session.warn(Warning::Truncated { examined_limit_reached });
+                        Response::TruncatedValues { values: to_bytes(pairs), next: Bytes(next), examined_limit_reached }
+                    }
+                    Ok(ScanPage { examined_limit_reached: true, .. }) => Response::Error(format!(
+                        "ExaminedLimitReached: scan examined {} entries, narrow the range or negotiate the truncation feature to continue",
+                        max_examined.unwrap_or_default()
+                    )),
+                    Ok(_) => Response::Error(format!(
+                        "ResponseTooLarge: scan result exceeds {} bytes, lower the limit or negotiate the truncation feature",
+                        max_bytes.unwrap_or_default()
+                    )),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::ScanAll { start, limit } => {
+                let start = start.as_ref().map(|(cf, Bytes(key))| (cf.as_str(), key.as_slice()));
+                let max_bytes = self.runtime.get().max_response_bytes;
+                match self.scan_all_page(&session.db, start, limit, max_bytes, session.request.deadline) {
+                    Ok((entries, next, truncated)) => {
+                        if truncated {
+                            session.warn(Warning::Truncated { examined_limit_reached: false });
+                        }
+                        Response::CfValues {
+                            entries: entries.into_iter().map(|(cf, k, v)| (cf, Bytes(k), Bytes(v))).collect(),
+                            next: next.map(|(cf, k)| (cf, Bytes(k))),
+                            truncated,
+                        }
+                    }
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::GetVersion { cf, key, version } => {
+                match self.raw_get_version(&cf, &key, version) {
+                    Ok(value) => Response::Value(value.map(Bytes)),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::History { cf, key, limit } => {
+                match self.raw_history(&cf, &key, limit) {
+                    Ok(versions) => Response::History(versions),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Batch { ops, mode } => self.execute_batch(session, ops, mode),
+            Command::Hello { client_version, features } => {
+                if client_version == 0 {
+                    return Response::Error(format!("Unsupported protocol version {}", client_version));
+                }
+                let accepted: Vec<String> = features
+                    .into_iter()
+                    .filter(|f| FEATURES.contains(&f.as_str()) || OPT_IN_FEATURES.contains(&f.as_str()))
+                    .collect();
+                session.protocol_version = Some(client_version.min(PROTOCOL_VERSION));
+                session.features = accepted.clone();
+                Response::Hello {
+                    server_version: PROTOCOL_VERSION,
+                    accepted_features: accepted,
+                }
+            }
+            Command::UseCf { cf } => {
+                match validate_cf_name(&cf) {
+                    Ok(_) => {
+                        session.default_cf = Some(cf);
+                        Response::Ok
+                    }
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::UseDb { name } => {
+                match validate_db_name(&name) {
+                    Ok(_) => {
+                        session.db = name;
+                        Response::Ok
+                    }
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::ListDbs => {
+                match self.list_dbs() {
+                    Ok(dbs) => Response::Databases(dbs),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::AdminAuth { token } => {
+                match &self.config.admin_token {
+                    Some(expected) if constant_time_eq(expected.as_bytes(), token.as_bytes()) => {
+                        session.is_admin = true;
+                        Response::Ok
+                    }
+                    Some(_) => Response::Error("invalid admin token".to_string()),
+                    None => Response::Error("admin authentication is not configured".to_string()),
+                }
+            }
+            Command::Auth { name, token } => {
+                let hash = acl::hash_token(&token);
+                let principal = self.config.principals.iter().find(|p| p.name == name);
+                match principal {
+                    Some(p) if constant_time_eq(p.token_sha256.as_bytes(), hash.as_bytes()) => {
+                        session.principal = Some(Arc::new(p.clone()));
+                        Response::Ok
+                    }
+                    _ => Response::Error("AuthFailed: unknown principal or invalid token".to_string()),
+                }
+            }
+            Command::Publish { channel, payload } => Response::Count(self.pubsub.publish(&channel, &payload)),
+            // 订阅由服务器在回复 Ok 前建立，这里只检查参数
+            Command::Subscribe { channels } => {
+                if channels.is_empty() {
+                    Response::Error("Subscribe requires at least one channel".to_string())
+                } else {
+                    Response::Ok
+                }
+            }
+            Command::DropDb { name, dry_run: true } => match self.drop_db_report(&name) {
+                Ok(report) => Response::DeletionPlan(report),
+                Err(e) => Response::Error(e),
+            },
+            Command::DropDb { name, dry_run: false } => {
+                match self.drop_db(&name) {
+                    Ok(_) => Response::Ok,
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::RestoreKey { cf, key, overwrite } => match self.raw_restore_key(&cf, &key, overwrite) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::ScanTrash { cf, limit } => match self.raw_scan_trash(&session.db, cf.as_deref(), limit) {
+                Ok(entries) => Response::Trash(entries),
+                Err(e) => Response::Error(e),
+            },
+            Command::Sample { cf, count } => match self.raw_sample(&session.db, cf, count) {
+                Ok(samples) => Response::Samples(samples),
+                Err(e) => Response::Error(e),
+            },
+            Command::PurgeTrash { all, dry_run: true } => match self.raw_purge_trash_report(all) {
+                Ok(report) => Response::DeletionPlan(report),
+                Err(e) => Response::Error(e),
+            },
+            Command::PurgeTrash { all, dry_run: false } => match self.raw_purge_trash(all) {
+                Ok(purged) => Response::Count(purged),
+                Err(e) => Response::Error(e),
+            },
+            Command::Info => {
+                match self.info(session, true) {
+                    Ok(response) => response,
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::InfoSummary => match self.info(session, false) {
+                Ok(response) => response,
+                Err(e) => Response::Error(e),
+            },
+            Command::ServerInfo => Response::ServerInfo(Box::new(self.server_info())),
+            Command::SetConfig { key, value } => match self.runtime.set(&key, &value) {
+                Ok(old) => {
+                    println!("Config: {} changed from {} to {}", key, old, value);
+                    Response::Ok
+                }
+                Err(e) => Response::Error(e.to_string()),
+            },
+            Command::GetConfig => {
+                let listen_addrs = self.listen_addrs.lock().map(|addrs| addrs.join(",")).unwrap_or_default();
+                let mut entries = self.runtime.entries();
+                entries.push(ConfigEntry { key: "listen_addrs".to_string(), value: listen_addrs, dynamic: false });
+                Response::Config(entries)
+            }
+            Command::ListCfs { start_after, limit } => match self.list_cfs(session, start_after.as_deref(), limit) {
+                Ok((cfs, next)) => Response::CfList { cfs, next },
+                Err(e) => Response::Error(e),
+            },
+            Command::Flush => {
+                match self.storage.flush() {
+                    Ok(stats) => Response::Flushed(stats),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::Compact => {
+                match self.storage.compact_within(session.request.deadline) {
+                    Ok(result) => Response::Compacted(result),
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::HotKeys { top_n } => match &self.hot_keys {
+                Some(tracker) => Response::HotKeys(tracker.top(top_n)),
+                None => Response::Error("hot key tracking is disabled".to_string()),
+            },
+            Command::ResetStats => {
+                if let Some(tracker) = &self.hot_keys {
+                    tracker.reset();
+                }
+                self.latency.reset();
+                self.storage.reset_lock_stats();
+                Response::Ok
+            }
+            Command::Clients => Response::Clients(self.clients.list()),
+            Command::RecentErrors { count } => Response::Errors(self.storage.error_log().recent(count)),
+            Command::KillClient { id } => match self.clients.kill(id) {
+                Ok(_) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Command::Verify { cf } => match self.storage.verify(cf.as_deref()) {
+                Ok(keys) => Response::CorruptKeys(keys.into_iter().map(|(cf, k)| (cf, Bytes(k))).collect()),
+                Err(e) => Response::Error(e),
+            },
+            Command::Repair { quarantine } => match self.storage.repair(quarantine) {
+                Ok(keys) => Response::CorruptKeys(keys.into_iter().map(|(cf, k)| (cf, Bytes(k))).collect()),
+                Err(e) => Response::Error(e),
+            },
+            Command::Audit { checks, .. } | Command::FixInvalid { checks, .. } if checks.is_empty() => {
+                Response::Error("InvalidAudit: at least one check is required".to_string())
+            }
+            Command::Audit { cf, checks, start, limit } => {
+                let start = start.as_ref().map(|(cf, Bytes(key))| (cf.as_str(), key.as_slice()));
+                let max_examined = self.runtime.get().max_scan_examined.unwrap_or(usize::MAX);
+                match self.storage.find_invalid(cf.as_deref(), start, &checks, limit, max_examined) {
+                    Ok(scan) => Response::Violations {
+                        violations: scan.violations,
+                        next: scan.next.map(|(cf, key)| (cf, Bytes(key))),
+                        examined_limit_reached: scan.examined_limit_reached,
+                    },
+                    Err(e) => Response::Error(e),
+                }
+            }
+            Command::FixInvalid { cf, checks, policy } => match self.storage.fix_invalid(cf.as_deref(), &checks, policy) {
+                Ok(report) => Response::Fixed(report),
+                Err(e) => Response::Error(e),
+            },
+            Command::AuditVerify => match &self.audit {
+                Some(log) => match log.verify() {
+                    Ok(report) => Response::AuditReport(report),
+                    Err(e) => Response::Error(e),
+                },
+                None => Response::Error("AuditDisabled: no audit log configured".to_string()),
+            },
+            Command::OplogRange { from_seq, to_seq } => match self.storage.oplog() {
+                Some(oplog) => match oplog.range(from_seq, to_seq, oplog::OPLOG_RANGE_LIMIT) {
+                    Ok((entries, next)) => Response::Oplog { entries, next },
+                    Err(e) => Response::Error(e),
+                },
+                None => Response::Error("OplogDisabled: no oplog configured".to_string()),
+            },
+            Command::Backup => match self.backup_lines_within(session.request.deadline) {
+                Ok(lines) => Response::Archive(lines),
+                Err(e) => Response::Error(e),
+            },
+            Command::Restore { archive, force } => match self.restore_lines(&archive, force) {
+                Ok(restored) => Response::Count(restored),
+                Err(e) => Response::Error(e),
+            },
+            Command::BackupToFile { path } => match self.backup_to_file_within(&path, session.request.deadline) {
+                Ok(count) => Response::Count(count),
+                Err(e) => Response::Error(e),
+            },
+            Command::RestoreFromFile { path, force } => match self.restore_from_file(&path, force) {
+                Ok(restored) => Response::Count(restored),
+                Err(e) => Response::Error(e),
+            },
+            // 关闭由 KvServer 在发送响应后执行
+            Command::Shutdown { .. } => Response::Ok,
+            Command::Idempotent { token, key, cmd } => self.execute_idempotent(session, token, key, *cmd),
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! 修改类命令的审计日志
+//!
+//! 每个修改类命令（Command::is_read_only 为 false）在回复前追加一行 JSON 记录，只记录键的
+//! SHA-256，不记录值。每条记录的 prev 是上一行的 SHA-256，第一条记录的 prev 全为 0，
+//! 删除或改动任何一行都会使之后的链接对不上。
+//!
+//! 日志超过 max_bytes 或 max_age 后把当前文件重命名为 `<path>.<n>`（n 从 1 递增），新文件以一条
+//! Continuation 记录开头，它的 prev 是上一个文件最后一行的哈希，哈希链因此跨文件延续。
+//!
+//! 设置了 retain 时，轮转后删除超出保留数的旧文件。删除前先原子写入 `<path>.pruned`，记下
+//! 保留下来的最早文件及其 Continuation 的 seq 和 prev，校验从该文件开始；删除中途崩溃留下的
+//! 旧文件在校验时被忽略，下一次轮转时删除。
+
+use crate::clock::Clock;
+use crate::protocol::{Command, Response};
+use crate::rotation::{LogFileStats, RotationPolicy};
+use crate::server::{ConnContext, Middleware};
+use crate::sha256;
+
+use serde::{Deserialize, Serialize};
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 第一条记录的 prev
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 审计日志中的一行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AuditEntry {
+    Command {
+        seq: u64,
+        prev: String,
+        timestamp_ms: u64,
+        conn_id: u64,
+        /// 通过 AdminAuth 的连接为 "admin"，通过 Auth 的连接为主体名，否则为 None
+        principal: Option<String>,
+        db: String,
+        command: String,
+        /// 命令涉及的 (列族, 键的 SHA-256)
+        keys: Vec<(String, String)>,
+        ok: bool,
+    },
+    /// 轮转后新文件的第一行
+    Continuation {
+        seq: u64,
+        prev: String,
+        timestamp_ms: u64,
+        previous_file: String,
+    },
+}
+
+/// 校验哈希链时只关心的字段
+#[derive(Deserialize)]
+struct Link {
+    seq: u64,
+    prev: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    previous_file: Option<String>,
+    #[serde(default)]
+    timestamp_ms: u64,
+}
+
+/// `<path>.pruned` 的内容：删除旧文件后哈希链的起点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PrunedMarker {
+    first_file: String,
+    seq: u64,
+    prev: String,
+}
+
+/// 哈希链中第一个断开的位置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditBreak {
+    pub file: String,
+    /// 从 1 开始的行号
+    pub line: usize,
+    pub reason: String,
+}
+
+/// AuditVerify 的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub files: usize,
+    /// 校验通过的行数，包括 Continuation
+    pub records: u64,
+    /// 链完整时为 None
+    pub broken: Option<AuditBreak>,
+}
+
+struct ChainState {
+    file: File,
+    bytes: u64,
+    next_seq: u64,
+    // 最后一行的哈希
+    head: String,
+    // 当前文件第一条记录的时间戳
+    opened_at_ms: u64,
+    rotations: u64,
+    pruned_files: u64,
+}
+
+/// 追加写入的审计日志，同时作为服务器中间件记录修改类命令
+pub struct AuditLog {
+    path: PathBuf,
+    policy: RotationPolicy,
+    clock: Arc<dyn Clock>,
+    state: Mutex<ChainState>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog").field("path", &self.path).field("policy", &self.policy).finish()
+    }
+}
+
+impl AuditLog {
+    /// 打开或创建审计日志，从已有文件的最后一行接上哈希链；max_bytes 为 0 时不轮转
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        Self::open_with_policy(path, RotationPolicy::by_size(max_bytes), clock)
+    }
+
+    /// 按 policy 轮转和保留文件的审计日志；至少保留一个轮转文件，链的起点总在编号文件上
+    pub fn open_with_policy(path: impl AsRef<Path>, mut policy: RotationPolicy, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        policy.retain = policy.retain.map(|n| n.max(1));
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let log = AuditLog {
+            state: Mutex::new(Self::resume(&path, clock.now_ms())?),
+            path,
+            policy,
+            clock,
+        };
+        // 当前文件为空而之前有轮转的文件时，先写入 Continuation 接上哈希链
+        let mut state = log.state.lock().map_err(|e| e.to_string())?;
+        if state.bytes == 0
+            && let Some(previous) = rotated_files(&log.path)?.last()
+        {
+            let previous_file = file_name(previous);
+            log.append_locked(&mut state, |seq, prev, timestamp_ms| AuditEntry::Continuation {
+                seq,
+                prev,
+                timestamp_ms,
+                previous_file,
+            })?;
+        }
+        drop(state);
+        Ok(log)
+    }
+
+    // 从已有的文件中找出最后一行，确定下一条记录的序号和 prev
+    fn resume(path: &Path, now_ms: u64) -> Result<ChainState, String> {
+        let last_line = |file: &Path| -> Result<Option<Vec<u8>>, String> {
+            let bytes = fs::read(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            Ok(complete_lines(&bytes).last().map(|line| line.to_vec()))
+        };
+        // 当前文件按第一条记录的时间计算写入时长
+        let opened_at_ms = match first_line(path)? {
+            Some(line) => serde_json::from_slice::<Link>(&line).map_or(now_ms, |link| link.timestamp_ms),
+            None => now_ms,
+        };
+        let current = if path.exists() { last_line(path)? } else { None };
+        let last = match current {
+            Some(line) => Some(line),
+            None => match rotated_files(path)?.last() {
+                Some(previous) => last_line(previous)?,
+                None => None,
+            },
+        };
+        let (next_seq, head) = match last {
+            Some(line) => {
+                let link: Link = serde_json::from_slice(&line)
+                    .map_err(|e| format!("Failed to parse last audit record in {}: {}", path.display(), e))?;
+                (link.seq + 1, sha256::hex_digest(&line))
+            }
+            None => (0, GENESIS.to_string()),
+        };
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let bytes = file.metadata().map_err(|e| e.to_string())?.len();
+        Ok(ChainState { file, bytes, next_seq, head, opened_at_ms, rotations: 0, pruned_files: 0 })
+    }
+
+    /// 审计日志的路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 当前文件和保留的轮转文件的统计
+    pub fn stats(&self) -> Result<LogFileStats, String> {
+        let state = self.state.lock().map_err(|e| e.to_string())?;
+        let rotated = rotated_files(&self.path)?;
+        let rotated_bytes: u64 = rotated.iter().filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+        Ok(LogFileStats {
+            name: "audit".to_string(),
+            active_bytes: state.bytes,
+            files: rotated.len() + 1,
+            total_bytes: rotated_bytes + state.bytes,
+            rotations: state.rotations,
+            pruned_files: state.pruned_files,
+        })
+    }
+
+    /// 记录一个修改类命令
+    pub fn record(&self, ctx: &ConnContext, cmd: &Command, ok: bool) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        if self.policy.should_rotate(state.bytes, state.opened_at_ms, self.clock.now_ms()) {
+            self.rotate(&mut state)?;
+        }
+        self.append_locked(&mut state, |seq, prev, timestamp_ms| AuditEntry::Command {
+            seq,
+            prev,
+            timestamp_ms,
+            conn_id: ctx.conn_id,
+            principal: if ctx.is_admin { Some("admin".to_string()) } else { ctx.principal.clone() },
+            db: ctx.db.clone(),
+            command: cmd.kind().to_string(),
+            keys: audited_keys(cmd).into_iter().map(|(cf, key)| (cf.to_string(), sha256::hex_digest(key))).collect(),
+            ok,
+        })
+    }
+
+    /// 重新计算整条哈希链，并确认最后一行与内存中记住的一致（检测末尾被截断）
+    pub fn verify(&self) -> Result<AuditReport, String> {
+        let state = self.state.lock().map_err(|e| e.to_string())?;
+        let (mut report, head) = verify_chain(&self.path)?;
+        if report.broken.is_none() && head != state.head {
+            report.broken = Some(AuditBreak {
+                file: file_name(&self.path),
+                line: complete_lines(&fs::read(&self.path).map_err(|e| e.to_string())?).len() + 1,
+                reason: format!("log ends before record {}, the tail was truncated", state.next_seq - 1),
+            });
+        }
+        Ok(report)
+    }
+
+    fn append_locked(
+        &self,
+        state: &mut ChainState,
+        entry: impl FnOnce(u64, String, u64) -> AuditEntry,
+    ) -> Result<(), String> {
+        let entry = entry(state.next_seq, state.head.clone(), self.clock.now_ms());
+        let mut line = serde_json::to_vec(&entry).map_err(|e| format!("Failed to serialize: {}", e))?;
+        let head = sha256::hex_digest(&line);
+        line.push(b'\n');
+        // 一次写入整行，崩溃时最多留下末尾不完整的一行
+        state.file.write_all(&line).map_err(|e| format!("Failed to write audit log: {}", e))?;
+        state.bytes += line.len() as u64;
+        state.next_seq += 1;
+        state.head = head;
+        Ok(())
+    }
+
+    // 把当前文件改名为下一个编号，在新文件开头写入 Continuation，然后删除超出保留数的文件
+    fn rotate(&self, state: &mut ChainState) -> Result<(), String> {
+        // 删除过旧文件后编号不连续，从最大的编号往后排
+        let next = numbered_files(&self.path)?.last().map_or(1, |(n, _)| n + 1);
+        let rotated = PathBuf::from(format!("{}.{}", self.path.display(), next));
+        state.file.sync_all().map_err(|e| format!("Failed to sync audit log: {}", e))?;
+        fs::rename(&self.path, &rotated).map_err(|e| format!("Failed to rotate audit log: {}", e))?;
+        state.file = File::create(&self.path).map_err(|e| format!("Failed to create {}: {}", self.path.display(), e))?;
+        state.bytes = 0;
+        state.opened_at_ms = self.clock.now_ms();
+        state.rotations += 1;
+        let previous_file = file_name(&rotated);
+        self.append_locked(state, |seq, prev, timestamp_ms| AuditEntry::Continuation {
+            seq,
+            prev,
+            timestamp_ms,
+            previous_file,
+        })?;
+        state.file.sync_all().map_err(|e| format!("Failed to sync audit log: {}", e))?;
+        self.prune(state)
+    }
+
+    // 先持久化新的链起点，再删除更早的文件
+    fn prune(&self, state: &mut ChainState) -> Result<(), String> {
+        let rotated = rotated_files(&self.path)?;
+        let expired = self.policy.expired(&rotated);
+        if expired.is_empty() {
+            return Ok(());
+        }
+        let first = rotated.get(expired.len()).unwrap_or(&self.path);
+        let line = first_line(first)?.ok_or_else(|| format!("{} is empty", first.display()))?;
+        let link: Link = serde_json::from_slice(&line)
+            .map_err(|e| format!("Failed to parse first audit record in {}: {}", first.display(), e))?;
+        let marker = PrunedMarker { first_file: file_name(first), seq: link.seq, prev: link.prev };
+        let bytes = serde_json::to_vec(&marker).map_err(|e| format!("Failed to serialize: {}", e))?;
+        let marker_path = pruned_marker_path(&self.path);
+        let tmp = marker_path.with_extension("pruned.tmp");
+        let mut file = File::create(&tmp).map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &marker_path).map_err(|e| format!("Failed to write {}: {}", marker_path.display(), e))?;
+
+        for file in expired {
+            fs::remove_file(file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+            state.pruned_files += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Middleware for AuditLog {
+    fn after(&self, ctx: &ConnContext, cmd: &Command, response: &Response, _elapsed: Duration) {
+        if cmd.is_read_only() {
+            return;
+        }
+        if let Err(e) = self.record(ctx, cmd, !matches!(response, Response::Error(_))) {
+            eprintln!("Audit: {}", e);
+        }
+    }
+}
+
+/// 校验 path 及其轮转文件中的哈希链，返回结果和最后一行的哈希；不需要打开 AuditLog
+pub fn verify_chain(path: &Path) -> Result<(AuditReport, String), String> {
+    let mut files = rotated_files(path)?;
+    if path.exists() {
+        files.push(path.to_path_buf());
+    }
+    let (mut head, mut next_seq) = (GENESIS.to_string(), 0);
+    let mut previous: Option<String> = None;
+
+    // 删除过旧文件时从标记记下的文件开始，更早的文件是删除中途崩溃留下的
+    let marker = read_pruned_marker(path)?;
+    if let Some(marker) = &marker {
+        let Some(start) = files.iter().position(|f| file_name(f) == marker.first_file) else {
+            let report = AuditReport {
+                files: files.len(),
+                records: 0,
+                broken: Some(AuditBreak {
+                    file: marker.first_file.clone(),
+                    line: 1,
+                    reason: "the first retained file is missing".to_string(),
+                }),
+            };
+            return Ok((report, head));
+        };
+        files.drain(..start);
+    }
+    let mut report = AuditReport { files: files.len(), ..AuditReport::default() };
+
+    for file in &files {
+        let name = file_name(file);
+        let bytes = fs::read(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        let lines = complete_lines(&bytes);
+        let broken = |line: usize, reason: String| Some(AuditBreak { file: name.clone(), line, reason });
+        for (index, line) in lines.iter().enumerate() {
+            let link = match serde_json::from_slice::<Link>(line) {
+                Ok(link) => link,
+                Err(e) => {
+                    report.broken = broken(index + 1, format!("unparseable record: {}", e));
+                    return Ok((report, head));
+                }
+            };
+            // 链的起点由标记确定，它之前的记录已经删除
+            let is_continuation = link.kind == "Continuation";
+            if previous.is_none() && index == 0
+                && let Some(marker) = &marker
+            {
+                if !is_continuation || link.seq != marker.seq || link.prev != marker.prev {
+                    report.broken = broken(1, "first record does not match the pruned marker".to_string());
+                    return Ok((report, head));
+                }
+                (head, next_seq) = (link.prev.clone(), link.seq);
+            }
+            // 只有轮转出的新文件以 Continuation 开头，并且必须指向上一个文件
+            let reason = if index == 0 && previous.is_some() && link.previous_file != previous {
+                Some(format!("expected a continuation of {}", previous.as_deref().unwrap_or_default()))
+            } else if is_continuation && (index > 0 || (previous.is_none() && marker.is_none())) {
+                Some(format!(
+                    "unexpected continuation of {}, earlier files are missing",
+                    link.previous_file.as_deref().unwrap_or_default()
+                ))
+            } else if link.seq != next_seq {
+                Some(format!("expected record {}, found {}", next_seq, link.seq))
+            } else if link.prev != head {
+                Some("prev does not match the hash of the previous record".to_string())
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                report.broken = broken(index + 1, reason);
+                return Ok((report, head));
+            }
+            head = sha256::hex_digest(line);
+            next_seq += 1;
+            report.records += 1;
+        }
+        if lines.iter().map(|l| l.len() + 1).sum::<usize>() < bytes.len() {
+            report.broken = broken(lines.len() + 1, "incomplete record".to_string());
+            return Ok((report, head));
+        }
+        previous = Some(name);
+    }
+    Ok((report, head))
+}
+
+// 命令修改的 (列族, 键)
+fn audited_keys(cmd: &Command) -> Vec<(&str, &[u8])> {
+    match cmd {
+        Command::Put { cf, key, .. }
+        | Command::Delete { cf, key }
+        | Command::GetDel { cf, key }
+        | Command::GetSet { cf, key, .. }
+        | Command::Expire { cf, key, .. }
+        | Command::RestoreKey { cf, key, .. } => vec![(cf, key)],
+        Command::Rename { cf, old_key, new_key, .. } => vec![(cf, old_key), (cf, new_key)],
+        Command::Copy { cf, src_key, dst_key, .. } => vec![(cf, src_key), (cf, dst_key)],
+        Command::Batch { ops, .. } => ops.iter().map(|op| (op.cf.as_str(), op.key.as_slice())).collect(),
+        Command::Idempotent { cmd, .. } => audited_keys(cmd),
+        _ => Vec::new(),
+    }
+}
+
+// 以换行结尾的完整行，不含换行
+fn complete_lines(bytes: &[u8]) -> Vec<&[u8]> {
+    bytes.split_inclusive(|b| *b == b'\n').filter_map(|line| line.strip_suffix(b"\n")).collect()
+}
+
+// 文件的第一个完整行；文件不存在时为 None
+fn first_line(file: &Path) -> Result<Option<Vec<u8>>, String> {
+    match fs::read(file) {
+        Ok(bytes) => Ok(complete_lines(&bytes).first().map(|line| line.to_vec())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", file.display(), e)),
+    }
+}
+
+fn pruned_marker_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.pruned", path.display()))
+}
+
+fn read_pruned_marker(path: &Path) -> Result<Option<PrunedMarker>, String> {
+    let marker = pruned_marker_path(path);
+    match fs::read(&marker) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", marker.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", marker.display(), e)),
+    }
+}
+
+// path 轮转出的文件，按编号排序
+fn rotated_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    Ok(numbered_files(path)?.into_iter().map(|(_, path)| path).collect())
+}
+
+fn numbered_files(path: &Path) -> Result<Vec<(u64, PathBuf)>, String> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", file_name(path));
+    let mut numbered = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(n) = name.strip_prefix(&prefix).and_then(|n| n.parse::<u64>().ok()) {
+            numbered.push((n, entry.path()));
+        }
+    }
+    numbered.sort();
+    Ok(numbered)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned())
+}
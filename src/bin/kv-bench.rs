@@ -0,0 +1,349 @@
+//! 压测工具：用多个连接对服务器执行可配置的负载，报告吞吐量和延迟分位数
+//!
+//! 读负载会先写入整个键空间，结束后抽样读取并校验值。每个线程使用自己的连接。
+
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::histogram::{Histogram, LatencySummary};
+use tinykv_rs::testing::datagen::{key, value, Rng};
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: kv-bench [--addr HOST:PORT] [--cf CF] [--threads N] [--ops N] [--warmup N] \
+[--value-size BYTES] [--key-space N] [--workload put|get|mixed:R/W|scan] [--verify N] [--csv PATH]";
+
+// 预写键空间时每批的键数
+const PRELOAD_BATCH: usize = 1000;
+// scan 负载每次扫描的条目数
+const SCAN_LIMIT: usize = 100;
+// CSV 文件为空时写入的表头
+const CSV_HEADER: &str = "workload,threads,ops,value_size,key_space,elapsed_ms,ops_per_sec,p50_us,p95_us,p99_us,max_us,errors";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Workload {
+    Put,
+    Get,
+    // 读写比例 read:write
+    Mixed { read: u32, write: u32 },
+    Scan,
+}
+
+impl Workload {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "put" => Ok(Workload::Put),
+            "get" => Ok(Workload::Get),
+            "scan" => Ok(Workload::Scan),
+            _ => {
+                let ratio = s.strip_prefix("mixed:").ok_or_else(|| format!("unknown workload '{}'", s))?;
+                let (read, write) = ratio.split_once('/').ok_or_else(|| format!("invalid mixed ratio '{}', expected R/W", ratio))?;
+                let parse = |n: &str| n.parse::<u32>().map_err(|_| format!("invalid mixed ratio '{}', expected R/W", ratio));
+                let (read, write) = (parse(read)?, parse(write)?);
+                if read + write == 0 {
+                    return Err("mixed ratio must not be 0/0".to_string());
+                }
+                Ok(Workload::Mixed { read, write })
+            }
+        }
+    }
+
+    /// 是否需要先写入键空间
+    fn reads(&self) -> bool {
+        !matches!(self, Workload::Put)
+    }
+}
+
+impl std::fmt::Display for Workload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Workload::Put => write!(f, "put"),
+            Workload::Get => write!(f, "get"),
+            Workload::Mixed { read, write } => write!(f, "mixed:{}/{}", read, write),
+            Workload::Scan => write!(f, "scan"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    addr: String,
+    cf: String,
+    threads: usize,
+    ops: u64,
+    warmup: u64,
+    value_size: usize,
+    key_space: u64,
+    workload: Workload,
+    /// 结束后抽样校验的键数
+    verify: u64,
+    /// 追加一行结果的 CSV 文件
+    csv: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            addr: "127.0.0.1:8080".to_string(),
+            cf: "bench".to_string(),
+            threads: 4,
+            ops: 100_000,
+            warmup: 10_000,
+            value_size: 100,
+            key_space: 10_000,
+            workload: Workload::Get,
+            verify: 100,
+            csv: None,
+        }
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    fn number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+        let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+        value.parse().map_err(|_| format!("invalid {} '{}'", flag, value))
+    }
+
+    let mut args_out = Args::default();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--addr" => args_out.addr = iter.next().ok_or("--addr requires a value")?,
+            "--cf" => args_out.cf = iter.next().ok_or("--cf requires a value")?,
+            "--threads" => args_out.threads = number(&arg, iter.next())?,
+            "--ops" => args_out.ops = number(&arg, iter.next())?,
+            "--warmup" => args_out.warmup = number(&arg, iter.next())?,
+            "--value-size" => args_out.value_size = number(&arg, iter.next())?,
+            "--key-space" => args_out.key_space = number(&arg, iter.next())?,
+            "--workload" => args_out.workload = Workload::parse(&iter.next().ok_or("--workload requires a value")?)?,
+            "--verify" => args_out.verify = number(&arg, iter.next())?,
+            "--csv" => args_out.csv = Some(iter.next().ok_or("--csv requires a value")?.into()),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other => return Err(format!("unexpected argument '{}'\n{}", other, USAGE)),
+        }
+    }
+    if args_out.threads == 0 || args_out.key_space == 0 {
+        return Err("--threads and --key-space must be at least 1".to_string());
+    }
+    Ok(args_out)
+}
+
+// 执行一次操作，返回是否成功
+fn run_op(client: &mut KvClient, args: &Args, rng: &mut Rng) -> bool {
+    let i = rng.below(args.key_space);
+    let read = match args.workload {
+        Workload::Put => false,
+        Workload::Get | Workload::Scan => true,
+        Workload::Mixed { read, write } => rng.below((read + write) as u64) < read as u64,
+    };
+    let result = if !read {
+        client.put_bytes(&args.cf, &key(i), &value(i, args.value_size))
+    } else if args.workload == Workload::Scan {
+        client.scan_bytes(&args.cf, &key(i), None, SCAN_LIMIT).map(|_| ())
+    } else {
+        client.get_bytes(&args.cf, &key(i)).map(|_| ())
+    };
+    result.is_ok()
+}
+
+/// 一个阶段的结果
+struct Phase {
+    elapsed: Duration,
+    latency: LatencySummary,
+    errors: u64,
+}
+
+// 把 ops 次操作均分给各线程，每个线程使用自己的连接
+fn run_phase(args: &Args, ops: u64, seed: u64) -> Result<Phase, String> {
+    let histogram = Histogram::new();
+    let mut clients = Vec::with_capacity(args.threads);
+    for _ in 0..args.threads {
+        clients.push(KvClient::connect(&args.addr).map_err(|e| format!("Failed to connect to {}: {}", args.addr, e))?);
+    }
+
+    let started = Instant::now();
+    let errors: u64 = thread::scope(|scope| {
+        let workers: Vec<_> = clients
+            .into_iter()
+            .enumerate()
+            .map(|(t, mut client)| {
+                let histogram = &histogram;
+                let share = ops / args.threads as u64 + u64::from((t as u64) < ops % args.threads as u64);
+                scope.spawn(move || {
+                    let mut rng = Rng::new(seed + t as u64);
+                    let mut errors = 0;
+                    for _ in 0..share {
+                        let op_started = Instant::now();
+                        if !run_op(&mut client, args, &mut rng) {
+                            errors += 1;
+                        }
+                        histogram.record_duration(op_started.elapsed());
+                    }
+                    errors
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().expect("bench worker panicked")).sum()
+    });
+    Ok(Phase { elapsed: started.elapsed(), latency: histogram.summary(), errors })
+}
+
+// 按批写入整个键空间
+fn preload(args: &Args) -> Result<(), String> {
+    let mut client = KvClient::connect(&args.addr).map_err(|e| format!("Failed to connect to {}: {}", args.addr, e))?;
+    let mut next = 0;
+    while next < args.key_space {
+        let end = (next + PRELOAD_BATCH as u64).min(args.key_space);
+        let ops = (next..end).map(|i| Modify::new_put(args.cf.clone(), key(i), value(i, args.value_size))).collect();
+        let outcome = client.write_batch(ops).map_err(|e| format!("Preload failed: {}", e))?;
+        if !outcome.is_applied() {
+            return Err(format!("Preload rejected: {:?}", outcome));
+        }
+        next = end;
+    }
+    Ok(())
+}
+
+// 抽样读取并校验值；预写过的键必须存在
+fn verify(args: &Args) -> Result<u64, String> {
+    let mut client = KvClient::connect(&args.addr).map_err(|e| format!("Failed to connect to {}: {}", args.addr, e))?;
+    let mut rng = Rng::new(u64::MAX / 3);
+    let mut checked = 0;
+    for _ in 0..args.verify {
+        let i = rng.below(args.key_space);
+        match client.get_bytes(&args.cf, &key(i)).map_err(|e| format!("Verify read failed: {}", e))? {
+            Some(got) if got == value(i, args.value_size) => checked += 1,
+            Some(_) => return Err(format!("Verify failed: wrong value for {}", String::from_utf8_lossy(&key(i)))),
+            None if args.workload.reads() => {
+                return Err(format!("Verify failed: {} is missing", String::from_utf8_lossy(&key(i))));
+            }
+            None => {}
+        }
+    }
+    Ok(checked)
+}
+
+fn csv_row(args: &Args, phase: &Phase) -> String {
+    let l = &phase.latency;
+    format!(
+        "{},{},{},{},{},{},{:.0},{},{},{},{},{}",
+        args.workload,
+        args.threads,
+        args.ops,
+        args.value_size,
+        args.key_space,
+        phase.elapsed.as_millis(),
+        throughput(args.ops, phase.elapsed),
+        l.p50,
+        l.p95,
+        l.p99,
+        l.max,
+        phase.errors
+    )
+}
+
+fn throughput(ops: u64, elapsed: Duration) -> f64 {
+    ops as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+fn write_csv(path: &PathBuf, row: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let empty = file.metadata().map(|m| m.len() == 0).unwrap_or(false);
+    let text = if empty { format!("{}\n{}\n", CSV_HEADER, row) } else { format!("{}\n", row) };
+    file.write_all(text.as_bytes()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    if args.workload.reads() {
+        let started = Instant::now();
+        preload(args)?;
+        println!("preloaded {} keys in {:.2?}", args.key_space, started.elapsed());
+    }
+    if args.warmup > 0 {
+        let warmup = run_phase(args, args.warmup, 1)?;
+        println!("warmup: {} ops in {:.2?}", args.warmup, warmup.elapsed);
+    }
+
+    let phase = run_phase(args, args.ops, 1_000)?;
+    let l = &phase.latency;
+    println!(
+        "{}: {} ops, {} threads, {:.2?}, {:.0} ops/s, {} errors",
+        args.workload,
+        args.ops,
+        args.threads,
+        phase.elapsed,
+        throughput(args.ops, phase.elapsed),
+        phase.errors
+    );
+    println!("latency (us): p50 {}  p95 {}  p99 {}  max {}", l.p50, l.p95, l.p99, l.max);
+
+    let checked = verify(args)?;
+    println!("verified {} of {} sampled keys", checked, args.verify);
+    if let Some(path) = &args.csv {
+        write_csv(path, &csv_row(args, &phase))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(&args) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        parse_args(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_workloads() {
+        assert_eq!(Workload::parse("put").unwrap(), Workload::Put);
+        assert_eq!(Workload::parse("mixed:80/20").unwrap(), Workload::Mixed { read: 80, write: 20 });
+        assert_eq!(Workload::parse("mixed:80/20").unwrap().to_string(), "mixed:80/20");
+        assert!(Workload::parse("mixed:0/0").is_err());
+        assert!(Workload::parse("mixed:80").is_err());
+        assert!(Workload::parse("delete").is_err());
+        assert!(!Workload::Put.reads());
+        assert!(Workload::Mixed { read: 0, write: 1 }.reads());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse("--threads 8 --ops 500 --workload scan --csv out.csv").unwrap();
+        assert_eq!((args.threads, args.ops, args.workload), (8, 500, Workload::Scan));
+        assert_eq!(args.csv, Some(PathBuf::from("out.csv")));
+        assert_eq!(parse("").unwrap(), Args::default());
+        assert!(parse("--threads 0").is_err());
+        assert!(parse("--ops many").is_err());
+        assert!(parse("--bogus").is_err());
+    }
+
+    #[test]
+    fn test_values_are_deterministic() {
+        assert_eq!(value(7, 5), b"7:7:7".to_vec());
+        assert_eq!(value(12, 0), Vec::<u8>::new());
+        assert_eq!(key(3), b"key0000000003".to_vec());
+        let (mut a, mut b) = (Rng::new(1), Rng::new(1));
+        assert_eq!((0..10).map(|_| a.below(100)).collect::<Vec<_>>(), (0..10).map(|_| b.below(100)).collect::<Vec<_>>());
+    }
+}
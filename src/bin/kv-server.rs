@@ -0,0 +1,171 @@
+use tinykv_rs::accesslog::LogFormat;
+use tinykv_rs::doctor::{self, Severity};
+use tinykv_rs::oplog::OplogConfig;
+use tinykv_rs::server::{self, ServerConfig};
+
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: kv-server [--data-dir DIR | --in-memory] [--addr HOST:PORT] [--force-unlock] [--audit-log PATH [--audit-max-bytes N] [--audit-retain N]] [--oplog PATH [--oplog-max-bytes N] [--oplog-retain N]] [--keepalive SECS] [--idle-timeout SECS] [--max-blocked-read SECS] [--max-conns-per-ip N [--exempt-localhost]] [--log-format text|json] [--tls-cert PATH --tls-key PATH] [--doctor]";
+
+/// 命令行参数，数据目录为 None 时使用纯内存模式
+struct Args {
+    data_dir: Option<PathBuf>,
+    addr: String,
+    /// 启动前删除数据目录中遗留的锁文件
+    force_unlock: bool,
+    audit_log: Option<PathBuf>,
+    audit_max_bytes: u64,
+    audit_retain: Option<usize>,
+    /// 见 StorageOptions::oplog
+    oplog: Option<OplogConfig>,
+    /// 见 ServerConfig 的同名选项
+    keepalive: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_blocked_read: Option<Duration>,
+    max_connections_per_ip: Option<usize>,
+    exempt_localhost: bool,
+    /// 访问日志的格式
+    log_format: LogFormat,
+    /// 见 ServerConfig::tls_cert_path / tls_key_path，需要 tls 特性
+    tls_cert: Option<PathBuf>,
+    tls_key: Option<PathBuf>,
+    /// 只检查运行环境并打印报告，不启动服务器
+    doctor: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        data_dir: Some(PathBuf::from("./kv_data")),
+        addr: "127.0.0.1:8080".to_string(),
+        force_unlock: false,
+        audit_log: None,
+        audit_max_bytes: 0,
+        audit_retain: None,
+        oplog: None,
+        keepalive: None,
+        idle_timeout: None,
+        max_blocked_read: None,
+        max_connections_per_ip: None,
+        exempt_localhost: false,
+        log_format: LogFormat::default(),
+        tls_cert: None,
+        tls_key: None,
+        doctor: false,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--data-dir" => args.data_dir = Some(iter.next().ok_or("--data-dir requires a value")?.into()),
+            "--in-memory" => args.data_dir = None,
+            "--addr" => args.addr = iter.next().ok_or("--addr requires a value")?,
+            "--force-unlock" => args.force_unlock = true,
+            "--audit-log" => args.audit_log = Some(iter.next().ok_or("--audit-log requires a value")?.into()),
+            "--audit-max-bytes" => {
+                let value = iter.next().ok_or("--audit-max-bytes requires a value")?;
+                args.audit_max_bytes = value.parse().map_err(|_| format!("invalid --audit-max-bytes '{}'", value))?;
+            }
+            "--audit-retain" => {
+                let value = iter.next().ok_or("--audit-retain requires a value")?;
+                args.audit_retain = Some(value.parse().map_err(|_| format!("invalid --audit-retain '{}'", value))?);
+            }
+            "--oplog" => args.oplog = Some(OplogConfig::new(iter.next().ok_or("--oplog requires a value")?)),
+            "--oplog-max-bytes" | "--oplog-retain" => {
+                let value = iter.next().ok_or_else(|| format!("{} requires a value", arg))?;
+                let oplog = args.oplog.as_mut().ok_or_else(|| format!("{} requires --oplog", arg))?;
+                let number = value.parse().map_err(|_| format!("invalid {} '{}'", arg, value))?;
+                match arg.as_str() {
+                    "--oplog-max-bytes" => oplog.rotation.max_bytes = number,
+                    _ => oplog.rotation.retain = Some(number as usize),
+                }
+            }
+            "--keepalive" => args.keepalive = Some(parse_secs("--keepalive", iter.next())?),
+            "--idle-timeout" => args.idle_timeout = Some(parse_secs("--idle-timeout", iter.next())?),
+            "--max-blocked-read" => args.max_blocked_read = Some(parse_secs("--max-blocked-read", iter.next())?),
+            "--max-conns-per-ip" => {
+                let value = iter.next().ok_or("--max-conns-per-ip requires a value")?;
+                match value.parse() {
+                    Ok(n) if n > 0 => args.max_connections_per_ip = Some(n),
+                    _ => return Err(format!("invalid --max-conns-per-ip '{}', expected a positive number", value)),
+                }
+            }
+            "--exempt-localhost" => args.exempt_localhost = true,
+            "--log-format" => args.log_format = LogFormat::parse(&iter.next().ok_or("--log-format requires a value")?)?,
+            "--tls-cert" => args.tls_cert = Some(iter.next().ok_or("--tls-cert requires a value")?.into()),
+            "--tls-key" => args.tls_key = Some(iter.next().ok_or("--tls-key requires a value")?.into()),
+            "--doctor" => args.doctor = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other => return Err(format!("unexpected argument '{}'\n{}", other, USAGE)),
+        }
+    }
+    Ok(args)
+}
+
+fn parse_secs(flag: &str, value: Option<String>) -> Result<Duration, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    match value.parse() {
+        Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(format!("invalid {} '{}', expected a positive number of seconds", flag, value)),
+    }
+}
+
+/// 打印完整的自检报告，有 Error 级别的项时返回非零退出码
+fn run_doctor(args: &Args) -> i32 {
+    let diagnostics = doctor::diagnose(args.data_dir.as_deref(), &args.addr);
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    let count = |severity| diagnostics.iter().filter(|d| d.severity == severity).count();
+    println!("{} error(s), {} warning(s)", count(Severity::Error), count(Severity::Warning));
+    if doctor::has_errors(&diagnostics) { 1 } else { 0 }
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    if args.tls_cert.is_some() != args.tls_key.is_some() {
+        eprintln!("--tls-cert and --tls-key must be given together");
+        process::exit(2);
+    }
+    if cfg!(not(feature = "tls")) && args.tls_cert.is_some() {
+        eprintln!("--tls-cert requires kv-server to be built with the tls feature");
+        process::exit(2);
+    }
+
+    if args.doctor {
+        process::exit(run_doctor(&args));
+    }
+
+    let mut config = ServerConfig {
+        data_path: args.data_dir,
+        audit_log: args.audit_log,
+        audit_max_bytes: args.audit_max_bytes,
+        audit_retain: args.audit_retain,
+        tcp_keepalive: args.keepalive,
+        idle_timeout: args.idle_timeout,
+        max_blocked_read: args.max_blocked_read,
+        max_connections_per_ip: args.max_connections_per_ip,
+        exempt_localhost: args.exempt_localhost,
+        log_format: args.log_format,
+        ..ServerConfig::default()
+    };
+    config.storage_options.force_unlock = args.force_unlock;
+    config.storage_options.oplog = args.oplog;
+    #[cfg(feature = "tls")]
+    {
+        config.tls_cert_path = args.tls_cert;
+        config.tls_key_path = args.tls_key;
+    }
+    if let Err(e) = server::run_config_with_shutdown(config, &args.addr) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,669 @@
+mod output;
+mod script;
+mod transfer;
+
+use output::Output;
+use script::Statement;
+use tinykv_rs::client::{BatchOutcome, KvClient};
+use tinykv_rs::oplog::{OplogEntry, OplogReader};
+use tinykv_rs::protocol::{Modify, ModifyOp, Violation};
+use tinykv_rs::storage::DeletionReport;
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// info --cfs 每次请求的列族数
+const CF_PAGE_SIZE: usize = 500;
+
+const USAGE: &str = "usage: tinykv-cli [--addr HOST:PORT] [--admin-token TOKEN] [--file PATH|-] [--batch] [--keep-going] [--yes] [--output text|hex|base64|raw] [COMMAND ...]";
+
+/// 命令行参数
+struct Args {
+    addr: String,
+    admin_token: Option<String>,
+    file: Option<String>,
+    batch: bool,
+    keep_going: bool,
+    /// 确认执行 shutdown 等不可撤销的命令
+    yes: bool,
+    /// 键和值的输出格式
+    output: Output,
+    command: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        addr: "127.0.0.1:8080".to_string(),
+        admin_token: None,
+        file: None,
+        batch: false,
+        keep_going: false,
+        yes: false,
+        output: Output::default(),
+        command: Vec::new(),
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--addr" => args.addr = iter.next().ok_or("--addr requires a value")?,
+            "--admin-token" => args.admin_token = Some(iter.next().ok_or("--admin-token requires a value")?),
+            "--file" => args.file = Some(iter.next().ok_or("--file requires a value")?),
+            "--batch" => args.batch = true,
+            "--keep-going" => args.keep_going = true,
+            "--yes" => args.yes = true,
+            "--output" => args.output = Output::parse(&iter.next().ok_or("--output requires a value")?)?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => {
+                args.command.push(arg);
+                args.command.extend(iter.by_ref());
+            }
+        }
+    }
+
+    if args.file.is_none() && args.command.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(args)
+}
+
+// 刷盘、整理超过这个时间还没有完成时开始显示进度
+const PROGRESS_DELAY: Duration = Duration::from_millis(300);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 执行刷盘或整理；标准错误是终端时另开一个连接轮询 Info，在标准错误上显示进度
+fn with_progress<T>(args: &Args, operation: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+    if !io::stderr().is_terminal() {
+        return operation();
+    }
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(|| show_progress(&args.addr, &done));
+        let result = operation();
+        done.store(true, Ordering::SeqCst);
+        result
+    })
+}
+
+fn show_progress(addr: &str, done: &AtomicBool) {
+    thread::sleep(PROGRESS_DELAY);
+    if done.load(Ordering::SeqCst) {
+        return;
+    }
+    let Ok(mut client) = KvClient::connect(addr) else {
+        return;
+    };
+    let mut shown = false;
+    while !done.load(Ordering::SeqCst) {
+        if let Ok(Some(status)) = client.maintenance_status()
+            && status.running()
+        {
+            let percent = match status.total_entries {
+                0 => 100,
+                total => status.processed_entries * 100 / total,
+            };
+            eprint!(
+                "\r{} {}: {}/{} entries ({}%)",
+                status.operation.as_str(),
+                status.phase.as_str(),
+                status.processed_entries, status.total_entries, percent
+            );
+            shown = true;
+        }
+        thread::sleep(PROGRESS_INTERVAL);
+    }
+    if shown {
+        eprintln!();
+    }
+}
+
+/// 执行一条语句，返回需要打印的输出；键和值按 args.output 渲染
+fn execute(client: &mut KvClient, statement: Statement, args: &Args) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let render = |bytes: &[u8]| args.output.render(bytes);
+    let output = match statement {
+        statement @ (Statement::OplogTail { .. } | Statement::OplogGrep { .. }) => execute_local(statement, &args.output)?,
+        Statement::Put { cf, key, value } => {
+            client.put(&cf, &key, &value)?;
+            None
+        }
+        Statement::Get { cf, key } => Some(match client.get_bytes(&cf, key.as_bytes())? {
+            Some(value) => render(&value),
+            None => b"(nil)".to_vec(),
+        }),
+        Statement::Delete { cf, key } => {
+            client.delete(&cf, &key)?;
+            None
+        }
+        Statement::Rename { cf, old_key, new_key, overwrite } => {
+            client.rename(&cf, &old_key, &new_key, overwrite)?;
+            None
+        }
+        Statement::Copy { cf, src_key, dst_key, overwrite } => {
+            client.copy(&cf, &src_key, &dst_key, overwrite)?;
+            None
+        }
+        Statement::Scan { cf, start, end, limit, keep_going } => {
+            let mut scan = client.scan_builder(&cf).from_inclusive(&start).limit(limit);
+            if let Some(end) = &end {
+                scan = scan.to_exclusive(end);
+            }
+            let (items, cursor) = if keep_going {
+                (scan.run_bytes()?, None)
+            } else {
+                let result = scan.run_bounded()?;
+                (result.items, result.cursor)
+            };
+            let mut lines: Vec<Vec<u8>> = items.iter().map(|(k, v)| [render(k), b": ".to_vec(), render(v)].concat()).collect();
+            if let Some(cursor) = cursor {
+                // 服务器限制了单次扫描检查的条目数，由用户决定是否继续
+                eprintln!("Stopped at the server's scan examination limit; rerun with `scan --continue` to scan past it");
+                lines.push([b"(more from ".to_vec(), render(&cursor), b")".to_vec()].concat());
+            }
+            Some(lines.join(&b'\n'))
+        }
+        Statement::ScanAll { limit } => {
+            let (entries, next) = client.scan_all_bytes(None, limit)?;
+            let mut lines: Vec<Vec<u8>> = entries
+                .iter()
+                .map(|(cf, k, v)| [format!("{}\t", cf).into_bytes(), render(k), b": ".to_vec(), render(v)].concat())
+                .collect();
+            if let Some((cf, key)) = next {
+                lines.push([format!("(more from {} ", cf).into_bytes(), render(&key), b")".to_vec()].concat());
+            }
+            Some(lines.join(&b'\n'))
+        }
+        Statement::ScanTrash { cf, limit } => Some(
+            client
+                .scan_trash(cf.as_deref(), limit)?
+                .iter()
+                .map(|e| {
+                    let head = format!("{}\t@{}ms\t", e.cf, e.deleted_at_ms).into_bytes();
+                    [head, render(&e.key.0), b": ".to_vec(), render(&e.value.0)].concat()
+                })
+                .collect::<Vec<_>>()
+                .join(&b'\n'),
+        ),
+        Statement::Sample { count, cf } => {
+            let samples = client.sample(cf.as_deref(), count)?;
+            let mut out = b"cf\tkey\tvalue_size\tttl".to_vec();
+            for s in samples {
+                let ttl = s.ttl_ms.map_or("-".to_string(), |ttl| format!("{}ms", ttl));
+                out.push(b'\n');
+                out.extend([format!("{}\t", s.cf).into_bytes(), render(&s.key.0), format!("\t{}\t{}", s.value_size, ttl).into_bytes()].concat());
+            }
+            Some(out)
+        }
+        Statement::Expire { cf, key, seconds } => {
+            client.expire(&cf, &key, Duration::from_secs(seconds))?;
+            None
+        }
+        Statement::Ttl { cf, key } => Some(match client.ttl(&cf, &key)? {
+            Some(ttl) => format!("{}ms", ttl.as_millis()).into_bytes(),
+            None => b"(no expiry)".to_vec(),
+        }),
+        Statement::Restore { cf, key, overwrite } => {
+            client.restore_key(&cf, &key, overwrite)?;
+            None
+        }
+        Statement::PurgeTrash { all, dry_run: true } => Some(render_plan(&client.purge_trash_dry_run(all)?, &args.output)),
+        Statement::PurgeTrash { all, dry_run: false } => {
+            if client.negotiated_features().iter().any(|f| f == "dry-run") {
+                confirm_large_delete(args, &client.purge_trash_dry_run(all)?)?;
+            }
+            Some(format!("purged {} entries", client.purge_trash(all)?).into_bytes())
+        }
+        Statement::DropDb { name, dry_run: true } => Some(render_plan(&client.drop_db_dry_run(&name)?, &args.output)),
+        Statement::DropDb { name, dry_run: false } => {
+            if client.negotiated_features().iter().any(|f| f == "dry-run") {
+                confirm_large_delete(args, &client.drop_db_dry_run(&name)?)?;
+            }
+            client.drop_db(&name)?;
+            None
+        }
+        Statement::History { cf, key, limit } => {
+            let versions = client.history(&cf, &key, limit)?;
+            Some(
+                versions
+                    .iter()
+                    .map(|v| {
+                        let value = match &v.value {
+                            Some(b) => render(&b.0),
+                            None => b"(deleted)".to_vec(),
+                        };
+                        [format!("v{} @ {}ms: ", v.version, v.timestamp_ms).into_bytes(), value].concat()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(&b'\n'),
+            )
+        }
+        Statement::Info { cfs: true } => {
+            // 列族可能很多，取到一页就输出一页
+            let mut stdout = io::stdout().lock();
+            for cf in client.iter_cfs(CF_PAGE_SIZE) {
+                let cf = cf?;
+                let quota = |max: Option<usize>| max.map_or("-".to_string(), |m| m.to_string());
+                writeln!(
+                    stdout,
+                    "{}\tkeys={} bytes={} max_keys={} max_bytes={} created={}ms",
+                    cf.name, cf.keys, cf.bytes, quota(cf.max_keys), quota(cf.max_bytes), cf.created_at_ms
+                )?;
+            }
+            None
+        }
+        Statement::Info { cfs: false } => {
+            let (total_keys, cf_count) = client.info()?;
+            let mut out = format!("total_keys: {}\ncolumn_families: {}", total_keys, cf_count);
+            if let Some(load) = client.load_status()? {
+                out += &format!("\nloading: {}/{} bytes, {} pending writes", load.loaded_bytes, load.total_bytes, load.pending_writes);
+                if let Some(error) = &load.error {
+                    out += &format!(" error={}", error);
+                }
+            }
+            out += &format!("\nerrors: {}", client.error_count()?);
+            let persistence = client.persistence_status()?;
+            if persistence.degraded {
+                out += &format!(
+                    "\npersistence: degraded since {}ms after {} failed flushes: {}",
+                    persistence.since_ms,
+                    persistence.consecutive_failures,
+                    persistence.last_error.as_deref().unwrap_or("")
+                );
+            }
+            let compression = client.compression_stats()?;
+            if compression.compressed_values > 0 {
+                out += &format!(
+                    "\ncompression: {} values, {}B -> {}B",
+                    compression.compressed_values, compression.logical_bytes, compression.physical_bytes
+                );
+            }
+            let spill = client.spill_stats()?;
+            if spill.files > 0 {
+                out += &format!("\nspill: {} values, {}B on disk in {} files, {}B reclaimable", spill.values, spill.disk_bytes, spill.files, spill.dead_bytes);
+            }
+            let locks = client.lock_stats()?;
+            if locks.read_lock_waits + locks.write_lock_waits > 0 {
+                out += &format!(
+                    "\nlock waits: read {}/{} write {}/{} p99={}us",
+                    locks.read_lock_waits, locks.read_lock_acquisitions, locks.write_lock_waits, locks.write_lock_acquisitions, locks.lock_wait_p99_us
+                );
+            }
+            for log in client.log_stats()? {
+                out += &format!(
+                    "\n{}: {} files, {}B ({}B active), {} rotations, {} pruned",
+                    log.name, log.files, log.total_bytes, log.active_bytes, log.rotations, log.pruned_files
+                );
+            }
+            for (command, s) in client.latency()? {
+                out += &format!("\n{}: count={} p50={}us p95={}us p99={}us max={}us", command, s.count, s.p50, s.p95, s.p99, s.max);
+            }
+            if let Some(m) = client.maintenance_status()? {
+                out += &format!("\nmaintenance: {} {} {}/{} entries", m.operation.as_str(), m.phase.as_str(), m.processed_entries, m.total_entries);
+                if let Some(error) = &m.error {
+                    out += &format!(" error={}", error);
+                }
+            }
+            let c = client.compaction_info()?;
+            out += &format!(
+                "\ncompaction: count={} automatic={} dead_ratio={:.2} last_reclaimed={}B",
+                c.compactions, c.automatic, c.dead_ratio, c.last_reclaimed_bytes
+            );
+            Some(out.into_bytes())
+        }
+        Statement::ServerInfo => Some(client.server_info()?.to_string().into_bytes()),
+        Statement::ConfigGet => Some(
+            client
+                .get_config()?
+                .iter()
+                .map(|e| format!("{}\t{}\t{}", e.key, e.value, if e.dynamic { "dynamic" } else { "static" }))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .into_bytes(),
+        ),
+        Statement::ConfigSet { key, value } => {
+            client.set_config(&key, &value)?;
+            Some(format!("{} = {}", key, value).into_bytes())
+        }
+        Statement::Flush => {
+            let stats = with_progress(args, || client.flush())?;
+            Some(format!("flushed {} bytes (fsync: {})", stats.bytes_written, stats.fsynced).into_bytes())
+        }
+        Statement::Compact => {
+            let result = with_progress(args, || client.compact())?;
+            Some(
+                format!(
+                    "removed {} dead entries, reclaimed {} disk bytes and {} bytes of memory capacity",
+                    result.dead_entries_removed, result.reclaimed_disk_bytes, result.reclaimed_capacity_bytes
+                )
+                .into_bytes(),
+            )
+        }
+        Statement::Clients => Some(
+            client
+                .clients()?
+                .iter()
+                .map(|c| {
+                    format!(
+                        "id={} addr={} db={} admin={} cmds={} in={} out={} last={} since={}ms",
+                        c.id,
+                        c.peer_addr,
+                        c.db,
+                        c.is_admin,
+                        c.commands,
+                        c.bytes_in,
+                        c.bytes_out,
+                        c.last_command.as_deref().unwrap_or("-"),
+                        c.connected_at_ms
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+                .into_bytes(),
+        ),
+        Statement::Errors { count } => Some(
+            client
+                .recent_errors(count)?
+                .iter()
+                .map(|e| format!("{}ms\t{}\t{}", e.timestamp_ms, e.category.as_str(), e.message))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .into_bytes(),
+        ),
+        Statement::AuditVerify => {
+            let report = client.audit_verify()?;
+            match report.broken {
+                None => Some(format!("ok: {} records in {} files", report.records, report.files).into_bytes()),
+                Some(b) => return Err(format!("broken at {}:{} after {} records: {}", b.file, b.line, report.records, b.reason).into()),
+            }
+        }
+        Statement::Audit { cf, checks } => {
+            let violations = client.audit(cf.as_deref(), &checks)?;
+            Some(render_violations(&violations, &args.output))
+        }
+        Statement::Fix { policy, cf, checks } => {
+            let report = client.fix_invalid(cf.as_deref(), &checks, policy)?;
+            let mut out = format!("fixed {} entries, skipped {}", report.fixed.len(), report.skipped.len()).into_bytes();
+            if !report.skipped.is_empty() {
+                out.push(b'\n');
+                out.extend(render_violations(&report.skipped, &args.output));
+            }
+            Some(out)
+        }
+        Statement::Kill { id } => {
+            client.kill_client(id)?;
+            None
+        }
+        Statement::Quota { cf, max_keys, max_bytes } => {
+            client.set_cf_quota(&cf, max_keys, max_bytes)?;
+            None
+        }
+        Statement::Import { file, options } => {
+            let summary = transfer::import(client, BufReader::new(File::open(&file)?), &options);
+            transfer_result("imported", summary)?
+        }
+        Statement::Export { file, options } => {
+            let summary = transfer::export(client, io::BufWriter::new(File::create(&file)?), &options)?;
+            transfer_result("exported", summary)?
+        }
+        Statement::Backup { file } => {
+            let count = client.backup(io::BufWriter::new(File::create(&file)?))?;
+            Some(format!("backed up {} keys to {}", count, file).into_bytes())
+        }
+        Statement::RestoreArchive { file, force } => {
+            let count = client.restore(BufReader::new(File::open(&file)?), force)?;
+            Some(format!("restored {} keys from {}", count, file).into_bytes())
+        }
+        Statement::OplogRange { from_seq, to_seq } => {
+            let entries = client.oplog_range(from_seq, to_seq)?;
+            Some(entries.iter().flat_map(|e| render_oplog_entry(e, &args.output)).collect::<Vec<_>>().join(&b'\n'))
+        }
+        Statement::Shutdown { flush } => {
+            if !args.yes {
+                return Err("shutdown stops the server, pass --yes to confirm".into());
+            }
+            client.shutdown(flush)?;
+            Some(b"server is shutting down".to_vec())
+        }
+    };
+    Ok(output)
+}
+
+/// 执行只读取本地文件的语句（见 Statement::is_local）
+fn execute_local(statement: Statement, output: &Output) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    match statement {
+        Statement::OplogTail { file, count } => {
+            let mut last = VecDeque::with_capacity(count);
+            for entry in OplogReader::open(&file)? {
+                if last.len() == count {
+                    last.pop_front();
+                }
+                if count > 0 {
+                    last.push_back(entry?);
+                }
+            }
+            Ok(Some(last.iter().flat_map(|e| render_oplog_entry(e, output)).collect::<Vec<_>>().join(&b'\n')))
+        }
+        Statement::OplogGrep { file, key, cf } => {
+            // oplog 可能很大，边读边输出
+            let mut stdout = io::stdout().lock();
+            for entry in OplogReader::open(&file)? {
+                let entry = entry?;
+                for op in entry.ops.iter().filter(|op| op.key == key.as_bytes() && cf.as_ref().is_none_or(|cf| &op.cf == cf)) {
+                    stdout.write_all(&render_oplog_op(&entry, op, output))?;
+                    stdout.write_all(b"\n")?;
+                }
+            }
+            Ok(None)
+        }
+        other => Err(format!("not a local statement: {:?}", other).into()),
+    }
+}
+
+/// oplog 记录中的每个操作一行：序号、时间、主体、操作、列族、键和值
+fn render_oplog_entry(entry: &OplogEntry, output: &Output) -> Vec<Vec<u8>> {
+    entry.ops.iter().map(|op| render_oplog_op(entry, op, output)).collect()
+}
+
+fn render_oplog_op(entry: &OplogEntry, op: &Modify, output: &Output) -> Vec<u8> {
+    let principal = entry.principal.as_deref().unwrap_or("-");
+    let head = format!("{}\t{}ms\t{}\t", entry.seq, entry.timestamp_ms, principal).into_bytes();
+    match op.op {
+        ModifyOp::Put => [head, format!("put\t{}\t", op.cf).into_bytes(), output.render(&op.key), b": ".to_vec(), output.render(&op.value)].concat(),
+        ModifyOp::Delete => [head, format!("delete\t{}\t", op.cf).into_bytes(), output.render(&op.key)].concat(),
+    }
+}
+
+/// 删除超过这么多键时需要 --yes 确认
+const CONFIRM_DELETE_KEYS: usize = 1000;
+
+fn confirm_large_delete(args: &Args, plan: &DeletionReport) -> Result<(), Box<dyn Error>> {
+    if plan.keys > CONFIRM_DELETE_KEYS && !args.yes {
+        return Err(format!("would delete {} keys ({} bytes), pass --yes to confirm", plan.keys, plan.bytes).into());
+    }
+    Ok(())
+}
+
+/// 试运行的结果：键数、字节数以及按存储顺序的首尾键
+fn render_plan(plan: &DeletionReport, output: &Output) -> Vec<u8> {
+    let mut out = format!("would delete {} keys ({} bytes)", plan.keys, plan.bytes).into_bytes();
+    for (label, entry) in [("first", &plan.first), ("last", &plan.last)] {
+        if let Some((cf, key)) = entry {
+            out.extend([format!("\n{}: {}\t", label, cf).into_bytes(), output.render(&key.0)].concat());
+        }
+    }
+    out
+}
+
+/// 每行一个违规条目：列族、键、值长度和没有通过的检查
+fn render_violations(violations: &[Violation], output: &Output) -> Vec<u8> {
+    let mut out = b"cf\tkey\tvalue_size\tfailed".to_vec();
+    for v in violations {
+        let failed: Vec<String> = v.failed.iter().map(|c| c.to_string()).collect();
+        out.push(b'\n');
+        out.extend([format!("{}\t", v.cf).into_bytes(), output.render(&v.key.0), format!("\t{}\t{}", v.value_len, failed.join(",")).into_bytes()].concat());
+    }
+    out
+}
+
+/// import / export 的结果：有失败的记录时整条语句算作失败，失败的记录已逐条报告
+fn transfer_result(verb: &str, summary: transfer::TransferSummary) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let message = format!("{} {} records, {} failed", verb, summary.ok, summary.failed);
+    match summary.failed {
+        0 => Ok(Some(message.into_bytes())),
+        _ => Err(message.into()),
+    }
+}
+
+/// 输出可能包含任意字节（--output raw），直接写入标准输出
+fn print_output(output: &[u8]) {
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(output).and_then(|_| stdout.write_all(b"\n"));
+}
+
+/// 批处理执行的统计
+#[derive(Default)]
+struct Summary {
+    ok: usize,
+    failed: usize,
+}
+
+/// 逐行执行脚本；`--batch` 时把连续的 put 合并成一次 Batch 命令
+fn run_script(client: &mut KvClient, reader: impl BufRead, args: &Args) -> Summary {
+    let mut summary = Summary::default();
+    // 待合并的 put：(行号, 修改)
+    let mut pending: Vec<(usize, Modify)> = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_no = index + 1;
+        if !args.keep_going && summary.failed > 0 {
+            break;
+        }
+
+        let statement = match line.map_err(|e| e.to_string()).and_then(|l| script::parse_line(&l)) {
+            Ok(Some(statement)) => statement,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("line {}: {}", line_no, e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        if args.batch
+            && let Statement::Put { cf, key, value } = &statement
+        {
+            pending.push((line_no, Modify::new_put(cf.clone(), key.as_bytes().to_vec(), value.as_bytes().to_vec())));
+            continue;
+        }
+
+        flush_batch(client, &mut pending, &mut summary);
+        if !args.keep_going && summary.failed > 0 {
+            break;
+        }
+
+        match execute(client, statement, args) {
+            Ok(output) => {
+                summary.ok += 1;
+                if let Some(output) = output {
+                    print_output(&output);
+                }
+            }
+            Err(e) => {
+                eprintln!("line {}: {}", line_no, e);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    if args.keep_going || summary.failed == 0 {
+        flush_batch(client, &mut pending, &mut summary);
+    }
+    summary
+}
+
+fn flush_batch(client: &mut KvClient, pending: &mut Vec<(usize, Modify)>, summary: &mut Summary) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let lines: Vec<usize> = pending.iter().map(|(line, _)| *line).collect();
+    let ops = pending.drain(..).map(|(_, op)| op).collect();
+    match client.write_batch(ops) {
+        Ok(BatchOutcome::Rejected(errors)) => {
+            for (i, e) in errors {
+                eprintln!("line {}: {}", lines[i], e);
+            }
+            summary.failed += lines.len();
+        }
+        Ok(_) => summary.ok += lines.len(),
+        Err(e) => {
+            eprintln!("lines {}-{}: batch failed: {}", lines[0], lines[lines.len() - 1], e);
+            summary.failed += lines.len();
+        }
+    }
+}
+
+fn run(args: Args) -> Result<bool, Box<dyn Error>> {
+    // 单条读取本地文件的语句不需要服务器
+    let statement = match &args.file {
+        Some(_) => None,
+        None => Some(script::parse_line(&args.command.join(" "))?.ok_or(USAGE)?),
+    };
+    if let Some(statement) = statement.as_ref().filter(|s| s.is_local()) {
+        if let Some(output) = execute_local(statement.clone(), &args.output)? {
+            print_output(&output);
+        }
+        return Ok(true);
+    }
+
+    let mut client = KvClient::connect(&args.addr)?;
+    if let Some(token) = &args.admin_token {
+        client.admin_auth(token)?;
+    }
+
+    let Some(file) = &args.file else {
+        if let Some(output) = execute(&mut client, statement.ok_or(USAGE)?, &args)? {
+            print_output(&output);
+        }
+        return Ok(true);
+    };
+
+    let started = Instant::now();
+    let summary = if file == "-" {
+        run_script(&mut client, io::stdin().lock(), &args)
+    } else {
+        run_script(&mut client, BufReader::new(File::open(file)?), &args)
+    };
+
+    eprintln!(
+        "{} ok, {} failed, elapsed {:.3}s",
+        summary.ok,
+        summary.failed,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(summary.failed == 0)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    match run(args) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
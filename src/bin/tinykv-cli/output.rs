@@ -0,0 +1,109 @@
+use tinykv_rs::protocol;
+
+/// 键和值的输出格式，由 `--output` 选择
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Output {
+    /// 可打印 ASCII 原样输出，其他字节转义，长值截断
+    #[default]
+    Text,
+    Hex,
+    Base64,
+    /// 原样输出字节，适合重定向到文件
+    Raw,
+}
+
+impl Output {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Output::Text),
+            "hex" => Ok(Output::Hex),
+            "base64" => Ok(Output::Base64),
+            "raw" => Ok(Output::Raw),
+            other => Err(format!("unknown output format '{}', expected text|hex|base64|raw", other)),
+        }
+    }
+
+    pub fn render(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Output::Text => protocol::display_bytes(bytes).into_bytes(),
+            Output::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>().into_bytes(),
+            Output::Base64 => base64(bytes).into_bytes(),
+            Output::Raw => bytes.to_vec(),
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// 标准字母表，带填充
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// 解码标准字母表的 base64，末尾的填充可以省略
+pub fn decode_base64(encoded: &[u8]) -> Result<Vec<u8>, String> {
+    let data = encoded.strip_suffix(b"==").or_else(|| encoded.strip_suffix(b"=")).unwrap_or(encoded);
+    if data.len() % 4 == 1 {
+        return Err(format!("invalid base64 length {}", encoded.len()));
+    }
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let digit = BASE64_ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or_else(|| format!("invalid base64 character '{}'", protocol::display_bytes(&[*c])))?;
+            n |= (digit as u32) << (18 - 6 * i);
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NASTY: &[u8] = b"a\nb\0c\xff\xfe\\d";
+
+    #[test]
+    fn test_render_formats() {
+        assert_eq!(Output::Text.render(NASTY), br"a\x0ab\x00c\xff\xfe\\d".to_vec());
+        assert_eq!(Output::Hex.render(NASTY), b"610a620063fffe5c64".to_vec());
+        assert_eq!(Output::Raw.render(NASTY), NASTY.to_vec());
+        assert_eq!(Output::Base64.render(NASTY), b"YQpiAGP//lxk".to_vec());
+        assert_eq!(Output::Base64.render(b""), b"".to_vec());
+        assert_eq!(Output::Base64.render(b"f"), b"Zg==".to_vec());
+        assert_eq!(Output::Base64.render(b"fo"), b"Zm8=".to_vec());
+        assert_eq!(Output::Base64.render(b"foo"), b"Zm9v".to_vec());
+    }
+
+    #[test]
+    fn test_decode_base64() {
+        for input in [&b""[..], b"f", b"fo", b"foo", NASTY] {
+            assert_eq!(decode_base64(base64(input).as_bytes()).unwrap(), input);
+        }
+        assert_eq!(decode_base64(b"Zm8").unwrap(), b"fo");
+        assert!(decode_base64(b"Zm9v!").is_err());
+        assert!(decode_base64(b"Z").is_err());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(Output::parse("hex").unwrap(), Output::Hex);
+        assert_eq!(Output::parse("raw").unwrap(), Output::Raw);
+        assert!(Output::parse("json").is_err());
+    }
+}
@@ -0,0 +1,549 @@
+use crate::transfer::{Format, TransferOptions};
+use tinykv_rs::protocol::{CheckKind, FixPolicy};
+
+/// 一条 CLI 语句，对应一次客户端调用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Put { cf: String, key: String, value: String },
+    Get { cf: String, key: String },
+    Delete { cf: String, key: String },
+    Expire { cf: String, key: String, seconds: u64 },
+    Ttl { cf: String, key: String },
+    Rename { cf: String, old_key: String, new_key: String, overwrite: bool },
+    Copy { cf: String, src_key: String, dst_key: String, overwrite: bool },
+    /// keep_going 时服务器因检查条目上限截断后自动续扫，否则停下并提示续扫位置
+    Scan { cf: String, start: String, end: Option<String>, limit: usize, keep_going: bool },
+    ScanAll { limit: usize },
+    ScanTrash { cf: Option<String>, limit: usize },
+    Sample { count: usize, cf: Option<String> },
+    Restore { cf: String, key: String, overwrite: bool },
+    /// dry_run 时只报告会被删除的条目
+    PurgeTrash { all: bool, dry_run: bool },
+    DropDb { name: String, dry_run: bool },
+    History { cf: String, key: String, limit: usize },
+    /// cfs 为 true 时逐个列出当前数据库的列族
+    Info { cfs: bool },
+    ServerInfo,
+    ConfigGet,
+    ConfigSet { key: String, value: String },
+    Flush,
+    Compact,
+    Clients,
+    Errors { count: usize },
+    Kill { id: u64 },
+    AuditVerify,
+    /// 列出 cf（None 时为所有列族）中违反 checks 的条目
+    Audit { cf: Option<String>, checks: Vec<CheckKind> },
+    /// 按 policy 处理违反 checks 的条目
+    Fix { policy: FixPolicy, cf: Option<String>, checks: Vec<CheckKind> },
+    Quota { cf: String, max_keys: Option<usize>, max_bytes: Option<usize> },
+    Shutdown { flush: bool },
+    Import { file: String, options: TransferOptions },
+    Export { file: String, options: TransferOptions },
+    /// 备份整个存储到本地文件
+    Backup { file: String },
+    /// 用本地的备份文件替换整个存储；服务器上已有数据时需要 force
+    RestoreArchive { file: String, force: bool },
+    /// 在本地读取 oplog 文件，输出最后 count 条记录，不连接服务器
+    OplogTail { file: String, count: usize },
+    /// 在本地读取 oplog 文件，输出修改了 key（以及列族 cf）的操作，不连接服务器
+    OplogGrep { file: String, key: String, cf: Option<String> },
+    /// 从服务器取回序号在 [from_seq, to_seq] 内的 oplog 记录
+    OplogRange { from_seq: u64, to_seq: Option<u64> },
+}
+
+impl Statement {
+    /// 只读取本地文件、不需要连接服务器的语句
+    pub fn is_local(&self) -> bool {
+        matches!(self, Statement::OplogTail { .. } | Statement::OplogGrep { .. })
+    }
+}
+
+/// 默认的 scan / history 条数
+const DEFAULT_LIMIT: usize = 100;
+
+/// oplog tail 默认输出的记录数
+const DEFAULT_TAIL: usize = 10;
+
+/// 解析一行语句，空行和 `#` 开头的注释行返回 None
+/// put 的值取键之后的整行剩余部分，因此可以包含空格
+pub fn parse_line(line: &str) -> Result<Option<Statement>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (verb, rest) = split_token(line);
+    let statement = match verb {
+        "put" | "set" => {
+            let (cf, rest) = split_token(rest);
+            let (key, value) = split_token(rest);
+            if cf.is_empty() || key.is_empty() || value.is_empty() {
+                return Err("usage: put <cf> <key> <value>".to_string());
+            }
+            Statement::Put { cf: cf.to_string(), key: key.to_string(), value: value.to_string() }
+        }
+        "get" => {
+            let [cf, key] = args::<2>(rest, "get <cf> <key>")?;
+            Statement::Get { cf, key }
+        }
+        "del" | "delete" => {
+            let [cf, key] = args::<2>(rest, "del <cf> <key>")?;
+            Statement::Delete { cf, key }
+        }
+        "expire" => {
+            let [cf, key, seconds] = args::<3>(rest, "expire <cf> <key> <seconds>")?;
+            let seconds = seconds.parse().map_err(|_| format!("invalid seconds '{}'", seconds))?;
+            Statement::Expire { cf, key, seconds }
+        }
+        "ttl" => {
+            let [cf, key] = args::<2>(rest, "ttl <cf> <key>")?;
+            Statement::Ttl { cf, key }
+        }
+        "rename" => {
+            let (cf, old_key, new_key, overwrite) = move_args(rest, "rename")?;
+            Statement::Rename { cf, old_key, new_key, overwrite }
+        }
+        "copy" => {
+            let (cf, src_key, dst_key, overwrite) = move_args(rest, "copy")?;
+            Statement::Copy { cf, src_key, dst_key, overwrite }
+        }
+        "scan" if rest.split_whitespace().next() == Some("--all") => {
+            let tokens: Vec<&str> = rest.split_whitespace().skip(1).collect();
+            if tokens.len() > 1 {
+                return Err("usage: scan --all [limit]".to_string());
+            }
+            Statement::ScanAll { limit: parse_limit(tokens.first())? }
+        }
+        "scan" if rest.split_whitespace().next() == Some("--trash") => {
+            let tokens: Vec<&str> = rest.split_whitespace().skip(1).collect();
+            match tokens[..] {
+                [] => Statement::ScanTrash { cf: None, limit: DEFAULT_LIMIT },
+                [cf] => Statement::ScanTrash { cf: Some(cf.to_string()), limit: DEFAULT_LIMIT },
+                [cf, limit] => Statement::ScanTrash { cf: Some(cf.to_string()), limit: parse_limit(Some(&limit))? },
+                _ => return Err("usage: scan --trash [cf] [limit]".to_string()),
+            }
+        }
+        "scan" => {
+            let mut tokens: Vec<&str> = rest.split_whitespace().collect();
+            let keep_going = tokens.first() == Some(&"--continue");
+            if keep_going {
+                tokens.remove(0);
+            }
+            if tokens.is_empty() || tokens.len() > 4 {
+                return Err("usage: scan [--continue] <cf> [start] [end] [limit]".to_string());
+            }
+            Statement::Scan {
+                cf: tokens[0].to_string(),
+                start: tokens.get(1).copied().unwrap_or("").to_string(),
+                end: tokens.get(2).filter(|e| **e != "-").map(|e| e.to_string()),
+                limit: parse_limit(tokens.get(3))?,
+                keep_going,
+            }
+        }
+        "sample" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+            [count] => Statement::Sample { count: parse_limit(Some(&count))?, cf: None },
+            [count, "--cf", cf] => Statement::Sample { count: parse_limit(Some(&count))?, cf: Some(cf.to_string()) },
+            _ => return Err("usage: sample <count> [--cf cf]".to_string()),
+        },
+        "history" => {
+            let tokens: Vec<&str> = rest.split_whitespace().collect();
+            if tokens.len() < 2 || tokens.len() > 3 {
+                return Err("usage: history <cf> <key> [limit]".to_string());
+            }
+            Statement::History {
+                cf: tokens[0].to_string(),
+                key: tokens[1].to_string(),
+                limit: parse_limit(tokens.get(2))?,
+            }
+        }
+        "info" => match rest {
+            "" => Statement::Info { cfs: false },
+            "--cfs" => Statement::Info { cfs: true },
+            _ => return Err("usage: info [--cfs]".to_string()),
+        },
+        "server-info" => no_args(rest, Statement::ServerInfo)?,
+        "config" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+            ["get"] => Statement::ConfigGet,
+            ["set", key, value] => Statement::ConfigSet { key: key.to_string(), value: value.to_string() },
+            _ => return Err("usage: config get | config set <key> <value>".to_string()),
+        },
+        "flush" => no_args(rest, Statement::Flush)?,
+        "compact" => no_args(rest, Statement::Compact)?,
+        "clients" => no_args(rest, Statement::Clients)?,
+        "errors" => {
+            let tokens: Vec<&str> = rest.split_whitespace().collect();
+            if tokens.len() > 1 {
+                return Err("usage: errors [count]".to_string());
+            }
+            Statement::Errors { count: parse_limit(tokens.first())? }
+        }
+        "audit" => match rest {
+            "verify" => Statement::AuditVerify,
+            _ => {
+                let (cf, checks) = check_args(rest, "audit verify | audit [--cf cf] <check>...")?;
+                Statement::Audit { cf, checks }
+            }
+        },
+        "fix" => {
+            let usage = "fix <delete|truncate|quarantine> [--cf cf] <check>...";
+            let (policy, rest) = split_token(rest);
+            if policy.is_empty() {
+                return Err(format!("usage: {}", usage));
+            }
+            let (cf, checks) = check_args(rest, usage)?;
+            Statement::Fix { policy: policy.parse()?, cf, checks }
+        }
+        "kill" => {
+            let [id] = args::<1>(rest, "kill <id>")?;
+            Statement::Kill { id: id.parse().map_err(|_| format!("invalid client id '{}'", id))? }
+        }
+        "restore" if rest.split_whitespace().next() == Some("--in") => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+            ["--in", file] => Statement::RestoreArchive { file: file.to_string(), force: false },
+            ["--in", file, "--force"] => Statement::RestoreArchive { file: file.to_string(), force: true },
+            _ => return Err("usage: restore --in <file> [--force]".to_string()),
+        },
+        "restore" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+            [cf, key] => Statement::Restore { cf: cf.to_string(), key: key.to_string(), overwrite: false },
+            [cf, key, "--overwrite"] => Statement::Restore { cf: cf.to_string(), key: key.to_string(), overwrite: true },
+            _ => return Err("usage: restore <cf> <key> [--overwrite]".to_string()),
+        },
+        "backup" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+            ["--out", file] => Statement::Backup { file: file.to_string() },
+            _ => return Err("usage: backup --out <file>".to_string()),
+        },
+        "purge-trash" => {
+            let flags = flags(rest, &["--all", "--dry-run"], "purge-trash [--all] [--dry-run]")?;
+            Statement::PurgeTrash { all: flags.contains(&"--all"), dry_run: flags.contains(&"--dry-run") }
+        }
+        "drop-db" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+            [name] => Statement::DropDb { name: name.to_string(), dry_run: false },
+            [name, "--dry-run"] => Statement::DropDb { name: name.to_string(), dry_run: true },
+            _ => return Err("usage: drop-db <name> [--dry-run]".to_string()),
+        },
+        "quota" => {
+            let [cf, max_keys, max_bytes] = args::<3>(rest, "quota <cf> <max_keys|-> <max_bytes|->")?;
+            Statement::Quota { cf, max_keys: parse_quota(&max_keys)?, max_bytes: parse_quota(&max_bytes)? }
+        }
+        "shutdown" => match rest {
+            "" => Statement::Shutdown { flush: true },
+            "--no-flush" => Statement::Shutdown { flush: false },
+            _ => return Err("usage: shutdown [--no-flush]".to_string()),
+        },
+        "import" => {
+            let (file, options) = transfer_args(rest, "import")?;
+            Statement::Import { file, options }
+        }
+        "export" => {
+            let (file, options) = transfer_args(rest, "export")?;
+            if options.format == Format::RedisProto {
+                return Err("export supports csv|jsonl".to_string());
+            }
+            Statement::Export { file, options }
+        }
+        "oplog" => oplog_args(rest)?,
+        other => return Err(format!("unknown command '{}'", other)),
+    };
+
+    Ok(Some(statement))
+}
+
+// 拆出第一个以空白分隔的词，返回 (词, 去掉前导空白的剩余部分)
+fn split_token(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(pos) => (&s[..pos], s[pos..].trim_start()),
+        None => (s, ""),
+    }
+}
+
+fn args<const N: usize>(rest: &str, usage: &str) -> Result<[String; N], String> {
+    let tokens: Vec<String> = rest.split_whitespace().map(str::to_string).collect();
+    tokens.try_into().map_err(|_| format!("usage: {}", usage))
+}
+
+// 只包含 allowed 中的开关，每个至多出现一次，顺序任意
+fn flags<'a>(rest: &'a str, allowed: &[&str], usage: &str) -> Result<Vec<&'a str>, String> {
+    let tokens: Vec<&str> = rest.split_whitespace().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        if !allowed.contains(token) || tokens[..i].contains(token) {
+            return Err(format!("usage: {}", usage));
+        }
+    }
+    Ok(tokens)
+}
+
+// rename / copy 的参数：<cf> <src> <dst> [--overwrite]
+fn move_args(rest: &str, verb: &str) -> Result<(String, String, String, bool), String> {
+    match rest.split_whitespace().collect::<Vec<_>>()[..] {
+        [cf, src, dst] => Ok((cf.to_string(), src.to_string(), dst.to_string(), false)),
+        [cf, src, dst, "--overwrite"] => Ok((cf.to_string(), src.to_string(), dst.to_string(), true)),
+        _ => Err(format!("usage: {} <cf> <src> <dst> [--overwrite]", verb)),
+    }
+}
+
+fn no_args(rest: &str, statement: Statement) -> Result<Statement, String> {
+    if rest.is_empty() {
+        Ok(statement)
+    } else {
+        Err(format!("unexpected arguments: {}", rest))
+    }
+}
+
+fn parse_limit(token: Option<&&str>) -> Result<usize, String> {
+    match token {
+        Some(t) => t.parse().map_err(|_| format!("invalid limit '{}'", t)),
+        None => Ok(DEFAULT_LIMIT),
+    }
+}
+
+// import / export 的参数：--format <fmt> [--delimiter <c>] [--base64] [--cf <cf>] <file>
+fn transfer_args(rest: &str, verb: &str) -> Result<(String, TransferOptions), String> {
+    let usage = || format!("usage: {} --format <csv|jsonl|redis-proto> [--delimiter <c>] [--base64] [--cf <cf>] <file>", verb);
+    let mut format = None;
+    let mut options = TransferOptions::new(Format::Csv);
+    let mut file = None;
+    let mut tokens = rest.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "--format" => format = Some(Format::parse(tokens.next().ok_or_else(usage)?)?),
+            "--delimiter" => {
+                options.delimiter = match tokens.next().ok_or_else(usage)? {
+                    "tab" | "\\t" => b'\t',
+                    d if d.len() == 1 && d != "\"" => d.as_bytes()[0],
+                    d => return Err(format!("invalid delimiter '{}', expected a single ASCII character or 'tab'", d)),
+                }
+            }
+            "--base64" => options.base64 = true,
+            "--cf" => options.cf = tokens.next().ok_or_else(usage)?.to_string(),
+            t if t.starts_with("--") || file.is_some() => return Err(usage()),
+            t => file = Some(t.to_string()),
+        }
+    }
+    options.format = format.ok_or_else(usage)?;
+    Ok((file.ok_or_else(usage)?, options))
+}
+
+// audit / fix 的参数：[--cf cf] <check>...，检查的写法见 CheckKind 的 Display
+fn check_args(rest: &str, usage: &str) -> Result<(Option<String>, Vec<CheckKind>), String> {
+    let (cf, checks) = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+        ["--cf", cf, ref checks @ ..] => (Some(cf.to_string()), checks.to_vec()),
+        ref checks => (None, checks.to_vec()),
+    };
+    if checks.is_empty() || checks.iter().any(|c| c.starts_with("--")) {
+        return Err(format!("usage: {}", usage));
+    }
+    let checks = checks.iter().map(|c| c.parse()).collect::<Result<Vec<CheckKind>, String>>()?;
+    Ok((cf, checks))
+}
+
+// oplog 的子命令：tail [-n N] <file> | grep --key K [--cf CF] <file> | range <from> [to]
+fn oplog_args(rest: &str) -> Result<Statement, String> {
+    let usage = || "usage: oplog tail [-n count] <file> | oplog grep --key <key> [--cf cf] <file> | oplog range <from> [to]".to_string();
+    let parse_seq = |t: &str| t.parse::<u64>().map_err(|_| format!("invalid sequence '{}'", t));
+    let (sub, rest) = split_token(rest);
+    let tokens: Vec<&str> = rest.split_whitespace().collect();
+    match (sub, &tokens[..]) {
+        ("tail", [file]) => Ok(Statement::OplogTail { file: file.to_string(), count: DEFAULT_TAIL }),
+        ("tail", ["-n", count, file]) => Ok(Statement::OplogTail { file: file.to_string(), count: parse_limit(Some(count))? }),
+        ("grep", _) => {
+            let (mut key, mut cf, mut file) = (None, None, None);
+            let mut iter = tokens.iter();
+            while let Some(token) = iter.next() {
+                match *token {
+                    "--key" => key = Some(iter.next().ok_or_else(usage)?.to_string()),
+                    "--cf" => cf = Some(iter.next().ok_or_else(usage)?.to_string()),
+                    t if t.starts_with("--") || file.is_some() => return Err(usage()),
+                    t => file = Some(t.to_string()),
+                }
+            }
+            Ok(Statement::OplogGrep { file: file.ok_or_else(usage)?, key: key.ok_or_else(usage)?, cf })
+        }
+        ("range", [from]) => Ok(Statement::OplogRange { from_seq: parse_seq(from)?, to_seq: None }),
+        ("range", [from, to]) => Ok(Statement::OplogRange { from_seq: parse_seq(from)?, to_seq: Some(parse_seq(to)?) }),
+        _ => Err(usage()),
+    }
+}
+
+// "-" 表示不限制
+fn parse_quota(token: &str) -> Result<Option<usize>, String> {
+    match token {
+        "-" => Ok(None),
+        t => t.parse().map(Some).map_err(|_| format!("invalid quota '{}'", t)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_statements() {
+        assert_eq!(parse_line("  # comment").unwrap(), None);
+        assert_eq!(parse_line("").unwrap(), None);
+        assert_eq!(
+            parse_line("put users u1 Bob Smith").unwrap(),
+            Some(Statement::Put { cf: "users".into(), key: "u1".into(), value: "Bob Smith".into() })
+        );
+        assert_eq!(
+            parse_line("del default tmp").unwrap(),
+            Some(Statement::Delete { cf: "default".into(), key: "tmp".into() })
+        );
+        assert_eq!(
+            parse_line("scan users a - 5").unwrap(),
+            Some(Statement::Scan { cf: "users".into(), start: "a".into(), end: None, limit: 5, keep_going: false })
+        );
+        assert_eq!(
+            parse_line("scan --continue users k").unwrap(),
+            Some(Statement::Scan { cf: "users".into(), start: "k".into(), end: None, limit: DEFAULT_LIMIT, keep_going: true })
+        );
+        assert_eq!(
+            parse_line("rename users u1 u2").unwrap(),
+            Some(Statement::Rename { cf: "users".into(), old_key: "u1".into(), new_key: "u2".into(), overwrite: false })
+        );
+        assert_eq!(
+            parse_line("copy users u1 u2 --overwrite").unwrap(),
+            Some(Statement::Copy { cf: "users".into(), src_key: "u1".into(), dst_key: "u2".into(), overwrite: true })
+        );
+        assert_eq!(
+            parse_line("expire sessions s1 60").unwrap(),
+            Some(Statement::Expire { cf: "sessions".into(), key: "s1".into(), seconds: 60 })
+        );
+        assert_eq!(parse_line("scan --all 20").unwrap(), Some(Statement::ScanAll { limit: 20 }));
+        assert_eq!(parse_line("scan --trash").unwrap(), Some(Statement::ScanTrash { cf: None, limit: DEFAULT_LIMIT }));
+        assert_eq!(
+            parse_line("scan --trash users 5").unwrap(),
+            Some(Statement::ScanTrash { cf: Some("users".into()), limit: 5 })
+        );
+        assert_eq!(parse_line("sample 10").unwrap(), Some(Statement::Sample { count: 10, cf: None }));
+        assert_eq!(
+            parse_line("sample 3 --cf users").unwrap(),
+            Some(Statement::Sample { count: 3, cf: Some("users".into()) })
+        );
+        assert!(parse_line("sample").is_err());
+        assert_eq!(
+            parse_line("restore users u1 --overwrite").unwrap(),
+            Some(Statement::Restore { cf: "users".into(), key: "u1".into(), overwrite: true })
+        );
+        assert_eq!(parse_line("backup --out data.tkv").unwrap(), Some(Statement::Backup { file: "data.tkv".into() }));
+        assert_eq!(
+            parse_line("restore --in data.tkv --force").unwrap(),
+            Some(Statement::RestoreArchive { file: "data.tkv".into(), force: true })
+        );
+        assert_eq!(parse_line("purge-trash --all").unwrap(), Some(Statement::PurgeTrash { all: true, dry_run: false }));
+        assert_eq!(
+            parse_line("purge-trash --dry-run --all").unwrap(),
+            Some(Statement::PurgeTrash { all: true, dry_run: true })
+        );
+        assert_eq!(
+            parse_line("drop-db logs --dry-run").unwrap(),
+            Some(Statement::DropDb { name: "logs".into(), dry_run: true })
+        );
+        assert_eq!(parse_line("info").unwrap(), Some(Statement::Info { cfs: false }));
+        assert_eq!(parse_line("info --cfs").unwrap(), Some(Statement::Info { cfs: true }));
+        assert_eq!(parse_line("server-info").unwrap(), Some(Statement::ServerInfo));
+        assert!(parse_line("server-info extra").is_err());
+        assert_eq!(parse_line("config get").unwrap(), Some(Statement::ConfigGet));
+        assert_eq!(
+            parse_line("config set max_scan_examined none").unwrap(),
+            Some(Statement::ConfigSet { key: "max_scan_examined".to_string(), value: "none".to_string() })
+        );
+        assert!(parse_line("config set max_scan_examined").is_err());
+        assert_eq!(parse_line("clients").unwrap(), Some(Statement::Clients));
+        assert_eq!(parse_line("errors 5").unwrap(), Some(Statement::Errors { count: 5 }));
+        assert_eq!(parse_line("kill 7").unwrap(), Some(Statement::Kill { id: 7 }));
+        assert_eq!(parse_line("audit verify").unwrap(), Some(Statement::AuditVerify));
+        assert_eq!(
+            parse_line("audit --cf users utf8-key max-value-len=1024").unwrap(),
+            Some(Statement::Audit { cf: Some("users".into()), checks: vec![CheckKind::Utf8Key, CheckKind::MaxValueLen(1024)] })
+        );
+        assert_eq!(
+            parse_line("fix quarantine empty-value max-key-len=64").unwrap(),
+            Some(Statement::Fix { policy: FixPolicy::Quarantine, cf: None, checks: vec![CheckKind::EmptyValue, CheckKind::MaxKeyLen(64)] })
+        );
+        assert_eq!(
+            parse_line("quota users 1000 -").unwrap(),
+            Some(Statement::Quota { cf: "users".into(), max_keys: Some(1000), max_bytes: None })
+        );
+        assert_eq!(parse_line("shutdown").unwrap(), Some(Statement::Shutdown { flush: true }));
+        assert_eq!(parse_line("shutdown --no-flush").unwrap(), Some(Statement::Shutdown { flush: false }));
+
+        let mut options = TransferOptions::new(Format::Csv);
+        options.delimiter = b';';
+        options.base64 = true;
+        assert_eq!(
+            parse_line("import --format csv --delimiter ; --base64 data.csv").unwrap(),
+            Some(Statement::Import { file: "data.csv".into(), options })
+        );
+        let mut options = TransferOptions::new(Format::RedisProto);
+        options.cf = "cache".into();
+        assert_eq!(
+            parse_line("import --format redis-proto --cf cache dump.txt").unwrap(),
+            Some(Statement::Import { file: "dump.txt".into(), options })
+        );
+        assert_eq!(
+            parse_line("export --format jsonl out.jsonl").unwrap(),
+            Some(Statement::Export { file: "out.jsonl".into(), options: TransferOptions::new(Format::Jsonl) })
+        );
+        assert_eq!(
+            parse_line("oplog tail data/oplog").unwrap(),
+            Some(Statement::OplogTail { file: "data/oplog".into(), count: DEFAULT_TAIL })
+        );
+        assert_eq!(parse_line("oplog tail -n 50 oplog").unwrap(), Some(Statement::OplogTail { file: "oplog".into(), count: 50 }));
+        assert_eq!(
+            parse_line("oplog grep --key u1 --cf users oplog").unwrap(),
+            Some(Statement::OplogGrep { file: "oplog".into(), key: "u1".into(), cf: Some("users".into()) })
+        );
+        assert_eq!(
+            parse_line("oplog grep oplog --key u1").unwrap(),
+            Some(Statement::OplogGrep { file: "oplog".into(), key: "u1".into(), cf: None })
+        );
+        assert_eq!(parse_line("oplog range 5").unwrap(), Some(Statement::OplogRange { from_seq: 5, to_seq: None }));
+        assert_eq!(parse_line("oplog range 5 9").unwrap(), Some(Statement::OplogRange { from_seq: 5, to_seq: Some(9) }));
+        assert!(parse_line("oplog tail oplog").unwrap().unwrap().is_local());
+        assert!(!parse_line("oplog range 1").unwrap().unwrap().is_local());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_line("pt users u1 Bob").is_err());
+        assert!(parse_line("put users u1").is_err());
+        assert!(parse_line("get users").is_err());
+        assert!(parse_line("scan users a b notanumber").is_err());
+        assert!(parse_line("rename users u1").is_err());
+        assert!(parse_line("copy users u1 u2 --force").is_err());
+        assert!(parse_line("info now").is_err());
+        assert!(parse_line("expire users u1 soon").is_err());
+        assert!(parse_line("ttl users").is_err());
+        assert!(parse_line("kill").is_err());
+        assert!(parse_line("kill abc").is_err());
+        assert!(parse_line("quota users 10").is_err());
+        assert!(parse_line("restore users").is_err());
+        assert!(parse_line("restore --in").is_err());
+        assert!(parse_line("backup data.tkv").is_err());
+        assert!(parse_line("purge-trash now").is_err());
+        assert!(parse_line("audit").is_err());
+        assert!(parse_line("audit --cf users").is_err());
+        assert!(parse_line("audit max-value-len=big").is_err());
+        assert!(parse_line("fix erase utf8-key").is_err());
+        assert!(parse_line("fix delete").is_err());
+        assert!(parse_line("purge-trash --all --all").is_err());
+        assert!(parse_line("drop-db").is_err());
+        assert!(parse_line("drop-db logs --force").is_err());
+        assert!(parse_line("quota users ten -").is_err());
+        assert!(parse_line("shutdown now").is_err());
+        assert!(parse_line("import data.csv").is_err());
+        assert!(parse_line("import --format xml data.xml").is_err());
+        assert!(parse_line("import --format csv a.csv b.csv").is_err());
+        assert!(parse_line("import --format csv --delimiter ab a.csv").is_err());
+        assert!(parse_line("export --format redis-proto out.txt").is_err());
+        assert!(parse_line("oplog").is_err());
+        assert!(parse_line("oplog tail").is_err());
+        assert!(parse_line("oplog tail -n ten oplog").is_err());
+        assert!(parse_line("oplog grep oplog").is_err());
+        assert!(parse_line("oplog grep --key u1").is_err());
+        assert!(parse_line("oplog grep --key u1 a b").is_err());
+        assert!(parse_line("oplog range first").is_err());
+        assert!(parse_line("oplog range 1 2 3").is_err());
+    }
+}
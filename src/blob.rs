@@ -0,0 +1,211 @@
+//! 冷值的磁盘层
+//!
+//! 开启 StorageOptions::spill_threshold 或 EvictionPolicy::Spill 后，值写入数据目录下
+//! blobs 目录中只追加的 blob 文件，内存中的条目只保存它的位置，读取时再从文件读回。
+//! blob 文件只是内存的延伸，段文件和快照仍然保存完整的值：打开存储时丢弃旧的 blob
+//! 文件，加载时按需重新写出，所以崩溃留下的 blob 文件不影响恢复。
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::storage::crc32;
+
+/// 数据目录下保存 blob 文件的子目录
+pub const BLOB_DIR: &str = "blobs";
+
+/// 当前 blob 文件达到该大小后，之后的值写入新文件
+const BLOB_FILE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// 值在 blob 文件中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueRef {
+    pub file: u32,
+    pub offset: u64,
+    pub len: u32,
+    pub crc: u32,
+}
+
+/// 磁盘层的统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpillStats {
+    /// 保存在 blob 文件中的值的个数和总字节数
+    pub values: usize,
+    pub disk_bytes: u64,
+    /// 现有的 blob 文件数和其中已不被引用、等待回收的字节数
+    pub files: usize,
+    pub dead_bytes: u64,
+    /// 因不再被引用而删除的 blob 文件数
+    pub reclaimed_files: u64,
+}
+
+#[derive(Debug, Default)]
+struct FileUsage {
+    total_bytes: u64,
+    live_bytes: u64,
+    live_values: usize,
+}
+
+#[derive(Debug, Default)]
+struct BlobState {
+    active: u32,
+    files: BTreeMap<u32, FileUsage>,
+    reclaimed_files: u64,
+}
+
+/// 数据目录下的 blob 文件
+#[derive(Debug)]
+pub(crate) struct BlobStore {
+    dir: PathBuf,
+    state: Mutex<BlobState>,
+}
+
+/// 写入 blob 文件的值；最后一个引用释放时把它占用的空间记为可回收，
+/// 不再是当前写入目标的文件在所有值都释放后删除
+#[derive(Debug)]
+pub(crate) struct BlobValue {
+    store: Arc<BlobStore>,
+    at: ValueRef,
+}
+
+impl BlobValue {
+    pub(crate) fn at(&self) -> ValueRef {
+        self.at
+    }
+
+    pub(crate) fn read(&self) -> Result<Vec<u8>, String> {
+        self.store.read(&self.at)
+    }
+}
+
+impl Drop for BlobValue {
+    fn drop(&mut self) {
+        self.store.release(&self.at);
+    }
+}
+
+impl BlobStore {
+    /// 打开数据目录 data_dir 的磁盘层，删除上次运行留下的 blob 文件
+    pub(crate) fn open(data_dir: &Path) -> Result<Arc<Self>, String> {
+        let dir = data_dir.join(BLOB_DIR);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|e| format!("Failed to clear {}: {}", dir.display(), e))?;
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let mut state = BlobState::default();
+        state.files.insert(0, FileUsage::default());
+        Ok(Arc::new(BlobStore { dir, state: Mutex::new(state) }))
+    }
+
+    fn file_path(&self, file: u32) -> PathBuf {
+        self.dir.join(format!("{:08}.blob", file))
+    }
+
+    /// 把值追加到当前 blob 文件
+    pub(crate) fn write(self: &Arc<Self>, value: &[u8]) -> Result<BlobValue, String> {
+        let len = u32::try_from(value.len()).map_err(|_| format!("Value of {} bytes is too large to spill", value.len()))?;
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        let active = state.active;
+        if state.files[&active].total_bytes >= BLOB_FILE_MAX_BYTES {
+            self.rotate(&mut state);
+        }
+        let file = state.active;
+        let path = self.file_path(file);
+        let mut handle = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        if let Err(e) = handle.write_all(value) {
+            // 写了一半的文件末尾长度未知，之后的值写入新文件
+            self.rotate(&mut state);
+            return Err(format!("Failed to write {}: {}", path.display(), e));
+        }
+
+        let usage = state.files.get_mut(&file).expect("active file is tracked");
+        let at = ValueRef { file, offset: usage.total_bytes, len, crc: crc32(value) };
+        usage.total_bytes += len as u64;
+        usage.live_bytes += len as u64;
+        usage.live_values += 1;
+        Ok(BlobValue { store: Arc::clone(self), at })
+    }
+
+    /// 读回值并校验 CRC32
+    pub(crate) fn read(&self, at: &ValueRef) -> Result<Vec<u8>, String> {
+        let path = self.file_path(at.file);
+        let mut value = vec![0; at.len as usize];
+        let mut file = fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        file.seek(SeekFrom::Start(at.offset))
+            .and_then(|_| file.read_exact(&mut value))
+            .map_err(|e| format!("Failed to read {} at {}: {}", path.display(), at.offset, e))?;
+        if crc32(&value) != at.crc {
+            return Err(format!("Corrupt blob in {} at {}", path.display(), at.offset));
+        }
+        Ok(value)
+    }
+
+    // 之后的值写入新文件，旧文件的值全部释放后即可删除
+    fn rotate(&self, state: &mut BlobState) {
+        let old = state.active;
+        state.active += 1;
+        state.files.insert(state.active, FileUsage::default());
+        self.remove_if_unused(state, old);
+    }
+
+    fn release(&self, at: &ValueRef) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if let Some(usage) = state.files.get_mut(&at.file) {
+            usage.live_bytes -= at.len as u64;
+            usage.live_values -= 1;
+        }
+        self.remove_if_unused(&mut state, at.file);
+    }
+
+    fn remove_if_unused(&self, state: &mut BlobState, file: u32) {
+        if file == state.active || state.files.get(&file).is_none_or(|usage| usage.live_values > 0) {
+            return;
+        }
+        state.files.remove(&file);
+        // 删除失败的文件在下次打开时清理
+        let _ = fs::remove_file(self.file_path(file));
+        state.reclaimed_files += 1;
+    }
+
+    /// 至少一半空间已不被引用的文件，整理时把其中的值重写到新文件；
+    /// 当前文件也在其中时先切换到新文件
+    pub(crate) fn sparse_files(&self) -> Vec<u32> {
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let sparse: Vec<u32> = state
+            .files
+            .iter()
+            .filter(|(_, usage)| usage.total_bytes > 0 && usage.live_bytes * 2 <= usage.total_bytes)
+            .map(|(&file, _)| file)
+            .collect();
+        if sparse.contains(&state.active) {
+            self.rotate(&mut state);
+        }
+        sparse
+    }
+
+    pub(crate) fn stats(&self) -> SpillStats {
+        let Ok(state) = self.state.lock() else {
+            return SpillStats::default();
+        };
+        let files = state.files.values().filter(|usage| usage.total_bytes > 0).count();
+        let mut stats = SpillStats { files, reclaimed_files: state.reclaimed_files, ..SpillStats::default() };
+        for usage in state.files.values() {
+            stats.values += usage.live_values;
+            stats.disk_bytes += usage.live_bytes;
+            stats.dead_bytes += usage.total_bytes - usage.live_bytes;
+        }
+        stats
+    }
+}
@@ -1,41 +1,568 @@
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use serde_json::json;
+use crate::storage::{CfKeys, CfOptions, CompactResult, CompactionInfo, CompressionStats, DeletionReport, FlushStats, KeySample, KvPairs, LoadStatus, LockWaitStats, MaintenanceStatus, PersistenceStatus, TrashEntry};
+use crate::api::EncodedKey;
+use crate::audit::AuditReport;
+use crate::histogram::LatencySummary;
+use crate::hotkeys::HotKey;
+use crate::clients::ClientInfo;
+use crate::errorlog::ErrorEvent;
+use crate::idempotency::IdempotencyStats;
+use crate::pubsub::Message;
+use crate::acl::EffectivePermissions;
+use crate::keepalive;
+use crate::oplog::OplogEntry;
+use crate::blob::SpillStats;
+use crate::rotation::LogFileStats;
+use crate::runtime_config::ConfigEntry;
+#[cfg(feature = "tls")]
+use crate::tls;
+use crate::protocol::{self, BatchMode, Bytes, CfInfo, CheckKind, Command, DbInfo, DEFAULT_CF, FixPolicy, FixReport, Modify, Response, ScanBound, ServerInfo, Transport, ValueFilter, Version, Violation, Warning};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 调用方可能需要区分处理的客户端错误，通过 `downcast_ref::<KvError>()` 取得
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KvError {
+    /// 请求没有在超时时间内完成，连接已被标记为损坏
+    Timeout,
+    /// 连接在之前的请求中损坏且无法重新建立
+    Broken,
+    /// 服务器在响应前关闭了连接
+    Closed,
+    /// 键的值无法解码为请求的类型
+    Deserialize { key: String, message: String },
+    /// 服务端返回的错误；code 为消息开头的错误码（如 UnknownCf），没有错误码时为 None
+    Server { code: Option<String>, message: String },
+    /// 响应的类型与命令不匹配
+    Protocol(String),
+}
+
+impl KvError {
+    /// 解析服务端的错误消息，"UnknownCf: missing" 的错误码为 UnknownCf
+    pub fn server(error: String) -> Self {
+        match protocol::error_code(&error) {
+            Some(code) => KvError::Server { code: Some(code.to_string()), message: error[code.len() + 2..].to_string() },
+            None => KvError::Server { code: None, message: error },
+        }
+    }
+
+    /// 服务端错误的错误码
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            KvError::Server { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KvError::Timeout => write!(f, "Request timed out"),
+            KvError::Broken => write!(f, "Connection is broken"),
+            KvError::Closed => write!(f, "Connection closed by server"),
+            KvError::Deserialize { key, message } => write!(f, "Failed to decode value of key {}: {}", key, message),
+            KvError::Server { code: Some(code), message } => write!(f, "{}: {}", code, message),
+            KvError::Server { code: None, message } => write!(f, "{}", message),
+            KvError::Protocol(message) => write!(f, "Unexpected response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for KvError {}
+
+/// 多地址客户端的故障转移策略
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 是否在故障转移后重试写命令，默认不重试；服务器支持时写命令带上幂等键，
+    /// 重试使用同一个键，已在服务器上生效的写命令不会再执行一次
+    pub retry_writes: bool,
+    /// 失败的地址在这段时间内不再被优先选择
+    pub cooldown: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retry_writes: false,
+            cooldown: Duration::from_secs(5),
+        }
+    }
+}
+
+/// scan_json 遇到无法解码的值时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeErrors {
+    /// 返回第一个解码错误
+    Fail,
+    /// 跳过该条目，错误收集到 JsonScan::errors
+    Skip,
+}
+
+/// scan_json 的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonScan<T> {
+    pub entries: Vec<(String, T)>,
+    /// DecodeErrors::Skip 时被跳过的条目，均为 KvError::Deserialize
+    pub errors: Vec<KvError>,
+}
+
+/// scan_all 的一页结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfScanPage {
+    /// (列族, 键, 值)，按 (列族, 键) 排序
+    pub entries: Vec<(String, String, String)>,
+    /// 下一页的起点 (列族, 键)，没有更多条目时为 None
+    pub next: Option<(String, String)>,
+    /// 这一页因服务器的响应大小上限在 limit 之前结束，从 next 继续即可读到其余条目
+    pub truncated: bool,
+}
+
+/// ScanBuilder::run_page 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanResult {
+    pub items: KvPairs,
+    /// false 表示结果因服务器的响应大小上限被截断，范围内还有更多条目
+    pub complete: bool,
+    /// 截断时第一个未返回的键，用 from_inclusive(cursor) 继续扫描
+    pub cursor: Option<Vec<u8>>,
+    /// 因服务器的单次检查条目上限（max_scan_examined）而截断，不是因为响应大小
+    pub examined_limit_reached: bool,
+}
+
+/// write_batch 的结果
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutcome {
+    /// 所有操作都已应用
+    Applied,
+    /// Atomic 模式下存在无效操作，没有做任何修改；(下标, 原因)
+    Rejected(Vec<(usize, String)>),
+    /// BestEffort 模式下每个操作的结果，有效的操作已应用
+    Partial(Vec<Result<(), String>>),
+}
+
+impl BatchOutcome {
+    /// 所有操作都已应用
+    pub fn is_applied(&self) -> bool {
+        match self {
+            BatchOutcome::Applied => true,
+            BatchOutcome::Rejected(_) => false,
+            BatchOutcome::Partial(results) => results.iter().all(Result::is_ok),
+        }
+    }
+}
+
+/// cf_info 每次请求的列族数
+const CF_PAGE_SIZE: usize = 1000;
+
+/// audit 每次请求的违规条目数
+const AUDIT_PAGE_SIZE: usize = 1000;
+
+/// KvClient::iter_cfs 返回的迭代器，当前页取完后按游标请求下一页
+pub struct CfInfoIter<'a> {
+    client: &'a mut KvClient,
+    page_size: usize,
+    page: std::vec::IntoIter<CfInfo>,
+    // 下一页的 start_after
+    next: Option<String>,
+    // 已经取到最后一页或请求失败
+    done: bool,
+}
+
+impl Iterator for CfInfoIter<'_> {
+    type Item = Result<CfInfo, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(cf) = self.page.next() {
+                return Some(Ok(cf));
+            }
+            if self.done {
+                return None;
+            }
+            match self.client.list_cfs(self.next.as_deref(), self.page_size) {
+                Ok((cfs, next)) => {
+                    self.done = next.is_none();
+                    self.next = next;
+                    self.page = cfs.into_iter();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// KvClient::subscribe 返回的消息流，连接断开或收到其他响应时结束
+pub struct MessageStream {
+    client: KvClient,
+}
+
+impl Iterator for MessageStream {
+    type Item = Message;
+
+    fn next(&mut self) -> Option<Message> {
+        match self.client.read_frame() {
+            Ok(Response::Message(message)) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+/// iter_cf 遇到连接错误时，同一页最多重试的次数
+const SCAN_RETRIES: usize = 3;
+
+/// KvClient::iter_cf 返回的迭代器，按键的升序逐页扫描整个列族
+///
+/// 连接断开时由下一次请求重新连接，从最后返回的键之后继续；即使重试的页与已返回的
+/// 条目重叠，也不会重复或乱序返回
+pub struct CfScanIter<'a> {
+    client: &'a mut KvClient,
+    cf: String,
+    page_size: usize,
+    page: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+    // 最后返回的键，下一页从它之后开始
+    last: Option<Vec<u8>>,
+    // 已经取到最后一页或请求失败
+    done: bool,
+}
+
+impl CfScanIter<'_> {
+    /// 取出剩余的全部条目
+    pub fn collect_to_map(self) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, Box<dyn std::error::Error>> {
+        self.collect()
+    }
+
+    fn fetch(&mut self) -> Result<KvPairs, Box<dyn std::error::Error>> {
+        let mut retries = 0;
+        loop {
+            let mut scan = self.client.scan_builder(&self.cf).limit(self.page_size);
+            if let Some(last) = &self.last {
+                scan = scan.from_exclusive(last);
+            }
+            match scan.run_bytes() {
+                Ok(page) => return Ok(page),
+                Err(e) if retries < SCAN_RETRIES && is_transient(e.as_ref()) => retries += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Iterator for CfScanIter<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for (key, value) in self.page.by_ref() {
+                // 只返回比上一个键大的条目，保证严格升序、没有重复
+                if self.last.as_ref().is_none_or(|last| key > *last) {
+                    self.last = Some(key.clone());
+                    return Some(Ok((key, value)));
+                }
+            }
+            if self.done {
+                return None;
+            }
+            match self.fetch() {
+                Ok(page) => {
+                    self.done = page.len() < self.page_size;
+                    self.page = page.into_iter();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+// 连接层面的错误，重新连接后可以重试；服务端错误和协议错误重试也不会成功
+fn is_transient(e: &(dyn std::error::Error + 'static)) -> bool {
+    match e.downcast_ref::<KvError>() {
+        Some(kv) => matches!(kv, KvError::Timeout | KvError::Closed),
+        None => e.is::<io::Error>(),
+    }
+}
+
+/// 服务器地址及其健康状态
+struct Endpoint {
+    addr: String,
+    unhealthy_until: Option<Instant>,
+    // 服务器不认识 Hello，之后连接时不再握手
+    legacy: bool,
+}
+
+impl Endpoint {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until.is_none_or(|until| until <= now)
+    }
+}
 
 /// KV 数据库客户端
 pub struct KvClient {
-    stream: TcpStream,
+    stream: Box<dyn Transport>,
+    // 已读取但尚未解析的响应字节
+    pending: Vec<u8>,
+    // 按优先级排列的服务器地址，from_stream 创建的客户端为空
+    endpoints: Vec<Endpoint>,
+    active: usize,
+    policy: RetryPolicy,
+    // 切换到其他服务器后需要重放的会话状态
+    db: Option<String>,
+    default_cf: Option<String>,
+    admin_token: Option<String>,
+    // Auth 使用的 (主体名, 令牌)，重连后重新认证
+    credentials: Option<(String, String)>,
+    // 当前 TCP 连接的句柄，用于设置超时；from_stream 创建的客户端为 None
+    tcp: Option<TcpStream>,
+    timeout: Option<Duration>,
+    // TCP keepalive 的探测间隔，之后重新建立的连接也会开启
+    keepalive: Option<Duration>,
+    // 请求中途出错后流中可能残留半个响应，不能再复用
+    broken: bool,
+    // 与当前服务器协商的特性
+    features: Vec<String>,
+    // 握手时请求 envelope，见 enable_envelope
+    envelope: bool,
+    // 握手时请求 msgpack，见 enable_msgpack
+    msgpack: bool,
+    // 最近一帧响应中服务器报告的执行时间
+    last_server_timing: Option<Duration>,
+    // 尚未被 take_warnings 取走的警告
+    warnings: Vec<Warning>,
+    // 幂等键的作用域，客户端创建时随机生成，重连后不变
+    idempotency_token: u64,
+    last_idempotency_key: u64,
+    // connect_tls 的配置和期望的服务器名，重新建立的连接也使用 TLS
+    #[cfg(feature = "tls")]
+    tls: Option<(Arc<rustls::ClientConfig>, rustls::pki_types::ServerName<'static>)>,
 }
 
 impl KvClient {
     /// 连接到 KV 服务器
     pub fn connect(addr: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let stream = TcpStream::connect(addr)?;
-        Ok(KvClient { stream })
+        Self::connect_multi_with_policy(&[addr], RetryPolicy::default())
     }
 
-    /// Get 操作：获取单个键值
-    pub fn get(&mut self, cf: &str, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        let cmd = json!({
-                "type": "Get",
-                "cf": cf,
-                "key": key.as_bytes()
-        });
+    /// 连接到 KV 服务器，连接和之后每次读写都不超过 timeout
+    pub fn connect_with_timeout(addr: &str, timeout: Duration) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open(&[addr], RetryPolicy::default(), Some(timeout))
+    }
 
-        self.send_command(&cmd)?;
-        let response = self.read_response()?;
+    /// 按顺序连接多个服务器中第一个可用的，请求遇到连接错误时切换到下一个
+    pub fn connect_multi(addrs: &[&str]) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::connect_multi_with_policy(addrs, RetryPolicy::default())
+    }
 
-        match response.get("Value") {
-            Some(serde_json::Value::Array(arr)) if arr.is_empty() => Ok(None),
-            Some(serde_json::Value::Null) => Ok(None),
-            Some(value) => {
-                let bytes: Vec<u8> = serde_json::from_value(value.clone())?;
-                Ok(Some(String::from_utf8(bytes)?))
-            }
-            None => Ok(None),
+    pub fn connect_multi_with_policy(addrs: &[&str], policy: RetryPolicy) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open(addrs, policy, None)
+    }
+
+    /// 通过 TLS 连接到 KV 服务器，只信任 ca_cert_path（PEM 文件）中的证书，并验证服务器证书
+    /// 属于 server_name（域名或 IP 地址）；之后重新建立的连接也使用 TLS。需要 tls 特性
+    #[cfg(feature = "tls")]
+    pub fn connect_tls(
+        addr: &str,
+        ca_cert_path: impl AsRef<std::path::Path>,
+        server_name: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = tls::client_config(ca_cert_path.as_ref())?;
+        let name = tls::server_name(server_name)?;
+        let mut client = Self::detached(&[addr], RetryPolicy::default(), None)?;
+        client.tls = Some((config, name));
+        client.reconnect()?;
+        Ok(client)
+    }
+
+    fn open(
+        addrs: &[&str],
+        policy: RetryPolicy,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut client = Self::detached(addrs, policy, timeout)?;
+        client.reconnect()?;
+        Ok(client)
+    }
+
+    /// 还没有建立连接的客户端
+    fn detached(
+        addrs: &[&str],
+        policy: RetryPolicy,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if addrs.is_empty() {
+            return Err("No server address given".into());
+        }
+
+        Ok(KvClient {
+            stream: Box::new(std::io::empty()),
+            pending: Vec::new(),
+            endpoints: addrs
+                .iter()
+                .map(|addr| Endpoint { addr: addr.to_string(), unhealthy_until: None, legacy: false })
+                .collect(),
+            active: 0,
+            policy,
+            db: None,
+            default_cf: None,
+            admin_token: None,
+            credentials: None,
+            tcp: None,
+            timeout,
+            keepalive: None,
+            broken: false,
+            features: Vec::new(),
+            envelope: false,
+            msgpack: false,
+            last_server_timing: None,
+            warnings: Vec::new(),
+            idempotency_token: RandomState::new().build_hasher().finish(),
+            last_idempotency_key: 0,
+            #[cfg(feature = "tls")]
+            tls: None,
+        })
+    }
+
+    /// 当前使用的服务器地址
+    pub fn current_endpoint(&self) -> Option<&str> {
+        self.endpoints.get(self.active).map(|e| e.addr.as_str())
+    }
+
+    /// 与当前服务器协商的协议特性
+    /// 旧服务器不支持握手，from_stream 创建的客户端不握手，两种情况下都为空
+    pub fn negotiated_features(&self) -> &[String] {
+        &self.features
+    }
+
+    /// 请求服务器为每个响应附带执行时间和警告（envelope 特性），之后重新建立的连接也会请求；
+    /// 立即在当前连接上重新握手，服务器不支持时返回错误
+    pub fn enable_envelope(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.envelope = true;
+        if self.broken {
+            return self.reconnect();
+        }
+        self.handshake()?;
+        if !self.features.iter().any(|f| f == "envelope") {
+            return Err("server does not support the envelope feature".into());
+        }
+        Ok(())
+    }
+
+    /// 请求服务器改用 MessagePack 编码（msgpack 特性），之后重新建立的连接也会请求；
+    /// 立即在当前连接上重新握手，服务器不支持时返回错误
+    pub fn enable_msgpack(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.msgpack = true;
+        if self.broken {
+            return self.reconnect();
+        }
+        self.handshake()?;
+        if !self.features.iter().any(|f| f == "msgpack") {
+            return Err("server does not support the msgpack feature".into());
+        }
+        Ok(())
+    }
+
+    /// 当前连接上请求和响应使用的编码
+    pub fn codec(&self) -> protocol::Codec {
+        protocol::Codec::negotiated(&self.features)
+    }
+
+    /// 最近一个响应中服务器执行命令用的时间；没有协商 envelope 时为 None
+    pub fn last_server_timing(&self) -> Option<Duration> {
+        self.last_server_timing
+    }
+
+    /// 取走之前的响应附带的警告
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// 连接到 KV 服务器并选择数据库
+    pub fn connect_db(addr: &str, db: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut client = Self::connect(addr)?;
+        client.use_db(db)?;
+        Ok(client)
+    }
+
+    /// 使用已建立的传输层（如 TLS 流）创建客户端
+    pub fn from_stream<S: Transport + 'static>(stream: S) -> Self {
+        KvClient {
+            stream: Box::new(stream),
+            pending: Vec::new(),
+            endpoints: Vec::new(),
+            active: 0,
+            policy: RetryPolicy::default(),
+            db: None,
+            default_cf: None,
+            admin_token: None,
+            credentials: None,
+            tcp: None,
+            timeout: None,
+            keepalive: None,
+            broken: false,
+            features: Vec::new(),
+            envelope: false,
+            msgpack: false,
+            last_server_timing: None,
+            warnings: Vec::new(),
+            idempotency_token: RandomState::new().build_hasher().finish(),
+            last_idempotency_key: 0,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
+    /// 设置之后每次读写的超时；超时的请求返回 KvError::Timeout
+    /// 只对 TCP 连接有效，之后重新建立的连接也会使用该超时；协商了 envelope 时，超时同时作为
+    /// 请求的截止时间发给服务器，服务器不再为已经超时的请求继续工作
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(tcp) = &self.tcp else {
+            return Err("Timeouts require a TCP connection".into());
+        };
+        tcp.set_read_timeout(Some(timeout))?;
+        tcp.set_write_timeout(Some(timeout))?;
+        self.timeout = Some(timeout);
+        Ok(())
+    }
+
+    /// 开启 TCP keepalive，连接空闲 interval 后开始探测，服务器失联时阻塞的读（例如订阅的消息流）
+    /// 会返回错误；请求的读写由 set_timeout 限制。只对 TCP 连接有效，之后重新建立的连接也会开启
+    pub fn set_keepalive(&mut self, interval: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(tcp) = &self.tcp else {
+            return Err("Keepalive requires a TCP connection".into());
+        };
+        keepalive::set_keepalive(tcp, interval)?;
+        self.keepalive = Some(interval);
+        Ok(())
+    }
+
+    /// Get 操作：获取单个键值
+    pub fn get(&mut self, cf: &str, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let cmd = Command::Get {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+        };
+
+        self.request_value(&cmd)
+    }
+
     /// Put 操作：写入键值对
     pub fn put(
         &mut self,
@@ -43,29 +570,155 @@ impl KvClient {
         key: &str,
         value: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let cmd = json!({
-                "type":"Put",
-                "cf": cf,
-                "key": key.as_bytes(),
-                "value": value.as_bytes()
-        });
+        let cmd = Command::Put {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+        };
 
-        self.send_command(&cmd)?;
-        self.read_response()?;
-        Ok(())
+        self.request_ok(&cmd)
     }
 
     /// Delete 操作：删除键
     pub fn delete(&mut self, cf: &str, key: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let cmd = json!({
-                "type": "Delete",
-                "cf": cf,
-                "key": key.as_bytes()
-        });
+        let cmd = Command::Delete {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+        };
 
-        self.send_command(&cmd)?;
-        self.read_response()?;
-        Ok(())
+        self.request_ok(&cmd)
+    }
+
+    /// 读取默认列族中的键：发送空列族名，由服务端替换为 UseCf 设置的列族或 DEFAULT_CF
+    pub fn get_default(&mut self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.get("", key)
+    }
+
+    /// 写入默认列族，见 get_default
+    pub fn put_default(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.put("", key, value)
+    }
+
+    /// 删除默认列族中的键，见 get_default
+    pub fn delete_default(&mut self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.delete("", key)
+    }
+
+    /// 原子地取出并删除键的值（如从工作队列中弹出）
+    pub fn get_del(&mut self, cf: &str, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let cmd = Command::GetDel {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+        };
+        self.request_value(&cmd)
+    }
+
+    /// 设置键在 ttl 后过期；键不存在时返回 KeyNotFound 错误
+    pub fn expire(&mut self, cf: &str, key: &str, ttl: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        let cmd = Command::Expire { cf: cf.to_string(), key: key.as_bytes().to_vec(), ttl_ms: ttl.as_millis() as u64 };
+        self.request_ok(&cmd)
+    }
+
+    /// 键的剩余生存时间，没有设置过期时间时为 None；键不存在时返回 KeyNotFound 错误
+    pub fn ttl(&mut self, cf: &str, key: &str) -> Result<Option<Duration>, Box<dyn std::error::Error>> {
+        match self.request(&Command::Ttl { cf: cf.to_string(), key: key.as_bytes().to_vec() })? {
+            Response::Ttl(ttl_ms) => Ok(ttl_ms.map(Duration::from_millis)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务器对 (cf, key) 编码键计算的稳定哈希，默认数据库中与 ShardedClient 的计算一致
+    pub fn key_hash(&mut self, cf: &str, key: &[u8]) -> Result<u64, Box<dyn std::error::Error>> {
+        match self.request(&Command::KeyHash { cf: cf.to_string(), key: key.to_vec() })? {
+            Response::KeyHash(hash) => Ok(hash),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 原子地写入新值并返回旧值
+    pub fn get_set(&mut self, cf: &str, key: &str, value: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let cmd = Command::GetSet {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+        };
+        self.request_value(&cmd)
+    }
+
+    /// 原子地重命名键；overwrite 为 false 时目标键已存在会返回 KeyExists 错误
+    pub fn rename(&mut self, cf: &str, old_key: &str, new_key: &str, overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let cmd = Command::Rename {
+            cf: cf.to_string(),
+            old_key: old_key.as_bytes().to_vec(),
+            new_key: new_key.as_bytes().to_vec(),
+            overwrite,
+        };
+        self.request_ok(&cmd)
+    }
+
+    /// 原子地复制键的值，错误情况与 rename 相同
+    pub fn copy(&mut self, cf: &str, src_key: &str, dst_key: &str, overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let cmd = Command::Copy {
+            cf: cf.to_string(),
+            src_key: src_key.as_bytes().to_vec(),
+            dst_key: dst_key.as_bytes().to_vec(),
+            overwrite,
+        };
+        self.request_ok(&cmd)
+    }
+
+    /// 按值查找键，按键顺序最多返回 limit 个；列族需要开启 index_values
+    pub fn find_by_value(&mut self, cf: &str, value: &str, limit: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let cmd = Command::FindByValue {
+            cf: cf.to_string(),
+            value: value.as_bytes().to_vec(),
+            limit,
+        };
+        match self.request(&cmd)? {
+            Response::Keys(keys) => keys
+                .into_iter()
+                .map(|Bytes(k)| Ok(String::from_utf8(k)?))
+                .collect(),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 尝试获取租约为 ttl 的锁，成功时返回令牌，锁被他人持有时返回 None
+    pub fn acquire_lock(&mut self, name: &str, ttl: Duration) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        let cmd = Command::LockAcquire { name: name.to_string(), ttl_ms: ttl.as_millis() as u64 };
+        match self.request(&cmd)? {
+            Response::LockToken(token) => Ok(token),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 释放锁，令牌不匹配时返回 LockNotHeld 错误
+    pub fn release_lock(&mut self, name: &str, token: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::LockRelease { name: name.to_string(), token })
+    }
+
+    /// 把锁的租约延长到从现在起 ttl，锁已过期或令牌不匹配时返回 LockNotHeld 错误
+    pub fn renew_lock(&mut self, name: &str, token: u64, ttl: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::LockRenew { name: name.to_string(), token, ttl_ms: ttl.as_millis() as u64 })
+    }
+
+    /// 批量写入：一组 Put/Delete 在服务端原子地应用，有无效操作时返回 BatchOutcome::Rejected
+    pub fn write_batch(&mut self, ops: Vec<Modify>) -> Result<BatchOutcome, Box<dyn std::error::Error>> {
+        self.write_batch_with_mode(ops, BatchMode::Atomic)
+    }
+
+    /// 按指定模式批量写入；BestEffort 跳过无效操作，返回每个操作的结果
+    pub fn write_batch_with_mode(
+        &mut self,
+        ops: Vec<Modify>,
+        mode: BatchMode,
+    ) -> Result<BatchOutcome, Box<dyn std::error::Error>> {
+        match self.request(&Command::Batch { ops, mode })? {
+            Response::Ok => Ok(BatchOutcome::Applied),
+            Response::BatchError(errors) => Ok(BatchOutcome::Rejected(errors)),
+            Response::BatchResults(results) => Ok(BatchOutcome::Partial(results)),
+            other => Err(unexpected(other)),
+        }
     }
 
     /// Scan 操作：范围扫描
@@ -76,72 +729,1305 @@ impl KvClient {
         end_key: Option<&str>,
         limit: usize,
     ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
-        let cmd = json!({
-                "type": "Scan",
-                "cf": cf,
-                "start_key": start_key.as_bytes(),
-                "end_key": end_key.map(|k| k.as_bytes()),
-                "limit": limit
-        });
+        self.scan_filtered(cf, start_key, end_key, limit, None)
+    }
+
+    /// 带服务端值过滤的范围扫描，limit 按匹配的条目计数
+    pub fn scan_filtered(
+        &mut self,
+        cf: &str,
+        start_key: &str,
+        end_key: Option<&str>,
+        limit: usize,
+        filter: Option<ValueFilter>,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let cmd = Command::Scan {
+            cf: cf.to_string(),
+            start_key: start_key.as_bytes().to_vec(),
+            end_key: end_key.map(|k| k.as_bytes().to_vec()),
+            limit,
+            filter,
+            start_bound: None,
+            end_bound: None,
+        };
+
+        Ok(self
+            .scan_to_end(cmd)?
+            .into_iter()
+            .map(|(k, v)| (String::from_utf8_lossy(&k).to_string(), String::from_utf8_lossy(&v).to_string()))
+            .collect())
+    }
+
+    /// 按原始字节读取值，不要求值是 UTF-8；键不存在时为 None，空值为 Some(vec![])
+    pub fn get_bytes(&mut self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let cmd = Command::Get { cf: cf.to_string(), key: key.to_vec() };
+        self.request_bytes(&cmd)
+    }
+
+    /// 按原始字节写入值
+    pub fn put_bytes(&mut self, cf: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::Put { cf: cf.to_string(), key: key.to_vec(), value: value.to_vec() })
+    }
+
+    /// 按原始字节扫描 [start_key, end_key) 范围，最多返回 limit 条
+    pub fn scan_bytes(
+        &mut self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<KvPairs, Box<dyn std::error::Error>> {
+        let cmd = Command::Scan {
+            cf: cf.to_string(),
+            start_key: start_key.to_vec(),
+            end_key: end_key.map(<[u8]>::to_vec),
+            limit,
+            filter: None,
+            start_bound: None,
+            end_bound: None,
+        };
+        self.scan_to_end(cmd)
+    }
+
+    /// 发送一次 Scan，返回这一页和截断时的续扫位置
+    fn scan_page(&mut self, cmd: &Command) -> Result<ScanResult, Box<dyn std::error::Error>> {
+        let pairs = |values: Vec<(Bytes, Bytes)>| values.into_iter().map(|(Bytes(k), Bytes(v))| (k, v)).collect();
+        match self.request(cmd)? {
+            Response::Values(values) => Ok(ScanResult { items: pairs(values), complete: true, cursor: None, examined_limit_reached: false }),
+            Response::TruncatedValues { values, next: Bytes(next), examined_limit_reached } => {
+                Ok(ScanResult { items: pairs(values), complete: false, cursor: Some(next), examined_limit_reached })
+            }
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 发送一次 Scan，每收到一帧就把其中的条目交给 on_chunk，返回截断时的续扫位置；
+    /// 已交出的条目无法收回，所以失败时不重试
+    fn scan_chunks(&mut self, cmd: &Command, on_chunk: &mut dyn FnMut(KvPairs)) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        if self.broken {
+            if self.endpoints.is_empty() {
+                return Err(KvError::Broken.into());
+            }
+            self.reconnect()?;
+        }
+        self.send_command(cmd).map_err(|e| self.transport_error(e))?;
 
-        self.send_command(&cmd)?;
-        let response = self.read_response()?;
+        let pairs = |values: Vec<(Bytes, Bytes)>| values.into_iter().map(|(Bytes(k), Bytes(v))| (k, v)).collect();
+        loop {
+            let (part, more) = match self.read_frame().map_err(|e| self.transport_error(e))? {
+                Response::Chunk { part, more } => (*part, more),
+                frame => (frame, false),
+            };
+            let cursor = match part {
+                Response::Values(values) => {
+                    on_chunk(pairs(values));
+                    None
+                }
+                Response::TruncatedValues { values, next: Bytes(next), .. } => {
+                    on_chunk(pairs(values));
+                    Some(next)
+                }
+                Response::Error(e) => return Err(KvError::server(e).into()),
+                other => {
+                    // 剩余的帧无法再与请求对应
+                    self.broken = true;
+                    return Err(unexpected(other));
+                }
+            };
+            if !more {
+                return Ok(cursor);
+            }
+        }
+    }
 
-        match response.get("Values") {
-            Some(values) => {
-                let items: Vec<Vec<Vec<u8>>> = serde_json::from_value(values.clone())?;
-                let result = items
-                    .into_iter()
-                    .map(|item| {
-                        (
-                            String::from_utf8_lossy(&item[0]).to_string(),
-                            String::from_utf8_lossy(&item[1]).to_string(),
-                        )
-                    })
-                    .collect();
-                Ok(result)
+    /// 按服务器返回的续扫位置继续请求，直到读完范围或凑够 limit 条；
+    /// stop_at_examined_limit 为 true 时在服务器的检查条目上限处停下，返回续扫位置
+    fn scan_until(&mut self, mut cmd: Command, stop_at_examined_limit: bool) -> Result<ScanResult, Box<dyn std::error::Error>> {
+        let mut items = Vec::new();
+        loop {
+            let page = self.scan_page(&cmd)?;
+            let (Some(cursor), Command::Scan { limit, start_bound, .. }) = (page.cursor.clone(), &mut cmd) else {
+                items.extend(page.items);
+                return Ok(ScanResult { items, ..page });
+            };
+            *limit = limit.saturating_sub(page.items.len());
+            items.extend(page.items);
+            if stop_at_examined_limit && page.examined_limit_reached {
+                return Ok(ScanResult { items, complete: false, cursor: Some(cursor), examined_limit_reached: true });
             }
-            None => Ok(Vec::new()),
+            *start_bound = Some(ScanBound::Included(cursor));
         }
     }
 
-    /// 获取服务器信息
-    pub fn info(&mut self) -> Result<(usize, Vec<String>), Box<dyn std::error::Error>> {
-        let cmd = json!({
-            "type": "Info" 
-        });
-        self.send_command(&cmd)?;
-        
-        let response = self.read_response()?;
-        if let Some(info) = response.get("Info") {
-            let total_keys: usize = serde_json::from_value(info["total_keys"].clone())?;
-            let cfs: Vec<String> = serde_json::from_value(info["column_families"].clone())?;
-            Ok((total_keys, cfs))
-        } else {
-            Err("Invalid response".into())
-        }
-    }
-
-    /// 刷盘持久化
-    pub fn flush(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let cmd = json!({
-            "type": "Flush"
-        });
-        self.send_command(&cmd)?;
-        self.read_response()?;
+    /// 读完整个范围，服务器因响应大小或检查条目上限截断时都自动续扫
+    fn scan_to_end(&mut self, cmd: Command) -> Result<KvPairs, Box<dyn std::error::Error>> {
+        Ok(self.scan_until(cmd, false)?.items)
+    }
+
+    /// 构造一次可指定包含或排除端点的范围扫描，默认扫描整个列族
+    pub fn scan_builder(&mut self, cf: &str) -> ScanBuilder<'_> {
+        ScanBuilder {
+            client: self,
+            cf: cf.to_string(),
+            start: ScanBound::Unbounded,
+            end: ScanBound::Unbounded,
+            limit: usize::MAX,
+            filter: None,
+        }
+    }
+
+    /// 把值序列化为 JSON 后写入
+    pub fn put_json<T: Serialize>(&mut self, cf: &str, key: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+        self.put_bytes(cf, key.as_bytes(), &serde_json::to_vec(value)?)
+    }
+
+    /// 读取并按 JSON 解码值，类型不匹配时返回 KvError::Deserialize
+    pub fn get_json<T: DeserializeOwned>(&mut self, cf: &str, key: &str) -> Result<Option<T>, Box<dyn std::error::Error>> {
+        match self.get_bytes(cf, key.as_bytes())? {
+            Some(value) => Ok(Some(decode_json(key.as_bytes(), &value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 扫描并按 JSON 解码每个值，on_error 决定遇到无法解码的值时失败还是跳过
+    pub fn scan_json<T: DeserializeOwned>(
+        &mut self,
+        cf: &str,
+        start_key: &str,
+        end_key: Option<&str>,
+        limit: usize,
+        on_error: DecodeErrors,
+    ) -> Result<JsonScan<T>, Box<dyn std::error::Error>> {
+        let items = self.scan_bytes(cf, start_key.as_bytes(), end_key.map(str::as_bytes), limit)?;
+        let mut scan = JsonScan { entries: Vec::new(), errors: Vec::new() };
+        for (key, value) in items {
+            match decode_json(&key, &value) {
+                Ok(decoded) => scan.entries.push((String::from_utf8_lossy(&key).into_owned(), decoded)),
+                Err(e) if on_error == DecodeErrors::Skip => scan.errors.push(e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(scan)
+    }
+
+    /// 跨列族扫描当前数据库，从 start（包含）开始最多返回 limit 条
+    pub fn scan_all(
+        &mut self,
+        start: Option<(&str, &str)>,
+        limit: usize,
+    ) -> Result<CfScanPage, Box<dyn std::error::Error>> {
+        let cmd = Command::ScanAll {
+            start: start.map(|(cf, key)| (cf.to_string(), Bytes(key.as_bytes().to_vec()))),
+            limit,
+        };
+
+        let lossy = |b: Bytes| String::from_utf8_lossy(&b.0).to_string();
+        match self.request(&cmd)? {
+            Response::CfValues { entries, next, truncated } => Ok(CfScanPage {
+                entries: entries.into_iter().map(|(cf, k, v)| (cf, lossy(k), lossy(v))).collect(),
+                next: next.map(|(cf, k)| (cf, lossy(k))),
+                truncated,
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 按原始字节跨列族扫描，返回 (条目, 下一页的起点)
+    pub fn scan_all_bytes(
+        &mut self,
+        start: Option<(&str, &[u8])>,
+        limit: usize,
+    ) -> Result<(Vec<protocol::CfEntry>, Option<protocol::CfCursor>), Box<dyn std::error::Error>> {
+        let cmd = Command::ScanAll {
+            start: start.map(|(cf, key)| (cf.to_string(), Bytes(key.to_vec()))),
+            limit,
+        };
+
+        match self.request(&cmd)? {
+            Response::CfValues { entries, next, .. } => Ok((
+                entries.into_iter().map(|(cf, Bytes(k), Bytes(v))| (cf, k, v)).collect(),
+                next.map(|(cf, Bytes(k))| (cf, k)),
+            )),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 读取键的指定历史版本，墓碑版本返回 None
+    pub fn get_version(
+        &mut self,
+        cf: &str,
+        key: &str,
+        version: u64,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let cmd = Command::GetVersion {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+            version,
+        };
+
+        self.request_value(&cmd)
+    }
+
+    /// 获取键的版本历史（从新到旧，包括当前版本）
+    pub fn history(
+        &mut self,
+        cf: &str,
+        key: &str,
+        limit: usize,
+    ) -> Result<Vec<Version>, Box<dyn std::error::Error>> {
+        let cmd = Command::History {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+            limit,
+        };
+
+        match self.request(&cmd)? {
+            Response::History(versions) => Ok(versions),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 使用管理令牌认证，成功后当前连接可以执行管理命令
+    pub fn admin_auth(&mut self, token: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::AdminAuth { token: token.to_string() })?;
+        self.admin_token = Some(token.to_string());
         Ok(())
     }
 
-    fn send_command(&mut self, cmd: &serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_vec(cmd)?;
-        self.stream.write_all(&json)?;
+    /// 以服务端配置的主体认证，之后按它的列族权限执行命令
+    pub fn auth(&mut self, name: &str, token: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::Auth { name: name.to_string(), token: token.to_string() })?;
+        self.credentials = Some((name.to_string(), token.to_string()));
         Ok(())
     }
 
-    fn read_response(&mut self) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-        let mut buffer = vec![0; 8192];
-        let n = self.stream.read(&mut buffer)?;
-        let response = serde_json::from_slice(&buffer[..n])?;
-        Ok(response)
+    /// 向频道发布一条消息，返回收到它的订阅者数；消息不持久化，没有订阅者时直接丢弃
+    pub fn publish(&mut self, channel: &str, payload: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        match self.request(&Command::Publish { channel: channel.to_string(), payload: payload.to_vec() })? {
+            Response::Count(receivers) => Ok(receivers),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 订阅频道；连接随后进入推送模式，不能再发送命令，所以消耗客户端。
+    /// 订阅期间不使用 set_timeout 设置的读超时；消息的 dropped 报告队列已满而丢弃的消息数
+    pub fn subscribe(mut self, channels: &[&str]) -> Result<MessageStream, Box<dyn std::error::Error>> {
+        let channels = channels.iter().map(|c| c.to_string()).collect();
+        self.request_ok(&Command::Subscribe { channels })?;
+        if let Some(tcp) = &self.tcp {
+            tcp.set_read_timeout(None)?;
+        }
+        Ok(MessageStream { client: self })
     }
-}
\ No newline at end of file
+
+    /// 当前连接的主体和列族权限
+    pub fn permissions(&mut self) -> Result<EffectivePermissions, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { permissions: Some(permissions), .. } => Ok(*permissions),
+            Response::Info { .. } => Err("server did not report permissions".into()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 切换当前连接使用的数据库
+    pub fn use_db(&mut self, db: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::UseDb { name: db.to_string() })?;
+        self.db = Some(db.to_string());
+        Ok(())
+    }
+
+    /// 设置当前连接的默认列族，之后各方法的 cf 传空字符串时使用它
+    pub fn use_cf(&mut self, cf: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::UseCf { cf: cf.to_string() })?;
+        self.default_cf = Some(cf.to_string());
+        Ok(())
+    }
+
+    /// 列出服务器上的数据库
+    pub fn list_dbs(&mut self) -> Result<Vec<DbInfo>, Box<dyn std::error::Error>> {
+        match self.request(&Command::ListDbs)? {
+            Response::Databases(dbs) => Ok(dbs),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 删除数据库及其全部数据
+    pub fn drop_db(&mut self, db: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::DropDb { name: db.to_string(), dry_run: false })
+    }
+
+    /// 试运行 drop_db：返回会被删除的键数、字节数和首尾键，不做任何修改
+    pub fn drop_db_dry_run(&mut self, db: &str) -> Result<DeletionReport, Box<dyn std::error::Error>> {
+        self.request_plan(&Command::DropDb { name: db.to_string(), dry_run: true })
+    }
+
+    /// 显式创建列族，服务器开启 strict_cf_mode 时写入前必须先创建
+    pub fn create_cf(&mut self, cf: &str, options: Option<CfOptions>) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::CreateCf { cf: cf.to_string(), options })
+    }
+
+    /// 恢复键最近一次被删除的值，未指定 overwrite 且键已有值时失败
+    pub fn restore_key(&mut self, cf: &str, key: &str, overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::RestoreKey { cf: cf.to_string(), key: key.as_bytes().to_vec(), overwrite })
+    }
+
+    /// 列出当前数据库回收站中的条目，cf 为 None 时列出所有列族
+    pub fn scan_trash(&mut self, cf: Option<&str>, limit: usize) -> Result<Vec<TrashEntry>, Box<dyn std::error::Error>> {
+        match self.request(&Command::ScanTrash { cf: cf.map(str::to_string), limit })? {
+            Response::Trash(entries) => Ok(entries),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 从当前数据库中均匀随机地抽取至多 count 个键，cf 为 None 时从所有列族中抽取
+    pub fn sample(&mut self, cf: Option<&str>, count: usize) -> Result<Vec<KeySample>, Box<dyn std::error::Error>> {
+        match self.request(&Command::Sample { cf: cf.map(str::to_string), count })? {
+            Response::Samples(samples) => Ok(samples),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 清理回收站，返回删除的条目数；all 为 false 时只删除超出保留期的条目（管理命令）
+    pub fn purge_trash(&mut self, all: bool) -> Result<usize, Box<dyn std::error::Error>> {
+        match self.request(&Command::PurgeTrash { all, dry_run: false })? {
+            Response::Count(purged) => Ok(purged),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 试运行 purge_trash，返回会被清理的条目（报告中是条目原来的列族和键）
+    pub fn purge_trash_dry_run(&mut self, all: bool) -> Result<DeletionReport, Box<dyn std::error::Error>> {
+        self.request_plan(&Command::PurgeTrash { all, dry_run: true })
+    }
+
+    // 不认识 dry_run 的旧服务器会忽略这个字段并真正执行删除，所以必须先协商
+    fn request_plan(&mut self, cmd: &Command) -> Result<DeletionReport, Box<dyn std::error::Error>> {
+        if !self.features.iter().any(|f| f == "dry-run") {
+            return Err(KvError::Protocol("server does not support dry-run".to_string()).into());
+        }
+        match self.request(cmd)? {
+            Response::DeletionPlan(report) => Ok(report),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 修改列族配额，None 表示不限制（管理命令）
+    pub fn set_cf_quota(
+        &mut self,
+        cf: &str,
+        max_keys: Option<usize>,
+        max_bytes: Option<usize>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::SetCfQuota { cf: cf.to_string(), max_keys, max_bytes })
+    }
+
+    /// 当前数据库的所有列族及其键数、创建时间、用量和配额
+    pub fn cf_info(&mut self) -> Result<Vec<CfInfo>, Box<dyn std::error::Error>> {
+        self.iter_cfs(CF_PAGE_SIZE).collect()
+    }
+
+    /// 列出当前数据库中名称在 start_after 之后的至多 limit 个列族，返回 (列族, 下一页的 start_after)
+    pub fn list_cfs(
+        &mut self,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<CfInfo>, Option<String>), Box<dyn std::error::Error>> {
+        let cmd = Command::ListCfs { start_after: start_after.map(str::to_string), limit };
+        match self.request(&cmd)? {
+            Response::CfList { cfs, next } => Ok((cfs, next)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 按名称顺序遍历当前数据库的所有列族，每次请求 page_size 个（至少 1 个）
+    pub fn iter_cfs(&mut self, page_size: usize) -> CfInfoIter<'_> {
+        CfInfoIter {
+            client: self,
+            page_size: page_size.max(1),
+            page: Vec::new().into_iter(),
+            next: None,
+            done: false,
+        }
+    }
+
+    /// 按键的升序遍历列族的所有条目，每次请求 page_size 条；连接错误时重新连接并从断点继续
+    pub fn iter_cf(&mut self, cf: &str, page_size: usize) -> CfScanIter<'_> {
+        CfScanIter {
+            client: self,
+            cf: cf.to_string(),
+            page_size: page_size.max(1),
+            page: Vec::new().into_iter(),
+            last: None,
+            done: false,
+        }
+    }
+
+    /// 按命令类型统计的服务端处理耗时（微秒）
+    pub fn latency(&mut self) -> Result<BTreeMap<String, LatencySummary>, Box<dyn std::error::Error>> {
+        match self.request(&Command::Info)? {
+            Response::Info { latency, .. } => Ok(latency),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端正在进行或最近一次的刷盘、整理的进度
+    pub fn maintenance_status(&mut self) -> Result<Option<MaintenanceStatus>, Box<dyn std::error::Error>> {
+        match self.request(&Command::Info)? {
+            Response::Info { maintenance, .. } => Ok(maintenance.map(|m| *m)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端的整理统计和当前的无效数据比例
+    pub fn compaction_info(&mut self) -> Result<CompactionInfo, Box<dyn std::error::Error>> {
+        match self.request(&Command::Info)? {
+            Response::Info { compaction, .. } => Ok(*compaction),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 获取服务器信息：当前数据库的键数和列族数；列族列表见 list_cfs
+    pub fn info(&mut self) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { total_keys, cf_count, .. } => Ok((total_keys, cf_count)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 修改服务器的运行时配置项，需要管理权限；未知或只能在启动时设置的配置项返回
+    /// UnknownConfig / StaticConfig 错误，见 runtime_config 模块
+    pub fn set_config(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::SetConfig { key: key.to_string(), value: value.to_string() })
+    }
+
+    /// 服务器所有配置项的当前值，需要管理权限
+    pub fn get_config(&mut self) -> Result<Vec<ConfigEntry>, Box<dyn std::error::Error>> {
+        match self.request(&Command::GetConfig)? {
+            Response::Config(entries) => Ok(entries),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 刷盘持久化，返回写入的字节数以及是否执行了 fsync
+    pub fn flush(&mut self) -> Result<FlushStats, Box<dyn std::error::Error>> {
+        match self.request(&Command::Flush)? {
+            Response::Flushed(stats) => Ok(stats),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 整理存储，返回回收的无效数据和内存容量
+    pub fn compact(&mut self) -> Result<CompactResult, Box<dyn std::error::Error>> {
+        match self.request(&Command::Compact)? {
+            Response::Compacted(result) => Ok(result),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端采样统计的热点键，按估计访问次数从高到低排列
+    pub fn hot_keys(&mut self, top_n: usize) -> Result<Vec<HotKey>, Box<dyn std::error::Error>> {
+        match self.request(&Command::HotKeys { top_n })? {
+            Response::HotKeys(keys) => Ok(keys),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 清空热点键统计
+    pub fn reset_stats(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::ResetStats)
+    }
+
+    /// 服务器上所有连接的统计信息（需要管理权限）
+    pub fn clients(&mut self) -> Result<Vec<ClientInfo>, Box<dyn std::error::Error>> {
+        match self.request(&Command::Clients)? {
+            Response::Clients(clients) => Ok(clients),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端最近的至多 count 条错误事件，新的在前（需要管理权限）
+    pub fn recent_errors(&mut self, count: usize) -> Result<Vec<ErrorEvent>, Box<dyn std::error::Error>> {
+        match self.request(&Command::RecentErrors { count })? {
+            Response::Errors(events) => Ok(events),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端幂等写请求缓存的统计
+    pub fn idempotency_stats(&mut self) -> Result<IdempotencyStats, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { idempotency, .. } => Ok(*idempotency),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端启动以来记录的错误事件数
+    pub fn error_count(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { errors_total, .. } => Ok(errors_total),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端压缩的值的数量，以及压缩前后的近似内存占用
+    pub fn compression_stats(&mut self) -> Result<CompressionStats, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { compression, .. } => Ok(*compression),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端移到磁盘层的值的数量和占用的磁盘空间
+    pub fn spill_stats(&mut self) -> Result<SpillStats, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { spill, .. } => Ok(*spill),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端存储数据锁的获取次数和等待时间
+    pub fn lock_stats(&mut self) -> Result<LockWaitStats, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { locks, .. } => Ok(*locks),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端段文件和审计日志的文件统计
+    pub fn log_stats(&mut self) -> Result<Vec<LogFileStats>, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { logs, .. } => Ok(logs),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端因读阻塞超过上限而断开的连接数，见 ServerConfig::max_blocked_read
+    pub fn connections_reaped(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { connections_reaped, .. } => Ok(connections_reaped),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端因来源 IP 的连接数达到上限而拒绝的连接数，见 ServerConfig::max_connections_per_ip
+    pub fn connections_rejected(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { connections_rejected, .. } => Ok(connections_rejected),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务器的版本、配置和限制，见 protocol::ServerInfo
+    pub fn server_info(&mut self) -> Result<ServerInfo, Box<dyn std::error::Error>> {
+        match self.request(&Command::ServerInfo)? {
+            Response::ServerInfo(info) => Ok(*info),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端的持久化状态，刷盘失败后 degraded 为 true，直到下一次刷盘成功
+    pub fn persistence_status(&mut self) -> Result<PersistenceStatus, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { persistence, .. } => Ok(*persistence),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 服务端延迟加载的进度，数据已经全部加载时为 None
+    pub fn load_status(&mut self) -> Result<Option<LoadStatus>, Box<dyn std::error::Error>> {
+        match self.request(&Command::InfoSummary)? {
+            Response::Info { load, .. } => Ok(load.map(|s| *s)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 断开指定 id 的连接（需要管理权限）
+    pub fn kill_client(&mut self, id: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::KillClient { id })
+    }
+
+    /// 请求服务器关闭，flush 为 true 时服务器在退出前刷盘
+    pub fn shutdown(&mut self, flush: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_ok(&Command::Shutdown { flush })
+    }
+
+    /// 校验值的校验和，返回损坏的 (列族, 键)；cf 为 None 时校验所有列族
+    pub fn verify(&mut self, cf: Option<&str>) -> Result<CfKeys, Box<dyn std::error::Error>> {
+        self.corrupt_keys(&Command::Verify { cf: cf.map(str::to_string) })
+    }
+
+    /// 删除损坏的条目（quarantine 时移到隔离列族），返回处理过的 (列族, 键)
+    pub fn repair(&mut self, quarantine: bool) -> Result<CfKeys, Box<dyn std::error::Error>> {
+        self.corrupt_keys(&Command::Repair { quarantine })
+    }
+
+    /// 按 checks 检查 cf（None 时为所有列族）中从 start（包含）开始的条目，返回最多 limit 个违规条目
+    /// 和续查位置；服务器因检查条目上限提前结束时违规条目可能少于 limit，没有更多条目时续查位置为 None
+    pub fn audit_page(
+        &mut self,
+        cf: Option<&str>,
+        checks: &[CheckKind],
+        start: Option<(&str, &[u8])>,
+        limit: usize,
+    ) -> Result<(Vec<Violation>, Option<protocol::CfCursor>), Box<dyn std::error::Error>> {
+        let cmd = Command::Audit {
+            cf: cf.map(str::to_string),
+            checks: checks.to_vec(),
+            start: start.map(|(cf, key)| (cf.to_string(), Bytes(key.to_vec()))),
+            limit: limit.max(1),
+        };
+        match self.request(&cmd)? {
+            Response::Violations { violations, next, .. } => Ok((violations, next.map(|(cf, Bytes(key))| (cf, key)))),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 检查所有条目（管理命令），自动从续查位置继续，返回全部违规条目
+    pub fn audit(&mut self, cf: Option<&str>, checks: &[CheckKind]) -> Result<Vec<Violation>, Box<dyn std::error::Error>> {
+        let (mut violations, mut next) = self.audit_page(cf, checks, None, AUDIT_PAGE_SIZE)?;
+        while let Some((next_cf, key)) = next {
+            let (more, more_next) = self.audit_page(cf, checks, Some((&next_cf, &key)), AUDIT_PAGE_SIZE)?;
+            violations.extend(more);
+            next = more_next;
+        }
+        Ok(violations)
+    }
+
+    /// 按 policy 处理所有违反 checks 的条目（管理命令）
+    pub fn fix_invalid(&mut self, cf: Option<&str>, checks: &[CheckKind], policy: FixPolicy) -> Result<FixReport, Box<dyn std::error::Error>> {
+        let cmd = Command::FixInvalid { cf: cf.map(str::to_string), checks: checks.to_vec(), policy };
+        match self.request(&cmd)? {
+            Response::Fixed(report) => Ok(report),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 重新校验审计日志的哈希链（管理命令）
+    pub fn audit_verify(&mut self) -> Result<AuditReport, Box<dyn std::error::Error>> {
+        match self.request(&Command::AuditVerify)? {
+            Response::AuditReport(report) => Ok(report),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 序号在 [from_seq, to_seq] 内的 oplog 记录（管理命令），每次最多 oplog::OPLOG_RANGE_LIMIT 条；
+    /// 还有更多记录时同时返回下一条的序号
+    pub fn oplog_range_page(&mut self, from_seq: u64, to_seq: Option<u64>) -> Result<(Vec<OplogEntry>, Option<u64>), Box<dyn std::error::Error>> {
+        match self.request(&Command::OplogRange { from_seq, to_seq })? {
+            Response::Oplog { entries, next } => Ok((entries, next)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 序号在 [from_seq, to_seq] 内的所有 oplog 记录（管理命令），自动分页
+    pub fn oplog_range(&mut self, from_seq: u64, to_seq: Option<u64>) -> Result<Vec<OplogEntry>, Box<dyn std::error::Error>> {
+        let (mut entries, mut next) = self.oplog_range_page(from_seq, to_seq)?;
+        while let Some(from_seq) = next {
+            let (more, more_next) = self.oplog_range_page(from_seq, to_seq)?;
+            entries.extend(more);
+            next = more_next;
+        }
+        Ok(entries)
+    }
+
+    /// 把整个存储备份到 writer（管理命令），返回备份的键数；备份文件按行分帧接收，格式见
+    /// StandaloneStorage::backup_to
+    pub fn backup(&mut self, mut writer: impl Write) -> Result<usize, Box<dyn std::error::Error>> {
+        let lines = match self.request(&Command::Backup)? {
+            Response::Archive(lines) => lines,
+            other => return Err(unexpected(other)),
+        };
+        for line in &lines {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        // 文件头和结尾各占一行
+        Ok(lines.len().saturating_sub(2))
+    }
+
+    /// 用 backup 写出的备份替换服务器上的整个存储（管理命令），返回恢复的键数；
+    /// 服务器上已有数据时需要 force
+    pub fn restore(&mut self, reader: impl io::BufRead, force: bool) -> Result<usize, Box<dyn std::error::Error>> {
+        let archive = reader.lines().collect::<Result<Vec<String>, _>>()?;
+        self.request_count(&Command::Restore { archive, force })
+    }
+
+    /// 在服务器上把备份写到 path（管理命令），返回备份的键数
+    pub fn backup_to_file(&mut self, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        self.request_count(&Command::BackupToFile { path: path.to_string() })
+    }
+
+    /// 用服务器上 path 处的备份替换整个存储（管理命令），返回恢复的键数
+    pub fn restore_from_file(&mut self, path: &str, force: bool) -> Result<usize, Box<dyn std::error::Error>> {
+        self.request_count(&Command::RestoreFromFile { path: path.to_string(), force })
+    }
+
+    fn request_count(&mut self, cmd: &Command) -> Result<usize, Box<dyn std::error::Error>> {
+        match self.request(cmd)? {
+            Response::Count(count) => Ok(count),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn corrupt_keys(&mut self, cmd: &Command) -> Result<CfKeys, Box<dyn std::error::Error>> {
+        match self.request(cmd)? {
+            Response::CorruptKeys(keys) => Ok(keys.into_iter().map(|(cf, Bytes(k))| (cf, k)).collect()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 发送命令并读取响应，服务端错误转换为 Err
+    ///
+    /// 有多个地址时，连接错误会把当前地址标记为不健康并切换到下一个地址；
+    /// 只读命令总是重试，写命令仅在 RetryPolicy::retry_writes 时重试
+    fn request(&mut self, cmd: &Command) -> Result<Response, Box<dyn std::error::Error>> {
+        // 列族名在本地校验，不合法的请求不发送；Batch 由服务端逐条校验并报告，
+        // 空的列族名表示默认列族，由服务端替换
+        if !matches!(cmd, Command::Batch { .. }) {
+            for cf in cmd.cfs().into_iter().filter(|cf| !cf.is_empty()) {
+                protocol::validate_cf_name(cf)?;
+            }
+        }
+        if self.broken {
+            // 丢弃损坏的连接，重新连接后再发送
+            if self.endpoints.is_empty() {
+                return Err(KvError::Broken.into());
+            }
+            self.reconnect()?;
+        }
+
+        let retryable = self.endpoints.len() > 1 && (cmd.is_read_only() || self.policy.retry_writes);
+        // 可能重试的写命令带上幂等键，重试时沿用，服务器不会把它执行两次
+        let idempotent = (retryable && !cmd.is_read_only() && !matches!(cmd, Command::Shutdown { .. })).then(|| {
+            self.last_idempotency_key += 1;
+            Command::Idempotent { token: self.idempotency_token, key: self.last_idempotency_key, cmd: Box::new(cmd.clone()) }
+        });
+        let response = match self.exchange(self.wire_command(cmd, idempotent.as_ref())) {
+            Ok(response) => response,
+            Err(e) if !retryable => return Err(e),
+            Err(_) => {
+                self.mark_unhealthy();
+                self.reconnect()?;
+                self.exchange(self.wire_command(cmd, idempotent.as_ref()))?
+            }
+        };
+
+        match response {
+            Response::Error(e) => Err(KvError::server(e).into()),
+            response => Ok(response),
+        }
+    }
+
+    /// 发送一条命令并读取响应；任何传输错误都会把连接标记为损坏
+    fn exchange(&mut self, cmd: &Command) -> Result<Response, Box<dyn std::error::Error>> {
+        let result = self.send_command(cmd).and_then(|_| self.read_response());
+        result.map_err(|e| self.transport_error(e))
+    }
+
+    // 把连接标记为损坏，读写超时转换为 KvError::Timeout
+    fn transport_error(&mut self, e: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+        self.broken = true;
+        match e.downcast_ref::<io::Error>() {
+            Some(io) if matches!(io.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => KvError::Timeout.into(),
+            _ => e,
+        }
+    }
+
+    // 当前服务器协商了 idempotency 时发送带幂等键的命令，否则发送原命令
+    fn wire_command<'c>(&self, cmd: &'c Command, idempotent: Option<&'c Command>) -> &'c Command {
+        match idempotent {
+            Some(wrapped) if self.features.iter().any(|f| f == "idempotency") => wrapped,
+            _ => cmd,
+        }
+    }
+
+    fn mark_unhealthy(&mut self) {
+        if let Some(endpoint) = self.endpoints.get_mut(self.active) {
+            endpoint.unhealthy_until = Some(Instant::now() + self.policy.cooldown);
+        }
+    }
+
+    /// 按顺序连接第一个可用的地址（健康的优先），并重放会话状态
+    fn reconnect(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let now = Instant::now();
+        let (healthy, cooling): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|&i| self.endpoints[i].is_healthy(now));
+
+        let mut last_error: Box<dyn std::error::Error> = "No server address given".into();
+        for index in healthy.into_iter().chain(cooling) {
+            match self.connect_endpoint(index) {
+                Ok(()) => {
+                    self.active = index;
+                    self.endpoints[index].unhealthy_until = None;
+                    return self.restore_session();
+                }
+                Err(e) => {
+                    self.endpoints[index].unhealthy_until = Some(now + self.policy.cooldown);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// 连接指定地址并握手；服务器不认识 Hello 而关闭连接时，重新连接并按旧协议通信
+    fn connect_endpoint(&mut self, index: usize) -> Result<(), Box<dyn std::error::Error>> {
+        self.attach(open_stream(&self.endpoints[index].addr, self.timeout)?)?;
+        if self.endpoints[index].legacy {
+            return Ok(());
+        }
+
+        match self.handshake() {
+            Err(e) if e.downcast_ref::<KvError>() == Some(&KvError::Closed) => {
+                self.endpoints[index].legacy = true;
+                self.attach(open_stream(&self.endpoints[index].addr, self.timeout)?)?;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn attach(&mut self, stream: TcpStream) -> io::Result<()> {
+        if let Some(interval) = self.keepalive {
+            // 失败时连接仍然可用，只是不能及时发现服务器失联
+            let _ = keepalive::set_keepalive(&stream, interval);
+        }
+        self.tcp = stream.try_clone().ok();
+        self.stream = self.wrap_stream(stream)?;
+        self.pending.clear();
+        self.broken = false;
+        self.features.clear();
+        Ok(())
+    }
+
+    /// connect_tls 创建的客户端把连接包装为 TLS 流，握手在第一次读写时进行
+    fn wrap_stream(&self, stream: TcpStream) -> io::Result<Box<dyn Transport>> {
+        #[cfg(feature = "tls")]
+        if let Some((config, name)) = &self.tls {
+            return Ok(Box::new(tls::connect(config, name, stream)?));
+        }
+        Ok(Box::new(stream))
+    }
+
+    fn handshake(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let opt_in = protocol::OPT_IN_FEATURES.iter().filter(|f| match **f {
+            "envelope" => self.envelope,
+            "msgpack" => self.msgpack,
+            _ => false,
+        });
+        let requested: Vec<String> = protocol::FEATURES.iter().chain(opt_in).map(|f| f.to_string()).collect();
+        let hello = Command::Hello { client_version: protocol::PROTOCOL_VERSION, features: requested.clone() };
+        match self.exchange(&hello)? {
+            Response::Hello { accepted_features, .. } => {
+                // 只使用自己请求过的特性
+                self.features = accepted_features.into_iter().filter(|f| requested.contains(f)).collect();
+                Ok(())
+            }
+            Response::Error(e) => Err(KvError::server(e).into()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn restore_session(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(token) = self.admin_token.clone() {
+            self.exchange_ok(&Command::AdminAuth { token })?;
+        }
+        if let Some((name, token)) = self.credentials.clone() {
+            self.exchange_ok(&Command::Auth { name, token })?;
+        }
+        if let Some(name) = self.db.clone() {
+            self.exchange_ok(&Command::UseDb { name })?;
+        }
+        if let Some(cf) = self.default_cf.clone() {
+            self.exchange_ok(&Command::UseCf { cf })?;
+        }
+        Ok(())
+    }
+
+    fn exchange_ok(&mut self, cmd: &Command) -> Result<(), Box<dyn std::error::Error>> {
+        match self.exchange(cmd)? {
+            Response::Ok => Ok(()),
+            Response::Error(e) => Err(KvError::server(e).into()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 发送期望 Value 响应的命令，值按 UTF-8 解码
+    fn request_value(&mut self, cmd: &Command) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match self.request_bytes(cmd)? {
+            Some(bytes) => Ok(Some(String::from_utf8(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 发送期望 Value 响应的命令；只有 Get 可能收到 NotFound
+    fn request_bytes(&mut self, cmd: &Command) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match self.request(cmd)? {
+            Response::Value(value) => Ok(value.map(|Bytes(v)| v)),
+            Response::NotFound if matches!(cmd, Command::Get { .. }) => Ok(None),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// 发送只期望 Ok 响应的命令
+    fn request_ok(&mut self, cmd: &Command) -> Result<(), Box<dyn std::error::Error>> {
+        match self.request(cmd)? {
+            Response::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    // 协商了 envelope 时把读写超时作为截止时间附带在请求中，服务器过期后放弃执行，见 deadline 模块
+    fn send_command(&mut self, cmd: &Command) -> Result<(), Box<dyn std::error::Error>> {
+        let deadline_ms = self.timeout.filter(|_| self.features.iter().any(|f| f == "envelope")).map(|t| t.as_millis() as u64);
+        let bytes = self.codec().encode(&protocol::Request { cmd, deadline_ms })?;
+        self.stream.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// 读取一条完整的响应；分帧发送的列表响应在这里拼回，中途收到 Error 帧时丢弃已收到的部分
+    fn read_response(&mut self) -> Result<Response, Box<dyn std::error::Error>> {
+        let mut assembled: Option<Response> = None;
+        loop {
+            let frame = self.read_frame()?;
+            let (part, more) = match (frame, &mut assembled) {
+                (Response::Chunk { part, more }, _) => (*part, more),
+                (frame, None) | (frame @ Response::Error(_), Some(_)) => return Ok(frame),
+                (frame, Some(_)) => return Err(unexpected(frame)),
+            };
+            match &mut assembled {
+                Some(response) => response.append(part)?,
+                None => assembled = Some(part),
+            }
+            if !more {
+                return Ok(assembled.expect("a part was just stored"));
+            }
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Response, Box<dyn std::error::Error>> {
+        let codec = self.codec();
+        if !self.features.iter().any(|f| f == "envelope") {
+            return match codec.read_message(&mut self.stream, &mut self.pending)? {
+                Some(response) => Ok(response),
+                None => Err(KvError::Closed.into()),
+            };
+        }
+        match codec.read_message::<protocol::Envelope, _>(&mut self.stream, &mut self.pending)? {
+            Some(envelope) => {
+                self.last_server_timing = Some(Duration::from_micros(envelope.server_us));
+                self.warnings.extend(envelope.warnings);
+                Ok(envelope.resp)
+            }
+            None => Err(KvError::Closed.into()),
+        }
+    }
+}
+
+/// KvClient::scan_builder 返回的范围扫描，未设置的一端无界
+pub struct ScanBuilder<'a> {
+    client: &'a mut KvClient,
+    cf: String,
+    start: ScanBound,
+    end: ScanBound,
+    limit: usize,
+    filter: Option<ValueFilter>,
+}
+
+impl<'a> ScanBuilder<'a> {
+    pub fn from_inclusive(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.start = ScanBound::Included(key.as_ref().to_vec());
+        self
+    }
+
+    pub fn from_exclusive(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.start = ScanBound::Excluded(key.as_ref().to_vec());
+        self
+    }
+
+    pub fn to_inclusive(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.end = ScanBound::Included(key.as_ref().to_vec());
+        self
+    }
+
+    pub fn to_exclusive(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.end = ScanBound::Excluded(key.as_ref().to_vec());
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// 只返回值满足 filter 的条目，limit 按匹配条目计数
+    pub fn filter(mut self, filter: ValueFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn run(self) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        Ok(self
+            .run_bytes()?
+            .into_iter()
+            .map(|(k, v)| (String::from_utf8_lossy(&k).to_string(), String::from_utf8_lossy(&v).to_string()))
+            .collect())
+    }
+
+    /// 读取整个范围；服务器按响应大小上限截断时自动从续扫位置继续
+    pub fn run_bytes(self) -> Result<KvPairs, Box<dyn std::error::Error>> {
+        let (client, cmd) = self.into_command();
+        client.scan_to_end(cmd)
+    }
+
+    /// 与 run_bytes 相同，但服务器因检查条目上限截断时停下，由调用方决定是否从 cursor 继续
+    pub fn run_bounded(self) -> Result<ScanResult, Box<dyn std::error::Error>> {
+        let (client, cmd) = self.into_command();
+        client.scan_until(cmd, true)
+    }
+
+    /// 只发送一次请求，结果可能因服务器的响应大小上限被截断
+    pub fn run_page(self) -> Result<ScanResult, Box<dyn std::error::Error>> {
+        let (client, cmd) = self.into_command();
+        client.scan_page(&cmd)
+    }
+
+    /// 只发送一次请求，服务器分帧发送时每收到一帧就调用一次 on_chunk，不等整个响应到齐；
+    /// 返回截断时的续扫位置
+    pub fn run_chunks(self, mut on_chunk: impl FnMut(KvPairs)) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let (client, cmd) = self.into_command();
+        client.scan_chunks(&cmd, &mut on_chunk)
+    }
+
+    fn into_command(self) -> (&'a mut KvClient, Command) {
+        let cmd = Command::Scan {
+            cf: self.cf,
+            start_key: Vec::new(),
+            end_key: None,
+            limit: self.limit,
+            filter: self.filter,
+            start_bound: Some(self.start),
+            end_bound: Some(self.end),
+        };
+        (self.client, cmd)
+    }
+}
+
+/// 持有中的锁：后台线程每隔 ttl/3 续约一次，丢弃时释放锁
+/// 续约使用独立的连接，持有者进程退出后锁在租约到期时自动失效
+pub struct LockGuard {
+    name: String,
+    token: u64,
+    lost: Arc<AtomicBool>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LockGuard {
+    /// 连接到 addr 并尝试获取锁，锁被他人持有时返回 None
+    pub fn acquire(addr: &str, name: &str, ttl: Duration) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let mut client = KvClient::connect(addr)?;
+        let Some(token) = client.acquire_lock(name, ttl)? else {
+            return Ok(None);
+        };
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let (stop, stopped) = mpsc::channel::<()>();
+        let (thread_name, thread_lost) = (name.to_string(), Arc::clone(&lost));
+        let thread = thread::spawn(move || {
+            // 句柄被丢弃时 recv_timeout 返回 Disconnected
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(ttl / 3) {
+                if client.renew_lock(&thread_name, token, ttl).is_err() {
+                    thread_lost.store(true, Ordering::SeqCst);
+                    return;
+                }
+            }
+            let _ = client.release_lock(&thread_name, token);
+        });
+
+        Ok(Some(LockGuard {
+            name: name.to_string(),
+            token,
+            lost,
+            stop: Some(stop),
+            thread: Some(thread),
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// 续约失败后返回 false，此时锁可能已被他人获取
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 每个节点在哈希环上的虚拟节点数
+const VIRTUAL_NODES: usize = 64;
+
+/// 按 (列族, 键) 的一致性哈希把请求分发到多台服务器的客户端
+/// 分片完全在客户端完成，服务器之间互不知晓；增删节点时不迁移数据，
+/// 只有落在变动区间内的键改变归属
+pub struct ShardedClient {
+    nodes: BTreeMap<String, KvClient>,
+    // 虚拟节点的哈希 -> 节点地址
+    ring: BTreeMap<u64, String>,
+}
+
+impl ShardedClient {
+    pub fn new(addrs: &[&str]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut sharded = ShardedClient { nodes: BTreeMap::new(), ring: BTreeMap::new() };
+        for addr in addrs {
+            sharded.add_node(addr)?;
+        }
+        Ok(sharded)
+    }
+
+    /// 连接并加入新节点，原本属于其他节点的一部分键改由它负责
+    pub fn add_node(&mut self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.nodes.contains_key(addr) {
+            return Err(format!("Node {} is already in the ring", addr).into());
+        }
+        self.nodes.insert(addr.to_string(), KvClient::connect(addr)?);
+        for i in 0..VIRTUAL_NODES {
+            self.ring.insert(protocol::stable_hash(format!("{}#{}", addr, i).as_bytes()), addr.to_string());
+        }
+        Ok(())
+    }
+
+    /// 移除节点并断开连接，它负责的键改由环上的下一个节点负责
+    pub fn remove_node(&mut self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.nodes.remove(addr).is_none() {
+            return Err(format!("Node {} is not in the ring", addr).into());
+        }
+        self.ring.retain(|_, node| node != addr);
+        Ok(())
+    }
+
+    /// 按地址排序的节点列表
+    pub fn nodes(&self) -> Vec<&str> {
+        self.nodes.keys().map(String::as_str).collect()
+    }
+
+    /// 负责 (cf, key) 的节点地址，没有节点时为 None
+    pub fn node_for(&self, cf: &str, key: &str) -> Option<&str> {
+        // 与服务器的 KeyHash 相同；空列族名在服务端解析为默认列族
+        let cf = if cf.is_empty() { DEFAULT_CF } else { cf };
+        let hash = EncodedKey::encode(cf, key.as_bytes()).stable_hash();
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, addr)| addr.as_str())
+    }
+
+    fn owner(&mut self, cf: &str, key: &str) -> Result<&mut KvClient, Box<dyn std::error::Error>> {
+        let addr = self.node_for(cf, key).ok_or("No nodes in the ring")?.to_string();
+        Ok(self.nodes.get_mut(&addr).expect("ring only references known nodes"))
+    }
+
+    pub fn get(&mut self, cf: &str, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.owner(cf, key)?.get(cf, key)
+    }
+
+    pub fn put(&mut self, cf: &str, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.owner(cf, key)?.put(cf, key, value)
+    }
+
+    pub fn delete(&mut self, cf: &str, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.owner(cf, key)?.delete(cf, key)
+    }
+
+    /// 按节点分组读取多个键，结果与 keys 一一对应
+    pub fn multi_get(&mut self, cf: &str, keys: &[&str]) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let mut by_node: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            let addr = self.node_for(cf, key).ok_or("No nodes in the ring")?;
+            by_node.entry(addr.to_string()).or_default().push(i);
+        }
+
+        let mut values = vec![None; keys.len()];
+        for (addr, indexes) in by_node {
+            let client = self.nodes.get_mut(&addr).expect("ring only references known nodes");
+            for i in indexes {
+                values[i] = client.get(cf, keys[i])?;
+            }
+        }
+        Ok(values)
+    }
+
+    /// 在所有节点上扫描 [start_key, end_key) 并按键归并，最多返回 limit 条
+    /// 增删节点后旧节点上可能残留同一个键，此时以当前负责该键的节点为准
+    pub fn scan(
+        &mut self,
+        cf: &str,
+        start_key: &str,
+        end_key: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        // (键, 是否来自负责该键的节点, 值)
+        let mut merged: Vec<(String, bool, String)> = Vec::new();
+        let addrs: Vec<String> = self.nodes.keys().cloned().collect();
+        for addr in addrs {
+            let client = self.nodes.get_mut(&addr).expect("node list was just read");
+            for (key, value) in client.scan(cf, start_key, end_key, limit)? {
+                let owned = self.node_for(cf, &key) == Some(addr.as_str());
+                merged.push((key, owned, value));
+            }
+        }
+
+        // 同一个键的条目相邻，负责节点的条目排在最前
+        merged.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        merged.dedup_by(|later, first| later.0 == first.0);
+        merged.truncate(limit);
+        Ok(merged.into_iter().map(|(key, _, value)| (key, value)).collect())
+    }
+}
+
+/// 建立 TCP 连接，给定 timeout 时同时限制连接、读和写的时间
+fn open_stream(addr: &str, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let Some(timeout) = timeout else {
+        return TcpStream::connect(addr);
+    };
+
+    let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "Address resolved to nothing");
+    for socket_addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn decode_json<T: DeserializeOwned>(key: &[u8], value: &[u8]) -> Result<T, KvError> {
+    serde_json::from_slice(value).map_err(|e| KvError::Deserialize {
+        key: String::from_utf8_lossy(key).into_owned(),
+        message: e.to_string(),
+    })
+}
+
+/// 响应类型与命令不匹配
+fn unexpected(response: Response) -> Box<dyn std::error::Error> {
+    KvError::Protocol(format!("{:?}", response)).into()
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Shutdown, TcpStream};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 一个连接的统计信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub peer_addr: String,
+    pub connected_at_ms: u64,
+    /// 已执行的命令数
+    pub commands: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// 最近一条命令的类型，尚未执行命令时为 None
+    pub last_command: Option<String>,
+    pub is_admin: bool,
+    pub db: String,
+}
+
+struct Client {
+    info: ClientInfo,
+    // 用于关闭或断开连接；serve_connection 提供的非 TCP 连接为 None
+    stream: Option<TcpStream>,
+    // 开始等待下一条命令的时间，执行命令和推送消息期间为 None
+    reading_since: Option<Instant>,
+    // 计入 per_ip 的来源地址，见 register_limited
+    ip: Option<IpAddr>,
+}
+
+/// 服务器上所有活跃连接，按连接 id 索引；连接线程退出时注销
+#[derive(Default)]
+pub struct ClientRegistry {
+    next_id: AtomicU64,
+    clients: Mutex<HashMap<u64, Client>>,
+    // 启动以来因读阻塞超过上限被断开的连接数
+    reaped: AtomicU64,
+    // 每个来源 IP 的活跃连接数，只统计经 register_limited 登记的连接
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+    // 启动以来因来源 IP 的连接数达到上限被拒绝的连接数
+    rejected: AtomicU64,
+}
+
+impl ClientRegistry {
+    /// 登记新连接，返回连接 id
+    pub fn register(&self, peer_addr: String, stream: Option<TcpStream>) -> u64 {
+        self.insert(peer_addr, stream, None)
+    }
+
+    /// 登记来自 ip 的新连接；该地址的活跃连接已有 limit 个时不登记，计入 rejected 并返回 None。
+    /// limit 为 None 时不限制，但仍然计数
+    pub fn register_limited(&self, peer_addr: String, ip: IpAddr, limit: Option<usize>, stream: Option<TcpStream>) -> Option<u64> {
+        {
+            let mut per_ip = self.per_ip.lock().ok()?;
+            let count = per_ip.entry(ip).or_insert(0);
+            if limit.is_some_and(|limit| *count >= limit) {
+                self.rejected.fetch_add(1, Ordering::SeqCst);
+                return None;
+            }
+            *count += 1;
+        }
+        Some(self.insert(peer_addr, stream, Some(ip)))
+    }
+
+    fn insert(&self, peer_addr: String, stream: Option<TcpStream>, ip: Option<IpAddr>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let info = ClientInfo {
+            id,
+            peer_addr,
+            connected_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            commands: 0,
+            bytes_in: 0,
+            bytes_out: 0,
+            last_command: None,
+            is_admin: false,
+            db: String::new(),
+        };
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(id, Client { info, stream, reading_since: None, ip });
+        }
+        id
+    }
+
+    pub fn unregister(&self, id: u64) {
+        let ip = match self.clients.lock() {
+            Ok(mut clients) => clients.remove(&id).and_then(|client| client.ip),
+            Err(_) => None,
+        };
+        if let Some(ip) = ip
+            && let Ok(mut per_ip) = self.per_ip.lock()
+            && let Some(count) = per_ip.get_mut(&ip)
+        {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&ip);
+            }
+        }
+    }
+
+    /// 来自 ip 的活跃连接数
+    pub fn connections_from(&self, ip: IpAddr) -> usize {
+        self.per_ip.lock().ok().and_then(|per_ip| per_ip.get(&ip).copied()).unwrap_or(0)
+    }
+
+    /// 启动以来被 register_limited 拒绝的连接数
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::SeqCst)
+    }
+
+    /// 记录连接执行完一条命令后的状态，bytes_in / bytes_out 为本条命令的收发字节数
+    pub fn record(&self, id: u64, command: &str, bytes_in: u64, bytes_out: u64, is_admin: bool, db: &str) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        if let Some(client) = clients.get_mut(&id) {
+            let info = &mut client.info;
+            info.commands += 1;
+            info.bytes_in += bytes_in;
+            info.bytes_out += bytes_out;
+            info.last_command = Some(command.to_string());
+            info.is_admin = is_admin;
+            info.db = db.to_string();
+        }
+    }
+
+    /// 标记连接开始（reading 为 true）或结束等待下一条命令
+    pub fn set_reading(&self, id: u64, reading: bool) {
+        if let Ok(mut clients) = self.clients.lock()
+            && let Some(client) = clients.get_mut(&id)
+        {
+            client.reading_since = reading.then(Instant::now);
+        }
+    }
+
+    /// 断开等待命令超过 max 的 TCP 连接，返回本次断开的 (id, 对端地址)；
+    /// 对端已经失联的半开连接不会再发来数据，连接线程会一直阻塞在读上
+    pub fn reap_blocked(&self, max: Duration) -> Vec<(u64, String)> {
+        let Ok(mut clients) = self.clients.lock() else {
+            return Vec::new();
+        };
+        let mut reaped = Vec::new();
+        for client in clients.values_mut() {
+            let (Some(since), Some(stream)) = (client.reading_since, &client.stream) else {
+                continue;
+            };
+            if since.elapsed() > max {
+                let _ = stream.shutdown(Shutdown::Both);
+                client.reading_since = None;
+                reaped.push((client.info.id, client.info.peer_addr.clone()));
+            }
+        }
+        self.reaped.fetch_add(reaped.len() as u64, Ordering::SeqCst);
+        reaped
+    }
+
+    /// 启动以来被 reap_blocked 断开的连接数
+    pub fn reaped(&self) -> u64 {
+        self.reaped.load(Ordering::SeqCst)
+    }
+
+    /// 按连接 id 排列的所有连接
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let Ok(clients) = self.clients.lock() else {
+            return Vec::new();
+        };
+        let mut list: Vec<ClientInfo> = clients.values().map(|c| c.info.clone()).collect();
+        list.sort_by_key(|info| info.id);
+        list
+    }
+
+    /// 断开连接；连接线程随后读到 EOF 并自行注销
+    pub fn kill(&self, id: u64) -> Result<(), String> {
+        let clients = self.clients.lock().map_err(|e| e.to_string())?;
+        match clients.get(&id) {
+            Some(Client { stream: Some(stream), .. }) => stream.shutdown(Shutdown::Both).map_err(|e| e.to_string()),
+            Some(_) => Err(format!("Client {} cannot be killed", id)),
+            None => Err(format!("No such client: {}", id)),
+        }
+    }
+
+    /// 对所有 TCP 连接执行 shutdown，返回连接数
+    pub fn shutdown_all(&self, how: Shutdown) -> Result<usize, String> {
+        let clients = self.clients.lock().map_err(|e| e.to_string())?;
+        for stream in clients.values().filter_map(|c| c.stream.as_ref()) {
+            let _ = stream.shutdown(how);
+        }
+        Ok(clients.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,65 @@
+//! 过期、租约等依赖时间的功能使用的时钟
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::fmt;
+
+/// 时钟读数：monotonic 只用于计算间隔，wall_ms 为 Unix 时间（毫秒），用于需要持久化或跨进程比较的时刻
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTime {
+    pub monotonic: Duration,
+    pub wall_ms: u64,
+}
+
+/// 过期、租约、清理周期等依赖时间的功能使用的时钟，测试中用 MockClock 代替真实时间
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> ClockTime;
+
+    fn now_ms(&self) -> u64 {
+        self.now().wall_ms
+    }
+}
+
+/// 系统时钟，monotonic 从进程内第一次读取时开始计时
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> ClockTime {
+        static START: OnceLock<Instant> = OnceLock::new();
+        ClockTime {
+            monotonic: START.get_or_init(Instant::now).elapsed(),
+            wall_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+        }
+    }
+}
+
+/// 只在 advance 时前进的时钟，两个读数同步前进
+#[derive(Debug, Default)]
+pub struct MockClock {
+    monotonic_ms: AtomicU64,
+    wall_ms: AtomicU64,
+}
+
+impl MockClock {
+    /// 墙钟时间从 wall_ms 开始
+    pub fn new(wall_ms: u64) -> Self {
+        MockClock { monotonic_ms: AtomicU64::new(0), wall_ms: AtomicU64::new(wall_ms) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let ms = by.as_millis() as u64;
+        self.monotonic_ms.fetch_add(ms, Ordering::SeqCst);
+        self.wall_ms.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> ClockTime {
+        ClockTime {
+            monotonic: Duration::from_millis(self.monotonic_ms.load(Ordering::SeqCst)),
+            wall_ms: self.wall_ms.load(Ordering::SeqCst),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! 请求的截止时间
+//!
+//! 协商了 envelope 的客户端在请求中附带 deadline_ms（见 protocol::Request），表示自己最多等待多久；
+//! 服务器读到请求时把它换算成 Deadline，在执行前和长操作的检查点上检查，过期后放弃剩余的工作并
+//! 返回 DeadlineExceeded 错误，不再为已经放弃等待的客户端继续占用存储锁。
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// 请求必须完成的时刻，NONE 表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub const NONE: Deadline = Deadline(None);
+
+    /// 从现在起 timeout 之后
+    pub fn after(timeout: Duration) -> Self {
+        Deadline(Instant::now().checked_add(timeout))
+    }
+
+    /// 请求中的 deadline_ms，从现在开始计算
+    pub fn from_ms(deadline_ms: Option<u64>) -> Self {
+        deadline_ms.map_or(Deadline::NONE, |ms| Deadline::after(Duration::from_millis(ms)))
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn expired(&self) -> bool {
+        self.0.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// 已过期时返回 DeadlineExceeded 错误，what 为被放弃的操作
+    pub fn check(&self, what: &str) -> Result<(), String> {
+        match self.expired() {
+            true => Err(format!("DeadlineExceeded: {} abandoned, the client stopped waiting", what)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// 每次写入前检查截止时间的 Write 包装，过期后写入返回 TimedOut 错误；
+/// 用于逐行写出的备份，每行都是一个检查点
+pub struct DeadlineWriter<W> {
+    inner: W,
+    deadline: Deadline,
+    what: &'static str,
+}
+
+impl<W: Write> DeadlineWriter<W> {
+    pub fn new(inner: W, deadline: Deadline, what: &'static str) -> Self {
+        DeadlineWriter { inner, deadline, what }
+    }
+}
+
+impl<W: Write> Write for DeadlineWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.deadline.check(self.what).map_err(|e| io::Error::new(io::ErrorKind::TimedOut, e))?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
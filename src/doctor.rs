@@ -0,0 +1,354 @@
+//! 环境自检
+//!
+//! diagnose 检查数据目录和监听地址是否可用：目录能否创建和写入、磁盘剩余空间、
+//! 锁文件、快照和段文件能否解析、格式版本、地址能否绑定以及系统时钟，每项给出
+//! 严重程度和修复建议。kv-server --doctor 打印完整报告；正常启动时只执行其中
+//! 不读数据文件、不绑定地址的几项（startup_checks），把问题写入日志。
+
+use crate::lockfile::{self, LockStatus};
+use crate::migration::{self, FORMAT_VERSION};
+use crate::storage;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 剩余空间低于此值时报 Error
+pub const MIN_FREE_BYTES: u64 = 64 << 20;
+/// 剩余空间低于此值时报 Warning
+pub const LOW_FREE_BYTES: u64 = 1 << 30;
+/// 早于此时间（2020-01-01）的系统时钟视为没有设置
+pub const MIN_SANE_TIME_MS: u64 = 1_577_836_800_000;
+/// 文件时间比当前时间晚超过此值时认为时钟被回拨过
+pub const CLOCK_SKEW_TOLERANCE: Duration = Duration::from_secs(60);
+
+/// 检查结果的严重程度，Error 表示服务器无法正常启动或运行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Severity::Info => "OK",
+            Severity::Warning => "WARN",
+            Severity::Error => "ERROR",
+        })
+    }
+}
+
+/// 一项检查的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 检查项名称，如 "directory"、"address"
+    pub check: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// 建议的修复方法
+    pub fix: Option<String>,
+}
+
+impl Diagnostic {
+    fn info(check: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic { check, severity: Severity::Info, message: message.into(), fix: None }
+    }
+
+    fn warning(check: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Diagnostic { check, severity: Severity::Warning, message: message.into(), fix: Some(fix.into()) }
+    }
+
+    fn error(check: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Diagnostic { check, severity: Severity::Error, message: message.into(), fix: Some(fix.into()) }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>5}] {}: {}", self.severity, self.check, self.message)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n        fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// 结果中是否有 Error 级别的项
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// 完整检查；data_path 为 None（纯内存模式）时只检查地址和时钟
+pub fn diagnose(data_path: Option<&Path>, addr: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    if let Some(dir) = data_path {
+        let usable = check_directory(dir, &mut diagnostics);
+        check_disk_space(dir, &mut diagnostics);
+        if usable && dir.is_dir() {
+            check_lock(dir, &mut diagnostics);
+            check_format_version(dir, &mut diagnostics);
+            check_data_files(dir, &mut diagnostics);
+        }
+    }
+    check_address(addr, &mut diagnostics);
+    check_clock(data_path, &mut diagnostics);
+    diagnostics
+}
+
+/// 启动时执行的简短检查：目录、磁盘空间、格式版本和时钟，不解析数据文件也不绑定地址
+pub fn startup_checks(data_path: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    if check_directory(data_path, &mut diagnostics) && data_path.is_dir() {
+        check_format_version(data_path, &mut diagnostics);
+    }
+    check_disk_space(data_path, &mut diagnostics);
+    check_clock(Some(data_path), &mut diagnostics);
+    diagnostics
+}
+
+// 目录存在时检查能否写入，不存在时检查最近的已有上级目录能否写入；返回目录是否可用
+fn check_directory(dir: &Path, out: &mut Vec<Diagnostic>) -> bool {
+    const CHECK: &str = "directory";
+    if dir.exists() && !dir.is_dir() {
+        out.push(Diagnostic::error(
+            CHECK,
+            format!("{} exists but is not a directory", dir.display()),
+            "remove the file or choose another --data-dir",
+        ));
+        return false;
+    }
+    let (target, exists) = match dir.is_dir() {
+        true => (dir.to_path_buf(), true),
+        false => match existing_ancestor(dir) {
+            Some(ancestor) => (ancestor, false),
+            None => {
+                out.push(Diagnostic::error(
+                    CHECK,
+                    format!("no existing parent directory for {}", dir.display()),
+                    "create the parent directory or choose another --data-dir",
+                ));
+                return false;
+            }
+        },
+    };
+    match probe_writable(&target) {
+        Ok(()) if exists => {
+            out.push(Diagnostic::info(CHECK, format!("{} is writable", dir.display())));
+            true
+        }
+        Ok(()) => {
+            out.push(Diagnostic::info(CHECK, format!("{} does not exist and will be created", dir.display())));
+            true
+        }
+        Err(e) => {
+            let message = match exists {
+                true => format!("{} is not writable: {}", dir.display(), e),
+                false => format!("{} cannot be created, {} is not writable: {}", dir.display(), target.display(), e),
+            };
+            out.push(Diagnostic::error(
+                CHECK,
+                message,
+                format!("grant the server user write access to {} or choose another --data-dir", target.display()),
+            ));
+            false
+        }
+    }
+}
+
+fn existing_ancestor(dir: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(dir).ok()?;
+    absolute.ancestors().skip(1).find(|p| p.is_dir()).map(Path::to_path_buf)
+}
+
+// 在 dir 中创建并删除一个临时文件
+fn probe_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".doctor-probe-{}", std::process::id()));
+    fs::write(&probe, b"probe")?;
+    fs::remove_file(&probe)
+}
+
+fn check_disk_space(dir: &Path, out: &mut Vec<Diagnostic>) {
+    const CHECK: &str = "disk_space";
+    let target = if dir.is_dir() { Some(dir.to_path_buf()) } else { existing_ancestor(dir) };
+    let Some(target) = target else { return };
+    match free_bytes(&target) {
+        Ok(Some(free)) if free < MIN_FREE_BYTES => out.push(Diagnostic::error(
+            CHECK,
+            format!("only {} bytes free on the volume of {}", free, target.display()),
+            "free up disk space or move the data directory to a larger volume",
+        )),
+        Ok(Some(free)) if free < LOW_FREE_BYTES => out.push(Diagnostic::warning(
+            CHECK,
+            format!("{} bytes free on the volume of {}", free, target.display()),
+            "free up disk space before the data grows",
+        )),
+        Ok(Some(free)) => out.push(Diagnostic::info(CHECK, format!("{} bytes free", free))),
+        Ok(None) => out.push(Diagnostic::info(CHECK, "free space is not available on this platform")),
+        Err(e) => out.push(Diagnostic::warning(
+            CHECK,
+            format!("failed to query free space of {}: {}", target.display(), e),
+            "check the volume manually",
+        )),
+    }
+}
+
+/// 目录所在卷上非特权用户可用的字节数；不支持的平台返回 None
+#[cfg(unix)]
+fn free_bytes(dir: &Path) -> io::Result<Option<u64>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    // SAFETY: path 是以 NUL 结尾的字符串，stat 是足够大小的本地变量
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+fn free_bytes(_dir: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+fn check_lock(dir: &Path, out: &mut Vec<Diagnostic>) {
+    const CHECK: &str = "lock";
+    out.push(match lockfile::lock_status(dir) {
+        Ok(LockStatus::Free) => Diagnostic::info(CHECK, "data directory is not locked"),
+        Ok(LockStatus::Stale(owner)) => Diagnostic::warning(
+            CHECK,
+            format!("stale lock of pid {} acquired at {}, the owner did not shut down cleanly", owner.pid, owner.acquired_at_ms),
+            "no action needed, the next server takes the lock over",
+        ),
+        Ok(LockStatus::Held { owner: Some(owner), owner_alive: true }) => Diagnostic::error(
+            CHECK,
+            format!("data directory is locked by running pid {}", owner.pid),
+            "stop the other server or choose another --data-dir",
+        ),
+        Ok(LockStatus::Held { owner: Some(owner), owner_alive: false }) => Diagnostic::error(
+            CHECK,
+            format!("data directory is locked although pid {} has exited", owner.pid),
+            "make sure no other server uses this directory, then start with --force-unlock",
+        ),
+        Ok(LockStatus::Held { owner: None, .. }) => Diagnostic::error(
+            CHECK,
+            "data directory is locked by an unknown owner",
+            "stop the other server or choose another --data-dir",
+        ),
+        Err(e) => Diagnostic::warning(
+            CHECK,
+            format!("failed to inspect {}: {}", lockfile::LOCK_FILE, e),
+            "check the permissions of the lock file",
+        ),
+    });
+}
+
+fn check_format_version(dir: &Path, out: &mut Vec<Diagnostic>) {
+    const CHECK: &str = "format_version";
+    out.push(match migration::read_version(dir) {
+        Ok(None) => Diagnostic::info(CHECK, format!("empty directory, will use format {}", FORMAT_VERSION)),
+        Ok(Some(version)) if version == FORMAT_VERSION => Diagnostic::info(CHECK, format!("format {}", version)),
+        Ok(Some(version)) if version > FORMAT_VERSION => Diagnostic::error(
+            CHECK,
+            format!("format {} is newer than the supported format {}", version, FORMAT_VERSION),
+            "upgrade the server or restore the directory from a compatible backup",
+        ),
+        Ok(Some(version)) => Diagnostic::warning(
+            CHECK,
+            format!("format {} will be migrated to {} on open", version, FORMAT_VERSION),
+            "back up the data directory before starting the server",
+        ),
+        Err(e) => Diagnostic::error(CHECK, e, format!("fix or remove {}", migration::FORMAT_VERSION_FILE)),
+    });
+}
+
+fn check_data_files(dir: &Path, out: &mut Vec<Diagnostic>) {
+    const CHECK: &str = "data_files";
+    let checks = match storage::inspect_data_files(dir) {
+        Ok(checks) => checks,
+        Err(e) => {
+            out.push(Diagnostic::error(CHECK, e, "restore the data directory from a backup"));
+            return;
+        }
+    };
+    if checks.is_empty() {
+        out.push(Diagnostic::info(CHECK, "no snapshot or segment files"));
+    }
+    for file in checks {
+        let last = file.records;
+        out.push(match (&file.error, file.corrupt_records.as_slice()) {
+            (Some(e), _) => Diagnostic::error(CHECK, e.clone(), "restore the data directory from a backup"),
+            (None, []) if file.records == 0 => {
+                Diagnostic::info(CHECK, format!("{}: {} entries, {} bytes", file.name, file.entries, file.bytes))
+            }
+            (None, []) => Diagnostic::info(CHECK, format!("{}: {} records, {} bytes", file.name, file.entries, file.bytes)),
+            // 只有最后一条记录不完整是写到一半时崩溃，加载时会跳过
+            (None, [only]) if *only == last => Diagnostic::warning(
+                CHECK,
+                format!("{}: last of {} records is incomplete", file.name, file.records),
+                "no action needed, the record is skipped on open",
+            ),
+            (None, corrupt) => Diagnostic::error(
+                CHECK,
+                format!("{}: {} of {} records are corrupt (first at record {})", file.name, corrupt.len(), file.records, corrupt[0]),
+                "open the storage with salvage to skip them, or restore from a backup",
+            ),
+        });
+    }
+}
+
+fn check_address(addr: &str, out: &mut Vec<Diagnostic>) {
+    const CHECK: &str = "address";
+    out.push(match TcpListener::bind(addr) {
+        Ok(_) => Diagnostic::info(CHECK, format!("{} can be bound", addr)),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => Diagnostic::error(
+            CHECK,
+            format!("{} is already in use", addr),
+            "stop the process using the port or choose another --addr",
+        ),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Diagnostic::error(
+            CHECK,
+            format!("no permission to bind {}", addr),
+            "use a port above 1024 or grant the server the capability to bind it",
+        ),
+        Err(e) => Diagnostic::error(CHECK, format!("cannot bind {}: {}", addr, e), "check the --addr value"),
+    });
+}
+
+// 系统时间要晚于 2020 年，且不早于数据目录中文件的修改时间
+fn check_clock(data_path: Option<&Path>, out: &mut Vec<Diagnostic>) {
+    const CHECK: &str = "clock";
+    let now = SystemTime::now();
+    let now_ms = now.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    if now_ms < MIN_SANE_TIME_MS {
+        out.push(Diagnostic::error(
+            CHECK,
+            format!("system time {} ms is before 2020", now_ms),
+            "synchronize the system clock, TTLs and lease times depend on it",
+        ));
+        return;
+    }
+    let newest = data_path
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .max();
+    match newest {
+        Some(modified) if modified > now + CLOCK_SKEW_TOLERANCE => {
+            let ahead = modified.duration_since(now).unwrap_or_default();
+            out.push(Diagnostic::warning(
+                CHECK,
+                format!("data files were modified {}s in the future, the clock may have gone backwards", ahead.as_secs()),
+                "synchronize the system clock before starting the server",
+            ));
+        }
+        _ => out.push(Diagnostic::info(CHECK, "system time looks sane")),
+    }
+}
@@ -0,0 +1,102 @@
+//! 最近的错误事件
+//!
+//! 服务器在后台刷盘失败、请求无法解析等情况下只打印日志，无人值守时很难事后排查。
+//! ErrorLog 在内存中保留最近的若干条错误事件，可以通过 RecentErrors 命令读取。
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 默认保留的错误事件数
+pub const ERROR_LOG_CAPACITY: usize = 256;
+
+/// 错误事件的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCategory {
+    Flush,
+    Compaction,
+    /// 打开存储时跳过的损坏记录
+    Recovery,
+    /// 过期键、锁、回收站等后台清理
+    Sweep,
+    /// 无法解析的请求
+    Request,
+    /// 因内存预算或配额被拒绝的写入
+    Rejected,
+    /// panic 后被停用的写入观察者，以及 oplog 的写入失败
+    Observer,
+    /// 被服务器断开的连接，例如读阻塞超过上限的半开连接
+    Connection,
+}
+
+impl ErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Flush => "flush",
+            ErrorCategory::Compaction => "compaction",
+            ErrorCategory::Recovery => "recovery",
+            ErrorCategory::Sweep => "sweep",
+            ErrorCategory::Request => "request",
+            ErrorCategory::Rejected => "rejected",
+            ErrorCategory::Observer => "observer",
+            ErrorCategory::Connection => "connection",
+        }
+    }
+}
+
+/// 一条错误事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorEvent {
+    pub timestamp_ms: u64,
+    pub category: ErrorCategory,
+    pub message: String,
+}
+
+/// 有界的错误事件环形缓冲区，满了以后丢弃最旧的事件
+#[derive(Debug)]
+pub struct ErrorLog {
+    events: Mutex<VecDeque<ErrorEvent>>,
+    capacity: usize,
+    // 启动以来记录的事件总数，包括已被丢弃的
+    total: AtomicU64,
+    clock: Arc<dyn Clock>,
+}
+
+impl ErrorLog {
+    pub fn new(capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        ErrorLog {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            total: AtomicU64::new(0),
+            clock,
+        }
+    }
+
+    pub fn record(&self, category: ErrorCategory, message: impl Into<String>) {
+        let event = ErrorEvent { timestamp_ms: self.clock.now_ms(), category, message: message.into() };
+        self.total.fetch_add(1, Ordering::Relaxed);
+        // 记录错误的线程可能正是在 panic 中，锁中毒时仍然继续使用缓冲区
+        let mut events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        if self.capacity > 0 {
+            events.push_back(event);
+        }
+    }
+
+    /// 最近的至多 count 条事件，新的在前
+    pub fn recent(&self, count: usize) -> Vec<ErrorEvent> {
+        let events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        events.iter().rev().take(count).cloned().collect()
+    }
+
+    /// 启动以来记录的事件总数
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::protocol::Modify;
+use crate::storage::StandaloneStorage;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 组提交配置，见 ServerConfig::group_commit
+#[derive(Debug, Clone)]
+pub struct GroupCommitConfig {
+    /// 收到第一个写请求后最多等待这么久，收集更多请求一起提交
+    pub window: Duration,
+    /// 一次提交最多合并的写请求数
+    pub max_batch: usize,
+    /// 等待提交的请求队列容量，队列满时写请求阻塞
+    pub queue_capacity: usize,
+}
+
+impl Default for GroupCommitConfig {
+    fn default() -> Self {
+        GroupCommitConfig {
+            window: Duration::from_micros(200),
+            max_batch: 128,
+            queue_capacity: 1024,
+        }
+    }
+}
+
+/// 组提交的累计统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupCommitStats {
+    /// 提交的合并批次数
+    pub batches: u64,
+    /// 提交的写请求数
+    pub writes: u64,
+}
+
+struct Pending {
+    batch: Vec<Modify>,
+    done: SyncSender<Result<(), String>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    batches: AtomicU64,
+    writes: AtomicU64,
+}
+
+/// 把并发的写请求交给单个提交线程，合并成一次 storage.write
+/// 提交线程在句柄被丢弃后处理完队列中的请求并退出
+pub struct GroupCommitter {
+    queue: SyncSender<Pending>,
+    counters: Arc<Counters>,
+}
+
+impl GroupCommitter {
+    pub fn start(storage: Arc<StandaloneStorage>, config: GroupCommitConfig) -> Self {
+        let (queue, pending) = mpsc::sync_channel(config.queue_capacity.max(1));
+        let counters = Arc::new(Counters::default());
+        let thread_counters = Arc::clone(&counters);
+        thread::spawn(move || run_committer(&storage, &config, &pending, &thread_counters));
+        GroupCommitter { queue, counters }
+    }
+
+    /// 提交一批修改并等待结果；批次仍然原子地生效
+    pub fn write(&self, batch: Vec<Modify>) -> Result<(), String> {
+        let (done, result) = mpsc::sync_channel(1);
+        self.queue
+            .send(Pending { batch, done })
+            .map_err(|_| "Group committer has stopped".to_string())?;
+        result.recv().map_err(|_| "Group committer has stopped".to_string())?
+    }
+
+    pub fn stats(&self) -> GroupCommitStats {
+        GroupCommitStats {
+            batches: self.counters.batches.load(Ordering::SeqCst),
+            writes: self.counters.writes.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn run_committer(storage: &StandaloneStorage, config: &GroupCommitConfig, pending: &Receiver<Pending>, counters: &Counters) {
+    while let Ok(first) = pending.recv() {
+        let mut group = vec![first];
+        let deadline = Instant::now() + config.window;
+        while group.len() < config.max_batch {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match pending.recv_timeout(timeout) {
+                Ok(next) => group.push(next),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        counters.batches.fetch_add(1, Ordering::SeqCst);
+        counters.writes.fetch_add(group.len() as u64, Ordering::SeqCst);
+        commit(storage, group);
+    }
+}
+
+/// 合并提交：各请求的批次拼接成一次 storage.write，每个批次仍然整体生效；
+/// 合并后的批次失败时（例如超出内存预算）逐个重试，让每个请求得到自己的结果
+fn commit(storage: &StandaloneStorage, group: Vec<Pending>) {
+    if group.len() == 1 {
+        let Pending { batch, done } = group.into_iter().next().unwrap();
+        let _ = done.send(storage.write(batch));
+        return;
+    }
+
+    let combined: Vec<Modify> = group.iter().flat_map(|p| p.batch.iter().cloned()).collect();
+    if storage.write(combined).is_ok() {
+        for Pending { done, .. } in group {
+            let _ = done.send(Ok(()));
+        }
+        return;
+    }
+    for Pending { batch, done } in group {
+        let _ = done.send(storage.write(batch));
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// 每个 2 的幂区间划分的子桶数（2^SUB_BITS），相对误差约为 1/16
+const SUB_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+// 可区分的最大值的指数，更大的值都记入最后一个桶（微秒计约 25 天）
+const MAX_EXPONENT: u32 = 40;
+
+/// 桶的总数，直方图的内存占用固定为 BUCKETS 个计数器
+pub const BUCKETS: usize = SUB_BUCKETS + (MAX_EXPONENT - SUB_BITS + 1) as usize * SUB_BUCKETS;
+
+/// 值所在的桶：小于 SUB_BUCKETS 的值各占一个桶，之后每个 2 的幂区间等分为 SUB_BUCKETS 个桶
+pub fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let exponent = (63 - value.leading_zeros()).min(MAX_EXPONENT);
+    if exponent == MAX_EXPONENT && value >> MAX_EXPONENT > 1 {
+        return BUCKETS - 1;
+    }
+    let sub = (value >> (exponent - SUB_BITS)) as usize - SUB_BUCKETS;
+    SUB_BUCKETS + (exponent - SUB_BITS) as usize * SUB_BUCKETS + sub
+}
+
+/// 桶的下界（包含）
+pub fn bucket_lower(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let exponent = ((index - SUB_BUCKETS) / SUB_BUCKETS) as u32 + SUB_BITS;
+    let sub = ((index - SUB_BUCKETS) % SUB_BUCKETS + SUB_BUCKETS) as u64;
+    sub << (exponent - SUB_BITS)
+}
+
+/// 桶的上界（不包含）
+pub fn bucket_upper(index: usize) -> u64 {
+    if index + 1 == BUCKETS {
+        return u64::MAX;
+    }
+    bucket_lower(index + 1)
+}
+
+/// 对数分桶的直方图，记录只做原子加法，可以在请求路径上并发使用
+pub struct Histogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, value: u64) {
+        self.buckets[bucket_index(value)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// 以微秒记录耗时
+    pub fn record_duration(&self, elapsed: Duration) {
+        self.record(elapsed.as_micros().min(u64::MAX as u128) as u64);
+    }
+
+    /// 把 other 的记录累加到当前直方图
+    pub fn merge(&self, other: &Histogram) {
+        for (bucket, theirs) in self.buckets.iter().zip(&other.buckets) {
+            bucket.fetch_add(theirs.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.count.fetch_add(other.count(), Ordering::Relaxed);
+        self.sum.fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max.fetch_max(other.max(), Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            count => self.sum.load(Ordering::Relaxed) as f64 / count as f64,
+        }
+    }
+
+    /// q 分位数（0.0 ~ 1.0），在所在桶内按计数线性插值，结果不超过记录到的最大值
+    pub fn percentile(&self, q: f64) -> u64 {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        percentile_of(&counts, q, self.max())
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let max = self.max();
+        LatencySummary {
+            count: counts.iter().sum(),
+            p50: percentile_of(&counts, 0.50, max),
+            p95: percentile_of(&counts, 0.95, max),
+            p99: percentile_of(&counts, 0.99, max),
+            max,
+        }
+    }
+}
+
+fn percentile_of(counts: &[u64], q: f64, max: u64) -> u64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+    // 第 target 个样本（从 1 开始）
+    let target = (q.clamp(0.0, 1.0) * total as f64).ceil().max(1.0);
+    let mut before = 0u64;
+    for (index, &n) in counts.iter().enumerate() {
+        if n == 0 {
+            continue;
+        }
+        if (before + n) as f64 >= target {
+            // 桶内的 n 个样本视为均匀分布在 [lower, last] 中，last 为桶内最大可能值（不超过 max）
+            let (lower, last) = (bucket_lower(index), (bucket_upper(index) - 1).min(max));
+            let fraction = (target - before as f64) / n as f64;
+            let value = lower as f64 + fraction * last.saturating_sub(lower) as f64;
+            return (value as u64).max(lower).min(max);
+        }
+        before += n;
+    }
+    max
+}
+
+/// 直方图的分位数摘要，单位与记录的值相同（耗时为微秒）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+/// 按名称分组的直方图，名称集合固定（如命令类型），第一次记录时创建
+#[derive(Default)]
+pub struct HistogramSet {
+    histograms: RwLock<HashMap<&'static str, Histogram>>,
+}
+
+impl HistogramSet {
+    pub fn record_duration(&self, name: &'static str, elapsed: Duration) {
+        if let Some(histogram) = self.histograms.read().unwrap().get(name) {
+            histogram.record_duration(elapsed);
+            return;
+        }
+        self.histograms.write().unwrap().entry(name).or_default().record_duration(elapsed);
+    }
+
+    /// 按名称排序的摘要，没有记录的名称不出现
+    pub fn summaries(&self) -> Vec<(String, LatencySummary)> {
+        let histograms = self.histograms.read().unwrap();
+        let mut summaries: Vec<(String, LatencySummary)> = histograms
+            .iter()
+            .map(|(name, h)| (name.to_string(), h.summary()))
+            .filter(|(_, s)| s.count > 0)
+            .collect();
+        summaries.sort_by(|a, b| a.0.cmp(&b.0));
+        summaries
+    }
+
+    pub fn reset(&self) {
+        for histogram in self.histograms.read().unwrap().values() {
+            histogram.reset();
+        }
+    }
+}
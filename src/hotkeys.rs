@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::protocol::Bytes;
+
+// count-min sketch 的行数和每行宽度
+const SKETCH_DEPTH: usize = 4;
+const SKETCH_WIDTH: usize = 2048;
+// top-K 候选集的容量，HotKeys 最多返回这么多个键
+pub const MAX_TRACKED_KEYS: usize = 64;
+
+/// 热点键的近似访问统计，计数已按采样率放大
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HotKey {
+    pub cf: String,
+    pub key: Bytes,
+    /// count-min 估计的总访问次数
+    pub count: u64,
+    /// 进入候选集之后采样到的读写次数
+    pub reads: u64,
+    pub writes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Default)]
+struct Candidate {
+    estimate: u32,
+    reads: u64,
+    writes: u64,
+}
+
+/// 基于采样的热点键统计：每 sample_every 次访问记录一次，
+/// 计数写入 count-min sketch，估计值最高的键保存在有界候选集中
+pub struct HotKeyTracker {
+    sample_every: u64,
+    // 未被采样的请求只对它做一次 fetch_add
+    ticks: AtomicU64,
+    sketch: Vec<AtomicU32>,
+    top: Mutex<HashMap<(String, Vec<u8>), Candidate>>,
+}
+
+impl HotKeyTracker {
+    pub fn new(sample_every: u64) -> Self {
+        HotKeyTracker {
+            sample_every: sample_every.max(1),
+            ticks: AtomicU64::new(0),
+            sketch: (0..SKETCH_DEPTH * SKETCH_WIDTH).map(|_| AtomicU32::new(0)).collect(),
+            top: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, cf: &str, key: &[u8], access: Access) {
+        if !self.ticks.fetch_add(1, Ordering::Relaxed).is_multiple_of(self.sample_every) {
+            return;
+        }
+
+        let mut estimate = u32::MAX;
+        for row in 0..SKETCH_DEPTH {
+            let cell = &self.sketch[row * SKETCH_WIDTH + slot(row, cf, key)];
+            estimate = estimate.min(cell.fetch_add(1, Ordering::Relaxed).saturating_add(1));
+        }
+
+        let mut top = self.top.lock().unwrap();
+        let id = (cf.to_string(), key.to_vec());
+        if !top.contains_key(&id) && top.len() >= MAX_TRACKED_KEYS {
+            // 候选集已满：只有估计值超过当前最小者时才替换它
+            let coldest = top
+                .iter()
+                .min_by_key(|(_, c)| c.estimate)
+                .map(|(id, c)| (id.clone(), c.estimate));
+            match coldest {
+                Some((coldest, min)) if min < estimate => {
+                    top.remove(&coldest);
+                }
+                _ => return,
+            }
+        }
+
+        let candidate = top.entry(id).or_default();
+        candidate.estimate = estimate;
+        match access {
+            Access::Read => candidate.reads += 1,
+            Access::Write => candidate.writes += 1,
+        }
+    }
+
+    /// 估计访问次数最多的 top_n 个键，从高到低排列
+    pub fn top(&self, top_n: usize) -> Vec<HotKey> {
+        let top = self.top.lock().unwrap();
+        let mut keys: Vec<HotKey> = top
+            .iter()
+            .map(|((cf, key), c)| HotKey {
+                cf: cf.clone(),
+                key: Bytes(key.clone()),
+                count: c.estimate as u64 * self.sample_every,
+                reads: c.reads * self.sample_every,
+                writes: c.writes * self.sample_every,
+            })
+            .collect();
+        keys.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.0.cmp(&b.key.0)));
+        keys.truncate(top_n);
+        keys
+    }
+
+    pub fn reset(&self) {
+        let mut top = self.top.lock().unwrap();
+        for cell in &self.sketch {
+            cell.store(0, Ordering::Relaxed);
+        }
+        top.clear();
+        self.ticks.store(0, Ordering::Relaxed);
+    }
+}
+
+fn slot(row: usize, cf: &str, key: &[u8]) -> usize {
+    let mut hasher = DefaultHasher::new();
+    row.hash(&mut hasher);
+    cf.hash(&mut hasher);
+    key.hash(&mut hasher);
+    (hasher.finish() % SKETCH_WIDTH as u64) as usize
+}
@@ -0,0 +1,185 @@
+//! 写命令的幂等键缓存
+//!
+//! 客户端用 Command::Idempotent 包装写命令，(令牌, 幂等键) 相同的重复请求
+//! 直接返回第一次执行时的响应，不再执行。缓存按条目数和空闲时间双重限制，
+//! 淘汰的条目对应的请求再次到达时会被重新执行。
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::protocol::Response;
+
+/// 幂等键缓存的容量
+#[derive(Debug, Clone)]
+pub struct IdempotencyConfig {
+    /// 最多保留的响应数，超出时淘汰最久未使用的
+    pub max_entries: usize,
+    /// 响应在最后一次使用后保留这么久
+    pub retention: Duration,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig {
+            max_entries: 10_000,
+            retention: Duration::from_secs(300),
+        }
+    }
+}
+
+/// 幂等键缓存的累计统计，见 Response::Info
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyStats {
+    /// 当前缓存的响应数
+    pub entries: usize,
+    /// 直接返回缓存响应的重复请求数
+    pub replayed: u64,
+    /// 因超过 max_entries 被淘汰的条目数
+    pub evicted_by_count: u64,
+    /// 因超过 retention 被淘汰的条目数
+    pub evicted_by_age: u64,
+}
+
+/// begin 的结果
+pub(crate) enum Claim {
+    /// 第一次见到这个键，执行后调用 finish 或 abandon
+    New,
+    /// 已经执行过，返回当时的响应
+    Replay(Box<Response>),
+    /// 同一个键的请求正在其他连接上执行
+    InProgress,
+}
+
+enum Slot {
+    InProgress,
+    // 序列化后的响应；Response 没有实现 Clone
+    Done(Vec<u8>),
+}
+
+struct Entry {
+    slot: Slot,
+    last_used_ms: u64,
+    // 在 order 中的位置
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<(u64, u64), Entry>,
+    // 按最后使用时间排列，最久未使用的在前
+    order: BTreeMap<u64, (u64, u64)>,
+    next_tick: u64,
+    stats: IdempotencyStats,
+}
+
+impl Inner {
+    fn touch(&mut self, id: (u64, u64), now_ms: u64) {
+        self.next_tick += 1;
+        let tick = self.next_tick;
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.order.remove(&entry.tick);
+            entry.tick = tick;
+            entry.last_used_ms = now_ms;
+            self.order.insert(tick, id);
+        }
+    }
+
+    fn remove(&mut self, id: (u64, u64)) {
+        if let Some(entry) = self.entries.remove(&id) {
+            self.order.remove(&entry.tick);
+        }
+    }
+
+    // 先按空闲时间淘汰，再按容量淘汰；order 按最后使用时间排列，只需检查开头
+    fn evict(&mut self, now_ms: u64, config: &IdempotencyConfig) {
+        let retention_ms = config.retention.as_millis() as u64;
+        let mut skipped = 0;
+        while skipped < self.order.len() {
+            let Some((&tick, &id)) = self.order.first_key_value() else { break };
+            let entry = &self.entries[&id];
+            let expired = now_ms.saturating_sub(entry.last_used_ms) >= retention_ms;
+            let over = self.entries.len() > config.max_entries;
+            if !expired && !over {
+                break;
+            }
+            // 执行中的条目不能淘汰，否则重复请求会再执行一次；把它移到末尾，完成时会重新排序
+            if matches!(entry.slot, Slot::InProgress) {
+                self.next_tick += 1;
+                let moved = self.next_tick;
+                self.order.remove(&tick);
+                self.order.insert(moved, id);
+                self.entries.get_mut(&id).expect("ordered entry exists").tick = moved;
+                skipped += 1;
+                continue;
+            }
+            self.remove(id);
+            if expired {
+                self.stats.evicted_by_age += 1;
+            } else {
+                self.stats.evicted_by_count += 1;
+            }
+        }
+    }
+}
+
+/// 最近执行过的幂等请求及其响应
+pub(crate) struct IdempotencyCache {
+    config: IdempotencyConfig,
+    inner: Mutex<Inner>,
+}
+
+impl IdempotencyCache {
+    pub(crate) fn new(config: IdempotencyConfig) -> Self {
+        IdempotencyCache { config, inner: Mutex::default() }
+    }
+
+    pub(crate) fn begin(&self, token: u64, key: u64, now_ms: u64) -> Claim {
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict(now_ms, &self.config);
+        let id = (token, key);
+        let claim = match inner.entries.get(&id).map(|e| &e.slot) {
+            Some(Slot::InProgress) => return Claim::InProgress,
+            Some(Slot::Done(response)) => match serde_json::from_slice(response) {
+                Ok(response) => Claim::Replay(Box::new(response)),
+                Err(e) => Claim::Replay(Box::new(Response::Error(format!("Failed to decode stored response: {}", e)))),
+            },
+            None => {
+                inner.entries.insert(id, Entry { slot: Slot::InProgress, last_used_ms: now_ms, tick: 0 });
+                Claim::New
+            }
+        };
+        if matches!(claim, Claim::Replay(_)) {
+            inner.stats.replayed += 1;
+        }
+        inner.touch(id, now_ms);
+        inner.evict(now_ms, &self.config);
+        claim
+    }
+
+    /// 记录执行结果，之后同一个键的请求直接返回它
+    pub(crate) fn finish(&self, token: u64, key: u64, response: &Response, now_ms: u64) {
+        let Ok(bytes) = serde_json::to_vec(response) else {
+            return self.abandon(token, key);
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(entry) = inner.entries.get_mut(&(token, key)) {
+            entry.slot = Slot::Done(bytes);
+        }
+        inner.touch((token, key), now_ms);
+        inner.evict(now_ms, &self.config);
+    }
+
+    /// 放弃执行中的键，之后同一个键的请求会重新执行
+    pub(crate) fn abandon(&self, token: u64, key: u64) {
+        self.inner.lock().unwrap().remove((token, key));
+    }
+
+    pub(crate) fn stats(&self, now_ms: u64) -> IdempotencyStats {
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict(now_ms, &self.config);
+        IdempotencyStats { entries: inner.entries.len(), ..inner.stats.clone() }
+    }
+}
@@ -0,0 +1,84 @@
+//! TCP keepalive 设置
+//!
+//! 标准库不提供 keepalive 的选项，这里直接调用 setsockopt：开启 SO_KEEPALIVE，
+//! 连接空闲 interval 后开始探测，之后每隔 interval 探测一次，连续 KEEPALIVE_PROBES
+//! 次没有回应时内核断开连接，阻塞在读上的线程随即返回错误。非 unix 平台上不做任何事。
+
+use std::io;
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// 判定对端失联前发送的探测次数
+pub const KEEPALIVE_PROBES: u32 = 3;
+
+/// 开启 TCP keepalive，interval 按秒取整（至少 1 秒）
+pub fn set_keepalive(stream: &TcpStream, interval: Duration) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = stream.as_raw_fd();
+        let secs = interval.as_secs().clamp(1, libc::c_int::MAX as u64) as libc::c_int;
+        setsockopt(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, secs)?;
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, secs)?;
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios"))]
+        {
+            setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs)?;
+            setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, KEEPALIVE_PROBES as libc::c_int)?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (stream, interval);
+    Ok(())
+}
+
+/// 连接是否开启了 keepalive；非 unix 平台上总是 false
+pub fn keepalive_enabled(stream: &TcpStream) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: value 和 len 指向足够大小的本地变量
+        let rc = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_KEEPALIVE,
+                &mut value as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(value != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        Ok(false)
+    }
+}
+
+#[cfg(unix)]
+fn setsockopt(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> io::Result<()> {
+    // SAFETY: value 是一个 c_int，长度与传入的一致
+    let rc = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -1,13 +1,35 @@
 pub mod storage;
 pub mod common;
+pub mod protocol;
+pub mod api;
+pub mod clock;
 pub mod server;
+pub mod accesslog;
 pub mod client;
+pub mod signal;
+pub mod keepalive;
+pub mod lockfile;
+pub mod migration;
+pub mod doctor;
+pub mod hotkeys;
+pub mod clients;
+pub mod group_commit;
+pub mod runtime_config;
+pub mod deadline;
+pub mod histogram;
+pub mod errorlog;
+pub mod blob;
+pub mod rotation;
+pub mod sha256;
+pub mod acl;
+pub mod audit;
+pub mod observer;
+pub mod oplog;
+pub mod idempotency;
+pub mod pubsub;
+pub mod trace;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod testing;
 
-use std::{error::Error, result};
-
-/// 启动 server
-pub fn run_server(data_path: &str, addr: &str) -> Result<(), Box<dyn Error>> {
-    let server = server::KvServer::new(data_path)?;
-    server.start(addr)?;
-    Ok(())
-}
+pub use server::{run_config_with_shutdown, run_server, run_server_with_shutdown};
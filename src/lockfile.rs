@@ -0,0 +1,179 @@
+//! 数据目录锁
+//!
+//! 打开持久化存储时在数据目录下创建 LOCK 文件并加排他的建议锁（flock），
+//! 防止两个进程或同一进程中的两个存储实例同时写同一个目录。锁随文件描述符
+//! 关闭而释放，进程崩溃时由操作系统释放；文件中记录持有者的 pid 和加锁时间，
+//! 用于报错和识别崩溃遗留的锁文件。
+
+use serde::{Deserialize, Serialize};
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 数据目录下锁文件的名称
+pub const LOCK_FILE: &str = "LOCK";
+
+/// 锁文件中记录的持有者
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub acquired_at_ms: u64,
+}
+
+/// 持有中的数据目录锁，drop 时清空锁文件并释放锁
+#[derive(Debug)]
+pub struct DirLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl DirLock {
+    /// 获取 dir 的锁；目录被其他实例锁定时返回 DataDirLocked 错误。
+    /// force 为 true 时先删除已有的锁文件，原持有者（如果还在运行）将不再受保护
+    pub fn acquire(dir: &Path, force: bool) -> Result<DirLock, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+        let path = dir.join(LOCK_FILE);
+        if force {
+            match fs::remove_file(&path) {
+                Ok(()) => println!("Lock: removed {} (force unlock)", path.display()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to remove lock file: {}", e)),
+            }
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| format!("Failed to open lock file: {}", e))?;
+        let previous = read_owner(&mut file);
+
+        if !try_lock(&file).map_err(|e| format!("Failed to lock {}: {}", path.display(), e))? {
+            return Err(match previous {
+                Some(owner) if !process_alive(owner.pid) => format!(
+                    "DataDirLocked: pid {}, acquired_at {} ({}; pid has exited but the lock is still held, \
+                     use --force-unlock if no other server uses this directory)",
+                    owner.pid,
+                    owner.acquired_at_ms,
+                    path.display()
+                ),
+                Some(owner) => format!(
+                    "DataDirLocked: pid {}, acquired_at {} ({})",
+                    owner.pid,
+                    owner.acquired_at_ms,
+                    path.display()
+                ),
+                None => format!("DataDirLocked: owner unknown ({})", path.display()),
+            });
+        }
+        // 拿到了锁但文件里还有记录，说明上一个持有者没有正常释放
+        if let Some(owner) = previous {
+            println!("Lock: taking over stale lock of pid {} acquired at {}", owner.pid, owner.acquired_at_ms);
+        }
+
+        let owner = LockOwner { pid: std::process::id(), acquired_at_ms: now_ms() };
+        let json = serde_json::to_vec(&owner).map_err(|e| e.to_string())?;
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(&json))
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to write lock file: {}", e))?;
+        Ok(DirLock { file, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        // 清空记录后关闭文件即释放锁；文件本身保留，避免与正在打开它的进程竞争
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// 读取 dir 下锁文件记录的持有者；没有锁文件或文件为空时返回 None
+pub fn read_lock_owner(dir: &Path) -> Option<LockOwner> {
+    File::open(dir.join(LOCK_FILE)).ok().and_then(|mut file| read_owner(&mut file))
+}
+
+/// 数据目录锁的状态，见 lock_status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockStatus {
+    /// 没有锁文件，或锁文件中没有记录且没有被锁定
+    Free,
+    /// 锁文件中有记录但没有被锁定，上一个持有者没有正常释放
+    Stale(LockOwner),
+    /// 目录正被某个实例锁定；owner_alive 为 false 时记录的 pid 已经退出
+    Held { owner: Option<LockOwner>, owner_alive: bool },
+}
+
+/// 检查 dir 的锁而不获取它；锁空闲时会短暂加锁再释放，不创建也不修改锁文件
+pub fn lock_status(dir: &Path) -> io::Result<LockStatus> {
+    let mut file = match File::open(dir.join(LOCK_FILE)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(LockStatus::Free),
+        Err(e) => return Err(e),
+    };
+    let owner = read_owner(&mut file);
+    if !try_lock(&file)? {
+        let owner_alive = owner.is_none_or(|owner| process_alive(owner.pid));
+        return Ok(LockStatus::Held { owner, owner_alive });
+    }
+    Ok(owner.map_or(LockStatus::Free, LockStatus::Stale))
+}
+
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// 尝试加排他锁，已被其他描述符锁定时返回 false
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: 描述符在 file 的生命周期内有效
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() == io::ErrorKind::WouldBlock { Ok(false) } else { Err(err) }
+}
+
+/// 非 unix 平台没有建议锁，总是成功
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // 0 和负数在 kill 中表示进程组，不是合法的持有者
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid <= 0 {
+        return false;
+    }
+    // SAFETY: 信号 0 只做存在性和权限检查，不发送信号
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
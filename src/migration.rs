@@ -0,0 +1,170 @@
+//! 数据目录的格式迁移
+//!
+//! 数据目录下的 FORMAT_VERSION 文件记录磁盘格式的版本。打开存储时按顺序执行从该版本
+//! 到 FORMAT_VERSION 之间的迁移步骤：先把数据文件复制到临时目录并在其中迁移，全部成功后
+//! 再替换数据目录中的文件，迁移失败或中途崩溃时原有的数据不受影响。
+//! 没有 FORMAT_VERSION 文件但已有数据的目录是引入版本号之前写出的，视为版本 1；
+//! 版本比当前程序新的目录拒绝打开。
+
+use crate::lockfile::LOCK_FILE;
+use crate::storage;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 当前程序读写的磁盘格式版本
+pub const FORMAT_VERSION: u32 = 2;
+
+/// 数据目录下记录格式版本的文件
+pub const FORMAT_VERSION_FILE: &str = "FORMAT_VERSION";
+
+/// 一个迁移步骤：就地改写目录中的文件，把上一个版本转换为下一个版本
+pub type Migration = fn(&Path) -> Result<(), String>;
+
+/// 按顺序注册的迁移步骤，第 i 个把版本 i + 1 转换为 i + 2
+pub const MIGRATIONS: &[(&str, Migration)] = &[("v1_to_v2", storage::migrate_v1_to_v2)];
+
+// 正在迁移的临时目录，崩溃后留下时直接删除
+const MIGRATING_DIR: &str = "migrate.tmp";
+// 迁移完成、等待替换的目录；存在时说明迁移已经提交，重新打开时继续替换
+const MIGRATED_DIR: &str = "migrate.done";
+// 已迁移目录中的文件列表，替换后数据目录中只保留这些文件
+const FILE_LIST: &str = "FILES";
+
+/// 目录的格式版本；没有版本文件时，有数据的目录为版本 1，空目录返回 None
+pub fn read_version(dir: &Path) -> Result<Option<u32>, String> {
+    match fs::read_to_string(dir.join(FORMAT_VERSION_FILE)) {
+        Ok(text) => match text.trim().parse() {
+            Ok(version) if version > 0 => Ok(Some(version)),
+            _ => Err(format!("InvalidFormatVersion: {:?} in {}", text.trim(), dir.display())),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok((!data_files(dir)?.is_empty()).then_some(1)),
+        Err(e) => Err(format!("Failed to read {}: {}", FORMAT_VERSION_FILE, e)),
+    }
+}
+
+/// 把 dir 迁移到当前格式，返回执行的迁移步骤；调用方需要持有目录锁
+pub fn migrate(dir: &Path) -> Result<Vec<String>, String> {
+    resume(dir)?;
+    let version = match read_version(dir)? {
+        Some(version) => version,
+        None => {
+            // 新目录直接使用当前格式
+            write_version(dir, FORMAT_VERSION)?;
+            return Ok(Vec::new());
+        }
+    };
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "UnsupportedFormat: {} has format version {}, this build supports up to {}",
+            dir.display(),
+            version,
+            FORMAT_VERSION
+        ));
+    }
+    let pending = &MIGRATIONS[version as usize - 1..];
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let work = dir.join(MIGRATING_DIR);
+    fs::create_dir(&work).map_err(|e| format!("Failed to create {}: {}", work.display(), e))?;
+    if let Err(e) = prepare(dir, &work, pending) {
+        let _ = fs::remove_dir_all(&work);
+        return Err(e);
+    }
+    // 重命名是提交点：之后崩溃时 resume 会完成替换
+    let done = dir.join(MIGRATED_DIR);
+    fs::rename(&work, &done).map_err(|e| format!("Failed to rename {}: {}", work.display(), e))?;
+    sync_dir(dir)?;
+    finish(dir)?;
+    Ok(pending.iter().map(|(name, _)| name.to_string()).collect())
+}
+
+// 把数据文件复制到 work 中并依次执行迁移，最后写入新的版本号和文件列表
+fn prepare(dir: &Path, work: &Path, pending: &[(&str, Migration)]) -> Result<(), String> {
+    for name in data_files(dir)? {
+        fs::copy(dir.join(&name), work.join(&name)).map_err(|e| format!("Failed to copy {}: {}", name, e))?;
+    }
+    for (name, step) in pending {
+        println!("Migration: running {} in {}", name, dir.display());
+        step(work).map_err(|e| format!("MigrationFailed: {}: {}", name, e))?;
+    }
+    write_version(work, FORMAT_VERSION)?;
+
+    let files = data_files(work)?;
+    for name in &files {
+        fs::File::open(work.join(name))
+            .and_then(|file| file.sync_all())
+            .map_err(|e| format!("Failed to sync {}: {}", name, e))?;
+    }
+    fs::write(work.join(FILE_LIST), files.join("\n")).map_err(|e| format!("Failed to write {}: {}", FILE_LIST, e))?;
+    sync_dir(work)
+}
+
+// 处理上次打开时中断的迁移：已提交的继续替换，未提交的丢弃
+fn resume(dir: &Path) -> Result<(), String> {
+    if dir.join(MIGRATED_DIR).exists() {
+        println!("Migration: resuming interrupted swap in {}", dir.display());
+        finish(dir)?;
+    }
+    let work = dir.join(MIGRATING_DIR);
+    if work.exists() {
+        println!("Migration: removing unfinished {}", work.display());
+        fs::remove_dir_all(&work).map_err(|e| format!("Failed to remove {}: {}", work.display(), e))?;
+    }
+    Ok(())
+}
+
+// 用已迁移的文件替换数据目录中的数据文件，中途崩溃后可以重复执行
+fn finish(dir: &Path) -> Result<(), String> {
+    let done = dir.join(MIGRATED_DIR);
+    let list = fs::read_to_string(done.join(FILE_LIST)).map_err(|e| format!("Failed to read {}: {}", FILE_LIST, e))?;
+    let keep: Vec<&str> = list.lines().collect();
+    for name in data_files(dir)? {
+        if !keep.contains(&name.as_str()) {
+            fs::remove_file(dir.join(&name)).map_err(|e| format!("Failed to remove {}: {}", name, e))?;
+        }
+    }
+    for name in keep {
+        let from = done.join(name);
+        if from.exists() {
+            fs::rename(&from, dir.join(name)).map_err(|e| format!("Failed to move {}: {}", name, e))?;
+        }
+    }
+    sync_dir(dir)?;
+    fs::remove_dir_all(&done).map_err(|e| format!("Failed to remove {}: {}", done.display(), e))
+}
+
+fn write_version(dir: &Path, version: u32) -> Result<(), String> {
+    let path = dir.join(FORMAT_VERSION_FILE);
+    fs::write(&path, format!("{}\n", version))
+        .and_then(|_| fs::File::open(&path)?.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", FORMAT_VERSION_FILE, e))
+}
+
+// 目录中除锁文件以外的普通文件，按文件名排序
+fn data_files(dir: &Path) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))? {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name != LOCK_FILE && entry.file_type().is_ok_and(|t| t.is_file()) {
+            files.push(name);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn sync_dir(dir: &Path) -> Result<(), String> {
+    // 只有类 Unix 系统支持以只读方式打开目录并 fsync
+    #[cfg(unix)]
+    fs::File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(|e| format!("Failed to sync {}: {}", dir.display(), e))?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
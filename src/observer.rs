@@ -0,0 +1,116 @@
+//! 写入观察者
+//!
+//! 嵌入存储的应用可以注册 WriteObserver，在每个批次提交后收到它的序号和内容，
+//! 用来把写入同步到其他系统。观察者在释放写锁之后按提交顺序调用。
+
+use crate::errorlog::{ErrorCategory, ErrorLog};
+use crate::protocol::Modify;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// 批次提交后的回调
+///
+/// 覆盖经过 StandaloneStorage::write 及原子操作（get_set、rename、锁等）提交的批次；
+/// 过期清理、DropDb、Repair 等直接改写数据的维护操作不通知。
+/// 回调期间后续提交的通知会等待，回调中不能再写入同一个存储。
+pub trait WriteObserver: Send {
+    /// seq 从 1 开始按提交顺序递增；空批次不通知
+    fn on_commit(&mut self, seq: u64, batch: &[Modify]);
+
+    /// StandaloneStorage::reload 用磁盘上的数据替换了内存中的数据，seq 是此前最后一次提交的序号；
+    /// 之前收到的批次可能已被丢弃，需要重新同步。默认不做任何事
+    fn on_reload(&mut self, _seq: u64) {}
+}
+
+/// 把每个批次发送到通道，由应用在自己的线程中消费；接收端关闭后不再发送。
+/// 重新加载时发送一个空批次，提交的批次不会为空
+#[derive(Debug)]
+pub struct ChannelObserver {
+    sender: Option<Sender<(u64, Vec<Modify>)>>,
+}
+
+impl ChannelObserver {
+    pub fn new(sender: Sender<(u64, Vec<Modify>)>) -> Self {
+        ChannelObserver { sender: Some(sender) }
+    }
+}
+
+impl WriteObserver for ChannelObserver {
+    fn on_commit(&mut self, seq: u64, batch: &[Modify]) {
+        if let Some(sender) = &self.sender
+            && sender.send((seq, batch.to_vec())).is_err()
+        {
+            self.sender = None;
+        }
+    }
+
+    fn on_reload(&mut self, seq: u64) {
+        self.on_commit(seq, &[]);
+    }
+}
+
+struct Registered {
+    observer: Box<dyn WriteObserver>,
+    // panic 过的观察者不再调用
+    disabled: bool,
+}
+
+/// 存储中注册的观察者
+#[derive(Default)]
+pub(crate) struct Observers {
+    list: Mutex<Vec<Registered>>,
+    // 注册的数量，没有观察者时写入路径不复制批次
+    count: AtomicUsize,
+}
+
+/// 持有时其他提交的通知需要等待，用来保证按提交顺序通知
+pub(crate) struct ObserverGuard<'a>(MutexGuard<'a, Vec<Registered>>);
+
+impl Observers {
+    pub(crate) fn register(&self, observer: Box<dyn WriteObserver>) {
+        self.lock().0.push(Registered { observer, disabled: false });
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.count.load(Ordering::SeqCst) == 0
+    }
+
+    /// 在释放写锁之前调用，保证通知的顺序与提交顺序一致
+    pub(crate) fn lock(&self) -> ObserverGuard<'_> {
+        // 回调的 panic 已被捕获，不会让锁中毒
+        ObserverGuard(self.list.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl ObserverGuard<'_> {
+    /// 依次调用观察者；panic 的观察者记录到错误日志后停用，不影响存储和其他观察者
+    pub(crate) fn notify(self, seq: u64, batch: &[Modify], errors: &ErrorLog) {
+        self.each(seq, errors, |observer| observer.on_commit(seq, batch));
+    }
+
+    /// 通知观察者存储已重新加载
+    pub(crate) fn notify_reload(self, seq: u64, errors: &ErrorLog) {
+        self.each(seq, errors, |observer| observer.on_reload(seq));
+    }
+
+    fn each(mut self, seq: u64, errors: &ErrorLog, mut call: impl FnMut(&mut dyn WriteObserver)) {
+        for (index, registered) in self.0.iter_mut().enumerate().filter(|(_, r)| !r.disabled) {
+            let observer = registered.observer.as_mut();
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| call(observer))) {
+                let reason = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                let message = format!("Write observer {} panicked at commit {} and was disabled: {}", index, seq, reason);
+                eprintln!("{}", message);
+                errors.record(ErrorCategory::Observer, message);
+                registered.disabled = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! 存储修改的操作日志（oplog）
+//!
+//! 配置 StorageOptions::oplog 后，存储把每个提交的批次追加为一行 JSON（OplogEntry），带 oplog
+//! 自己的序号、提交时间和提交它的连接主体。它经由写入观察者记录，覆盖范围与 WriteObserver 相同：
+//! 过期时间、过期清理、DropDb 等维护操作不记录。与 WAL 不同，oplog 不在刷盘或整理后截断，
+//! 只按 RotationPolicy 轮转为 `<path>.<n>` 并删除超出保留数的旧文件，用来追查一个键是怎样变成
+//! 现在的值的；从第一个文件开始按顺序重放（见 replay）可以在空存储上重建数据。
+
+use crate::clock::Clock;
+use crate::errorlog::{ErrorCategory, ErrorLog};
+use crate::observer::WriteObserver;
+use crate::protocol::Modify;
+use crate::rotation::{LogFileStats, RotationPolicy};
+use crate::storage::StandaloneStorage;
+
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 一次 OplogRange 最多返回的记录数
+pub const OPLOG_RANGE_LIMIT: usize = 1000;
+
+/// oplog 的位置和轮转策略
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OplogConfig {
+    /// 当前文件的路径，轮转出的文件为 `<path>.<n>`
+    pub path: PathBuf,
+    pub rotation: RotationPolicy,
+}
+
+impl OplogConfig {
+    /// 不轮转的 oplog
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        OplogConfig { path: path.into(), rotation: RotationPolicy::default() }
+    }
+}
+
+/// oplog 中的一行：一个提交的批次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OplogEntry {
+    /// 从 1 开始递增，跨重启和轮转连续
+    pub seq: u64,
+    pub timestamp_ms: u64,
+    /// 通过 AdminAuth 的连接为 "admin"，通过 Auth 的连接为主体名；
+    /// 直接调用存储或经组提交合并的写入为 None
+    #[serde(default)]
+    pub principal: Option<String>,
+    /// 批次中的修改，列族为存储中的名称（非默认数据库带前缀）
+    pub ops: Vec<Modify>,
+}
+
+thread_local! {
+    // 当前线程上提交写入的主体，见 enter_principal
+    static PRINCIPAL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 在当前线程上标记提交写入的主体，守卫丢弃时恢复原来的值；
+/// 观察者在提交写入的线程上调用，oplog 从这里取得主体
+pub fn enter_principal(principal: Option<String>) -> PrincipalGuard {
+    PrincipalGuard(PRINCIPAL.with(|current| current.replace(principal)))
+}
+
+pub struct PrincipalGuard(Option<String>);
+
+impl Drop for PrincipalGuard {
+    fn drop(&mut self) {
+        PRINCIPAL.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+fn current_principal() -> Option<String> {
+    PRINCIPAL.with(|current| current.borrow().clone())
+}
+
+struct OplogState {
+    file: File,
+    bytes: u64,
+    next_seq: u64,
+    // 当前文件第一条记录的时间戳
+    opened_at_ms: u64,
+    rotations: u64,
+    pruned_files: u64,
+}
+
+/// 追加写入的 oplog
+pub struct Oplog {
+    path: PathBuf,
+    policy: RotationPolicy,
+    clock: Arc<dyn Clock>,
+    state: Mutex<OplogState>,
+}
+
+impl std::fmt::Debug for Oplog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Oplog").field("path", &self.path).field("policy", &self.policy).finish()
+    }
+}
+
+impl Oplog {
+    /// 打开或创建 oplog，序号接着已有文件的最后一条记录；
+    /// 上次崩溃留下的不完整的最后一行被截掉
+    pub fn open(config: &OplogConfig, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let path = config.path.clone();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let now = clock.now_ms();
+        let existing = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let complete = complete_len(&existing);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        if complete < existing.len() {
+            file.set_len(complete as u64).map_err(|e| format!("Failed to truncate {}: {}", path.display(), e))?;
+        }
+
+        let first = |bytes: &[u8]| bytes.split(|b| *b == b'\n').next().and_then(|line| serde_json::from_slice::<OplogEntry>(line).ok());
+        let opened_at_ms = first(&existing[..complete]).map_or(now, |entry| entry.timestamp_ms);
+        let last = match last_entry(&existing[..complete], &path)? {
+            Some(entry) => Some(entry),
+            None => match rotated_files(&path)?.last() {
+                Some(previous) => {
+                    let bytes = fs::read(previous).map_err(|e| format!("Failed to read {}: {}", previous.display(), e))?;
+                    last_entry(&bytes[..complete_len(&bytes)], previous)?
+                }
+                None => None,
+            },
+        };
+        let state = OplogState {
+            file,
+            bytes: complete as u64,
+            next_seq: last.map_or(1, |entry| entry.seq + 1),
+            opened_at_ms,
+            rotations: 0,
+            pruned_files: 0,
+        };
+        // 至少保留一个轮转文件，当前文件为空时序号从它接上
+        let mut policy = config.rotation.clone();
+        policy.retain = policy.retain.map(|n| n.max(1));
+        Ok(Oplog { path, policy, clock, state: Mutex::new(state) })
+    }
+
+    /// 当前文件的路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 下一条记录的序号
+    pub fn next_seq(&self) -> Result<u64, String> {
+        Ok(self.state.lock().map_err(|e| e.to_string())?.next_seq)
+    }
+
+    /// 追加一个批次，返回它的序号
+    pub fn append(&self, ops: &[Modify], principal: Option<String>) -> Result<u64, String> {
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        let now = self.clock.now_ms();
+        if self.policy.should_rotate(state.bytes, state.opened_at_ms, now) {
+            self.rotate(&mut state)?;
+        }
+        let entry = OplogEntry { seq: state.next_seq, timestamp_ms: now, principal, ops: ops.to_vec() };
+        let mut line = serde_json::to_vec(&entry).map_err(|e| format!("Failed to serialize: {}", e))?;
+        line.push(b'\n');
+        // 一次写入整行，崩溃时最多留下末尾不完整的一行
+        state.file.write_all(&line).map_err(|e| format!("Failed to write oplog: {}", e))?;
+        if state.bytes == 0 {
+            state.opened_at_ms = now;
+        }
+        state.bytes += line.len() as u64;
+        state.next_seq += 1;
+        Ok(entry.seq)
+    }
+
+    /// 序号在 [from_seq, to_seq] 内的记录，最多 limit 条；还有更多记录时同时返回下一条的序号。
+    /// 已被保留策略删除的记录不再返回
+    pub fn range(&self, from_seq: u64, to_seq: Option<u64>, limit: usize) -> Result<(Vec<OplogEntry>, Option<u64>), String> {
+        // 持有锁，读取期间文件不会被轮转或删除
+        let _state = self.state.lock().map_err(|e| e.to_string())?;
+        let mut entries = Vec::new();
+        for entry in OplogReader::open(&self.path)? {
+            let entry = entry?;
+            if entry.seq < from_seq {
+                continue;
+            }
+            if to_seq.is_some_and(|to| entry.seq > to) {
+                break;
+            }
+            if entries.len() == limit {
+                return Ok((entries, Some(entry.seq)));
+            }
+            entries.push(entry);
+        }
+        Ok((entries, None))
+    }
+
+    /// 当前文件和保留的轮转文件的统计
+    pub fn stats(&self) -> Result<LogFileStats, String> {
+        let state = self.state.lock().map_err(|e| e.to_string())?;
+        let rotated = rotated_files(&self.path)?;
+        let rotated_bytes: u64 = rotated.iter().filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+        Ok(LogFileStats {
+            name: "oplog".to_string(),
+            active_bytes: state.bytes,
+            files: rotated.len() + 1,
+            total_bytes: rotated_bytes + state.bytes,
+            rotations: state.rotations,
+            pruned_files: state.pruned_files,
+        })
+    }
+
+    // 把当前文件改名为下一个编号，然后删除超出保留数的旧文件
+    fn rotate(&self, state: &mut OplogState) -> Result<(), String> {
+        let next = numbered_files(&self.path)?.last().map_or(1, |(n, _)| n + 1);
+        let rotated = PathBuf::from(format!("{}.{}", self.path.display(), next));
+        state.file.sync_all().map_err(|e| format!("Failed to sync oplog: {}", e))?;
+        fs::rename(&self.path, &rotated).map_err(|e| format!("Failed to rotate oplog: {}", e))?;
+        state.file = File::create(&self.path).map_err(|e| format!("Failed to create {}: {}", self.path.display(), e))?;
+        state.bytes = 0;
+        state.rotations += 1;
+        for file in self.policy.expired(&rotated_files(&self.path)?) {
+            fs::remove_file(file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+            state.pruned_files += 1;
+        }
+        Ok(())
+    }
+}
+
+/// 把提交的批次写入 oplog 的观察者；写入失败记录到错误日志，不影响提交本身
+pub(crate) struct OplogObserver {
+    pub(crate) oplog: Arc<Oplog>,
+    pub(crate) errors: Arc<ErrorLog>,
+}
+
+impl WriteObserver for OplogObserver {
+    fn on_commit(&mut self, _seq: u64, batch: &[Modify]) {
+        if let Err(e) = self.oplog.append(batch, current_principal()) {
+            let message = format!("Oplog: {}", e);
+            eprintln!("{}", message);
+            self.errors.record(ErrorCategory::Observer, message);
+        }
+    }
+}
+
+/// 按序号顺序读取 oplog 的所有文件（先轮转出的文件，再当前文件），逐行解析，不把文件整个读入内存；
+/// 只打开文件不需要打开存储，正在写入的 oplog 末尾不完整的一行被忽略
+pub struct OplogReader {
+    files: std::vec::IntoIter<PathBuf>,
+    current: Option<(PathBuf, BufReader<File>, usize)>,
+    line: Vec<u8>,
+}
+
+impl OplogReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut files = rotated_files(path)?;
+        if path.exists() {
+            files.push(path.to_path_buf());
+        }
+        if files.is_empty() {
+            return Err(format!("No oplog at {}", path.display()));
+        }
+        Ok(OplogReader { files: files.into_iter(), current: None, line: Vec::new() })
+    }
+}
+
+impl Iterator for OplogReader {
+    type Item = Result<OplogEntry, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() {
+                let path = self.files.next()?;
+                match File::open(&path) {
+                    Ok(file) => self.current = Some((path, BufReader::new(file), 0)),
+                    // 读取期间被保留策略删除的文件
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Some(Err(format!("Failed to open {}: {}", path.display(), e))),
+                }
+            }
+            let (path, reader, line_no) = self.current.as_mut()?;
+            self.line.clear();
+            match reader.read_until(b'\n', &mut self.line) {
+                Err(e) => return Some(Err(format!("Failed to read {}: {}", path.display(), e))),
+                // 文件结束，或者末尾没有换行的不完整记录
+                Ok(_) if self.line.last() != Some(&b'\n') => self.current = None,
+                Ok(_) => {
+                    *line_no += 1;
+                    let entry = serde_json::from_slice(&self.line[..self.line.len() - 1])
+                        .map_err(|e| format!("Failed to parse {}:{}: {}", path.display(), line_no, e));
+                    return Some(entry);
+                }
+            }
+        }
+    }
+}
+
+/// 按顺序把 entries 中的批次写入 storage，返回重放的批次数；
+/// 在空存储上重放完整的 oplog 得到与记录时相同的数据
+pub fn replay(entries: impl IntoIterator<Item = Result<OplogEntry, String>>, storage: &StandaloneStorage) -> Result<u64, String> {
+    let mut replayed = 0;
+    for entry in entries {
+        let entry = entry?;
+        storage.write(entry.ops).map_err(|e| format!("Failed to replay oplog entry {}: {}", entry.seq, e))?;
+        replayed += 1;
+    }
+    Ok(replayed)
+}
+
+// 以换行结尾的完整行的总长度
+fn complete_len(bytes: &[u8]) -> usize {
+    bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1)
+}
+
+fn last_entry(complete: &[u8], path: &Path) -> Result<Option<OplogEntry>, String> {
+    match complete.strip_suffix(b"\n").and_then(|bytes| bytes.split(|b| *b == b'\n').next_back()) {
+        Some(line) => serde_json::from_slice(line)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse last oplog entry in {}: {}", path.display(), e)),
+        None => Ok(None),
+    }
+}
+
+// path 轮转出的文件，按编号排序
+fn rotated_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    Ok(numbered_files(path)?.into_iter().map(|(_, path)| path).collect())
+}
+
+fn numbered_files(path: &Path) -> Result<Vec<(u64, PathBuf)>, String> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned()));
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut numbered = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(n) = name.strip_prefix(&prefix).and_then(|n| n.parse::<u64>().ok()) {
+            numbered.push((n, entry.path()));
+        }
+    }
+    numbered.sort();
+    Ok(numbered)
+}
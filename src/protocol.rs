@@ -0,0 +1,1924 @@
+//! 线协议：客户端与服务器之间交换的命令、响应和消息分帧
+
+use crate::audit;
+use crate::clients;
+use crate::errorlog::ErrorEvent;
+use crate::histogram::LatencySummary;
+use crate::idempotency::IdempotencyStats;
+use crate::acl::EffectivePermissions;
+use crate::blob::SpillStats;
+use crate::rotation::LogFileStats;
+use crate::runtime_config;
+use crate::hotkeys;
+use crate::oplog;
+use crate::pubsub;
+use crate::storage;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::error::Error;
+use std::io::{Read, Write};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+/// 默认数据库名，未选择数据库的连接都使用它
+pub const DEFAULT_DB: &str = "default";
+
+/// 默认列族名：命令中的列族为空、连接又没有用 UseCf 设置默认列族时使用它；Info 总是列出它
+pub const DEFAULT_CF: &str = "default";
+
+/// 数据库与列族之间的分隔符，非默认数据库的列族在存储中编码为 `db/cf`
+pub const DB_SEPARATOR: &str = "/";
+
+/// 数据库名最大长度
+pub const MAX_DB_NAME_LEN: usize = 64;
+
+/// 列族名最大长度
+pub const MAX_CF_NAME_LEN: usize = 64;
+
+/// 线协议版本，Hello 握手时交换，双方按较小的版本通信
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 服务器的版本号，取自 Cargo 包版本
+pub const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 构建时的 git 提交，由构建脚本从 TINYKV_GIT_HASH 或 git 仓库取得；都没有时为 None
+pub const GIT_HASH: Option<&str> = option_env!("TINYKV_GIT_HASH");
+
+/// 服务器支持的可选协议特性，Hello 握手时协商
+/// 没有发送 Hello 的旧客户端不协商任何特性，按最初的裸 JSON 协议处理
+pub const FEATURES: &[&str] = &["scan-filter", "scan-all", "atomic-ops", "not-found", "dry-run", "truncation", "idempotency", "chunked"];
+
+/// 服务器支持但客户端需要显式请求的特性；协商后响应的格式会改变，不在默认握手中请求
+pub const OPT_IN_FEATURES: &[&str] = &["envelope", "msgpack"];
+
+/// 连接传输层：任何双向字节流（明文 TcpStream、TLS 流等）
+/// 客户端和服务端的命令处理都只依赖该接口
+pub trait Transport: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Transport for T {}
+
+/// 读取缓冲区的最小增长量
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// 从流中读取下一个完整的 JSON 消息
+/// 一条消息可能被拆分到多次 read 中，未解析完的字节累积在 pending 里，
+/// 读多的字节（下一条消息的开头）也留在 pending 中供下次调用使用。
+/// 对端在消息边界处关闭连接时返回 Ok(None)
+pub fn read_message<T, R>(stream: &mut R, pending: &mut Vec<u8>) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: DeserializeOwned,
+    R: Read + ?Sized,
+{
+    loop {
+        if !pending.is_empty() {
+            let mut messages = serde_json::Deserializer::from_slice(pending).into_iter::<T>();
+            match messages.next() {
+                Some(Ok(message)) => {
+                    let consumed = messages.byte_offset();
+                    pending.drain(..consumed);
+                    return Ok(Some(message));
+                }
+                // 消息尚不完整，继续读取
+                Some(Err(e)) if e.is_eof() => {}
+                Some(Err(e)) => return Err(e.into()),
+                // 只剩空白字符
+                None => pending.clear(),
+            }
+        }
+
+        if fill(stream, pending)? == 0 {
+            if pending.iter().all(u8::is_ascii_whitespace) {
+                return Ok(None);
+            }
+            return Err("Connection closed in the middle of a message".into());
+        }
+    }
+}
+
+// 再读一次追加到 pending，返回读到的字节数。按已缓冲的大小成倍扩大读取量，
+// 避免大消息被反复从头解析太多次
+fn fill<R: Read + ?Sized>(stream: &mut R, pending: &mut Vec<u8>) -> std::io::Result<usize> {
+    let filled = pending.len();
+    pending.resize(filled + filled.max(READ_CHUNK_SIZE), 0);
+    match stream.read(&mut pending[filled..]) {
+        Ok(n) => {
+            pending.truncate(filled + n);
+            Ok(n)
+        }
+        Err(e) => {
+            pending.truncate(filled);
+            Err(e)
+        }
+    }
+}
+
+/// 消息的编码方式
+///
+/// 连接开始时总是 JSON，消息之间没有分隔，由解析器确定边界（见 read_message）。Hello 协商了
+/// msgpack 特性后，之后的请求和响应（包括推送的消息）改用 MessagePack，每帧前加 4 字节大端长度。
+/// MessagePack 和 JSON 一样是自描述的，命令和响应沿用同一套 serde 表示（带标签的枚举、请求中
+/// flatten 的选项），只是字节数组编码为二进制而不是数字数组。Hello 的回复仍按握手前的编码发送
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Codec {
+    /// 按连接协商的特性选择编码
+    pub fn negotiated(features: &[String]) -> Self {
+        match features.iter().any(|f| f == "msgpack") {
+            true => Codec::MessagePack,
+            false => Codec::Json,
+        }
+    }
+
+    /// 编码一条消息，MessagePack 带长度前缀
+    pub fn encode<T: Serialize + ?Sized>(self, message: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Codec::Json => Ok(serde_json::to_vec(message)?),
+            Codec::MessagePack => {
+                let mut frame = vec![0; 4];
+                // 结构体按字段名编码为 map，与 JSON 一样能解析带标签的枚举和 flatten 的字段
+                rmp_serde::encode::write_named(&mut frame, message)?;
+                let len = u32::try_from(frame.len() - 4).map_err(|_| "Message too large for a frame")?;
+                frame[..4].copy_from_slice(&len.to_be_bytes());
+                Ok(frame)
+            }
+        }
+    }
+
+    /// 从流中读取下一条消息，与 read_message 一样在 pending 中保留多读的字节；
+    /// 对端在消息边界处关闭连接时返回 Ok(None)
+    pub fn read_message<T, R>(self, stream: &mut R, pending: &mut Vec<u8>) -> Result<Option<T>, Box<dyn Error>>
+    where
+        T: DeserializeOwned,
+        R: Read + ?Sized,
+    {
+        if self == Codec::Json {
+            return read_message(stream, pending);
+        }
+        loop {
+            if let Some(header) = pending.first_chunk::<4>() {
+                let end = 4 + u32::from_be_bytes(*header) as usize;
+                if pending.len() >= end {
+                    let message = rmp_serde::from_slice(&pending[4..end]);
+                    pending.drain(..end);
+                    return Ok(Some(message?));
+                }
+            }
+            if fill(stream, pending)? == 0 {
+                if pending.is_empty() {
+                    return Ok(None);
+                }
+                return Err("Connection closed in the middle of a message".into());
+            }
+        }
+    }
+}
+
+// Batch 遇到无效操作时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchMode {
+    // 有任何无效操作时不做修改，返回 BatchError
+    #[default]
+    Atomic,
+    // 跳过无效操作，返回每个操作的结果
+    BestEffort,
+}
+
+impl BatchMode {
+    fn is_atomic(&self) -> bool {
+        *self == BatchMode::Atomic
+    }
+}
+
+// Scan 范围的一端，与 std::ops::Bound 对应
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScanBound {
+    Included(#[serde(with = "serde_bytes")] Vec<u8>),
+    Excluded(#[serde(with = "serde_bytes")] Vec<u8>),
+    Unbounded,
+}
+
+impl ScanBound {
+    pub fn as_bound(&self) -> Bound<&[u8]> {
+        match self {
+            ScanBound::Included(key) => Bound::Included(key),
+            ScanBound::Excluded(key) => Bound::Excluded(key),
+            ScanBound::Unbounded => Bound::Unbounded,
+        }
+    }
+}
+
+impl fmt::Display for ScanBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanBound::Included(key) => write!(f, "Included({})", display_bytes(key)),
+            ScanBound::Excluded(key) => write!(f, "Excluded({})", display_bytes(key)),
+            ScanBound::Unbounded => write!(f, "Unbounded"),
+        }
+    }
+}
+
+// Scan 的值过滤条件，在服务端求值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueFilter {
+    // 值以给定字节串开头
+    Prefix(#[serde(with = "serde_bytes")] Vec<u8>),
+    // 值包含给定字节串
+    Contains(#[serde(with = "serde_bytes")] Vec<u8>),
+    // 值长度不小于给定字节数
+    SizeAtLeast(usize),
+    // 值长度不大于给定字节数
+    SizeAtMost(usize),
+}
+
+impl ValueFilter {
+    pub fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            ValueFilter::Prefix(prefix) => value.starts_with(prefix),
+            ValueFilter::Contains(needle) => {
+                needle.is_empty() || value.windows(needle.len()).any(|w| w == needle.as_slice())
+            }
+            ValueFilter::SizeAtLeast(n) => value.len() >= *n,
+            ValueFilter::SizeAtMost(n) => value.len() <= *n,
+        }
+    }
+}
+
+// Audit / FixInvalid 对每个条目做的检查
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckKind {
+    // 键不是合法的 UTF-8
+    Utf8Key,
+    // 键长度超过给定字节数
+    MaxKeyLen(usize),
+    // 值长度超过给定字节数
+    MaxValueLen(usize),
+    // 值为空
+    EmptyValue,
+}
+
+impl CheckKind {
+    /// 条目是否违反该检查
+    pub fn fails(&self, key: &[u8], value: &[u8]) -> bool {
+        match self {
+            CheckKind::Utf8Key => std::str::from_utf8(key).is_err(),
+            CheckKind::MaxKeyLen(n) => key.len() > *n,
+            CheckKind::MaxValueLen(n) => value.len() > *n,
+            CheckKind::EmptyValue => value.is_empty(),
+        }
+    }
+}
+
+/// 与 FromStr 互逆：utf8-key、max-key-len=N、max-value-len=N、empty-value
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckKind::Utf8Key => write!(f, "utf8-key"),
+            CheckKind::MaxKeyLen(n) => write!(f, "max-key-len={}", n),
+            CheckKind::MaxValueLen(n) => write!(f, "max-value-len={}", n),
+            CheckKind::EmptyValue => write!(f, "empty-value"),
+        }
+    }
+}
+
+impl std::str::FromStr for CheckKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let limit = |n: &str| n.parse().map_err(|_| format!("invalid length '{}' in check '{}'", n, s));
+        match s.split_once('=') {
+            None if s == "utf8-key" => Ok(CheckKind::Utf8Key),
+            None if s == "empty-value" => Ok(CheckKind::EmptyValue),
+            Some(("max-key-len", n)) => limit(n).map(CheckKind::MaxKeyLen),
+            Some(("max-value-len", n)) => limit(n).map(CheckKind::MaxValueLen),
+            _ => Err(format!("unknown check '{}', expected utf8-key, max-key-len=N, max-value-len=N or empty-value", s)),
+        }
+    }
+}
+
+// FixInvalid 对违规条目的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FixPolicy {
+    // 删除条目
+    Delete,
+    // 把超长的值截断到 MaxValueLen 的上限；只能修复值过长，其他违规的条目原样保留并报告为 skipped
+    Truncate,
+    // 把条目移到 storage::QUARANTINE_CF，与 Repair 相同
+    Quarantine,
+}
+
+impl std::str::FromStr for FixPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(FixPolicy::Delete),
+            "truncate" => Ok(FixPolicy::Truncate),
+            "quarantine" => Ok(FixPolicy::Quarantine),
+            _ => Err(format!("unknown fix policy '{}', expected delete, truncate or quarantine", s)),
+        }
+    }
+}
+
+/// 违反检查的条目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub cf: String,
+    pub key: Bytes,
+    /// 条目没有通过的检查，按请求中的顺序
+    pub failed: Vec<CheckKind>,
+    pub value_len: usize,
+}
+
+/// FixInvalid 的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixReport {
+    /// 已删除、截断或隔离的条目
+    pub fixed: Vec<Violation>,
+    /// 按策略无法修复、原样保留的条目
+    pub skipped: Vec<Violation>,
+}
+
+// 修改操作类型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ModifyOp {
+    Put,
+    Delete,
+}
+
+// 单个修改操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Modify {
+    pub op: ModifyOp,
+    pub cf: String,
+    #[serde(with = "serde_bytes")]
+    pub key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+}
+
+impl Modify {
+    pub fn new_put(cf: String, key: Vec<u8>, value: Vec<u8>) -> Self {
+        Modify {
+            op: ModifyOp::Put,
+            cf,
+            key,
+            value,
+        }
+    }
+
+    pub fn new_delete(cf: String, key: Vec<u8>) -> Self {
+        Modify {
+            op: ModifyOp::Delete,
+            cf,
+            key,
+            value: Vec::new(),
+        }
+    }
+}
+
+// 请求命令；以后会增加新的命令，外部代码匹配时需要通配分支
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")] 
+#[non_exhaustive]
+pub enum Command {
+    Get {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+    },
+    Put {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        value: Vec<u8>,
+    },
+    Delete {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+    },
+    // 跨列族扫描当前数据库，按 (列族, 键) 排序；start 为包含的起点
+    ScanAll {
+        #[serde(default)]
+        start: Option<(String, Bytes)>,
+        limit: usize,
+    },
+    // 原子地读取并删除，返回旧值
+    GetDel {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+    },
+    // 原子地写入新值，返回旧值
+    GetSet {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        value: Vec<u8>,
+    },
+    // 原子地移动值；未指定 overwrite 时目标键必须不存在
+    Rename {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        old_key: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        new_key: Vec<u8>,
+        #[serde(default)]
+        overwrite: bool,
+    },
+    // 原子地复制值；未指定 overwrite 时目标键必须不存在
+    Copy {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        src_key: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        dst_key: Vec<u8>,
+        #[serde(default)]
+        overwrite: bool,
+    },
+    // 设置键在 ttl_ms 毫秒后过期，之后写入新值会清除过期时间；键不存在时返回 KeyNotFound 错误
+    Expire {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+        ttl_ms: u64,
+    },
+    // 键的剩余生存时间；键不存在时返回 KeyNotFound 错误
+    Ttl {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+    },
+    // 服务器对编码键计算的稳定哈希（见 stable_hash），用于核对分片客户端的数据分布
+    KeyHash {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+    },
+    // 把键最近一次被软删除的值移回原处，需要开启 ServerConfig::trash_retention
+    RestoreKey {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+        #[serde(default)]
+        overwrite: bool,
+    },
+    // 列出当前数据库回收站中的条目，cf 为 None 时列出所有列族
+    ScanTrash {
+        #[serde(default)]
+        cf: Option<String>,
+        limit: usize,
+    },
+    // 从当前数据库中均匀随机地抽取至多 count 个键，cf 为 None 时从所有列族中抽取
+    Sample {
+        #[serde(default)]
+        cf: Option<String>,
+        count: usize,
+    },
+    // 清理所有数据库的回收站：all 时清空，否则只删除超出保留期的条目
+    // dry_run 时只返回会被删除的条目（DeletionPlan），不做任何修改
+    PurgeTrash {
+        #[serde(default)]
+        all: bool,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    // 显式创建列族，可以同时设置列族选项；列族已存在时返回 CfExists 错误
+    CreateCf {
+        cf: String,
+        #[serde(default)]
+        options: Option<storage::CfOptions>,
+    },
+    // 修改列族配额，None 表示不限制；其他列族选项保持不变
+    SetCfQuota {
+        cf: String,
+        #[serde(default)]
+        max_keys: Option<usize>,
+        #[serde(default)]
+        max_bytes: Option<usize>,
+    },
+    // 按值查找键，要求列族开启 CfOptions::index_values
+    FindByValue {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        value: Vec<u8>,
+        limit: usize,
+    },
+    // 获取租约为 ttl_ms 毫秒的锁，锁在所有数据库间共享
+    LockAcquire {
+        name: String,
+        ttl_ms: u64,
+    },
+    // 只有令牌与持有者一致时才释放
+    LockRelease {
+        name: String,
+        token: u64,
+    },
+    LockRenew {
+        name: String,
+        token: u64,
+        ttl_ms: u64,
+    },
+    // 范围扫描 [start_key, end_key)；给出 start_bound / end_bound 时以它们为准，
+    // 用于包含终点或排除起点的范围
+    Scan {
+        cf: String,
+        #[serde(default, with = "serde_bytes")]
+        start_key: Vec<u8>,
+        #[serde(default, with = "serde_bytes")]
+        end_key: Option<Vec<u8>>,
+        limit: usize,
+        // 只返回值满足过滤条件的条目，limit 按匹配条目计数
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<ValueFilter>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start_bound: Option<ScanBound>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end_bound: Option<ScanBound>,
+    },
+    GetVersion {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+        version: u64,
+    },
+    History {
+        cf: String,
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+        limit: usize,
+    },
+    // 应用一组修改，先校验所有操作再做修改；操作可以跨列族，整批在同一次提交中生效，
+    // 读者看不到只应用了一部分的批次
+    Batch {
+        ops: Vec<Modify>,
+        #[serde(default, skip_serializing_if = "BatchMode::is_atomic")]
+        mode: BatchMode,
+    },
+    // 连接上可选的第一条消息，协商协议版本和特性
+    Hello {
+        client_version: u32,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+    UseDb {
+        name: String,
+    },
+    // 设置会话的默认列族，之后 cf 为空字符串的命令使用它
+    UseCf {
+        cf: String,
+    },
+    AdminAuth {
+        token: String,
+    },
+    // 以 ServerConfig::principals 中的主体认证，之后按它的列族权限执行命令，见 acl 模块
+    Auth {
+        name: String,
+        token: String,
+    },
+    ListDbs,
+    DropDb {
+        name: String,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    Info,
+    // 只包含汇总信息的 Info，不列出列族；列族用 ListCfs 分页获取
+    InfoSummary,
+    // 服务器自身的版本、配置和限制，与数据统计的 Info 分开
+    ServerInfo,
+    // 按名称分页列出当前数据库的列族，start_after 为上一页最后一个列族
+    ListCfs {
+        #[serde(default)]
+        start_after: Option<String>,
+        limit: usize,
+    },
+    // 修改一个运行时配置项，见 runtime_config 模块
+    SetConfig {
+        key: String,
+        value: String,
+    },
+    // 列出所有配置项的当前值
+    GetConfig,
+    Flush,
+    Compact,
+    // 采样统计出的热点键，需要在配置中开启
+    HotKeys {
+        top_n: usize,
+    },
+    ResetStats,
+    // 列出服务器上的所有连接
+    Clients,
+    // 最近的至多 count 条错误事件，新的在前
+    RecentErrors {
+        count: usize,
+    },
+    // 断开指定 id 的连接
+    KillClient {
+        id: u64,
+    },
+    // 校验值的 CRC32，cf 为 None 时校验所有列族
+    Verify {
+        #[serde(default)]
+        cf: Option<String>,
+    },
+    // 删除损坏的条目，quarantine 时移到 storage::QUARANTINE_CF
+    Repair {
+        #[serde(default)]
+        quarantine: bool,
+    },
+    // 逐条检查 cf（None 时为所有列族，不含内部列族）中从 start（包含）开始的条目，最多返回 limit 个
+    // 违反 checks 的条目；检查的条目数受 max_scan_examined 限制，续查位置在 Violations 中返回
+    Audit {
+        #[serde(default)]
+        cf: Option<String>,
+        checks: Vec<CheckKind>,
+        #[serde(default)]
+        start: Option<(String, Bytes)>,
+        limit: usize,
+    },
+    // 按 policy 处理所有违反 checks 的条目，见 FixPolicy
+    FixInvalid {
+        #[serde(default)]
+        cf: Option<String>,
+        checks: Vec<CheckKind>,
+        policy: FixPolicy,
+    },
+    // 重新计算审计日志的哈希链，报告第一个断开的位置；需要配置 ServerConfig::audit_log
+    AuditVerify,
+    // 序号在 [from_seq, to_seq] 内的 oplog 记录，to_seq 为 None 时直到最新的记录；
+    // 每次最多返回 oplog::OPLOG_RANGE_LIMIT 条，续取位置在 Oplog 中返回。需要配置 StorageOptions::oplog
+    OplogRange {
+        from_seq: u64,
+        #[serde(default)]
+        to_seq: Option<u64>,
+    },
+    // 整个存储的备份，以 Archive 返回备份文件的各行，格式见 StandaloneStorage::backup_to
+    Backup,
+    // 用备份文件的各行替换整个存储，回复恢复的键数（Count）；存储中已有数据时需要 force
+    Restore {
+        archive: Vec<String>,
+        #[serde(default)]
+        force: bool,
+    },
+    // 在服务器上把备份写到 path，回复写入的键数（Count）
+    BackupToFile {
+        path: String,
+    },
+    // 用服务器上 path 处的备份文件替换整个存储，同 Restore
+    RestoreFromFile {
+        path: String,
+        #[serde(default)]
+        force: bool,
+    },
+    // 回复 Ok 后由服务器执行关闭流程
+    Shutdown {
+        #[serde(default)]
+        flush: bool,
+    },
+    // 向频道发布一条消息，回复收到消息的订阅者数（Count）；消息不持久化，见 pubsub 模块
+    Publish {
+        channel: String,
+        #[serde(with = "serde_bytes")]
+        payload: Vec<u8>,
+    },
+    // 订阅频道：回复 Ok 后连接进入推送模式，服务器逐条发送 Response::Message，不再接受命令
+    Subscribe {
+        channels: Vec<String>,
+    },
+    // 带幂等键的写命令：(token, key) 相同的请求只执行一次，重复的请求返回第一次的响应；
+    // token 由客户端为自己的会话生成，跨重连保持不变
+    Idempotent {
+        token: u64,
+        key: u64,
+        cmd: Box<Command>,
+    },
+}
+
+impl Command {
+    pub fn new_get(cf: String, key: Vec<u8>) -> Self {
+        Command::Get { cf, key }
+    }
+
+    pub fn new_put(cf: String, key: Vec<u8>, value: Vec<u8>) -> Self {
+        Command::Put { cf, key, value }
+    }
+
+    pub fn new_delete(cf: String, key: Vec<u8>) -> Self {
+        Command::Delete { cf, key }
+    }
+
+    /// 扫描 [start_key, end_key)，不带过滤条件和显式端点
+    pub fn new_scan(cf: String, start_key: Vec<u8>, end_key: Option<Vec<u8>>, limit: usize) -> Self {
+        Command::Scan { cf, start_key, end_key, limit, filter: None, start_bound: None, end_bound: None }
+    }
+
+    pub fn new_batch(ops: Vec<Modify>, mode: BatchMode) -> Self {
+        Command::Batch { ops, mode }
+    }
+
+    /// 命令是否只能在管理员连接上执行
+    /// 新增命令必须在这里显式声明其类别
+    pub fn requires_admin(&self) -> bool {
+        match self {
+            Command::Idempotent { cmd, .. } => cmd.requires_admin(),
+            Command::Flush
+            | Command::Compact
+            | Command::SetConfig { .. }
+            | Command::GetConfig
+            | Command::DropDb { .. }
+            | Command::ResetStats
+            | Command::SetCfQuota { .. }
+            | Command::PurgeTrash { .. }
+            | Command::Clients
+            | Command::RecentErrors { .. }
+            | Command::KillClient { .. }
+            | Command::Verify { .. }
+            | Command::Repair { .. }
+            | Command::Audit { .. }
+            | Command::FixInvalid { .. }
+            | Command::AuditVerify
+            | Command::OplogRange { .. }
+            | Command::Backup
+            | Command::Restore { .. }
+            | Command::BackupToFile { .. }
+            | Command::RestoreFromFile { .. }
+            | Command::Shutdown { .. } => true,
+            Command::Get { .. }
+            | Command::Put { .. }
+            | Command::Delete { .. }
+            | Command::GetDel { .. }
+            | Command::GetSet { .. }
+            | Command::Rename { .. }
+            | Command::Copy { .. }
+            | Command::Expire { .. }
+            | Command::Ttl { .. }
+            | Command::KeyHash { .. }
+            | Command::CreateCf { .. }
+            | Command::RestoreKey { .. }
+            | Command::ScanTrash { .. }
+            | Command::LockAcquire { .. }
+            | Command::LockRelease { .. }
+            | Command::LockRenew { .. }
+            | Command::Scan { .. }
+            | Command::ScanAll { .. }
+            | Command::Sample { .. }
+            | Command::FindByValue { .. }
+            | Command::GetVersion { .. }
+            | Command::History { .. }
+            | Command::Batch { .. }
+            | Command::Hello { .. }
+            | Command::UseDb { .. }
+            | Command::UseCf { .. }
+            | Command::AdminAuth { .. }
+            | Command::Auth { .. }
+            | Command::Publish { .. }
+            | Command::Subscribe { .. }
+            | Command::ListDbs
+            | Command::Info
+            | Command::InfoSummary
+            | Command::ServerInfo
+            | Command::ListCfs { .. }
+            | Command::HotKeys { .. } => false,
+        }
+    }
+
+    /// 命令是否不修改任何数据，客户端故障转移时可以安全重试
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Command::Idempotent { cmd, .. } => cmd.is_read_only(),
+            Command::Get { .. }
+            | Command::Ttl { .. }
+            | Command::KeyHash { .. }
+            | Command::Scan { .. }
+            | Command::ScanAll { .. }
+            | Command::Sample { .. }
+            | Command::FindByValue { .. }
+            | Command::GetVersion { .. }
+            | Command::History { .. }
+            | Command::Hello { .. }
+            | Command::UseDb { .. }
+            | Command::UseCf { .. }
+            | Command::AdminAuth { .. }
+            | Command::Auth { .. }
+            | Command::Subscribe { .. }
+            | Command::ListDbs
+            | Command::Info
+            | Command::InfoSummary
+            | Command::ServerInfo
+            | Command::GetConfig
+            | Command::ListCfs { .. }
+            | Command::HotKeys { .. }
+            | Command::Clients
+            | Command::RecentErrors { .. }
+            | Command::ScanTrash { .. }
+            | Command::Verify { .. }
+            | Command::Audit { .. }
+            | Command::AuditVerify
+            | Command::OplogRange { .. }
+            | Command::Backup
+            | Command::BackupToFile { .. }
+            | Command::PurgeTrash { dry_run: true, .. }
+            | Command::DropDb { dry_run: true, .. } => true,
+            Command::Put { .. }
+            | Command::Delete { .. }
+            | Command::GetDel { .. }
+            | Command::GetSet { .. }
+            | Command::Rename { .. }
+            | Command::Copy { .. }
+            | Command::Expire { .. }
+            | Command::CreateCf { .. }
+            | Command::RestoreKey { .. }
+            | Command::PurgeTrash { .. }
+            | Command::LockAcquire { .. }
+            | Command::LockRelease { .. }
+            | Command::LockRenew { .. }
+            | Command::Publish { .. }
+            | Command::Batch { .. }
+            | Command::DropDb { .. }
+            | Command::SetConfig { .. }
+            | Command::Flush
+            | Command::Compact
+            | Command::ResetStats
+            | Command::SetCfQuota { .. }
+            | Command::KillClient { .. }
+            | Command::Repair { .. }
+            | Command::FixInvalid { .. }
+            | Command::Restore { .. }
+            | Command::RestoreFromFile { .. }
+            | Command::Shutdown { .. } => false,
+        }
+    }
+
+    /// 命令类型名，用于统计和日志
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::Get { .. } => "Get",
+            Command::Put { .. } => "Put",
+            Command::Delete { .. } => "Delete",
+            Command::ScanAll { .. } => "ScanAll",
+            Command::GetDel { .. } => "GetDel",
+            Command::GetSet { .. } => "GetSet",
+            Command::Rename { .. } => "Rename",
+            Command::Copy { .. } => "Copy",
+            Command::Expire { .. } => "Expire",
+            Command::Ttl { .. } => "Ttl",
+            Command::KeyHash { .. } => "KeyHash",
+            Command::RestoreKey { .. } => "RestoreKey",
+            Command::ScanTrash { .. } => "ScanTrash",
+            Command::Sample { .. } => "Sample",
+            Command::PurgeTrash { .. } => "PurgeTrash",
+            Command::CreateCf { .. } => "CreateCf",
+            Command::SetCfQuota { .. } => "SetCfQuota",
+            Command::FindByValue { .. } => "FindByValue",
+            Command::LockAcquire { .. } => "LockAcquire",
+            Command::LockRelease { .. } => "LockRelease",
+            Command::LockRenew { .. } => "LockRenew",
+            Command::Scan { .. } => "Scan",
+            Command::GetVersion { .. } => "GetVersion",
+            Command::History { .. } => "History",
+            Command::Batch { .. } => "Batch",
+            Command::Hello { .. } => "Hello",
+            Command::UseDb { .. } => "UseDb",
+            Command::UseCf { .. } => "UseCf",
+            Command::AdminAuth { .. } => "AdminAuth",
+            Command::Auth { .. } => "Auth",
+            Command::Publish { .. } => "Publish",
+            Command::Subscribe { .. } => "Subscribe",
+            Command::ListDbs => "ListDbs",
+            Command::DropDb { .. } => "DropDb",
+            Command::Info => "Info",
+            Command::InfoSummary => "InfoSummary",
+            Command::ServerInfo => "ServerInfo",
+            Command::ListCfs { .. } => "ListCfs",
+            Command::SetConfig { .. } => "SetConfig",
+            Command::GetConfig => "GetConfig",
+            Command::Flush => "Flush",
+            Command::Compact => "Compact",
+            Command::HotKeys { .. } => "HotKeys",
+            Command::ResetStats => "ResetStats",
+            Command::Clients => "Clients",
+            Command::RecentErrors { .. } => "RecentErrors",
+            Command::KillClient { .. } => "KillClient",
+            Command::Verify { .. } => "Verify",
+            Command::AuditVerify => "AuditVerify",
+            Command::OplogRange { .. } => "OplogRange",
+            Command::Repair { .. } => "Repair",
+            Command::Audit { .. } => "Audit",
+            Command::FixInvalid { .. } => "FixInvalid",
+            Command::Backup => "Backup",
+            Command::Restore { .. } => "Restore",
+            Command::BackupToFile { .. } => "BackupToFile",
+            Command::RestoreFromFile { .. } => "RestoreFromFile",
+            Command::Shutdown { .. } => "Shutdown",
+            // 按被包装的命令统计
+            Command::Idempotent { cmd, .. } => cmd.kind(),
+        }
+    }
+
+    /// 命令作用的所有列族，客户端发送前用它校验列族名
+    pub fn cfs(&self) -> Vec<&str> {
+        match self {
+            Command::Idempotent { cmd, .. } => cmd.cfs(),
+            Command::Get { cf, .. }
+            | Command::Put { cf, .. }
+            | Command::Delete { cf, .. }
+            | Command::GetDel { cf, .. }
+            | Command::GetSet { cf, .. }
+            | Command::Rename { cf, .. }
+            | Command::Copy { cf, .. }
+            | Command::Expire { cf, .. }
+            | Command::Ttl { cf, .. }
+            | Command::KeyHash { cf, .. }
+            | Command::CreateCf { cf, .. }
+            | Command::RestoreKey { cf, .. }
+            | Command::SetCfQuota { cf, .. }
+            | Command::Scan { cf, .. }
+            | Command::FindByValue { cf, .. }
+            | Command::GetVersion { cf, .. }
+            | Command::History { cf, .. } => vec![cf],
+            Command::Batch { ops, .. } => ops.iter().map(|op| op.cf.as_str()).collect(),
+            Command::Verify { cf }
+            | Command::ScanTrash { cf, .. }
+            | Command::Sample { cf, .. }
+            | Command::Audit { cf, .. }
+            | Command::FixInvalid { cf, .. } => cf.iter().map(String::as_str).collect(),
+            Command::ScanAll { start, .. } => start.iter().map(|(cf, _)| cf.as_str()).collect(),
+            Command::LockAcquire { .. }
+            | Command::LockRelease { .. }
+            | Command::LockRenew { .. }
+            | Command::Hello { .. }
+            | Command::UseDb { .. }
+            | Command::UseCf { .. }
+            | Command::AdminAuth { .. }
+            | Command::Auth { .. }
+            | Command::Publish { .. }
+            | Command::Subscribe { .. }
+            | Command::ListDbs
+            | Command::DropDb { .. }
+            | Command::Info
+            | Command::InfoSummary
+            | Command::ServerInfo
+            | Command::ListCfs { .. }
+            | Command::SetConfig { .. }
+            | Command::GetConfig
+            | Command::Flush
+            | Command::Compact
+            | Command::HotKeys { .. }
+            | Command::ResetStats
+            | Command::PurgeTrash { .. }
+            | Command::Clients
+            | Command::RecentErrors { .. }
+            | Command::KillClient { .. }
+            | Command::Repair { .. }
+            | Command::AuditVerify
+            | Command::OplogRange { .. }
+            | Command::Backup
+            | Command::Restore { .. }
+            | Command::BackupToFile { .. }
+            | Command::RestoreFromFile { .. }
+            | Command::Shutdown { .. } => Vec::new(),
+        }
+    }
+
+    /// 命令读写的所有键，不含扫描的边界
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            Command::Get { key, .. }
+            | Command::Put { key, .. }
+            | Command::Delete { key, .. }
+            | Command::GetDel { key, .. }
+            | Command::GetSet { key, .. }
+            | Command::Expire { key, .. }
+            | Command::Ttl { key, .. }
+            | Command::KeyHash { key, .. }
+            | Command::RestoreKey { key, .. }
+            | Command::GetVersion { key, .. }
+            | Command::History { key, .. } => vec![key],
+            Command::Rename { old_key, new_key, .. } => vec![old_key, new_key],
+            Command::Copy { src_key, dst_key, .. } => vec![src_key, dst_key],
+            Command::Batch { ops, .. } => ops.iter().map(|op| op.key.as_slice()).collect(),
+            Command::Idempotent { cmd, .. } => cmd.keys(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// 命令作用的所有列族，不针对列族的命令返回空列表
+    pub fn cfs_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Command::Idempotent { cmd, .. } => cmd.cfs_mut(),
+            Command::Get { cf, .. }
+            | Command::Put { cf, .. }
+            | Command::Delete { cf, .. }
+            | Command::GetDel { cf, .. }
+            | Command::GetSet { cf, .. }
+            | Command::Rename { cf, .. }
+            | Command::Copy { cf, .. }
+            | Command::Expire { cf, .. }
+            | Command::Ttl { cf, .. }
+            | Command::KeyHash { cf, .. }
+            | Command::CreateCf { cf, .. }
+            | Command::RestoreKey { cf, .. }
+            | Command::SetCfQuota { cf, .. }
+            | Command::Scan { cf, .. }
+            | Command::FindByValue { cf, .. }
+            | Command::GetVersion { cf, .. }
+            | Command::History { cf, .. } => vec![cf],
+            Command::Batch { ops, .. } => ops.iter_mut().map(|op| &mut op.cf).collect(),
+            Command::Verify { cf }
+            | Command::ScanTrash { cf, .. }
+            | Command::Sample { cf, .. }
+            | Command::Audit { cf, .. }
+            | Command::FixInvalid { cf, .. } => cf.iter_mut().collect(),
+            // 起点的列族在 raw_scan_all 中按会话的数据库解析
+            Command::ScanAll { .. }
+            | Command::LockAcquire { .. }
+            | Command::LockRelease { .. }
+            | Command::LockRenew { .. }
+            | Command::Hello { .. }
+            | Command::UseDb { .. }
+            | Command::UseCf { .. }
+            | Command::AdminAuth { .. }
+            | Command::Auth { .. }
+            | Command::Publish { .. }
+            | Command::Subscribe { .. }
+            | Command::ListDbs
+            | Command::DropDb { .. }
+            | Command::Info
+            | Command::InfoSummary
+            | Command::ServerInfo
+            | Command::ListCfs { .. }
+            | Command::SetConfig { .. }
+            | Command::GetConfig
+            | Command::Flush
+            | Command::Compact
+            | Command::HotKeys { .. }
+            | Command::ResetStats
+            | Command::PurgeTrash { .. }
+            | Command::Clients
+            | Command::RecentErrors { .. }
+            | Command::KillClient { .. }
+            | Command::Repair { .. }
+            | Command::AuditVerify
+            | Command::OplogRange { .. }
+            | Command::Backup
+            | Command::Restore { .. }
+            | Command::BackupToFile { .. }
+            | Command::RestoreFromFile { .. }
+            | Command::Shutdown { .. } => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Get { cf, key } => {
+                write!(f, "Get(cf: {}, key: {})", cf, display_bytes(key))
+            }
+            Command::Put { cf, key, value } => {
+                write!(
+                    f,
+                    "Put(cf: {}, key: {}, value: {})",
+                    cf,
+                    display_bytes(key),
+                    display_bytes(value)
+                )
+            }
+            Command::Delete { cf, key } => {
+                write!(f, "Delete(cf: {}, key: {})", cf, display_bytes(key))
+            }
+            Command::ScanAll { start, limit } => match start {
+                Some((cf, Bytes(key))) => write!(
+                    f,
+                    "ScanAll(start: {}/{}, limit: {})",
+                    cf,
+                    display_bytes(key),
+                    limit
+                ),
+                None => write!(f, "ScanAll(limit: {})", limit),
+            },
+            Command::GetDel { cf, key } => {
+                write!(f, "GetDel(cf: {}, key: {})", cf, display_bytes(key))
+            }
+            Command::Expire { cf, key, ttl_ms } => {
+                write!(f, "Expire(cf: {}, key: {}, ttl_ms: {})", cf, display_bytes(key), ttl_ms)
+            }
+            Command::Ttl { cf, key } => write!(f, "Ttl(cf: {}, key: {})", cf, display_bytes(key)),
+            Command::KeyHash { cf, key } => write!(f, "KeyHash(cf: {}, key: {})", cf, display_bytes(key)),
+            Command::GetSet { cf, key, value } => {
+                write!(
+                    f,
+                    "GetSet(cf: {}, key: {}, value: {})",
+                    cf,
+                    display_bytes(key),
+                    display_bytes(value)
+                )
+            }
+            Command::Rename { cf, old_key, new_key, overwrite } => {
+                write!(
+                    f,
+                    "Rename(cf: {}, old_key: {}, new_key: {}, overwrite: {})",
+                    cf,
+                    display_bytes(old_key),
+                    display_bytes(new_key),
+                    overwrite
+                )
+            }
+            Command::Copy { cf, src_key, dst_key, overwrite } => {
+                write!(
+                    f,
+                    "Copy(cf: {}, src_key: {}, dst_key: {}, overwrite: {})",
+                    cf,
+                    display_bytes(src_key),
+                    display_bytes(dst_key),
+                    overwrite
+                )
+            }
+            Command::FindByValue { cf, value, limit } => {
+                write!(
+                    f,
+                    "FindByValue(cf: {}, value: {}, limit: {})",
+                    cf,
+                    display_bytes(value),
+                    limit
+                )
+            }
+            Command::CreateCf { cf, options } => match options {
+                Some(options) => write!(f, "CreateCf(cf: {}, options: {:?})", cf, options),
+                None => write!(f, "CreateCf(cf: {})", cf),
+            },
+            Command::SetCfQuota { cf, max_keys, max_bytes } => {
+                write!(f, "SetCfQuota(cf: {}, max_keys: {:?}, max_bytes: {:?})", cf, max_keys, max_bytes)
+            }
+            Command::LockAcquire { name, ttl_ms } => write!(f, "LockAcquire(name: {}, ttl_ms: {})", name, ttl_ms),
+            Command::LockRelease { name, token } => write!(f, "LockRelease(name: {}, token: {})", name, token),
+            Command::LockRenew { name, token, ttl_ms } => {
+                write!(f, "LockRenew(name: {}, token: {}, ttl_ms: {})", name, token, ttl_ms)
+            }
+            Command::Scan { cf, start_key, end_key, limit, filter, start_bound, end_bound } => {
+                let end_key_str = match end_key {
+                    Some(k) => display_bytes(k),
+                    None => "None".to_string(),
+                };
+                write!(
+                    f,
+                    "Scan(cf: {}, start_key: {}, end_key: {}, limit: {}",
+                    cf,
+                    display_bytes(start_key),
+                    end_key_str,
+                    limit
+                )?;
+                if let Some(bound) = start_bound {
+                    write!(f, ", start_bound: {}", bound)?;
+                }
+                if let Some(bound) = end_bound {
+                    write!(f, ", end_bound: {}", bound)?;
+                }
+                if let Some(filter) = filter {
+                    write!(f, ", filter: {:?}", filter)?;
+                }
+                write!(f, ")")
+            }
+            Command::GetVersion { cf, key, version } => {
+                write!(
+                    f,
+                    "GetVersion(cf: {}, key: {}, version: {})",
+                    cf,
+                    display_bytes(key),
+                    version
+                )
+            }
+            Command::History { cf, key, limit } => {
+                write!(
+                    f,
+                    "History(cf: {}, key: {}, limit: {})",
+                    cf,
+                    display_bytes(key),
+                    limit
+                )
+            }
+            Command::Batch { ops, mode } => write!(f, "Batch(ops: {}, mode: {:?})", ops.len(), mode),
+            Command::Hello { client_version, features } => {
+                write!(f, "Hello(client_version: {}, features: [{}])", client_version, features.join(", "))
+            }
+            Command::UseDb { name } => write!(f, "UseDb(name: {})", name),
+            Command::UseCf { cf } => write!(f, "UseCf(cf: {})", cf),
+            Command::AdminAuth { .. } => write!(f, "AdminAuth"),
+            Command::Auth { name, .. } => write!(f, "Auth {}", name),
+            Command::Publish { channel, payload } => write!(f, "Publish(channel: {}, payload: {})", channel, display_bytes(payload)),
+            Command::Subscribe { channels } => write!(f, "Subscribe(channels: [{}])", channels.join(", ")),
+            Command::ListDbs => write!(f, "ListDbs"),
+            Command::DropDb { name, dry_run } => write!(f, "DropDb(name: {}, dry_run: {})", name, dry_run),
+            Command::RestoreKey { cf, key, overwrite } => {
+                write!(f, "RestoreKey(cf: {}, key: {}, overwrite: {})", cf, display_bytes(key), overwrite)
+            }
+            Command::ScanTrash { cf, limit } => {
+                write!(f, "ScanTrash(cf: {}, limit: {})", cf.as_deref().unwrap_or("*"), limit)
+            }
+            Command::Sample { cf, count } => write!(f, "Sample(cf: {}, count: {})", cf.as_deref().unwrap_or("*"), count),
+            Command::PurgeTrash { all, dry_run } => write!(f, "PurgeTrash(all: {}, dry_run: {})", all, dry_run),
+            Command::Info => write!(f, "Info"),
+            Command::InfoSummary => write!(f, "InfoSummary"),
+            Command::ServerInfo => write!(f, "ServerInfo"),
+            Command::ListCfs { start_after, limit } => {
+                write!(f, "ListCfs(start_after: {}, limit: {})", start_after.as_deref().unwrap_or("-"), limit)
+            }
+            Command::SetConfig { key, value } => write!(f, "SetConfig {}={}", key, value),
+            Command::GetConfig => write!(f, "GetConfig"),
+            Command::Flush => write!(f, "Flush"),
+            Command::Compact => write!(f, "Compact"),
+            Command::HotKeys { top_n } => write!(f, "HotKeys(top_n: {})", top_n),
+            Command::ResetStats => write!(f, "ResetStats"),
+            Command::Clients => write!(f, "Clients"),
+            Command::AuditVerify => write!(f, "AuditVerify"),
+            Command::OplogRange { from_seq, to_seq } => match to_seq {
+                Some(to_seq) => write!(f, "OplogRange({}..={})", from_seq, to_seq),
+                None => write!(f, "OplogRange({}..)", from_seq),
+            },
+            Command::RecentErrors { count } => write!(f, "RecentErrors(count: {})", count),
+            Command::KillClient { id } => write!(f, "KillClient(id: {})", id),
+            Command::Verify { cf } => write!(f, "Verify(cf: {})", cf.as_deref().unwrap_or("*")),
+            Command::Repair { quarantine } => write!(f, "Repair(quarantine: {})", quarantine),
+            Command::Audit { cf, checks, limit, .. } => {
+                let checks: Vec<String> = checks.iter().map(CheckKind::to_string).collect();
+                write!(f, "Audit(cf: {}, checks: [{}], limit: {})", cf.as_deref().unwrap_or("*"), checks.join(", "), limit)
+            }
+            Command::FixInvalid { cf, checks, policy } => {
+                let checks: Vec<String> = checks.iter().map(CheckKind::to_string).collect();
+                write!(f, "FixInvalid(cf: {}, checks: [{}], policy: {:?})", cf.as_deref().unwrap_or("*"), checks.join(", "), policy)
+            }
+            Command::Backup => write!(f, "Backup"),
+            Command::Restore { archive, force } => write!(f, "Restore(lines: {}, force: {})", archive.len(), force),
+            Command::BackupToFile { path } => write!(f, "BackupToFile(path: {})", path),
+            Command::RestoreFromFile { path, force } => write!(f, "RestoreFromFile(path: {}, force: {})", path, force),
+            Command::Shutdown { flush } => write!(f, "Shutdown(flush: {})", flush),
+            Command::Idempotent { token, key, cmd } => write!(f, "Idempotent(token: {}, key: {}, {})", token, key, cmd),
+        }
+    }
+}
+
+// #[serde(transparent)] 表示序列化时和内部 Vec<u8> 一样
+// Base64 编码会自动应用
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Bytes(#[serde(with = "serde_bytes")] pub Vec<u8>);
+
+// 键的一个历史版本，value 为 None 表示删除墓碑
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub version: u64,
+    pub timestamp_ms: u64,
+    pub value: Option<Bytes>,
+}
+
+// 列族的统计信息，created_at_ms 为 0 表示创建时间未知（早于记录创建时间的数据）
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CfInfo {
+    pub name: String,
+    pub keys: usize,
+    pub created_at_ms: u64,
+    // 用量与配额，见 storage::CfUsage 和 storage::CfOptions
+    #[serde(default)]
+    pub bytes: usize,
+    #[serde(default)]
+    pub logical_bytes: usize,
+    #[serde(default)]
+    pub max_keys: Option<usize>,
+    #[serde(default)]
+    pub max_bytes: Option<usize>,
+}
+
+// 单个数据库的统计信息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DbInfo {
+    pub name: String,
+    pub total_keys: usize,
+    pub column_families: usize,
+}
+
+/// 服务器自身的信息：版本、数据目录、持久化方式、开启的功能、监听地址和限制，
+/// 启动时作为横幅打印，也可以用 Command::ServerInfo 查询
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: String,
+    #[serde(default)]
+    pub git_hash: Option<String>,
+    pub protocol_version: u32,
+    /// 数据目录的格式版本，见 migration::FORMAT_VERSION
+    pub format_version: u32,
+    pub pid: u32,
+    pub started_at_ms: u64,
+    /// 服务器正在监听的地址，只通过 serve_connection 服务时为空
+    pub listen_addrs: Vec<String>,
+    /// 数据目录，纯内存模式为 None
+    pub data_path: Option<String>,
+    pub durability: storage::Durability,
+    /// 是否配置了后台刷盘策略，否则只在 Flush、关闭和整理时写盘
+    pub background_flush: bool,
+    /// 开启的服务端功能，如 admin-auth、acl、audit-log、group-commit
+    pub features: Vec<String>,
+    /// 可以在 Hello 中协商的协议特性
+    pub protocol_features: Vec<String>,
+    pub limits: ServerLimits,
+}
+
+/// ServerInfo 中的限制，None 表示不限制
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ServerLimits {
+    pub max_memory_bytes: Option<usize>,
+    pub max_response_bytes: Option<usize>,
+    pub max_scan_examined: Option<usize>,
+    pub response_chunk_bytes: Option<usize>,
+    pub segment_max_bytes: u64,
+    pub idle_timeout_ms: Option<u64>,
+    pub max_blocked_read_ms: Option<u64>,
+    pub pubsub_queue_capacity: usize,
+    #[serde(default)]
+    pub max_connections_per_ip: Option<usize>,
+}
+
+impl fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+        let list = |items: &[String]| if items.is_empty() { "-".to_string() } else { items.join(", ") };
+        write!(f, "tinykv {}", self.version)?;
+        if let Some(hash) = &self.git_hash {
+            write!(f, " ({})", hash)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  pid:               {}", self.pid)?;
+        writeln!(f, "  listen:            {}", list(&self.listen_addrs))?;
+        writeln!(f, "  data path:         {}", self.data_path.as_deref().unwrap_or("(in-memory)"))?;
+        writeln!(f, "  format version:    {}", self.format_version)?;
+        writeln!(
+            f,
+            "  durability:        {:?}{}",
+            self.durability,
+            if self.background_flush { ", background flush" } else { "" }
+        )?;
+        writeln!(f, "  protocol:          v{} ({})", self.protocol_version, list(&self.protocol_features))?;
+        writeln!(f, "  features:          {}", list(&self.features))?;
+        let limits = &self.limits;
+        writeln!(f, "  max memory:        {}", or_dash(limits.max_memory_bytes.map(|b| format!("{}B", b))))?;
+        writeln!(f, "  max response:      {}", or_dash(limits.max_response_bytes.map(|b| format!("{}B", b))))?;
+        writeln!(f, "  max scan examined: {}", or_dash(limits.max_scan_examined.map(|n| n.to_string())))?;
+        writeln!(f, "  response chunks:   {}", or_dash(limits.response_chunk_bytes.map(|b| format!("{}B", b))))?;
+        writeln!(f, "  segment size:      {}B", limits.segment_max_bytes)?;
+        writeln!(f, "  idle timeout:      {}", or_dash(limits.idle_timeout_ms.map(|ms| format!("{}ms", ms))))?;
+        writeln!(f, "  max blocked read:  {}", or_dash(limits.max_blocked_read_ms.map(|ms| format!("{}ms", ms))))?;
+        writeln!(f, "  conns per ip:      {}", or_dash(limits.max_connections_per_ip.map(|n| n.to_string())))?;
+        write!(f, "  pubsub queue:      {}", limits.pubsub_queue_capacity)
+    }
+}
+
+// 响应结果；与 Command 一样不保证变体的集合不变
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+#[non_exhaustive]
+pub enum Response {
+    Ok,
+
+    // 用 Bytes 包装 Option<Vec<u8>>
+    Value(Option<Bytes>),
+
+    // Get 的键不存在；只发给协商了 not-found 特性的连接，其他连接仍收到 Value(None)
+    NotFound,
+
+    // 用 Bytes 包装 tuple 内的 Vec<u8>
+    Values(Vec<(Bytes, Bytes)>),
+
+    // 按键顺序排列的键
+    Keys(Vec<Bytes>),
+
+    // 获取到的锁令牌，锁被他人持有时为 None
+    LockToken(Option<u64>),
+
+    Error(String),
+
+    // 从新到旧排列的版本历史
+    History(Vec<Version>),
+
+    // 数据库列表
+    Databases(Vec<DbInfo>),
+
+    // 刷盘结果
+    Flushed(storage::FlushStats),
+
+    // 整理结果
+    Compacted(storage::CompactResult),
+
+    ServerInfo(Box<ServerInfo>),
+
+    // 所有配置项，见 Command::GetConfig
+    Config(Vec<runtime_config::ConfigEntry>),
+
+    // total_keys 和 column_families 针对当前连接选择的数据库
+    Info {
+        total_keys: usize,
+        column_families: Vec<String>,
+        // 当前数据库的所有列族，包括还没有数据的列族
+        #[serde(default)]
+        cf_info: Vec<CfInfo>,
+        #[serde(default)]
+        databases: Vec<DbInfo>,
+        #[serde(default)]
+        durability: storage::Durability,
+        // 整个存储（所有数据库）的近似内存占用
+        #[serde(default)]
+        memory_bytes: usize,
+        #[serde(default)]
+        evicted_keys: u64,
+        // 压缩前后的内存占用，见 storage::StorageOptions::compress_threshold；装箱原因同 maintenance
+        #[serde(default)]
+        compression: Box<storage::CompressionStats>,
+        #[serde(default)]
+        flush: Box<storage::FlushInfo>,
+        // 按命令类型统计的处理耗时分位数（微秒），ResetStats 清零
+        #[serde(default)]
+        latency: BTreeMap<String, LatencySummary>,
+        // 当前数据库的列族数，包括还没有数据的列族
+        #[serde(default)]
+        cf_count: usize,
+        // 启动以来记录的错误事件数，见 RecentErrors
+        #[serde(default)]
+        errors_total: u64,
+        // 正在进行或最近一次的刷盘、整理的进度；以下三项装箱以免 Response 的所有变体都随 Info 变大
+        #[serde(default)]
+        maintenance: Option<Box<storage::MaintenanceStatus>>,
+        #[serde(default)]
+        compaction: Box<storage::CompactionInfo>,
+        // 幂等写请求的缓存统计
+        #[serde(default)]
+        idempotency: Box<IdempotencyStats>,
+        // 移到磁盘层的值，memory_bytes 不包括它们，见 blob 模块
+        #[serde(default)]
+        spill: Box<SpillStats>,
+        // 段文件（"wal"）和审计日志（"audit"，配置时）的文件数、大小和轮转次数
+        #[serde(default)]
+        logs: Vec<LogFileStats>,
+        // 存储数据锁的获取次数和等待时间，ResetStats 清零
+        #[serde(default)]
+        locks: Box<storage::LockWaitStats>,
+        // 启动以来因读阻塞超过 ServerConfig::max_blocked_read 被断开的连接数
+        #[serde(default)]
+        connections_reaped: u64,
+        // 启动以来因来源 IP 的连接数达到 ServerConfig::max_connections_per_ip 被拒绝的连接数
+        #[serde(default)]
+        connections_rejected: u64,
+        // 刷盘失败后的降级状态
+        #[serde(default)]
+        persistence: Box<storage::PersistenceStatus>,
+        // 当前连接的主体和列族权限，见 acl 模块
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        permissions: Option<Box<EffectivePermissions>>,
+        // 服务器启动时的恢复结果，只出现在第一次 Info 响应中
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        recovery: Option<Box<storage::RecoveryReport>>,
+        // 延迟加载的进度，只在加载完成前（或加载失败后）出现；加载完成前其他统计为空
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        load: Option<Box<storage::LoadStatus>>,
+    },
+
+    // 按估计访问次数从高到低排列的热点键
+    HotKeys(Vec<hotkeys::HotKey>),
+
+    // 按连接 id 排列的连接统计
+    Clients(Vec<clients::ClientInfo>),
+
+    // 最近的错误事件，新的在前
+    Errors(Vec<ErrorEvent>),
+
+    // Sample 抽到的键，按 (列族, 键) 排序
+    Samples(Vec<storage::KeySample>),
+
+    // 试运行的删除类命令会删除的条目
+    DeletionPlan(storage::DeletionReport),
+
+    // Atomic 模式的 Batch 中所有无效操作的 (下标, 原因)，没有做任何修改
+    BatchError(Vec<(usize, String)>),
+
+    // BestEffort 模式的 Batch 中每个操作的结果
+    BatchResults(Vec<Result<(), String>>),
+
+    // 回收站中的条目，按 (列族, 键, 删除时间) 排序
+    Trash(Vec<storage::TrashEntry>),
+
+    // 受影响的条目数
+    Count(usize),
+
+    // 键的剩余生存时间（毫秒），没有设置过期时间时为 None
+    Ttl(Option<u64>),
+
+    // KeyHash 的结果
+    KeyHash(u64),
+
+    // 校验失败的 (列族, 键)
+    CorruptKeys(Vec<(String, Bytes)>),
+
+    // AuditVerify 的结果
+    AuditReport(audit::AuditReport),
+
+    // Audit 的结果；next 为下一个未检查的条目（包含），检查完所有条目时为 None。
+    // examined_limit_reached 表示因检查的条目数达到服务器上限而在 limit 之前结束
+    Violations {
+        violations: Vec<Violation>,
+        next: Option<(String, Bytes)>,
+        #[serde(default)]
+        examined_limit_reached: bool,
+    },
+
+    // FixInvalid 的结果
+    Fixed(FixReport),
+
+    // OplogRange 的结果；next 为下一条未返回的记录的序号，已返回范围内的所有记录时为 None
+    Oplog {
+        entries: Vec<oplog::OplogEntry>,
+        next: Option<u64>,
+    },
+
+    // Backup 的结果：备份文件的各行，不含换行
+    Archive(Vec<String>),
+
+    // ListCfs 的结果，next 为下一页的 start_after，没有更多列族时为 None
+    CfList {
+        cfs: Vec<CfInfo>,
+        next: Option<String>,
+    },
+
+    // ScanAll 的结果：(列族, 键, 值)，next 为下一页的起点，没有更多条目时为 None；
+    // truncated 表示这一页因响应大小上限在 limit 之前结束
+    CfValues {
+        entries: Vec<(String, Bytes, Bytes)>,
+        next: Option<(String, Bytes)>,
+        #[serde(default)]
+        truncated: bool,
+    },
+
+    // Scan 的结果超过响应大小上限时返回已读到的部分，next 为第一个未返回的键（包含）；
+    // 只发给协商了 truncation 特性的连接，其他连接收到 ResponseTooLarge 错误。
+    // examined_limit_reached 表示因检查的条目数达到服务器上限而提前结束（其他连接收到
+    // ExaminedLimitReached 错误），是否从 next 继续由客户端决定
+    TruncatedValues {
+        values: Vec<(Bytes, Bytes)>,
+        next: Bytes,
+        #[serde(default)]
+        examined_limit_reached: bool,
+    },
+
+    // 分成多帧发送的列表响应中的一帧，part 是同一种响应中的一段，最后一帧 more 为 false；
+    // 中途出错时以 Error 帧结束。只发给协商了 chunked 特性的连接，见 Response::into_chunks
+    Chunk {
+        part: Box<Response>,
+        more: bool,
+    },
+
+    // 订阅的频道上发布的一条消息，只在 Subscribe 之后的推送模式中发送
+    Message(pubsub::Message),
+
+    // 握手结果：服务器的协议版本和双方都支持的特性
+    Hello {
+        server_version: u32,
+        accepted_features: Vec<String>,
+    },
+}
+
+impl Response {
+    pub fn new_error(message: impl Into<String>) -> Self {
+        Response::Error(message.into())
+    }
+
+    /// 读取单个键的响应，None 表示键不存在
+    pub fn new_value(value: Option<Vec<u8>>) -> Self {
+        Response::Value(value.map(Bytes))
+    }
+
+    /// 把列表响应（Values、TruncatedValues、CfValues、Violations、Oplog、Keys、Archive）按条目大小切成若干帧，每帧的条目
+    /// 累计不超过 max_bytes（至少一条），续扫位置只放在最后一帧；只有一帧或不是列表响应时原样返回
+    pub fn into_chunks(self, max_bytes: usize) -> Vec<Response> {
+        let parts: Vec<Response> = match self {
+            Response::Values(values) => {
+                split_by_size(values, max_bytes, |(Bytes(k), Bytes(v))| entry_wire_size(k, v)).into_iter().map(Response::Values).collect()
+            }
+            Response::Keys(keys) => {
+                split_by_size(keys, max_bytes, |Bytes(k)| entry_wire_size(k, b"")).into_iter().map(Response::Keys).collect()
+            }
+            Response::Archive(lines) => split_by_size(lines, max_bytes, String::len).into_iter().map(Response::Archive).collect(),
+            Response::TruncatedValues { values, next, examined_limit_reached } => {
+                let mut parts: Vec<Response> =
+                    split_by_size(values, max_bytes, |(Bytes(k), Bytes(v))| entry_wire_size(k, v)).into_iter().map(Response::Values).collect();
+                let values = match parts.pop() {
+                    Some(Response::Values(values)) => values,
+                    _ => Vec::new(),
+                };
+                parts.push(Response::TruncatedValues { values, next, examined_limit_reached });
+                parts
+            }
+            Response::Violations { violations, next, examined_limit_reached } => {
+                let mut parts: Vec<Response> = split_by_size(violations, max_bytes, |v| v.cf.len() + entry_wire_size(&v.key.0, b""))
+                    .into_iter()
+                    .map(|violations| Response::Violations { violations, next: None, examined_limit_reached: false })
+                    .collect();
+                if let Some(Response::Violations { next: last_next, examined_limit_reached: last_reached, .. }) = parts.last_mut() {
+                    *last_next = next;
+                    *last_reached = examined_limit_reached;
+                }
+                parts
+            }
+            Response::Oplog { entries, next } => {
+                let mut parts: Vec<Response> = split_by_size(entries, max_bytes, |e| e.ops.iter().map(|op| op.cf.len() + entry_wire_size(&op.key, &op.value)).sum())
+                    .into_iter()
+                    .map(|entries| Response::Oplog { entries, next: None })
+                    .collect();
+                if let Some(Response::Oplog { next: last_next, .. }) = parts.last_mut() {
+                    *last_next = next;
+                }
+                parts
+            }
+            Response::CfValues { entries, next, truncated } => {
+                let mut parts: Vec<Response> = split_by_size(entries, max_bytes, |(cf, Bytes(k), Bytes(v))| cf.len() + entry_wire_size(k, v))
+                    .into_iter()
+                    .map(|entries| Response::CfValues { entries, next: None, truncated: false })
+                    .collect();
+                if let Some(Response::CfValues { next: last_next, truncated: last_truncated, .. }) = parts.last_mut() {
+                    *last_next = next;
+                    *last_truncated = truncated;
+                }
+                parts
+            }
+            response => return vec![response],
+        };
+        if parts.len() <= 1 {
+            return parts;
+        }
+        let last = parts.len() - 1;
+        parts.into_iter().enumerate().map(|(i, part)| Response::Chunk { part: Box::new(part), more: i < last }).collect()
+    }
+
+    /// 把 into_chunks 切出的下一段接到已收到的部分之后，续扫位置以后一段为准
+    pub fn append(&mut self, part: Response) -> Result<(), String> {
+        match (&mut *self, part) {
+            (Response::Values(values), Response::Values(more)) => values.extend(more),
+            (Response::Keys(keys), Response::Keys(more)) => keys.extend(more),
+            (Response::Archive(lines), Response::Archive(more)) => lines.extend(more),
+            (Response::Values(values), Response::TruncatedValues { values: more, next, examined_limit_reached }) => {
+                values.extend(more);
+                *self = Response::TruncatedValues { values: std::mem::take(values), next, examined_limit_reached };
+            }
+            (Response::CfValues { entries, next, truncated }, Response::CfValues { entries: more, next: more_next, truncated: more_truncated }) => {
+                entries.extend(more);
+                *next = more_next;
+                *truncated = more_truncated;
+            }
+            (
+                Response::Violations { violations, next, examined_limit_reached },
+                Response::Violations { violations: more, next: more_next, examined_limit_reached: more_reached },
+            ) => {
+                violations.extend(more);
+                *next = more_next;
+                *examined_limit_reached = more_reached;
+            }
+            (Response::Oplog { entries, next }, Response::Oplog { entries: more, next: more_next }) => {
+                entries.extend(more);
+                *next = more_next;
+            }
+            (_, part) => return Err(format!("Chunk {:?} does not continue the response", part)),
+        }
+        Ok(())
+    }
+}
+
+/// 响应的摘要，只包含类型、条目数和字节数，不输出键和值的内容，可以安全地写进日志；
+/// 错误只输出错误码（见 error_code），完整的消息可能包含键
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs_bytes = |pairs: &[(Bytes, Bytes)]| pairs.iter().map(|(k, v)| k.0.len() + v.0.len()).sum::<usize>();
+        let more = |more: bool| if more { ", more: true" } else { "" };
+        match self {
+            Response::Ok => write!(f, "Ok"),
+            Response::Value(Some(value)) => write!(f, "Value(bytes: {})", value.0.len()),
+            Response::Value(None) => write!(f, "Value(nil)"),
+            Response::NotFound => write!(f, "NotFound"),
+            Response::Values(values) => write!(f, "Values(entries: {}, bytes: {})", values.len(), pairs_bytes(values)),
+            Response::Keys(keys) => write!(f, "Keys(count: {})", keys.len()),
+            Response::LockToken(token) => write!(f, "LockToken(acquired: {})", token.is_some()),
+            Response::Error(message) => write!(f, "Error({})", error_code(message).unwrap_or("-")),
+            Response::History(versions) => write!(f, "History(versions: {})", versions.len()),
+            Response::Databases(dbs) => write!(f, "Databases(count: {})", dbs.len()),
+            Response::Flushed(stats) => write!(f, "Flushed(bytes: {})", stats.bytes_written),
+            Response::Compacted(result) => write!(f, "Compacted(dead_entries: {})", result.dead_entries_removed),
+            Response::ServerInfo(_) => write!(f, "ServerInfo"),
+            Response::Config(entries) => write!(f, "Config(entries: {})", entries.len()),
+            Response::Info { total_keys, cf_count, .. } => write!(f, "Info(total_keys: {}, cfs: {})", total_keys, cf_count),
+            Response::HotKeys(keys) => write!(f, "HotKeys(count: {})", keys.len()),
+            Response::Clients(clients) => write!(f, "Clients(count: {})", clients.len()),
+            Response::Errors(errors) => write!(f, "Errors(count: {})", errors.len()),
+            Response::Samples(samples) => write!(f, "Samples(count: {})", samples.len()),
+            Response::DeletionPlan(plan) => write!(f, "DeletionPlan(keys: {}, bytes: {})", plan.keys, plan.bytes),
+            Response::BatchError(errors) => write!(f, "BatchError(errors: {})", errors.len()),
+            Response::BatchResults(results) => {
+                let failed = results.iter().filter(|r| r.is_err()).count();
+                write!(f, "BatchResults(ops: {}, failed: {})", results.len(), failed)
+            }
+            Response::Trash(entries) => write!(f, "Trash(entries: {})", entries.len()),
+            Response::Count(count) => write!(f, "Count({})", count),
+            Response::Ttl(Some(ttl_ms)) => write!(f, "Ttl(ms: {})", ttl_ms),
+            Response::Ttl(None) => write!(f, "Ttl(none)"),
+            Response::KeyHash(_) => write!(f, "KeyHash"),
+            Response::CorruptKeys(keys) => write!(f, "CorruptKeys(count: {})", keys.len()),
+            Response::AuditReport(report) => write!(f, "AuditReport(records: {}, broken: {})", report.records, report.broken.is_some()),
+            Response::Violations { violations, next, .. } => write!(f, "Violations(count: {}{})", violations.len(), more(next.is_some())),
+            Response::Fixed(report) => write!(f, "Fixed(fixed: {}, skipped: {})", report.fixed.len(), report.skipped.len()),
+            Response::Oplog { entries, next } => write!(f, "Oplog(entries: {}{})", entries.len(), more(next.is_some())),
+            Response::Archive(lines) => write!(f, "Archive(lines: {})", lines.len()),
+            Response::CfList { cfs, next } => write!(f, "CfList(count: {}{})", cfs.len(), more(next.is_some())),
+            Response::CfValues { entries, next, .. } => {
+                let bytes: usize = entries.iter().map(|(_, k, v)| k.0.len() + v.0.len()).sum();
+                write!(f, "CfValues(entries: {}, bytes: {}{})", entries.len(), bytes, more(next.is_some()))
+            }
+            Response::TruncatedValues { values, .. } => {
+                write!(f, "TruncatedValues(entries: {}, bytes: {}, more: true)", values.len(), pairs_bytes(values))
+            }
+            Response::Chunk { part, more: has_more } => write!(f, "Chunk({}{})", part, more(*has_more)),
+            Response::Message(message) => write!(f, "Message(channel: {}, bytes: {})", message.channel, message.payload.0.len()),
+            Response::Hello { server_version, accepted_features } => {
+                write!(f, "Hello(version: {}, features: {})", server_version, accepted_features.join(","))
+            }
+        }
+    }
+}
+
+/// 响应附带的非致命警告，只有协商了 envelope 的连接才会收到
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[non_exhaustive]
+pub enum Warning {
+    /// 结果被截断，需要从续扫位置继续读取
+    Truncated { examined_limit_reached: bool },
+    /// 写命令隐式创建了列族
+    CfAutoCreated { cf: String },
+    /// 写入的值接近响应大小上限，之后可能无法通过扫描读出
+    ValueNearLimit { cf: String, size: usize, limit: usize },
+    /// 刷盘失败，写入只保存在内存中，见 storage::PersistenceStatus
+    PersistenceDegraded { error: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::Truncated { examined_limit_reached: true } => write!(f, "result truncated: examined limit reached"),
+            Warning::Truncated { examined_limit_reached: false } => write!(f, "result truncated: response size limit reached"),
+            Warning::CfAutoCreated { cf } => write!(f, "column family '{}' auto-created", cf),
+            Warning::ValueNearLimit { cf, size, limit } => {
+                write!(f, "value of {} bytes in '{}' is near the {} byte response limit", size, cf, limit)
+            }
+            Warning::PersistenceDegraded { error } => write!(f, "persistence degraded, write kept in memory only: {}", error),
+        }
+    }
+}
+
+/// 客户端发出的一帧请求：命令本身，以及协商了 envelope 后可以附带的请求选项。选项与命令的字段
+/// 并列编码，不带选项的请求与单独的 Command 编码相同，服务器总是按 Request 解析
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request<C = Command> {
+    #[serde(flatten)]
+    pub cmd: C,
+    /// 客户端最多等待的毫秒数，从服务器读到请求时开始计算，见 deadline 模块
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+/// 协商了 envelope 的连接上每一帧响应的外层：服务器执行命令用的时间和警告；
+/// 分帧发送的响应只在第一帧附带警告
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    pub resp: Response,
+    #[serde(default)]
+    pub server_us: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+}
+
+// 按 size 累计切分，每段不超过 max_bytes，但至少包含一条；空列表得到一个空段
+fn split_by_size<T>(items: Vec<T>, max_bytes: usize, size: impl Fn(&T) -> usize) -> Vec<Vec<T>> {
+    let mut parts = vec![Vec::new()];
+    let mut used = 0;
+    for item in items {
+        let item_size = size(&item);
+        let current = parts.last_mut().expect("parts is never empty");
+        if !current.is_empty() && used + item_size > max_bytes {
+            parts.push(Vec::new());
+            used = 0;
+        }
+        used += item_size;
+        parts.last_mut().expect("parts is never empty").push(item);
+    }
+    parts
+}
+
+// 校验数据库名：非空、长度受限，只允许字母、数字和 '-'
+pub fn validate_db_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Database name must not be empty".to_string());
+    }
+    if name.len() > MAX_DB_NAME_LEN {
+        return Err(format!("Database name longer than {} bytes", MAX_DB_NAME_LEN));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid database name: {}", name));
+    }
+    Ok(())
+}
+
+// 校验列族名：非空、长度受限，不能包含数据库分隔符和 '_'（'_' 开头的名字保留给内部列族）
+pub fn validate_cf_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Column family name must not be empty".to_string());
+    }
+    if name.len() > MAX_CF_NAME_LEN {
+        return Err(format!("Column family name longer than {} bytes", MAX_CF_NAME_LEN));
+    }
+    for separator in ["_", DB_SEPARATOR] {
+        if name.contains(separator) {
+            return Err(format!("Column family name must not contain '{}': {}", separator, name));
+        }
+    }
+    Ok(())
+}
+
+// 校验键：不能为空；空值是合法的
+pub fn validate_key(key: &[u8]) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("EmptyKey: key must not be empty".to_string());
+    }
+    Ok(())
+}
+
+/// 跨列族扫描的条目：(列族, 键, 值)
+pub type CfEntry = (String, Vec<u8>, Vec<u8>);
+
+/// 跨列族扫描的位置：(列族, 键)
+pub type CfCursor = (String, Vec<u8>);
+
+/// 一个扫描条目在响应中占用的字节数，即 (Bytes, Bytes) 序列化成 JSON 的长度
+/// 响应大小上限按条目累计，不计列表的分隔符和外层信封
+pub fn entry_wire_size(key: &[u8], value: &[u8]) -> usize {
+    // Bytes 序列化为十进制数字组成的数组，如 [1,22,255]
+    let array_len = |bytes: &[u8]| {
+        let digits: usize = bytes.iter().map(|&b| if b >= 100 { 3 } else if b >= 10 { 2 } else { 1 }).sum();
+        2 + digits + bytes.len().saturating_sub(1)
+    };
+    3 + array_len(key) + array_len(value)
+}
+
+/// 错误消息开头的错误码："UnknownCf: missing" 的错误码为 UnknownCf，没有错误码时为 None
+pub fn error_code(message: &str) -> Option<&str> {
+    match message.split_once(": ") {
+        Some((code, _)) if !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric()) => Some(code),
+        _ => None,
+    }
+}
+
+/// display_bytes 最多渲染的字节数，超出部分以省略号和总长度代替
+pub const DISPLAY_BYTES_LIMIT: usize = 64;
+
+/// 把任意字节渲染为可安全打印的文本：可打印 ASCII 原样输出，
+/// 反斜杠写作 `\\`，其他字节写作 `\xNN`；超过 DISPLAY_BYTES_LIMIT 的部分截断
+pub fn display_bytes(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().min(DISPLAY_BYTES_LIMIT));
+    for &b in bytes.iter().take(DISPLAY_BYTES_LIMIT) {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    if bytes.len() > DISPLAY_BYTES_LIMIT {
+        out.push_str(&format!("...({} bytes)", bytes.len()));
+    }
+    out
+}
+
+/// 稳定的 64 位哈希：FNV-1a 加 splitmix64 的收尾混合，结果只依赖输入字节。
+/// 它是协议的一部分，ShardedClient 用它把键分配到节点，修改会改变已有数据的分布
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58476d1ce4e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
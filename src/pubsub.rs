@@ -0,0 +1,179 @@
+//! 轻量的发布/订阅频道
+//!
+//! 连接用 Command::Subscribe 订阅一组频道后进入推送模式，之后不再接受命令；Command::Publish
+//! 把消息发给频道当前的所有订阅者。每个订阅者有一个有界队列，队列满时新消息被丢弃并计数，
+//! 发布者从不等待慢的订阅者。消息不持久化，订阅之前发布的消息收不到；不支持按模式订阅。
+
+use crate::protocol::Bytes;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// 每个订阅者默认最多排队的消息数
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// 推送给订阅者的一条消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub channel: String,
+    pub payload: Bytes,
+    /// 这条消息送达之前，因该订阅者的队列已满而丢弃的消息数
+    #[serde(default)]
+    pub dropped: u64,
+}
+
+/// 累计的发布统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PubSubStats {
+    pub channels: usize,
+    pub subscribers: usize,
+    pub published: u64,
+    /// 放入订阅者队列的消息数，一条消息发给多个订阅者时分别计数
+    pub delivered: u64,
+    /// 因订阅者队列已满而丢弃的消息数
+    pub dropped: u64,
+}
+
+// 队列中的消息，多个订阅者共享同一份内容
+type Queued = Arc<(String, Vec<u8>)>;
+
+struct Subscriber {
+    id: u64,
+    sender: SyncSender<Queued>,
+    // 上一条消息送达以来丢弃的消息数
+    dropped: AtomicU64,
+}
+
+/// 频道 -> 订阅者
+pub struct PubSub {
+    channels: Mutex<HashMap<String, Vec<Arc<Subscriber>>>>,
+    queue_capacity: usize,
+    next_id: AtomicU64,
+    published: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Default for PubSub {
+    fn default() -> Self {
+        PubSub::new(DEFAULT_QUEUE_CAPACITY)
+    }
+}
+
+impl PubSub {
+    /// queue_capacity 为每个订阅者最多排队的消息数，至少为 1
+    pub fn new(queue_capacity: usize) -> Self {
+        PubSub {
+            channels: Mutex::default(),
+            queue_capacity: queue_capacity.max(1),
+            next_id: AtomicU64::new(1),
+            published: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// 把消息放入频道每个订阅者的队列，返回放入的订阅者数；队列已满的订阅者丢弃这条消息
+    pub fn publish(&self, channel: &str, payload: &[u8]) -> usize {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        let channels = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(subscribers) = channels.get(channel) else {
+            return 0;
+        };
+        let message: Queued = Arc::new((channel.to_string(), payload.to_vec()));
+        let mut delivered = 0;
+        for subscriber in subscribers {
+            match subscriber.sender.try_send(Arc::clone(&message)) {
+                Ok(()) => delivered += 1,
+                Err(TrySendError::Full(_)) => {
+                    subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                // 订阅正在取消
+                Err(TrySendError::Disconnected(_)) => {}
+            }
+        }
+        self.delivered.fetch_add(delivered as u64, Ordering::Relaxed);
+        delivered
+    }
+
+    /// 订阅 channels 中的每个频道，返回的 Subscription 丢弃时取消订阅
+    pub fn subscribe(self: &Arc<Self>, channels: &[String]) -> Subscription {
+        let (sender, receiver) = mpsc::sync_channel(self.queue_capacity);
+        let subscriber = Arc::new(Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            sender,
+            dropped: AtomicU64::new(0),
+        });
+        let mut channels: Vec<String> = channels.to_vec();
+        channels.sort();
+        channels.dedup();
+        let mut map = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        for channel in &channels {
+            map.entry(channel.clone()).or_default().push(Arc::clone(&subscriber));
+        }
+        drop(map);
+        Subscription { hub: Arc::clone(self), subscriber, channels, receiver }
+    }
+
+    pub fn stats(&self) -> PubSubStats {
+        let channels = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut ids: Vec<u64> = channels.values().flatten().map(|s| s.id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        PubSubStats {
+            channels: channels.len(),
+            subscribers: ids.len(),
+            published: self.published.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 一个连接的订阅，丢弃时取消订阅
+pub struct Subscription {
+    hub: Arc<PubSub>,
+    subscriber: Arc<Subscriber>,
+    channels: Vec<String>,
+    receiver: Receiver<Queued>,
+}
+
+impl Subscription {
+    /// 取出下一条消息，timeout 内没有消息时返回 None
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Message> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(queued) => {
+                let (channel, payload) = &*queued;
+                Some(Message {
+                    channel: channel.clone(),
+                    payload: Bytes(payload.clone()),
+                    dropped: self.subscriber.dropped.swap(0, Ordering::Relaxed),
+                })
+            }
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut map = self.hub.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        for channel in &self.channels {
+            if let Some(subscribers) = map.get_mut(channel) {
+                subscribers.retain(|s| s.id != self.subscriber.id);
+                if subscribers.is_empty() {
+                    map.remove(channel);
+                }
+            }
+        }
+    }
+}
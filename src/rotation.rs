@@ -0,0 +1,62 @@
+//! 只追加文件的轮转策略
+//!
+//! 段文件（WAL）、审计日志和 oplog 共用同一套判断：当前文件超过 max_bytes 或写入超过 max_age 后
+//! 换到新文件。审计日志和 oplog 只保留最新的 retain 个轮转文件；段文件只在整理写出新的基础快照、
+//! 清单持久化之后才删除，retain 对它不起作用。
+
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+/// 追加写入的文件何时轮转、保留多少个轮转出的文件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// 当前文件达到这么多字节后轮转，0 表示不按大小轮转
+    pub max_bytes: u64,
+    /// 当前文件开始写入这么久后轮转，None 表示不按时间轮转
+    pub max_age: Option<Duration>,
+    /// 最多保留的轮转文件数，更早的在轮转时删除；None 表示全部保留
+    pub retain: Option<usize>,
+}
+
+impl RotationPolicy {
+    /// 按大小轮转的策略
+    pub fn by_size(max_bytes: u64) -> Self {
+        RotationPolicy { max_bytes, ..RotationPolicy::default() }
+    }
+
+    /// 写入下一条记录前是否应当轮转；空文件不轮转
+    pub fn should_rotate(&self, bytes: u64, opened_at_ms: u64, now_ms: u64) -> bool {
+        if bytes == 0 {
+            return false;
+        }
+        let too_big = self.max_bytes > 0 && bytes >= self.max_bytes;
+        let too_old = self.max_age.is_some_and(|age| now_ms.saturating_sub(opened_at_ms) >= age.as_millis() as u64);
+        too_big || too_old
+    }
+
+    /// 从旧到新排列的轮转文件中超出 retain、应当删除的部分
+    pub fn expired<'a, T>(&self, rotated: &'a [T]) -> &'a [T] {
+        match self.retain {
+            Some(retain) => &rotated[..rotated.len().saturating_sub(retain)],
+            None => &[],
+        }
+    }
+}
+
+/// 一类追加文件的统计，见 Response::Info
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogFileStats {
+    /// "wal"、"audit" 或 "oplog"
+    pub name: String,
+    /// 当前文件的字节数
+    pub active_bytes: u64,
+    /// 现有的文件数，包括当前文件
+    pub files: usize,
+    /// 所有文件的总字节数
+    pub total_bytes: u64,
+    /// 启动以来的轮转次数
+    pub rotations: u64,
+    /// 启动以来因超出保留数删除的文件数
+    pub pruned_files: u64,
+}
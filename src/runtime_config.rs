@@ -0,0 +1,217 @@
+//! 运行时可以修改的服务器配置
+//!
+//! ServerConfig 的字段分为两类：静态字段（数据目录、持久化方式、认证、审计日志等）只在启动时生效；
+//! 动态字段由 RuntimeConfig 保存，管理员用 SetConfig 修改后，下一个请求就按新值处理，
+//! 刷盘间隔由刷盘线程在下一次检查时读取。GetConfig 列出两类字段的当前值。
+
+use serde::{Deserialize, Serialize};
+
+use crate::server::ServerConfig;
+use crate::storage::StandaloneStorage;
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// 可以用 SetConfig 修改的配置项
+pub const DYNAMIC_KEYS: &[&str] = &[
+    "max_response_bytes",
+    "max_scan_examined",
+    "response_chunk_bytes",
+    "lock_wait_warn_threshold_ms",
+    "idle_timeout_ms",
+    "strict_cf_mode",
+    "fail_writes_when_degraded",
+    "flush_interval_ms",
+];
+
+/// 只能在启动时设置的配置项，SetConfig 返回 StaticConfig 错误
+pub const STATIC_KEYS: &[&str] = &[
+    "listen_addrs",
+    "data_path",
+    "durability",
+    "admin_auth",
+    "audit_log",
+    "group_commit",
+    "trash_retention_ms",
+    "max_memory_bytes",
+    "segment_max_bytes",
+    "max_blocked_read_ms",
+];
+
+/// 动态配置项的当前值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DynamicConfig {
+    pub max_response_bytes: Option<usize>,
+    pub max_scan_examined: Option<usize>,
+    pub response_chunk_bytes: Option<usize>,
+    pub lock_wait_warn_threshold: Option<Duration>,
+    /// 只对之后建立的连接生效
+    pub idle_timeout: Option<Duration>,
+    pub strict_cf_mode: bool,
+    pub fail_writes_when_degraded: bool,
+}
+
+/// GetConfig 返回的一项配置；值的格式与 SetConfig 接受的一致，未设置的限制为 none
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    /// 是否可以用 SetConfig 修改
+    pub dynamic: bool,
+}
+
+/// SetConfig 失败的原因，转换为字符串时带有错误码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Unknown(String),
+    Static(String),
+    Invalid { key: String, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Unknown(key) => write!(f, "UnknownConfig: no config key '{}'", key),
+            ConfigError::Static(key) => write!(f, "StaticConfig: '{}' can only be set at startup", key),
+            ConfigError::Invalid { key, reason } => write!(f, "InvalidConfig: {}: {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// 服务器的动态配置，与 API 和连接线程共享
+pub struct RuntimeConfig {
+    values: RwLock<DynamicConfig>,
+    // 静态配置项的值，启动时确定
+    static_values: Vec<(&'static str, String)>,
+    // 刷盘间隔保存在存储中，见 StandaloneStorage::set_flush_interval
+    storage: Arc<StandaloneStorage>,
+}
+
+impl RuntimeConfig {
+    pub fn new(config: &ServerConfig, storage: Arc<StandaloneStorage>) -> Self {
+        let options = &config.storage_options;
+        let static_values = vec![
+            ("data_path", storage.path().map_or("none".to_string(), |p| p.display().to_string())),
+            ("durability", format!("{:?}", storage.durability())),
+            ("admin_auth", config.admin_token.is_some().to_string()),
+            ("audit_log", config.audit_log.as_ref().map_or("none".to_string(), |p| p.display().to_string())),
+            ("group_commit", config.group_commit.is_some().to_string()),
+            ("trash_retention_ms", format_duration(config.trash_retention)),
+            ("max_memory_bytes", format_limit(options.max_memory_bytes)),
+            ("segment_max_bytes", options.segment_max_bytes.to_string()),
+            ("max_blocked_read_ms", format_duration(config.max_blocked_read)),
+        ];
+        RuntimeConfig {
+            values: RwLock::new(DynamicConfig {
+                max_response_bytes: config.max_response_bytes,
+                max_scan_examined: config.max_scan_examined,
+                response_chunk_bytes: config.response_chunk_bytes,
+                lock_wait_warn_threshold: config.lock_wait_warn_threshold,
+                idle_timeout: config.idle_timeout,
+                strict_cf_mode: config.strict_cf_mode,
+                fail_writes_when_degraded: config.fail_writes_when_degraded,
+            }),
+            static_values,
+            storage,
+        }
+    }
+
+    /// 动态配置项的当前值
+    pub fn get(&self) -> DynamicConfig {
+        *self.values.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 所有配置项的当前值，动态项在前；listen_addrs 由 API 补上
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        let dynamic = DYNAMIC_KEYS.iter().map(|key| ConfigEntry {
+            key: key.to_string(),
+            value: self.value(key).unwrap_or_default(),
+            dynamic: true,
+        });
+        let fixed = self.static_values.iter().map(|(key, value)| ConfigEntry {
+            key: key.to_string(),
+            value: value.clone(),
+            dynamic: false,
+        });
+        dynamic.chain(fixed).collect()
+    }
+
+    /// 修改一个动态配置项，立即生效；返回修改前的值
+    pub fn set(&self, key: &str, value: &str) -> Result<String, ConfigError> {
+        if STATIC_KEYS.contains(&key) {
+            return Err(ConfigError::Static(key.to_string()));
+        }
+        let old = self.value(key).ok_or_else(|| ConfigError::Unknown(key.to_string()))?;
+        let invalid = |reason: String| ConfigError::Invalid { key: key.to_string(), reason };
+
+        if key == "flush_interval_ms" {
+            let interval = parse_duration(value).map_err(invalid)?;
+            self.storage.set_flush_interval(interval).map_err(invalid)?;
+            return Ok(old);
+        }
+        let mut values = self.values.write().unwrap_or_else(|e| e.into_inner());
+        match key {
+            "max_response_bytes" => values.max_response_bytes = parse_limit(value).map_err(invalid)?,
+            "max_scan_examined" => values.max_scan_examined = parse_limit(value).map_err(invalid)?,
+            "response_chunk_bytes" => values.response_chunk_bytes = parse_limit(value).map_err(invalid)?,
+            "lock_wait_warn_threshold_ms" => values.lock_wait_warn_threshold = parse_duration(value).map_err(invalid)?,
+            "idle_timeout_ms" => values.idle_timeout = parse_duration(value).map_err(invalid)?,
+            "strict_cf_mode" => values.strict_cf_mode = parse_bool(value).map_err(invalid)?,
+            "fail_writes_when_degraded" => values.fail_writes_when_degraded = parse_bool(value).map_err(invalid)?,
+            _ => return Err(ConfigError::Unknown(key.to_string())),
+        }
+        Ok(old)
+    }
+
+    // 动态配置项的当前值，未知的配置项返回 None
+    fn value(&self, key: &str) -> Option<String> {
+        let values = self.get();
+        Some(match key {
+            "max_response_bytes" => format_limit(values.max_response_bytes),
+            "max_scan_examined" => format_limit(values.max_scan_examined),
+            "response_chunk_bytes" => format_limit(values.response_chunk_bytes),
+            "lock_wait_warn_threshold_ms" => format_duration(values.lock_wait_warn_threshold),
+            "idle_timeout_ms" => format_duration(values.idle_timeout),
+            "strict_cf_mode" => values.strict_cf_mode.to_string(),
+            "fail_writes_when_degraded" => values.fail_writes_when_degraded.to_string(),
+            "flush_interval_ms" => format_duration(self.storage.flush_interval()),
+            _ => return None,
+        })
+    }
+}
+
+fn format_limit(limit: Option<usize>) -> String {
+    limit.map_or("none".to_string(), |n| n.to_string())
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    duration.map_or("none".to_string(), |d| d.as_millis().to_string())
+}
+
+// 正整数，或 none 表示不限制
+fn parse_limit(value: &str) -> Result<Option<usize>, String> {
+    match value {
+        "none" => Ok(None),
+        _ => match value.parse::<usize>() {
+            Ok(0) => Err("must be greater than 0, use 'none' to remove the limit".to_string()),
+            Ok(n) => Ok(Some(n)),
+            Err(_) => Err(format!("expected a positive number or 'none', got '{}'", value)),
+        },
+    }
+}
+
+// 毫秒数，或 none 表示关闭
+fn parse_duration(value: &str) -> Result<Option<Duration>, String> {
+    parse_limit(value).map(|ms| ms.map(|ms| Duration::from_millis(ms as u64)))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" => Ok(true),
+        "false" | "off" => Ok(false),
+        _ => Err(format!("expected true or false, got '{}'", value)),
+    }
+}
@@ -1,35 +1,392 @@
+use crate::accesslog::{self, AccessLog, AccessRecord};
+use crate::acl;
+use crate::audit::AuditLog;
 use crate::storage;
-use crate::common;
+use crate::clients::ClientRegistry;
+use crate::group_commit::GroupCommitConfig;
+use crate::idempotency::IdempotencyConfig;
+use crate::rotation::RotationPolicy;
+use crate::api;
+use crate::deadline::Deadline;
+use crate::protocol;
+use crate::pubsub;
+use crate::errorlog::ErrorCategory;
+use crate::signal;
+use crate::keepalive;
+use crate::doctor::{self, Severity};
+use crate::trace;
+#[cfg(feature = "tls")]
+use crate::tls;
 
-use std::sync::{Arc};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::thread;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 被拒绝的连接关闭前最多等待对端关闭的时间
+const REJECT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 推送模式下检查服务器是否开始关闭的间隔
+const SUBSCRIPTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 检查读阻塞连接的最长间隔，max_blocked_read 较小时按它的一半检查
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 服务器配置
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// 数据目录，None 表示纯内存模式
+    pub data_path: Option<PathBuf>,
+    /// 存储引擎选项；其中的 clock 也决定过期键、锁和回收站清理线程的周期
+    pub storage_options: storage::StorageOptions,
+    /// 管理令牌；设置后连接必须先通过 AdminAuth 才能执行管理命令
+    pub admin_token: Option<String>,
+    /// 可以用 Auth 认证的主体及其列族权限；非空时未认证的连接不能访问任何列族，见 acl 模块
+    pub principals: Vec<acl::Principal>,
+    /// 开启热点键统计时每 N 次 Get/Put 采样一次，None 表示关闭
+    pub hot_key_sample_every: Option<u64>,
+    /// 开启后 Put / Delete / Batch 经由单个提交线程合并写入，None 表示每个请求直接写入
+    pub group_commit: Option<GroupCommitConfig>,
+    /// 开启后写入不存在的列族返回 UnknownCf 错误，列族必须先用 CreateCf 创建
+    pub strict_cf_mode: bool,
+    /// 设置后 Delete 和 DropDb 把条目移到回收站（storage::TRASH_CF），保留这么久后清理，
+    /// 期间可以用 RestoreKey 恢复；None 表示直接删除
+    pub trash_retention: Option<Duration>,
+    /// 按顺序包在每个命令外面的中间件，排在内置的审计日志之后
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+    /// 设置后把每个修改类命令追加到这个审计日志，见 audit 模块
+    pub audit_log: Option<PathBuf>,
+    /// 审计日志超过这么多字节后轮转，0 表示不轮转
+    pub audit_max_bytes: u64,
+    /// 审计日志写入超过这么久后轮转，None 表示不按时间轮转
+    pub audit_max_age: Option<Duration>,
+    /// 最多保留的审计日志轮转文件数（至少 1），None 表示全部保留
+    pub audit_retain: Option<usize>,
+    /// Scan / ScanAll 响应中条目的累计字节上限（见 protocol::entry_wire_size），
+    /// 超出时提前结束并返回续扫位置；None 表示不限制
+    pub max_response_bytes: Option<usize>,
+    /// 单个 Scan 最多检查的条目数（包括不匹配过滤条件和已过期的条目），达到后提前结束并
+    /// 返回续扫位置，避免过滤条件很少匹配时一个请求遍历整个列族；None 表示不限制
+    pub max_scan_examined: Option<usize>,
+    /// 幂等写请求（Command::Idempotent）的响应缓存容量
+    pub idempotency: IdempotencyConfig,
+    /// 设置后列表响应（Scan、ScanAll 等）按条目累计字节切成多帧发送，客户端收齐后拼回，
+    /// 避免一次序列化整个大响应；只对协商了 chunked 特性的连接生效，None 表示不分帧
+    pub response_chunk_bytes: Option<usize>,
+    /// 每个订阅者最多排队的发布消息数，队列满时丢弃新消息；None 表示 pubsub::DEFAULT_QUEUE_CAPACITY
+    pub pubsub_queue_capacity: Option<usize>,
+    /// 一个命令等待存储数据锁的总时间超过该值时输出一条带命令类型的警告，None 表示不检查
+    pub lock_wait_warn_threshold: Option<Duration>,
+    /// 对接受的 TCP 连接开启 keepalive，空闲这么久后开始探测，见 keepalive 模块；None 表示不开启
+    pub tcp_keepalive: Option<Duration>,
+    /// 连接在这么久内没有发来完整的命令时关闭（TCP 连接的读超时）；None 表示不限制
+    pub idle_timeout: Option<Duration>,
+    /// 后台定期断开等待命令超过这么久的 TCP 连接，计入 Info 的 connections_reaped；
+    /// 与 idle_timeout 不同，它不依赖读超时，也能清理 keepalive 没有发现的半开连接。None 表示不检查
+    pub max_blocked_read: Option<Duration>,
+    /// 刷盘失败进入降级状态（见 storage::PersistenceStatus）后，修改数据的命令返回
+    /// PersistenceDegraded 错误而不是只在内存中生效；默认只附带警告，保持可用
+    pub fail_writes_when_degraded: bool,
+    /// 同一来源 IP 最多同时保持的 TCP 连接数，超出时新连接收到 TooManyConnections 错误后被关闭，
+    /// 计入 Info 的 connections_rejected；None 表示不限制
+    pub max_connections_per_ip: Option<usize>,
+    /// 来自回环地址的连接不受 max_connections_per_ip 限制，便于本机的运维工具连接
+    pub exempt_localhost: bool,
+    /// 访问日志的格式，见 accesslog 模块
+    pub log_format: accesslog::LogFormat,
+    /// 访问日志的去处，None 表示标准输出
+    pub log_sink: Option<Arc<dyn accesslog::LogSink>>,
+    /// PEM 格式的证书链；与 tls_key_path 一起设置后，接受的连接先完成 TLS 握手再处理命令，见 tls 模块
+    #[cfg(feature = "tls")]
+    pub tls_cert_path: Option<PathBuf>,
+    /// 证书对应的 PEM 格式私钥
+    #[cfg(feature = "tls")]
+    pub tls_key_path: Option<PathBuf>,
+}
+
+/// 中间件看到的连接信息
+#[derive(Debug, Clone)]
+pub struct ConnContext {
+    /// 连接 ID，与 Clients 列表一致
+    pub conn_id: u64,
+    /// 对端地址，serve_connection 服务的连接为空
+    pub peer_addr: String,
+    /// 执行命令时选择的数据库
+    pub db: String,
+    /// 是否已通过 AdminAuth
+    pub is_admin: bool,
+    /// 通过 Auth 认证的主体名
+    pub principal: Option<String>,
+}
+
+/// 命令处理前后的钩子，用于审计、改写拒绝、自定义指标等
+pub trait Middleware: Send + Sync + fmt::Debug {
+    /// 命令执行前调用；返回 Err 时不再执行命令和后面的 before，直接把该响应回复给客户端
+    fn before(&self, _ctx: &ConnContext, _cmd: &protocol::Command) -> Result<(), Box<protocol::Response>> {
+        Ok(())
+    }
+
+    /// 回复前调用，包括被 before 拒绝的命令；elapsed 为从 before 开始的耗时
+    fn after(&self, _ctx: &ConnContext, _cmd: &protocol::Command, _response: &protocol::Response, _elapsed: Duration) {}
+}
+
+/// 把每个命令（包括值）打印到标准输出；服务器自己输出不含键和值的访问日志（见 accesslog 模块），
+/// 不再默认使用它，需要时可以加到 ServerConfig::middlewares
+#[derive(Debug, Default)]
+pub struct RequestLogger;
+
+impl Middleware for RequestLogger {
+    fn before(&self, _ctx: &ConnContext, cmd: &protocol::Command) -> Result<(), Box<protocol::Response>> {
+        println!("{}", cmd);
+        Ok(())
+    }
+}
+
+/// 服务器与连接线程共享的运行状态
+#[derive(Default)]
+struct ServerState {
+    shutting_down: AtomicBool,
+    /// 监听地址，关闭时用于唤醒阻塞在 accept 上的线程
+    local_addr: Mutex<Option<SocketAddr>>,
+    /// 活跃连接，与 API 共享，关闭时用于中断读取
+    clients: Arc<ClientRegistry>,
+    /// 关闭流程（包括刷盘）是否已完成，监听线程在退出前等待它
+    shutdown_complete: Mutex<bool>,
+    shutdown_done: Condvar,
+}
+
+/// 关闭流程的选项
+#[derive(Debug, Clone)]
+pub struct ShutdownOptions {
+    /// 等待进行中请求完成的最长时间，超时后强制断开剩余连接
+    pub drain_timeout: Duration,
+    /// 是否在退出前刷盘
+    pub flush: bool,
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        ShutdownOptions {
+            drain_timeout: Duration::from_secs(5),
+            flush: true,
+        }
+    }
+}
+
+/// 关闭流程的结果
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownReport {
+    /// 在期限内自行结束的连接数
+    pub drained: usize,
+    /// 超时后被强制断开的连接数
+    pub forced: usize,
+    /// 刷盘结果，未刷盘时为 None
+    pub flush: Option<storage::FlushStats>,
+}
 
 /// KV 数据库服务器
 pub struct KvServer {
-    api: Arc<common::RawKeyValueApi>,
+    api: Arc<api::RawKeyValueApi>,
+    middlewares: Arc<[Arc<dyn Middleware>]>,
+    access_log: Arc<AccessLog>,
+    storage: Arc<storage::StandaloneStorage>,
+    state: Arc<ServerState>,
+    // 配置了证书时接受的连接都包装为 TLS 流
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+    // 配置了 flush_policy 时的后台刷盘线程，随服务器一起停止
+    _flusher: Option<storage::FlushScheduler>,
+    // 回收租约过期的锁
+    _lock_sweeper: storage::Sweeper,
+    // 删除已过期的键
+    _expiry_sweeper: storage::Sweeper,
+    // 配置了 trash_retention 时按保留期清理回收站
+    _trash_sweeper: Option<storage::Sweeper>,
+    // 配置了 compaction 策略时按无效数据比例后台整理
+    _compactor: Option<storage::Sweeper>,
+    // 配置了 max_blocked_read 时断开读阻塞过久的连接
+    _reaper: Option<storage::Sweeper>,
+    // 持久化降级时按退避时间重试刷盘
+    _flush_retrier: Option<storage::Sweeper>,
 }
 
 impl KvServer {
-    pub fn new(storage_path: &str) -> Result<Self, String> {
-        let storage = Arc::new(storage::StandaloneStorage::open(storage_path)?);
-        let api = Arc::new(common::RawKeyValueApi::new(storage));
-        Ok(KvServer { api })
+    pub fn new(storage_path: impl AsRef<Path>) -> Result<Self, String> {
+        Self::with_options(storage_path, storage::StorageOptions::default())
     }
 
+    /// 不持久化数据的服务器
+    pub fn in_memory() -> Result<Self, String> {
+        Self::with_config(ServerConfig::default())
+    }
+
+    /// 使用指定的存储选项（如列族版本记录）创建服务器
+    pub fn with_options(storage_path: impl AsRef<Path>, options: storage::StorageOptions) -> Result<Self, String> {
+        Self::with_config(ServerConfig {
+            data_path: Some(storage_path.as_ref().to_path_buf()),
+            storage_options: options,
+            ..ServerConfig::default()
+        })
+    }
+
+    pub fn with_config(config: ServerConfig) -> Result<Self, String> {
+        // 启动前的简短自检，只记录有问题的项，是否能打开由存储自己判断
+        if let Some(path) = &config.data_path {
+            for diagnostic in doctor::startup_checks(path).iter().filter(|d| d.severity > Severity::Info) {
+                eprintln!("Doctor: {}", diagnostic);
+            }
+        }
+        let storage = Arc::new(match &config.data_path {
+            Some(path) => storage::StandaloneStorage::open_with_options(path, config.storage_options.clone())?,
+            None => storage::StandaloneStorage::in_memory_with_options(config.storage_options.clone()),
+        });
+        // 延迟加载时在后台加载数据，不阻塞监听
+        storage.start_loader();
+        let trash_sweeper = config
+            .trash_retention
+            .map(|retention| storage.start_trash_sweeper(retention, storage::TRASH_SWEEP_INTERVAL));
+        let audit = match &config.audit_log {
+            Some(path) => {
+                let policy = RotationPolicy {
+                    max_bytes: config.audit_max_bytes,
+                    max_age: config.audit_max_age,
+                    retain: config.audit_retain,
+                };
+                Some(Arc::new(AuditLog::open_with_policy(path, policy, Arc::clone(&config.storage_options.clock))?))
+            }
+            None => None,
+        };
+        let middlewares: Arc<[Arc<dyn Middleware>]> = audit
+            .iter()
+            .map(|log| Arc::clone(log) as Arc<dyn Middleware>)
+            .chain(config.middlewares.iter().cloned())
+            .collect();
+        #[cfg(feature = "tls")]
+        let tls = match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert), Some(key)) => Some(tls::server_config(cert, key)?),
+            (None, None) => None,
+            _ => return Err("tls_cert_path and tls_key_path must be set together".to_string()),
+        };
+        let sink = config.log_sink.clone().unwrap_or_else(|| Arc::new(accesslog::StdoutSink));
+        let access_log = Arc::new(AccessLog::new(config.log_format, sink));
+        let max_blocked_read = config.max_blocked_read;
+        let mut api = api::RawKeyValueApi::with_config(Arc::clone(&storage), Arc::new(config));
+        if let Some(log) = audit {
+            api = api.with_audit_log(log);
+        }
+        let api = Arc::new(api);
+        let state = ServerState {
+            clients: Arc::clone(api.clients()),
+            ..ServerState::default()
+        };
+        let reaper = max_blocked_read.map(|max| {
+            let (clients, errors) = (Arc::clone(&state.clients), Arc::clone(storage.error_log()));
+            storage::Sweeper::every((max / 2).min(REAP_INTERVAL), move || {
+                for (id, peer_addr) in clients.reap_blocked(max) {
+                    let message = format!("Closed connection {} ({}) blocked in read for over {:?}", id, peer_addr, max);
+                    eprintln!("{}", message);
+                    errors.record(ErrorCategory::Connection, message);
+                }
+            })
+        });
+        Ok(KvServer {
+            api,
+            middlewares,
+            access_log,
+            _flusher: storage.start_flush_scheduler(),
+            _lock_sweeper: storage.start_lock_sweeper(storage::LOCK_SWEEP_INTERVAL),
+            _expiry_sweeper: storage.start_expiry_sweeper(storage::EXPIRY_SWEEP_INTERVAL),
+            _trash_sweeper: trash_sweeper,
+            _compactor: storage.start_compaction_scheduler(),
+            _reaper: reaper,
+            _flush_retrier: storage.start_flush_retrier(),
+            storage,
+            state: Arc::new(state),
+            #[cfg(feature = "tls")]
+            tls,
+        })
+    }
+
+    /// 在当前线程上监听并服务，直到关闭流程开始
     pub fn start(&self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
         let listener = TcpListener::bind(addr)?;
-        println!("KV Server listening on {}", addr);
+        self.serve(listener)
+    }
+
+    /// 在后台线程启动服务器，返回用于查询地址和关闭服务器的句柄
+    /// 监听地址可以使用端口 0，由系统分配端口
+    pub fn start_background(self, addr: &str) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let server = Arc::new(self);
+
+        let accept_server = Arc::clone(&server);
+        let accept_thread = thread::spawn(move || {
+            if let Err(e) = accept_server.serve(listener) {
+                eprintln!("Accept loop failed: {}", e);
+            }
+        });
+
+        Ok(ServerHandle {
+            addr: local_addr,
+            server,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    fn serve(&self, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        let local_addr = listener.local_addr()?;
+        *self.state.local_addr.lock().map_err(|e| e.to_string())? = Some(local_addr);
+        println!("KV Server listening on {}", local_addr);
+        self.api.add_listen_addr(local_addr.to_string());
+        println!("{}", self.api.server_info());
 
         for stream in listener.incoming() {
+            if self.state.shutting_down.load(Ordering::SeqCst) {
+                break;
+            }
+
             match stream {
                 Ok(stream) => {
+                    if let Err(e) = self.configure_stream(&stream) {
+                        eprintln!("Failed to configure connection: {}", e);
+                    }
                     let api = Arc::clone(&self.api);
+                    let state = Arc::clone(&self.state);
+                    let storage = Arc::clone(&self.storage);
+                    let middlewares = Arc::clone(&self.middlewares);
+                    let access_log = Arc::clone(&self.access_log);
+                    let peer = stream.peer_addr();
+                    let peer_addr = peer.as_ref().map(|a| a.to_string()).unwrap_or_default();
+                    let conn_id = match peer {
+                        Ok(peer) => {
+                            let config = self.api.config();
+                            let limit = config.max_connections_per_ip.filter(|_| !(config.exempt_localhost && peer.ip().is_loopback()));
+                            match state.clients.register_limited(peer_addr.clone(), peer.ip(), limit, stream.try_clone().ok()) {
+                                Some(id) => id,
+                                None => {
+                                    self.reject_connection(stream, &peer_addr, limit.unwrap_or(0));
+                                    continue;
+                                }
+                            }
+                        }
+                        Err(_) => state.clients.register(peer_addr.clone(), stream.try_clone().ok()),
+                    };
+
+                    let stream = self.wrap_stream(stream);
                     thread::spawn(move || {
-                        if let Err(e) = Self::handle_client(stream, api) {
+                        let ctx = Self::conn_context(conn_id, peer_addr);
+                        let result = stream.map_err(Into::into).and_then(|stream| {
+                            Self::handle_client(stream, ctx, &api, &middlewares, &access_log, &state, &storage)
+                        });
+                        if let Err(e) = result {
                             eprintln!("Error handling client: {}", e);
                         }
+                        state.clients.unregister(conn_id);
                     });
                 }
                 Err(e) => {
@@ -38,35 +395,403 @@ impl KvServer {
             }
         }
 
+        // 关闭可能由客户端的 Shutdown 命令发起，等它刷盘完成后再返回
+        Self::wait_shutdown_complete(&self.state)?;
         Ok(())
     }
 
-    fn handle_client(
-        mut stream: TcpStream,
-        api: Arc<common::RawKeyValueApi>,
+    /// 回复超出 max_connections_per_ip 的连接并关闭它，记录到错误日志。在单独的线程中执行，
+    /// 先关闭写端再读完客户端已经发来的数据（如 Hello），避免带着未读数据关闭时对端收到 RST 而读不到错误
+    fn reject_connection(&self, stream: TcpStream, peer_addr: &str, limit: usize) {
+        let message = format!("Rejected connection from {}: per-ip connection limit of {} reached", peer_addr, limit);
+        eprintln!("{}", message);
+        self.storage.error_log().record(ErrorCategory::Connection, message);
+        // TLS 握手也受这个超时限制
+        let _ = stream.set_read_timeout(Some(REJECT_DRAIN_TIMEOUT));
+        let raw = stream.try_clone();
+        let wrapped = self.wrap_stream(stream);
+        thread::spawn(move || {
+            if let Ok(mut stream) = wrapped {
+                let response = protocol::Response::Error(format!("TooManyConnections: per-ip connection limit of {} reached", limit));
+                if let Ok(bytes) = serde_json::to_vec(&response) {
+                    let _ = stream.write_all(&bytes);
+                }
+            }
+            if let Ok(mut raw) = raw {
+                let _ = raw.shutdown(Shutdown::Write);
+                let _ = std::io::copy(&mut raw, &mut std::io::sink());
+            }
+        });
+    }
+
+    /// 配置了证书时把接受的连接包装为 TLS 流，握手在第一次读写时进行
+    fn wrap_stream(&self, stream: TcpStream) -> std::io::Result<Box<dyn protocol::Transport>> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            return Ok(Box::new(tls::accept(config, stream)?));
+        }
+        Ok(Box::new(stream))
+    }
+
+    /// 按配置设置 keepalive 和空闲超时；空闲超时取当前的运行时配置
+    fn configure_stream(&self, stream: &TcpStream) -> std::io::Result<()> {
+        if let Some(interval) = self.api.config().tcp_keepalive {
+            keepalive::set_keepalive(stream, interval)?;
+        }
+        stream.set_read_timeout(self.api.runtime_config().get().idle_timeout)
+    }
+
+    /// 关闭服务器：停止接受连接，等待进行中的请求完成，必要时强制断开，最后刷盘
+    fn shutdown(&self, options: &ShutdownOptions) -> Result<ShutdownReport, String> {
+        Self::shutdown_with(&self.state, &self.storage, options)
+    }
+
+    /// 关闭流程只执行一次；重复调用时等待已开始的关闭完成并返回空报告
+    fn shutdown_with(
+        state: &ServerState,
+        storage: &storage::StandaloneStorage,
+        options: &ShutdownOptions,
+    ) -> Result<ShutdownReport, String> {
+        if state.shutting_down.swap(true, Ordering::SeqCst) {
+            Self::wait_shutdown_complete(state)?;
+            return Ok(ShutdownReport::default());
+        }
+
+        let result = Self::run_shutdown(state, storage, options);
+        *state.shutdown_complete.lock().map_err(|e| e.to_string())? = true;
+        state.shutdown_done.notify_all();
+        result
+    }
+
+    fn wait_shutdown_complete(state: &ServerState) -> Result<(), String> {
+        let mut complete = state.shutdown_complete.lock().map_err(|e| e.to_string())?;
+        while !*complete {
+            complete = state.shutdown_done.wait(complete).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn run_shutdown(
+        state: &ServerState,
+        storage: &storage::StandaloneStorage,
+        options: &ShutdownOptions,
+    ) -> Result<ShutdownReport, String> {
+        println!("Shutdown: stop accepting connections");
+        let local_addr = *state.local_addr.lock().map_err(|e| e.to_string())?;
+        if let Some(addr) = local_addr {
+            // 唤醒阻塞在 accept 上的线程，让它看到关闭标志
+            let _ = TcpStream::connect(addr);
+        }
+
+        // 关闭读端：空闲连接立即结束，正在处理的请求仍能写回响应
+        let initial = state.clients.shutdown_all(Shutdown::Read)?;
+        println!("Shutdown: draining {} connection(s)", initial);
+
+        let deadline = Instant::now() + options.drain_timeout;
+        let mut report = ShutdownReport::default();
+        while !state.clients.is_empty() {
+            if Instant::now() >= deadline {
+                report.forced = state.clients.shutdown_all(Shutdown::Both)?;
+                println!("Shutdown: forcing {} connection(s) closed", report.forced);
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        report.drained = initial.saturating_sub(report.forced);
+
+        // 纯内存模式没有可刷的数据
+        if options.flush && storage.path().is_some() {
+            let stats = storage.flush()?;
+            println!("Shutdown: flushed {} bytes", stats.bytes_written);
+            report.flush = Some(stats);
+        }
+
+        println!("Shutdown: complete");
+        Ok(report)
+    }
+
+    /// 在任意传输层上服务一个已建立的连接
+    /// 嵌入方可以自行完成 TLS 握手等包装，再交给该方法处理命令
+    /// 这类连接出现在 Clients 列表中，但无法被 KillClient 断开
+    pub fn serve_connection<S: Read + Write>(&self, stream: S) -> Result<(), Box<dyn std::error::Error>> {
+        let conn_id = self.state.clients.register(String::new(), None);
+        let ctx = Self::conn_context(conn_id, String::new());
+        let result = Self::handle_client(stream, ctx, &self.api, &self.middlewares, &self.access_log, &self.state, &self.storage);
+        self.state.clients.unregister(conn_id);
+        result
+    }
+
+    fn conn_context(conn_id: u64, peer_addr: String) -> ConnContext {
+        ConnContext { conn_id, peer_addr, db: protocol::DEFAULT_DB.to_string(), is_admin: false, principal: None }
+    }
+
+    fn handle_client<S: Read + Write>(
+        stream: S,
+        mut ctx: ConnContext,
+        api: &api::RawKeyValueApi,
+        middlewares: &[Arc<dyn Middleware>],
+        access_log: &AccessLog,
+        state: &Arc<ServerState>,
+        storage: &Arc<storage::StandaloneStorage>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut buffer = vec![0; 8192];
+        let mut stream = CountingStream { inner: stream, read: 0, written: 0 };
+        let mut pending = Vec::new();
+        let mut session = api.new_session();
+        let conn_id = ctx.conn_id;
+        // 连接上的请求序号，用于访问日志
+        let mut seq = 0;
+        let conn_span = trace::connection(conn_id, &ctx.peer_addr);
+        let _conn_span = conn_span.enter();
 
         loop {
-            let n = stream.read(&mut buffer)?;
-            if n == 0 {
-                break;
+            // 与 envelope 一样按执行命令前协商的特性决定，Hello 的回复仍用握手前的编码
+            let codec = protocol::Codec::negotiated(&session.features);
+            state.clients.set_reading(conn_id, true);
+            let request = match codec.read_message::<protocol::Request, _>(&mut stream, &mut pending) {
+                Ok(Some(request)) => request,
+                Ok(None) => break,
+                Err(e) if is_timeout(e.as_ref()) => {
+                    println!("Closing connection {}: idle timeout", conn_id);
+                    break;
+                }
+                Err(e) => {
+                    // 连接被重置等 IO 错误不是请求本身的问题
+                    if !e.is::<std::io::Error>() {
+                        let message = format!("Bad request from connection {}: {}", conn_id, e);
+                        storage.error_log().record(ErrorCategory::Request, message);
+                    }
+                    return Err(e);
+                }
+            };
+            state.clients.set_reading(conn_id, false);
+            let received = Instant::now();
+            seq += 1;
+            // 截止时间从读到请求时开始计算
+            let deadline = Deadline::from_ms(request.deadline_ms);
+            let cmd = request.cmd;
+            let kind = cmd.kind();
+            let command_span = trace::command(kind);
+            let _command_span = command_span.enter();
+            let shutdown = match &cmd {
+                protocol::Command::Shutdown { flush } => Some(*flush),
+                _ => None,
+            };
+            let subscribe = match &cmd {
+                protocol::Command::Subscribe { channels } => Some(channels.clone()),
+                _ => None,
+            };
+            // 按执行命令前协商的特性决定格式，Hello 的回复总是不带外层
+            let envelope = session.has_feature("envelope");
+            let (cmd, response, server_time) = Self::run_middlewares(api, middlewares, &mut ctx, &mut session, cmd, deadline);
+            let shutdown = shutdown.filter(|_| matches!(response, protocol::Response::Ok));
+            // 在回复 Ok 之前订阅，客户端收到回复后发布的消息都能收到
+            let subscription = subscribe
+                .filter(|_| matches!(response, protocol::Response::Ok))
+                .map(|channels| api.pubsub().subscribe(&channels));
+
+            // 协商了 chunked 的连接按 response_chunk_bytes 分帧发送列表响应
+            let chunk_bytes = api.runtime_config().get().response_chunk_bytes.filter(|_| session.has_feature("chunked"));
+            // 访问日志的字段在分帧前取得，耗时和字节数在写完后填入
+            let mut access = AccessRecord::new(conn_id, seq, &cmd, &response, Duration::ZERO, 0);
+            command_span.record_access(&access);
+            let frames = match chunk_bytes {
+                Some(max) => response.into_chunks(max),
+                None => vec![response],
+            };
+            let mut warnings = std::mem::take(&mut session.request.warnings);
+            for frame in frames {
+                let bytes = match envelope {
+                    true => codec.encode(&protocol::Envelope {
+                        resp: frame,
+                        server_us: server_time.as_micros() as u64,
+                        warnings: std::mem::take(&mut warnings),
+                    })?,
+                    false => codec.encode(&frame)?,
+                };
+                stream.write_all(&bytes)?;
             }
+            access.duration_us = received.elapsed().as_micros() as u64;
+            access.resp_bytes = stream.written;
+            access_log.record(&access);
+
+            // 未解析的剩余字节计入下一条命令
+            let bytes_in = stream.read - pending.len() as u64;
+            state.clients.record(conn_id, kind, bytes_in, stream.written, session.is_admin, &session.db);
+            stream.read = pending.len() as u64;
+            stream.written = 0;
+
+            if let Some(subscription) = subscription {
+                return Self::stream_messages(&mut stream, &subscription, state, envelope, codec);
+            }
+
+            // 先回复再关闭；关闭流程会等待本连接结束，因此放到单独的线程执行
+            if let Some(flush) = shutdown {
+                println!("Shutdown: requested by client");
+                let options = ShutdownOptions { flush, ..ShutdownOptions::default() };
+                let (state, storage) = (Arc::clone(state), Arc::clone(storage));
+                thread::spawn(move || {
+                    if let Err(e) = Self::shutdown_with(&state, &storage, &options) {
+                        eprintln!("Shutdown failed: {}", e);
+                    }
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 订阅后的推送模式：逐条发送消息，直到写入失败（连接已断开）或服务器开始关闭；
+    /// 空闲的连接要等到下一条消息或关闭时才发现对端已断开
+    /// 协商了 envelope 的连接上消息同样带外层，server_us 为 0；消息按连接协商的编码发送
+    fn stream_messages<S: Write>(
+        stream: &mut S,
+        subscription: &pubsub::Subscription,
+        state: &ServerState,
+        envelope: bool,
+        codec: protocol::Codec,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        while !state.shutting_down.load(Ordering::SeqCst) {
+            if let Some(message) = subscription.recv_timeout(SUBSCRIPTION_POLL_INTERVAL) {
+                let resp = protocol::Response::Message(message);
+                let bytes = match envelope {
+                    true => codec.encode(&protocol::Envelope { resp, server_us: 0, warnings: Vec::new() })?,
+                    false => codec.encode(&resp)?,
+                };
+                stream.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
 
-            let cmd: common::Command = serde_json::from_slice(&buffer[..n])?;
-            println!("{}", cmd);
-            let response: common::Response = api.handle_command(cmd);
-            
-            let response_json = serde_json::to_vec(&response)?;
-            stream.write_all(&response_json)?;
+    /// 依次调用 before，全部通过后执行命令，再依次调用 after；返回中间件看到的命令（已替换默认列族）、
+    /// 响应和 handle_command 用的时间，被中间件拒绝时为 0
+    fn run_middlewares(
+        api: &api::RawKeyValueApi,
+        middlewares: &[Arc<dyn Middleware>],
+        ctx: &mut ConnContext,
+        session: &mut api::Session,
+        mut cmd: protocol::Command,
+        deadline: Deadline,
+    ) -> (protocol::Command, protocol::Response, Duration) {
+        ctx.db.clone_from(&session.db);
+        ctx.is_admin = session.is_admin;
+        ctx.principal = session.principal.as_ref().map(|p| p.name.clone());
+        // 中间件看到替换后的列族
+        session.apply_default_cf(&mut cmd);
+        let start = Instant::now();
+        let rejected = middlewares.iter().find_map(|m| m.before(ctx, &cmd).err());
+        let (cmd, response, server_time) = match rejected {
+            Some(response) => (cmd, *response, Duration::ZERO),
+            None => {
+                let handle_start = Instant::now();
+                let response = api.handle_command_with_deadline(session, cmd.clone(), deadline);
+                (cmd, response, handle_start.elapsed())
+            }
+        };
+        let elapsed = start.elapsed();
+        for m in middlewares {
+            m.after(ctx, &cmd, &response, elapsed);
         }
+        (cmd, response, server_time)
+    }
+}
+
+// 读超时（idle_timeout）在不同平台上表现为 WouldBlock 或 TimedOut
+fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(|io| matches!(io.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut))
+}
+
+/// 统计收发字节数的传输层包装
+struct CountingStream<S> {
+    inner: S,
+    read: u64,
+    written: u64,
+}
+
+impl<S: Read> Read for CountingStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for CountingStream<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 后台运行的服务器句柄
+pub struct ServerHandle {
+    addr: SocketAddr,
+    server: Arc<KvServer>,
+    accept_thread: Option<JoinHandle<()>>,
+}
 
+impl ServerHandle {
+    /// 服务器实际监听的地址
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 服务器是否已经停止（例如收到了客户端的 Shutdown 命令）
+    pub fn is_finished(&self) -> bool {
+        self.accept_thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// 等待服务器因 Shutdown 命令停止
+    pub fn wait(mut self) -> Result<(), String> {
+        if let Some(accept_thread) = self.accept_thread.take() {
+            accept_thread.join().map_err(|_| "Accept thread panicked".to_string())?;
+        }
         Ok(())
     }
+
+    /// 执行关闭流程并等待监听线程退出
+    pub fn shutdown(mut self, options: ShutdownOptions) -> Result<ShutdownReport, String> {
+        let report = self.server.shutdown(&options)?;
+        if let Some(accept_thread) = self.accept_thread.take() {
+            accept_thread.join().map_err(|_| "Accept thread panicked".to_string())?;
+        }
+        Ok(report)
+    }
 }
 
-pub fn run_server(data_path: &str, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// 运行服务器直到收到 Shutdown 命令并完成关闭流程
+pub fn run_server(data_path: impl AsRef<Path>, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
     let server = KvServer::new(data_path)?;
     server.start(addr)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 运行服务器直到收到 SIGINT/SIGTERM 或 Shutdown 命令，然后执行关闭流程
+/// 关闭期间再次收到信号会以 signal::FORCED_EXIT_CODE 立即退出进程
+pub fn run_server_with_shutdown(data_path: impl AsRef<Path>, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = ServerConfig { data_path: Some(data_path.as_ref().to_path_buf()), ..ServerConfig::default() };
+    run_config_with_shutdown(config, addr)
+}
+
+/// 与 run_server_with_shutdown 相同，使用完整的服务器配置
+pub fn run_config_with_shutdown(config: ServerConfig, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    signal::install()?;
+    let handle = KvServer::with_config(config)?.start_background(addr)?;
+
+    while signal::received() == 0 {
+        if handle.is_finished() {
+            handle.wait()?;
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    println!("Shutdown: signal received, send again to force exit");
+    handle.shutdown(ShutdownOptions::default())?;
+    Ok(())
+}
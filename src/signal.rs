@@ -0,0 +1,47 @@
+//! 进程信号处理
+//!
+//! 信号处理函数只做异步信号安全的原子计数：第一次 SIGINT/SIGTERM 由
+//! 主线程轮询后走正常的关闭流程，第二次直接以 FORCED_EXIT_CODE 退出。
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 关闭过程中再次收到信号时使用的退出码
+pub const FORCED_EXIT_CODE: i32 = 130;
+
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signum: libc::c_int) {
+    if RECEIVED.fetch_add(1, Ordering::SeqCst) >= 1 {
+        // SAFETY: _exit 是异步信号安全的
+        unsafe { libc::_exit(FORCED_EXIT_CODE) };
+    }
+}
+
+/// 为 SIGINT 和 SIGTERM 安装处理函数
+pub fn install() -> io::Result<()> {
+    #[cfg(unix)]
+    for signum in [libc::SIGINT, libc::SIGTERM] {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: 处理函数只访问原子变量和 _exit
+        if unsafe { libc::signal(signum, handler) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// 已收到的信号次数
+pub fn received() -> usize {
+    RECEIVED.load(Ordering::SeqCst)
+}
+
+/// 阻塞直到收到第一个信号
+pub fn wait() {
+    while received() == 0 {
+        thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -1,180 +1,4433 @@
-use crate::common;
+use crate::api::EncodedKey;
+use crate::clock::{Clock, SystemClock};
+use crate::deadline::Deadline;
+use crate::protocol;
+use crate::errorlog::{ErrorCategory, ErrorLog, ERROR_LOG_CAPACITY};
+use crate::lockfile::DirLock;
+use crate::migration;
+use crate::observer::{Observers, WriteObserver};
+use crate::oplog::{Oplog, OplogConfig, OplogObserver};
+use crate::blob::{BlobStore, BlobValue, SpillStats};
+use crate::histogram::{Histogram, LatencySummary};
+use crate::rotation::{LogFileStats, RotationPolicy};
 
-use std::sync::{Arc, RwLock};
-use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Bound;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
 
-// 独立存储引擎
-pub struct StandaloneStorage {
-    data: Arc<RwLock<BTreeMap<Vec<u8>, Vec<u8>>>>,
-    path: String,
+/// 列族选项
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CfOptions {
+    /// 每个键保留的历史版本数，0 表示不记录历史
+    #[serde(default)]
+    pub keep_versions: usize,
+    /// 维护值到键的倒排索引，支持按值查找键；适合取值较少的分类值
+    #[serde(default)]
+    pub index_values: bool,
+    /// 列族最多包含的键数，超出时写入返回 QuotaExceeded 错误
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_keys: Option<usize>,
+    /// 列族最多占用的字节数（按 CfUsage::bytes 计算），超出时写入返回 QuotaExceeded 错误
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
 }
 
-impl StandaloneStorage {
-    pub fn new() -> Self {
-        StandaloneStorage {
-            data: Arc::new(RwLock::new(BTreeMap::new())),
-            path: String::new(),
+/// 列族的用量，随写入增量维护
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CfUsage {
+    pub keys: usize,
+    /// 近似内存占用，与 Info 中的 memory_bytes 口径相同；压缩的值按压缩后的大小计算
+    pub bytes: usize,
+    /// 按未压缩的值计算的占用，没有开启压缩时与 bytes 相同
+    #[serde(default)]
+    pub logical_bytes: usize,
+}
+
+/// 值压缩的统计，见 StorageOptions::compress_threshold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionStats {
+    pub compressed_values: usize,
+    /// 所有条目按未压缩的值计算的近似内存占用
+    pub logical_bytes: usize,
+    /// 实际的近似内存占用，与 Info 中的 memory_bytes 相同
+    pub physical_bytes: usize,
+}
+
+/// 刷盘的持久性级别
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Durability {
+    /// 直接覆盖写数据文件，写入中途崩溃可能留下不完整的文件
+    None,
+    /// 先写临时文件再原子重命名，但不等待数据落盘
+    #[default]
+    FlushOnly,
+    /// 在 FlushOnly 基础上对文件和所在目录执行 fsync
+    Fsync,
+}
+
+/// 一次刷盘的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlushStats {
+    pub bytes_written: u64,
+    pub fsynced: bool,
+}
+
+/// 数据锁的获取次数和等待时间（微秒），见 StandaloneStorage::lock_stats
+///
+/// 只有没能立即拿到锁的获取才计时，等待分布只包括这些获取
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockWaitStats {
+    pub read_lock_acquisitions: u64,
+    pub write_lock_acquisitions: u64,
+    /// 需要等待的获取次数
+    pub read_lock_waits: u64,
+    pub write_lock_waits: u64,
+    /// 读锁和写锁等待时间合在一起的 p99
+    pub lock_wait_p99_us: u64,
+    pub read_wait: LatencySummary,
+    pub write_wait: LatencySummary,
+}
+
+thread_local! {
+    // 当前线程累计的锁等待时间（微秒），见 take_thread_lock_wait
+    static THREAD_LOCK_WAIT_US: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// 取出并清零当前线程自上次调用以来等待数据锁的总时间，用于按命令报告等待
+pub fn take_thread_lock_wait() -> Duration {
+    Duration::from_micros(THREAD_LOCK_WAIT_US.with(|wait| wait.replace(0)))
+}
+
+#[derive(Default)]
+struct LockCounters {
+    read_acquisitions: AtomicU64,
+    write_acquisitions: AtomicU64,
+    read_wait: Histogram,
+    write_wait: Histogram,
+    all_wait: Histogram,
+}
+
+impl LockCounters {
+    // 获取读锁；只在 try_read 失败、需要等待时计时
+    fn read<'a>(&self, lock: &'a RwLock<StorageData>) -> Result<RwLockReadGuard<'a, StorageData>, String> {
+        self.read_acquisitions.fetch_add(1, Ordering::Relaxed);
+        match lock.try_read() {
+            Ok(guard) => return Ok(guard),
+            Err(TryLockError::Poisoned(e)) => return Err(e.to_string()),
+            Err(TryLockError::WouldBlock) => {}
+        }
+        let started = Instant::now();
+        let guard = lock.read().map_err(|e| e.to_string())?;
+        self.record_wait(&self.read_wait, started.elapsed());
+        Ok(guard)
+    }
+
+    fn write<'a>(&self, lock: &'a RwLock<StorageData>) -> Result<RwLockWriteGuard<'a, StorageData>, String> {
+        self.write_acquisitions.fetch_add(1, Ordering::Relaxed);
+        match lock.try_write() {
+            Ok(guard) => return Ok(guard),
+            Err(TryLockError::Poisoned(e)) => return Err(e.to_string()),
+            Err(TryLockError::WouldBlock) => {}
+        }
+        let started = Instant::now();
+        let guard = lock.write().map_err(|e| e.to_string())?;
+        self.record_wait(&self.write_wait, started.elapsed());
+        Ok(guard)
+    }
+
+    fn record_wait(&self, histogram: &Histogram, waited: Duration) {
+        histogram.record_duration(waited);
+        self.all_wait.record_duration(waited);
+        THREAD_LOCK_WAIT_US.with(|wait| wait.set(wait.get().saturating_add(waited.as_micros() as u64)));
+    }
+
+    fn stats(&self) -> LockWaitStats {
+        LockWaitStats {
+            read_lock_acquisitions: self.read_acquisitions.load(Ordering::Relaxed),
+            write_lock_acquisitions: self.write_acquisitions.load(Ordering::Relaxed),
+            read_lock_waits: self.read_wait.count(),
+            write_lock_waits: self.write_wait.count(),
+            lock_wait_p99_us: self.all_wait.percentile(0.99),
+            read_wait: self.read_wait.summary(),
+            write_wait: self.write_wait.summary(),
+        }
+    }
+
+    fn reset(&self) {
+        self.read_acquisitions.store(0, Ordering::Relaxed);
+        self.write_acquisitions.store(0, Ordering::Relaxed);
+        self.read_wait.reset();
+        self.write_wait.reset();
+        self.all_wait.reset();
+    }
+}
+
+/// 刷盘状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlushInfo {
+    /// 已完成的刷盘次数
+    pub flushes: u64,
+    pub last_duration_ms: u64,
+    pub last_bytes: u64,
+    /// 尚未刷盘的修改数
+    pub dirty: u64,
+}
+
+/// 整理统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompactionInfo {
+    /// 已完成的整理次数，包括后台触发的
+    pub compactions: u64,
+    /// 其中由后台整理线程触发的次数
+    pub automatic: u64,
+    pub last_started_at_ms: u64,
+    pub last_duration_ms: u64,
+    /// 最近一次整理释放的磁盘空间
+    pub last_reclaimed_bytes: u64,
+    /// 最近一次整理重建内存结构释放的容量
+    #[serde(default)]
+    pub last_reclaimed_capacity_bytes: u64,
+    /// 当前的无效数据比例，见 StandaloneStorage::dead_ratio
+    pub dead_ratio: f64,
+}
+
+/// 持久化状态；刷盘失败后进入降级状态，直到下一次刷盘成功
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistenceStatus {
+    pub degraded: bool,
+    /// 最近一次失败是因为磁盘空间或配额耗尽（ENOSPC / EDQUOT）
+    pub disk_full: bool,
+    /// 进入降级状态时的毫秒时间戳
+    pub since_ms: u64,
+    pub consecutive_failures: u64,
+    pub last_error: Option<String>,
+    /// 下一次自动重试刷盘的毫秒时间戳
+    pub next_retry_at_ms: u64,
+}
+
+/// 一次整理的结果；无效数据的回收和内存容量的回收分开统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactResult {
+    /// 删除的过期键和超出保留数量的历史版本
+    pub dead_entries_removed: u64,
+    /// 重写基础快照释放的磁盘空间，纯内存模式下为 0
+    pub reclaimed_disk_bytes: u64,
+    /// 收缩键和值的缓冲区释放的容量（capacity - len）
+    pub reclaimed_capacity_bytes: u64,
+    /// 被收缩的缓冲区个数
+    pub shrunk_buffers: u64,
+}
+
+/// 维护操作的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaintenanceOperation {
+    Flush,
+    Compact,
+}
+
+impl MaintenanceOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenanceOperation::Flush => "flush",
+            MaintenanceOperation::Compact => "compact",
+        }
+    }
+}
+
+/// 维护操作所处的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaintenancePhase {
+    /// 在数据锁内收集条目
+    Collecting,
+    /// 序列化并写盘
+    Writing,
+    /// 已结束，成功或失败
+    Done,
+}
+
+impl MaintenancePhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenancePhase::Collecting => "collecting",
+            MaintenancePhase::Writing => "writing",
+            MaintenancePhase::Done => "done",
+        }
+    }
+}
+
+/// 正在进行或最近一次的维护操作（刷盘、整理）的进度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub operation: MaintenanceOperation,
+    pub started_at_ms: u64,
+    /// 已收集的条目数
+    pub processed_entries: u64,
+    pub total_entries: u64,
+    pub phase: MaintenancePhase,
+    /// 操作失败时的错误，保留到下一次操作开始
+    pub error: Option<String>,
+}
+
+impl MaintenanceStatus {
+    pub fn running(&self) -> bool {
+        self.phase != MaintenancePhase::Done
+    }
+}
+
+// 维护操作的进度；已处理条数用原子计数，收集条目的循环里更新不需要加锁
+#[derive(Default)]
+struct MaintenanceTracker {
+    status: Mutex<Option<MaintenanceStatus>>,
+    processed: AtomicU64,
+}
+
+impl MaintenanceTracker {
+    fn start(&self, operation: MaintenanceOperation, total_entries: usize, started_at_ms: u64) {
+        self.processed.store(0, Ordering::Relaxed);
+        if let Ok(mut status) = self.status.lock() {
+            *status = Some(MaintenanceStatus {
+                operation,
+                started_at_ms,
+                processed_entries: 0,
+                total_entries: total_entries as u64,
+                phase: MaintenancePhase::Collecting,
+                error: None,
+            });
+        }
+    }
+
+    fn advance(&self) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn set_phase(&self, phase: MaintenancePhase) {
+        if let Ok(mut status) = self.status.lock()
+            && let Some(status) = status.as_mut()
+        {
+            status.phase = phase;
+        }
+    }
+
+    fn finish<T>(&self, result: &Result<T, String>) {
+        if let Ok(mut status) = self.status.lock()
+            && let Some(status) = status.as_mut()
+        {
+            status.phase = MaintenancePhase::Done;
+            status.error = result.as_ref().err().cloned();
+        }
+    }
+
+    fn status(&self) -> Option<MaintenanceStatus> {
+        let mut status = self.status.lock().ok()?.clone()?;
+        status.processed_entries = self.processed.load(Ordering::Relaxed).min(status.total_entries);
+        Some(status)
+    }
+}
+
+/// 打开持久化存储时的恢复结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    /// 基础快照中的条目数
+    pub snapshot_entries: usize,
+    /// 重放的段文件记录数
+    pub wal_records_replayed: usize,
+    /// 跳过的损坏记录数，包括段文件末尾写了一半的记录
+    pub corrupt_records_skipped: usize,
+    /// 最后一条已应用记录的序号，没有记录时为 0
+    pub last_sequence: u64,
+    /// 加载时已经过期而被丢弃的键数
+    #[serde(default)]
+    pub expired_entries_dropped: usize,
+    /// 打开时执行的格式迁移，按执行顺序
+    #[serde(default)]
+    pub migrations: Vec<String>,
+}
+
+/// 延迟加载期间读请求的处理方式，见 StorageOptions::lazy_load
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadingReads {
+    /// 阻塞到加载完成
+    Wait,
+    /// 立即返回 Loading 错误
+    Fail,
+}
+
+/// 延迟加载的进度
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadStatus {
+    pub loaded: bool,
+    /// 已读取并解析的快照和段文件字节数
+    pub loaded_bytes: u64,
+    pub total_bytes: u64,
+    /// 加载期间收到、等待在加载完成时应用的写入批次数
+    pub pending_writes: usize,
+    /// 后台加载失败的原因，失败后读写都返回 LoadFailed 错误
+    pub error: Option<String>,
+}
+
+/// 后台刷盘策略，见 StandaloneStorage::start_flush_scheduler
+#[derive(Debug, Clone)]
+pub struct FlushPolicy {
+    /// 有未刷盘的修改且距上次刷盘超过该时间时刷盘
+    pub interval: Option<Duration>,
+    /// 未刷盘的修改数达到该值时刷盘
+    pub dirty_threshold: Option<u64>,
+    /// 未刷盘的修改数超过该值后，每次写入返回前休眠 throttle，让刷盘线程追上
+    pub high_water_mark: Option<u64>,
+    pub throttle: Duration,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy {
+            interval: Some(Duration::from_secs(1)),
+            dirty_threshold: None,
+            high_water_mark: None,
+            throttle: Duration::from_millis(1),
+        }
+    }
+}
+
+/// 后台整理策略，见 StandaloneStorage::start_compaction_scheduler
+#[derive(Debug, Clone)]
+pub struct CompactionPolicy {
+    /// 无效数据比例超过该值时整理
+    pub trigger_dead_ratio: f64,
+    /// 距上次整理（包括手动整理）不足该时间时不触发
+    pub min_interval: Duration,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        CompactionPolicy {
+            trigger_dead_ratio: 0.5,
+            min_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+// 刷盘线程检查触发条件的间隔
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// 整理线程计算无效数据比例的间隔，计算需要持有读锁
+const COMPACTION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// 过期锁清理线程的运行间隔
+pub const LOCK_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+// 回收站清理线程的运行间隔
+pub const TRASH_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+// 过期键清理线程的运行间隔
+pub const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+// 降级状态下重试刷盘的退避时间，每次失败翻倍
+const FLUSH_RETRY_INITIAL: Duration = Duration::from_millis(100);
+const FLUSH_RETRY_MAX: Duration = Duration::from_secs(30);
+
+// 降级状态下检查是否到了重试时间的间隔
+pub const FLUSH_RETRY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 把 IO 错误转换为错误消息；磁盘空间或配额耗尽时加上 DiskFull 前缀
+fn io_error(context: &str, e: io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => format!("DiskFull: {}: {}", context, e),
+        _ => format!("{}: {}", context, e),
+    }
+}
+
+/// 后台刷盘线程的句柄，丢弃时停止线程
+pub struct FlushScheduler {
+    stop: Arc<AtomicBool>,
+    requested: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FlushScheduler {
+    /// 请求尽快刷盘一次，不等待刷盘完成
+    pub fn request_flush(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for FlushScheduler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 定期清理线程（过期锁、回收站、后台整理）的句柄，丢弃时停止线程
+pub struct Sweeper {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Sweeper {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Sweeper {
+    /// 每隔 interval 在后台线程调用一次 tick，直到 Sweeper 被丢弃
+    pub(crate) fn every(interval: Duration, mut tick: impl FnMut() + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            let mut last_tick = Instant::now();
+            while !thread_stop.load(Ordering::SeqCst) {
+                thread::sleep(FLUSH_POLL_INTERVAL.min(interval));
+                if last_tick.elapsed() >= interval {
+                    tick();
+                    last_tick = Instant::now();
+                }
+            }
+        });
+        Sweeper { stop, thread: Some(thread) }
+    }
+}
+
+/// 持久化使用的文件系统操作，测试中可以替换为模拟实现
+pub trait FileSystem: Send + Sync + fmt::Debug {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// 写入整个文件，sync 为 true 时在返回前 fsync 文件
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()>;
+    /// 追加到已有文件末尾，sync 为 true 时在返回前 fsync 文件
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// fsync 目录，使其中的重命名持久化
+    fn sync_dir(&self, path: &Path) -> io::Result<()>;
+    /// 读取整个文件，加载快照和段文件时使用
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+    /// 以流的方式读取文件，加载基础快照时使用；默认读出整个文件
+    fn open_reader(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.read_file(path)?)))
+    }
+    /// 由 write 逐步写出文件内容，写入基础快照时使用；默认先在内存中拼出整个文件再调用 write_file
+    fn write_file_with(&self, path: &Path, sync: bool, write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+        let mut data = Vec::new();
+        write(&mut data)?;
+        self.write_file(path, &data, sync)
+    }
+}
+
+/// 统计经过的字节数
+struct Counting<T> {
+    inner: T,
+    bytes: u64,
+}
+
+impl<T> Counting<T> {
+    fn new(inner: T) -> Self {
+        Counting { inner, bytes: 0 }
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 基于 std::fs 的文件系统实现
+#[derive(Debug, Default)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        let mut file = fs::File::create(path)?;
+        file.write_all(data)?;
+        file.flush()?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new().append(true).open(path)?;
+        file.write_all(data)?;
+        file.flush()?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn open_reader(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::BufReader::new(fs::File::open(path)?)))
+    }
+
+    fn write_file_with(&self, path: &Path, sync: bool, write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        // 只有类 Unix 系统支持以只读方式打开目录并 fsync
+        #[cfg(unix)]
+        fs::File::open(path)?.sync_all()?;
+        #[cfg(not(unix))]
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// 超出内存预算时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvictionPolicy {
+    /// 拒绝会增加内存占用的写入
+    #[default]
+    None,
+    /// 淘汰最近最少访问的条目，直到回到预算以内
+    Lru,
+    /// 把最近最少访问的值移到磁盘（见 blob 模块），键仍然保留在内存中，
+    /// 所以键本身的占用超出预算时不再限制；需要数据目录，纯内存存储中与 None 相同
+    Spill,
+}
+
+/// 存储引擎选项
+#[derive(Debug, Clone)]
+pub struct StorageOptions {
+    /// 按列族名配置的列族选项
+    pub cf_options: HashMap<String, CfOptions>,
+    /// 刷盘的持久性级别
+    pub durability: Durability,
+    /// 持久化使用的文件系统
+    pub fs: Arc<dyn FileSystem>,
+    /// 为每个值保存 CRC32 校验和，读取时校验
+    pub checksums: bool,
+    /// 条目占用内存的上限（近似值），超过后按 eviction 处理
+    pub max_memory_bytes: Option<usize>,
+    pub eviction: EvictionPolicy,
+    /// 后台刷盘与写入限速策略，None 表示只在显式请求时刷盘
+    pub flush_policy: Option<FlushPolicy>,
+    /// 后台整理策略，None 表示只在显式请求时整理
+    pub compaction: Option<CompactionPolicy>,
+    /// 段文件达到该大小后，下一次刷盘写入新的段文件
+    pub segment_max_bytes: u64,
+    /// 段文件开始写入超过该时间后，下一次刷盘写入新的段文件；重启后从打开时重新计时
+    pub segment_max_age: Option<Duration>,
+    /// 跳过段文件中间的损坏记录继续打开，而不是报错；被跳过记录中的修改会丢失
+    pub salvage: bool,
+    /// 打开前删除数据目录中已有的锁文件（lockfile::LOCK_FILE），用于原持有者异常后无法自动释放的情况
+    pub force_unlock: bool,
+    /// 过期、锁租约、回收站、版本时间戳和清理周期使用的时钟
+    pub clock: Arc<dyn Clock>,
+    /// 设置后 open 只读取清单就返回，由 start_loader 在后台加载数据；
+    /// 加载期间读请求按该值等待或失败，Put/Delete/Batch 暂存后在加载完成时按顺序应用
+    pub lazy_load: Option<LoadingReads>,
+    /// 超过该长度（字节）且可以压缩的值在内存中压缩保存，读取时透明解压；None 表示不压缩
+    /// 只影响内存占用，刷盘时写入未压缩的值
+    pub compress_threshold: Option<usize>,
+    /// 超过该长度（字节）的值写入数据目录下的 blob 文件，内存中只保存位置，读取时从文件读回；
+    /// None 表示只在 EvictionPolicy::Spill 下移出。纯内存存储中不起作用
+    pub spill_threshold: Option<usize>,
+    /// 设置后把每个提交的批次追加到 oplog，见 oplog 模块；None 表示不记录
+    pub oplog: Option<OplogConfig>,
+}
+
+impl Default for StorageOptions {
+    fn default() -> Self {
+        StorageOptions {
+            cf_options: HashMap::new(),
+            durability: Durability::default(),
+            fs: Arc::new(OsFileSystem),
+            checksums: false,
+            max_memory_bytes: None,
+            eviction: EvictionPolicy::default(),
+            flush_policy: None,
+            compaction: Some(CompactionPolicy::default()),
+            segment_max_bytes: DEFAULT_SEGMENT_MAX_BYTES,
+            segment_max_age: None,
+            salvage: false,
+            force_unlock: false,
+            clock: Arc::new(SystemClock),
+            lazy_load: None,
+            compress_threshold: None,
+            spill_threshold: None,
+            oplog: None,
+        }
+    }
+}
+
+const DEFAULT_SEGMENT_MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+/// 单个键的版本历史
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct KeyHistory {
+    /// 当前值（或当前墓碑）的版本号与写入时间
+    current_version: u64,
+    current_timestamp_ms: u64,
+    /// 旧版本，新版本在前
+    versions: VecDeque<protocol::Version>,
+}
+
+/// 随写入增量维护的总量，在写锁内更新，读取时不需要加锁
+#[derive(Debug, Default)]
+struct Totals {
+    keys: AtomicUsize,
+    // entries 的近似内存占用，见 entry_size；压缩的值按压缩后的大小计算
+    memory_bytes: AtomicUsize,
+    // 按未压缩的值计算的 memory_bytes
+    logical_bytes: AtomicUsize,
+    compressed_values: AtomicUsize,
+}
+
+impl Totals {
+    fn add(&self, physical: usize, logical: usize, compressed: usize) {
+        self.keys.fetch_add(1, Ordering::Relaxed);
+        self.memory_bytes.fetch_add(physical, Ordering::Relaxed);
+        self.logical_bytes.fetch_add(logical, Ordering::Relaxed);
+        self.compressed_values.fetch_add(compressed, Ordering::Relaxed);
+    }
+
+    fn sub(&self, physical: usize, logical: usize, compressed: usize) {
+        self.keys.fetch_sub(1, Ordering::Relaxed);
+        self.memory_bytes.fetch_sub(physical, Ordering::Relaxed);
+        self.logical_bytes.fetch_sub(logical, Ordering::Relaxed);
+        self.compressed_values.fetch_sub(compressed, Ordering::Relaxed);
+    }
+
+    fn copy_from(&self, other: &Totals) {
+        for (to, from) in [
+            (&self.keys, &other.keys),
+            (&self.memory_bytes, &other.memory_bytes),
+            (&self.logical_bytes, &other.logical_bytes),
+            (&self.compressed_values, &other.compressed_values),
+        ] {
+            to.store(from.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.memory_bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// 受读写锁保护的存储状态
+#[derive(Default)]
+struct StorageData {
+    entries: BTreeMap<Vec<u8>, StoredValue>,
+    history: BTreeMap<Vec<u8>, KeyHistory>,
+    cf_options: HashMap<String, CfOptions>,
+    // 带前缀的键 -> 值的 CRC32，未开启校验时为 None
+    checksums: Option<BTreeMap<Vec<u8>, u32>>,
+    // 与 StandaloneStorage 共享，Info 读取总量时不需要读锁
+    totals: Arc<Totals>,
+    // 超过该长度的值尝试压缩，None 表示不压缩
+    compress_threshold: Option<usize>,
+    // 值的磁盘层，只在持久化模式下开启 spill_threshold 或 EvictionPolicy::Spill 时存在
+    blobs: Option<Arc<BlobStore>>,
+    // 超过该长度的值写入磁盘层
+    spill_threshold: Option<usize>,
+    // 按列族统计的用量，没有键的列族不出现
+    cf_usage: HashMap<String, CfUsage>,
+    // 访问时间索引，只在 EvictionPolicy::Lru 和 Spill 下存在
+    lru: Option<LruIndex>,
+    evicted_keys: u64,
+    // 开启 index_values 的列族的倒排索引，加载时重建
+    value_index: HashMap<String, ValueIndex>,
+    // 上次刷盘以来修改过的带前缀的键，只在持久化模式下记录
+    dirty_keys: Option<BTreeSet<Vec<u8>>>,
+    // 列族选项或列族列表自上次刷盘以来有变化
+    cf_options_dirty: bool,
+    // 已知的列族 -> 创建时间（毫秒），旧数据中无法得知创建时间的列族为 0
+    cf_created: BTreeMap<String, u64>,
+    // 设置了过期时间的带前缀的键 -> 过期时间（毫秒）
+    expirations: BTreeMap<Vec<u8>, u64>,
+    // 按过期时间排序的 (过期时间, 带前缀的键)，清理时从头取出
+    expiry_index: BTreeSet<(u64, Vec<u8>)>,
+}
+
+/// 值 -> 具有该值的键（不带前缀）
+type ValueIndex = BTreeMap<Vec<u8>, BTreeSet<Vec<u8>>>;
+
+/// 键的值从 old 变为 new 时更新所在列族的倒排索引，列族没有索引时不做任何事
+fn update_value_index(
+    indexes: &mut HashMap<String, ValueIndex>,
+    prefixed_key: &[u8],
+    old: Option<&[u8]>,
+    new: Option<&[u8]>,
+) {
+    let Some((cf, key)) = EncodedKey::decode_bytes(prefixed_key) else {
+        return;
+    };
+    let Some(index) = indexes.get_mut(cf) else {
+        return;
+    };
+
+    if let Some(old) = old
+        && let Some(keys) = index.get_mut(old)
+    {
+        keys.remove(key);
+        if keys.is_empty() {
+            index.remove(old);
+        }
+    }
+    if let Some(new) = new {
+        index.entry(new.to_vec()).or_default().insert(key.to_vec());
+    }
+}
+
+// 旧值读取失败时无法按值定位，在列族的整个索引中去掉该键
+fn unindex_key(indexes: &mut HashMap<String, ValueIndex>, prefixed_key: &[u8]) {
+    let Some((cf, key)) = EncodedKey::decode_bytes(prefixed_key) else {
+        return;
+    };
+    if let Some(index) = indexes.get_mut(cf) {
+        index.retain(|_, keys| {
+            keys.remove(key);
+            !keys.is_empty()
+        });
+    }
+}
+
+// 每次淘汰时随机抽样的条目数，从中淘汰最久未访问的一个
+const EVICTION_SAMPLES: usize = 16;
+
+/// 近似 LRU：每个条目记录最后访问的逻辑时钟，淘汰时随机抽样比较，
+/// 不维护全局有序的链表。读路径只需要在读锁下做两次原子操作
+#[derive(Default)]
+struct LruIndex {
+    clock: AtomicU64,
+    // 键所在的槽位和最后访问时间；槽位用于 O(1) 随机抽样
+    entries: HashMap<Vec<u8>, (usize, AtomicU64)>,
+    slots: Vec<Vec<u8>>,
+    rng: u64,
+}
+
+impl LruIndex {
+    fn touch(&self, prefixed_key: &[u8]) {
+        if let Some((_, last_access)) = self.entries.get(prefixed_key) {
+            last_access.store(self.clock.fetch_add(1, Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    fn insert(&mut self, prefixed_key: &[u8]) {
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        match self.entries.get(prefixed_key) {
+            Some((_, last_access)) => last_access.store(now, Ordering::Relaxed),
+            None => {
+                self.entries.insert(prefixed_key.to_vec(), (self.slots.len(), AtomicU64::new(now)));
+                self.slots.push(prefixed_key.to_vec());
+            }
+        }
+    }
+
+    fn remove(&mut self, prefixed_key: &[u8]) {
+        let Some((slot, _)) = self.entries.remove(prefixed_key) else {
+            return;
+        };
+        self.slots.swap_remove(slot);
+        if let Some(moved) = self.slots.get(slot)
+            && let Some(entry) = self.entries.get_mut(moved)
+        {
+            entry.0 = slot;
+        }
+    }
+
+    /// 随机抽样若干条目，返回其中最久未访问的键
+    fn sample_oldest(&mut self) -> Option<Vec<u8>> {
+        let mut oldest: Option<(u64, usize)> = None;
+        for _ in 0..EVICTION_SAMPLES.min(self.slots.len()) {
+            let slot = (xorshift(&mut self.rng) % self.slots.len() as u64) as usize;
+            let last_access = self.entries[&self.slots[slot]].1.load(Ordering::Relaxed);
+            if oldest.is_none_or(|(t, _)| last_access < t) {
+                oldest = Some((last_access, slot));
+            }
+        }
+        oldest.map(|(_, slot)| self.slots[slot].clone())
+    }
+
+    fn rebuild<'a>(&mut self, keys: impl Iterator<Item = &'a Vec<u8>>) {
+        self.entries.clear();
+        self.slots.clear();
+        for key in keys {
+            self.insert(key);
+        }
+    }
+}
+
+// xorshift64，state 不能为 0
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+// 每个条目除键值内容外的固定开销（两个 Vec 头和 B 树节点中的份额）
+const ENTRY_OVERHEAD: usize = 64;
+
+fn entry_size(key: &[u8], value_len: usize) -> usize {
+    key.len() + value_len + ENTRY_OVERHEAD
+}
+
+/// 多余容量至少这么多、且容量达到长度的两倍时，整理会收缩缓冲区
+pub const SHRINK_MIN_SLACK: usize = 64;
+
+// 容量远大于长度时收缩到实际长度，返回释放的字节数
+fn shrink_oversized(buffer: &mut Vec<u8>) -> Option<u64> {
+    let slack = buffer.capacity() - buffer.len();
+    if slack < SHRINK_MIN_SLACK || buffer.capacity() < 2 * buffer.len() {
+        return None;
+    }
+    let before = buffer.capacity();
+    buffer.shrink_to_fit();
+    Some((before - buffer.capacity()) as u64)
+}
+
+// 以 deflate 格式压缩值
+fn deflate(value: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(value.len() / 2), Compression::default());
+    encoder.write_all(value).expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+// 解压 deflate 数据，解压后的长度必须为 len
+fn inflate(data: &[u8], len: usize) -> Result<Vec<u8>, String> {
+    let mut value = Vec::with_capacity(len);
+    DeflateDecoder::new(data)
+        .take(len as u64 + 1)
+        .read_to_end(&mut value)
+        .map_err(|e| format!("Corrupted compressed value: {}", e))?;
+    if value.len() != len {
+        return Err(format!("Corrupted compressed value: expected {} bytes, got {}", len, value.len()));
+    }
+    Ok(value)
+}
+
+/// 内存中的值；超过压缩阈值且压缩后更小的值以压缩形式保存，读取时解压；
+/// 移到磁盘层的值只保存位置，读取时从 blob 文件读回
+#[derive(Clone)]
+enum StoredValue {
+    Plain(Vec<u8>),
+    Compressed { data: Vec<u8>, len: usize },
+    OnDisk(Arc<BlobValue>),
+}
+
+impl StoredValue {
+    fn new(value: Vec<u8>, compress_threshold: Option<usize>) -> Self {
+        if compress_threshold.is_some_and(|threshold| value.len() > threshold) {
+            let data = deflate(&value);
+            if data.len() < value.len() {
+                return StoredValue::Compressed { data, len: value.len() };
+            }
+        }
+        StoredValue::Plain(value)
+    }
+
+    /// 内存中保存值的缓冲区，磁盘层的值没有
+    fn buffer_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            StoredValue::Plain(data) | StoredValue::Compressed { data, .. } => Some(data),
+            StoredValue::OnDisk(_) => None,
+        }
+    }
+
+    /// 读取值；磁盘层的值读取 blob 文件失败、压缩的值解压失败时返回错误
+    fn try_get(&self) -> Result<Cow<'_, [u8]>, String> {
+        match self {
+            StoredValue::Plain(value) => Ok(Cow::Borrowed(value)),
+            StoredValue::Compressed { data, len } => Ok(Cow::Owned(inflate(data, *len)?)),
+            StoredValue::OnDisk(blob) => Ok(Cow::Owned(blob.read()?)),
+        }
+    }
+
+    fn try_to_vec(&self) -> Result<Vec<u8>, String> {
+        self.try_get().map(Cow::into_owned)
+    }
+
+    /// 未压缩的长度
+    fn len(&self) -> usize {
+        match self {
+            StoredValue::Plain(value) => value.len(),
+            StoredValue::Compressed { len, .. } => *len,
+            StoredValue::OnDisk(blob) => blob.at().len as usize,
+        }
+    }
+
+    /// 在内存中占用的长度，磁盘层的值为 0
+    fn physical_len(&self) -> usize {
+        match self {
+            StoredValue::Plain(value) => value.len(),
+            StoredValue::Compressed { data, .. } => data.len(),
+            StoredValue::OnDisk(_) => 0,
+        }
+    }
+}
+
+impl StorageData {
+    /// 从各映射自身的迭代器重建，批量构建的 B 树节点是满的；顺带把容量远大于长度的键和值
+    /// 收缩到实际长度。返回 (释放的容量字节数, 收缩的缓冲区数)
+    fn rebuild(&mut self) -> (u64, u64) {
+        let (mut reclaimed, mut shrunk) = (0, 0);
+        let mut shrink = |buffer: &mut Vec<u8>| {
+            if let Some(freed) = shrink_oversized(buffer) {
+                reclaimed += freed;
+                shrunk += 1;
+            }
+        };
+        self.entries = std::mem::take(&mut self.entries)
+            .into_iter()
+            .map(|(mut key, mut value)| {
+                shrink(&mut key);
+                if let Some(buffer) = value.buffer_mut() {
+                    shrink(buffer);
+                }
+                (key, value)
+            })
+            .collect();
+        self.history = std::mem::take(&mut self.history).into_iter().collect();
+        self.expirations = std::mem::take(&mut self.expirations).into_iter().collect();
+        self.expiry_index = std::mem::take(&mut self.expiry_index).into_iter().collect();
+        if let Some(checksums) = &mut self.checksums {
+            *checksums = std::mem::take(checksums).into_iter().collect();
+        }
+        (reclaimed, shrunk)
+    }
+
+    /// 所有键和内存中的值的多余容量（capacity - len）之和
+    fn capacity_slack(&self) -> u64 {
+        let slack = |buffer: &Vec<u8>| (buffer.capacity() - buffer.len()) as u64;
+        self.entries
+            .iter()
+            .map(|(key, value)| {
+                slack(key)
+                    + match value {
+                        StoredValue::Plain(data) | StoredValue::Compressed { data, .. } => slack(data),
+                        StoredValue::OnDisk(_) => 0,
+                    }
+            })
+            .sum()
+    }
+
+    fn keep_versions(&self, cf: &str) -> usize {
+        self.cf_options.get(cf).map_or(0, |o| o.keep_versions)
+    }
+
+    /// 在键被覆盖或删除前把旧值推入历史，并推进版本号
+    /// 开启版本记录之前写入的值作为 0 号版本保留；旧值读取失败时不做修改
+    fn record_version(&mut self, prefixed_key: &[u8], keep: usize, now: u64) -> Result<(), String> {
+        let old_value = self.entries.get(prefixed_key).map(StoredValue::try_to_vec).transpose()?;
+        let history = self.history.entry(prefixed_key.to_vec()).or_default();
+
+        if history.current_version > 0 || old_value.is_some() {
+            history.versions.push_front(protocol::Version {
+                version: history.current_version,
+                timestamp_ms: history.current_timestamp_ms,
+                value: old_value.map(protocol::Bytes),
+            });
+            history.versions.truncate(keep);
+        }
+
+        history.current_version += 1;
+        history.current_timestamp_ms = now;
+        Ok(())
+    }
+
+    /// 超过 spill_threshold 的值写入磁盘层，写入失败时保留在内存中
+    fn store_value(&self, value: Vec<u8>) -> StoredValue {
+        if let Some(blobs) = &self.blobs
+            && self.spill_threshold.is_some_and(|threshold| value.len() > threshold)
+            && let Ok(blob) = blobs.write(&value)
+        {
+            return StoredValue::OnDisk(Arc::new(blob));
+        }
+        StoredValue::new(value, self.compress_threshold)
+    }
+
+    // 新值是否会直接写入磁盘层
+    fn spills(&self, len: usize) -> bool {
+        self.blobs.is_some() && self.spill_threshold.is_some_and(|threshold| len > threshold)
+    }
+
+    /// 写入新值，同时清除键的过期时间
+    fn insert(&mut self, prefixed_key: Vec<u8>, value: Vec<u8>) {
+        self.mark_dirty(&prefixed_key);
+        self.set_expiry(&prefixed_key, None);
+        if let Some(checksums) = &mut self.checksums {
+            checksums.insert(prefixed_key.clone(), crc32(&value));
+        }
+        if let Some(lru) = &mut self.lru {
+            lru.insert(&prefixed_key);
+        }
+        if !self.value_index.is_empty() {
+            match self.entries.get(&prefixed_key).map(StoredValue::try_get).transpose() {
+                Ok(old) => update_value_index(&mut self.value_index, &prefixed_key, old.as_deref(), Some(&value)),
+                Err(_) => {
+                    unindex_key(&mut self.value_index, &prefixed_key);
+                    update_value_index(&mut self.value_index, &prefixed_key, None, Some(&value));
+                }
+            }
+        }
+        let stored = self.store_value(value);
+        if let Some(old) = self.entries.remove(&prefixed_key) {
+            self.account(&prefixed_key, &old, false);
+        }
+        self.account(&prefixed_key, &stored, true);
+        self.entries.insert(prefixed_key, stored);
+    }
+
+    /// 把键的值计入（added 为 true）或移出内存占用和列族用量
+    fn account(&mut self, prefixed_key: &[u8], value: &StoredValue, added: bool) {
+        let physical = entry_size(prefixed_key, value.physical_len());
+        let logical = entry_size(prefixed_key, value.len());
+        let compressed = matches!(value, StoredValue::Compressed { .. }) as usize;
+        let usage = cf_of(prefixed_key).map(|cf| (cf.to_string(), self.cf_usage.entry(cf.to_string()).or_default()));
+        if added {
+            self.totals.add(physical, logical, compressed);
+            if let Some((_, usage)) = usage {
+                usage.keys += 1;
+                usage.bytes += physical;
+                usage.logical_bytes += logical;
+            }
+        } else {
+            self.totals.sub(physical, logical, compressed);
+            if let Some((cf, usage)) = usage {
+                usage.keys -= 1;
+                usage.bytes -= physical;
+                usage.logical_bytes -= logical;
+                if usage.keys == 0 {
+                    self.cf_usage.remove(&cf);
+                }
+            }
+        }
+    }
+
+    /// 删除键，返回原来保存的值；只有列族建了值索引时才读出旧值。调用方不需要旧值时直接丢弃，
+    /// 磁盘层的值随之记为可回收
+    fn remove(&mut self, prefixed_key: &[u8]) -> Option<StoredValue> {
+        self.mark_dirty(prefixed_key);
+        self.set_expiry(prefixed_key, None);
+        if let Some(checksums) = &mut self.checksums {
+            checksums.remove(prefixed_key);
+        }
+        if let Some(lru) = &mut self.lru {
+            lru.remove(prefixed_key);
+        }
+        let old = self.entries.remove(prefixed_key)?;
+        self.account(prefixed_key, &old, false);
+        if cf_of(prefixed_key).is_some_and(|cf| self.value_index.contains_key(cf)) {
+            match old.try_get() {
+                Ok(value) => update_value_index(&mut self.value_index, prefixed_key, Some(&value), None),
+                Err(_) => unindex_key(&mut self.value_index, prefixed_key),
+            }
+        }
+        Some(old)
+    }
+
+    /// 设置或清除键的过期时间（毫秒）
+    fn set_expiry(&mut self, prefixed_key: &[u8], expires_at_ms: Option<u64>) {
+        if let Some(old) = self.expirations.remove(prefixed_key) {
+            self.expiry_index.remove(&(old, prefixed_key.to_vec()));
+        }
+        if let Some(at) = expires_at_ms {
+            self.expirations.insert(prefixed_key.to_vec(), at);
+            self.expiry_index.insert((at, prefixed_key.to_vec()));
+        }
+    }
+
+    /// 源键的过期时间，表示为目标键要设置的 (带列族前缀的键, 过期时间)；源键没有过期时间时为空
+    fn carried_expiry(&self, cf: &str, src_key: &[u8], dst_key: &[u8]) -> Vec<(Vec<u8>, u64)> {
+        let src = EncodedKey::encode(cf, src_key).into_bytes();
+        self.expirations.get(&src).map(|&at| (EncodedKey::encode(cf, dst_key).into_bytes(), at)).into_iter().collect()
+    }
+
+    fn is_expired(&self, prefixed_key: &[u8], now: u64) -> bool {
+        self.expirations.get(prefixed_key).is_some_and(|&at| at <= now)
+    }
+
+    /// 删除在 now 时已过期的键，返回删除的键数
+    fn remove_expired(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while let Some((at, prefixed_key)) = self.expiry_index.first().cloned()
+            && at <= now
+        {
+            self.remove(&prefixed_key);
+            removed += 1;
+        }
+        removed
+    }
+
+    fn mark_dirty(&mut self, prefixed_key: &[u8]) {
+        if let Some(keys) = &mut self.dirty_keys {
+            keys.insert(prefixed_key.to_vec());
+        }
+    }
+
+    /// 取出待刷盘的键和列族选项是否有变化
+    fn take_dirty(&mut self) -> (BTreeSet<Vec<u8>>, bool) {
+        let keys = self.dirty_keys.as_mut().map(std::mem::take).unwrap_or_default();
+        (keys, std::mem::replace(&mut self.cf_options_dirty, false))
+    }
+
+    /// 刷盘失败后放回取出的修改，留到下次刷盘
+    fn restore_dirty(&mut self, keys: BTreeSet<Vec<u8>>, cf_options_dirty: bool) {
+        if let Some(dirty) = &mut self.dirty_keys {
+            dirty.extend(keys);
+        }
+        self.cf_options_dirty |= cf_options_dirty;
+    }
+
+    /// 键当前的完整状态
+    fn key_record(&self, prefixed_key: &[u8]) -> Result<KeyRecord, String> {
+        Ok(KeyRecord {
+            key: protocol::Bytes(prefixed_key.to_vec()),
+            value: self.entries.get(prefixed_key).map(StoredValue::try_to_vec).transpose()?.map(protocol::Bytes),
+            history: self.history.get(prefixed_key).cloned(),
+            checksum: self.checksums.as_ref().and_then(|c| c.get(prefixed_key).copied()),
+            expires_at_ms: self.expirations.get(prefixed_key).copied(),
+        })
+    }
+
+    /// 完整快照，last_sequence 为快照包含的最后一条段文件记录；每收集一个条目或历史推进一次 progress
+    /// 有值读取失败时返回错误
+    fn snapshot(&self, last_sequence: u64, progress: &MaintenanceTracker) -> Result<Snapshot, String> {
+        Ok(Snapshot {
+            entries: self
+                .entries
+                .iter()
+                .map(|(k, v)| {
+                    progress.advance();
+                    Ok((protocol::Bytes(k.clone()), protocol::Bytes(v.try_to_vec()?)))
+                })
+                .collect::<Result<_, String>>()?,
+            history: self
+                .history
+                .iter()
+                .map(|(k, h)| {
+                    progress.advance();
+                    (protocol::Bytes(k.clone()), h.clone())
+                })
+                .collect(),
+            cf_options: self.cf_options.clone(),
+            cf_created: self.cf_created.clone(),
+            checksums: self
+                .checksums
+                .iter()
+                .flatten()
+                .map(|(k, sum)| (protocol::Bytes(k.clone()), *sum))
+                .collect(),
+            expirations: self.expirations.iter().map(|(k, at)| (protocol::Bytes(k.clone()), *at)).collect(),
+            last_sequence,
+            key_format: KEY_FORMAT,
+        })
+    }
+
+    /// 把最近最少访问的值移到磁盘层，直到内存占用不超过 max；键、历史和校验和不变
+    fn spill_until(&mut self, max: usize) {
+        let Some(blobs) = self.blobs.clone() else {
+            return;
+        };
+        while self.totals.memory_bytes() > max {
+            let Some(lru) = self.lru.as_mut() else {
+                break;
+            };
+            // 移到磁盘的键离开访问索引，之后不再被抽中；重新写入时回到索引
+            let Some(victim) = lru.sample_oldest() else {
+                break;
+            };
+            lru.remove(&victim);
+            let Some(value) = self.entries.get(&victim) else {
+                continue;
+            };
+            if matches!(value, StoredValue::OnDisk(_)) {
+                continue;
+            }
+            let Ok(blob) = value.try_get().and_then(|value| blobs.write(&value)) else {
+                break;
+            };
+            let spilled = StoredValue::OnDisk(Arc::new(blob));
+            let old = self.entries.insert(victim.clone(), spilled.clone()).expect("victim was just read");
+            self.account(&victim, &old, false);
+            self.account(&victim, &spilled, true);
+        }
+    }
+
+    /// 整理时把 sparse 中的 blob 文件里的值重写到当前文件，旧文件在最后一个值释放时删除
+    fn rewrite_blobs(&mut self, sparse: &[u32]) -> Result<(), String> {
+        let Some(blobs) = self.blobs.clone() else {
+            return Ok(());
+        };
+        for value in self.entries.values_mut() {
+            if let StoredValue::OnDisk(blob) = value
+                && sparse.contains(&blob.at().file)
+            {
+                let moved = blobs.write(&blob.read()?)?;
+                *value = StoredValue::OnDisk(Arc::new(moved));
+            }
+        }
+        Ok(())
+    }
+
+    /// 淘汰最近最少访问的条目（连同其历史），直到内存占用不超过 max
+    fn evict_until(&mut self, max: usize) {
+        while self.totals.memory_bytes() > max {
+            let Some(victim) = self.lru.as_mut().and_then(LruIndex::sample_oldest) else {
+                break;
+            };
+            self.remove(&victim);
+            self.history.remove(&victim);
+            self.evicted_keys += 1;
+        }
+    }
+
+    /// 应用一批修改后内存占用的变化量（近似：不考虑批次内对同一个键的重复修改，新值按未压缩的大小计算）
+    fn memory_delta(&self, batch: &[protocol::Modify]) -> isize {
+        batch
+            .iter()
+            .map(|modify| {
+                let prefixed_key = EncodedKey::encode(&modify.cf, &modify.key).into_bytes();
+                let old = self.entries.get(&prefixed_key).map_or(0, |v| entry_size(&prefixed_key, v.physical_len()));
+                let new = match modify.op {
+                    protocol::ModifyOp::Put if self.spills(modify.value.len()) => entry_size(&prefixed_key, 0),
+                    protocol::ModifyOp::Put => entry_size(&prefixed_key, modify.value.len()),
+                    protocol::ModifyOp::Delete => 0,
+                };
+                new as isize - old as isize
+            })
+            .sum()
+    }
+
+    /// 检查批次应用后每个受影响列族的配额；只拒绝让用量增长且超出配额的列族，删除总是允许
+    fn check_quotas(&self, batch: &[protocol::Modify]) -> Result<(), String> {
+        // 按键合并批次内的修改，得到每个键最终的占用（None 表示删除）
+        let mut finals: BTreeMap<Vec<u8>, (&str, Option<usize>)> = BTreeMap::new();
+        for modify in batch {
+            let options = self.cf_options.get(&modify.cf);
+            if options.is_none_or(|o| o.max_keys.is_none() && o.max_bytes.is_none()) {
+                continue;
+            }
+            let prefixed_key = EncodedKey::encode(&modify.cf, &modify.key).into_bytes();
+            let size = match modify.op {
+                protocol::ModifyOp::Put => Some(entry_size(&prefixed_key, modify.value.len())),
+                protocol::ModifyOp::Delete => None,
+            };
+            finals.insert(prefixed_key, (modify.cf.as_str(), size));
+        }
+
+        let mut deltas: BTreeMap<&str, (isize, isize)> = BTreeMap::new();
+        for (prefixed_key, (cf, size)) in &finals {
+            let old = self.entries.get(prefixed_key).map(|v| entry_size(prefixed_key, v.physical_len()));
+            let delta = deltas.entry(cf).or_default();
+            delta.0 += size.is_some() as isize - old.is_some() as isize;
+            delta.1 += size.unwrap_or(0) as isize - old.unwrap_or(0) as isize;
+        }
+
+        for (cf, (keys_delta, bytes_delta)) in deltas {
+            let options = &self.cf_options[cf];
+            let usage = self.cf_usage.get(cf).copied().unwrap_or_default();
+            if let Some(max) = options.max_keys
+                && keys_delta > 0
+                && usage.keys.saturating_add_signed(keys_delta) > max
+            {
+                return Err(format!(
+                    "QuotaExceeded: column family {} max_keys {}, write needs {} more key(s), {} in use",
+                    cf, max, keys_delta, usage.keys
+                ));
+            }
+            if let Some(max) = options.max_bytes
+                && bytes_delta > 0
+                && usage.bytes.saturating_add_signed(bytes_delta) > max
+            {
+                return Err(format!(
+                    "QuotaExceeded: column family {} max_bytes {}, write needs {} more byte(s), {} in use",
+                    cf, max, bytes_delta, usage.bytes
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// entries 被整体替换或批量删除后重建内存统计和访问索引
+    /// 先统计到新的 Totals 再一次性写回共享的那个，不加锁的读取不会看到清零的中间状态
+    fn rebuild_accounting(&mut self) -> Result<(), String> {
+        let shared = std::mem::take(&mut self.totals);
+        self.cf_usage.clear();
+        let entries = std::mem::take(&mut self.entries);
+        for (k, v) in &entries {
+            self.account(k, v, true);
+        }
+        self.entries = entries;
+        shared.copy_from(&self.totals);
+        self.totals = shared;
+        if let Some(lru) = &mut self.lru {
+            lru.rebuild(self.entries.keys());
+        }
+        self.rebuild_value_index()
+    }
+
+    /// 第一次写入列族时记录它的创建时间，内部列族不记录
+    fn register_cf(&mut self, cf: &str, created_at_ms: u64) {
+        if !self.cf_created.contains_key(cf) && !is_internal_cf(cf) {
+            self.cf_created.insert(cf.to_string(), created_at_ms);
+            self.cf_options_dirty = true;
+        }
+    }
+
+    /// 按当前列族选项重建倒排索引，关闭 index_values 的列族丢弃索引；有值读取失败时保留原来的索引
+    fn rebuild_value_index(&mut self) -> Result<(), String> {
+        let mut value_index: HashMap<String, ValueIndex> = self
+            .cf_options
+            .iter()
+            .filter(|(_, options)| options.index_values)
+            .map(|(cf, _)| (cf.clone(), ValueIndex::new()))
+            .collect();
+        if !value_index.is_empty() {
+            for (prefixed_key, value) in &self.entries {
+                update_value_index(&mut value_index, prefixed_key, None, Some(&value.try_get()?));
+            }
+        }
+        self.value_index = value_index;
+        Ok(())
+    }
+
+    /// 读取键的值，值与校验和不一致时返回 Corrupt 错误
+    fn get_checked(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let prefixed_key = EncodedKey::encode(cf, key).into_bytes();
+        let Some(value) = self.entries.get(&prefixed_key) else {
+            return Ok(None);
+        };
+        let value = value.try_get()?;
+        if !self.is_intact(&prefixed_key, &value) {
+            return Err(format!("Corrupt value for key {} in column family {}", protocol::display_bytes(key), cf));
+        }
+        Ok(Some(value.into_owned()))
+    }
+
+    /// 读取必须存在的键，不存在时返回 KeyNotFound 错误
+    fn get_existing(&self, cf: &str, key: &[u8]) -> Result<Vec<u8>, String> {
+        self.get_checked(cf, key)?
+            .ok_or_else(|| format!("KeyNotFound: {} in column family {}", protocol::display_bytes(key), cf))
+    }
+
+    /// 目标键已存在且不允许覆盖时返回 KeyExists 错误
+    fn check_destination(&self, cf: &str, key: &[u8], overwrite: bool) -> Result<(), String> {
+        if !overwrite && self.entries.contains_key(&EncodedKey::encode(cf, key).into_bytes()) {
+            return Err(format!("KeyExists: {} in column family {}", protocol::display_bytes(key), cf));
+        }
+        Ok(())
+    }
+
+    /// 检查 token 是否为锁的当前持有者；给出 now 时已过期的租约也视为未持有
+    fn check_lock_holder(&self, name: &str, token: u64, now: Option<u64>) -> Result<(), String> {
+        let held = match self.get_checked(LOCKS_CF, name.as_bytes())? {
+            Some(value) => {
+                let (holder, expires) = decode_lock(&value)?;
+                holder == token && now.is_none_or(|now| expires > now)
+            }
+            None => false,
+        };
+        if !held {
+            return Err(format!("LockNotHeld: {} with token {}", name, token));
+        }
+        Ok(())
+    }
+
+    /// 值与保存的校验和不一致时返回 false，未开启校验时总是 true
+    fn is_intact(&self, prefixed_key: &[u8], value: &[u8]) -> bool {
+        match &self.checksums {
+            Some(checksums) => checksums.get(prefixed_key) == Some(&crc32(value)),
+            None => true,
+        }
+    }
+}
+
+// CRC32 (IEEE) 查找表，编译期生成
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_extend(0, data)
+}
+
+/// 接着已有的校验和继续计算，crc32_extend(crc32(a), b) == crc32(a + b)
+fn crc32_extend(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+/// 隔离损坏条目的列族，键为原条目带前缀的键
+pub const QUARANTINE_CF: &str = "__corrupt";
+
+/// 分布式锁所在的内部列族，键为锁名，值为持有者的令牌和租约到期时间
+pub const LOCKS_CF: &str = "__locks";
+
+/// 软删除的条目保存在这个内部列族中，键的编码见 trash_key
+pub const TRASH_CF: &str = "__trash";
+
+/// 回收站中的一个条目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub cf: String,
+    pub key: protocol::Bytes,
+    pub deleted_at_ms: u64,
+    pub value: protocol::Bytes,
+}
+
+/// Sample 抽到的一个键
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySample {
+    pub cf: String,
+    pub key: protocol::Bytes,
+    pub value_size: usize,
+    /// 剩余生存时间（毫秒），没有设置过期时间时为 None
+    pub ttl_ms: Option<u64>,
+}
+
+/// 删除类操作试运行的结果：会被删除的键数和字节数，以及按存储顺序的第一个和最后一个键
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletionReport {
+    pub keys: usize,
+    /// 与 CfUsage::logical_bytes 口径相同
+    pub bytes: usize,
+    pub first: Option<(String, protocol::Bytes)>,
+    pub last: Option<(String, protocol::Bytes)>,
+}
+
+impl DeletionReport {
+    fn add(&mut self, cf: String, key: Vec<u8>, bytes: usize) {
+        self.keys += 1;
+        self.bytes += bytes;
+        let entry = (cf, protocol::Bytes(key));
+        if self.first.is_none() {
+            self.first = Some(entry);
+        } else {
+            self.last = Some(entry);
+        }
+    }
+}
+
+// 带前缀的键以 prefix 开头的条目
+fn entries_with_prefix<'a, 'p>(
+    entries: &'a BTreeMap<Vec<u8>, StoredValue>,
+    prefix: &'p [u8],
+) -> impl Iterator<Item = (&'a Vec<u8>, &'a StoredValue)> + use<'a, 'p> {
+    entries.range(prefix.to_vec()..).take_while(move |(k, _)| k.starts_with(prefix))
+}
+
+// 回收站键：cf 长度（u16）+ cf + key 长度（u32）+ key + 删除时间（u64），均为大端
+// 同一个键的多次删除相邻并按删除时间排序
+fn trash_key(cf: &str, key: &[u8], deleted_at_ms: u64) -> Vec<u8> {
+    let mut encoded = trash_prefix(cf, Some(key));
+    encoded.extend_from_slice(&deleted_at_ms.to_be_bytes());
+    encoded
+}
+
+fn trash_prefix(cf: &str, key: Option<&[u8]>) -> Vec<u8> {
+    let mut prefix = (cf.len() as u16).to_be_bytes().to_vec();
+    prefix.extend_from_slice(cf.as_bytes());
+    if let Some(key) = key {
+        prefix.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        prefix.extend_from_slice(key);
+    }
+    prefix
+}
+
+fn decode_trash_key(encoded: &[u8]) -> Result<(String, Vec<u8>, u64), String> {
+    let invalid = || "Invalid trash key".to_string();
+    let (cf_len, rest) = encoded.split_first_chunk::<2>().ok_or_else(invalid)?;
+    let (cf, rest) = rest.split_at_checked(u16::from_be_bytes(*cf_len) as usize).ok_or_else(invalid)?;
+    let (key_len, rest) = rest.split_first_chunk::<4>().ok_or_else(invalid)?;
+    let (key, rest) = rest.split_at_checked(u32::from_be_bytes(*key_len) as usize).ok_or_else(invalid)?;
+    let deleted_at: [u8; 8] = rest.try_into().map_err(|_| invalid())?;
+    let cf = String::from_utf8(cf.to_vec()).map_err(|_| invalid())?;
+    Ok((cf, key.to_vec(), u64::from_be_bytes(deleted_at)))
+}
+
+/// 以 "__" 开头的列族由存储内部使用，不出现在列族列表中
+fn is_internal_cf(cf: &str) -> bool {
+    cf.starts_with("__")
+}
+
+fn encode_lock(token: u64, expires_at_ms: u64) -> Vec<u8> {
+    [token.to_be_bytes(), expires_at_ms.to_be_bytes()].concat()
+}
+
+/// 解析锁的值，返回 (令牌, 到期时间毫秒)
+fn decode_lock(value: &[u8]) -> Result<(u64, u64), String> {
+    if value.len() != 16 {
+        return Err(format!("Malformed lock entry of {} bytes", value.len()));
+    }
+    let (token, expires) = value.split_at(8);
+    Ok((
+        u64::from_be_bytes(token.try_into().unwrap()),
+        u64::from_be_bytes(expires.try_into().unwrap()),
+    ))
+}
+
+/// 磁盘快照格式
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    #[serde(default)]
+    entries: Vec<(protocol::Bytes, protocol::Bytes)>,
+    #[serde(default)]
+    history: Vec<(protocol::Bytes, KeyHistory)>,
+    #[serde(default)]
+    cf_options: HashMap<String, CfOptions>,
+    #[serde(default)]
+    cf_created: BTreeMap<String, u64>,
+    #[serde(default)]
+    checksums: Vec<(protocol::Bytes, u32)>,
+    // 设置了过期时间的键 -> 过期时间（毫秒）
+    #[serde(default)]
+    expirations: Vec<(protocol::Bytes, u64)>,
+    // 快照包含的最后一条段文件记录的序号
+    #[serde(default)]
+    last_sequence: u64,
+    // 键的编码格式，见 KEY_FORMAT
+    #[serde(default)]
+    key_format: u32,
+}
+
+/// 磁盘上编码键的格式版本：0 为旧的 `cf_key` 格式，1 为 EncodedKey；
+/// 旧格式的文件在打开时由 migrate_v1_to_v2 转换
+const KEY_FORMAT: u32 = 1;
+
+/// 把旧格式的编码键转换为 EncodedKey；内部列族名本身以 '_' 开头，先按已知的内部列族拆分，
+/// 隔离列族中保存的是完整的旧编码键，一并转换。列族为空的旧键（`_key`）归入 DEFAULT_CF
+fn upgrade_legacy_key(legacy: &[u8]) -> Vec<u8> {
+    let internal = [QUARANTINE_CF, LOCKS_CF, TRASH_CF]
+        .into_iter()
+        .find(|cf| legacy.strip_prefix(cf.as_bytes()).is_some_and(|rest| rest.first() == Some(&b'_')));
+    let cf = match internal {
+        Some(cf) => cf,
+        None => match legacy.iter().position(|&b| b == b'_').map(|pos| std::str::from_utf8(&legacy[..pos])) {
+            Some(Ok(cf)) => cf,
+            _ => return legacy.to_vec(),
+        },
+    };
+    let key = &legacy[cf.len() + 1..];
+    let cf = if cf.is_empty() { protocol::DEFAULT_CF } else { cf };
+    if cf == QUARANTINE_CF {
+        EncodedKey::encode(cf, &upgrade_legacy_key(key)).into_bytes()
+    } else {
+        EncodedKey::encode(cf, key).into_bytes()
+    }
+}
+
+// 写出备份文件的一行，并把它计入校验和
+fn write_archive_line(writer: &mut impl Write, crc: &mut u32, record: &ArchiveRecord) -> Result<(), String> {
+    let mut line = serde_json::to_vec(record).map_err(|e| format!("Failed to serialize: {}", e))?;
+    line.push(b'\n');
+    *crc = crc32_extend(*crc, &line);
+    writer.write_all(&line).map_err(|e| format!("Failed to write archive: {}", e))
+}
+
+/// 解析并校验备份文件，返回其中的完整状态；格式不对、被截断或校验和不符时返回 InvalidArchive 错误
+fn read_archive(reader: impl Read) -> Result<ReplayState, String> {
+    let invalid = |message: String| format!("InvalidArchive: {}", message);
+    let mut reader = io::BufReader::new(reader);
+    let mut state = ReplayState::default();
+    let (mut crc, mut records, mut line_no) = (0, 0, 0);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        reader.read_until(b'\n', &mut line).map_err(|e| format!("Failed to read archive: {}", e))?;
+        line_no += 1;
+        let Some(json) = line.strip_suffix(b"\n") else {
+            return Err(invalid("the archive is truncated".to_string()));
+        };
+        let record: ArchiveRecord = serde_json::from_slice(json).map_err(|e| invalid(format!("line {}: {}", line_no, e)))?;
+        match record {
+            ArchiveRecord::Header(header) if line_no == 1 => {
+                if header.format != ARCHIVE_FORMAT || header.version != ARCHIVE_VERSION || header.key_format != KEY_FORMAT {
+                    return Err(invalid(format!(
+                        "unsupported format {} version {} key format {}",
+                        header.format, header.version, header.key_format
+                    )));
+                }
+                state.cf_options = header.cf_options;
+                state.cf_created = header.cf_created;
+            }
+            _ if line_no == 1 => return Err(invalid("missing header".to_string())),
+            ArchiveRecord::Key(key) => {
+                records += 1;
+                state.apply(SegmentRecord { keys: vec![key], ..SegmentRecord::default() });
+            }
+            ArchiveRecord::End { records: expected, crc32 } => {
+                if expected != records || crc32 != crc {
+                    return Err(invalid(format!(
+                        "checksum mismatch: expected {} records with crc32 {:08x}, read {} with {:08x}",
+                        expected, crc32, records, crc
+                    )));
+                }
+                let trailing = reader.fill_buf().map_err(|e| format!("Failed to read archive: {}", e))?;
+                if !trailing.is_empty() {
+                    return Err(invalid("unexpected data after the end record".to_string()));
+                }
+                return Ok(state);
+            }
+            ArchiveRecord::Header(_) => return Err(invalid(format!("line {}: duplicate header", line_no))),
+        }
+        crc = crc32_extend(crc, &line);
+    }
+}
+
+/// 清单文件名，清单列出当前的基础快照和需要按顺序重放的段文件
+const MANIFEST_FILE: &str = "MANIFEST";
+
+/// 旧版本的完整快照文件，没有清单时作为基础快照，由 migrate_v1_to_v2 转换
+const LEGACY_SNAPSHOT_FILE: &str = "data.json";
+
+/// 格式版本 1 → 2：data.json 转为清单引用的基础快照，快照和段文件中旧格式的编码键转换为 EncodedKey。
+/// 解析失败的段文件记录原样保留，由加载时的损坏记录处理决定是否跳过
+pub(crate) fn migrate_v1_to_v2(dir: &Path) -> Result<(), String> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let mut manifest: Manifest = if manifest_path.exists() {
+        let json = fs::read(&manifest_path).map_err(|e| format!("Failed to read manifest: {}", e))?;
+        serde_json::from_slice(&json).map_err(|e| format!("Failed to parse manifest: {}", e))?
+    } else if dir.join(LEGACY_SNAPSHOT_FILE).exists() {
+        Manifest { base: Some(LEGACY_SNAPSHOT_FILE.to_string()), ..Manifest::default() }
+    } else {
+        return Ok(());
+    };
+    let upgrade = |key: &mut protocol::Bytes| key.0 = upgrade_legacy_key(&key.0);
+
+    if let Some(base) = manifest.base.clone() {
+        let file = fs::File::open(dir.join(&base)).map_err(|e| format!("Failed to read {}: {}", base, e))?;
+        let mut snapshot: Snapshot = serde_json::from_reader(io::BufReader::new(file))
+            .map_err(|e| format!("Failed to parse {}: {}", base, e))?;
+        if snapshot.key_format != KEY_FORMAT {
+            snapshot.entries.iter_mut().for_each(|(k, _)| upgrade(k));
+            snapshot.history.iter_mut().for_each(|(k, _)| upgrade(k));
+            snapshot.checksums.iter_mut().for_each(|(k, _)| upgrade(k));
+            snapshot.expirations.iter_mut().for_each(|(k, _)| upgrade(k));
+            snapshot.key_format = KEY_FORMAT;
+        }
+        let name = if base == LEGACY_SNAPSHOT_FILE { manifest.next_name("base", "json") } else { base.clone() };
+        let file = fs::File::create(dir.join(&name)).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        let mut writer = io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &snapshot)
+            .map_err(|e| e.to_string())
+            .and_then(|_| writer.flush().map_err(|e| e.to_string()))
+            .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        if name != base {
+            fs::remove_file(dir.join(&base)).map_err(|e| format!("Failed to remove {}: {}", base, e))?;
+            manifest.base = Some(name);
+        }
+    }
+
+    for segment in &manifest.segments {
+        let bytes = fs::read(dir.join(segment)).map_err(|e| format!("Failed to read {}: {}", segment, e))?;
+        let mut rewritten = Vec::with_capacity(bytes.len());
+        for line in bytes.split_inclusive(|b| *b == b'\n') {
+            let parsed = line.strip_suffix(b"\n").and_then(|l| serde_json::from_slice::<SegmentRecord>(l).ok());
+            match parsed {
+                Some(mut record) if record.key_format != KEY_FORMAT => {
+                    record.keys.iter_mut().for_each(|r| upgrade(&mut r.key));
+                    record.key_format = KEY_FORMAT;
+                    rewritten.extend(serde_json::to_vec(&record).map_err(|e| format!("Failed to serialize: {}", e))?);
+                    rewritten.push(b'\n');
+                }
+                _ => rewritten.extend_from_slice(line),
+            }
+        }
+        fs::write(dir.join(segment), rewritten).map_err(|e| format!("Failed to write {}: {}", segment, e))?;
+    }
+
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("Failed to serialize: {}", e))?;
+    fs::write(&manifest_path, json).map_err(|e| format!("Failed to write manifest: {}", e))
+}
+
+/// inspect_data_files 中一个数据文件的检查结果
+#[derive(Debug, Clone, Default)]
+pub struct DataFileCheck {
+    pub name: String,
+    pub bytes: u64,
+    /// 快照中的键数或段文件中能解析的记录数
+    pub entries: usize,
+    /// 段文件的总行数
+    pub records: usize,
+    /// 无法解析的段文件记录的行号（从 1 开始）
+    pub corrupt_records: Vec<usize>,
+    /// 文件无法读取或快照无法解析时的错误
+    pub error: Option<String>,
+}
+
+/// 只读地解析 dir 中清单引用的快照和段文件，不加锁也不修改任何文件。
+/// 没有清单时检查旧版本的 data.json；两者都没有时返回空列表，清单本身无法解析时返回错误
+pub fn inspect_data_files(dir: &Path) -> Result<Vec<DataFileCheck>, String> {
+    let manifest = match StandaloneStorage::read_manifest(dir)? {
+        Some(manifest) => manifest,
+        None if dir.join(LEGACY_SNAPSHOT_FILE).exists() => {
+            Manifest { base: Some(LEGACY_SNAPSHOT_FILE.to_string()), ..Manifest::default() }
+        }
+        None => return Ok(Vec::new()),
+    };
+    let mut checks = Vec::new();
+    if let Some(base) = &manifest.base {
+        let mut check = DataFileCheck { name: base.clone(), ..DataFileCheck::default() };
+        match fs::File::open(dir.join(base)) {
+            Ok(file) => {
+                let mut reader = Counting::new(io::BufReader::new(file));
+                match serde_json::from_reader::<_, Snapshot>(&mut reader) {
+                    Ok(snapshot) => check.entries = snapshot.entries.len(),
+                    Err(e) => check.error = Some(format!("Failed to parse {}: {}", base, e)),
+                }
+                check.bytes = reader.bytes;
+            }
+            Err(e) => check.error = Some(format!("Failed to read {}: {}", base, e)),
+        }
+        checks.push(check);
+    }
+    for segment in &manifest.segments {
+        let mut check = DataFileCheck { name: segment.clone(), ..DataFileCheck::default() };
+        match fs::read(dir.join(segment)) {
+            Ok(bytes) => {
+                check.bytes = bytes.len() as u64;
+                for (index, line) in bytes.split_inclusive(|b| *b == b'\n').enumerate() {
+                    check.records += 1;
+                    let parsed = line.strip_suffix(b"\n").map(serde_json::from_slice::<SegmentRecord>);
+                    match parsed {
+                        Some(Ok(_)) => check.entries += 1,
+                        _ => check.corrupt_records.push(index + 1),
+                    }
+                }
+            }
+            Err(e) => check.error = Some(format!("Failed to read segment {}: {}", segment, e)),
+        }
+        checks.push(check);
+    }
+    Ok(checks)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    base: Option<String>,
+    #[serde(default)]
+    segments: Vec<String>,
+    // 用于生成新文件名的序号，保证文件名不重复
+    #[serde(default)]
+    next_file: u64,
+}
+
+impl Manifest {
+    fn next_name(&mut self, prefix: &str, extension: &str) -> String {
+        self.next_file += 1;
+        format!("{}-{:06}.{}", prefix, self.next_file, extension)
+    }
+
+    fn references(&self, name: &str) -> bool {
+        self.base.as_deref() == Some(name) || self.segments.iter().any(|s| s == name)
+    }
+}
+
+/// 段文件中的一行：一次刷盘写入的所有变化
+#[derive(Default, Serialize, Deserialize)]
+struct SegmentRecord {
+    // 每次刷盘递增的序号
+    #[serde(default)]
+    sequence: u64,
+    #[serde(default)]
+    keys: Vec<KeyRecord>,
+    // 列族选项有变化时记录全部选项
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cf_options: Option<HashMap<String, CfOptions>>,
+    // 与 cf_options 一起记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cf_created: Option<BTreeMap<String, u64>>,
+    // 键的编码格式，见 KEY_FORMAT
+    #[serde(default)]
+    key_format: u32,
+}
+
+/// 键在刷盘时的完整状态，重放时直接覆盖之前的状态
+#[derive(Serialize, Deserialize)]
+struct KeyRecord {
+    key: protocol::Bytes,
+    #[serde(default)]
+    value: Option<protocol::Bytes>,
+    #[serde(default)]
+    history: Option<KeyHistory>,
+    #[serde(default)]
+    checksum: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at_ms: Option<u64>,
+}
+
+/// 备份文件格式的标识和版本，见 StandaloneStorage::backup_to
+const ARCHIVE_FORMAT: &str = "tinykv-archive";
+const ARCHIVE_VERSION: u32 = 1;
+
+/// 备份文件中的一行：第一行是 Header，之后每个键一行 Key，最后一行 End
+/// 记录其前所有字节（包括换行）的 CRC32
+#[derive(Serialize, Deserialize)]
+enum ArchiveRecord {
+    Header(ArchiveHeader),
+    Key(KeyRecord),
+    End { records: u64, crc32: u32 },
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveHeader {
+    format: String,
+    version: u32,
+    // 键的编码格式，见 KEY_FORMAT
+    key_format: u32,
+    created_at_ms: u64,
+    #[serde(default)]
+    cf_options: HashMap<String, CfOptions>,
+    #[serde(default)]
+    cf_created: BTreeMap<String, u64>,
+}
+
+/// 加载时逐步重放的持久化状态
+#[derive(Default)]
+struct ReplayState {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    history: BTreeMap<Vec<u8>, KeyHistory>,
+    cf_options: HashMap<String, CfOptions>,
+    cf_created: BTreeMap<String, u64>,
+    checksums: BTreeMap<Vec<u8>, u32>,
+    expirations: BTreeMap<Vec<u8>, u64>,
+}
+
+impl ReplayState {
+    fn from_snapshot(snapshot: Snapshot) -> Self {
+        ReplayState {
+            entries: snapshot.entries.into_iter().map(|(k, v)| (k.0, v.0)).collect(),
+            history: snapshot.history.into_iter().map(|(k, h)| (k.0, h)).collect(),
+            cf_options: snapshot.cf_options,
+            cf_created: snapshot.cf_created,
+            checksums: snapshot.checksums.into_iter().map(|(k, sum)| (k.0, sum)).collect(),
+            expirations: snapshot.expirations.into_iter().map(|(k, at)| (k.0, at)).collect(),
+        }
+    }
+
+    fn apply(&mut self, record: SegmentRecord) {
+        for KeyRecord { key, value, history, checksum, expires_at_ms } in record.keys {
+            match value {
+                Some(value) => self.entries.insert(key.0.clone(), value.0),
+                None => self.entries.remove(&key.0),
+            };
+            match history {
+                Some(history) => self.history.insert(key.0.clone(), history),
+                None => self.history.remove(&key.0),
+            };
+            match checksum {
+                Some(checksum) => self.checksums.insert(key.0.clone(), checksum),
+                None => self.checksums.remove(&key.0),
+            };
+            match expires_at_ms {
+                Some(at) => self.expirations.insert(key.0, at),
+                None => self.expirations.remove(&key.0),
+            };
+        }
+        if let Some(cf_options) = record.cf_options {
+            self.cf_options = cf_options;
+        }
+        if let Some(cf_created) = record.cf_created {
+            self.cf_created = cf_created;
+        }
+    }
+
+    /// 丢弃 now 时已经过期的键，返回这些键和其中仍有值的条目数
+    fn drop_expired(&mut self, now: u64) -> (Vec<Vec<u8>>, usize) {
+        let expired: Vec<Vec<u8>> =
+            self.expirations.iter().filter(|(_, at)| **at <= now).map(|(k, _)| k.clone()).collect();
+        let mut dropped = 0;
+        for key in &expired {
+            self.expirations.remove(key);
+            if self.entries.remove(key).is_some() {
+                dropped += 1;
+            }
+        }
+        self.expirations.retain(|k, _| self.entries.contains_key(k));
+        (expired, dropped)
+    }
+
+    /// 作为基础快照写入磁盘的完整状态
+    fn to_snapshot(&self, last_sequence: u64) -> Snapshot {
+        let bytes = |k: &Vec<u8>| protocol::Bytes(k.clone());
+        Snapshot {
+            entries: self.entries.iter().map(|(k, v)| (bytes(k), bytes(v))).collect(),
+            history: self.history.iter().map(|(k, h)| (bytes(k), h.clone())).collect(),
+            cf_options: self.cf_options.clone(),
+            cf_created: self.cf_created.clone(),
+            checksums: self.checksums.iter().map(|(k, sum)| (bytes(k), *sum)).collect(),
+            expirations: self.expirations.iter().map(|(k, at)| (bytes(k), *at)).collect(),
+            last_sequence,
+            key_format: KEY_FORMAT,
+        }
+    }
+}
+
+/// 刷盘和整理共享的段文件状态
+#[derive(Default)]
+struct LogState {
+    manifest: Manifest,
+    // 最后一个段文件的大小
+    active_bytes: u64,
+    // 基础快照和所有段文件的总大小
+    disk_bytes: u64,
+    last_sequence: u64,
+    // 最后一个段文件开始写入（或打开）时的毫秒时间戳
+    active_started_ms: u64,
+    // 启动以来切换段文件和删除旧文件的次数
+    rotations: u64,
+    pruned_files: u64,
+}
+
+// 整理统计，以及最近一次整理开始时的单调时间
+#[derive(Default)]
+struct CompactionState {
+    info: CompactionInfo,
+    last_started: Option<Duration>,
+}
+
+// 持久化状态，以及降级时下一次重试的退避时间
+#[derive(Default)]
+struct PersistenceState {
+    status: PersistenceStatus,
+    backoff: Duration,
+}
+
+// 延迟加载的状态；没有开启延迟加载时一开始就是已加载
+struct Loader {
+    reads: LoadingReads,
+    // 与 status.loaded 一致，供读写路径无锁检查
+    loaded: AtomicBool,
+    started: AtomicBool,
+    status: Mutex<LoadStatus>,
+    done: Condvar,
+    // 加载期间的写入批次，加载完成时按顺序应用；没有在加载时为 None
+    overlay: Mutex<Option<Vec<Vec<protocol::Modify>>>>,
+    // 打开时显式传入的列族选项，加载完成后覆盖快照中保存的选项
+    cf_options: Mutex<HashMap<String, CfOptions>>,
+}
+
+impl Loader {
+    fn loaded() -> Self {
+        Loader {
+            reads: LoadingReads::Wait,
+            loaded: AtomicBool::new(true),
+            started: AtomicBool::new(false),
+            status: Mutex::new(LoadStatus { loaded: true, ..LoadStatus::default() }),
+            done: Condvar::new(),
+            overlay: Mutex::new(None),
+            cf_options: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn pending(reads: LoadingReads, total_bytes: u64, cf_options: HashMap<String, CfOptions>) -> Self {
+        Loader {
+            reads,
+            loaded: AtomicBool::new(false),
+            started: AtomicBool::new(false),
+            status: Mutex::new(LoadStatus { total_bytes, ..LoadStatus::default() }),
+            done: Condvar::new(),
+            overlay: Mutex::new(Some(Vec::new())),
+            cf_options: Mutex::new(cf_options),
+        }
+    }
+}
+
+// 从磁盘读取并重放后、还没有装入内存的数据
+struct LoadedState {
+    manifest: Manifest,
+    state: ReplayState,
+    report: RecoveryReport,
+    // 加载时已经过期而被丢弃的键
+    expired: Vec<Vec<u8>>,
+    active_bytes: u64,
+    disk_bytes: u64,
+    stored_records: u64,
+}
+
+
+// 独立存储引擎
+pub struct StandaloneStorage {
+    data: Arc<RwLock<StorageData>>,
+    // 与 data.totals 是同一个
+    totals: Arc<Totals>,
+    // 规范化后的数据目录，纯内存模式为 None
+    path: Option<PathBuf>,
+    durability: Durability,
+    fs: Arc<dyn FileSystem>,
+    max_memory_bytes: Option<usize>,
+    eviction: EvictionPolicy,
+    flush_policy: Option<FlushPolicy>,
+    // flush_policy 中的刷盘间隔，可以在运行时修改，刷盘线程每次检查时读取
+    flush_interval: Mutex<Option<Duration>>,
+    compaction_policy: Option<CompactionPolicy>,
+    // 自上次刷盘以来的修改数，只在持久化模式下计数
+    dirty: AtomicU64,
+    // 磁盘上的键记录数：基础快照中的条目加上段文件中的记录，减去内存中的键数即为无效记录
+    stored_records: AtomicU64,
+    compaction: Mutex<CompactionState>,
+    persistence: Mutex<PersistenceState>,
+    // 保证同一时刻只有一个刷盘或整理在进行
+    log: Mutex<LogState>,
+    segment_rotation: RotationPolicy,
+    last_flush: Mutex<FlushInfo>,
+    maintenance: MaintenanceTracker,
+    // 下一个锁令牌；以启动时的毫秒时间戳乘 1000 为起点，重启后发放的令牌仍然递增
+    next_lock_token: AtomicU64,
+    salvage: bool,
+    // 打开时的恢复结果，由第一次 Info 取走
+    recovery: Mutex<Option<RecoveryReport>>,
+    // 打开时执行的格式迁移，记入恢复结果
+    migrations: Vec<String>,
+    // 持久化模式下持有的数据目录锁，drop 时释放
+    _dir_lock: Option<DirLock>,
+    clock: Arc<dyn Clock>,
+    // 后台刷盘、整理、清理和恢复中出现的错误，服务端的请求错误也记录在这里
+    errors: Arc<ErrorLog>,
+    loader: Loader,
+    observers: Observers,
+    // 最近一次通知观察者的提交序号
+    commit_seq: AtomicU64,
+    // 配置了 StorageOptions::oplog 时记录提交的批次，由注册的观察者写入
+    oplog: Option<Arc<Oplog>>,
+    // 数据锁的获取和等待统计，读取器共享
+    locks: Arc<LockCounters>,
+}
+
+impl Default for StandaloneStorage {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+impl StandaloneStorage {
+    /// 纯内存存储，不读写磁盘；flush 返回 NoPersistencePath 错误
+    pub fn in_memory() -> Self {
+        Self::in_memory_with_options(StorageOptions::default())
+    }
+
+    /// 使用指定选项的纯内存存储，与持久化相关的选项不起作用
+    pub fn in_memory_with_options(options: StorageOptions) -> Self {
+        // 没有数据目录时不会读取磁盘，构造不会失败
+        Self::open_at(None, options).expect("in-memory storage never touches the disk")
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        Self::open_with_options(path, StorageOptions::default())
+    }
+
+    /// 打开持久化存储：创建数据目录并规范化路径，空路径返回 InvalidPath 错误
+    pub fn open_with_options(path: impl AsRef<Path>, options: StorageOptions) -> Result<Self, String> {
+        let path = path.as_ref();
+        if path.as_os_str().is_empty() {
+            return Err("InvalidPath: empty data path, use StandaloneStorage::in_memory() for a store without persistence".to_string());
+        }
+        options.fs.create_dir_all(path)
+            .map_err(|e| format!("InvalidPath: failed to create {}: {}", path.display(), e))?;
+        let path = fs::canonicalize(path)
+            .map_err(|e| format!("InvalidPath: failed to resolve {}: {}", path.display(), e))?;
+        Self::open_at(Some(path), options)
+    }
+
+    fn open_at(path: Option<PathBuf>, options: StorageOptions) -> Result<Self, String> {
+        let mut data = StorageData {
+            checksums: options.checksums.then(BTreeMap::new),
+            lru: matches!(options.eviction, EvictionPolicy::Lru | EvictionPolicy::Spill).then(|| LruIndex {
+                rng: SystemClock.now_ms() | 1,
+                ..LruIndex::default()
+            }),
+            dirty_keys: path.is_some().then(BTreeSet::new),
+            compress_threshold: options.compress_threshold,
+            spill_threshold: options.spill_threshold,
+            ..StorageData::default()
+        };
+        let dir_lock = match &path {
+            Some(dir) => Some(DirLock::acquire(dir, options.force_unlock)?),
+            None => None,
+        };
+        // 持有目录锁之后才清理旧的 blob 文件
+        let spill = options.spill_threshold.is_some() || options.eviction == EvictionPolicy::Spill;
+        if let Some(dir) = path.as_ref().filter(|_| spill) {
+            data.blobs = Some(BlobStore::open(dir)?);
+        }
+        let eviction = match options.eviction {
+            EvictionPolicy::Spill if data.blobs.is_none() => EvictionPolicy::None,
+            eviction => eviction,
+        };
+        // 持有目录锁之后才迁移，避免两个实例同时改写同一个目录
+        let migrations = match &path {
+            Some(dir) => migration::migrate(dir)?,
+            None => Vec::new(),
+        };
+        // 延迟加载时只读取清单，没有数据可加载时与立即加载相同
+        let manifest = match (options.lazy_load, &path) {
+            (Some(_), Some(dir)) => Self::read_manifest(dir)?.map(|manifest| (dir, manifest)),
+            _ => None,
+        };
+        let loader = match (options.lazy_load, &manifest) {
+            (Some(reads), Some((dir, manifest))) => {
+                let files = manifest.base.iter().chain(&manifest.segments);
+                let total_bytes = files.filter_map(|f| fs::metadata(dir.join(f)).ok()).map(|m| m.len()).sum();
+                Loader::pending(reads, total_bytes, options.cf_options.clone())
+            }
+            _ => Loader::loaded(),
+        };
+        let oplog = match &options.oplog {
+            Some(config) => Some(Arc::new(Oplog::open(config, Arc::clone(&options.clock))?)),
+            None => None,
+        };
+        let storage = StandaloneStorage {
+            salvage: options.salvage,
+            recovery: Mutex::new(None),
+            migrations,
+            _dir_lock: dir_lock,
+            totals: Arc::clone(&data.totals),
+            data: Arc::new(RwLock::new(data)),
+            path,
+            durability: options.durability,
+            fs: options.fs,
+            max_memory_bytes: options.max_memory_bytes,
+            eviction,
+            flush_interval: Mutex::new(options.flush_policy.as_ref().and_then(|p| p.interval)),
+            flush_policy: options.flush_policy,
+            compaction_policy: options.compaction,
+            dirty: AtomicU64::new(0),
+            stored_records: AtomicU64::new(0),
+            compaction: Mutex::new(CompactionState::default()),
+            persistence: Mutex::new(PersistenceState::default()),
+            log: Mutex::new(LogState::default()),
+            segment_rotation: RotationPolicy {
+                max_bytes: options.segment_max_bytes.max(1),
+                max_age: options.segment_max_age,
+                retain: None,
+            },
+            last_flush: Mutex::new(FlushInfo::default()),
+            maintenance: MaintenanceTracker::default(),
+            next_lock_token: AtomicU64::new(options.clock.now_ms().saturating_mul(1000)),
+            errors: Arc::new(ErrorLog::new(ERROR_LOG_CAPACITY, Arc::clone(&options.clock))),
+            clock: options.clock,
+            loader,
+            observers: Observers::default(),
+            commit_seq: AtomicU64::new(0),
+            oplog,
+            locks: Arc::default(),
+        };
+        if let Some(oplog) = &storage.oplog {
+            storage.register_observer(Box::new(OplogObserver { oplog: Arc::clone(oplog), errors: Arc::clone(&storage.errors) }));
+        }
+        if !storage.loader.loaded.load(Ordering::SeqCst) {
+            return Ok(storage);
+        }
+        if let Some(report) = storage.load_from_disk()? {
+            println!(
+                "Recovery: {} snapshot entries, {} records replayed, {} corrupt records skipped, {} expired entries dropped, last sequence {}",
+                report.snapshot_entries,
+                report.wal_records_replayed,
+                report.corrupt_records_skipped,
+                report.expired_entries_dropped,
+                report.last_sequence
+            );
+            *storage.recovery.lock().map_err(|e| e.to_string())? = Some(report);
+        }
+
+        storage.apply_cf_options(options.cf_options)?;
+        Ok(storage)
+    }
+
+    // 显式传入的选项优先于快照中保存的选项
+    fn apply_cf_options(&self, cf_options: HashMap<String, CfOptions>) -> Result<(), String> {
+        let mut data = self.lock_write()?;
+        data.cf_options_dirty = !cf_options.is_empty();
+        data.cf_options.extend(cf_options);
+        data.rebuild_value_index()
+    }
+
+    /// 延迟加载模式下启动后台加载线程；不需要加载或已经启动过时返回 None
+    /// 线程持有存储的强引用直到加载结束
+    pub fn start_loader(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if self.loader.loaded.load(Ordering::SeqCst) || self.loader.started.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        let storage = Arc::clone(self);
+        Some(thread::spawn(move || {
+            if let Err(e) = storage.lazy_load() {
+                eprintln!("Background load failed: {}", e);
+                storage.errors.record(ErrorCategory::Recovery, format!("Background load failed: {}", e));
+                // 暂存的写入随之丢弃，之后的读写都返回 LoadFailed
+                if let Ok(mut overlay) = storage.loader.overlay.lock() {
+                    *overlay = None;
+                }
+                if let Ok(mut status) = storage.loader.status.lock() {
+                    status.error = Some(e);
+                    status.pending_writes = 0;
+                }
+                storage.loader.done.notify_all();
+            }
+        }))
+    }
+
+    fn lazy_load(&self) -> Result<(), String> {
+        let Some(manifest) = Self::read_manifest(self.dir()?)? else {
+            return Err("manifest disappeared before loading".to_string());
+        };
+        let loaded = self.replay(manifest, |bytes| {
+            if let Ok(mut status) = self.loader.status.lock() {
+                status.loaded_bytes += bytes;
+            }
+        })?;
+
+        // 持有 overlay 锁直到暂存的批次应用完，新的写入排在它们之后
+        let mut overlay = self.loader.overlay.lock().map_err(|e| e.to_string())?;
+        let report = self.install(loaded)?;
+        println!(
+            "Background load finished: {} snapshot entries, {} records replayed, {} writes merged",
+            report.snapshot_entries,
+            report.wal_records_replayed,
+            overlay.as_ref().map_or(0, Vec::len)
+        );
+        *self.recovery.lock().map_err(|e| e.to_string())? = Some(report);
+        let cf_options = std::mem::take(&mut *self.loader.cf_options.lock().map_err(|e| e.to_string())?);
+        self.apply_cf_options(cf_options)?;
+        for (i, batch) in overlay.take().unwrap_or_default().into_iter().enumerate() {
+            let data = self.lock_write()?;
+            if let Err(e) = self.write_planned_locked(data, |_| Ok(((), batch, Vec::new()))) {
+                eprintln!("Dropping write {} received during loading: {}", i, e);
+                self.errors.record(ErrorCategory::Recovery, format!("Dropped write {} received during loading: {}", i, e));
+            }
+        }
+
+        let mut status = self.loader.status.lock().map_err(|e| e.to_string())?;
+        status.loaded = true;
+        status.pending_writes = 0;
+        self.loader.loaded.store(true, Ordering::SeqCst);
+        drop(status);
+        drop(overlay);
+        self.loader.done.notify_all();
+        Ok(())
+    }
+
+    /// 延迟加载的进度；没有开启延迟加载或已经加载完成时 loaded 为 true
+    pub fn load_status(&self) -> Result<LoadStatus, String> {
+        Ok(self.loader.status.lock().map_err(|e| e.to_string())?.clone())
+    }
+
+    /// 数据是否已经全部加载
+    pub fn is_loaded(&self) -> bool {
+        self.loader.loaded.load(Ordering::SeqCst)
+    }
+
+    // 等待延迟加载完成；LoadingReads::Fail 时立即返回 Loading 错误
+    fn wait_loaded(&self) -> Result<(), String> {
+        if self.is_loaded() {
+            return Ok(());
+        }
+        let mut status = self.loader.status.lock().map_err(|e| e.to_string())?;
+        loop {
+            if let Some(e) = &status.error {
+                return Err(format!("LoadFailed: {}", e));
+            }
+            if status.loaded {
+                return Ok(());
+            }
+            if self.loader.reads == LoadingReads::Fail {
+                return Err(format!("Loading: {} of {} bytes loaded", status.loaded_bytes, status.total_bytes));
+            }
+            status = self.loader.done.wait(status).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // 加载完成后的读锁；统计类的方法直接读取，加载期间看到的是空数据
+    fn read_data(&self) -> Result<RwLockReadGuard<'_, StorageData>, String> {
+        self.wait_loaded()?;
+        self.lock_read()
+    }
+
+    fn write_data(&self) -> Result<RwLockWriteGuard<'_, StorageData>, String> {
+        self.wait_loaded()?;
+        self.lock_write()
+    }
+
+    fn lock_read(&self) -> Result<RwLockReadGuard<'_, StorageData>, String> {
+        self.locks.read(&self.data)
+    }
+
+    fn lock_write(&self) -> Result<RwLockWriteGuard<'_, StorageData>, String> {
+        self.locks.write(&self.data)
+    }
+
+    /// 数据锁的获取次数和等待时间
+    pub fn lock_stats(&self) -> LockWaitStats {
+        self.locks.stats()
+    }
+
+    /// 清零 lock_stats，由 ResetStats 调用
+    pub fn reset_lock_stats(&self) {
+        self.locks.reset();
+    }
+
+    /// 打开持久化存储并返回恢复结果；纯内存模式或目录中没有数据时返回空报告
+    pub fn open_with_report(path: impl AsRef<Path>) -> Result<(Self, RecoveryReport), String> {
+        let storage = Self::open(path)?;
+        let report = storage.recovery.lock().map_err(|e| e.to_string())?.clone().unwrap_or_default();
+        Ok((storage, report))
+    }
+
+    /// 取走打开时的恢复结果，之后再调用返回 None
+    pub fn take_recovery_report(&self) -> Result<Option<RecoveryReport>, String> {
+        Ok(self.recovery.lock().map_err(|e| e.to_string())?.take())
+    }
+
+    /// 显式创建列族，可以同时设置列族选项；列族已存在时返回 CfExists 错误
+    pub fn create_cf(&self, cf: &str, options: Option<CfOptions>) -> Result<(), String> {
+        let mut data = self.write_data()?;
+        if data.cf_created.contains_key(cf) {
+            return Err(format!("CfExists: {}", cf));
+        }
+        data.register_cf(cf, self.clock.now_ms());
+        drop(data);
+        match options {
+            Some(options) => self.set_cf_options(cf, options),
+            None => Ok(()),
+        }
+    }
+
+    /// 列族是否已创建（显式创建或曾被写入）
+    pub fn cf_exists(&self, cf: &str) -> Result<bool, String> {
+        let data = self.read_data()?;
+        Ok(data.cf_created.contains_key(cf))
+    }
+
+    /// 所有已知列族及其创建时间（毫秒），按列族名排序
+    pub fn cf_created(&self) -> Result<Vec<(String, u64)>, String> {
+        let data = self.lock_read()?;
+        Ok(data.cf_created.iter().map(|(cf, at)| (cf.clone(), *at)).collect())
+    }
+
+    /// 设置列族选项，新选项对之后的写入生效
+    pub fn set_cf_options(&self, cf: &str, options: CfOptions) -> Result<(), String> {
+        let mut data = self.write_data()?;
+        let reindex = data.cf_options.get(cf).is_some_and(|o| o.index_values) != options.index_values;
+        let previous = data.cf_options.insert(cf.to_string(), options);
+        if reindex && let Err(e) = data.rebuild_value_index() {
+            match previous {
+                Some(previous) => data.cf_options.insert(cf.to_string(), previous),
+                None => data.cf_options.remove(cf),
+            };
+            return Err(e);
+        }
+        data.cf_options_dirty = true;
+        Ok(())
+    }
+
+    /// 在同一个写锁内应用整个批次，批次可以跨列族，读者只会看到全部或全部没有生效
+    /// 延迟加载期间批次暂存起来，加载完成时按顺序应用，届时才检查配额和内存预算
+    pub fn write(&self, batch: Vec<protocol::Modify>) -> Result<(), String> {
+        if !self.loader.loaded.load(Ordering::SeqCst) {
+            let mut overlay = self.loader.overlay.lock().map_err(|e| e.to_string())?;
+            if let Some(pending) = overlay.as_mut() {
+                pending.push(batch);
+                self.loader.status.lock().map_err(|e| e.to_string())?.pending_writes = pending.len();
+                return Ok(());
+            }
+        }
+        self.write_after_read(batch, |_| Ok(()))
+    }
+
+    /// 注册写入观察者，之后提交的每个非空批次都会通知它，见 WriteObserver
+    pub fn register_observer(&self, observer: Box<dyn WriteObserver>) {
+        self.observers.register(observer);
+    }
+
+    /// 配置了 StorageOptions::oplog 时的操作日志
+    pub fn oplog(&self) -> Option<&Arc<Oplog>> {
+        self.oplog.as_ref()
+    }
+
+    /// 原子地取出并删除键的值
+    pub fn get_del(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let delete = protocol::Modify::new_delete(cf.to_string(), key.to_vec());
+        self.write_after_read(vec![delete], |data| data.get_checked(cf, key))
+    }
+
+    /// 原子地写入新值并返回旧值
+    pub fn get_set(&self, cf: &str, key: &[u8], value: Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+        let put = protocol::Modify::new_put(cf.to_string(), key.to_vec(), value);
+        self.write_after_read(vec![put], |data| data.get_checked(cf, key))
+    }
+
+    /// 原子地把 old_key 的值和过期时间移动到 new_key
+    /// 源键不存在时返回 KeyNotFound；未指定 overwrite 且目标键已存在时返回 KeyExists，两种情况都不做修改
+    pub fn rename(&self, cf: &str, old_key: &[u8], new_key: &[u8], overwrite: bool) -> Result<(), String> {
+        self.write_planned_with_expiry(|data| {
+            let value = data.get_existing(cf, old_key)?;
+            if old_key == new_key {
+                return Ok(((), Vec::new(), Vec::new()));
+            }
+            data.check_destination(cf, new_key, overwrite)?;
+            let batch = vec![
+                protocol::Modify::new_put(cf.to_string(), new_key.to_vec(), value),
+                protocol::Modify::new_delete(cf.to_string(), old_key.to_vec()),
+            ];
+            Ok(((), batch, data.carried_expiry(cf, old_key, new_key)))
+        })
+    }
+
+    /// 原子地把 src_key 的值和过期时间复制到 dst_key，错误情况与 rename 相同
+    pub fn copy(&self, cf: &str, src_key: &[u8], dst_key: &[u8], overwrite: bool) -> Result<(), String> {
+        self.write_planned_with_expiry(|data| {
+            let value = data.get_existing(cf, src_key)?;
+            data.check_destination(cf, dst_key, overwrite)?;
+            let batch = vec![protocol::Modify::new_put(cf.to_string(), dst_key.to_vec(), value)];
+            Ok(((), batch, data.carried_expiry(cf, src_key, dst_key)))
+        })
+    }
+
+    /// 尝试获取锁，租约为 ttl_ms 毫秒；锁被他人持有且未过期时返回 None
+    /// 过期的锁可以直接被接管，不必等待清理线程
+    pub fn lock_acquire(&self, name: &str, ttl_ms: u64) -> Result<Option<u64>, String> {
+        let now = self.clock.now_ms();
+        self.write_planned(|data| {
+            if let Some(value) = data.get_checked(LOCKS_CF, name.as_bytes())?
+                && decode_lock(&value)?.1 > now
+            {
+                return Ok((None, Vec::new()));
+            }
+            let token = self.next_lock_token.fetch_add(1, Ordering::SeqCst);
+            let put = protocol::Modify::new_put(LOCKS_CF.to_string(), name.as_bytes().to_vec(), encode_lock(token, now + ttl_ms));
+            Ok((Some(token), vec![put]))
+        })
+    }
+
+    /// 释放锁，令牌与当前持有者不一致时返回 LockNotHeld 错误
+    pub fn lock_release(&self, name: &str, token: u64) -> Result<(), String> {
+        self.write_planned(|data| {
+            data.check_lock_holder(name, token, None)?;
+            Ok(((), vec![protocol::Modify::new_delete(LOCKS_CF.to_string(), name.as_bytes().to_vec())]))
+        })
+    }
+
+    /// 把租约延长到从现在起 ttl_ms 毫秒；锁已过期或令牌不一致时返回 LockNotHeld 错误
+    pub fn lock_renew(&self, name: &str, token: u64, ttl_ms: u64) -> Result<(), String> {
+        let now = self.clock.now_ms();
+        self.write_planned(|data| {
+            data.check_lock_holder(name, token, Some(now))?;
+            let put = protocol::Modify::new_put(LOCKS_CF.to_string(), name.as_bytes().to_vec(), encode_lock(token, now + ttl_ms));
+            Ok(((), vec![put]))
+        })
+    }
+
+    /// 删除所有租约已过期的锁，返回删除的数量
+    pub fn sweep_expired_locks(&self) -> Result<usize, String> {
+        let now = self.clock.now_ms();
+        self.write_planned(|data| {
+            let prefix = EncodedKey::cf_prefix(LOCKS_CF).into_bytes();
+            let mut batch = Vec::new();
+            for (prefixed_key, value) in data.entries.range(prefix.clone()..) {
+                if !prefixed_key.starts_with(&prefix) {
+                    break;
+                }
+                if decode_lock(&value.try_get()?).is_ok_and(|(_, expires)| expires <= now) {
+                    batch.push(protocol::Modify::new_delete(LOCKS_CF.to_string(), prefixed_key[prefix.len()..].to_vec()));
+                }
+            }
+            Ok((batch.len(), batch))
+        })
+    }
+
+    /// 启动定期清理过期锁的线程，线程只持有弱引用
+    pub fn start_lock_sweeper(self: &Arc<Self>, interval: Duration) -> Sweeper {
+        self.start_sweeper(interval, "Lock", |storage| storage.sweep_expired_locks())
+    }
+
+    /// 设置键在 ttl 后过期，之后写入新值会清除过期时间；键不存在时返回 KeyNotFound 错误
+    pub fn expire(&self, cf: &str, key: &[u8], ttl: Duration) -> Result<(), String> {
+        let mut data = self.write_data()?;
+        self.remove_expired(&mut data);
+        data.get_existing(cf, key)?;
+        let prefixed_key = EncodedKey::encode(cf, key).into_bytes();
+        let expires_at_ms = self.clock.now_ms().saturating_add(ttl.as_millis() as u64);
+        data.set_expiry(&prefixed_key, Some(expires_at_ms));
+        data.mark_dirty(&prefixed_key);
+        if self.path.is_some() {
+            self.dirty.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// 键的剩余生存时间，没有设置过期时间时为 None；键不存在时返回 KeyNotFound 错误
+    pub fn ttl(&self, cf: &str, key: &[u8]) -> Result<Option<Duration>, String> {
+        let now = self.clock.now_ms();
+        let prefixed_key = EncodedKey::encode(cf, key).into_bytes();
+        let data = self.read_data()?;
+        if data.is_expired(&prefixed_key, now) || !data.entries.contains_key(&prefixed_key) {
+            return Err(format!("KeyNotFound: {} in column family {}", protocol::display_bytes(key), cf));
+        }
+        Ok(data.expirations.get(&prefixed_key).map(|at| Duration::from_millis(at - now)))
+    }
+
+    /// 删除所有已过期的键，返回删除的数量
+    pub fn purge_expired(&self) -> Result<usize, String> {
+        let mut data = self.write_data()?;
+        Ok(self.remove_expired(&mut data))
+    }
+
+    /// 启动定期删除过期键的线程，线程只持有弱引用
+    pub fn start_expiry_sweeper(self: &Arc<Self>, interval: Duration) -> Sweeper {
+        self.start_sweeper(interval, "Expiry", |storage| storage.purge_expired())
+    }
+
+    /// 在写锁内删除已过期的键，并计入未刷盘的修改数
+    fn remove_expired(&self, data: &mut StorageData) -> usize {
+        let removed = data.remove_expired(self.clock.now_ms());
+        if removed > 0 && self.path.is_some() {
+            self.dirty.fetch_add(removed as u64, Ordering::SeqCst);
+        }
+        removed
+    }
+
+    /// 软删除：把键的当前值移到回收站后删除，键不存在时与普通删除相同
+    pub fn soft_delete(&self, cf: &str, key: &[u8]) -> Result<(), String> {
+        let now = self.clock.now_ms();
+        self.write_planned(|data| {
+            let mut batch = Vec::new();
+            if let Some(value) = data.entries.get(&EncodedKey::encode(cf, key).into_bytes()) {
+                batch.push(protocol::Modify::new_put(TRASH_CF.to_string(), trash_key(cf, key, now), value.try_to_vec()?));
+            }
+            batch.push(protocol::Modify::new_delete(cf.to_string(), key.to_vec()));
+            Ok(((), batch))
+        })
+    }
+
+    /// 把键最近一次被软删除的值移回原处
+    /// 回收站中没有该键时返回 NotInTrash；未指定 overwrite 且键已有值时返回 KeyExists
+    pub fn restore_key(&self, cf: &str, key: &[u8], overwrite: bool) -> Result<(), String> {
+        self.write_planned(|data| {
+            let prefix = EncodedKey::encode(TRASH_CF, &trash_prefix(cf, Some(key))).into_bytes();
+            let latest = data
+                .entries
+                .range(prefix.clone()..)
+                .take_while(|(k, _)| k.starts_with(&prefix))
+                .last();
+            let Some((trashed, value)) = latest else {
+                return Err(format!("NotInTrash: {} in column family {}", protocol::display_bytes(key), cf));
+            };
+            data.check_destination(cf, key, overwrite)?;
+
+            let trash_cf_prefix = EncodedKey::cf_prefix(TRASH_CF).into_bytes().len();
+            let batch = vec![
+                protocol::Modify::new_put(cf.to_string(), key.to_vec(), value.try_to_vec()?),
+                protocol::Modify::new_delete(TRASH_CF.to_string(), trashed[trash_cf_prefix..].to_vec()),
+            ];
+            Ok(((), batch))
+        })
+    }
+
+    /// 回收站中的条目，按 (列族, 键, 删除时间) 排序；给出 cf 时只列出该列族的条目
+    pub fn trash_entries(&self, cf: Option<&str>) -> Result<Vec<TrashEntry>, String> {
+        let data = self.read_data()?;
+        let trash_cf_prefix = EncodedKey::cf_prefix(TRASH_CF).into_bytes();
+        let prefix = EncodedKey::encode(TRASH_CF, &cf.map(|cf| trash_prefix(cf, None)).unwrap_or_default()).into_bytes();
+
+        let mut entries = Vec::new();
+        for (encoded, value) in data.entries.range(prefix.clone()..) {
+            if !encoded.starts_with(&prefix) {
+                break;
+            }
+            let (entry_cf, key, deleted_at_ms) = decode_trash_key(&encoded[trash_cf_prefix.len()..])?;
+            entries.push(TrashEntry {
+                cf: entry_cf,
+                key: protocol::Bytes(key),
+                deleted_at_ms,
+                value: protocol::Bytes(value.try_to_vec()?),
+            });
+        }
+        entries.sort_by(|a, b| (&a.cf, &a.key.0, a.deleted_at_ms).cmp(&(&b.cf, &b.key.0, b.deleted_at_ms)));
+        Ok(entries)
+    }
+
+    /// 从 cfs 中均匀随机地抽取至多 count 个未过期的键，按 (列族, 键) 排序
+    /// 在一个读锁内做蓄水池抽样，结果来自同一时刻的数据；不复制条目，但要遍历这些列族中的所有键
+    pub fn sample_keys(&self, cfs: &[String], count: usize) -> Result<Vec<KeySample>, String> {
+        let now = self.clock.now_ms();
+        let data = self.read_data()?;
+        let mut rng = RandomState::new().build_hasher().finish() | 1;
+        let mut reservoir: Vec<(&Vec<u8>, &StoredValue)> = Vec::new();
+        let mut seen = 0;
+        for cf in cfs {
+            let prefix = EncodedKey::cf_prefix(cf).into_bytes();
+            for (prefixed_key, value) in data.entries.range(prefix.clone()..) {
+                if !prefixed_key.starts_with(&prefix) {
+                    break;
+                }
+                if data.is_expired(prefixed_key, now) {
+                    continue;
+                }
+                seen += 1;
+                if reservoir.len() < count {
+                    reservoir.push((prefixed_key, value));
+                } else {
+                    // 第 seen 个键以 count / seen 的概率替换蓄水池中的一个
+                    let slot = (xorshift(&mut rng) % seen) as usize;
+                    if slot < count {
+                        reservoir[slot] = (prefixed_key, value);
+                    }
+                }
+            }
+        }
+
+        let mut samples: Vec<KeySample> = reservoir
+            .into_iter()
+            .filter_map(|(prefixed_key, value)| {
+                let (cf, key) = EncodedKey::decode_bytes(prefixed_key)?;
+                Some(KeySample {
+                    cf: cf.to_string(),
+                    key: protocol::Bytes(key.to_vec()),
+                    value_size: value.len(),
+                    ttl_ms: data.expirations.get(prefixed_key).map(|at| at - now),
+                })
+            })
+            .collect();
+        samples.sort_by(|a, b| (&a.cf, &a.key.0).cmp(&(&b.cf, &b.key.0)));
+        Ok(samples)
+    }
+
+    /// 清理回收站：给出 retention 时只删除早于保留期的条目，否则清空；返回删除的数量
+    pub fn purge_trash(&self, retention: Option<Duration>) -> Result<usize, String> {
+        let cutoff = self.trash_cutoff(retention);
+        self.write_planned(|data| {
+            let batch: Vec<protocol::Modify> = Self::expired_trash(data, cutoff)?
+                .into_iter()
+                .map(|(trash_key, _)| protocol::Modify::new_delete(TRASH_CF.to_string(), trash_key.to_vec()))
+                .collect();
+            Ok((batch.len(), batch))
+        })
+    }
+
+    /// purge_trash 的试运行，不修改任何数据；键为回收站条目原来的 (列族, 键)
+    pub fn purge_trash_report(&self, retention: Option<Duration>) -> Result<DeletionReport, String> {
+        let cutoff = self.trash_cutoff(retention);
+        let data = self.read_data()?;
+        let mut report = DeletionReport::default();
+        for (trash_key, value) in Self::expired_trash(&data, cutoff)? {
+            let (cf, key, _) = decode_trash_key(trash_key)?;
+            let encoded = EncodedKey::encode(TRASH_CF, trash_key).into_bytes();
+            report.add(cf, key, entry_size(&encoded, value.len()));
+        }
+        Ok(report)
+    }
+
+    fn trash_cutoff(&self, retention: Option<Duration>) -> u64 {
+        retention.map_or(u64::MAX, |r| self.clock.now_ms().saturating_sub(r.as_millis() as u64))
+    }
+
+    // 删除时间早于 cutoff 的回收站条目：(回收站键, 值)
+    fn expired_trash(data: &StorageData, cutoff: u64) -> Result<Vec<(&[u8], &StoredValue)>, String> {
+        let prefix = EncodedKey::cf_prefix(TRASH_CF).into_bytes();
+        let mut expired = Vec::new();
+        for (encoded, value) in entries_with_prefix(&data.entries, &prefix) {
+            let trash_key = &encoded[prefix.len()..];
+            if decode_trash_key(trash_key)?.2 < cutoff {
+                expired.push((trash_key, value));
+            }
+        }
+        Ok(expired)
+    }
+
+    /// 启动按保留期清理回收站的线程，线程只持有弱引用
+    pub fn start_trash_sweeper(self: &Arc<Self>, retention: Duration, interval: Duration) -> Sweeper {
+        self.start_sweeper(interval, "Trash", move |storage| storage.purge_trash(Some(retention)))
+    }
+
+    /// 按存储的时钟每隔 interval 在后台执行一次 sweep，存储被释放或句柄被丢弃后退出
+    fn start_sweeper(
+        self: &Arc<Self>,
+        interval: Duration,
+        name: &'static str,
+        sweep: impl Fn(&Self) -> Result<usize, String> + Send + 'static,
+    ) -> Sweeper {
+        let storage: Weak<Self> = Arc::downgrade(self);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let clock = Arc::clone(&self.clock);
+        let mut last_sweep = clock.now().monotonic;
+
+        let thread = thread::spawn(move || {
+            while !thread_stop.load(Ordering::SeqCst) {
+                thread::sleep(FLUSH_POLL_INTERVAL);
+                if clock.now().monotonic.saturating_sub(last_sweep) < interval {
+                    continue;
+                }
+                let Some(storage) = storage.upgrade() else {
+                    break;
+                };
+                if !storage.is_loaded() {
+                    continue;
+                }
+                if let Err(e) = sweep(&storage) {
+                    eprintln!("{} sweep failed: {}", name, e);
+                    storage.errors.record(ErrorCategory::Sweep, format!("{} sweep failed: {}", name, e));
+                }
+                last_sweep = clock.now().monotonic;
+            }
+        });
+
+        Sweeper {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// 在同一个写锁内先执行 read，再应用 batch；read 失败时不做任何修改
+    fn write_after_read<R>(
+        &self,
+        batch: Vec<protocol::Modify>,
+        read: impl FnOnce(&StorageData) -> Result<R, String>,
+    ) -> Result<R, String> {
+        self.write_planned(|data| Ok((read(data)?, batch)))
+    }
+
+    /// 在同一个写锁内由 plan 读取数据并决定要应用的批次；plan 失败时不做任何修改
+    fn write_planned<R>(
+        &self,
+        plan: impl FnOnce(&StorageData) -> Result<(R, Vec<protocol::Modify>), String>,
+    ) -> Result<R, String> {
+        let data = self.write_data()?;
+        self.write_planned_locked(data, |data| plan(data).map(|(result, batch)| (result, batch, Vec::new())))
+    }
+
+    /// 与 write_planned 相同，plan 另外返回 (带列族前缀的键, 过期时间) 列表，在同一个写锁内于批次之后设置
+    fn write_planned_with_expiry<R>(
+        &self,
+        plan: impl FnOnce(&StorageData) -> Result<(R, Vec<protocol::Modify>, Vec<(Vec<u8>, u64)>), String>,
+    ) -> Result<R, String> {
+        let data = self.write_data()?;
+        self.write_planned_locked(data, plan)
+    }
+
+    fn write_planned_locked<R>(
+        &self,
+        mut data: RwLockWriteGuard<'_, StorageData>,
+        plan: impl FnOnce(&StorageData) -> Result<(R, Vec<protocol::Modify>, Vec<(Vec<u8>, u64)>), String>,
+    ) -> Result<R, String> {
+        // 先删除已过期的键，plan 读到的数据中不包含它们
+        self.remove_expired(&mut data);
+        let (result, batch, expiries) = plan(&data)?;
+        data.check_quotas(&batch)?;
+
+        // 超出内存预算时只拒绝会增加占用的批次，删除和缩小值总是允许
+        if let Some(max) = self.max_memory_bytes
+            && self.eviction == EvictionPolicy::None
+        {
+            let delta = data.memory_delta(&batch);
+            let used = data.totals.memory_bytes();
+            if delta > 0 && used + delta as usize > max {
+                return Err(format!(
+                    "OutOfMemoryBudget: write needs {} bytes, {} of {} bytes in use",
+                    delta, used, max
+                ));
+            }
+        }
+
+        // 记录版本要读出被覆盖的旧值，先确认都能读取，读取失败时整个批次不生效
+        for modify in &batch {
+            if data.keep_versions(&modify.cf) > 0
+                && let Some(old) = data.entries.get(&EncodedKey::encode(&modify.cf, &modify.key).into_bytes())
+            {
+                old.try_get()?;
+            }
+        }
+
+        let modifications = batch.len() as u64;
+        // 只有注册了观察者时才需要保留批次
+        let observed = (!self.observers.is_empty() && !batch.is_empty()).then(|| batch.clone());
+        let now = self.clock.now_ms();
+        for modify in batch {
+            let prefixed_key = EncodedKey::encode(&modify.cf, &modify.key).into_bytes();
+            let keep = data.keep_versions(&modify.cf);
+
+            match modify.op {
+                protocol::ModifyOp::Put => {
+                    data.register_cf(&modify.cf, now);
+                    if keep > 0 {
+                        data.record_version(&prefixed_key, keep, now)?;
+                    }
+                    data.insert(prefixed_key, modify.value);
+                }
+                protocol::ModifyOp::Delete => {
+                    // 只有真正删除了值才记录墓碑版本
+                    if keep > 0 && data.entries.contains_key(&prefixed_key) {
+                        data.record_version(&prefixed_key, keep, now)?;
+                    }
+                    data.remove(&prefixed_key);
+                }
+            }
+        }
+        // 写入新值会清除过期时间，所以在批次之后设置
+        for (prefixed_key, at) in expiries {
+            data.set_expiry(&prefixed_key, Some(at));
+        }
+
+        match (self.max_memory_bytes, self.eviction) {
+            (Some(max), EvictionPolicy::Lru) => data.evict_until(max),
+            (Some(max), EvictionPolicy::Spill) => data.spill_until(max),
+            _ => {}
+        }
+
+        // 在写锁内计数，刷盘时读到的计数与快照内容一致
+        let dirty = match self.path {
+            Some(_) => self.dirty.fetch_add(modifications, Ordering::SeqCst) + modifications,
+            None => 0,
+        };
+        match observed {
+            Some(batch) => {
+                // 在写锁内取得序号和观察者锁，释放写锁后再通知，通知顺序与提交顺序一致
+                let seq = self.commit_seq.fetch_add(1, Ordering::SeqCst) + 1;
+                let observers = self.observers.lock();
+                drop(data);
+                observers.notify(seq, &batch, &self.errors);
+            }
+            None => drop(data),
+        }
+
+        if let Some(policy) = &self.flush_policy
+            && policy.high_water_mark.is_some_and(|mark| dirty > mark)
+        {
+            thread::sleep(policy.throttle);
+        }
+
+        Ok(result)
+    }
+
+    /// 按 flush_policy 启动后台刷盘线程；未配置策略或纯内存模式时返回 None
+    /// 线程只持有弱引用，存储被释放或句柄被丢弃后退出
+    pub fn start_flush_scheduler(self: &Arc<Self>) -> Option<FlushScheduler> {
+        let policy = self.flush_policy.clone()?;
+        self.path.as_ref()?;
+
+        let storage: Weak<Self> = Arc::downgrade(self);
+        let stop = Arc::new(AtomicBool::new(false));
+        let requested = Arc::new(AtomicBool::new(false));
+        let (thread_stop, thread_requested) = (Arc::clone(&stop), Arc::clone(&requested));
+
+        let thread = thread::spawn(move || {
+            let mut last_flush = Instant::now();
+            while !thread_stop.load(Ordering::SeqCst) {
+                thread::sleep(FLUSH_POLL_INTERVAL);
+                let Some(storage) = storage.upgrade() else {
+                    break;
+                };
+
+                let dirty = storage.dirty.load(Ordering::SeqCst);
+                let due = thread_requested.swap(false, Ordering::SeqCst)
+                    || policy.dirty_threshold.is_some_and(|threshold| dirty >= threshold)
+                    || storage.flush_interval().is_some_and(|interval| dirty > 0 && last_flush.elapsed() >= interval);
+                if due && storage.flush_retry_due() {
+                    if let Err(e) = storage.flush() {
+                        eprintln!("Background flush failed: {}", e);
+                    }
+                    last_flush = Instant::now();
+                }
+            }
+        });
+
+        Some(FlushScheduler {
+            stop,
+            requested,
+            thread: Some(thread),
+        })
+    }
+
+    /// 后台刷盘的间隔，没有配置 flush_policy 或策略中没有间隔时为 None
+    pub fn flush_interval(&self) -> Option<Duration> {
+        self.flush_interval.lock().map(|interval| *interval).unwrap_or(None)
+    }
+
+    /// 修改后台刷盘的间隔，刷盘线程下一次检查时生效；没有配置 flush_policy 时返回 NoFlushPolicy 错误
+    pub fn set_flush_interval(&self, interval: Option<Duration>) -> Result<(), String> {
+        if self.flush_policy.is_none() {
+            return Err("NoFlushPolicy: the server was started without a background flush policy".to_string());
+        }
+        *self.flush_interval.lock().map_err(|e| e.to_string())? = interval;
+        Ok(())
+    }
+
+    pub fn flush_info(&self) -> Result<FlushInfo, String> {
+        let mut info = self.last_flush.lock().map_err(|e| e.to_string())?.clone();
+        info.dirty = self.dirty.load(Ordering::SeqCst);
+        Ok(info)
+    }
+
+    /// 无效数据比例的估计值：磁盘上被覆盖或删除的键记录与内存中已过期的键
+    /// 占全部记录的比例，整理后回到 0
+    pub fn dead_ratio(&self) -> Result<f64, String> {
+        let data = self.lock_read()?;
+        let entries = data.entries.len() as u64;
+        let expired = data.expiry_index.range(..(self.clock.now_ms() + 1, Vec::new())).count() as u64;
+        let overwritten = self.stored_records.load(Ordering::SeqCst).saturating_sub(entries);
+        Ok(match overwritten + entries {
+            0 => 0.0,
+            total => (overwritten + expired) as f64 / total as f64,
+        })
+    }
+
+    pub fn compaction_info(&self) -> Result<CompactionInfo, String> {
+        let mut info = self.compaction.lock().map_err(|e| e.to_string())?.info.clone();
+        info.dead_ratio = self.dead_ratio()?;
+        Ok(info)
+    }
+
+    /// 按 compaction 策略启动后台整理线程；未配置策略或纯内存模式时返回 None
+    /// 同一时刻只有一个整理在进行，距上次整理不足 min_interval 时不触发
+    pub fn start_compaction_scheduler(self: &Arc<Self>) -> Option<Sweeper> {
+        let policy = self.compaction_policy.clone()?;
+        self.path.as_ref()?;
+
+        let storage: Weak<Self> = Arc::downgrade(self);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+
+        let thread = thread::spawn(move || {
+            while !thread_stop.load(Ordering::SeqCst) {
+                thread::sleep(COMPACTION_POLL_INTERVAL);
+                let Some(storage) = storage.upgrade() else {
+                    break;
+                };
+                match storage.compaction_due(&policy) {
+                    Ok(true) => {
+                        if let Err(e) = storage.compact_with(true, Deadline::NONE) {
+                            eprintln!("Background compaction failed: {}", e);
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        eprintln!("Background compaction check failed: {}", e);
+                        storage.errors.record(ErrorCategory::Compaction, format!("Compaction check failed: {}", e));
+                    }
+                }
+            }
+        });
+
+        Some(Sweeper {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn compaction_due(&self, policy: &CompactionPolicy) -> Result<bool, String> {
+        if !self.is_loaded() {
+            return Ok(false);
+        }
+        let last_started = self.compaction.lock().map_err(|e| e.to_string())?.last_started;
+        if last_started.is_some_and(|at| self.clock.now().monotonic.saturating_sub(at) < policy.min_interval) {
+            return Ok(false);
+        }
+        Ok(self.dead_ratio()? > policy.trigger_dead_ratio)
+    }
+
+    /// 最近的错误事件，见 errorlog::ErrorLog
+    pub fn error_log(&self) -> &Arc<ErrorLog> {
+        &self.errors
+    }
+
+    /// 存储使用的时钟，见 StorageOptions::clock
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// 正在进行或最近一次的刷盘、整理的进度，还没有进行过时为 None
+    pub fn maintenance_status(&self) -> Option<MaintenanceStatus> {
+        self.maintenance.status()
+    }
+
+    /// 延迟加载期间按 LoadingReads 等待或返回 Loading 错误
+    pub fn reader(&self) -> Result<Box<dyn StorageReader>, String> {
+        self.wait_loaded()?;
+        Ok(Box::new(StandaloneStorageReader {
+            data: Arc::clone(&self.data),
+            clock: Arc::clone(&self.clock),
+            locks: Arc::clone(&self.locks),
+        }))
+    }
+
+    pub fn flush(&self) -> Result<FlushStats, String> {
+        self.save_to_disk()
+    }
+
+    /// 规范化后的数据目录，纯内存模式返回 None
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn dir(&self) -> Result<&Path, String> {
+        self.path.as_deref().ok_or_else(|| "NoPersistencePath: storage is in-memory only".to_string())
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// 条目占用内存的近似值：键值长度之和加上每个条目的固定开销
+    pub fn memory_usage(&self) -> Result<usize, String> {
+        Ok(self.totals.memory_bytes())
+    }
+
+    /// 压缩的值的数量，以及压缩前后的近似内存占用
+    pub fn compression_stats(&self) -> Result<CompressionStats, String> {
+        Ok(CompressionStats {
+            compressed_values: self.totals.compressed_values.load(Ordering::Relaxed),
+            logical_bytes: self.totals.logical_bytes.load(Ordering::Relaxed),
+            physical_bytes: self.totals.memory_bytes(),
+        })
+    }
+
+    /// 磁盘层的统计，没有开启磁盘层时全为 0
+    /// 基础快照和段文件的统计；纯内存存储返回全零
+    pub fn wal_stats(&self) -> Result<LogFileStats, String> {
+        let log = self.log.lock().map_err(|e| e.to_string())?;
+        let files = log.manifest.base.iter().count() + log.manifest.segments.len();
+        let active_bytes = if log.manifest.segments.is_empty() { 0 } else { log.active_bytes.min(log.disk_bytes) };
+        Ok(LogFileStats {
+            name: "wal".to_string(),
+            active_bytes,
+            files,
+            total_bytes: log.disk_bytes,
+            rotations: log.rotations,
+            pruned_files: log.pruned_files,
+        })
+    }
+
+    pub fn spill_stats(&self) -> Result<SpillStats, String> {
+        let data = self.lock_read()?;
+        Ok(data.blobs.as_ref().map(|blobs| blobs.stats()).unwrap_or_default())
+    }
+
+    /// 因超出内存预算被淘汰的键数
+    pub fn evicted_keys(&self) -> Result<u64, String> {
+        let data = self.lock_read()?;
+        Ok(data.evicted_keys)
+    }
+
+    /// 整理存储：按当前列族选项裁剪版本历史，
+    /// 并丢弃已关闭版本记录的列族或已删除且无旧版本的键的历史。
+    /// 持久化模式下把全部数据合并成新的基础快照，替换清单后删除旧的快照和段文件
+    pub fn compact(&self) -> Result<CompactResult, String> {
+        self.compact_with(false, Deadline::NONE)
+    }
+
+    /// 同 compact，但等到其他刷盘或整理结束时已过 deadline 就放弃，返回 DeadlineExceeded 错误；
+    /// 整理一旦开始改写数据就会完成，不会留下一半的快照
+    pub fn compact_within(&self, deadline: Deadline) -> Result<CompactResult, String> {
+        self.compact_with(false, deadline)
+    }
+
+    /// 所有键和内存中的值的多余容量之和，用于观察整理的效果
+    pub fn capacity_slack(&self) -> Result<u64, String> {
+        Ok(self.lock_read()?.capacity_slack())
+    }
+
+    /// 整理并更新整理统计；automatic 表示由后台整理线程触发
+    fn compact_with(&self, automatic: bool, deadline: Deadline) -> Result<CompactResult, String> {
+        self.wait_loaded()?;
+        let mut log = self.log.lock().map_err(|e| e.to_string())?;
+        deadline.check("Compact")?;
+        let started = Instant::now();
+        let started_at_ms = self.clock.now_ms();
+        // 失败的整理同样计入间隔，避免后台线程反复重试
+        self.compaction.lock().map_err(|e| e.to_string())?.last_started = Some(self.clock.now().monotonic);
+        let disk_bytes = log.disk_bytes;
+
+        let mut result = self.compact_locked(&mut log, started, started_at_ms);
+        match &mut result {
+            Err(e) => self.errors.record(ErrorCategory::Compaction, e.clone()),
+            Ok(compacted) => {
+                compacted.reclaimed_disk_bytes = disk_bytes.saturating_sub(log.disk_bytes);
+                let mut state = self.compaction.lock().map_err(|e| e.to_string())?;
+                state.info.compactions += 1;
+                state.info.automatic += automatic as u64;
+                state.info.last_started_at_ms = started_at_ms;
+                state.info.last_duration_ms = started.elapsed().as_millis() as u64;
+                state.info.last_reclaimed_bytes = compacted.reclaimed_disk_bytes;
+                state.info.last_reclaimed_capacity_bytes = compacted.reclaimed_capacity_bytes;
+            }
         }
+        result
     }
 
-    pub fn open(path: &str) -> Result<Self, String> {
-        let storage = StandaloneStorage {
-            data: Arc::new(RwLock::new(BTreeMap::new())),
-            path: path.to_string(),
+    fn compact_locked(&self, log: &mut LogState, started: Instant, started_at_ms: u64) -> Result<CompactResult, String> {
+        let mut data = self.lock_write()?;
+        let mut compacted = CompactResult { dead_entries_removed: self.remove_expired(&mut data) as u64, ..CompactResult::default() };
+        let sparse = data.blobs.as_ref().map(|blobs| blobs.sparse_files()).unwrap_or_default();
+        data.rewrite_blobs(&sparse)?;
+        let StorageData { entries, history, cf_options, .. } = &mut *data;
+        self.maintenance.start(MaintenanceOperation::Compact, entries.len() + history.len(), started_at_ms);
+
+        let mut dropped_versions = 0;
+        history.retain(|key, h| {
+            let keep = cf_of(key)
+                .and_then(|cf| cf_options.get(cf))
+                .map_or(0, |o| o.keep_versions);
+            let kept = h.versions.len().min(keep);
+            dropped_versions += h.versions.len() - kept;
+            if keep == 0 {
+                return false;
+            }
+            h.versions.truncate(keep);
+            entries.contains_key(key) || !h.versions.is_empty()
+        });
+        compacted.dead_entries_removed += dropped_versions as u64;
+        (compacted.reclaimed_capacity_bytes, compacted.shrunk_buffers) = data.rebuild();
+
+        if self.path.is_none() {
+            self.maintenance.finish(&Ok(()));
+            return Ok(compacted);
+        }
+
+        let flushed_dirty = self.dirty.load(Ordering::SeqCst);
+        let snapshot = match data.snapshot(log.last_sequence, &self.maintenance) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                let result = Err(e);
+                self.maintenance.finish(&result);
+                return result;
+            }
         };
-        storage.load_from_disk()?;
-        Ok(storage)
+        let (dirty_keys, cf_options_dirty) = data.take_dirty();
+        drop(data);
+
+        self.maintenance.set_phase(MaintenancePhase::Writing);
+        let result = match self.write_base(log, &snapshot) {
+            Ok(bytes) => self.finish_flush(flushed_dirty, started, bytes).map(|_| ()),
+            Err(e) => {
+                self.lock_write()?.restore_dirty(dirty_keys, cf_options_dirty);
+                Err(e)
+            }
+        };
+        self.maintenance.finish(&result);
+        result.map(|_| compacted)
     }
 
-    pub fn write(&self, batch: Vec<common::Modify>) -> Result<(), String> {
-        let mut data = self.data.write().map_err(|e| e.to_string())?;
+    /// 把上次刷盘以来修改过的键追加到当前段文件，写入量只与修改量有关
+    /// 纯内存模式没有可写的目录，返回 NoPersistencePath 错误
+    pub fn save_to_disk(&self) -> Result<FlushStats, String> {
+        self.dir()?;
+        // 在取得 log 锁之前等待，加载线程装入数据时需要它
+        self.wait_loaded()?;
 
-        for modify in batch {
-            let prefixed_key = common::key_with_cf(&modify.cf, &modify.key);
+        let mut log = self.log.lock().map_err(|e| e.to_string())?;
+        let started = Instant::now();
+        let mut data = self.lock_write()?;
+        let flushed_dirty = self.dirty.load(Ordering::SeqCst);
+        let (dirty_keys, cf_options_dirty) = data.take_dirty();
+        self.maintenance.start(MaintenanceOperation::Flush, dirty_keys.len(), self.clock.now_ms());
+        let keys = dirty_keys
+            .iter()
+            .map(|k| {
+                self.maintenance.advance();
+                data.key_record(k)
+            })
+            .collect::<Result<Vec<_>, String>>();
+        let result = match keys {
+            // 有值读取失败时这次不写入，修改留到下次刷盘
+            Err(e) => {
+                data.restore_dirty(dirty_keys, cf_options_dirty);
+                drop(data);
+                Err(e)
+            }
+            Ok(keys) => {
+                let record = SegmentRecord {
+                    sequence: log.last_sequence + 1,
+                    keys,
+                    cf_options: cf_options_dirty.then(|| data.cf_options.clone()),
+                    cf_created: cf_options_dirty.then(|| data.cf_created.clone()),
+                    key_format: KEY_FORMAT,
+                };
+                drop(data);
 
-            match modify.op {
-                common::ModifyOp::Put => {
-                    data.insert(prefixed_key, modify.value);
-                }
-                common::ModifyOp::Delete => {
-                    data.remove(&prefixed_key);
+                self.maintenance.set_phase(MaintenancePhase::Writing);
+                if record.keys.is_empty() && record.cf_options.is_none() {
+                    self.finish_flush(flushed_dirty, started, 0)
+                } else {
+                    match self.append_record(&mut log, &record) {
+                        Ok(bytes) => self.finish_flush(flushed_dirty, started, bytes),
+                        Err(e) => {
+                            self.lock_write()?.restore_dirty(dirty_keys, cf_options_dirty);
+                            Err(e)
+                        }
+                    }
                 }
             }
+        };
+        if let Err(e) = &result {
+            self.errors.record(ErrorCategory::Flush, e.clone());
         }
+        self.update_persistence(&result)?;
+        self.maintenance.finish(&result);
+        result
+    }
 
+    /// 按刷盘结果进入或离开降级状态；失败时退避时间翻倍，首次进入降级状态时记入最近错误
+    fn update_persistence(&self, result: &Result<FlushStats, String>) -> Result<(), String> {
+        let mut state = self.persistence.lock().map_err(|e| e.to_string())?;
+        let now = self.clock.now_ms();
+        match result {
+            Ok(_) => {
+                if state.status.degraded {
+                    println!(
+                        "Persistence recovered after {} failed flushes",
+                        state.status.consecutive_failures
+                    );
+                }
+                *state = PersistenceState::default();
+            }
+            Err(e) => {
+                if !state.status.degraded {
+                    state.status.since_ms = now;
+                    state.backoff = FLUSH_RETRY_INITIAL;
+                    self.errors.record(ErrorCategory::Flush, format!("Persistence degraded: {}", e));
+                    eprintln!("Persistence degraded: {}", e);
+                } else {
+                    state.backoff = (state.backoff * 2).min(FLUSH_RETRY_MAX);
+                }
+                state.status.degraded = true;
+                state.status.disk_full = e.starts_with("DiskFull");
+                state.status.consecutive_failures += 1;
+                state.status.last_error = Some(e.clone());
+                state.status.next_retry_at_ms = now.saturating_add(state.backoff.as_millis() as u64);
+            }
+        }
         Ok(())
     }
 
-    pub fn reader(&self) -> Result<Box<dyn StorageReader>, String> {
-        Ok(Box::new(StandaloneStorageReader {
-            data: Arc::clone(&self.data),
+    /// 持久化状态，见 PersistenceStatus
+    pub fn persistence_status(&self) -> PersistenceStatus {
+        self.persistence.lock().map(|state| state.status.clone()).unwrap_or_default()
+    }
+
+    /// 没有处于降级状态，或已经到了下一次重试的时间
+    pub fn flush_retry_due(&self) -> bool {
+        let status = self.persistence_status();
+        !status.degraded || self.clock.now_ms() >= status.next_retry_at_ms
+    }
+
+    /// 启动降级状态下按退避时间重试刷盘的线程，线程只持有弱引用；纯内存模式时返回 None
+    pub fn start_flush_retrier(self: &Arc<Self>) -> Option<Sweeper> {
+        self.path.as_ref()?;
+        let storage: Weak<Self> = Arc::downgrade(self);
+        Some(Sweeper::every(FLUSH_RETRY_POLL_INTERVAL, move || {
+            let Some(storage) = storage.upgrade() else {
+                return;
+            };
+            if storage.persistence_status().degraded && storage.flush_retry_due() {
+                // 失败已经记入降级状态和最近错误
+                let _ = storage.flush();
+            }
         }))
     }
 
-    pub fn flush(&self) -> Result<(), String> {
-        self.save_to_disk()
+    fn finish_flush(&self, flushed_dirty: u64, started: Instant, bytes: u64) -> Result<FlushStats, String> {
+        self.dirty.fetch_sub(flushed_dirty, Ordering::SeqCst);
+        let mut last_flush = self.last_flush.lock().map_err(|e| e.to_string())?;
+        last_flush.flushes += 1;
+        last_flush.last_duration_ms = started.elapsed().as_millis() as u64;
+        last_flush.last_bytes = bytes;
+
+        Ok(FlushStats {
+            bytes_written: bytes,
+            fsynced: self.durability == Durability::Fsync,
+        })
     }
 
-    pub fn save_to_disk(&self) -> Result<(), String> {
-        if self.path.is_empty() {
-            return Ok(());
+    /// 追加一行记录，返回写入的字节数；当前段文件达到 segment_max_bytes 或 segment_max_age 时先切换到新文件
+    fn append_record(&self, log: &mut LogState, record: &SegmentRecord) -> Result<u64, String> {
+        let dir = self.dir()?;
+        self.fs.create_dir_all(dir)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+        let mut line = serde_json::to_vec(record)
+            .map_err(|e| format!("Failed to serialize: {}", e))?;
+        line.push(b'\n');
+        let fsync = self.durability == Durability::Fsync;
+
+        let now = self.clock.now_ms();
+        let rotate = log.active_bytes == u64::MAX
+            || self.segment_rotation.should_rotate(log.active_bytes, log.active_started_ms, now);
+        if let Some(active) = log.manifest.segments.last()
+            && !rotate
+        {
+            if let Err(e) = self.fs.append_file(&dir.join(active), &line, fsync) {
+                // 文件末尾可能留下半行，之后不再向它追加
+                log.active_bytes = u64::MAX;
+                return Err(io_error("Failed to append to segment", e));
+            }
+            log.active_bytes += line.len() as u64;
+            log.disk_bytes += line.len() as u64;
+            log.last_sequence = record.sequence;
+            self.stored_records.fetch_add(record.keys.len() as u64, Ordering::SeqCst);
+            return Ok(line.len() as u64);
+        }
+
+        // 新段文件写完后才加入清单，替换清单前崩溃时它不会被重放
+        let mut manifest = log.manifest.clone();
+        let segment = manifest.next_name("segment", "log");
+        let segment_path = dir.join(&segment);
+        if let Err(e) = self.fs.write_file(&segment_path, &line, fsync) {
+            // 还没有加入清单，删除写了一半的文件以释放空间
+            let _ = self.fs.remove_file(&segment_path);
+            return Err(io_error("Failed to write segment", e));
+        }
+        manifest.segments.push(segment);
+        self.write_manifest(&mut log.manifest, manifest)?;
+        if log.active_bytes > 0 {
+            log.rotations += 1;
         }
+        log.active_bytes = line.len() as u64;
+        log.active_started_ms = now;
+        log.disk_bytes += line.len() as u64;
+        log.last_sequence = record.sequence;
+        self.stored_records.fetch_add(record.keys.len() as u64, Ordering::SeqCst);
+        Ok(line.len() as u64)
+    }
 
-        let data = self.data.read().map_err(|e| e.to_string())?;
-        
-        fs::create_dir_all(&self.path)
+    /// 写入新的基础快照并替换清单，然后删除不再被引用的文件；返回快照的字节数
+    fn write_base(&self, log: &mut LogState, snapshot: &Snapshot) -> Result<u64, String> {
+        let dir = self.dir()?;
+        self.fs.create_dir_all(dir)
             .map_err(|e| format!("Failed to create directory: {}", e))?;
 
-        let file_path = format!("{}/data.json", self.path);
-        let json = serde_json::to_string_pretty(&*data)
+        let mut manifest = log.manifest.clone();
+        let base = manifest.next_name("base", "json");
+        // 直接序列化到文件，不在内存中拼出整个 JSON
+        let mut bytes = 0;
+        let base_path = dir.join(&base);
+        let written = self.fs.write_file_with(&base_path, self.durability == Durability::Fsync, &mut |writer| {
+            let mut writer = Counting::new(writer);
+            serde_json::to_writer_pretty(&mut writer, snapshot)?;
+            bytes = writer.bytes;
+            Ok(())
+        });
+        if let Err(e) = written {
+            // 清单仍然指向之前的快照，删除写了一半的新快照
+            let _ = self.fs.remove_file(&base_path);
+            return Err(io_error("Failed to write snapshot", e));
+        }
+        manifest.base = Some(base);
+        manifest.segments.clear();
+        self.write_manifest(&mut log.manifest, manifest)?;
+        log.active_bytes = 0;
+        log.disk_bytes = bytes;
+        self.stored_records.store(snapshot.entries.len() as u64, Ordering::SeqCst);
+
+        log.pruned_files += self.remove_unreferenced(&log.manifest);
+        Ok(bytes)
+    }
+
+    /// 持久化新的清单，成功后替换内存中的 current
+    /// 除 Durability::None 外先写临时文件再原子重命名，崩溃时旧清单保持完整
+    fn write_manifest(&self, current: &mut Manifest, manifest: Manifest) -> Result<(), String> {
+        let dir = self.dir()?;
+        let json = serde_json::to_vec_pretty(&manifest)
             .map_err(|e| format!("Failed to serialize: {}", e))?;
-        
-        fs::write(&file_path, json)
-            .map_err(|e| format!("Failed to write file: {}", e))?;
 
+        let file_path = dir.join(MANIFEST_FILE);
+        let fsync = self.durability == Durability::Fsync;
+        if self.durability == Durability::None {
+            self.fs.write_file(&file_path, &json, false)
+                .map_err(|e| io_error("Failed to write file", e))?;
+        } else {
+            let tmp_path = dir.join(format!("{}.tmp", MANIFEST_FILE));
+            if let Err(e) = self.fs.write_file(&tmp_path, &json, fsync) {
+                let _ = self.fs.remove_file(&tmp_path);
+                return Err(io_error("Failed to write file", e));
+            }
+            self.fs.rename(&tmp_path, &file_path)
+                .map_err(|e| format!("Failed to rename file: {}", e))?;
+            if fsync {
+                self.fs.sync_dir(dir)
+                    .map_err(|e| format!("Failed to sync directory: {}", e))?;
+            }
+        }
+
+        *current = manifest;
         Ok(())
     }
 
-    pub fn load_from_disk(&self) -> Result<(), String> {
-        if self.path.is_empty() {
-            return Ok(());
+    /// 删除清单没有引用的快照和段文件，包括之前崩溃留下的文件；失败只打印日志
+    fn remove_unreferenced(&self, manifest: &Manifest) -> u64 {
+        let Some(Ok(dir)) = self.path.as_ref().map(fs::read_dir) else {
+            return 0;
+        };
+        let mut removed = 0;
+        for entry in dir.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let owned = name.starts_with("base-") || name.starts_with("segment-");
+            if !owned || manifest.references(&name) {
+                continue;
+            }
+            match self.fs.remove_file(&entry.path()) {
+                Ok(()) => removed += 1,
+                Err(e) => {
+                    eprintln!("Failed to remove {}: {}", name, e);
+                    self.errors.record(ErrorCategory::Compaction, format!("Failed to remove {}: {}", name, e));
+                }
+            }
         }
+        removed
+    }
 
-        let file_path = format!("{}/data.json", self.path);
-        if !Path::new(&file_path).exists() {
-            return Ok(());
-        }
+    /// 加载基础快照并按清单顺序重放段文件，返回恢复结果；没有可加载的数据时返回 None
+    ///
+    /// 段文件末尾的损坏记录是写到一半时崩溃留下的，跳过后之后的刷盘写入新的段文件；
+    /// 损坏记录之后还有记录时说明文件被破坏，除非开启 salvage 否则打开失败
+    fn load_from_disk(&self) -> Result<Option<RecoveryReport>, String> {
+        let Some(dir) = &self.path else {
+            return Ok(None);
+        };
+        let Some(manifest) = Self::read_manifest(dir)? else {
+            return Ok(None);
+        };
+        let loaded = self.replay(manifest, |_| {})?;
+        self.install(loaded).map(Some)
+    }
 
-        let json = fs::read_to_string(&file_path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-        
-        let data: BTreeMap<Vec<u8>, Vec<u8>> = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to deserialize: {}", e))?;
+    /// 丢弃内存中的数据，按磁盘上的快照和段文件重新加载，返回恢复结果
+    ///
+    /// 有未刷盘的写入时拒绝执行（UnflushedWrites），force 为 true 时丢弃这些写入。
+    /// 替换在写锁内一次完成，读取者看到的要么是旧数据要么是新数据；完成后通知观察者
+    /// WriteObserver::on_reload，以便它们重新同步
+    pub fn reload(&self, force: bool) -> Result<RecoveryReport, String> {
+        let dir = self.dir()?;
+        self.wait_loaded()?;
+        // 持有段文件状态锁，重放期间刷盘和整理不能改动磁盘上的文件
+        let mut log = self.log.lock().map_err(|e| e.to_string())?;
+        self.check_unflushed(force)?;
+        let manifest = Self::read_manifest(dir)?.unwrap_or_default();
+        let loaded = self.replay(manifest, |_| {})?;
 
-        let mut storage_data = self.data.write().map_err(|e| e.to_string())?;
-        *storage_data = data;
+        let mut data = self.lock_write()?;
+        // 重放期间可能有新的写入
+        self.check_unflushed(force)?;
+        self.dirty.store(0, Ordering::SeqCst);
+        let report = self.install_locked(&mut log, &mut data, loaded)?;
+        let seq = self.commit_seq.load(Ordering::SeqCst);
+        let observers = self.observers.lock();
+        drop(data);
+        drop(log);
+        observers.notify_reload(seq, &self.errors);
+        Ok(report)
+    }
 
+    fn check_unflushed(&self, force: bool) -> Result<(), String> {
+        let dirty = self.dirty.load(Ordering::SeqCst);
+        if dirty > 0 && !force {
+            return Err(format!("UnflushedWrites: {} writes have not been flushed, flush first or force the reload", dirty));
+        }
         Ok(())
     }
 
+    /// 把整个存储写成一个自描述的备份文件：文件头（格式版本、列族选项），每个键一行（值、历史版本、
+    /// 校验和、过期时间），最后一行是之前所有内容的 CRC32；返回写入的键数
+    pub fn backup_to(&self, mut writer: impl Write) -> Result<usize, String> {
+        self.wait_loaded()?;
+        // 在读锁内收集，写出时不阻塞写入
+        let (header, records) = {
+            let data = self.lock_read()?;
+            let keys: BTreeSet<&Vec<u8>> = data.entries.keys().chain(data.history.keys()).collect();
+            let header = ArchiveHeader {
+                format: ARCHIVE_FORMAT.to_string(),
+                version: ARCHIVE_VERSION,
+                key_format: KEY_FORMAT,
+                created_at_ms: self.clock.now_ms(),
+                cf_options: data.cf_options.clone(),
+                cf_created: data.cf_created.clone(),
+            };
+            let records = keys.into_iter().map(|k| data.key_record(k)).collect::<Result<Vec<KeyRecord>, String>>()?;
+            (header, records)
+        };
+
+        let count = records.len();
+        let mut crc = 0;
+        write_archive_line(&mut writer, &mut crc, &ArchiveRecord::Header(header))?;
+        for record in records {
+            write_archive_line(&mut writer, &mut crc, &ArchiveRecord::Key(record))?;
+        }
+        let end = ArchiveRecord::End { records: count as u64, crc32: crc };
+        write_archive_line(&mut writer, &mut crc, &end)?;
+        writer.flush().map_err(|e| format!("Failed to write archive: {}", e))?;
+        Ok(count)
+    }
+
+    /// 用 backup_to 写出的备份文件替换存储的全部内容，返回恢复的键数。备份先完整解析、校验到一旁，
+    /// 再在写锁内一次性换入，读者看不到一半的状态；持久化存储同时写入新的基础快照。
+    /// 存储中已有数据时返回 StoreNotEmpty 错误，除非 force
+    pub fn restore_from(&self, reader: impl Read, force: bool) -> Result<usize, String> {
+        self.wait_loaded()?;
+        let mut state = read_archive(reader)?;
+        state.drop_expired(self.clock.now_ms());
+        let restored = state.entries.len();
+
+        let mut log = self.log.lock().map_err(|e| e.to_string())?;
+        let mut data = self.lock_write()?;
+        let existing = data.entries.keys().filter(|k| cf_of(k).is_some_and(|cf| !is_internal_cf(cf))).count();
+        if existing > 0 && !force {
+            return Err(format!("StoreNotEmpty: {} keys would be replaced, force the restore to overwrite them", existing));
+        }
+        if self.path.is_some() {
+            // 先写新的基础快照，失败时内存和磁盘上的数据都保持原样
+            let snapshot = state.to_snapshot(log.last_sequence);
+            self.write_base(&mut log, &snapshot)?;
+        }
+
+        self.dirty.store(0, Ordering::SeqCst);
+        let loaded = LoadedState {
+            manifest: log.manifest.clone(),
+            state,
+            report: RecoveryReport { snapshot_entries: restored, last_sequence: log.last_sequence, ..RecoveryReport::default() },
+            expired: Vec::new(),
+            active_bytes: log.active_bytes,
+            disk_bytes: log.disk_bytes,
+            stored_records: self.stored_records.load(Ordering::SeqCst),
+        };
+        self.install_locked(&mut log, &mut data, loaded)?;
+        let seq = self.commit_seq.load(Ordering::SeqCst);
+        let observers = self.observers.lock();
+        drop(data);
+        drop(log);
+        observers.notify_reload(seq, &self.errors);
+        Ok(restored)
+    }
+
+    // 读取清单，没有清单时返回 None
+    fn read_manifest(dir: &Path) -> Result<Option<Manifest>, String> {
+        let manifest_path = dir.join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("Failed to read manifest: {}", e))?;
+        let manifest = serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to parse manifest: {}", e))?;
+        Ok(Some(manifest))
+    }
+
+    // 读取并重放清单中的快照和段文件，不持有任何锁；每读完一个文件以其字节数调用 progress
+    fn replay(&self, manifest: Manifest, mut progress: impl FnMut(u64)) -> Result<LoadedState, String> {
+        let dir = self.dir()?;
+        let mut report = RecoveryReport { migrations: self.migrations.clone(), ..RecoveryReport::default() };
+        let (mut disk_bytes, mut stored_records) = (0, 0);
+        let mut state = match &manifest.base {
+            Some(base) => {
+                // 边读边解析，不先把整个文件读入内存
+                let reader = self.fs.open_reader(&dir.join(base))
+                    .map_err(|e| format!("Failed to read file: {}", e))?;
+                let mut reader = Counting::new(reader);
+                let snapshot: Snapshot = serde_json::from_reader(&mut reader)
+                    .map_err(|e| format!("Failed to deserialize: {}", e))?;
+                disk_bytes = reader.bytes;
+                progress(reader.bytes);
+                report.snapshot_entries = snapshot.entries.len();
+                stored_records = snapshot.entries.len() as u64;
+                report.last_sequence = snapshot.last_sequence;
+                ReplayState::from_snapshot(snapshot)
+            }
+            None => ReplayState::default(),
+        };
+
+        let mut active_bytes = 0;
+        for segment in &manifest.segments {
+            let bytes = self.fs.read_file(&dir.join(segment))
+                .map_err(|e| format!("Failed to read segment {}: {}", segment, e))?;
+            active_bytes = bytes.len() as u64;
+            disk_bytes += bytes.len() as u64;
+            progress(bytes.len() as u64);
+            let lines: Vec<&[u8]> = bytes.split_inclusive(|b| *b == b'\n').collect();
+            for (index, line) in lines.iter().enumerate() {
+                let parsed = line
+                    .strip_suffix(b"\n")
+                    .ok_or_else(|| "incomplete record".to_string())
+                    .and_then(|l| serde_json::from_slice::<SegmentRecord>(l).map_err(|e| e.to_string()));
+                let record = match parsed {
+                    Ok(record) => record,
+                    Err(e) => {
+                        if index + 1 < lines.len() && !self.salvage {
+                            return Err(format!(
+                                "Corrupt record {} of {} in segment {}: {} (open with salvage to skip it)",
+                                index + 1,
+                                lines.len(),
+                                segment,
+                                e
+                            ));
+                        }
+                        eprintln!("Recovery: skipping corrupt record {} in segment {}: {}", index + 1, segment, e);
+                        self.errors.record(
+                            ErrorCategory::Recovery,
+                            format!("Skipped corrupt record {} in segment {}: {}", index + 1, segment, e),
+                        );
+                        report.corrupt_records_skipped += 1;
+                        // 不再向带有损坏记录的文件追加
+                        active_bytes = u64::MAX;
+                        continue;
+                    }
+                };
+                report.wal_records_replayed += 1;
+                stored_records += record.keys.len() as u64;
+                report.last_sequence = report.last_sequence.max(record.sequence);
+                state.apply(record);
+            }
+        }
+
+        // 丢弃加载时已经过期的键
+        let (expired, dropped) = state.drop_expired(self.clock.now_ms());
+        report.expired_entries_dropped += dropped;
+
+        Ok(LoadedState { manifest, state, report, expired, active_bytes, disk_bytes, stored_records })
+    }
+
+    // 把重放的数据装入内存，替换其中的全部内容
+    fn install(&self, loaded: LoadedState) -> Result<RecoveryReport, String> {
+        let mut log = self.log.lock().map_err(|e| e.to_string())?;
+        let mut storage_data = self.lock_write()?;
+        self.install_locked(&mut log, &mut storage_data, loaded)
+    }
+
+    // 用加载的状态替换内存中的数据，调用方持有段文件状态锁和写锁
+    fn install_locked(&self, log: &mut LogState, storage_data: &mut StorageData, loaded: LoadedState) -> Result<RecoveryReport, String> {
+        let LoadedState { manifest, state, report, expired, active_bytes, disk_bytes, stored_records } = loaded;
+        log.manifest = manifest;
+        log.active_bytes = active_bytes;
+        log.disk_bytes = disk_bytes;
+        log.last_sequence = report.last_sequence;
+        log.active_started_ms = self.clock.now_ms();
+        self.stored_records.store(stored_records, Ordering::SeqCst);
+
+        // 重新加载时丢弃旧的过期时间和未刷盘的键，其余索引在 rebuild_accounting 中重建
+        storage_data.expirations.clear();
+        storage_data.expiry_index.clear();
+        if let Some(dirty_keys) = &mut storage_data.dirty_keys {
+            dirty_keys.clear();
+        }
+        storage_data.cf_options_dirty = false;
+        let entries = state.entries.into_iter().map(|(k, v)| (k, storage_data.store_value(v))).collect();
+        storage_data.entries = entries;
+        storage_data.history = state.history;
+        storage_data.cf_options = state.cf_options;
+        storage_data.cf_created = state.cf_created;
+        // 旧数据没有记录列族列表，按现有的键补齐
+        let cfs: BTreeSet<String> = storage_data.entries.keys().filter_map(|k| cf_of(k)).map(str::to_string).collect();
+        for cf in cfs {
+            if !cf.is_empty() {
+                storage_data.register_cf(&cf, 0);
+            }
+        }
+        storage_data.rebuild_accounting()?;
+        for (key, at) in state.expirations {
+            storage_data.set_expiry(&key, Some(at));
+        }
+        // 丢弃的键在下次刷盘时记为删除
+        for key in &expired {
+            storage_data.mark_dirty(key);
+        }
+        self.dirty.fetch_add(expired.len() as u64, Ordering::SeqCst);
+
+        if storage_data.checksums.is_some() {
+            // 没有保存校验和的条目（例如刚开启校验）按当前值补齐
+            let mut checksums = state.checksums;
+            checksums.retain(|k, _| storage_data.entries.contains_key(k));
+            for (k, v) in &storage_data.entries {
+                if !checksums.contains_key(k) {
+                    checksums.insert(k.clone(), crc32(&v.try_get()?));
+                }
+            }
+            storage_data.checksums = Some(checksums);
+        }
+
+        Ok(report)
+    }
+
     pub fn get_stats(&self) -> Result<(usize, Vec<String>), String> {
-        let data = self.data.read().map_err(|e| e.to_string())?;
-        
-        let mut cfs = std::collections::HashSet::new();
-        for key in data.keys() {
-            if let Some(sep_pos) = key.iter().position(|&b| b == b'_') {
-                if let Ok(cf) = std::str::from_utf8(&key[..sep_pos]) {
-                    cfs.insert(cf.to_string());
+        let cf_list = self.cf_usage()?.into_iter().map(|(cf, _)| cf).collect();
+        Ok((self.total_keys(), cf_list))
+    }
+
+    /// 所有列族的键数，不需要加锁
+    pub fn total_keys(&self) -> usize {
+        self.totals.keys.load(Ordering::Relaxed)
+    }
+
+    /// 按列族统计键数量，按列族名排序
+    pub fn cf_stats(&self) -> Result<Vec<(String, usize)>, String> {
+        Ok(self.cf_usage()?.into_iter().map(|(cf, usage)| (cf, usage.keys)).collect())
+    }
+
+    /// 按列族统计的用量，按列族名排序；没有键的列族不列出
+    pub fn cf_usage(&self) -> Result<Vec<(String, CfUsage)>, String> {
+        let data = self.lock_read()?;
+        let mut usage: Vec<(String, CfUsage)> = data.cf_usage.iter().map(|(cf, u)| (cf.clone(), *u)).collect();
+        usage.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(usage)
+    }
+
+    /// 列族当前的选项，没有设置过时为默认值
+    pub fn cf_options(&self, cf: &str) -> Result<CfOptions, String> {
+        let data = self.lock_read()?;
+        Ok(data.cf_options.get(cf).cloned().unwrap_or_default())
+    }
+
+    /// 修改列族配额，其他选项保持不变；调低到用量以下时不删除数据，只拒绝继续增长
+    pub fn set_cf_quota(&self, cf: &str, max_keys: Option<usize>, max_bytes: Option<usize>) -> Result<(), String> {
+        let mut data = self.write_data()?;
+        let options = data.cf_options.entry(cf.to_string()).or_default();
+        options.max_keys = max_keys;
+        options.max_bytes = max_bytes;
+        data.cf_options_dirty = true;
+        Ok(())
+    }
+
+    /// 删除所有编码后以 prefix 开头的键（连同其版本历史），返回删除的键数
+    pub fn delete_prefix(&self, prefix: &[u8]) -> Result<usize, String> {
+        self.delete_prefix_with(prefix, false)
+    }
+
+    /// 与 delete_prefix 相同，但先把删除的条目移到回收站
+    pub fn trash_prefix(&self, prefix: &[u8]) -> Result<usize, String> {
+        self.delete_prefix_with(prefix, true)
+    }
+
+    /// delete_prefix 和 trash_prefix 的试运行，不修改任何数据
+    pub fn delete_prefix_report(&self, prefix: &[u8]) -> Result<DeletionReport, String> {
+        let data = self.read_data()?;
+        let mut report = DeletionReport::default();
+        for (encoded, value) in entries_with_prefix(&data.entries, prefix) {
+            let (cf, key) = EncodedKey::decode_bytes(encoded).ok_or("Invalid encoded key")?;
+            report.add(cf.to_string(), key.to_vec(), entry_size(encoded, value.len()));
+        }
+        Ok(report)
+    }
+
+    fn delete_prefix_with(&self, prefix: &[u8], trash: bool) -> Result<usize, String> {
+        let mut data = self.write_data()?;
+
+        if trash {
+            let now = self.clock.now_ms();
+            let trashed: Vec<(Vec<u8>, Vec<u8>)> = entries_with_prefix(&data.entries, prefix)
+                .filter_map(|(k, v)| {
+                    let (cf, key) = EncodedKey::decode_bytes(k)?;
+                    Some(v.try_to_vec().map(|v| (EncodedKey::encode(TRASH_CF, &trash_key(cf, key, now)).into_bytes(), v)))
+                })
+                .collect::<Result<_, String>>()?;
+            for (encoded, value) in trashed {
+                data.insert(encoded, value);
+            }
+        }
+
+        let removed: Vec<Vec<u8>> = data
+            .entries
+            .keys()
+            .chain(data.history.keys())
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect();
+        for key in &removed {
+            data.mark_dirty(key);
+        }
+
+        let before = data.entries.len();
+        data.entries.retain(|k, _| !k.starts_with(prefix));
+        data.history.retain(|k, _| !k.starts_with(prefix));
+        if let Some(checksums) = &mut data.checksums {
+            checksums.retain(|k, _| !k.starts_with(prefix));
+        }
+        let cfs_before = data.cf_created.len();
+        data.cf_created.retain(|cf, _| !EncodedKey::cf_prefix(cf).into_bytes().starts_with(prefix));
+        data.cf_options_dirty |= data.cf_created.len() != cfs_before;
+        data.rebuild_accounting()?;
+
+        Ok(before - data.entries.len())
+    }
+
+    /// 校验所有条目（或指定列族的条目），返回值与校验和不一致的 (列族, 键)
+    pub fn verify(&self, cf: Option<&str>) -> Result<CfKeys, String> {
+        let data = self.read_data()?;
+        let corrupt = corrupt_keys(&data, cf)?;
+        Ok(corrupt.iter().map(|k| split_key(k, cf)).collect())
+    }
+
+    /// 删除损坏的条目，quarantine 为 true 时把它们移到 QUARANTINE_CF
+    pub fn repair(&self, quarantine: bool) -> Result<CfKeys, String> {
+        let mut data = self.write_data()?;
+        let corrupt = corrupt_keys(&data, None)?;
+
+        let mut repaired = Vec::with_capacity(corrupt.len());
+        for prefixed_key in corrupt {
+            // 读不出来的值无法隔离，只删除
+            if let Some(value) = data.remove(&prefixed_key)
+                && quarantine
+                && let Ok(value) = value.try_to_vec()
+            {
+                data.insert(EncodedKey::encode(QUARANTINE_CF, &prefixed_key).into_bytes(), value);
+            }
+            repaired.push(split_key(&prefixed_key, None));
+        }
+        Ok(repaired)
+    }
+
+    /// 按 checks 检查 cf（None 时为所有非内部列族）中从 start（包含）开始的条目，找到 limit 个违规条目
+    /// 或检查了 max_examined 个条目时停止；过期的条目计入检查数，但不检查
+    pub fn find_invalid(
+        &self,
+        cf: Option<&str>,
+        start: Option<(&str, &[u8])>,
+        checks: &[protocol::CheckKind],
+        limit: usize,
+        max_examined: usize,
+    ) -> Result<InvalidScan, String> {
+        let mut scan = InvalidScan::default();
+        let Some(range) = audit_range(cf, start) else {
+            return Ok(scan);
+        };
+        let data = self.read_data()?;
+        let now = self.clock.now_ms();
+
+        for (prefixed_key, value) in data.entries.range::<Vec<u8>, _>(range) {
+            let Some((entry_cf, key)) = EncodedKey::decode_bytes(prefixed_key) else {
+                continue;
+            };
+            if cf.is_none() && is_internal_cf(entry_cf) {
+                continue;
+            }
+            if scan.violations.len() >= limit || scan.examined >= max_examined {
+                scan.examined_limit_reached = scan.violations.len() < limit;
+                scan.next = Some((entry_cf.to_string(), key.to_vec()));
+                break;
+            }
+            scan.examined += 1;
+            if data.is_expired(prefixed_key, now) {
+                continue;
+            }
+            scan.violations.extend(find_violation(entry_cf, key, &value.try_get()?, checks));
+        }
+        Ok(scan)
+    }
+
+    /// 按 policy 处理 cf（None 时为所有非内部列族）中所有违反 checks 的条目；
+    /// Truncate 只能修复值过长的条目，截断后保留原来的过期时间
+    pub fn fix_invalid(
+        &self,
+        cf: Option<&str>,
+        checks: &[protocol::CheckKind],
+        policy: protocol::FixPolicy,
+    ) -> Result<protocol::FixReport, String> {
+        let mut report = protocol::FixReport::default();
+        let Some(range) = audit_range(cf, None) else {
+            return Ok(report);
+        };
+        let mut data = self.write_data()?;
+        let now = self.clock.now_ms();
+
+        let found: Vec<(Vec<u8>, protocol::Violation)> = data
+            .entries
+            .range::<Vec<u8>, _>(range)
+            .filter(|(prefixed_key, _)| !data.is_expired(prefixed_key, now))
+            .filter_map(|(prefixed_key, value)| {
+                let (entry_cf, key) = EncodedKey::decode_bytes(prefixed_key)?;
+                if cf.is_none() && is_internal_cf(entry_cf) {
+                    return None;
+                }
+                let value = match value.try_get() {
+                    Ok(value) => value,
+                    Err(e) => return Some(Err(e)),
+                };
+                find_violation(entry_cf, key, &value, checks).map(|violation| Ok((prefixed_key.clone(), violation)))
+            })
+            .collect::<Result<_, String>>()?;
+
+        for (prefixed_key, violation) in found {
+            match policy {
+                protocol::FixPolicy::Delete => {
+                    data.remove(&prefixed_key);
+                }
+                protocol::FixPolicy::Quarantine => {
+                    if let Some(value) = data.remove(&prefixed_key) {
+                        data.insert(EncodedKey::encode(QUARANTINE_CF, &prefixed_key).into_bytes(), value.try_to_vec()?);
+                    }
+                }
+                protocol::FixPolicy::Truncate => {
+                    let max_lens: Option<Vec<usize>> = violation
+                        .failed
+                        .iter()
+                        .map(|check| match check {
+                            protocol::CheckKind::MaxValueLen(n) => Some(*n),
+                            _ => None,
+                        })
+                        .collect();
+                    let Some(max_len) = max_lens.and_then(|lens| lens.into_iter().min()) else {
+                        report.skipped.push(violation);
+                        continue;
+                    };
+                    let Some(mut value) = data.entries.get(&prefixed_key).map(StoredValue::try_to_vec).transpose()? else {
+                        continue;
+                    };
+                    value.truncate(max_len);
+                    let expires_at_ms = data.expirations.get(&prefixed_key).copied();
+                    data.insert(prefixed_key.clone(), value);
+                    data.set_expiry(&prefixed_key, expires_at_ms);
                 }
             }
+            report.fixed.push(violation);
+        }
+        Ok(report)
+    }
+}
+
+/// Audit 检查的编码键范围，指定列族时限定在该列族内；start 为起点（包含）
+fn audit_range(cf: Option<&str>, start: Option<(&str, &[u8])>) -> Option<EncodedRange> {
+    match cf {
+        Some(cf) => cf_key_range(cf, start.map_or(Bound::Unbounded, |(_, key)| Bound::Included(key)), Bound::Unbounded),
+        None => {
+            let start = start.map_or(Bound::Unbounded, |(cf, key)| Bound::Included(EncodedKey::encode(cf, key).into_bytes()));
+            Some((start, Bound::Unbounded))
         }
+    }
+}
+
+/// 条目没有通过的检查，全部通过时返回 None
+fn find_violation(cf: &str, key: &[u8], value: &[u8], checks: &[protocol::CheckKind]) -> Option<protocol::Violation> {
+    let failed: Vec<protocol::CheckKind> = checks.iter().filter(|check| check.fails(key, value)).copied().collect();
+    if failed.is_empty() {
+        return None;
+    }
+    Some(protocol::Violation { cf: cf.to_string(), key: protocol::Bytes(key.to_vec()), failed, value_len: value.len() })
+}
+
+/// 值与校验和不一致的条目（带前缀的键）
+fn corrupt_keys(data: &StorageData, cf: Option<&str>) -> Result<Vec<Vec<u8>>, String> {
+    if data.checksums.is_none() {
+        return Err("Checksums are not enabled".to_string());
+    }
 
-        let mut cf_list: Vec<String> = cfs.into_iter().collect();
-        cf_list.sort();
+    let prefix = cf.map(|cf| EncodedKey::cf_prefix(cf).into_bytes());
+    let mut corrupt = Vec::new();
+    for (k, v) in &data.entries {
+        if let Some(prefix) = &prefix
+            && !k.starts_with(prefix)
+        {
+            continue;
+        }
+        // 读不出来的值同样视为损坏
+        if !v.try_get().is_ok_and(|value| data.is_intact(k, &value)) {
+            corrupt.push(k.clone());
+        }
+    }
+    Ok(corrupt)
+}
 
-        Ok((data.len(), cf_list))
+/// 把带前缀的键拆成 (列族, 原始键)，已知列族时按它拆分
+fn split_key(prefixed_key: &[u8], cf: Option<&str>) -> (String, Vec<u8>) {
+    let split = match cf {
+        Some(cf) => EncodedKey::key_in_cf(cf, prefixed_key).map(|key| (cf, key)),
+        None => EncodedKey::decode_bytes(prefixed_key),
+    };
+    match split {
+        Some((cf, key)) => (cf.to_string(), key.to_vec()),
+        None => (String::new(), prefixed_key.to_vec()),
     }
 }
 
+/// 从带前缀的键中解析列族名
+fn cf_of(key: &[u8]) -> Option<&str> {
+    EncodedKey::decode_bytes(key).map(|(cf, _)| cf)
+}
+
+/// 扫描结果：(原始键, 值) 列表
+pub type KvPairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// StorageReader::scan_examined_cf 的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExaminedScan {
+    pub pairs: KvPairs,
+    /// 检查过的条目数，包括过期和不匹配过滤条件的条目
+    pub examined: usize,
+    /// 因检查数达到上限而停止时，下一个未检查的键（包含）
+    pub next: Option<Vec<u8>>,
+}
+
+/// StandaloneStorage::find_invalid 的一页结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InvalidScan {
+    pub violations: Vec<protocol::Violation>,
+    /// 检查过的条目数，包括过期的条目
+    pub examined: usize,
+    /// 提前结束时下一个未检查的 (列族, 键)（包含）
+    pub next: Option<(String, Vec<u8>)>,
+    /// 因检查数达到上限而在找到 limit 个违规条目之前结束
+    pub examined_limit_reached: bool,
+}
+
+/// (列族, 原始键) 列表
+pub type CfKeys = Vec<(String, Vec<u8>)>;
+
+/// 列族内的键值迭代器，产出 (原始键, 值)
+pub type CfIter<'a> = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a>;
+
 /// 存储读取器接口
 pub trait StorageReader {
     fn get_cf(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String>;
+    /// 按键顺序流式遍历列族中 [start_key, end_key) 范围内的条目
+    fn iter_cf<'a>(&'a self, cf: &str, start_key: &[u8], end_key: Option<&[u8]>) -> Result<CfIter<'a>, String>;
+    /// 扫描 [start_key, end_key) 范围内的条目；给定 filter 时只返回值匹配的条目，limit 按匹配条目计数
     fn scan_cf(
         &self,
         cf: &str,
         start_key: &[u8],
         end_key: Option<&[u8]>,
         limit: usize,
-    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String>;
+        filter: Option<&protocol::ValueFilter>,
+    ) -> Result<KvPairs, String> {
+        let end = end_key.map_or(Bound::Unbounded, Bound::Excluded);
+        self.scan_range_cf(cf, Bound::Included(start_key), end, limit, filter)
+    }
+    /// 与 scan_cf 相同，但两端可以是包含、排除或无界
+    fn scan_range_cf(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&protocol::ValueFilter>,
+    ) -> Result<KvPairs, String>;
+    /// 与 scan_range_cf 相同，但最多检查 max_examined 个条目（包括不匹配 filter 的），
+    /// 达到上限时停止并在 next 中返回下一个未检查的键
+    fn scan_examined_cf(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&protocol::ValueFilter>,
+        max_examined: usize,
+    ) -> Result<ExaminedScan, String>;
+    /// 读取指定版本的值，墓碑版本返回 None
+    fn get_version_cf(&self, cf: &str, key: &[u8], version: u64) -> Result<Option<Vec<u8>>, String>;
+    /// 从新到旧列出键的版本（包括当前版本）
+    fn history_cf(&self, cf: &str, key: &[u8], limit: usize) -> Result<Vec<protocol::Version>, String>;
+    /// 存在条目的列族，按编码后的键序排列
+    fn column_families(&self) -> Result<Vec<String>, String>;
+    /// 值等于 value 的键，按键顺序最多返回 limit 个；要求列族开启 index_values
+    fn find_by_value_cf(&self, cf: &str, value: &[u8], limit: usize) -> Result<Vec<Vec<u8>>, String>;
+}
+
+/// 编码键空间中的范围，可直接传给 BTreeMap::range
+type EncodedRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// 列族 cf 中 (start, end) 对应的编码键范围，范围为空时返回 None
+/// 无界的一端落在列族前缀的边界上，遍历不会进入其他列族的键空间
+fn cf_key_range(cf: &str, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Option<EncodedRange> {
+    let start = match start {
+        Bound::Included(k) => Bound::Included(EncodedKey::encode(cf, k).into_bytes()),
+        Bound::Excluded(k) => Bound::Excluded(EncodedKey::encode(cf, k).into_bytes()),
+        Bound::Unbounded => Bound::Included(EncodedKey::encode(cf, b"").into_bytes()),
+    };
+    let end = match end {
+        Bound::Included(k) => Bound::Included(EncodedKey::encode(cf, k).into_bytes()),
+        Bound::Excluded(k) => Bound::Excluded(EncodedKey::encode(cf, k).into_bytes()),
+        Bound::Unbounded => match EncodedKey::cf_end(cf) {
+            Some(end) => Bound::Excluded(end.into_bytes()),
+            None => Bound::Unbounded,
+        },
+    };
+
+    // 起点大于终点，或两端相等且不都包含时范围为空（BTreeMap::range 对部分这种范围会 panic）
+    let empty = match (&start, &end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e)) => s >= e,
+        _ => false,
+    };
+    if empty {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// 范围内出现不属于列族的键说明 cf_key_range 有误，记录日志并返回错误
+fn outside_cf(cf: &str, prefixed_key: &[u8]) -> String {
+    eprintln!(
+        "Invariant violation: key {} is outside column family {}",
+        protocol::display_bytes(prefixed_key),
+        cf
+    );
+    format!("Internal error: scan of column family {} reached a key outside it", cf)
+}
+
+/// 列族迭代器
+///
+/// 迭代器在整个生命周期内持有存储的读锁，因此看到的是一致的快照，
+/// 代价是在迭代器被丢弃之前写入会被阻塞。它每次只克隆当前条目，
+/// 内存占用与结果集大小无关；长时间的遍历应及时丢弃迭代器。
+struct CfIterator<'a> {
+    data: RwLockReadGuard<'a, StorageData>,
+    // 创建迭代器时的时间，在这之前过期的键被跳过
+    now: u64,
+    cf: String,
+    // 下一次查找的起点，每产出一个条目就推进到它之后
+    next_start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+}
+
+impl Iterator for CfIterator<'_> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = &self.data;
+        let (k, v) = data
+            .entries
+            .range::<Vec<u8>, _>((self.next_start.as_ref(), self.end.as_ref()))
+            .find(|(k, _)| !data.is_expired(k, self.now))?;
+        let Some(key) = EncodedKey::key_in_cf(&self.cf, k) else {
+            // 迭代器无法返回错误，在这里结束遍历
+            outside_cf(&self.cf, k);
+            return None;
+        };
+        let value = match v.try_to_vec() {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("Failed to read value of {}: {}", protocol::display_bytes(k), e);
+                return None;
+            }
+        };
+        self.next_start = Bound::Excluded(k.clone());
+        Some((key.to_vec(), value))
+    }
 }
 
 /// 独立存储读取器
 struct StandaloneStorageReader {
-    data: Arc<RwLock<BTreeMap<Vec<u8>, Vec<u8>>>>,
+    data: Arc<RwLock<StorageData>>,
+    clock: Arc<dyn Clock>,
+    locks: Arc<LockCounters>,
+}
+
+impl StandaloneStorageReader {
+    fn lock_read(&self) -> Result<RwLockReadGuard<'_, StorageData>, String> {
+        self.locks.read(&self.data)
+    }
 }
 
 impl StorageReader for StandaloneStorageReader {
     fn get_cf(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
-        let prefixed_key = common::key_with_cf(cf, key);
-        let data = self.data.read().map_err(|e| e.to_string())?;
-        Ok(data.get(&prefixed_key).cloned())
+        let prefixed_key = EncodedKey::encode(cf, key).into_bytes();
+        let data = self.lock_read()?;
+        if data.is_expired(&prefixed_key, self.clock.now_ms()) {
+            return Ok(None);
+        }
+        if let Some(lru) = &data.lru {
+            lru.touch(&prefixed_key);
+        }
+        data.get_checked(cf, key)
     }
 
-    fn scan_cf(
+    fn iter_cf<'a>(&'a self, cf: &str, start_key: &[u8], end_key: Option<&[u8]>) -> Result<CfIter<'a>, String> {
+        let data = self.lock_read()?;
+        let end_key = end_key.map_or(Bound::Unbounded, Bound::Excluded);
+        let Some((start, end)) = cf_key_range(cf, Bound::Included(start_key), end_key) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+
+        Ok(Box::new(CfIterator {
+            data,
+            now: self.clock.now_ms(),
+            cf: cf.to_string(),
+            next_start: start,
+            end,
+        }))
+    }
+
+    fn scan_range_cf(
         &self,
         cf: &str,
-        start_key: &[u8],
-        end_key: Option<&[u8]>,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
         limit: usize,
-    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String> {
-        let data = self.data.read().map_err(|e| e.to_string())?;
-        let prefixed_start = common::key_with_cf(cf, start_key);
-        let prefixed_end = end_key.map(|k| common::key_with_cf(cf, k));
-
-        let mut results = Vec::new();
-        
-        for (k, v) in data.iter() {
-            if k < &prefixed_start {
+        filter: Option<&protocol::ValueFilter>,
+    ) -> Result<KvPairs, String> {
+        Ok(self.scan_examined_cf(cf, start, end, limit, filter, usize::MAX)?.pairs)
+    }
+
+    fn scan_examined_cf(
+        &self,
+        cf: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        filter: Option<&protocol::ValueFilter>,
+        max_examined: usize,
+    ) -> Result<ExaminedScan, String> {
+        let mut scan = ExaminedScan::default();
+        let Some((start, end)) = cf_key_range(cf, start, end) else {
+            return Ok(scan);
+        };
+        let data = self.lock_read()?;
+        let now = self.clock.now_ms();
+
+        for (prefixed_key, value) in data.entries.range::<Vec<u8>, _>((start, end)) {
+            if scan.pairs.len() >= limit {
+                break;
+            }
+            let key = EncodedKey::key_in_cf(cf, prefixed_key).ok_or_else(|| outside_cf(cf, prefixed_key))?;
+            if scan.examined >= max_examined {
+                scan.next = Some(key.to_vec());
+                break;
+            }
+            // 过期的条目同样计入检查数
+            scan.examined += 1;
+            if data.is_expired(prefixed_key, now) {
                 continue;
             }
+            let value = value.try_get()?;
+            if filter.is_none_or(|f| f.matches(&value)) {
+                scan.pairs.push((key.to_vec(), value.into_owned()));
+            }
+        }
+        Ok(scan)
+    }
 
-            if let Some(ref end) = prefixed_end {
-                if k >= end {
-                    break;
-                }
+    fn get_version_cf(&self, cf: &str, key: &[u8], version: u64) -> Result<Option<Vec<u8>>, String> {
+        let prefixed_key = EncodedKey::encode(cf, key).into_bytes();
+        let data = self.lock_read()?;
+        if data.keep_versions(cf) == 0 {
+            return Err(format!("Versioning is not enabled for column family {}", cf));
+        }
+
+        let history = data.history.get(&prefixed_key);
+        if history.map_or(0, |h| h.current_version) == version {
+            return data.entries.get(&prefixed_key).map(StoredValue::try_to_vec).transpose();
+        }
+
+        history
+            .and_then(|h| h.versions.iter().find(|v| v.version == version))
+            .map(|v| v.value.clone().map(|b| b.0))
+            .ok_or_else(|| format!("Version {} not found", version))
+    }
+
+    fn history_cf(&self, cf: &str, key: &[u8], limit: usize) -> Result<Vec<protocol::Version>, String> {
+        let prefixed_key = EncodedKey::encode(cf, key).into_bytes();
+        let data = self.lock_read()?;
+        if data.keep_versions(cf) == 0 {
+            return Err(format!("Versioning is not enabled for column family {}", cf));
+        }
+
+        let mut versions = Vec::new();
+        let current = data.entries.get(&prefixed_key).map(StoredValue::try_to_vec).transpose()?.map(protocol::Bytes);
+        match data.history.get(&prefixed_key) {
+            Some(h) => {
+                versions.push(protocol::Version {
+                    version: h.current_version,
+                    timestamp_ms: h.current_timestamp_ms,
+                    value: current,
+                });
+                versions.extend(h.versions.iter().cloned());
             }
+            None if current.is_some() => {
+                versions.push(protocol::Version { version: 0, timestamp_ms: 0, value: current });
+            }
+            None => {}
+        }
 
-            if let Some(original_key) = common::strip_cf_prefix(cf, k) {
-                results.push((original_key.to_vec(), v.clone()));
-                
-                if results.len() >= limit {
-                    break;
-                }
+        versions.truncate(limit);
+        Ok(versions)
+    }
+
+    fn column_families(&self) -> Result<Vec<String>, String> {
+        let data = self.lock_read()?;
+
+        // 每个列族只查找一次：找到一个键后直接跳到该列族前缀的上界
+        let mut cfs = Vec::new();
+        let mut from: Bound<Vec<u8>> = Bound::Unbounded;
+        while let Some((key, _)) = data.entries.range::<Vec<u8>, _>((from.as_ref(), Bound::Unbounded)).next() {
+            let Some(cf) = cf_of(key) else {
+                break;
+            };
+            cfs.push(cf.to_string());
+            match EncodedKey::cf_end(cf) {
+                Some(end) => from = Bound::Included(end.into_bytes()),
+                None => break,
             }
         }
+        Ok(cfs)
+    }
 
-        Ok(results)
+    fn find_by_value_cf(&self, cf: &str, value: &[u8], limit: usize) -> Result<Vec<Vec<u8>>, String> {
+        let data = self.lock_read()?;
+        let Some(index) = data.value_index.get(cf) else {
+            return Err(format!("Value index is not enabled for column family {}", cf));
+        };
+        let now = self.clock.now_ms();
+        Ok(index
+            .get(value)
+            .map(|keys| {
+                keys.iter()
+                    .filter(|k| !data.is_expired(EncodedKey::encode(cf, k).as_bytes(), now))
+                    .take(limit)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,153 @@
+pub mod datagen;
+
+use crate::client::KvClient;
+use crate::server::{KvServer, ServerConfig, ServerHandle, ShutdownOptions};
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// 测试用的临时服务器：数据目录在系统临时目录下，监听随机端口
+/// 丢弃时关闭服务器并删除数据目录
+pub struct TestServer {
+    handle: Option<ServerHandle>,
+    addr: String,
+    dir: PathBuf,
+    config: ServerConfig,
+    client: KvClient,
+}
+
+impl TestServer {
+    /// 以默认配置启动服务器
+    pub fn start() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::start_with_config(ServerConfig::default())
+    }
+
+    /// 以指定配置启动服务器，config.data_path 会被替换为新建的临时目录
+    pub fn start_with_config(mut config: ServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let dir = std::env::temp_dir().join(format!(
+            "tinykv_test_{}_{}_{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::SeqCst),
+            nanos
+        ));
+        fs::create_dir_all(&dir)?;
+        config.data_path = Some(dir.clone());
+
+        let (handle, addr, client) = match Self::listen(&config) {
+            Ok(started) => started,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(e);
+            }
+        };
+        Ok(TestServer { handle: Some(handle), addr, dir, config, client })
+    }
+
+    fn listen(config: &ServerConfig) -> Result<(ServerHandle, String, KvClient), Box<dyn std::error::Error>> {
+        let handle = KvServer::with_config(config.clone())?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+        let client = KvClient::connect(&addr)?;
+        Ok((handle, addr, client))
+    }
+
+    /// 服务器监听的地址
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// 服务器的数据目录
+    pub fn data_path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 启动时建立的客户端连接
+    pub fn client(&mut self) -> &mut KvClient {
+        &mut self.client
+    }
+
+    /// 建立一个新的客户端连接
+    pub fn connect(&self) -> Result<KvClient, Box<dyn std::error::Error>> {
+        KvClient::connect(&self.addr)
+    }
+
+    /// 刷盘后关闭服务器，再用同一数据目录重新启动；地址会改变
+    pub fn restart(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(handle) = self.handle.take() {
+            handle.shutdown(ShutdownOptions { drain_timeout: Duration::from_secs(1), flush: true })?;
+        }
+        let (handle, addr, client) = Self::listen(&self.config)?;
+        self.handle = Some(handle);
+        self.addr = addr;
+        self.client = client;
+        Ok(())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.shutdown(ShutdownOptions { drain_timeout: Duration::from_millis(100), flush: false });
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// 测试用的临时目录，丢弃时连同内容一起删除
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// 在系统临时目录下新建空目录，目录名包含 name、进程号和序号，同名的多次调用得到不同的目录
+pub fn temp_dir(name: &str) -> TempDir {
+    let path = std::env::temp_dir().join(format!(
+        "tinykv_{}_{}_{}",
+        name,
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap_or_else(|e| panic!("create {}: {}", path.display(), e));
+    TempDir { path }
+}
+
+/// 轮询直到 done 返回 true，5 秒内没有满足时 panic
+pub fn wait_until(mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "condition not reached in time");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,79 @@
+//! 测试和基准测试共用的数据生成
+//!
+//! 键和值只由序号决定，随机数只由种子决定，同样的参数在任何机器上生成同样的数据，
+//! 校验时也不需要记录写入过什么。
+
+use crate::protocol::Modify;
+use crate::storage::StandaloneStorage;
+
+/// 第 i 个键，按序号排序与按字节排序一致
+pub fn key(i: u64) -> Vec<u8> {
+    format!("key{:010}", i).into_bytes()
+}
+
+/// 第 i 个键的值，长度为 size
+pub fn value(i: u64, size: usize) -> Vec<u8> {
+    let seed = format!("{}:", i).into_bytes();
+    seed.iter().copied().cycle().take(size).collect()
+}
+
+/// xorshift64，每个线程各自持有
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// [0, n) 中的一个数
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// count 个键 key(0)..key(count)，值为 value(i, value_size)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dataset {
+    pub count: u64,
+    pub value_size: usize,
+}
+
+impl Dataset {
+    pub fn new(count: u64, value_size: usize) -> Self {
+        Dataset { count, value_size }
+    }
+
+    /// 按键顺序写入整个数据集的 Put，每批最多 batch 个
+    pub fn batches(&self, cf: &str, batch: usize) -> Vec<Vec<Modify>> {
+        let batch = batch.max(1) as u64;
+        (0..self.count)
+            .step_by(batch as usize)
+            .map(|start| {
+                (start..(start + batch).min(self.count))
+                    .map(|i| Modify::new_put(cf.to_string(), key(i), value(i, self.value_size)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// 把整个数据集写入 storage 的 cf 列族
+    pub fn load_into(&self, storage: &StandaloneStorage, cf: &str) -> Result<(), String> {
+        for batch in self.batches(cf, 1000) {
+            storage.write(batch)?;
+        }
+        Ok(())
+    }
+
+    /// 从数据集中均匀抽取 n 个序号（可重复）
+    pub fn sample(&self, seed: u64, n: usize) -> Vec<u64> {
+        let mut rng = Rng::new(seed);
+        (0..n).map(|_| rng.below(self.count)).collect()
+    }
+}
@@ -0,0 +1,122 @@
+//! TLS 传输层（tls 特性）
+//!
+//! 服务器配置了证书链和私钥（ServerConfig::tls_cert_path / tls_key_path）后，接受的每个 TCP 连接
+//! 先完成 TLS 握手再交给命令处理；客户端用 KvClient::connect_tls 按给定的 CA 证书验证服务器。
+//! 分帧和命令处理与明文连接共用，只依赖 protocol::Transport。证书和私钥都是 PEM 文件。
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConnection, ConnectionCommon, RootCertStore, ServerConnection, SideData, StreamOwned};
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Arc;
+
+/// TCP 连接上的 TLS 流；释放时发送 close_notify，对端据此把随后的 EOF 当作正常关闭而不是截断
+pub struct TlsStream<C, D>(StreamOwned<C, TcpStream>)
+where
+    C: DerefMut + Deref<Target = ConnectionCommon<D>>,
+    D: SideData;
+
+/// 服务端的 TLS 流
+pub type ServerStream = TlsStream<ServerConnection, rustls::server::ServerConnectionData>;
+
+/// 客户端的 TLS 流
+pub type ClientStream = TlsStream<ClientConnection, rustls::client::ClientConnectionData>;
+
+impl<C, D> Read for TlsStream<C, D>
+where
+    C: DerefMut + Deref<Target = ConnectionCommon<D>>,
+    D: SideData,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<C, D> Write for TlsStream<C, D>
+where
+    C: DerefMut + Deref<Target = ConnectionCommon<D>>,
+    D: SideData,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<C, D> Drop for TlsStream<C, D>
+where
+    C: DerefMut + Deref<Target = ConnectionCommon<D>>,
+    D: SideData,
+{
+    fn drop(&mut self) {
+        self.0.conn.send_close_notify();
+        let _ = self.0.flush();
+    }
+}
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// 读取 PEM 文件中的全部证书，文件中没有证书时返回错误
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read certificates from {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// 由证书链和私钥构造服务端配置
+pub fn server_config(cert_path: &Path, key_path: &Path) -> Result<Arc<rustls::ServerConfig>, String> {
+    let certs = load_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Failed to read private key from {}: {}", key_path.display(), e))?;
+    let config = rustls::ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+/// 只信任 ca_cert_path 中证书的客户端配置
+pub fn client_config(ca_cert_path: &Path) -> Result<Arc<rustls::ClientConfig>, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_cert_path)? {
+        roots.add(cert).map_err(|e| format!("Invalid CA certificate in {}: {}", ca_cert_path.display(), e))?;
+    }
+    let config = rustls::ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// 校验并转换客户端期望的服务器名（域名或 IP 地址）
+pub fn server_name(name: &str) -> Result<ServerName<'static>, String> {
+    ServerName::try_from(name.to_string()).map_err(|e| format!("Invalid TLS server name '{}': {}", name, e))
+}
+
+/// 包装接受的连接，握手在第一次读写时进行
+pub fn accept(config: &Arc<rustls::ServerConfig>, stream: TcpStream) -> io::Result<ServerStream> {
+    let conn = ServerConnection::new(Arc::clone(config)).map_err(io::Error::other)?;
+    Ok(TlsStream(StreamOwned::new(conn, stream)))
+}
+
+/// 包装到服务器的连接，握手在第一次读写时进行
+pub fn connect(config: &Arc<rustls::ClientConfig>, name: &ServerName<'static>, stream: TcpStream) -> io::Result<ClientStream> {
+    let conn = ClientConnection::new(Arc::clone(config), name.clone()).map_err(io::Error::other)?;
+    Ok(TlsStream(StreamOwned::new(conn, stream)))
+}
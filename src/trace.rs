@@ -0,0 +1,111 @@
+//! 可选的 tracing 集成（tracing 特性）
+//!
+//! 开启后 handle_client 为每个连接打开一个 `connection` span（conn_id、peer_addr），连接上的每个命令
+//! 是它的 `command` 子 span，字段与访问日志一致（cmd、cf、key_len、status、code），另有
+//! lock_wait_us（等待存储数据锁的总时间）和 exec_us（执行命令的时间，包括等锁）。
+//! 没有开启特性时这里的类型不占空间，函数都是空操作。
+
+use crate::accesslog::{AccessRecord, Status};
+
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// 是否编译了 tracing 支持；关闭时调用方可以跳过只为 span 准备的计时
+pub const ENABLED: bool = cfg!(feature = "tracing");
+
+/// 连接或命令的 span
+#[derive(Debug, Clone)]
+pub struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+}
+
+/// 进入 span 的守卫，释放时退出
+pub struct Entered<'a> {
+    #[cfg(feature = "tracing")]
+    _guard: tracing::span::Entered<'a>,
+    _span: PhantomData<&'a Span>,
+}
+
+/// 连接的 span，conn_id 与 Clients 列表一致
+pub fn connection(conn_id: u64, peer_addr: &str) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        Span { inner: tracing::info_span!("connection", conn_id, peer_addr) }
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (conn_id, peer_addr);
+        Span {}
+    }
+}
+
+/// 命令的 span，作为当前 span（通常是连接）的子 span；其他字段在执行后由 record_access 填入
+pub fn command(kind: &'static str) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::field::Empty;
+        Span {
+            inner: tracing::info_span!(
+                "command",
+                cmd = kind,
+                cf = Empty,
+                key_len = Empty,
+                status = Empty,
+                code = Empty,
+                lock_wait_us = Empty,
+                exec_us = Empty
+            ),
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = kind;
+        Span {}
+    }
+}
+
+impl Span {
+    pub fn enter(&self) -> Entered<'_> {
+        Entered {
+            #[cfg(feature = "tracing")]
+            _guard: self.inner.enter(),
+            _span: PhantomData,
+        }
+    }
+
+    /// 按访问日志的记录填入命令的 cf、key_len、status 和 code
+    pub fn record_access(&self, record: &AccessRecord) {
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(cf) = &record.cf {
+                self.inner.record("cf", cf.as_str());
+            }
+            if let Some(key_len) = record.key_len {
+                self.inner.record("key_len", key_len);
+            }
+            let status = match record.status {
+                Status::Ok => "ok",
+                Status::Error => "error",
+            };
+            self.inner.record("status", status);
+            if let Some(code) = &record.code {
+                self.inner.record("code", code.as_str());
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = (record, Status::Ok);
+    }
+}
+
+/// 在当前的 command span 上记录存储的锁等待和执行时间；当前 span 没有这些字段时忽略
+pub fn record_storage(lock_wait: Duration, exec: Duration) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("lock_wait_us", lock_wait.as_micros() as u64);
+        span.record("exec_us", exec.as_micros() as u64);
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (lock_wait, exec);
+}
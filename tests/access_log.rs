@@ -0,0 +1,121 @@
+use tinykv_rs::accesslog::{AccessRecord, LogFormat, LogSink, Status};
+use tinykv_rs::protocol::{BatchMode, Bytes, Command, Modify, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 收集服务器写出的日志行
+    #[derive(Debug, Default)]
+    struct Captured(Mutex<Vec<String>>);
+
+    impl LogSink for Captured {
+        fn write_line(&self, line: &str) {
+            self.0.lock().unwrap().push(line.to_string());
+        }
+    }
+
+    impl Captured {
+        /// 访问日志在回复写完之后才输出，等到至少有 count 行
+        fn wait_for(&self, count: usize) -> Vec<String> {
+            let started = Instant::now();
+            loop {
+                let lines = self.0.lock().unwrap().clone();
+                if lines.len() >= count || started.elapsed() > Duration::from_secs(5) {
+                    return lines;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+    }
+
+    fn start(format: LogFormat) -> Result<(TestServer, Arc<Captured>), Box<dyn std::error::Error>> {
+        let captured = Arc::new(Captured::default());
+        let config = ServerConfig {
+            admin_token: Some("root".to_string()),
+            log_format: format,
+            log_sink: Some(Arc::clone(&captured) as Arc<dyn LogSink>),
+            ..ServerConfig::default()
+        };
+        Ok((TestServer::start_with_config(config)?, captured))
+    }
+
+    #[test]
+    fn test_text_lines_have_stable_fields() -> Result<(), Box<dyn std::error::Error>> {
+        let (server, captured) = start(LogFormat::Text)?;
+        // TestServer 自己的连接也会记录，用新连接以便按连接 ID 过滤
+        let mut client = server.connect()?;
+        client.put("users", "u1", "secret-value")?;
+        client.get("users", "u1")?;
+        assert!(client.shutdown(false).is_err());
+
+        let lines = captured.wait_for(5);
+        let conn = lines.iter().find_map(|l| l.contains("cmd=Put").then(|| l.split(' ').nth(2).unwrap().to_string())).unwrap();
+        let lines: Vec<&String> = lines.iter().filter(|l| l.split(' ').nth(2) == Some(conn.as_str())).collect();
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert!(lines.iter().all(|l| !l.contains("secret")), "{:?}", lines);
+
+        // 去掉随耗时变化的字段后逐行比较
+        let fixed: Vec<String> = lines.iter().map(|l| l.split(' ').filter(|f| !f.starts_with("duration_us=")).collect::<Vec<_>>().join(" ")).collect();
+        let resp_bytes = |line: &str| -> u64 { line.rsplit_once("resp_bytes=").unwrap().1.parse().unwrap() };
+        assert!(fixed[0].starts_with(&format!("INFO access {} seq=1 cmd=Hello cf=- key_len=- status=ok code=- resp_bytes=", conn)), "{}", fixed[0]);
+        assert_eq!(fixed[1], format!("INFO access {} seq=2 cmd=Put cf=users key_len=2 status=ok code=- resp_bytes={}", conn, resp_bytes(&fixed[1])));
+        assert_eq!(fixed[2], format!("INFO access {} seq=3 cmd=Get cf=users key_len=2 status=ok code=- resp_bytes={}", conn, resp_bytes(&fixed[2])));
+        // 值只计入回复的字节数
+        assert!(resp_bytes(&fixed[2]) > "secret-value".len() as u64);
+        assert_eq!(fixed[3], format!("INFO access {} seq=4 cmd=Shutdown cf=- key_len=- status=error code=- resp_bytes={}", conn, resp_bytes(&fixed[3])));
+        assert!(lines.iter().all(|l| l.contains(" duration_us=")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_lines_parse_as_records() -> Result<(), Box<dyn std::error::Error>> {
+        let (server, captured) = start(LogFormat::Json)?;
+        let mut client = server.connect()?;
+        client.put("cf a", "key", "secret-value")?;
+        client.admin_auth("root")?;
+        assert!(client.oplog_range(1, None).is_err());
+
+        let lines = captured.wait_for(5);
+        assert!(lines.iter().all(|l| l.starts_with(r#"{"level":"info","msg":"access","conn":"#)), "{:?}", lines);
+        assert!(lines.iter().all(|l| !l.contains("secret")), "{:?}", lines);
+        let records: Vec<AccessRecord> = lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let put = records.iter().find(|r| r.cmd == "Put").unwrap();
+        assert_eq!((put.cf.as_deref(), put.key_len, put.status, put.code.as_deref()), (Some("cf a"), Some(3), Status::Ok, None));
+        assert!(put.resp_bytes > 0);
+        let failed = records.iter().find(|r| r.conn == put.conn && r.status == Status::Error).unwrap();
+        assert_eq!((failed.seq, failed.cmd.as_str(), failed.code.as_deref()), (put.seq + 2, "OplogRange", Some("OplogDisabled")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_fields() {
+        let batch = Command::new_batch(
+            vec![
+                Modify::new_put("a".into(), b"k1".to_vec(), b"v".to_vec()),
+                Modify::new_delete("b".into(), b"key2".to_vec()),
+                Modify::new_put("a".into(), b"k3".to_vec(), b"v".to_vec()),
+            ],
+            BatchMode::Atomic,
+        );
+        let record = AccessRecord::new(3, 9, &batch, &Response::Error("QuotaExceeded: cf a".into()), Duration::from_micros(42), 100);
+        assert_eq!(
+            record.to_line(LogFormat::Text),
+            "INFO access conn=3 seq=9 cmd=Batch cf=a,b key_len=8 status=error code=QuotaExceeded duration_us=42 resp_bytes=100"
+        );
+        let info = AccessRecord::new(1, 1, &Command::Info, &Response::Value(Some(Bytes(b"x".to_vec()))), Duration::ZERO, 10);
+        assert_eq!(
+            info.to_line(LogFormat::Json),
+            r#"{"level":"info","msg":"access","conn":1,"seq":1,"cmd":"Info","cf":null,"key_len":null,"status":"ok","code":null,"duration_us":0,"resp_bytes":10}"#
+        );
+        let spaced = AccessRecord::new(1, 1, &Command::Get { cf: "my cf".into(), key: Vec::new() }, &Response::Ok, Duration::ZERO, 0);
+        assert!(spaced.to_line(LogFormat::Text).contains(r" cf=my\x20cf key_len=0 "));
+        assert!(LogFormat::parse("xml").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use tinykv_rs::acl::{self, CfPermission, Permission, Principal};
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{BatchMode, Command, Modify, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principals() -> Vec<Principal> {
+        vec![
+            Principal::new(
+                "orders-svc",
+                "orders-token",
+                vec![CfPermission::new("orders*", true, true, false), CfPermission::new("users", true, false, false)],
+            ),
+            Principal::new("ops", "ops-token", vec![CfPermission::new("*", true, false, true)]),
+        ]
+    }
+
+    fn api() -> RawKeyValueApi {
+        let config = ServerConfig { admin_token: Some("root".to_string()), principals: principals(), ..ServerConfig::default() };
+        RawKeyValueApi::with_config(Arc::new(storage::StandaloneStorage::in_memory()), Arc::new(config))
+    }
+
+    fn login(api: &RawKeyValueApi, name: &str, token: &str) -> Session {
+        let mut session = api.new_session();
+        let response = api.handle_command(&mut session, Command::Auth { name: name.to_string(), token: token.to_string() });
+        assert!(matches!(response, Response::Ok), "{:?}", response);
+        session
+    }
+
+    fn put(cf: &str) -> Command {
+        Command::Put { cf: cf.to_string(), key: b"k".to_vec(), value: b"v".to_vec() }
+    }
+
+    fn get(cf: &str) -> Command {
+        Command::Get { cf: cf.to_string(), key: b"k".to_vec() }
+    }
+
+    fn denied(response: Response) -> String {
+        match response {
+            Response::Error(e) if e.starts_with("PermissionDenied") => e,
+            other => panic!("expected PermissionDenied, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(acl::glob_match("*", ""));
+        assert!(acl::glob_match("*", "anything"));
+        assert!(acl::glob_match("orders", "orders"));
+        assert!(!acl::glob_match("orders", "orders2"));
+        assert!(acl::glob_match("orders*", "orders_2024"));
+        assert!(acl::glob_match("*_archive", "orders_archive"));
+        assert!(acl::glob_match("a*b*c", "a-b-b-c"));
+        assert!(!acl::glob_match("a*b*c", "a-b-b-d"));
+        assert!(acl::glob_match("user?", "users"));
+        assert!(!acl::glob_match("user?", "user"));
+
+        let principal = &principals()[0];
+        assert!(principal.allows("orders_eu", Permission::Write));
+        assert!(principal.allows("users", Permission::Read));
+        assert!(!principal.allows("users", Permission::Write));
+        assert!(!principal.allows("orders", Permission::Admin));
+    }
+
+    #[test]
+    fn test_unauthenticated_connections_are_denied_by_default() {
+        let api = api();
+        let mut session = api.new_session();
+        let e = denied(api.handle_command(&mut session, get("users")));
+        assert_eq!(e, "PermissionDenied: anonymous lacks read on cf users");
+        denied(api.handle_command(&mut session, Command::ScanAll { start: None, limit: 10 }));
+        // 不涉及列族的命令照常执行
+        assert!(matches!(api.handle_command(&mut session, Command::ListCfs { start_after: None, limit: 10 }), Response::CfList { .. }));
+
+        let wrong = api.handle_command(&mut session, Command::Auth { name: "ops".to_string(), token: "guess".to_string() });
+        assert!(matches!(wrong, Response::Error(e) if e.starts_with("AuthFailed")));
+
+        // 管理令牌不受列族权限限制
+        assert!(matches!(api.handle_command(&mut session, Command::AdminAuth { token: "root".to_string() }), Response::Ok));
+        assert!(matches!(api.handle_command(&mut session, put("anything")), Response::Ok));
+    }
+
+    #[test]
+    fn test_principal_permissions_per_cf() {
+        let api = api();
+        let mut session = login(&api, "orders-svc", "orders-token");
+        assert!(matches!(api.handle_command(&mut session, put("orders")), Response::Ok));
+        assert!(matches!(api.handle_command(&mut session, get("users")), Response::Value(None)));
+        let e = denied(api.handle_command(&mut session, put("users")));
+        assert_eq!(e, "PermissionDenied: orders-svc lacks write on cf users");
+        denied(api.handle_command(&mut session, get("billing")));
+        denied(api.handle_command(&mut session, Command::Flush));
+
+        // 默认列族替换后再检查
+        assert!(matches!(api.handle_command(&mut session, Command::UseCf { cf: "users".to_string() }), Response::Ok));
+        denied(api.handle_command(&mut session, put("")));
+
+        let mut ops = login(&api, "ops", "ops-token");
+        assert!(matches!(api.handle_command(&mut ops, Command::Flush), Response::Error(e) if !e.starts_with("PermissionDenied")));
+        assert!(matches!(api.handle_command(&mut ops, Command::ScanAll { start: None, limit: 10 }), Response::CfValues { .. }));
+        let e = denied(api.handle_command(&mut ops, put("orders")));
+        assert_eq!(e, "PermissionDenied: ops lacks write on cf orders");
+    }
+
+    #[test]
+    fn test_publish_requires_write_on_all_cfs() {
+        let api = api();
+        let publish = || Command::Publish { channel: "news".to_string(), payload: b"x".to_vec() };
+        let mut ops = login(&api, "ops", "ops-token");
+        let e = denied(api.handle_command(&mut ops, publish()));
+        assert_eq!(e, "PermissionDenied: ops lacks write on all column families");
+        let mut session = login(&api, "orders-svc", "orders-token");
+        denied(api.handle_command(&mut session, publish()));
+        denied(api.handle_command(&mut api.new_session(), publish()));
+
+        let mut admin = api.new_session();
+        assert!(matches!(api.handle_command(&mut admin, Command::AdminAuth { token: "root".to_string() }), Response::Ok));
+        assert!(matches!(api.handle_command(&mut admin, publish()), Response::Count(0)));
+    }
+
+    #[test]
+    fn test_batches_check_every_op() {
+        let api = api();
+        let mut session = login(&api, "orders-svc", "orders-token");
+        let ops = vec![
+            Modify::new_put("orders".to_string(), b"o1".to_vec(), b"v".to_vec()),
+            Modify::new_put("users".to_string(), b"u1".to_vec(), b"v".to_vec()),
+        ];
+        let e = denied(api.handle_command(&mut session, Command::new_batch(ops.clone(), BatchMode::Atomic)));
+        assert!(e.ends_with("on cf users"), "{}", e);
+        // 整个批次被拒绝，允许的操作也没有写入
+        assert!(matches!(api.handle_command(&mut session, get("orders")), Response::Value(None)));
+
+        let allowed = ops.into_iter().filter(|op| op.cf == "orders").collect();
+        assert!(matches!(api.handle_command(&mut session, Command::new_batch(allowed, BatchMode::Atomic)), Response::Ok));
+    }
+
+    #[test]
+    fn test_client_auth_and_info_permissions() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { principals: principals(), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        let anonymous = client.permissions()?;
+        assert_eq!((anonymous.principal.as_str(), anonymous.unrestricted), ("anonymous", false));
+        assert!(client.put("orders", "o1", "v").unwrap_err().to_string().contains("PermissionDenied"));
+
+        client.auth("orders-svc", "orders-token")?;
+        client.put("orders", "o1", "v")?;
+        assert_eq!(client.get("orders", "o1")?, Some("v".to_string()));
+        let permissions = client.permissions()?;
+        assert_eq!(permissions.principal, "orders-svc");
+        assert_eq!(permissions.permissions, principals()[0].permissions);
+        assert!(client.auth("orders-svc", "wrong").is_err());
+        Ok(())
+    }
+}
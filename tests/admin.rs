@@ -0,0 +1,61 @@
+use tinykv_rs::storage;
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Command, Response};
+use tinykv_rs::server::ServerConfig;
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_with_token(token: Option<&str>) -> RawKeyValueApi {
+        let config = ServerConfig {
+            admin_token: token.map(str::to_string),
+            ..ServerConfig::default()
+        };
+        RawKeyValueApi::with_config(Arc::new(storage::StandaloneStorage::in_memory()), Arc::new(config))
+    }
+
+    fn is_admin_required(response: &Response) -> bool {
+        matches!(response, Response::Error(e) if e == "admin required")
+    }
+
+    #[test]
+    fn test_admin_commands_denied_without_auth() {
+        let api = api_with_token(Some("secret"));
+        let mut session = api.new_session();
+
+        assert!(is_admin_required(&api.handle_command(&mut session, Command::Flush)));
+        assert!(is_admin_required(&api.handle_command(&mut session, Command::Compact)));
+        assert!(is_admin_required(&api.handle_command(&mut session, Command::DropDb { name: "x".to_string(), dry_run: false })));
+
+        // 普通命令不受影响
+        let put = Command::Put { cf: "default".to_string(), key: b"k".to_vec(), value: b"v".to_vec() };
+        assert!(matches!(api.handle_command(&mut session, put), Response::Ok));
+    }
+
+    #[test]
+    fn test_admin_auth_grants_and_rejects() {
+        let api = api_with_token(Some("secret"));
+        let mut session = api.new_session();
+
+        let wrong = api.handle_command(&mut session, Command::AdminAuth { token: "guess".to_string() });
+        assert!(matches!(wrong, Response::Error(e) if e == "invalid admin token"));
+        assert!(is_admin_required(&api.handle_command(&mut session, Command::Compact)));
+
+        let granted = api.handle_command(&mut session, Command::AdminAuth { token: "secret".to_string() });
+        assert!(matches!(granted, Response::Ok));
+        assert!(matches!(api.handle_command(&mut session, Command::Compact), Response::Compacted(_)));
+
+        // 其他连接仍然需要单独认证
+        let mut other = api.new_session();
+        assert!(is_admin_required(&api.handle_command(&mut other, Command::Compact)));
+    }
+
+    #[test]
+    fn test_no_token_configured_allows_admin_commands() {
+        let api = api_with_token(None);
+        let mut session = api.new_session();
+        assert!(matches!(api.handle_command(&mut session, Command::Compact), Response::Compacted(_)));
+    }
+}
@@ -0,0 +1,133 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::KvServer;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage::{self, CfOptions};
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set_and_get_del() {
+        let storage = storage::StandaloneStorage::in_memory();
+        storage.set_cf_options("cf", CfOptions { keep_versions: 4, ..CfOptions::default() }).unwrap();
+
+        assert_eq!(storage.get_set("cf", b"k", b"v1".to_vec()).unwrap(), None);
+        assert_eq!(storage.get_set("cf", b"k", b"v2".to_vec()).unwrap(), Some(b"v1".to_vec()));
+        assert_eq!(storage.get_del("cf", b"k").unwrap(), Some(b"v2".to_vec()));
+        assert_eq!(storage.get_del("cf", b"k").unwrap(), None);
+
+        // 与普通写入一样记录版本历史
+        let history = storage.reader().unwrap().history_cf("cf", b"k", 10).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].value, None);
+    }
+
+    #[test]
+    fn test_racing_get_del_yields_value_once() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+        let mut setup = KvClient::connect(&addr)?;
+
+        for round in 0..50 {
+            let key = format!("job{}", round);
+            setup.put("queue", &key, "payload")?;
+
+            let barrier = Arc::new(Barrier::new(2));
+            let racers: Vec<_> = (0..2)
+                .map(|_| {
+                    let (addr, key, barrier) = (addr.clone(), key.clone(), Arc::clone(&barrier));
+                    thread::spawn(move || {
+                        let mut client = KvClient::connect(&addr).unwrap();
+                        barrier.wait();
+                        client.get_del("queue", &key).unwrap()
+                    })
+                })
+                .collect();
+
+            let mut results: Vec<Option<String>> = racers.into_iter().map(|r| r.join().unwrap()).collect();
+            results.sort();
+            assert_eq!(results, vec![None, Some("payload".to_string())]);
+        }
+
+        assert_eq!(setup.get_set("queue", "k", "new")?, None);
+        assert_eq!(setup.get_set("queue", "k", "newer")?, Some("new".to_string()));
+        Ok(())
+    }
+
+    fn get(storage: &storage::StandaloneStorage, key: &[u8]) -> Option<Vec<u8>> {
+        storage.reader().unwrap().get_cf("cf", key).unwrap()
+    }
+
+    #[test]
+    fn test_rename() {
+        let storage = storage::StandaloneStorage::in_memory();
+        storage.write(vec![
+            Modify::new_put("cf".to_string(), b"a".to_vec(), b"1".to_vec()),
+            Modify::new_put("cf".to_string(), b"b".to_vec(), b"2".to_vec()),
+        ]).unwrap();
+
+        storage.rename("cf", b"a", b"c", false).unwrap();
+        assert_eq!(get(&storage, b"a"), None);
+        assert_eq!(get(&storage, b"c"), Some(b"1".to_vec()));
+
+        // 目标已存在时不覆盖，两个键都保持不变
+        let err = storage.rename("cf", b"c", b"b", false).unwrap_err();
+        assert!(err.starts_with("KeyExists"), "{}", err);
+        assert_eq!(get(&storage, b"c"), Some(b"1".to_vec()));
+        assert_eq!(get(&storage, b"b"), Some(b"2".to_vec()));
+
+        storage.rename("cf", b"c", b"b", true).unwrap();
+        assert_eq!(get(&storage, b"c"), None);
+        assert_eq!(get(&storage, b"b"), Some(b"1".to_vec()));
+
+        // 源键不存在时不触碰目标键
+        let err = storage.rename("cf", b"missing", b"b", true).unwrap_err();
+        assert!(err.starts_with("KeyNotFound"), "{}", err);
+        assert_eq!(get(&storage, b"b"), Some(b"1".to_vec()));
+
+        storage.rename("cf", b"b", b"b", false).unwrap();
+        assert_eq!(get(&storage, b"b"), Some(b"1".to_vec()));
+    }
+
+    #[test]
+    fn test_copy() {
+        let storage = storage::StandaloneStorage::in_memory();
+        storage.write(vec![
+            Modify::new_put("cf".to_string(), b"a".to_vec(), b"1".to_vec()),
+            Modify::new_put("cf".to_string(), b"b".to_vec(), b"2".to_vec()),
+        ]).unwrap();
+
+        storage.copy("cf", b"a", b"c", false).unwrap();
+        assert_eq!(get(&storage, b"a"), Some(b"1".to_vec()));
+        assert_eq!(get(&storage, b"c"), Some(b"1".to_vec()));
+
+        assert!(storage.copy("cf", b"a", b"b", false).unwrap_err().starts_with("KeyExists"));
+        assert_eq!(get(&storage, b"b"), Some(b"2".to_vec()));
+        storage.copy("cf", b"a", b"b", true).unwrap();
+        assert_eq!(get(&storage, b"b"), Some(b"1".to_vec()));
+
+        assert!(storage.copy("cf", b"missing", b"d", false).unwrap_err().starts_with("KeyNotFound"));
+        assert_eq!(get(&storage, b"d"), None);
+    }
+
+    #[test]
+    fn test_rename_and_copy_over_client() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+        client.put("users", "u1", "alice")?;
+        client.rename("users", "u1", "u2", false)?;
+        client.copy("users", "u2", "u3", false)?;
+        assert_eq!(client.get("users", "u1")?, None);
+        assert_eq!(client.get("users", "u2")?, Some("alice".to_string()));
+        assert_eq!(client.get("users", "u3")?, Some("alice".to_string()));
+
+        assert!(client.rename("users", "u2", "u3", false).unwrap_err().to_string().starts_with("KeyExists"));
+        assert!(client.copy("users", "u1", "u4", true).unwrap_err().to_string().starts_with("KeyNotFound"));
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use tinykv_rs::audit::{self, AuditEntry, AuditLog};
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::{Command, Modify};
+use tinykv_rs::rotation::RotationPolicy;
+use tinykv_rs::server::{ConnContext, ServerConfig};
+use tinykv_rs::sha256;
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> ConnContext {
+        ConnContext { conn_id: 7, peer_addr: String::new(), db: "default".to_string(), is_admin: false, principal: None }
+    }
+
+    fn put(key: &str) -> Command {
+        Command::Put { cf: "users".to_string(), key: key.as_bytes().to_vec(), value: b"secret".to_vec() }
+    }
+
+    fn entries(path: &Path) -> Vec<AuditEntry> {
+        fs::read_to_string(path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_sha256_vectors() {
+        assert_eq!(sha256::hex_digest(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256::hex_digest(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            sha256::hex_digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256::hex_digest(&vec![b'a'; 1_000_000]),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn test_server_records_mutating_commands() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = temp_dir("audit_server");
+        let path = dir.join("audit.log");
+        let mut server = TestServer::start_with_config(ServerConfig { audit_log: Some(path.clone()), ..ServerConfig::default() })?;
+        let client = server.client();
+        client.put("users", "u1", "secret")?;
+        client.get("users", "u1")?;
+        assert!(client.write_batch(vec![Modify::new_delete("users".into(), b"u1".to_vec())])?.is_applied());
+        assert!(client.rename("users", "missing", "other", false).is_err());
+
+        let log = entries(&path);
+        let commands: Vec<(&str, bool)> = log
+            .iter()
+            .map(|e| match e {
+                AuditEntry::Command { command, ok, .. } => (command.as_str(), *ok),
+                other => panic!("unexpected entry {:?}", other),
+            })
+            .collect();
+        assert_eq!(commands, vec![("Put", true), ("Batch", true), ("Rename", false)]);
+        let AuditEntry::Command { seq, prev, principal, keys, .. } = &log[0] else { unreachable!() };
+        // 没有配置管理令牌时所有连接都是管理员
+        assert_eq!((*seq, principal.as_deref()), (0, Some("admin")));
+        assert_eq!(prev, &"0".repeat(64));
+        assert_eq!(keys, &vec![("users".to_string(), sha256::hex_digest(b"u1"))]);
+        // 只记录键的哈希，不记录值
+        assert!(!fs::read_to_string(&path)?.contains("secret"));
+
+        let report = client.audit_verify()?;
+        assert_eq!((report.files, report.records, report.broken), (1, 3, None));
+        let logs = client.log_stats()?;
+        assert_eq!(logs.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), vec!["wal", "audit"]);
+        assert_eq!((logs[1].files, logs[1].total_bytes), (1, fs::metadata(&path)?.len()));
+        drop(server);
+
+        let mut plain = TestServer::start()?;
+        let err = plain.client().audit_verify().unwrap_err();
+        assert!(err.to_string().starts_with("AuditDisabled"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampering_is_detected() -> Result<(), String> {
+        let dir = temp_dir("audit_tamper");
+        let path = dir.join("audit.log");
+        let log = AuditLog::open(&path, 0, Arc::new(MockClock::new(1_000)))?;
+        for key in ["a", "b", "c", "d"] {
+            log.record(&ctx(), &put(key), true)?;
+        }
+        assert_eq!(log.verify()?.broken, None);
+        let original = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = original.lines().collect();
+
+        // 改动第二行，第三行的 prev 对不上
+        let mut tampered: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        tampered[1] = tampered[1].replace("\"conn_id\":7", "\"conn_id\":8");
+        fs::write(&path, format!("{}\n", tampered.join("\n"))).unwrap();
+        let broken = log.verify()?.broken.unwrap();
+        assert_eq!((broken.file.as_str(), broken.line), ("audit.log", 3));
+
+        // 删掉最后一行：链本身完整，但与内存中的最后一条记录不一致
+        fs::write(&path, format!("{}\n", lines[..3].join("\n"))).unwrap();
+        let report = log.verify()?;
+        assert_eq!(report.records, 3);
+        assert!(report.broken.unwrap().reason.contains("truncated"));
+
+        // 删掉中间一行
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(log.verify()?.broken.unwrap().line, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotation_keeps_the_chain() -> Result<(), String> {
+        let dir = temp_dir("audit_rotate");
+        let path = dir.join("audit.log");
+        let clock = Arc::new(MockClock::new(1_000));
+        let log = AuditLog::open(&path, 600, clock.clone())?;
+        for i in 0..10 {
+            log.record(&ctx(), &put(&format!("k{}", i)), true)?;
+        }
+        let report = log.verify()?;
+        assert!(report.files > 2, "{:?}", report);
+        assert_eq!(report.broken, None);
+        let AuditEntry::Continuation { previous_file, .. } = &entries(&path)[0] else { panic!("no continuation header") };
+        assert_eq!(previous_file, &format!("audit.log.{}", report.files - 1));
+        drop(log);
+
+        // 重新打开后接着原来的链写
+        let log = AuditLog::open(&path, 600, clock)?;
+        log.record(&ctx(), &put("after-restart"), true)?;
+        // 每个轮转出的新文件多一行 Continuation
+        let report = log.verify()?;
+        assert_eq!((report.records, report.broken), (11 + report.files as u64 - 1, None));
+
+        // 删除最早的文件后，链以 Continuation 开头
+        fs::remove_file(dir.join("audit.log.1")).unwrap();
+        let (report, _) = audit::verify_chain(&path)?;
+        let broken = report.broken.unwrap();
+        assert_eq!((broken.file.as_str(), broken.line), ("audit.log.2", 1));
+        assert!(broken.reason.contains("earlier files are missing"), "{}", broken.reason);
+        Ok(())
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_age_rotation_prunes_old_files() -> Result<(), String> {
+        let dir = temp_dir("audit_retain");
+        let path = dir.join("audit.log");
+        let clock = Arc::new(MockClock::new(1_000));
+        let policy = RotationPolicy { max_age: Some(Duration::from_secs(3600)), retain: Some(2), ..RotationPolicy::default() };
+        let log = AuditLog::open_with_policy(&path, policy.clone(), clock.clone())?;
+        for i in 0..6 {
+            log.record(&ctx(), &put(&format!("k{}", i)), true)?;
+            // 同一小时内的记录写在同一个文件里
+            log.record(&ctx(), &put(&format!("k{}-again", i)), true)?;
+            clock.advance(Duration::from_secs(7200));
+        }
+        assert_eq!(files(&dir), vec!["audit.log", "audit.log.4", "audit.log.5", "audit.log.pruned"]);
+        let stats = log.stats()?;
+        assert_eq!((stats.files, stats.rotations, stats.pruned_files), (3, 5, 3));
+        assert_eq!(log.verify()?, audit::AuditReport { files: 3, records: 3 * 3, broken: None });
+        drop(log);
+
+        // 删除中途崩溃留下的旧文件不参与校验，下一次轮转时删除；编号接着最大的往后排
+        fs::write(dir.join("audit.log.3"), b"left over").unwrap();
+        assert_eq!(audit::verify_chain(&path)?.0.broken, None);
+        let log = AuditLog::open_with_policy(&path, policy, clock)?;
+        log.record(&ctx(), &put("after-restart"), true)?;
+        assert_eq!(files(&dir), vec!["audit.log", "audit.log.5", "audit.log.6", "audit.log.pruned"]);
+        assert_eq!(log.verify()?.broken, None);
+
+        // 保留的最早文件被改动或删除都会被发现
+        let first = dir.join("audit.log.5");
+        let original = fs::read_to_string(&first).unwrap();
+        fs::write(&first, original.replacen("\"seq\":", "\"seq\":1", 1)).unwrap();
+        assert!(log.verify()?.broken.unwrap().reason.contains("pruned marker"));
+        fs::remove_file(&first).unwrap();
+        let broken = log.verify()?.broken.unwrap();
+        assert_eq!(broken.file, "audit.log.5");
+        Ok(())
+    }
+}
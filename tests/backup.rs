@@ -0,0 +1,167 @@
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{CfOptions, StandaloneStorage};
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::fs;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(storage: &StandaloneStorage, cf: &str, key: &str, value: &str) {
+        storage.write(vec![Modify::new_put(cf.to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn get(storage: &StandaloneStorage, cf: &str, key: &str) -> Option<String> {
+        storage.reader().unwrap().get_cf(cf, key.as_bytes()).unwrap().map(|v| String::from_utf8(v).unwrap())
+    }
+
+    /// users 列族保留 3 个版本，u1 写过两次，session 带过期时间
+    fn populated() -> StandaloneStorage {
+        let storage = StandaloneStorage::in_memory();
+        storage.create_cf("users", Some(CfOptions { keep_versions: 3, ..CfOptions::default() })).unwrap();
+        put(&storage, "users", "u1", "alice");
+        put(&storage, "users", "u1", "alice2");
+        put(&storage, "users", "u2", "bob");
+        put(&storage, "cache", "session", "token");
+        storage.expire("cache", b"session", Duration::from_secs(600)).unwrap();
+        storage
+    }
+
+    fn backup(storage: &StandaloneStorage) -> Vec<u8> {
+        let mut archive = Vec::new();
+        storage.backup_to(&mut archive).unwrap();
+        archive
+    }
+
+    #[test]
+    fn test_round_trip_keeps_metadata() {
+        let source = populated();
+        let archive = backup(&source);
+
+        let restored = StandaloneStorage::in_memory();
+        assert_eq!(restored.restore_from(&archive[..], false).unwrap(), 3);
+        assert_eq!(get(&restored, "users", "u1"), Some("alice2".to_string()));
+        assert_eq!(get(&restored, "users", "u2"), Some("bob".to_string()));
+        assert_eq!(restored.cf_options("users").unwrap().keep_versions, 3);
+
+        let history = restored.reader().unwrap().history_cf("users", b"u1", 10).unwrap();
+        assert_eq!(history, source.reader().unwrap().history_cf("users", b"u1", 10).unwrap());
+        assert_eq!(history.len(), 2);
+
+        let ttl = restored.ttl("cache", b"session").unwrap().expect("ttl survives the restore");
+        assert!(ttl > Duration::from_secs(500) && ttl <= Duration::from_secs(600), "{:?}", ttl);
+        assert_eq!(restored.ttl("users", b"u2").unwrap(), None);
+
+        // 恢复出的存储再备份一次，内容不变（只有文件头的时间戳可能不同）
+        let lines = |archive: &[u8]| String::from_utf8(archive.to_vec()).unwrap().lines().skip(1).map(str::to_string).collect::<Vec<_>>();
+        let again = backup(&restored);
+        assert_eq!(lines(&again)[..3], lines(&archive)[..3]);
+    }
+
+    #[test]
+    fn test_restore_refuses_non_empty_store_without_force() {
+        let archive = backup(&populated());
+        let target = StandaloneStorage::in_memory();
+        put(&target, "users", "old", "value");
+
+        let e = target.restore_from(&archive[..], false).unwrap_err();
+        assert!(e.starts_with("StoreNotEmpty"), "{}", e);
+        assert_eq!(get(&target, "users", "old"), Some("value".to_string()));
+
+        // force 时整个替换，之前的键不再存在
+        target.restore_from(&archive[..], true).unwrap();
+        assert_eq!(get(&target, "users", "old"), None);
+        assert_eq!(get(&target, "users", "u2"), Some("bob".to_string()));
+        assert_eq!(target.total_keys(), 3);
+    }
+
+    #[test]
+    fn test_damaged_archive_is_rejected_without_changes() {
+        let archive = backup(&populated());
+        let target = StandaloneStorage::in_memory();
+        put(&target, "users", "old", "value");
+
+        // 改动一个值的字节，校验和不再一致
+        let text = String::from_utf8(archive.clone()).unwrap().replacen("[98,111,98]", "[98,111,99]", 1);
+        assert_ne!(text.as_bytes(), &archive[..]);
+        let truncated = &archive[..archive.len() - 10];
+        let without_end = &archive[..archive[..archive.len() - 1].iter().rposition(|b| *b == b'\n').unwrap() + 1];
+        for damaged in [text.as_bytes(), truncated, without_end, b"not an archive\n"] {
+            let e = target.restore_from(damaged, true).unwrap_err();
+            assert!(e.starts_with("InvalidArchive"), "{}", e);
+        }
+        assert_eq!(get(&target, "users", "old"), Some("value".to_string()));
+        assert_eq!(target.total_keys(), 1);
+    }
+
+    #[test]
+    fn test_restore_is_persisted() {
+        let archive = backup(&populated());
+        let path = temp_dir("backup_persisted");
+        let storage = StandaloneStorage::open(&path).unwrap();
+        put(&storage, "users", "old", "value");
+        storage.flush().unwrap();
+
+        storage.restore_from(&archive[..], true).unwrap();
+        assert_eq!(storage.flush_info().unwrap().dirty, 0);
+        drop(storage);
+
+        let storage = StandaloneStorage::open(&path).unwrap();
+        assert_eq!(get(&storage, "users", "old"), None);
+        assert_eq!(get(&storage, "users", "u1"), Some("alice2".to_string()));
+        assert_eq!(storage.reader().unwrap().history_cf("users", b"u1", 10).unwrap().len(), 2);
+        assert!(storage.ttl("cache", b"session").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_backup_streams_to_client_in_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { response_chunk_bytes: Some(64), ..ServerConfig::default() };
+        let mut source = TestServer::start_with_config(config)?;
+        let client = source.client();
+        for i in 0..50 {
+            client.put("users", &format!("u{:02}", i), &format!("user {}", i))?;
+        }
+        let mut archive = Vec::new();
+        assert_eq!(client.backup(&mut archive)?, 50);
+
+        let mut target = TestServer::start()?;
+        let client = target.client();
+        client.put("users", "old", "value")?;
+        let e = client.restore(&archive[..], false).unwrap_err();
+        assert!(e.to_string().starts_with("StoreNotEmpty"), "{}", e);
+        assert_eq!(client.restore(&archive[..], true)?, 50);
+        assert_eq!(client.get("users", "u07")?, Some("user 7".to_string()));
+        assert_eq!(client.get("users", "old")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_and_restore_server_side_files() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let path = server.data_path().join("backup.tkv").to_string_lossy().into_owned();
+        let client = server.client();
+        client.put("users", "u1", "alice")?;
+        assert_eq!(client.backup_to_file(&path)?, 1);
+        assert!(!fs::exists(format!("{}.tmp", path))?);
+
+        client.put("users", "u1", "changed")?;
+        assert_eq!(client.restore_from_file(&path, true)?, 1);
+        assert_eq!(client.get("users", "u1")?, Some("alice".to_string()));
+
+        let e = client.restore_from_file(&format!("{}.missing", path), true).unwrap_err();
+        assert!(e.to_string().contains("Failed to open"), "{}", e);
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_starts_with_header() {
+        let archive = backup(&populated());
+        let first = archive.split(|b| *b == b'\n').next().unwrap();
+        let header: serde_json::Value = serde_json::from_slice(first).unwrap();
+        assert_eq!(header["Header"]["format"], "tinykv-archive");
+        assert_eq!(header["Header"]["version"], 1);
+    }
+}
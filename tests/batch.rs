@@ -0,0 +1,249 @@
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::client::BatchOutcome;
+use tinykv_rs::group_commit::GroupCommitConfig;
+use tinykv_rs::protocol::{BatchMode, Command, Modify, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{StandaloneStorage, StorageOptions};
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(cf: &str, key: &str, value: &str) -> Modify {
+        Modify::new_put(cf.into(), key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    // 有效、不合法的列族名、未创建的列族、有效
+    fn mixed_ops() -> Vec<Modify> {
+        vec![
+            put("users", "a", "1"),
+            put("bad name", "b", "2"),
+            put("missing", "c", "3"),
+            Modify::new_delete("users".into(), b"x".to_vec()),
+        ]
+    }
+
+    fn strict_server() -> Result<TestServer, Box<dyn std::error::Error>> {
+        let mut server = TestServer::start_with_config(ServerConfig { strict_cf_mode: true, ..ServerConfig::default() })?;
+        server.client().create_cf("users", None)?;
+        Ok(server)
+    }
+
+    #[test]
+    fn test_atomic_batch_reports_every_invalid_op() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = strict_server()?;
+        let client = server.client();
+
+        let BatchOutcome::Rejected(errors) = client.write_batch(mixed_ops())? else {
+            panic!("expected Rejected");
+        };
+        let indices: Vec<usize> = errors.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![1, 2]);
+        assert!(errors[1].1.contains("UnknownCf: missing"), "{}", errors[1].1);
+        // 没有做任何修改
+        assert_eq!(client.get("users", "a")?, None);
+
+        let ops = vec![put("users", "a", "1"), put("users", "b", "2")];
+        assert_eq!(client.write_batch(ops)?, BatchOutcome::Applied);
+        assert_eq!(client.get("users", "b")?, Some("2".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_effort_batch_applies_valid_ops() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = strict_server()?;
+        let client = server.client();
+        client.put("users", "x", "old")?;
+
+        let BatchOutcome::Partial(results) = client.write_batch_with_mode(mixed_ops(), BatchMode::BestEffort)? else {
+            panic!("expected Partial");
+        };
+        let ok: Vec<bool> = results.iter().map(Result::is_ok).collect();
+        assert_eq!(ok, vec![true, false, false, true]);
+        assert!(!BatchOutcome::Partial(results).is_applied());
+        assert_eq!(client.get("users", "a")?, Some("1".to_string()));
+        assert_eq!(client.get("users", "x")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_effort_batch_reports_rejected_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            storage_options: StorageOptions { max_memory_bytes: Some(1024), ..StorageOptions::default() },
+            ..ServerConfig::default()
+        };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        let big = "x".repeat(4096);
+        let ops = vec![put("cf", "a", "1"), put("cf", "big", &big), put("cf", "b", "2")];
+
+        // 原子模式下整个批次被拒绝
+        assert!(client.write_batch(ops.clone()).unwrap_err().to_string().contains("OutOfMemoryBudget"));
+        assert_eq!(client.get("cf", "a")?, None);
+
+        let BatchOutcome::Partial(results) = client.write_batch_with_mode(ops, BatchMode::BestEffort)? else {
+            panic!("expected Partial");
+        };
+        assert!(results[0].is_ok() && results[2].is_ok());
+        assert!(results[1].as_ref().unwrap_err().starts_with("OutOfMemoryBudget"), "{:?}", results[1]);
+        assert_eq!(client.get("cf", "b")?, Some("2".to_string()));
+        assert_eq!(client.get("cf", "big")?, None);
+        Ok(())
+    }
+
+    const ENTITIES: u64 = 4;
+
+    // 把实体和它的索引项写成同一代；奇数代先写索引，两种顺序都要整体生效
+    fn entity_with_index(id: u64, generation: u64) -> Vec<Modify> {
+        let entity = put("entity", &format!("e{}", id), &generation.to_string());
+        let index = put("index", &format!("i{}", id), &generation.to_string());
+        match generation % 2 {
+            0 => vec![entity, index],
+            _ => vec![index, entity],
+        }
+    }
+
+    fn generation(value: Option<String>) -> u64 {
+        value.map_or(0, |v| v.parse().unwrap())
+    }
+
+    /// 反复读取实体和索引：先读到的一方不可能比后读到的一方更新，否则读到了只应用了一半的批次
+    fn check_pairs(read: &mut dyn FnMut(&str, &str) -> Option<String>, stop: &AtomicBool) -> u64 {
+        let mut checks = 0;
+        while !stop.load(Ordering::SeqCst) {
+            for id in 0..ENTITIES {
+                let (entity, index) = (format!("e{}", id), format!("i{}", id));
+                let first = generation(read("entity", &entity));
+                let second = generation(read("index", &index));
+                assert!(second >= first, "entity {} at generation {} but index at {}", id, first, second);
+                let first = generation(read("index", &index));
+                let second = generation(read("entity", &entity));
+                assert!(second >= first, "index {} at generation {} but entity at {}", id, first, second);
+                checks += 1;
+            }
+        }
+        checks
+    }
+
+    #[test]
+    fn test_readers_never_see_half_of_a_mixed_cf_batch() {
+        let storage = Arc::new(StandaloneStorage::in_memory());
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (storage, stop) = (Arc::clone(&storage), Arc::clone(&stop));
+                thread::spawn(move || {
+                    let reader = storage.reader().unwrap();
+                    let mut read = |cf: &str, key: &str| reader.get_cf(cf, key.as_bytes()).unwrap().map(|v| String::from_utf8(v).unwrap());
+                    check_pairs(&mut read, &stop)
+                })
+            })
+            .collect();
+
+        for generation in 1..=2000 {
+            storage.write(entity_with_index(generation % ENTITIES, generation)).unwrap();
+        }
+        stop.store(true, Ordering::SeqCst);
+        for reader in readers {
+            assert!(reader.join().unwrap() > 0);
+        }
+    }
+
+    #[test]
+    fn test_group_committed_mixed_cf_batches_stay_atomic() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { group_commit: Some(GroupCommitConfig::default()), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut reader_client = server.connect()?;
+        let reader_stop = Arc::clone(&stop);
+        let reader = thread::spawn(move || {
+            let mut read = |cf: &str, key: &str| reader_client.get(cf, key).unwrap();
+            check_pairs(&mut read, &reader_stop)
+        });
+
+        // 每个实体一个写入者，组提交会把它们的批次合并成一次写入
+        let writers: Vec<_> = (0..ENTITIES)
+            .map(|id| {
+                let mut client = server.connect().unwrap();
+                thread::spawn(move || {
+                    for generation in 1..=100 {
+                        assert_eq!(client.write_batch(entity_with_index(id, generation)).unwrap(), BatchOutcome::Applied);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        stop.store(true, Ordering::SeqCst);
+        assert!(reader.join().unwrap() > 0);
+        let mut client = server.connect()?;
+        for id in 0..ENTITIES {
+            assert_eq!(client.get("entity", &format!("e{}", id))?, Some("100".to_string()));
+            assert_eq!(client.get("index", &format!("i{}", id))?, Some("100".to_string()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_concurrent_mixed_cf_batches_do_not_deadlock() {
+        const CFS: [&str; 4] = ["a", "b", "c", "d"];
+        for group_commit in [None, Some(GroupCommitConfig::default())] {
+            let config = Arc::new(ServerConfig { group_commit, ..ServerConfig::default() });
+            let api = Arc::new(RawKeyValueApi::with_config(Arc::new(StandaloneStorage::in_memory()), config));
+            let (done, finished) = mpsc::channel();
+            let mut workers = Vec::new();
+            for seed in 1..=8u64 {
+                let (api, done) = (Arc::clone(&api), done.clone());
+                workers.push(thread::spawn(move || {
+                    let mut rng = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                    let mut next = |n: u64| {
+                        rng ^= rng << 13;
+                        rng ^= rng >> 7;
+                        rng ^= rng << 17;
+                        rng % n
+                    };
+                    let mut session = Session::default();
+                    for _ in 0..200 {
+                        // 列族的顺序和数量随机，同一批次中可以重复出现同一个键
+                        let ops = (0..1 + next(6))
+                            .map(|_| {
+                                let cf = CFS[next(4) as usize];
+                                let key = format!("k{}", next(8));
+                                match next(3) {
+                                    0 => Modify::new_delete(cf.to_string(), key.into_bytes()),
+                                    _ => put(cf, &key, &seed.to_string()),
+                                }
+                            })
+                            .collect();
+                        let response = api.handle_command(&mut session, Command::new_batch(ops, BatchMode::Atomic));
+                        assert!(matches!(response, Response::Ok), "{:?}", response);
+                        // 穿插跨列族的读取
+                        let scan = api.handle_command(&mut session, Command::ScanAll { start: None, limit: 16 });
+                        assert!(matches!(scan, Response::CfValues { .. }), "{:?}", scan);
+                    }
+                    done.send(()).unwrap();
+                }));
+            }
+            drop(done);
+            for _ in 1..=8 {
+                match finished.recv_timeout(Duration::from_secs(30)) {
+                    Ok(()) => {}
+                    // 有线程失败退出，join 时报告它的错误
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    Err(mpsc::RecvTimeoutError::Timeout) => panic!("batches did not finish, possible deadlock"),
+                }
+            }
+            for worker in workers {
+                worker.join().unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use tinykv_rs::storage::{self, StorageOptions};
+use tinykv_rs::api::{EncodedKey, RawKeyValueApi};
+use tinykv_rs::protocol::{Command, Modify, Response};
+use tinykv_rs::testing::temp_dir;
+use std::path::Path;
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(path: Option<&Path>) -> storage::StandaloneStorage {
+        let options = StorageOptions { checksums: true, ..StorageOptions::default() };
+        match path {
+            Some(path) => storage::StandaloneStorage::open_with_options(path, options).unwrap(),
+            None => storage::StandaloneStorage::in_memory_with_options(options),
+        }
+    }
+
+    /// 模拟磁盘上的数据被改动：修改第一个段文件中第一条记录的值的一个字节
+    fn tamper_first_value(path: &Path) {
+        let dir = path;
+        let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join("MANIFEST")).unwrap()).unwrap();
+        let file = dir.join(manifest["segments"][0].as_str().unwrap());
+        let contents = std::fs::read_to_string(&file).unwrap();
+        let (first, rest) = contents.split_once('\n').unwrap();
+
+        let mut record: serde_json::Value = serde_json::from_str(first).unwrap();
+        let byte = &mut record["keys"][0]["value"][0];
+        *byte = serde_json::json!(byte.as_u64().unwrap() ^ 1);
+        std::fs::write(&file, format!("{}\n{}", record, rest)).unwrap();
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(storage::crc32(b""), 0);
+        assert_eq!(storage::crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_corrupt_value_detected_and_repaired() {
+        let path = temp_dir("checksum_repair");
+        let storage = open(Some(&path));
+        storage.write(vec![
+            Modify::new_put("cf".to_string(), b"a".to_vec(), b"apple".to_vec()),
+            Modify::new_put("cf".to_string(), b"b".to_vec(), b"banana".to_vec()),
+        ]).unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        tamper_first_value(&path);
+        let storage = open(Some(&path));
+        let reader = storage.reader().unwrap();
+        assert!(reader.get_cf("cf", b"a").unwrap_err().starts_with("Corrupt"));
+        assert_eq!(reader.get_cf("cf", b"b").unwrap(), Some(b"banana".to_vec()));
+        drop(reader);
+
+        assert_eq!(storage.verify(None).unwrap(), vec![("cf".to_string(), b"a".to_vec())]);
+        assert!(storage.verify(Some("other")).unwrap().is_empty());
+
+        assert_eq!(storage.repair(true).unwrap().len(), 1);
+        assert!(storage.verify(None).unwrap().is_empty());
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("cf", b"a").unwrap(), None);
+        // 隔离的条目保留损坏后的值，键为原条目的编码键
+        let quarantined = EncodedKey::encode("cf", b"a");
+        assert_eq!(reader.get_cf(storage::QUARANTINE_CF, quarantined.as_bytes()).unwrap(), Some(b"`pple".to_vec()));
+    }
+
+    #[test]
+    fn test_checksums_follow_batch_writes_and_deletes() {
+        let storage = Arc::new(open(None));
+        storage.write(vec![
+            Modify::new_put("cf".to_string(), b"k".to_vec(), b"v1".to_vec()),
+            Modify::new_put("cf".to_string(), b"k".to_vec(), b"v2".to_vec()),
+            Modify::new_put("cf".to_string(), b"gone".to_vec(), b"x".to_vec()),
+            Modify::new_delete("cf".to_string(), b"gone".to_vec()),
+        ]).unwrap();
+        assert!(storage.verify(None).unwrap().is_empty());
+
+        let api = RawKeyValueApi::new(storage);
+        let mut session = api.new_session();
+        match api.handle_command(&mut session, Command::Verify { cf: Some("cf".to_string()) }) {
+            Response::CorruptKeys(keys) => assert!(keys.is_empty()),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_verify_requires_checksums() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = api.new_session();
+        assert!(matches!(api.handle_command(&mut session, Command::Verify { cf: None }), Response::Error(_)));
+    }
+}
@@ -0,0 +1,174 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::{self, Bytes, Command, Modify, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::testing::TestServer;
+
+use std::io::{self, Cursor, Read, Write};
+use std::net::TcpStream;
+
+/// 回放事先写好的服务器帧，丢弃客户端发出的命令
+struct Scripted(Cursor<Vec<u8>>);
+
+impl Scripted {
+    fn new(frames: &[Response]) -> Self {
+        let bytes = frames.iter().flat_map(|f| serde_json::to_vec(f).unwrap()).collect();
+        Scripted(Cursor::new(bytes))
+    }
+}
+
+impl Read for Scripted {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for Scripted {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..n).map(|i| (format!("k{:02}", i).into_bytes(), format!("v{}", i).into_bytes())).collect()
+    }
+
+    fn values(n: usize) -> Vec<(Bytes, Bytes)> {
+        entries(n).into_iter().map(|(k, v)| (Bytes(k), Bytes(v))).collect()
+    }
+
+    fn json(response: &Response) -> String {
+        serde_json::to_string(response).unwrap()
+    }
+
+    // 按客户端的方式拼回 into_chunks 的结果
+    fn reassemble(frames: Vec<Response>) -> Response {
+        let mut assembled: Option<Response> = None;
+        for frame in frames {
+            let Response::Chunk { part, .. } = frame else { return frame };
+            match &mut assembled {
+                Some(response) => response.append(*part).unwrap(),
+                None => assembled = Some(*part),
+            }
+        }
+        assembled.unwrap()
+    }
+
+    fn start_server(chunk_bytes: usize) -> TestServer {
+        let config = ServerConfig { response_chunk_bytes: Some(chunk_bytes), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config).unwrap();
+        let ops = entries(20).into_iter().map(|(k, v)| Modify::new_put("users".to_string(), k, v)).collect();
+        assert!(server.client().write_batch(ops).unwrap().is_applied());
+        server
+    }
+
+    #[test]
+    fn test_list_responses_split_and_reassemble_in_order() {
+        let entry = protocol::entry_wire_size(b"k00", b"v0");
+        let frames = Response::Values(values(10)).into_chunks(2 * entry);
+        assert_eq!(frames.len(), 5);
+        let flags: Vec<bool> = frames.iter().map(|f| matches!(f, Response::Chunk { more: true, .. })).collect();
+        assert_eq!(flags, vec![true, true, true, true, false]);
+        assert_eq!(json(&reassemble(frames)), json(&Response::Values(values(10))));
+
+        // 续扫位置只在最后一帧，拼回后保持不变
+        let truncated = Response::TruncatedValues { values: values(5), next: Bytes(b"k05".to_vec()), examined_limit_reached: false };
+        let expected = json(&truncated);
+        let frames = truncated.into_chunks(1);
+        assert!(matches!(frames.first(), Some(Response::Chunk { part, .. }) if matches!(**part, Response::Values(_))));
+        assert_eq!(json(&reassemble(frames)), expected);
+
+        let entries: Vec<(String, Bytes, Bytes)> = values(4).into_iter().map(|(k, v)| ("users".to_string(), k, v)).collect();
+        let page = Response::CfValues { entries, next: Some(("users".to_string(), Bytes(b"k04".to_vec()))), truncated: true };
+        let expected = json(&page);
+        assert_eq!(json(&reassemble(page.into_chunks(1))), expected);
+
+        let keys = Response::Keys(values(3).into_iter().map(|(k, _)| k).collect());
+        let expected = json(&keys);
+        assert_eq!(json(&reassemble(keys.into_chunks(1))), expected);
+
+        // 一帧就能装下的响应和非列表响应不分帧
+        assert!(matches!(&Response::Values(values(3)).into_chunks(usize::MAX)[..], [Response::Values(v)] if v.len() == 3));
+        assert!(matches!(&Response::Ok.into_chunks(1)[..], [Response::Ok]));
+    }
+
+    #[test]
+    fn test_client_reassembles_chunked_scans() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = start_server(1);
+        let client = server.client();
+        assert!(client.negotiated_features().contains(&"chunked".to_string()));
+
+        assert_eq!(client.scan_bytes("users", b"", None, 100)?, entries(20));
+        assert_eq!(client.scan_all(None, 100)?.entries.len(), 20);
+
+        // 流式接口每帧调用一次
+        let mut chunks = Vec::new();
+        let cursor = client.scan_builder("users").run_chunks(|chunk| chunks.push(chunk))?;
+        assert_eq!(cursor, None);
+        assert_eq!(chunks.len(), 20);
+        assert_eq!(chunks.concat(), entries(20));
+        Ok(())
+    }
+
+    #[test]
+    fn test_frames_on_the_wire() -> Result<(), Box<dyn std::error::Error>> {
+        let server = start_server(3 * protocol::entry_wire_size(b"k00", b"v0"));
+        let scan = Command::new_scan("users".to_string(), Vec::new(), None, 100);
+        let mut pending = Vec::new();
+
+        // 没有协商 chunked 的连接收到一整条响应
+        let mut legacy = TcpStream::connect(server.addr())?;
+        legacy.write_all(&serde_json::to_vec(&scan)?)?;
+        let response: Response = protocol::read_message(&mut legacy, &mut pending)?.unwrap();
+        assert!(matches!(&response, Response::Values(v) if v.len() == 20), "{:?}", response);
+
+        let mut stream = TcpStream::connect(server.addr())?;
+        let hello = Command::Hello { client_version: protocol::PROTOCOL_VERSION, features: vec!["chunked".to_string()] };
+        stream.write_all(&serde_json::to_vec(&hello)?)?;
+        let _: Response = protocol::read_message(&mut stream, &mut pending)?.unwrap();
+        stream.write_all(&serde_json::to_vec(&scan)?)?;
+        let mut sizes = Vec::new();
+        loop {
+            let frame: Response = protocol::read_message(&mut stream, &mut pending)?.unwrap();
+            let Response::Chunk { part, more } = frame else { panic!("unexpected {:?}", frame) };
+            let Response::Values(values) = *part else { panic!("unexpected part") };
+            sizes.push(values.len());
+            if !more {
+                break;
+            }
+        }
+        // k10 之后的条目更长，每帧装下的条目更少
+        assert_eq!(sizes.iter().sum::<usize>(), 20);
+        assert!(sizes.len() > 6 && sizes.iter().all(|&n| (1..=3).contains(&n)), "{:?}", sizes);
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_frame_ends_the_sequence() {
+        let chunk = |n: usize, more: bool| Response::Chunk { part: Box::new(Response::Values(values(n))), more };
+        let frames = [chunk(2, true), Response::Error("ReadFailed: disk error".to_string())];
+
+        let mut client = KvClient::from_stream(Scripted::new(&frames));
+        let err = client.scan_bytes("users", b"", None, 100).unwrap_err();
+        assert!(err.to_string().contains("ReadFailed"), "{}", err);
+
+        // 流式接口已交出的帧保留，随后返回错误
+        let mut client = KvClient::from_stream(Scripted::new(&frames));
+        let mut received = Vec::new();
+        let err = client.scan_builder("users").run_chunks(|chunk| received.extend(chunk)).unwrap_err();
+        assert!(err.to_string().contains("ReadFailed"), "{}", err);
+        assert_eq!(received, entries(2));
+
+        // 不能接续的帧是协议错误
+        let frames = [chunk(2, true), Response::Keys(Vec::new())];
+        let mut client = KvClient::from_stream(Scripted::new(&frames));
+        assert!(client.scan_bytes("users", b"", None, 100).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::{KvServer, ServerConfig};
+
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 连接线程在服务器端异步注销，等待列表收敛到期望的数量
+    fn wait_for_clients(admin: &mut KvClient, expected: usize) -> Vec<tinykv_rs::clients::ClientInfo> {
+        for _ in 0..200 {
+            let clients = admin.clients().unwrap();
+            if clients.len() == expected {
+                return clients;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("client list did not reach {} entries", expected);
+    }
+
+    #[test]
+    fn test_list_and_kill_clients() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+        let mut admin = KvClient::connect(&addr)?;
+        let mut first = KvClient::connect(&addr)?;
+        let mut second = KvClient::connect_db(&addr, "app")?;
+
+        first.put("default", "k", "v")?;
+        first.get("default", "k")?;
+        second.put("default", "k", "v")?;
+
+        let clients = wait_for_clients(&mut admin, 3);
+        let ids: Vec<u64> = clients.iter().map(|c| c.id).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        let first_info = &clients[1];
+        assert_eq!(first_info.last_command.as_deref(), Some("Get"));
+        // Hello + Put + Get
+        assert_eq!(first_info.commands, 3);
+        assert!(first_info.bytes_in > 0 && first_info.bytes_out > 0);
+        assert_eq!(clients[2].db, "app");
+        assert!(!clients[2].peer_addr.is_empty());
+
+        admin.kill_client(first_info.id)?;
+        assert!(first.get("default", "k").is_err());
+        assert_eq!(second.get("default", "k")?, Some("v".to_string()));
+
+        let remaining = wait_for_clients(&mut admin, 2);
+        assert!(remaining.iter().all(|c| c.id != first_info.id));
+        assert!(admin.kill_client(first_info.id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_clients_requires_admin() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { admin_token: Some("secret".to_string()), ..ServerConfig::default() };
+        let handle = KvServer::with_config(config)?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+
+        let mut client = KvClient::connect(&addr)?;
+        assert!(client.clients().is_err());
+        assert!(client.kill_client(1).is_err());
+
+        client.admin_auth("secret")?;
+        let clients = client.clients()?;
+        assert_eq!(clients.len(), 1);
+        assert!(clients[0].is_admin);
+        Ok(())
+    }
+}
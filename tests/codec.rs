@@ -0,0 +1,218 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::{self, Bytes, Codec, Command, Envelope, Modify, Request, Response, Warning};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::testing::TestServer;
+
+use std::io::{self, Cursor, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// 统计收发字节数的连接
+struct Counted {
+    stream: TcpStream,
+    read: Arc<AtomicU64>,
+    written: Arc<AtomicU64>,
+}
+
+impl Read for Counted {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stream.read(buf)?;
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl Write for Counted {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.stream.write(buf)?;
+        self.written.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json<T: serde::Serialize>(message: &T) -> String {
+        serde_json::to_string(message).unwrap()
+    }
+
+    // 经 codec 编码再解码，用 JSON 表示比较是否与原值相同
+    fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(codec: Codec, message: &T) -> T {
+        let bytes = codec.encode(message).unwrap();
+        let mut pending = Vec::new();
+        let decoded = codec.read_message(&mut Cursor::new(bytes), &mut pending).unwrap().unwrap();
+        assert!(pending.is_empty());
+        assert_eq!(json(&decoded), json(message));
+        decoded
+    }
+
+    fn entries(n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..n).map(|i| (format!("k{:03}", i).into_bytes(), format!("value-{}", i).repeat(4).into_bytes())).collect()
+    }
+
+    #[test]
+    fn test_messages_round_trip_through_both_codecs() {
+        let values: Vec<(Bytes, Bytes)> = entries(3).into_iter().map(|(k, v)| (Bytes(k), Bytes(v))).collect();
+        for codec in [Codec::Json, Codec::MessagePack] {
+            round_trip(codec, &Command::new_put("users".to_string(), b"k".to_vec(), vec![0, 255, 10]));
+            round_trip(codec, &Command::new_scan("users".to_string(), Vec::new(), Some(b"z".to_vec()), 10));
+            let ops = vec![Modify::new_put("users".to_string(), b"a".to_vec(), b"1".to_vec())];
+            round_trip(codec, &Command::Batch { ops, mode: Default::default() });
+            round_trip(codec, &Command::Hello { client_version: protocol::PROTOCOL_VERSION, features: vec!["msgpack".to_string()] });
+
+            // 请求选项与命令的字段并列编码
+            let put = Command::new_put("users".to_string(), b"k".to_vec(), b"v".to_vec());
+            let request = round_trip(codec, &Request { cmd: put.clone(), deadline_ms: Some(250) });
+            assert_eq!(request.deadline_ms, Some(250));
+            let request = round_trip(codec, &Request { cmd: put, deadline_ms: None });
+            assert_eq!(request.deadline_ms, None);
+
+            round_trip(codec, &Response::Ok);
+            round_trip(codec, &Response::Value(Some(Bytes(b"\x00bin".to_vec()))));
+            round_trip(codec, &Response::Values(values.clone()));
+            round_trip(codec, &Response::Error("NotFound: missing".to_string()));
+            round_trip(codec, &Response::Chunk { part: Box::new(Response::Values(values.clone())), more: true });
+            let envelope = Envelope {
+                resp: Response::Values(values.clone()),
+                server_us: 42,
+                warnings: vec![Warning::CfAutoCreated { cf: "users".to_string() }],
+            };
+            round_trip(codec, &envelope);
+        }
+    }
+
+    #[test]
+    fn test_msgpack_frames_are_length_prefixed_and_smaller() {
+        let value = vec![200u8; 64];
+        let response = Response::Value(Some(Bytes(value.clone())));
+        let frame = Codec::MessagePack.encode(&response).unwrap();
+        let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, frame.len() - 4);
+        // JSON 把字节数组写成数字数组，MessagePack 写成二进制
+        assert!(frame.len() < Codec::Json.encode(&response).unwrap().len() / 2);
+
+        // 多帧连续到达时逐条读取，半帧之后连接关闭是错误
+        let mut bytes = frame.clone();
+        bytes.extend(Codec::MessagePack.encode(&Response::Ok).unwrap());
+        bytes.extend(&frame[..frame.len() - 1]);
+        let mut stream = Cursor::new(bytes);
+        let mut pending = Vec::new();
+        let first: Response = Codec::MessagePack.read_message(&mut stream, &mut pending).unwrap().unwrap();
+        assert!(matches!(first, Response::Value(Some(v)) if v.0 == value));
+        let second: Response = Codec::MessagePack.read_message(&mut stream, &mut pending).unwrap().unwrap();
+        assert!(matches!(second, Response::Ok));
+        assert!(Codec::MessagePack.read_message::<Response, _>(&mut stream, &mut pending).is_err());
+
+        let mut empty = Cursor::new(Vec::new());
+        assert!(Codec::MessagePack.read_message::<Response, _>(&mut empty, &mut Vec::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_client_negotiates_msgpack() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { response_chunk_bytes: Some(64), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let mut client = server.connect()?;
+        assert_eq!(client.codec(), Codec::Json);
+        assert!(!client.negotiated_features().contains(&"msgpack".to_string()));
+        client.enable_msgpack()?;
+        assert_eq!(client.codec(), Codec::MessagePack);
+
+        client.put_bytes("users", b"\x00key", b"\xffvalue")?;
+        assert_eq!(client.get_bytes("users", b"\x00key")?, Some(b"\xffvalue".to_vec()));
+        let ops = entries(20).into_iter().map(|(k, v)| Modify::new_put("users".to_string(), k, v)).collect();
+        assert!(client.write_batch(ops)?.is_applied());
+        // 分帧发送的扫描结果同样能拼回
+        let scanned = client.scan_bytes("users", b"k", None, 100)?;
+        assert_eq!(scanned, entries(20));
+
+        // JSON 客户端看到同样的数据
+        let json_client = server.client();
+        assert_eq!(json_client.scan_bytes("users", b"k", None, 100)?, entries(20));
+
+        // 与 envelope 一起使用
+        client.enable_envelope()?;
+        assert_eq!(client.codec(), Codec::MessagePack);
+        client.put("fresh", "k", "v")?;
+        assert_eq!(client.take_warnings(), vec![Warning::CfAutoCreated { cf: "fresh".to_string() }]);
+        assert!(client.last_server_timing().is_some());
+
+        // 推送的消息也按协商的编码发送
+        let mut subscriber = server.connect()?;
+        subscriber.enable_msgpack()?;
+        let mut messages = subscriber.subscribe(&["news"])?;
+        assert_eq!(client.publish("news", b"\x01\x02")?, 1);
+        assert_eq!(messages.next().unwrap().payload, Bytes(b"\x01\x02".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_switches_codec_after_hello() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let mut stream = TcpStream::connect(server.addr())?;
+        let mut pending = Vec::new();
+
+        // Hello 的回复仍是 JSON
+        let hello = Command::Hello { client_version: protocol::PROTOCOL_VERSION, features: vec!["msgpack".to_string()] };
+        stream.write_all(&serde_json::to_vec(&hello)?)?;
+        let response: Response = protocol::read_message(&mut stream, &mut pending)?.unwrap();
+        assert!(matches!(&response, Response::Hello { accepted_features, .. } if accepted_features == &["msgpack"]));
+
+        let put = Command::new_put("users".to_string(), b"k".to_vec(), b"v".to_vec());
+        stream.write_all(&Codec::MessagePack.encode(&put)?)?;
+        let response: Response = Codec::MessagePack.read_message(&mut stream, &mut pending)?.unwrap();
+        assert!(matches!(response, Response::Ok), "{:?}", response);
+
+        // 之后发送的 JSON 被当作长度前缀，连接关闭后服务器只读到半帧
+        stream.write_all(&serde_json::to_vec(&put)?)?;
+        stream.shutdown(std::net::Shutdown::Write)?;
+        assert!(Codec::MessagePack.read_message::<Response, _>(&mut stream, &mut pending).map_or(true, |r| r.is_none()));
+        Ok(())
+    }
+
+    // 同样的负载分别走 JSON 和 MessagePack，返回 (收发字节数, 用时)
+    fn run_workload(server: &TestServer, cf: &str, codec: Codec) -> Result<(u64, std::time::Duration), Box<dyn std::error::Error>> {
+        let read = Arc::new(AtomicU64::new(0));
+        let written = Arc::new(AtomicU64::new(0));
+        let stream = Counted { stream: TcpStream::connect(server.addr())?, read: read.clone(), written: written.clone() };
+        let mut client = KvClient::from_stream(stream);
+        if codec == Codec::MessagePack {
+            client.enable_msgpack()?;
+        }
+        read.store(0, Ordering::Relaxed);
+        written.store(0, Ordering::Relaxed);
+
+        let started = Instant::now();
+        for (key, value) in entries(200) {
+            client.put_bytes(cf, &key, &value)?;
+        }
+        for _ in 0..20 {
+            assert_eq!(client.scan_bytes(cf, b"", None, 200)?.len(), 200);
+        }
+        Ok((read.load(Ordering::Relaxed) + written.load(Ordering::Relaxed), started.elapsed()))
+    }
+
+    #[test]
+    fn test_msgpack_moves_fewer_bytes_than_json() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let (json_bytes, json_time) = run_workload(&server, "json", Codec::Json)?;
+        let (msgpack_bytes, msgpack_time) = run_workload(&server, "msgpack", Codec::MessagePack)?;
+        println!(
+            "json: {} bytes in {:?}; msgpack: {} bytes in {:?} ({:.0}% of json)",
+            json_bytes,
+            json_time,
+            msgpack_bytes,
+            msgpack_time,
+            msgpack_bytes as f64 * 100.0 / json_bytes as f64
+        );
+        assert!(msgpack_bytes * 2 < json_bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage::{self, CompactionPolicy, StorageOptions, SHRINK_MIN_SLACK};
+use tinykv_rs::testing::{temp_dir, wait_until, TestServer};
+
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(path: Option<&Path>, clock: &Arc<MockClock>, policy: Option<CompactionPolicy>) -> Arc<storage::StandaloneStorage> {
+        let options = StorageOptions { clock: clock.clone(), compaction: policy, ..StorageOptions::default() };
+        Arc::new(match path {
+            Some(path) => storage::StandaloneStorage::open_with_options(path, options).unwrap(),
+            None => storage::StandaloneStorage::in_memory_with_options(options),
+        })
+    }
+
+    fn write(storage: &storage::StandaloneStorage, keys: std::ops::Range<usize>, delete: bool) {
+        let batch = keys
+            .map(|i| {
+                let key = format!("k{:03}", i).into_bytes();
+                match delete {
+                    true => Modify::new_delete("cf".into(), key),
+                    false => Modify::new_put("cf".into(), key, vec![b'v'; 64]),
+                }
+            })
+            .collect();
+        storage.write(batch).unwrap();
+    }
+
+    fn disk_bytes(path: &Path) -> u64 {
+        std::fs::read_dir(path).unwrap().flatten().map(|e| e.metadata().unwrap().len()).sum()
+    }
+
+    #[test]
+    fn test_scheduler_compacts_once_per_interval() {
+        let path = temp_dir("compaction_scheduler");
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let policy = CompactionPolicy { trigger_dead_ratio: 0.5, min_interval: Duration::from_secs(60) };
+        let storage = open(Some(&path), &clock, Some(policy));
+        write(&storage, 0..100, false);
+        storage.flush().unwrap();
+        let full = disk_bytes(&path);
+
+        // 没有无效数据时不整理
+        let _scheduler = storage.start_compaction_scheduler().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(storage.compaction_info().unwrap().compactions, 0);
+
+        write(&storage, 0..80, true);
+        storage.flush().unwrap();
+        wait_until(|| storage.compaction_info().unwrap().compactions == 1);
+        let info = storage.compaction_info().unwrap();
+        assert_eq!(info.automatic, 1);
+        assert!(info.last_reclaimed_bytes > 0);
+        assert_eq!(info.dead_ratio, 0.0);
+        assert!(disk_bytes(&path) < full, "{} >= {}", disk_bytes(&path), full);
+
+        // 比例再次超过阈值，但距上次整理不足 min_interval
+        write(&storage, 80..95, true);
+        storage.flush().unwrap();
+        assert!(storage.dead_ratio().unwrap() > 0.5);
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(storage.compaction_info().unwrap().compactions, 1);
+
+        clock.advance(Duration::from_secs(60));
+        wait_until(|| storage.compaction_info().unwrap().compactions == 2);
+        assert_eq!(storage.get_stats().unwrap().0, 5);
+
+        drop(_scheduler);
+        drop(storage);
+    }
+
+    #[test]
+    fn test_scheduler_can_be_disabled() {
+        let path = temp_dir("compaction_disabled");
+        let clock = Arc::new(MockClock::new(0));
+        let storage = open(Some(&path), &clock, None);
+        assert!(storage.start_compaction_scheduler().is_none());
+        drop(storage);
+
+        // 纯内存模式没有磁盘上的无效数据，不启动整理线程
+        let storage = open(None, &clock, Some(CompactionPolicy::default()));
+        assert!(storage.start_compaction_scheduler().is_none());
+    }
+
+    #[test]
+    fn test_expired_keys_count_as_dead() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(0));
+        let storage = open(None, &clock, None);
+        write(&storage, 0..4, false);
+        for key in ["k000", "k001", "k002"] {
+            storage.expire("cf", key.as_bytes(), Duration::from_secs(1))?;
+        }
+        assert_eq!(storage.dead_ratio()?, 0.0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(storage.dead_ratio()?, 0.75);
+        storage.compact()?;
+        assert_eq!(storage.dead_ratio()?, 0.0);
+        assert_eq!(storage.get_stats()?.0, 1);
+
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.compact()?;
+        let info = client.compaction_info()?;
+        assert_eq!((info.compactions, info.automatic), (1, 0));
+        Ok(())
+    }
+
+    /// 写入 1000 个容量远大于长度的值，再删除其中 90%
+    fn oversized_then_mass_delete(storage: &storage::StandaloneStorage) {
+        let batch = (0..1000)
+            .map(|i| {
+                let mut value = Vec::with_capacity(4096);
+                value.extend_from_slice(format!("value {}", i).as_bytes());
+                Modify::new_put("cf".into(), format!("k{:04}", i).into_bytes(), value)
+            })
+            .collect();
+        storage.write(batch).unwrap();
+        let deletes = (0..1000).filter(|i| i % 10 != 0).map(|i| Modify::new_delete("cf".into(), format!("k{:04}", i).into_bytes()));
+        storage.write(deletes.collect()).unwrap();
+    }
+
+    #[test]
+    fn test_compact_shrinks_oversized_buffers() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(0));
+        let storage = open(None, &clock, None);
+        oversized_then_mass_delete(&storage);
+        let before = storage.capacity_slack()?;
+        assert!(before >= 100 * (4096 - 16), "{}", before);
+
+        let result = storage.compact()?;
+        let after = storage.capacity_slack()?;
+        assert_eq!(result.shrunk_buffers, 100);
+        assert_eq!(result.reclaimed_capacity_bytes, before - after);
+        assert!(after < 100 * SHRINK_MIN_SLACK as u64, "{} bytes of slack left", after);
+        // 纯内存模式没有磁盘上的无效数据
+        assert_eq!((result.reclaimed_disk_bytes, result.dead_entries_removed), (0, 0));
+
+        // 重建后内容和顺序不变，再次整理没有可收缩的缓冲区
+        let remaining = storage.reader()?.scan_cf("cf", b"", None, usize::MAX, None)?;
+        assert_eq!(remaining.len(), 100);
+        assert_eq!(remaining[3], (b"k0030".to_vec(), b"value 30".to_vec()));
+        assert_eq!(storage.compact()?.shrunk_buffers, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_reports_dead_entries_separately() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(0));
+        let path = temp_dir("compact_result");
+        let storage = open(Some(&path), &clock, None);
+        for _ in 0..5 {
+            write(&storage, 0..10, false);
+            storage.flush()?;
+        }
+        storage.expire("cf", b"k000", Duration::from_secs(1))?;
+        storage.flush()?;
+        clock.advance(Duration::from_secs(1));
+
+        // 段文件中被覆盖的写入和已过期的 k000 是无效数据，值的缓冲区没有多余容量
+        let result = storage.compact()?;
+        assert_eq!(result.dead_entries_removed, 1);
+        assert!(result.reclaimed_disk_bytes > 0);
+        assert_eq!(result.reclaimed_capacity_bytes, 0);
+        let info = storage.compaction_info()?;
+        assert_eq!(info.last_reclaimed_bytes, result.reclaimed_disk_bytes);
+        assert_eq!(info.last_reclaimed_capacity_bytes, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_receives_compact_result() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.put("cf", "k", "v")?;
+        let result = client.compact()?;
+        assert_eq!(result.dead_entries_removed, 0);
+        assert_eq!(client.compaction_info()?.last_reclaimed_capacity_bytes, result.reclaimed_capacity_bytes);
+        Ok(())
+    }
+}
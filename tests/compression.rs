@@ -0,0 +1,139 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{self, StorageOptions};
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 压缩率很高的 JSON 文档
+    fn document(id: usize) -> Vec<u8> {
+        let items: Vec<String> = (0..50).map(|i| format!(r#"{{"item":{},"status":"active","tags":["a","b"]}}"#, i)).collect();
+        format!(r#"{{"id":{},"items":[{}]}}"#, id, items.join(",")).into_bytes()
+    }
+
+    /// 几乎不可压缩的数据，相当于已经压缩过的值
+    fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect()
+    }
+
+    fn put(key: &str, value: Vec<u8>) -> Modify {
+        Modify::new_put("docs".into(), key.as_bytes().to_vec(), value)
+    }
+
+    #[test]
+    fn test_values_round_trip_through_deflate() -> Result<(), Box<dyn std::error::Error>> {
+        let long_run = vec![b'x'; 5000];
+        let mut mixed = noise(300, 7);
+        mixed.extend_from_slice(&long_run);
+        mixed.extend(noise(20, 9));
+        let inputs = [Vec::new(), b"abc".to_vec(), document(1), noise(4096, 1), long_run, mixed];
+        let storage = storage::StandaloneStorage::in_memory_with_options(StorageOptions {
+            compress_threshold: Some(0),
+            ..StorageOptions::default()
+        });
+        storage.write(inputs.iter().enumerate().map(|(i, input)| put(&format!("v{}", i), input.clone())).collect())?;
+
+        let reader = storage.reader()?;
+        for (i, input) in inputs.iter().enumerate() {
+            assert_eq!(reader.get_cf("docs", format!("v{}", i).as_bytes())?, Some(input.clone()));
+        }
+        drop(reader);
+        // 压缩后不变小的值（空值、短值、噪声）按原样保存
+        assert_eq!(storage.compression_stats()?.compressed_values, 3);
+
+        let doc = storage::StandaloneStorage::in_memory_with_options(StorageOptions {
+            compress_threshold: Some(0),
+            ..StorageOptions::default()
+        });
+        doc.write(vec![put("doc", document(2))])?;
+        let stats = doc.compression_stats()?;
+        assert!(stats.physical_bytes * 5 < stats.logical_bytes, "{:?}", stats);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_values_round_trip_and_persist() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("compression");
+        let clock = Arc::new(MockClock::new(0));
+        let options = StorageOptions {
+            compress_threshold: Some(256),
+            checksums: true,
+            clock: clock.clone(),
+            ..StorageOptions::default()
+        };
+        let storage = storage::StandaloneStorage::open_with_options(&path, options.clone())?;
+        storage.write(vec![put("doc", document(1)), put("noise", noise(1024, 3)), put("small", b"{}".to_vec())])?;
+
+        // 只有可压缩且超过阈值的值被压缩
+        let stats = storage.compression_stats()?;
+        assert_eq!(stats.compressed_values, 1);
+        assert!(stats.physical_bytes + document(1).len() / 2 < stats.logical_bytes, "{:?}", stats);
+        assert_eq!(stats.physical_bytes, storage.memory_usage()?);
+        let usage = storage.cf_usage()?;
+        assert_eq!(usage[0].1.logical_bytes, stats.logical_bytes);
+
+        let reader = storage.reader()?;
+        assert_eq!(reader.get_cf("docs", b"doc")?, Some(document(1)));
+        assert_eq!(reader.get_cf("docs", b"noise")?, Some(noise(1024, 3)));
+        let scanned = reader.scan_cf("docs", b"", None, 10, None)?;
+        assert_eq!(scanned[0], (b"doc".to_vec(), document(1)));
+        drop(reader);
+        assert!(storage.verify(None)?.is_empty());
+        assert_eq!(storage.get_set("docs", b"doc", document(2))?, Some(document(1)));
+
+        // 过期时间与压缩互不影响
+        storage.expire("docs", b"doc", Duration::from_secs(1))?;
+        assert_eq!(storage.ttl("docs", b"doc")?, Some(Duration::from_secs(1)));
+
+        // 磁盘上保存未压缩的值，关闭压缩后仍然可以读取
+        storage.flush()?;
+        drop(storage);
+        let plain = storage::StandaloneStorage::open_with_options(&path, StorageOptions { compress_threshold: None, ..options.clone() })?;
+        assert_eq!(plain.reader()?.get_cf("docs", b"doc")?, Some(document(2)));
+        assert_eq!(plain.compression_stats()?.compressed_values, 0);
+        drop(plain);
+
+        let reopened = storage::StandaloneStorage::open_with_options(&path, options)?;
+        assert_eq!(reopened.compression_stats()?.compressed_values, 1);
+        assert_eq!(reopened.reader()?.get_cf("docs", b"doc")?, Some(document(2)));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(reopened.reader()?.get_cf("docs", b"doc")?, None);
+        drop(reopened);
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_shows_compression_savings() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            storage_options: StorageOptions { compress_threshold: Some(256), ..StorageOptions::default() },
+            ..ServerConfig::default()
+        };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        for i in 0..10 {
+            let doc = String::from_utf8(document(i))?;
+            client.put("docs", &format!("d{}", i), &doc)?;
+        }
+        assert_eq!(client.get("docs", "d3")?, Some(String::from_utf8(document(3))?));
+
+        let stats = client.compression_stats()?;
+        assert_eq!(stats.compressed_values, 10);
+        assert!(stats.physical_bytes * 3 < stats.logical_bytes, "{:?}", stats);
+        let cf = client.cf_info()?.into_iter().find(|cf| cf.name == "docs").unwrap();
+        assert_eq!((cf.bytes, cf.logical_bytes), (stats.physical_bytes, stats.logical_bytes));
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::errorlog::ErrorCategory;
+use tinykv_rs::protocol::{self, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::testing::TestServer;
+
+use std::io::Read;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: usize = 4;
+
+    fn start(exempt_localhost: bool) -> Result<TestServer, Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_connections_per_ip: Some(LIMIT), exempt_localhost, ..ServerConfig::default() };
+        TestServer::start_with_config(config)
+    }
+
+    /// 读取服务器在关闭前发来的唯一一条响应
+    fn rejection(addr: &str) -> Result<Response, Box<dyn std::error::Error>> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut pending = Vec::new();
+        let response = protocol::read_message::<Response, _>(&mut stream, &mut pending)?.expect("response");
+        // 随后连接被关闭
+        assert_eq!(stream.read(&mut [0u8; 16])?, 0);
+        Ok(response)
+    }
+
+    #[test]
+    fn test_connections_over_the_limit_are_rejected() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = start(false)?;
+        // TestServer 自己的连接占用一个名额
+        let mut clients: Vec<KvClient> = (1..LIMIT).map(|_| server.connect()).collect::<Result<_, _>>()?;
+
+        let error = server.connect().err().expect("connection over the limit").to_string();
+        assert!(error.contains("per-ip connection limit"), "{}", error);
+        for _ in 0..20 {
+            let response = rejection(server.addr())?;
+            assert!(matches!(&response, Response::Error(e) if e.starts_with("TooManyConnections: per-ip connection limit")), "{:?}", response);
+        }
+
+        // 已有的连接不受影响
+        for (i, client) in clients.iter_mut().enumerate() {
+            client.put("cf", &format!("k{}", i), "v")?;
+        }
+        let admin = server.client();
+        assert_eq!(admin.get("cf", "k0")?, Some("v".to_string()));
+        assert_eq!(admin.connections_rejected()?, 21);
+        assert_eq!(admin.clients()?.len(), LIMIT);
+        let errors = admin.recent_errors(100)?;
+        let rejected = errors.iter().filter(|e| e.category == ErrorCategory::Connection && e.message.contains("per-ip connection limit")).count();
+        assert_eq!(rejected, 21, "{:?}", errors);
+
+        // 关闭一个连接后名额释放；注销在连接线程中进行，重试直到成功
+        drop(clients.pop());
+        let started = Instant::now();
+        let mut reconnected = loop {
+            match server.connect() {
+                Ok(client) => break client,
+                Err(e) if started.elapsed() < Duration::from_secs(5) => {
+                    assert!(e.to_string().contains("per-ip connection limit"), "{}", e);
+                    thread::sleep(Duration::from_millis(20));
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        reconnected.put("cf", "again", "v")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_localhost_can_be_exempted() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = start(true)?;
+        let mut clients: Vec<KvClient> = (0..LIMIT * 3).map(|_| server.connect()).collect::<Result<_, _>>()?;
+        for client in &mut clients {
+            client.put("cf", "k", "v")?;
+        }
+        let admin = server.client();
+        assert_eq!(admin.connections_rejected()?, 0);
+        assert_eq!(admin.server_info()?.limits.max_connections_per_ip, Some(LIMIT));
+        Ok(())
+    }
+}
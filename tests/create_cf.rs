@@ -0,0 +1,182 @@
+use tinykv_rs::client::{KvClient, KvError};
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{self, Command, Response};
+use tinykv_rs::server::{KvServer, ServerConfig};
+use tinykv_rs::storage::{self, CfOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict() -> ServerConfig {
+        ServerConfig { strict_cf_mode: true, ..ServerConfig::default() }
+    }
+
+    #[test]
+    fn test_strict_mode_requires_create_cf() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::with_config(strict())?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+        let err = client.put("users", "k", "v").unwrap_err();
+        assert!(err.to_string().contains("UnknownCf: users"), "{}", err);
+        // 读请求不受严格模式影响
+        assert_eq!(client.get("users", "k")?, None);
+
+        client.create_cf("users", None)?;
+        client.put("users", "k", "v")?;
+        assert_eq!(client.get("users", "k")?, Some("v".to_string()));
+
+        let err = client.create_cf("users", None).unwrap_err();
+        assert!(err.to_string().contains("CfExists"), "{}", err);
+
+        // 其他数据库中的同名列族需要单独创建
+        client.use_db("app")?;
+        assert!(client.put("users", "k", "v").is_err());
+        client.create_cf("users", None)?;
+        client.put("users", "k", "v")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_permissive_mode_creates_cf_on_first_write() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+        client.put("users", "k", "v")?;
+        client.create_cf("empty", Some(CfOptions { keep_versions: 2, ..CfOptions::default() }))?;
+        assert!(client.create_cf("users", None).is_err());
+
+        let info = client.cf_info()?;
+        let names: Vec<(&str, usize)> = info.iter().map(|c| (c.name.as_str(), c.keys)).collect();
+        assert_eq!(names, vec![("empty", 0), ("users", 1)]);
+        assert!(info.iter().all(|c| c.created_at_ms > 0));
+        // 列族数包括还没有数据的列族
+        assert_eq!(client.info()?, (1, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cf_names_are_validated() -> Result<(), Box<dyn std::error::Error>> {
+        assert!(protocol::validate_cf_name("users").is_ok());
+        assert!(protocol::validate_cf_name("用户").is_ok());
+        assert!(protocol::validate_cf_name("").is_err());
+        assert!(protocol::validate_cf_name("a_b").is_err());
+        assert!(protocol::validate_cf_name("a/b").is_err());
+        assert!(protocol::validate_cf_name(&"x".repeat(protocol::MAX_CF_NAME_LEN)).is_ok());
+        assert!(protocol::validate_cf_name(&"x".repeat(protocol::MAX_CF_NAME_LEN + 1)).is_err());
+
+        // 客户端在发送前拒绝，连接保持可用
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+        assert!(client.create_cf("a_b", None).is_err());
+        // 空列族名表示默认列族
+        client.put("", "k", "v")?;
+        assert_eq!(client.get(protocol::DEFAULT_CF, "k")?, Some("v".to_string()));
+        client.put("ok", "k", "v")?;
+
+        // 服务端同样校验
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = Session::default();
+        let cmd = Command::CreateCf { cf: "a_b".to_string(), options: None };
+        assert!(matches!(api.handle_command(&mut session, cmd), Response::Error(e) if e.contains("'_'")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_creation_time_survives_reopen() {
+        let path = temp_dir("create_cf_reopen");
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        storage.create_cf("empty", None).unwrap();
+        storage
+            .write(vec![protocol::Modify::new_put("users".to_string(), b"k".to_vec(), b"v".to_vec())])
+            .unwrap();
+        let created = storage.cf_created().unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(storage.cf_created().unwrap(), created);
+        assert!(storage.cf_exists("empty").unwrap());
+
+        // 整理后创建时间保存在基础快照中
+        storage.compact().unwrap();
+        drop(storage);
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(storage.cf_created().unwrap(), created);
+    }
+
+    #[test]
+    fn test_list_cfs_pages_by_name() {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        for i in 0..25 {
+            storage.create_cf(&format!("cf{:02}", i), None).unwrap();
+        }
+        storage.create_cf("app/other", None).unwrap();
+        let api = RawKeyValueApi::new(storage);
+        let mut session = Session::default();
+
+        let mut pages = Vec::new();
+        let mut start_after = None;
+        loop {
+            match api.handle_command(&mut session, Command::ListCfs { start_after: start_after.clone(), limit: 10 }) {
+                Response::CfList { cfs, next } => {
+                    pages.push(cfs.iter().map(|c| c.name.clone()).collect::<Vec<_>>());
+                    match next {
+                        Some(next) => start_after = Some(next),
+                        None => break,
+                    }
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![10, 10, 5]);
+        assert_eq!(pages[1][0], "cf10");
+        assert_eq!(pages[2].last().unwrap(), "cf24");
+
+        // 游标可以落在不存在的列族上；其他数据库的列族不出现
+        match api.handle_command(&mut session, Command::ListCfs { start_after: Some("cf235".into()), limit: 10 }) {
+            Response::CfList { cfs, next } => {
+                assert_eq!(cfs.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["cf24"]);
+                assert_eq!(next, None);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // 空页无法翻页，limit 为 0 是错误而不是空结果
+        match api.handle_command(&mut session, Command::ListCfs { start_after: None, limit: 0 }) {
+            Response::Error(e) => assert!(e.starts_with("InvalidLimit"), "{}", e),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        match api.handle_command(&mut session, Command::InfoSummary) {
+            Response::Info { cf_info, column_families, cf_count, .. } => {
+                assert!(cf_info.is_empty() && column_families.is_empty());
+                assert_eq!(cf_count, 25);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_client_iterates_cfs() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+        for i in 0..12 {
+            client.put(&format!("cf{:02}", i), "k", "v")?;
+        }
+
+        let names: Vec<String> = client.iter_cfs(5).map(|cf| cf.map(|c| c.name)).collect::<Result<_, _>>()?;
+        assert_eq!(names, (0..12).map(|i| format!("cf{:02}", i)).collect::<Vec<_>>());
+        // 恰好取完时没有下一页
+        let (first, next) = client.list_cfs(None, 12)?;
+        assert_eq!((first.len(), next), (12, None));
+        let error = client.list_cfs(None, 0).unwrap_err();
+        assert_eq!(error.downcast_ref::<KvError>().and_then(KvError::code), Some("InvalidLimit"));
+        assert_eq!(client.iter_cfs(0).count(), 12);
+        assert_eq!(client.info()?, (12, 12));
+        Ok(())
+    }
+}
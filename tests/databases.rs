@@ -0,0 +1,72 @@
+use tinykv_rs::storage;
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{Bytes, Command, Response};
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(api: &RawKeyValueApi, session: &mut Session, cf: &str, key: &str, value: &str) -> Response {
+        api.handle_command(session, Command::Put {
+            cf: cf.to_string(),
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+        })
+    }
+
+    fn get(api: &RawKeyValueApi, session: &mut Session, cf: &str, key: &str) -> Option<Vec<u8>> {
+        match api.handle_command(session, Command::Get { cf: cf.to_string(), key: key.as_bytes().to_vec() }) {
+            Response::Value(v) => v.map(|Bytes(b)| b),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_databases_are_isolated() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut default = Session::default();
+        let mut app = Session::default();
+        assert!(matches!(api.handle_command(&mut app, Command::UseDb { name: "app1".to_string() }), Response::Ok));
+
+        put(&api, &mut default, "users", "u1", "default-bob");
+        put(&api, &mut app, "users", "u1", "app-bob");
+
+        assert_eq!(get(&api, &mut default, "users", "u1"), Some(b"default-bob".to_vec()));
+        assert_eq!(get(&api, &mut app, "users", "u1"), Some(b"app-bob".to_vec()));
+        // 默认数据库保持原有的存储编码，旧客户端直接可见
+        assert_eq!(api.raw_get("users", b"u1").unwrap(), Some(b"default-bob".to_vec()));
+
+        // 不能通过列族名越过数据库边界
+        assert!(matches!(put(&api, &mut default, "app1/users", "u1", "x"), Response::Error(_)));
+
+        match api.handle_command(&mut app, Command::Info) {
+            Response::Info { total_keys, column_families, databases, .. } => {
+                assert_eq!(total_keys, 1);
+                // 每个数据库都列出默认列族
+                assert_eq!(column_families, vec!["default".to_string(), "users".to_string()]);
+                assert_eq!(databases.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), vec!["app1", "default"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_drop_db() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = Session::default();
+        api.handle_command(&mut session, Command::UseDb { name: "tmp".to_string() });
+        put(&api, &mut session, "cf", "k", "v");
+
+        let mut guest = Session { is_admin: false, ..Session::default() };
+        assert!(matches!(api.handle_command(&mut guest, Command::DropDb { name: "tmp".to_string(), dry_run: false }), Response::Error(_)));
+        assert!(matches!(api.handle_command(&mut session, Command::DropDb { name: "default".to_string(), dry_run: false }), Response::Error(_)));
+
+        assert!(matches!(api.handle_command(&mut session, Command::DropDb { name: "tmp".to_string(), dry_run: false }), Response::Ok));
+        assert_eq!(get(&api, &mut session, "cf", "k"), None);
+        match api.handle_command(&mut session, Command::ListDbs) {
+            Response::Databases(dbs) => assert_eq!(dbs.len(), 1),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::client::KvClient;
+use tinykv_rs::deadline::{Deadline, DeadlineWriter};
+use tinykv_rs::protocol::{self, Bytes, Command, Envelope, Modify, Request, Response, ValueFilter};
+use tinykv_rs::storage::StandaloneStorage;
+use tinykv_rs::testing::TestServer;
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> Vec<u8> {
+        format!("k{:07}", i).into_bytes()
+    }
+
+    /// count 个值都为 "other" 的键
+    fn populate(storage: &StandaloneStorage, count: usize) {
+        for chunk_start in (0..count).step_by(100_000) {
+            let ops = (chunk_start..(chunk_start + 100_000).min(count))
+                .map(|i| Modify::new_put("logs".to_string(), key(i), b"other".to_vec()))
+                .collect();
+            storage.write(ops).unwrap();
+        }
+    }
+
+    fn is_deadline_exceeded(response: &Response) -> bool {
+        matches!(response, Response::Error(e) if e.starts_with("DeadlineExceeded"))
+    }
+
+    #[test]
+    fn test_scan_with_tiny_deadline_gives_up_promptly() {
+        let storage = Arc::new(StandaloneStorage::in_memory());
+        populate(&storage, 1_000_000);
+        let api = RawKeyValueApi::new(storage);
+        let mut session = api.new_session();
+
+        // 过滤条件不匹配任何条目，没有截止时间时要检查完整个列族
+        let scan = Command::Scan {
+            cf: "logs".to_string(),
+            start_key: Vec::new(),
+            end_key: None,
+            limit: 10,
+            filter: Some(ValueFilter::Prefix(b"match".to_vec())),
+            start_bound: None,
+            end_bound: None,
+        };
+        let started = Instant::now();
+        let response = api.handle_command_with_deadline(&mut session, scan, Deadline::after(Duration::from_millis(20)));
+        assert!(is_deadline_exceeded(&response), "{:?}", response);
+        assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+
+        let started = Instant::now();
+        let scan_all = Command::ScanAll { start: None, limit: 2_000_000 };
+        let response = api.handle_command_with_deadline(&mut session, scan_all, Deadline::after(Duration::from_millis(20)));
+        assert!(is_deadline_exceeded(&response), "{:?}", response);
+        assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+
+        // 截止时间足够时结果与不带截止时间相同
+        let scan = Command::new_scan("logs".to_string(), key(500_000), Some(key(500_003)), 10);
+        let response = api.handle_command_with_deadline(&mut session, scan, Deadline::after(Duration::from_secs(60)));
+        let Response::Values(values) = response else { panic!("unexpected {:?}", response) };
+        assert_eq!(values.into_iter().map(|(Bytes(k), _)| k).collect::<Vec<_>>(), vec![key(500_000), key(500_001), key(500_002)]);
+    }
+
+    #[test]
+    fn test_expired_deadline_skips_execution() {
+        let storage = Arc::new(StandaloneStorage::in_memory());
+        let api = RawKeyValueApi::new(Arc::clone(&storage));
+        let mut session = api.new_session();
+        let expired = Deadline::after(Duration::ZERO);
+
+        let put = Command::Put { cf: "cf".to_string(), key: b"k".to_vec(), value: b"v".to_vec() };
+        let response = api.handle_command_with_deadline(&mut session, put, expired);
+        assert!(is_deadline_exceeded(&response), "{:?}", response);
+        assert_eq!(storage.reader().unwrap().get_cf("cf", b"k").unwrap(), None);
+        assert!(is_deadline_exceeded(&api.handle_command_with_deadline(&mut session, Command::Compact, expired)));
+
+        // 备份逐行写出，过期后下一行的写入失败
+        let error = storage.backup_to(DeadlineWriter::new(Vec::new(), expired, "Backup")).unwrap_err();
+        assert!(error.contains("DeadlineExceeded"), "{}", error);
+        assert!(storage.backup_to(DeadlineWriter::new(Vec::new(), Deadline::NONE, "Backup")).is_ok());
+    }
+
+    #[test]
+    fn test_server_reads_deadline_from_the_request() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let mut stream = TcpStream::connect(server.addr())?;
+        let mut pending = Vec::new();
+        let put = Command::Put { cf: "cf".to_string(), key: b"k".to_vec(), value: b"v".to_vec() };
+
+        let mut send = |deadline_ms: Option<u64>| -> Result<Response, Box<dyn std::error::Error>> {
+            stream.write_all(&serde_json::to_vec(&Request { cmd: &put, deadline_ms })?)?;
+            Ok(protocol::read_message::<Response, _>(&mut stream, &mut pending)?.expect("response"))
+        };
+        let response = send(Some(0))?;
+        assert!(is_deadline_exceeded(&response), "{:?}", response);
+        assert!(matches!(send(Some(60_000))?, Response::Ok));
+        // 不带截止时间的请求与单独的命令编码相同
+        assert_eq!(serde_json::to_string(&Request { cmd: &put, deadline_ms: None })?, serde_json::to_string(&put)?);
+        assert!(matches!(send(None)?, Response::Ok));
+        Ok(())
+    }
+
+    /// 回应握手和任意命令的服务器，把每个请求中的 deadline_ms 发到返回的通道
+    fn recording_server() -> (String, mpsc::Receiver<Option<u64>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut pending = Vec::new();
+            let mut envelope = false;
+            while let Ok(Some(request)) = protocol::read_message::<Request, _>(&mut stream, &mut pending) {
+                let bytes = match request.cmd {
+                    Command::Hello { features, .. } => {
+                        envelope = features.iter().any(|f| f == "envelope");
+                        let hello = Response::Hello { server_version: protocol::PROTOCOL_VERSION, accepted_features: features };
+                        serde_json::to_vec(&hello).unwrap()
+                    }
+                    _ => {
+                        tx.send(request.deadline_ms).unwrap();
+                        match envelope {
+                            true => serde_json::to_vec(&Envelope { resp: Response::Ok, server_us: 0, warnings: Vec::new() }).unwrap(),
+                            false => serde_json::to_vec(&Response::Ok).unwrap(),
+                        }
+                    }
+                };
+                stream.write_all(&bytes).unwrap();
+            }
+        });
+        (addr, rx)
+    }
+
+    #[test]
+    fn test_client_sends_its_timeout_as_the_deadline() -> Result<(), Box<dyn std::error::Error>> {
+        let (addr, deadlines) = recording_server();
+        let mut client = KvClient::connect_with_timeout(&addr, Duration::from_millis(1500))?;
+
+        // 没有协商 envelope 时不附带
+        client.put("cf", "k", "v")?;
+        assert_eq!(deadlines.recv()?, None);
+
+        client.enable_envelope()?;
+        client.put("cf", "k", "v")?;
+        assert_eq!(deadlines.recv()?, Some(1500));
+        client.set_timeout(Duration::from_secs(3))?;
+        client.put("cf", "k", "v")?;
+        assert_eq!(deadlines.recv()?, Some(3000));
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{BatchMode, Bytes, Command, Modify, Response, DEFAULT_CF};
+use tinykv_rs::storage;
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::fs;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(api: &RawKeyValueApi, session: &mut Session, cf: &str, key: &str) -> Response {
+        api.handle_command(session, Command::Get { cf: cf.to_string(), key: key.as_bytes().to_vec() })
+    }
+
+    #[test]
+    fn test_empty_cf_uses_session_default() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = api.new_session();
+        api.raw_put("users".to_string(), b"u1".to_vec(), b"alice".to_vec()).unwrap();
+        api.raw_put("orders".to_string(), b"u1".to_vec(), b"o-1".to_vec()).unwrap();
+        api.raw_put(DEFAULT_CF.to_string(), b"u1".to_vec(), b"d-1".to_vec()).unwrap();
+
+        // 没有设置默认列族时使用 DEFAULT_CF
+        assert!(matches!(get(&api, &mut session, "", "u1"), Response::Value(Some(Bytes(v))) if v == b"d-1"));
+        let invalid = api.handle_command(&mut session, Command::UseCf { cf: "a_b".to_string() });
+        assert!(matches!(invalid, Response::Error(_)));
+
+        assert!(matches!(api.handle_command(&mut session, Command::UseCf { cf: "users".to_string() }), Response::Ok));
+        assert!(matches!(get(&api, &mut session, "", "u1"), Response::Value(Some(Bytes(v))) if v == b"alice"));
+        // 显式的列族名不受默认列族影响
+        assert!(matches!(get(&api, &mut session, "orders", "u1"), Response::Value(Some(Bytes(v))) if v == b"o-1"));
+
+        // 中途切换默认列族，Batch 中的空列族名同样替换
+        assert!(matches!(api.handle_command(&mut session, Command::UseCf { cf: "orders".to_string() }), Response::Ok));
+        assert!(matches!(get(&api, &mut session, "", "u1"), Response::Value(Some(Bytes(v))) if v == b"o-1"));
+        let ops = vec![Modify::new_put(String::new(), b"o2".to_vec(), b"o-2".to_vec())];
+        assert!(matches!(api.handle_command(&mut session, Command::Batch { ops, mode: BatchMode::Atomic }), Response::Ok));
+        assert_eq!(api.raw_get("orders", b"o2").unwrap(), Some(b"o-2".to_vec()));
+
+        // 默认列族属于连接，其他会话不受影响
+        let mut other = api.new_session();
+        assert!(matches!(get(&api, &mut other, "", "u1"), Response::Value(Some(Bytes(v))) if v == b"d-1"));
+    }
+
+    #[test]
+    fn test_client_use_cf() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        assert_eq!(client.get("", "k")?, None);
+
+        client.use_cf("users")?;
+        client.put("", "u1", "alice")?;
+        assert_eq!(client.get("users", "u1")?, Some("alice".to_string()));
+        client.use_cf("orders")?;
+        client.put("", "u1", "o-1")?;
+        assert_eq!(client.get("", "u1")?, Some("o-1".to_string()));
+        assert_eq!(client.get("users", "u1")?, Some("alice".to_string()));
+        assert_eq!(client.scan("", "", None, 10)?, vec![("u1".to_string(), "o-1".to_string())]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_cf_and_default_address_the_same_entry() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.put("", "k", "empty")?;
+        assert_eq!(client.get(DEFAULT_CF, "k")?, Some("empty".to_string()));
+        client.put(DEFAULT_CF, "k", "named")?;
+        assert_eq!(client.get_default("k")?, Some("named".to_string()));
+        client.put_default("k2", "v")?;
+        assert_eq!(client.scan(DEFAULT_CF, "", None, 10)?.len(), 2);
+        client.delete_default("k")?;
+        assert_eq!(client.get("", "k")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_always_lists_default_cf() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = api.new_session();
+        api.raw_put("users".to_string(), b"u1".to_vec(), b"alice".to_vec()).unwrap();
+        match api.handle_command(&mut session, Command::Info) {
+            Response::Info { column_families, cf_info, cf_count, .. } => {
+                assert_eq!(column_families, vec!["default".to_string(), "users".to_string()]);
+                let names: Vec<(&str, usize)> = cf_info.iter().map(|c| (c.name.as_str(), c.keys)).collect();
+                assert_eq!(names, vec![("default", 0), ("users", 1)]);
+                // 没有创建的默认列族不计入列族数
+                assert_eq!(cf_count, 1);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_legacy_keys_without_cf_move_to_default() -> Result<(), String> {
+        let dir = temp_dir("default_cf_legacy");
+        // 旧的 `cf_key` 编码中列族为空的键以 '_' 开头
+        let legacy = serde_json::json!({ "entries": [[b"_old".to_vec(), b"value".to_vec()], [b"users_u1".to_vec(), b"alice".to_vec()]] });
+        fs::write(dir.join("data.json"), legacy.to_string()).unwrap();
+
+        let storage = storage::StandaloneStorage::open(&dir)?;
+        let reader = storage.reader()?;
+        assert_eq!(reader.get_cf(DEFAULT_CF, b"old")?, Some(b"value".to_vec()));
+        assert_eq!(reader.get_cf("users", b"u1")?, Some(b"alice".to_vec()));
+        drop(reader);
+        drop(storage);
+        Ok(())
+    }
+}
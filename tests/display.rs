@@ -0,0 +1,61 @@
+use tinykv_rs::protocol::{self, Bytes, Command, DISPLAY_BYTES_LIMIT, Response};
+use tinykv_rs::storage::StandaloneStorage;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_bytes_escapes_non_printable() {
+        assert_eq!(protocol::display_bytes(b"plain text"), "plain text");
+        assert_eq!(protocol::display_bytes(b"line1\nline2\r\t"), r"line1\x0aline2\x0d\x09");
+        assert_eq!(protocol::display_bytes(b"a\0b"), r"a\x00b");
+        assert_eq!(protocol::display_bytes(b"\xff\xfe\x80"), r"\xff\xfe\x80");
+        assert_eq!(protocol::display_bytes(b"\x1b[31m"), r"\x1b[31m");
+        // 反斜杠本身转义，输出没有歧义
+        assert_eq!(protocol::display_bytes(br"\x00"), r"\\x00");
+        // 非 ASCII 的 UTF-8 同样按字节转义
+        assert_eq!(protocol::display_bytes("é".as_bytes()), r"\xc3\xa9");
+    }
+
+    #[test]
+    fn test_display_bytes_truncates_long_values() {
+        let exact = vec![b'a'; DISPLAY_BYTES_LIMIT];
+        assert_eq!(protocol::display_bytes(&exact), "a".repeat(DISPLAY_BYTES_LIMIT));
+
+        let long = vec![b'a'; 1000];
+        let shown = protocol::display_bytes(&long);
+        assert_eq!(shown, format!("{}...(1000 bytes)", "a".repeat(DISPLAY_BYTES_LIMIT)));
+    }
+
+    #[test]
+    fn test_command_display_is_printable() {
+        let cmd = Command::Put { cf: "bin".to_string(), key: b"k\n\0".to_vec(), value: vec![0xff; 200] };
+        let line = cmd.to_string();
+        assert!(line.chars().all(|c| c.is_ascii_graphic() || c == ' '), "{}", line);
+        assert!(line.starts_with(r"Put(cf: bin, key: k\x0a\x00, value: \xff\xff"), "{}", line);
+        assert!(line.ends_with("...(200 bytes))"), "{}", line);
+
+        // 存储层的错误信息同样转义键
+        let storage = StandaloneStorage::in_memory();
+        let err = storage.rename("cf", b"\x00\n", b"b", false).unwrap_err();
+        assert!(err.contains(r"\x00\x0a"), "{}", err);
+    }
+
+    #[test]
+    fn test_response_display_hides_values() {
+        let secret = || Bytes(b"secret".to_vec());
+        assert_eq!(Response::Value(Some(secret())).to_string(), "Value(bytes: 6)");
+        assert_eq!(Response::Values(vec![(Bytes(b"k".to_vec()), secret()); 3]).to_string(), "Values(entries: 3, bytes: 21)");
+        let page = Response::CfValues { entries: vec![("cf".to_string(), secret(), secret())], next: Some(("cf".to_string(), secret())), truncated: false };
+        assert_eq!(page.to_string(), "CfValues(entries: 1, bytes: 12, more: true)");
+        assert_eq!(Response::Keys(vec![secret()]).to_string(), "Keys(count: 1)");
+        let chunk = Response::Chunk { part: Box::new(Response::Values(vec![(secret(), secret())])), more: true };
+        assert_eq!(chunk.to_string(), "Chunk(Values(entries: 1, bytes: 12), more: true)");
+        // 错误消息可能包含键，只输出错误码
+        assert_eq!(Response::Error("UnknownCf: secret".to_string()).to_string(), "Error(UnknownCf)");
+        assert_eq!(Response::Error("secret is missing".to_string()).to_string(), "Error(-)");
+        assert_eq!(protocol::error_code("Invalid value: x"), None);
+        assert_eq!(Response::BatchResults(vec![Ok(()), Err("secret".to_string())]).to_string(), "BatchResults(ops: 2, failed: 1)");
+    }
+}
@@ -0,0 +1,228 @@
+use tinykv_rs::doctor::{self, Diagnostic, Severity};
+use tinykv_rs::lockfile::{self, LockStatus};
+use tinykv_rs::migration;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage::{self, StandaloneStorage};
+use tinykv_rs::testing::{temp_dir, TempDir};
+
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 写入三次刷盘并整理一次，目录中有基础快照和一个三条记录的段文件
+    fn populated(name: &str) -> (TempDir, PathBuf) {
+        let path = temp_dir(&format!("doctor_{}", name));
+        let storage = StandaloneStorage::open(&path).unwrap();
+        storage.write(vec![Modify::new_put("cf".to_string(), b"base".to_vec(), b"v".to_vec())]).unwrap();
+        storage.compact().unwrap();
+        for key in ["a", "b", "c"] {
+            storage.write(vec![Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), b"v".to_vec())]).unwrap();
+            storage.flush().unwrap();
+        }
+        drop(storage);
+        let segment = files_with_prefix(&path, "segment-").pop().unwrap();
+        (path, segment)
+    }
+
+    fn files_with_prefix(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.file_name().unwrap().to_string_lossy().starts_with(prefix))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn rewrite_line(segment: &Path, index: usize, content: &str) {
+        let text = fs::read_to_string(segment).unwrap();
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        lines[index] = content.to_string();
+        fs::write(segment, lines.join("\n") + "\n").unwrap();
+    }
+
+    fn find<'a>(diagnostics: &'a [Diagnostic], check: &str, severity: Severity) -> Option<&'a Diagnostic> {
+        diagnostics.iter().find(|d| d.check == check && d.severity == severity)
+    }
+
+    fn free_addr() -> String {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_healthy_directory_has_no_errors() {
+        let (path, _) = populated("healthy");
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(!doctor::has_errors(&diagnostics), "{:?}", diagnostics);
+        for check in ["directory", "disk_space", "lock", "format_version", "data_files", "address", "clock"] {
+            assert!(diagnostics.iter().any(|d| d.check == check), "missing {}: {:?}", check, diagnostics);
+        }
+        // 快照和段文件分别报告条目数
+        let files: Vec<&Diagnostic> = diagnostics.iter().filter(|d| d.check == "data_files").collect();
+        assert_eq!(files.len(), 2, "{:?}", files);
+        assert!(files.iter().any(|d| d.message.contains("1 entries")), "{:?}", files);
+        assert!(files.iter().any(|d| d.message.contains("3 records")), "{:?}", files);
+    }
+
+    #[test]
+    fn test_missing_directory_will_be_created() {
+        let dir = temp_dir("doctor_missing");
+        let path = dir.join("nested");
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(!doctor::has_errors(&diagnostics), "{:?}", diagnostics);
+        assert!(find(&diagnostics, "directory", Severity::Info).unwrap().message.contains("will be created"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_read_only_directory_is_an_error() {
+        let path = temp_dir("doctor_read_only");
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions.clone()).unwrap();
+
+        // root 不受目录权限限制，此时改用内核提供的只读目录
+        let target = if fs::write(path.join("probe"), b"x").is_ok() { PathBuf::from("/proc/self") } else { path.to_path_buf() };
+        let diagnostics = doctor::diagnose(Some(&target), &free_addr());
+        let error = find(&diagnostics, "directory", Severity::Error).expect("directory error");
+        assert!(error.message.contains("not writable"), "{}", error);
+        assert!(error.fix.as_deref().unwrap().contains("write access"));
+        assert!(doctor::has_errors(&doctor::startup_checks(&target)));
+
+        // 不存在的子目录也无法创建
+        let diagnostics = doctor::diagnose(Some(&target.join("sub")), &free_addr());
+        assert!(find(&diagnostics, "directory", Severity::Error).unwrap().message.contains("cannot be created"));
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+    }
+
+    #[test]
+    fn test_file_in_place_of_directory_is_an_error() {
+        let dir = temp_dir("doctor_file");
+        let path = dir.join("file");
+        fs::write(&path, b"not a directory").unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(find(&diagnostics, "directory", Severity::Error).unwrap().message.contains("not a directory"));
+    }
+
+    #[test]
+    fn test_occupied_port_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let diagnostics = doctor::diagnose(None, &addr);
+        let error = find(&diagnostics, "address", Severity::Error).expect("address error");
+        assert!(error.message.contains("already in use"), "{}", error);
+
+        // 纯内存模式只检查地址和时钟
+        assert!(diagnostics.iter().all(|d| d.check == "address" || d.check == "clock"), "{:?}", diagnostics);
+        drop(listener);
+        assert!(!doctor::has_errors(&doctor::diagnose(None, &addr)));
+        assert!(doctor::has_errors(&doctor::diagnose(None, "not an address")));
+    }
+
+    #[test]
+    fn test_corrupt_segment_record_is_an_error() {
+        let (path, segment) = populated("corrupt_segment");
+        rewrite_line(&segment, 1, "{\"keys\": [broken");
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        let error = find(&diagnostics, "data_files", Severity::Error).expect("data file error");
+        assert!(error.message.contains("1 of 3 records are corrupt (first at record 2)"), "{}", error);
+        assert!(error.fix.as_deref().unwrap().contains("salvage"));
+    }
+
+    #[test]
+    fn test_torn_last_record_is_a_warning() {
+        let (path, segment) = populated("torn");
+        rewrite_line(&segment, 2, "{\"sequence\":4,\"keys\":[{\"ke");
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(!doctor::has_errors(&diagnostics), "{:?}", diagnostics);
+        assert!(find(&diagnostics, "data_files", Severity::Warning).unwrap().message.contains("last of 3 records"));
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_and_missing_segment_are_errors() {
+        let (path, segment) = populated("corrupt_base");
+        let base = files_with_prefix(&path, "base-").pop().unwrap();
+        fs::write(&base, b"{\"entries\": [").unwrap();
+        fs::remove_file(&segment).unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        let errors: Vec<&Diagnostic> =
+            diagnostics.iter().filter(|d| d.check == "data_files" && d.severity == Severity::Error).collect();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].message.starts_with("Failed to parse base-"), "{}", errors[0]);
+        assert!(errors[1].message.starts_with("Failed to read segment"), "{}", errors[1]);
+
+        // 清单本身损坏
+        fs::write(path.join("MANIFEST"), b"garbage").unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(find(&diagnostics, "data_files", Severity::Error).unwrap().message.contains("manifest"));
+    }
+
+    #[test]
+    fn test_lock_held_by_open_storage_is_an_error() {
+        let path = temp_dir("doctor_locked");
+        let storage = StandaloneStorage::open(&path).unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        let error = find(&diagnostics, "lock", Severity::Error).expect("lock error");
+        assert!(error.message.contains(&format!("running pid {}", std::process::id())), "{}", error);
+        // 检查不会释放或抢占锁
+        assert!(matches!(lockfile::lock_status(&path).unwrap(), LockStatus::Held { owner_alive: true, .. }));
+        assert!(StandaloneStorage::open(&path).is_err());
+
+        drop(storage);
+        assert_eq!(lockfile::lock_status(&path).unwrap(), LockStatus::Free);
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(find(&diagnostics, "lock", Severity::Info).is_some(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_stale_lock_is_a_warning() {
+        let path = temp_dir("doctor_stale_lock");
+        fs::write(path.join(lockfile::LOCK_FILE), br#"{"pid":4194304,"acquired_at_ms":1}"#).unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(!doctor::has_errors(&diagnostics), "{:?}", diagnostics);
+        assert!(find(&diagnostics, "lock", Severity::Warning).unwrap().message.contains("pid 4194304"));
+    }
+
+    #[test]
+    fn test_newer_format_version_is_an_error() {
+        let (path, _) = populated("format");
+        fs::write(path.join(migration::FORMAT_VERSION_FILE), format!("{}", migration::FORMAT_VERSION + 1)).unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(find(&diagnostics, "format_version", Severity::Error).unwrap().message.contains("newer"));
+        // 启动时的简短检查也能发现，不解析数据文件
+        let startup = doctor::startup_checks(&path);
+        assert!(doctor::has_errors(&startup));
+        assert!(startup.iter().all(|d| d.check != "data_files" && d.check != "address"), "{:?}", startup);
+
+        fs::write(path.join(migration::FORMAT_VERSION_FILE), "garbage").unwrap();
+        let diagnostics = doctor::diagnose(Some(&path), &free_addr());
+        assert!(find(&diagnostics, "format_version", Severity::Error).unwrap().message.starts_with("InvalidFormatVersion"));
+    }
+
+    #[test]
+    fn test_inspect_data_files_counts_entries() {
+        let (path, _) = populated("inspect");
+        let checks = storage::inspect_data_files(&path).unwrap();
+        assert_eq!(checks.len(), 2);
+        assert_eq!((checks[0].entries, checks[0].records), (1, 0));
+        assert_eq!((checks[1].entries, checks[1].records), (3, 3));
+        assert!(checks.iter().all(|c| c.error.is_none() && c.corrupt_records.is_empty() && c.bytes > 0));
+        assert!(storage::inspect_data_files(&temp_dir("doctor_inspect_empty")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_report_format() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let report = doctor::diagnose(None, &addr).iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\n");
+        assert!(report.contains(&format!("[ERROR] address: {} is already in use\n        fix: ", addr)), "{}", report);
+        assert!(report.contains("[   OK] clock: "), "{}", report);
+    }
+}
@@ -0,0 +1,82 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::Bytes;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::StorageOptions;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(cf: &str, key: &str) -> Option<(String, Bytes)> {
+        Some((cf.to_string(), Bytes(key.as_bytes().to_vec())))
+    }
+
+    #[test]
+    fn test_drop_db_dry_run_matches_real_run() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let mut app = server.connect()?;
+        app.use_db("app")?;
+        for i in 0..20 {
+            app.put("users", &format!("u{:02}", i), "alice")?;
+        }
+        app.put("orders", "o1", "pending")?;
+        server.client().put("users", "u00", "default-db")?;
+
+        let client = server.client();
+        let plan = client.drop_db_dry_run("app")?;
+        assert_eq!(plan.keys, 21);
+        assert!(plan.bytes > 0);
+        // 按存储顺序，列族名不带数据库前缀
+        assert_eq!(plan.first, entry("orders", "o1"));
+        assert_eq!(plan.last, entry("users", "u19"));
+
+        // 试运行不修改任何数据，重复试运行结果相同
+        assert_eq!(app.get("users", "u05")?, Some("alice".to_string()));
+        assert_eq!(client.drop_db_dry_run("app")?, plan);
+        assert!(client.list_dbs()?.iter().any(|db| db.name == "app"));
+
+        client.drop_db("app")?;
+        assert_eq!(app.get("users", "u05")?, None);
+        assert_eq!(client.drop_db_dry_run("app")?.keys, 0);
+        assert_eq!(client.get("users", "u00")?, Some("default-db".to_string()));
+
+        assert!(client.drop_db_dry_run("default").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_purge_trash_dry_run_matches_real_run() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let mut server = TestServer::start_with_config(ServerConfig {
+            trash_retention: Some(Duration::from_secs(60)),
+            storage_options: StorageOptions { clock: clock.clone(), ..StorageOptions::default() },
+            ..ServerConfig::default()
+        })?;
+        let client = server.client();
+        for key in ["a", "b", "c"] {
+            client.put("users", key, "v")?;
+            client.delete("users", key)?;
+        }
+        clock.advance(Duration::from_secs(61));
+        client.put("users", "d", "v")?;
+        client.delete("users", "d")?;
+
+        // 只有超出保留期的三个条目会被清理，报告的是原来的列族（非默认数据库带前缀）和键
+        let plan = client.purge_trash_dry_run(false)?;
+        assert_eq!(plan.keys, 3);
+        assert_eq!(plan.first, entry("users", "a"));
+        assert_eq!(plan.last, entry("users", "c"));
+        assert_eq!(client.scan_trash(None, 10)?.len(), 4);
+        assert_eq!(client.purge_trash_dry_run(true)?.keys, 4);
+
+        assert_eq!(client.purge_trash(false)?, plan.keys);
+        assert_eq!(client.scan_trash(None, 10)?.len(), 1);
+        assert_eq!(client.purge_trash_dry_run(true)?.keys, client.purge_trash(true)?);
+        assert_eq!(client.purge_trash_dry_run(true)?, Default::default());
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use tinykv_rs::storage::{self, Durability, FileSystem, OsFileSystem, StorageOptions};
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::testing::temp_dir;
+
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// 记录所有调用并转发给真实文件系统
+#[derive(Debug, Default)]
+struct RecordingFs {
+    calls: Mutex<Vec<String>>,
+}
+
+impl RecordingFs {
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+impl FileSystem for RecordingFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.create_dir_all(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        let name = path.file_name().unwrap().to_string_lossy();
+        self.record(format!("write {} sync={}", name, sync));
+        OsFileSystem.write_file(path, data, sync)
+    }
+
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        let name = path.file_name().unwrap().to_string_lossy();
+        self.record(format!("append {} sync={}", name, sync));
+        OsFileSystem.append_file(path, data, sync)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record("rename".to_string());
+        OsFileSystem.rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name().unwrap().to_string_lossy();
+        self.record(format!("remove {}", name));
+        OsFileSystem.remove_file(path)
+    }
+
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        self.record("sync_dir".to_string());
+        OsFileSystem.sync_dir(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flush_with(durability: Durability, name: &str) -> (storage::FlushStats, Vec<String>) {
+        let path = temp_dir(name);
+        let fs = Arc::new(RecordingFs::default());
+        let options = StorageOptions { durability, fs: fs.clone(), ..StorageOptions::default() };
+        let storage = Arc::new(storage::StandaloneStorage::open_with_options(&path, options).unwrap());
+        let api = RawKeyValueApi::new(Arc::clone(&storage));
+        api.raw_put("default".to_string(), b"k".to_vec(), b"v".to_vec()).unwrap();
+
+        let stats = storage.flush().unwrap();
+        // 第二次刷盘只追加到已有的段文件
+        api.raw_put("default".to_string(), b"k2".to_vec(), b"v2".to_vec()).unwrap();
+        storage.flush().unwrap();
+
+        // 无论哪种级别，数据都能重新加载；重新打开前先释放数据目录锁
+        drop(api);
+        drop(storage);
+        let reopened = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(reopened.reader().unwrap().get_cf("default", b"k").unwrap(), Some(b"v".to_vec()));
+        assert_eq!(reopened.reader().unwrap().get_cf("default", b"k2").unwrap(), Some(b"v2".to_vec()));
+
+        (stats, fs.calls())
+    }
+
+    #[test]
+    fn test_fsync_durability_syncs_file_and_directory() {
+        let (stats, calls) = flush_with(Durability::Fsync, "durability_fsync");
+        assert!(stats.fsynced);
+        assert!(stats.bytes_written > 0);
+        assert_eq!(
+            calls,
+            vec![
+                "write segment-000001.log sync=true",
+                "write MANIFEST.tmp sync=true",
+                "rename",
+                "sync_dir",
+                "append segment-000001.log sync=true",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flush_only_and_none_skip_fsync() {
+        let (stats, calls) = flush_with(Durability::FlushOnly, "durability_flush_only");
+        assert!(!stats.fsynced);
+        assert_eq!(
+            calls,
+            vec![
+                "write segment-000001.log sync=false",
+                "write MANIFEST.tmp sync=false",
+                "rename",
+                "append segment-000001.log sync=false",
+            ]
+        );
+
+        let (stats, calls) = flush_with(Durability::None, "durability_none");
+        assert!(!stats.fsynced);
+        assert_eq!(
+            calls,
+            vec![
+                "write segment-000001.log sync=false",
+                "write MANIFEST sync=false",
+                "append segment-000001.log sync=false",
+            ]
+        );
+    }
+}
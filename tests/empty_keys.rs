@@ -0,0 +1,155 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{self, BatchMode, Bytes, Command, Modify, Response};
+use tinykv_rs::client::{BatchOutcome, KvError};
+use tinykv_rs::storage;
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api() -> RawKeyValueApi {
+        RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()))
+    }
+
+    fn is_empty_key(response: &Response) -> bool {
+        matches!(response, Response::Error(e) if e.starts_with("EmptyKey"))
+    }
+
+    #[test]
+    fn test_every_key_command_rejects_empty_key() {
+        let api = api();
+        let mut session = api.new_session();
+        let cf = || "users".to_string();
+        let commands = vec![
+            Command::Get { cf: cf(), key: Vec::new() },
+            Command::Put { cf: cf(), key: Vec::new(), value: b"v".to_vec() },
+            Command::Delete { cf: cf(), key: Vec::new() },
+            Command::GetDel { cf: cf(), key: Vec::new() },
+            Command::GetSet { cf: cf(), key: Vec::new(), value: b"v".to_vec() },
+            Command::Rename { cf: cf(), old_key: Vec::new(), new_key: b"k".to_vec(), overwrite: false },
+            Command::Rename { cf: cf(), old_key: b"k".to_vec(), new_key: Vec::new(), overwrite: false },
+            Command::Copy { cf: cf(), src_key: Vec::new(), dst_key: b"k".to_vec(), overwrite: false },
+            Command::Copy { cf: cf(), src_key: b"k".to_vec(), dst_key: Vec::new(), overwrite: false },
+            Command::Expire { cf: cf(), key: Vec::new(), ttl_ms: 1_000 },
+            Command::Ttl { cf: cf(), key: Vec::new() },
+            Command::RestoreKey { cf: cf(), key: Vec::new(), overwrite: false },
+            Command::GetVersion { cf: cf(), key: Vec::new(), version: 1 },
+            Command::History { cf: cf(), key: Vec::new(), limit: 10 },
+        ];
+        for cmd in commands {
+            let kind = cmd.kind();
+            assert!(is_empty_key(&api.handle_command(&mut session, cmd)), "{}", kind);
+        }
+        // 非空的键照常处理，且之前的命令没有写入任何东西
+        assert!(matches!(api.handle_command(&mut session, Command::Get { cf: cf(), key: b"k".to_vec() }), Response::Value(None)));
+        assert_eq!(api.raw_scan("users", b"", None, usize::MAX).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_raw_api_rejects_empty_key() {
+        let api = api();
+        assert!(api.raw_put("users".to_string(), Vec::new(), b"v".to_vec()).unwrap_err().starts_with("EmptyKey"));
+        assert!(api.raw_get("users", b"").unwrap_err().starts_with("EmptyKey"));
+        assert!(api.raw_delete("users".to_string(), Vec::new()).unwrap_err().starts_with("EmptyKey"));
+        assert!(api.raw_get_set("users", b"", Vec::new()).unwrap_err().starts_with("EmptyKey"));
+        assert!(api.raw_rename("users", b"a", b"", true).unwrap_err().starts_with("EmptyKey"));
+        assert!(protocol::validate_key(b"").is_err());
+        assert!(protocol::validate_key(b"k").is_ok());
+    }
+
+    #[test]
+    fn test_batch_reports_empty_keys_per_op() {
+        let api = api();
+        let mut session = api.new_session();
+        let ops = || {
+            vec![
+                Modify::new_put("users".to_string(), b"a".to_vec(), b"1".to_vec()),
+                Modify::new_put("users".to_string(), Vec::new(), b"2".to_vec()),
+                Modify::new_delete("users".to_string(), Vec::new()),
+            ]
+        };
+
+        let atomic = api.handle_command(&mut session, Command::Batch { ops: ops(), mode: BatchMode::Atomic });
+        let Response::BatchError(errors) = atomic else { panic!("unexpected {:?}", atomic) };
+        assert_eq!(errors.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(errors.iter().all(|(_, e)| e.starts_with("EmptyKey")));
+        assert_eq!(api.raw_get("users", b"a").unwrap(), None);
+
+        let partial = api.handle_command(&mut session, Command::Batch { ops: ops(), mode: BatchMode::BestEffort });
+        let Response::BatchResults(results) = partial else { panic!("unexpected {:?}", partial) };
+        assert!(results[0].is_ok());
+        assert!(results[1].as_ref().unwrap_err().starts_with("EmptyKey"));
+        assert!(results[2].as_ref().unwrap_err().starts_with("EmptyKey"));
+        assert_eq!(api.raw_get("users", b"a").unwrap(), Some(b"1".to_vec()));
+    }
+
+    #[test]
+    fn test_empty_value_is_distinct_from_missing() -> Result<(), Box<dyn std::error::Error>> {
+        // 协议上空值和不存在是两种不同的响应
+        let empty = serde_json::to_string(&Response::Value(Some(Bytes(Vec::new()))))?;
+        let missing = serde_json::to_string(&Response::Value(None))?;
+        assert_ne!(empty, missing);
+        assert!(matches!(serde_json::from_str(&empty)?, Response::Value(Some(Bytes(v))) if v.is_empty()));
+        assert!(matches!(serde_json::from_str(&missing)?, Response::Value(None)));
+
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.put_bytes("users", b"blank", b"")?;
+        assert_eq!(client.get_bytes("users", b"blank")?, Some(Vec::new()));
+        assert_eq!(client.get("users", "blank")?, Some(String::new()));
+        assert_eq!(client.get_bytes("users", b"missing")?, None);
+        assert_eq!(client.get_set("users", "blank", "filled")?, Some(String::new()));
+        assert_eq!(client.get_set("users", "blank", "")?, Some("filled".to_string()));
+        assert_eq!(client.get_del("users", "blank")?, Some(String::new()));
+        assert_eq!(client.get_bytes("users", b"blank")?, None);
+
+        let err = client.put_bytes("users", b"", b"v").unwrap_err();
+        let err = err.downcast_ref::<KvError>().unwrap();
+        assert_eq!(err.code(), Some("EmptyKey"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_boundaries_with_empty_values() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        let outcome = client.write_batch(vec![
+            Modify::new_put("users".to_string(), b"a".to_vec(), Vec::new()),
+            Modify::new_put("users".to_string(), b"b".to_vec(), b"2".to_vec()),
+            Modify::new_put("users".to_string(), b"c".to_vec(), Vec::new()),
+        ])?;
+        assert!(matches!(outcome, BatchOutcome::Applied));
+
+        // 空的起始键表示从头扫描，空值的条目照常返回
+        let all = client.scan_bytes("users", b"", None, 10)?;
+        assert_eq!(all, vec![(b"a".to_vec(), Vec::new()), (b"b".to_vec(), b"2".to_vec()), (b"c".to_vec(), Vec::new())]);
+        assert_eq!(client.scan_bytes("users", b"", Some(b"b"), 10)?, vec![(b"a".to_vec(), Vec::new())]);
+        assert_eq!(client.scan_bytes("users", b"c", None, 10)?, vec![(b"c".to_vec(), Vec::new())]);
+        assert_eq!(client.scan("users", "", None, 1)?, vec![("a".to_string(), String::new())]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_values_survive_restart() {
+        let dir = temp_dir("empty_values");
+        {
+            let storage = storage::StandaloneStorage::open(&dir).unwrap();
+            storage.write(vec![Modify::new_put("users".to_string(), b"snap".to_vec(), Vec::new())]).unwrap();
+            storage.flush().unwrap();
+            // 整理后 snap 在基础快照中，wal 在之后的段文件中
+            storage.compact().unwrap();
+            storage.write(vec![Modify::new_put("users".to_string(), b"wal".to_vec(), Vec::new())]).unwrap();
+            storage.flush().unwrap();
+        }
+        let storage = storage::StandaloneStorage::open(&dir).unwrap();
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("users", b"snap").unwrap(), Some(Vec::new()));
+        assert_eq!(reader.get_cf("users", b"wal").unwrap(), Some(Vec::new()));
+        assert_eq!(reader.get_cf("users", b"missing").unwrap(), None);
+        drop(reader);
+        drop(storage);
+    }
+}
@@ -0,0 +1,119 @@
+use tinykv_rs::api::EncodedKey;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage;
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 确定性的伪随机数（xorshift64），失败时可以按种子复现
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        /// 任意字节串，偏向分隔符、0x00 和 0xFF 等边界字节
+        fn bytes(&mut self) -> Vec<u8> {
+            let special = [b'_', b'/', 0x00, 0xFF, 0xFE, b'a'];
+            (0..self.below(8))
+                .map(|_| match self.below(2) {
+                    0 => special[self.below(special.len())],
+                    _ => self.next() as u8,
+                })
+                .collect()
+        }
+
+        /// 任意列族名，包括空名、分隔符和多字节字符
+        fn cf(&mut self) -> String {
+            let pieces = ["", "_", "__", "/", "a", "b", "\0", "é", "\u{10ffff}", "db/cf", "__trash"];
+            (0..self.below(4)).map(|_| pieces[self.below(pieces.len())]).collect()
+        }
+    }
+
+    #[test]
+    fn test_decode_inverts_encode() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..10_000 {
+            let (cf, key) = (rng.cf(), rng.bytes());
+            let encoded = EncodedKey::encode(&cf, &key);
+            assert_eq!(encoded.decode(), Some((cf.as_str(), key.as_slice())), "{:?} {:?}", cf, key);
+        }
+        assert_eq!(EncodedKey::encode("", b"").decode(), Some(("", &b""[..])));
+        assert_eq!(EncodedKey::encode("a_b", b"_c").decode(), Some(("a_b", &b"_c"[..])));
+    }
+
+    #[test]
+    fn test_encoding_is_injective_and_keeps_cf_ranges() {
+        let mut rng = Rng(42);
+        for _ in 0..10_000 {
+            let (cf1, key1, cf2, key2) = (rng.cf(), rng.bytes(), rng.cf(), rng.bytes());
+            let (a, b) = (EncodedKey::encode(&cf1, &key1), EncodedKey::encode(&cf2, &key2));
+            assert_eq!(a == b, (&cf1, &key1) == (&cf2, &key2));
+
+            // 同一列族内按原始键排序
+            if cf1 == cf2 {
+                assert_eq!(a.cmp(&b), key1.cmp(&key2));
+            }
+            // 键落在且只落在自己列族的 [cf_prefix, cf_end) 中
+            let in_range = |cf: &str, k: &EncodedKey| {
+                EncodedKey::cf_prefix(cf) <= *k && EncodedKey::cf_end(cf).is_none_or(|end| *k < end)
+            };
+            assert!(in_range(&cf1, &a));
+            assert_eq!(in_range(&cf2, &a), cf1 == cf2, "{:?} {:?} {:?}", cf1, key1, cf2);
+        }
+        assert_eq!(EncodedKey::cf_end(""), None);
+    }
+
+    #[test]
+    fn test_legacy_segment_keys_are_upgraded() {
+        let path = temp_dir("encoded_key_legacy");
+
+        // 旧格式：列族名、'_'、原始键；内部列族名本身以 '_' 开头
+        let record = serde_json::json!({
+            "sequence": 1,
+            "keys": [
+                { "key": b"users_u1".to_vec(), "value": b"alice".to_vec() },
+                { "key": b"users_a_b".to_vec(), "value": b"underscore".to_vec() },
+                { "key": b"__locks_job".to_vec(), "value": vec![0u8; 16] },
+                { "key": b"__corrupt_users_bad".to_vec(), "value": b"x".to_vec() },
+            ],
+        });
+        let manifest = serde_json::json!({ "segments": ["segment-000001.log"], "next_file": 1 });
+        fs::write(path.join("segment-000001.log"), format!("{}\n", record)).unwrap();
+        fs::write(path.join("MANIFEST"), manifest.to_string()).unwrap();
+
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("users", b"u1").unwrap(), Some(b"alice".to_vec()));
+        assert_eq!(reader.get_cf("users", b"a_b").unwrap(), Some(b"underscore".to_vec()));
+        assert_eq!(reader.get_cf(storage::LOCKS_CF, b"job").unwrap(), Some(vec![0u8; 16]));
+        let quarantined = EncodedKey::encode("users", b"bad");
+        assert_eq!(reader.get_cf(storage::QUARANTINE_CF, quarantined.as_bytes()).unwrap(), Some(b"x".to_vec()));
+        drop(reader);
+
+        // 新写入的记录使用新格式，与旧记录一起重放
+        storage.write(vec![Modify::new_delete("users".into(), b"u1".to_vec())]).unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(storage.reader().unwrap().get_cf("users", b"u1").unwrap(), None);
+        assert_eq!(storage.cf_stats().unwrap(), vec![
+            (storage::QUARANTINE_CF.to_string(), 1),
+            (storage::LOCKS_CF.to_string(), 1),
+            ("users".to_string(), 1),
+        ]);
+        drop(storage);
+    }
+}
@@ -0,0 +1,141 @@
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::{self, entry_wire_size, Command, Envelope, Response, Warning};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage;
+use tinykv_rs::testing::TestServer;
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope_client(server: &TestServer) -> KvClient {
+        let mut client = server.connect().unwrap();
+        client.enable_envelope().unwrap();
+        assert!(client.negotiated_features().contains(&"envelope".to_string()));
+        client
+    }
+
+    #[test]
+    fn test_truncated_scan_carries_a_warning() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_response_bytes: Some(4 * entry_wire_size(b"k0", b"value")), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let mut client = envelope_client(&server);
+        for i in 0..10 {
+            client.put("users", &format!("k{}", i), "value")?;
+        }
+        client.take_warnings();
+
+        let page = client.scan_builder("users").run_page()?;
+        assert!(!page.complete);
+        assert_eq!(client.take_warnings(), vec![Warning::Truncated { examined_limit_reached: false }]);
+
+        // 范围内的结果放得下时没有警告
+        let page = client.scan_builder("users").to_exclusive("k2").run_page()?;
+        assert!(page.complete);
+        assert!(client.take_warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_created_cf_carries_a_warning() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let mut client = envelope_client(&server);
+        client.put("fresh", "k", "v")?;
+        assert_eq!(client.take_warnings(), vec![Warning::CfAutoCreated { cf: "fresh".to_string() }]);
+
+        // 列族已经存在，或显式创建时没有警告
+        client.put("fresh", "k2", "v")?;
+        client.create_cf("explicit", None)?;
+        client.put("explicit", "k", "v")?;
+        assert_eq!(client.get("fresh", "k")?, Some("v".to_string()));
+        assert!(client.take_warnings().is_empty());
+
+        // 其他数据库中的列族按会话中的名称报告
+        client.use_db("tenant")?;
+        client.put("orders", "k", "v")?;
+        assert_eq!(client.take_warnings(), vec![Warning::CfAutoCreated { cf: "orders".to_string() }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_near_response_limit_carries_a_warning() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_response_bytes: Some(1000), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let mut client = envelope_client(&server);
+        client.create_cf("blobs", None)?;
+        client.put("blobs", "small", "v")?;
+        assert!(client.take_warnings().is_empty());
+
+        let big = "x".repeat(300);
+        client.put("blobs", "big", &big)?;
+        let size = entry_wire_size(b"big", big.as_bytes());
+        assert_eq!(client.take_warnings(), vec![Warning::ValueNearLimit { cf: "blobs".to_string(), size, limit: 1000 }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_timing_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let mut plain = server.connect()?;
+        plain.put("cf", "k", "v")?;
+        assert_eq!(plain.last_server_timing(), None);
+        assert!(plain.take_warnings().is_empty());
+
+        let mut client = envelope_client(&server);
+        assert_eq!(client.get("cf", "k")?, Some("v".to_string()));
+        assert!(client.last_server_timing().is_some());
+
+        // 服务端错误同样带外层，错误仍然转换为 Err
+        assert!(client.put("bad_cf", "k", "v").is_err());
+        assert!(client.take_warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_is_only_sent_when_negotiated() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let mut stream = TcpStream::connect(server.addr())?;
+        let mut pending = Vec::new();
+        let send = |stream: &mut TcpStream, cmd: &Command| stream.write_all(&serde_json::to_vec(cmd).unwrap());
+
+        // 默认握手不请求 envelope
+        send(&mut stream, &Command::Hello { client_version: 1, features: vec!["chunked".to_string()] })?;
+        let hello: Response = protocol::read_message(&mut stream, &mut pending)?.unwrap();
+        assert!(matches!(hello, Response::Hello { ref accepted_features, .. } if accepted_features == &["chunked"]));
+        send(&mut stream, &Command::Put { cf: "new".to_string(), key: b"k".to_vec(), value: b"v".to_vec() })?;
+        assert!(matches!(protocol::read_message::<Response, _>(&mut stream, &mut pending)?, Some(Response::Ok)));
+
+        // 协商后的 Hello 回复本身仍然不带外层
+        send(&mut stream, &Command::Hello { client_version: 1, features: vec!["envelope".to_string()] })?;
+        let hello: Response = protocol::read_message(&mut stream, &mut pending)?.unwrap();
+        assert!(matches!(hello, Response::Hello { ref accepted_features, .. } if accepted_features == &["envelope"]));
+        send(&mut stream, &Command::Put { cf: "other".to_string(), key: b"k".to_vec(), value: b"v".to_vec() })?;
+        let envelope: Envelope = protocol::read_message(&mut stream, &mut pending)?.unwrap();
+        assert!(matches!(envelope.resp, Response::Ok));
+        assert_eq!(envelope.warnings, vec![Warning::CfAutoCreated { cf: "other".to_string() }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_warnings_are_only_collected_with_envelope() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let put = |cf: &str| Command::Put { cf: cf.to_string(), key: b"k".to_vec(), value: b"v".to_vec() };
+
+        let mut session = Session::default();
+        assert!(matches!(api.handle_command(&mut session, put("a")), Response::Ok));
+        assert!(session.request.warnings.is_empty());
+
+        session.features = vec!["envelope".to_string()];
+        assert!(matches!(api.handle_command(&mut session, put("b")), Response::Ok));
+        assert_eq!(session.request.warnings, vec![Warning::CfAutoCreated { cf: "b".to_string() }]);
+        // 每个请求开始时清空
+        assert!(matches!(api.handle_command(&mut session, put("b")), Response::Ok));
+        assert!(session.request.warnings.is_empty());
+        assert_eq!(Warning::CfAutoCreated { cf: "b".to_string() }.to_string(), "column family 'b' auto-created");
+    }
+}
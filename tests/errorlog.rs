@@ -0,0 +1,72 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::errorlog::{ErrorCategory, ErrorLog};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::StorageOptions;
+use tinykv_rs::testing::TestServer;
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_keeps_newest() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let log = ErrorLog::new(3, clock.clone());
+        for i in 0..5 {
+            log.record(ErrorCategory::Flush, format!("error {}", i));
+            clock.advance(Duration::from_millis(1));
+        }
+
+        let recent = log.recent(10);
+        let messages: Vec<&str> = recent.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["error 4", "error 3", "error 2"]);
+        assert_eq!(recent[0].timestamp_ms, 1_004);
+        assert_eq!(log.recent(1).len(), 1);
+        assert_eq!(log.total(), 5);
+
+        // 容量为 0 时只计数
+        let log = ErrorLog::new(0, clock);
+        log.record(ErrorCategory::Request, "dropped");
+        assert!(log.recent(10).is_empty());
+        assert_eq!(log.total(), 1);
+    }
+
+    #[test]
+    fn test_server_records_rejected_and_bad_requests() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            storage_options: StorageOptions { max_memory_bytes: Some(1024), ..StorageOptions::default() },
+            ..ServerConfig::default()
+        };
+        let mut server = TestServer::start_with_config(config)?;
+        assert_eq!(server.client().error_count()?, 0);
+
+        // 超出内存预算的写入
+        assert!(server.client().put("cf", "big", &"x".repeat(4096)).is_err());
+        // 普通的命令错误不记录
+        assert!(server.client().ttl("cf", "missing").is_err());
+        assert_eq!(server.client().error_count()?, 1);
+
+        // 无法解析的请求：服务端记录后断开连接
+        let mut stream = TcpStream::connect(server.addr())?;
+        stream.write_all(br#"{"type":"NoSuchCommand"}"#)?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.client().error_count()? < 2 {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let events = server.client().recent_errors(10)?;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].category, ErrorCategory::Request);
+        assert!(events[0].message.contains("NoSuchCommand"), "{}", events[0].message);
+        assert_eq!(events[1].category, ErrorCategory::Rejected);
+        assert!(events[1].message.starts_with("Put: OutOfMemoryBudget"), "{}", events[1].message);
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use tinykv_rs::storage::{self, EvictionPolicy, StorageOptions};
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Command, Modify, Response};
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: usize = 4000;
+
+    fn lru_storage() -> storage::StandaloneStorage {
+        let options = StorageOptions {
+            max_memory_bytes: Some(BUDGET),
+            eviction: EvictionPolicy::Lru,
+            ..StorageOptions::default()
+        };
+        storage::StandaloneStorage::in_memory_with_options(options)
+    }
+
+    fn put(storage: &storage::StandaloneStorage, key: &str) {
+        storage.write(vec![Modify::new_put("cache".to_string(), key.as_bytes().to_vec(), vec![b'v'; 100])]).unwrap();
+    }
+
+    #[test]
+    fn test_lru_keeps_recently_read_keys() {
+        let storage = lru_storage();
+        let hot: Vec<String> = (0..5).map(|i| format!("hot{}", i)).collect();
+        for key in &hot {
+            put(&storage, key);
+        }
+
+        for i in 0..200 {
+            put(&storage, &format!("cold{:03}", i));
+            let reader = storage.reader().unwrap();
+            for key in &hot {
+                assert!(reader.get_cf("cache", key.as_bytes()).unwrap().is_some(), "{} evicted", key);
+            }
+            assert!(storage.memory_usage().unwrap() <= BUDGET);
+        }
+
+        // 早期写入且之后没有被读过的键已被淘汰
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("cache", b"cold000").unwrap(), None);
+        assert!(reader.get_cf("cache", b"cold199").unwrap().is_some());
+        drop(reader);
+
+        let (total_keys, _) = storage.get_stats().unwrap();
+        assert_eq!(storage.evicted_keys().unwrap() as usize, 205 - total_keys);
+    }
+
+    #[test]
+    fn test_lru_never_rejects_writes() {
+        let storage = Arc::new(lru_storage());
+        for i in 0..100 {
+            put(&storage, &format!("k{}", i));
+        }
+
+        let api = RawKeyValueApi::new(storage);
+        match api.handle_command(&mut api.new_session(), Command::Info) {
+            Response::Info { memory_bytes, evicted_keys, total_keys, .. } => {
+                assert!(memory_bytes <= BUDGET);
+                assert!(evicted_keys > 0);
+                assert_eq!(total_keys as u64 + evicted_keys, 100);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+}
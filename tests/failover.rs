@@ -0,0 +1,76 @@
+use tinykv_rs::client::{KvClient, RetryPolicy};
+use tinykv_rs::server::{KvServer, ServerHandle, ShutdownOptions};
+
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 启动一个内存模式的服务器，并写入一个标明自身身份的键
+    fn start(name: &str) -> (ServerHandle, String) {
+        let handle = KvServer::in_memory().unwrap().start_background("127.0.0.1:0").unwrap();
+        let addr = handle.local_addr().to_string();
+        KvClient::connect(&addr).unwrap().put("default", "who", name).unwrap();
+        (handle, addr)
+    }
+
+    fn kill(handle: ServerHandle) {
+        handle
+            .shutdown(ShutdownOptions { drain_timeout: Duration::from_millis(100), flush: false })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reads_fail_over_to_next_endpoint() -> Result<(), Box<dyn std::error::Error>> {
+        let (primary, primary_addr) = start("primary");
+        let (follower, follower_addr) = start("follower");
+
+        let mut client = KvClient::connect_multi(&[&primary_addr, &follower_addr])?;
+        assert_eq!(client.current_endpoint(), Some(primary_addr.as_str()));
+        assert_eq!(client.get("default", "who")?, Some("primary".to_string()));
+
+        kill(primary);
+        assert_eq!(client.get("default", "who")?, Some("follower".to_string()));
+        assert_eq!(client.current_endpoint(), Some(follower_addr.as_str()));
+
+        // 首选地址不可用时 connect_multi 直接使用下一个地址
+        let client = KvClient::connect_multi(&[&primary_addr, &follower_addr])?;
+        assert_eq!(client.current_endpoint(), Some(follower_addr.as_str()));
+
+        kill(follower);
+        Ok(())
+    }
+
+    #[test]
+    fn test_writes_retried_only_when_allowed() -> Result<(), Box<dyn std::error::Error>> {
+        let (primary, primary_addr) = start("primary");
+        let (follower, follower_addr) = start("follower");
+
+        let mut cautious = KvClient::connect_multi(&[&primary_addr, &follower_addr])?;
+        let policy = RetryPolicy { retry_writes: true, ..RetryPolicy::default() };
+        let mut eager = KvClient::connect_multi_with_policy(&[&primary_addr, &follower_addr], policy)?;
+        cautious.use_db("app")?;
+        eager.use_db("app")?;
+
+        kill(primary);
+        assert!(cautious.put("default", "k", "v").is_err());
+        eager.put("default", "k", "v")?;
+        assert_eq!(eager.current_endpoint(), Some(follower_addr.as_str()));
+
+        // 切换后重放了数据库选择
+        let mut check = KvClient::connect_db(&follower_addr, "app")?;
+        assert_eq!(check.get("default", "k")?, Some("v".to_string()));
+
+        kill(follower);
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_endpoints_down() {
+        let (server, addr) = start("only");
+        kill(server);
+        assert!(KvClient::connect_multi(&[&addr, &addr]).is_err());
+        assert!(KvClient::connect_multi(&[]).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use tinykv_rs::storage::{self, FlushPolicy, StorageOptions};
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::testing::{temp_dir, wait_until};
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(path: Option<&Path>, policy: FlushPolicy) -> Arc<storage::StandaloneStorage> {
+        let options = StorageOptions { flush_policy: Some(policy), ..StorageOptions::default() };
+        Arc::new(match path {
+            Some(path) => storage::StandaloneStorage::open_with_options(path, options).unwrap(),
+            None => storage::StandaloneStorage::in_memory_with_options(options),
+        })
+    }
+
+    fn put(storage: &storage::StandaloneStorage, key: String) {
+        storage.write(vec![Modify::new_put("cf".to_string(), key.into_bytes(), vec![b'v'; 64])]).unwrap();
+    }
+
+    #[test]
+    fn test_dirty_count_bounded_under_heavy_writes() {
+        let path = temp_dir("flush_scheduler_load");
+        let storage = open(Some(&path), FlushPolicy {
+            interval: None,
+            dirty_threshold: Some(200),
+            high_water_mark: Some(1000),
+            throttle: Duration::from_millis(2),
+        });
+        let scheduler = storage.start_flush_scheduler().unwrap();
+
+        let writing = Arc::new(AtomicBool::new(true));
+        let monitor = {
+            let (storage, writing) = (Arc::clone(&storage), Arc::clone(&writing));
+            thread::spawn(move || {
+                let mut max_dirty = 0;
+                while writing.load(Ordering::SeqCst) {
+                    max_dirty = max_dirty.max(storage.flush_info().unwrap().dirty);
+                    thread::sleep(Duration::from_millis(1));
+                }
+                max_dirty
+            })
+        };
+
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let storage = Arc::clone(&storage);
+                thread::spawn(move || {
+                    for i in 0..2500 {
+                        put(&storage, format!("t{}-{:05}", t, i));
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        writing.store(false, Ordering::SeqCst);
+        let max_dirty = monitor.join().unwrap();
+
+        // 没有后台刷盘时脏计数会涨到 10000
+        assert!(max_dirty < 5000, "dirty count reached {}", max_dirty);
+        let info = storage.flush_info().unwrap();
+        assert!(info.flushes > 1);
+        assert!(info.last_bytes > 0);
+
+        scheduler.request_flush();
+        wait_until(|| storage.flush_info().unwrap().dirty == 0);
+        drop(scheduler);
+    }
+
+    #[test]
+    fn test_interval_flush_and_scheduler_stop() {
+        let path = temp_dir("flush_scheduler_interval");
+        let storage = open(Some(&path), FlushPolicy {
+            interval: Some(Duration::from_millis(20)),
+            ..FlushPolicy::default()
+        });
+        let scheduler = storage.start_flush_scheduler().unwrap();
+
+        put(&storage, "k".to_string());
+        wait_until(|| storage.flush_info().unwrap().flushes == 1);
+        assert!(path.join("MANIFEST").exists());
+
+        // 没有新的修改时不会重复刷盘
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(storage.flush_info().unwrap().flushes, 1);
+
+        drop(scheduler);
+        put(&storage, "k2".to_string());
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(storage.flush_info().unwrap().dirty, 1);
+    }
+
+    #[test]
+    fn test_memory_mode_has_no_scheduler() {
+        assert!(open(None, FlushPolicy::default()).start_flush_scheduler().is_none());
+    }
+}
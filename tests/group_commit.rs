@@ -0,0 +1,133 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Command, Response};
+use tinykv_rs::group_commit::GroupCommitConfig;
+use tinykv_rs::server::{KvServer, ServerConfig};
+use tinykv_rs::storage::{self, StorageOptions};
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WRITERS: usize = 32;
+
+    fn grouped(window: Duration) -> ServerConfig {
+        ServerConfig {
+            group_commit: Some(GroupCommitConfig { window, ..GroupCommitConfig::default() }),
+            ..ServerConfig::default()
+        }
+    }
+
+    fn put(cf: &str, key: String, value: Vec<u8>) -> Command {
+        Command::Put { cf: cf.to_string(), key: key.into_bytes(), value }
+    }
+
+    #[test]
+    fn test_concurrent_writes_are_grouped() {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        let api = Arc::new(RawKeyValueApi::with_config(
+            Arc::clone(&storage),
+            Arc::new(grouped(Duration::from_millis(2))),
+        ));
+
+        let barrier = Arc::new(Barrier::new(WRITERS));
+        let threads: Vec<_> = (0..WRITERS)
+            .map(|w| {
+                let (api, barrier) = (Arc::clone(&api), Arc::clone(&barrier));
+                thread::spawn(move || {
+                    let mut session = api.new_session();
+                    barrier.wait();
+                    for i in 0..20 {
+                        let response = api.handle_command(&mut session, put("cf", format!("w{}-{}", w, i), b"v".to_vec()));
+                        assert!(matches!(response, Response::Ok), "{:?}", response);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(storage.get_stats().unwrap().0, WRITERS * 20);
+        let stats = api.group_commit_stats().unwrap();
+        assert_eq!(stats.writes, (WRITERS * 20) as u64);
+        assert!(stats.batches < stats.writes, "{:?}", stats);
+
+        // 读请求不经过提交线程
+        let mut session = api.new_session();
+        api.handle_command(&mut session, Command::Get { cf: "cf".to_string(), key: b"w0-0".to_vec() });
+        assert_eq!(api.group_commit_stats().unwrap().writes, (WRITERS * 20) as u64);
+    }
+
+    #[test]
+    fn test_failed_request_does_not_fail_its_group() {
+        let options = StorageOptions { max_memory_bytes: Some(4096), ..StorageOptions::default() };
+        let storage = Arc::new(storage::StandaloneStorage::in_memory_with_options(options));
+        let api = Arc::new(RawKeyValueApi::with_config(
+            Arc::clone(&storage),
+            Arc::new(grouped(Duration::from_millis(20))),
+        ));
+
+        let barrier = Arc::new(Barrier::new(4));
+        let threads: Vec<_> = (0..4)
+            .map(|w| {
+                let (api, barrier) = (Arc::clone(&api), Arc::clone(&barrier));
+                thread::spawn(move || {
+                    let mut session = api.new_session();
+                    // 第 0 个写入单独就超出预算，其他写入都放得下
+                    let size = if w == 0 { 8192 } else { 16 };
+                    barrier.wait();
+                    api.handle_command(&mut session, put("cf", format!("k{}", w), vec![b'x'; size]))
+                })
+            })
+            .collect();
+        let results: Vec<Response> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+        assert!(matches!(&results[0], Response::Error(e) if e.contains("OutOfMemoryBudget")));
+        assert!(results[1..].iter().all(|r| matches!(r, Response::Ok)));
+        assert_eq!(storage.get_stats().unwrap().0, 3);
+    }
+
+    /// 32 个并发连接各写入一批键，返回每秒写入数
+    fn puts_per_sec(config: ServerConfig, puts_per_writer: usize) -> f64 {
+        let handle = KvServer::with_config(config).unwrap().start_background("127.0.0.1:0").unwrap();
+        let addr = handle.local_addr().to_string();
+        let barrier = Arc::new(Barrier::new(WRITERS + 1));
+
+        let threads: Vec<_> = (0..WRITERS)
+            .map(|w| {
+                let (addr, barrier) = (addr.clone(), Arc::clone(&barrier));
+                thread::spawn(move || {
+                    let mut client = KvClient::connect(&addr).unwrap();
+                    barrier.wait();
+                    for i in 0..puts_per_writer {
+                        client.put("bench", &format!("w{}-{}", w, i), "value").unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        let started = Instant::now();
+        for t in threads {
+            t.join().unwrap();
+        }
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let mut client = KvClient::connect(&addr).unwrap();
+        assert_eq!(client.scan("bench", "", None, usize::MAX).unwrap().len(), WRITERS * puts_per_writer);
+        (WRITERS * puts_per_writer) as f64 / elapsed
+    }
+
+    #[test]
+    fn test_group_commit_throughput() {
+        let direct = puts_per_sec(ServerConfig::default(), 100);
+        let grouped = puts_per_sec(grouped(Duration::from_micros(200)), 100);
+        // 吞吐量与机器负载有关，这里只打印对比，不作为断言
+        println!("32 writers: direct {:.0} puts/sec, group commit {:.0} puts/sec", direct, grouped);
+    }
+}
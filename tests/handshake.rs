@@ -0,0 +1,106 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{self, Command, Response};
+use tinykv_rs::server::KvServer;
+use tinykv_rs::storage;
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features() -> Vec<String> {
+        protocol::FEATURES.iter().map(|f| f.to_string()).collect()
+    }
+
+    /// 模拟不认识 Hello 的旧服务器：无法解析的命令会使其关闭连接
+    fn legacy_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let api = Arc::new(RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory())));
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (mut stream, api) = (stream.unwrap(), Arc::clone(&api));
+                thread::spawn(move || {
+                    let mut pending = Vec::new();
+                    let mut session = api.new_session();
+                    while let Ok(Some(message)) = protocol::read_message::<serde_json::Value, _>(&mut stream, &mut pending) {
+                        if message["type"] == "Hello" {
+                            return;
+                        }
+                        let cmd: Command = serde_json::from_value(message).unwrap();
+                        let response = api.handle_command(&mut session, cmd);
+                        stream.write_all(&serde_json::to_vec(&response).unwrap()).unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_hello_records_negotiated_features() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = Session::default();
+        assert_eq!(session.protocol_version, None);
+
+        let hello = Command::Hello {
+            client_version: 7,
+            features: vec!["scan-all".to_string(), "compression".to_string()],
+        };
+        match api.handle_command(&mut session, hello) {
+            Response::Hello { server_version, accepted_features } => {
+                assert_eq!(server_version, protocol::PROTOCOL_VERSION);
+                assert_eq!(accepted_features, vec!["scan-all".to_string()]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert_eq!(session.protocol_version, Some(protocol::PROTOCOL_VERSION));
+        assert_eq!(session.features, vec!["scan-all".to_string()]);
+
+        let hello = Command::Hello { client_version: 0, features: Vec::new() };
+        assert!(matches!(api.handle_command(&mut session, hello), Response::Error(_)));
+    }
+
+    #[test]
+    fn test_new_client_against_new_server() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+        assert_eq!(client.negotiated_features(), features().as_slice());
+
+        client.put("default", "k", "v")?;
+        assert_eq!(client.get("default", "k")?, Some("v".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_client_against_legacy_server() -> Result<(), Box<dyn std::error::Error>> {
+        let addr = legacy_server();
+        let mut client = KvClient::connect_db(&addr, "app")?;
+        assert!(client.negotiated_features().is_empty());
+
+        client.put("default", "k", "v")?;
+        assert_eq!(client.get("default", "k")?, Some("v".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_client_against_new_server() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+
+        // 从不发送 Hello 的连接按裸 JSON 协议处理
+        let mut legacy = KvClient::from_stream(TcpStream::connect(&addr)?);
+        assert!(legacy.negotiated_features().is_empty());
+        legacy.put("default", "k", "v")?;
+
+        let mut client = KvClient::connect(&addr)?;
+        assert_eq!(client.get("default", "k")?, Some("v".to_string()));
+        Ok(())
+    }
+}
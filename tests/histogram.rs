@@ -0,0 +1,115 @@
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{Command, Response};
+use tinykv_rs::histogram::{self, Histogram, BUCKETS};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::StandaloneStorage;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_boundaries() {
+        // 小值每个值一个桶
+        for value in 0..16 {
+            assert_eq!(histogram::bucket_index(value), value as usize);
+            assert_eq!(histogram::bucket_lower(value as usize), value);
+        }
+        // 每个桶的上界是下一个桶的下界，值总落在所在桶的范围内
+        for index in 0..BUCKETS - 1 {
+            let (lower, upper) = (histogram::bucket_lower(index), histogram::bucket_upper(index));
+            assert!(lower < upper, "bucket {}", index);
+            assert_eq!(histogram::bucket_index(lower), index);
+            assert_eq!(histogram::bucket_index(upper - 1), index);
+        }
+        assert_eq!(histogram::bucket_index(1024), histogram::bucket_index(1087));
+        assert_ne!(histogram::bucket_index(1087), histogram::bucket_index(1088));
+        // 超出范围的值都记入最后一个桶
+        assert_eq!(histogram::bucket_index(u64::MAX), BUCKETS - 1);
+        assert_eq!(histogram::bucket_upper(BUCKETS - 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_percentile_interpolation() {
+        let h = Histogram::new();
+        assert_eq!(h.percentile(0.5), 0);
+        for value in 1..=1000 {
+            h.record(value);
+        }
+        assert_eq!(h.count(), 1000);
+        assert_eq!(h.max(), 1000);
+        assert!((h.mean() - 500.5).abs() < 1e-9);
+        // 桶内插值后误差在桶宽（约 1/16）以内
+        for (q, expected) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0)] {
+            let got = h.percentile(q) as f64;
+            assert!((got - expected).abs() <= expected / 16.0, "p{} = {}", q, got);
+        }
+        assert_eq!(h.percentile(1.0), 1000);
+
+        // 分位数不超过记录到的最大值
+        let single = Histogram::new();
+        single.record(1_000_001);
+        assert_eq!(single.percentile(0.5), 1_000_001);
+        assert_eq!(single.summary().p99, 1_000_001);
+    }
+
+    #[test]
+    fn test_merge_and_reset() {
+        let (a, b) = (Histogram::new(), Histogram::new());
+        for value in 0..100 {
+            a.record(value);
+            b.record(value + 100);
+        }
+        a.merge(&b);
+        assert_eq!(a.count(), 200);
+        assert_eq!(a.max(), 199);
+        let p50 = a.percentile(0.5);
+        assert!((94..=106).contains(&p50), "{}", p50);
+
+        a.reset();
+        assert_eq!(a.summary(), histogram::LatencySummary::default());
+        assert_eq!(b.count(), 100);
+    }
+
+    #[test]
+    fn test_info_reports_command_latency() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { admin_token: Some("s".into()), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        for i in 0..10 {
+            client.put("cf", &format!("k{}", i), "v")?;
+        }
+        client.get("cf", "k1")?;
+
+        let latency = client.latency()?;
+        assert_eq!(latency["Put"].count, 10);
+        assert_eq!(latency["Get"].count, 1);
+        assert!(latency["Put"].p50 <= latency["Put"].p99 && latency["Put"].p99 <= latency["Put"].max);
+        assert!(!latency.contains_key("Delete"));
+
+        // ResetStats 清空统计
+        client.admin_auth("s")?;
+        client.reset_stats()?;
+        let latency = client.latency()?;
+        assert!(!latency.contains_key("Put"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_records_each_command() {
+        let api = RawKeyValueApi::new(Arc::new(StandaloneStorage::in_memory()));
+        let mut session = Session::default();
+        api.handle_command(&mut session, Command::Get { cf: "cf".into(), key: b"k".to_vec() });
+        match api.handle_command(&mut session, Command::Info) {
+            Response::Info { latency, .. } => {
+                assert_eq!(latency["Get"].count, 1);
+                assert!(latency["Get"].max < Duration::from_secs(1).as_micros() as u64);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+}
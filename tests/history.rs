@@ -0,0 +1,73 @@
+use tinykv_rs::storage::{self, CfOptions, StorageOptions};
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol;
+use tinykv_rs::testing::temp_dir;
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versioned_options(cf: &str, keep_versions: usize) -> StorageOptions {
+        let mut options = StorageOptions::default();
+        options.cf_options.insert(cf.to_string(), CfOptions { keep_versions, ..CfOptions::default() });
+        options
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_records_tombstones() {
+        let storage = storage::StandaloneStorage::in_memory_with_options(versioned_options("users", 2));
+        let api = RawKeyValueApi::new(Arc::new(storage));
+
+        for v in ["a", "b", "c"] {
+            api.raw_put("users".to_string(), b"k".to_vec(), v.as_bytes().to_vec()).unwrap();
+        }
+        api.raw_delete("users".to_string(), b"k".to_vec()).unwrap();
+
+        let history = api.raw_history("users", b"k", 10).unwrap();
+        let values: Vec<Option<&[u8]>> = history
+            .iter()
+            .map(|v| v.value.as_ref().map(|b| b.0.as_slice()))
+            .collect();
+        // 当前墓碑 + 最多 2 个旧版本
+        assert_eq!(values, vec![None, Some(&b"c"[..]), Some(&b"b"[..])]);
+        assert_eq!(history.iter().map(|v| v.version).collect::<Vec<_>>(), vec![4, 3, 2]);
+
+        assert_eq!(api.raw_get_version("users", b"k", 3).unwrap(), Some(b"c".to_vec()));
+        assert_eq!(api.raw_get_version("users", b"k", 4).unwrap(), None);
+        assert!(api.raw_get_version("users", b"k", 1).is_err());
+    }
+
+    #[test]
+    fn test_history_disabled_cf_is_rejected() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        api.raw_put("default".to_string(), b"k".to_vec(), b"v".to_vec()).unwrap();
+
+        assert!(api.raw_history("default", b"k", 10).is_err());
+        assert!(api.raw_get_version("default", b"k", 1).is_err());
+    }
+
+    #[test]
+    fn test_history_survives_reload_and_compact_trims() {
+        let path = temp_dir("history_reload");
+        {
+            let storage = Arc::new(storage::StandaloneStorage::open_with_options(&path, versioned_options("users", 3)).unwrap());
+            let api = RawKeyValueApi::new(Arc::clone(&storage));
+            for v in ["a", "b", "c", "d"] {
+                api.raw_put("users".to_string(), b"k".to_vec(), v.as_bytes().to_vec()).unwrap();
+            }
+            storage.flush().unwrap();
+        }
+
+        let storage = Arc::new(storage::StandaloneStorage::open(&path).unwrap());
+        let api = RawKeyValueApi::new(Arc::clone(&storage));
+        assert_eq!(api.raw_get("users", b"k").unwrap(), Some(b"d".to_vec()));
+        assert_eq!(api.raw_history("users", b"k", 10).unwrap().len(), 4);
+
+        storage.set_cf_options("users", CfOptions { keep_versions: 1, ..CfOptions::default() }).unwrap();
+        storage.compact().unwrap();
+        let history = api.raw_history("users", b"k", 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].value, Some(protocol::Bytes(b"c".to_vec())));
+    }
+}
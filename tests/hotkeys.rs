@@ -0,0 +1,107 @@
+use tinykv_rs::storage;
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{Command, Response};
+use tinykv_rs::hotkeys::{Access, HotKeyTracker, MAX_TRACKED_KEYS};
+use tinykv_rs::server::ServerConfig;
+use std::sync::{Arc};
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_with_sampling(sample_every: Option<u64>) -> RawKeyValueApi {
+        let config = ServerConfig {
+            hot_key_sample_every: sample_every,
+            ..ServerConfig::default()
+        };
+        RawKeyValueApi::with_config(Arc::new(storage::StandaloneStorage::in_memory()), Arc::new(config))
+    }
+
+    fn get(key: &str) -> Command {
+        Command::Get { cf: "default".to_string(), key: key.as_bytes().to_vec() }
+    }
+
+    fn put(key: &str) -> Command {
+        Command::Put { cf: "default".to_string(), key: key.as_bytes().to_vec(), value: b"v".to_vec() }
+    }
+
+    #[test]
+    fn test_tracker_finds_hot_key_among_many() {
+        let tracker = HotKeyTracker::new(1);
+        for i in 0..1000 {
+            tracker.record("default", format!("cold{}", i).as_bytes(), Access::Read);
+            tracker.record("default", b"hot", if i % 4 == 0 { Access::Write } else { Access::Read });
+        }
+
+        let top = tracker.top(3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0].key.0, b"hot".to_vec());
+        assert!(top[0].count >= 1000);
+        assert_eq!((top[0].reads, top[0].writes), (750, 250));
+        assert!(tracker.top(usize::MAX).len() <= MAX_TRACKED_KEYS);
+    }
+
+    #[test]
+    fn test_hot_keys_command_and_reset() {
+        let api = api_with_sampling(Some(1));
+        let mut session = api.new_session();
+        api.handle_command(&mut session, put("k1"));
+        for _ in 0..5 {
+            api.handle_command(&mut session, get("k1"));
+            api.handle_command(&mut session, get("k2"));
+        }
+        api.handle_command(&mut session, get("k2"));
+
+        match api.handle_command(&mut session, Command::HotKeys { top_n: 1 }) {
+            Response::HotKeys(keys) => {
+                assert_eq!(keys.len(), 1);
+                assert_eq!(keys[0].key.0, b"k1".to_vec());
+                assert_eq!((keys[0].count, keys[0].reads, keys[0].writes), (6, 5, 1));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        assert!(matches!(api.handle_command(&mut session, Command::ResetStats), Response::Ok));
+        assert!(matches!(
+            api.handle_command(&mut session, Command::HotKeys { top_n: 10 }),
+            Response::HotKeys(keys) if keys.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_hot_keys_disabled_by_default() {
+        let api = api_with_sampling(None);
+        let mut session = Session::default();
+        api.handle_command(&mut session, get("k"));
+        assert!(matches!(api.handle_command(&mut session, Command::HotKeys { top_n: 10 }), Response::Error(_)));
+    }
+
+    fn time_gets(api: &RawKeyValueApi) -> Duration {
+        let mut session = api.new_session();
+        api.handle_command(&mut session, put("bench"));
+        // 取多轮中的最小值以减少调度抖动
+        (0..5)
+            .map(|_| {
+                let started = Instant::now();
+                for _ in 0..20_000 {
+                    api.handle_command(&mut session, get("bench"));
+                }
+                started.elapsed()
+            })
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sampling_overhead_is_small() {
+        let disabled = time_gets(&api_with_sampling(None));
+        let enabled = time_gets(&api_with_sampling(Some(100)));
+        assert!(
+            enabled < disabled * 2,
+            "tracking enabled {:?} vs disabled {:?}",
+            enabled,
+            disabled
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::client::{KvClient, RetryPolicy};
+use tinykv_rs::idempotency::{IdempotencyConfig, IdempotencyStats};
+use tinykv_rs::protocol::{self, Bytes, Command, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{self, StorageOptions};
+use tinykv_rs::testing::TestServer;
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_with(config: IdempotencyConfig, clock: Arc<MockClock>) -> RawKeyValueApi {
+        let options = StorageOptions { clock, ..StorageOptions::default() };
+        let storage = Arc::new(storage::StandaloneStorage::in_memory_with_options(options));
+        let config = ServerConfig { idempotency: config, ..ServerConfig::default() };
+        RawKeyValueApi::with_config(storage, Arc::new(config))
+    }
+
+    fn api() -> RawKeyValueApi {
+        api_with(IdempotencyConfig::default(), Arc::new(MockClock::new(1_000)))
+    }
+
+    fn idempotent(token: u64, key: u64, cmd: Command) -> Command {
+        Command::Idempotent { token, key, cmd: Box::new(cmd) }
+    }
+
+    fn get_set(value: &str) -> Command {
+        Command::GetSet { cf: "users".to_string(), key: b"k".to_vec(), value: value.as_bytes().to_vec() }
+    }
+
+    fn value(response: &Response) -> Option<&[u8]> {
+        match response {
+            Response::Value(Some(Bytes(v))) => Some(v),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_request_replays_the_first_response() {
+        let api = api();
+        let mut session = api.new_session();
+        api.raw_put("users".to_string(), b"k".to_vec(), b"0".to_vec()).unwrap();
+
+        let first = api.handle_command(&mut session, idempotent(7, 1, get_set("1")));
+        let retried = api.handle_command(&mut session, idempotent(7, 1, get_set("1")));
+        assert_eq!(value(&first), Some(&b"0"[..]));
+        assert_eq!(value(&retried), Some(&b"0"[..]));
+        assert_eq!(api.raw_get("users", b"k").unwrap(), Some(b"1".to_vec()));
+
+        // 其他令牌下的同一个键是另一个请求
+        let other = api.handle_command(&mut session, idempotent(8, 1, get_set("2")));
+        assert_eq!(value(&other), Some(&b"1"[..]));
+        assert_eq!(api.idempotency_stats().replayed, 1);
+        assert_eq!(api.idempotency_stats().entries, 2);
+    }
+
+    #[test]
+    fn test_failed_requests_are_executed_again() {
+        let api = api();
+        let mut session = api.new_session();
+        let rename = || Command::Rename { cf: "users".to_string(), old_key: b"a".to_vec(), new_key: b"b".to_vec(), overwrite: false };
+
+        let missing = api.handle_command(&mut session, idempotent(7, 1, rename()));
+        assert!(matches!(missing, Response::Error(_)), "{:?}", missing);
+        api.raw_put("users".to_string(), b"a".to_vec(), b"1".to_vec()).unwrap();
+        assert!(matches!(api.handle_command(&mut session, idempotent(7, 1, rename())), Response::Ok));
+        assert!(matches!(api.handle_command(&mut session, idempotent(7, 1, rename())), Response::Ok));
+        assert_eq!(api.raw_get("users", b"b").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(api.idempotency_stats().replayed, 1);
+    }
+
+    #[test]
+    fn test_only_write_commands_can_be_wrapped() {
+        let api = api();
+        let mut session = api.new_session();
+        let get = Command::new_get("users".to_string(), b"k".to_vec());
+        let nested = idempotent(7, 1, Command::new_put("users".to_string(), b"k".to_vec(), b"v".to_vec()));
+        for cmd in [get, nested, Command::Shutdown { flush: false }] {
+            let response = api.handle_command(&mut session, idempotent(7, 2, cmd));
+            assert!(matches!(&response, Response::Error(e) if e.starts_with("Idempotent cannot wrap")), "{:?}", response);
+        }
+    }
+
+    #[test]
+    fn test_cache_is_bounded_by_count_and_age() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let config = IdempotencyConfig { max_entries: 2, retention: Duration::from_secs(10) };
+        let api = api_with(config, Arc::clone(&clock));
+        let mut session = api.new_session();
+
+        for key in 1..=3 {
+            api.handle_command(&mut session, idempotent(7, key, get_set(&key.to_string())));
+        }
+        assert_eq!(api.idempotency_stats(), IdempotencyStats { entries: 2, replayed: 0, evicted_by_count: 1, evicted_by_age: 0 });
+
+        // 淘汰的键再次到达时重新执行
+        let again = api.handle_command(&mut session, idempotent(7, 1, get_set("again")));
+        assert_eq!(value(&again), Some(&b"3"[..]));
+
+        clock.advance(Duration::from_secs(10));
+        let stats = api.idempotency_stats();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.evicted_by_count, 2);
+        assert_eq!(stats.evicted_by_age, 2);
+    }
+
+    /// 转发到 upstream 的代理：收到第一个 kind 命令后把它转发出去，丢弃响应并断开连接
+    fn lossy_proxy(upstream: String, kind: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut server = TcpStream::connect(&upstream).unwrap();
+            let (mut from_client, mut from_server) = (Vec::new(), Vec::new());
+            while let Ok(Some(cmd)) = protocol::read_message::<Command, _>(&mut client, &mut from_client) {
+                server.write_all(&serde_json::to_vec(&cmd).unwrap()).unwrap();
+                let response: Response = protocol::read_message(&mut server, &mut from_server).unwrap().unwrap();
+                if cmd.kind() == kind {
+                    return;
+                }
+                client.write_all(&serde_json::to_vec(&response).unwrap()).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_retry_after_lost_response_applies_once() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        server.client().put("users", "a", "1")?;
+        let proxy = lossy_proxy(server.addr().to_string(), "Rename");
+
+        let policy = RetryPolicy { retry_writes: true, ..RetryPolicy::default() };
+        let mut client = KvClient::connect_multi_with_policy(&[&proxy, server.addr()], policy)?;
+        assert!(client.negotiated_features().contains(&"idempotency".to_string()));
+
+        // 第一次发送已在服务器上执行，响应丢失后重试到第二个地址；
+        // 重复执行会因为 a 已不存在而失败
+        client.rename("users", "a", "b", false)?;
+        assert_eq!(client.current_endpoint(), Some(server.addr()));
+        assert_eq!(client.get("users", "a")?, None);
+        assert_eq!(client.get("users", "b")?, Some("1".to_string()));
+        assert_eq!(client.idempotency_stats()?.replayed, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use tinykv_rs::api::{EncodedKey, RawKeyValueApi};
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::{Bytes, CheckKind, Command, FixPolicy, Modify, Response, Violation};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{self, StandaloneStorage};
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKS: [CheckKind; 4] = [CheckKind::Utf8Key, CheckKind::MaxKeyLen(16), CheckKind::MaxValueLen(64), CheckKind::EmptyValue];
+
+    /// 每种检查各有一个违规条目，另有同时违反两项的条目和合法的条目
+    fn seed(client: &mut KvClient) -> Result<(), Box<dyn std::error::Error>> {
+        client.put_bytes("users", b"\xff\xfeuser", b"v")?;
+        client.put_bytes("users", &[b'k'; 32], b"v")?;
+        client.put_bytes("users", b"big", &[b'x'; 100])?;
+        client.put_bytes("users", b"empty", b"")?;
+        client.put_bytes("users", b"ok", b"fine")?;
+        client.put_bytes("logs", b"\xc3", b"")?;
+        client.put_bytes("logs", b"line", b"fine")?;
+        Ok(())
+    }
+
+    fn summary(violations: &[Violation]) -> Vec<(String, Vec<u8>, Vec<CheckKind>)> {
+        violations.iter().map(|v| (v.cf.clone(), v.key.0.clone(), v.failed.clone())).collect()
+    }
+
+    #[test]
+    fn test_audit_detects_each_check() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        seed(client)?;
+
+        // 按存储顺序：列族，然后键
+        let violations = client.audit(None, &CHECKS)?;
+        assert_eq!(
+            summary(&violations),
+            vec![
+                ("logs".to_string(), b"\xc3".to_vec(), vec![CheckKind::Utf8Key, CheckKind::EmptyValue]),
+                ("users".to_string(), b"big".to_vec(), vec![CheckKind::MaxValueLen(64)]),
+                ("users".to_string(), b"empty".to_vec(), vec![CheckKind::EmptyValue]),
+                ("users".to_string(), vec![b'k'; 32], vec![CheckKind::MaxKeyLen(16)]),
+                ("users".to_string(), b"\xff\xfeuser".to_vec(), vec![CheckKind::Utf8Key]),
+            ]
+        );
+        assert_eq!(violations[1].value_len, 100);
+
+        // 只检查指定的列族和检查项
+        let violations = client.audit(Some("users"), &[CheckKind::Utf8Key])?;
+        assert_eq!(summary(&violations), vec![("users".to_string(), b"\xff\xfeuser".to_vec(), vec![CheckKind::Utf8Key])]);
+        assert!(client.audit(Some("missing"), &CHECKS)?.is_empty());
+
+        let error = client.audit(None, &[]).unwrap_err().to_string();
+        assert!(error.contains("InvalidAudit"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit_respects_the_examined_bound_and_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_scan_examined: Some(3), response_chunk_bytes: Some(16), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        for i in 0..20 {
+            client.put_bytes("cf", format!("key{:02}", i).as_bytes(), if i % 2 == 0 { b"" } else { b"v" })?;
+        }
+
+        // 每次请求最多检查 3 个条目，续查位置是下一个未检查的键
+        let (violations, next) = client.audit_page(None, &[CheckKind::EmptyValue], None, 100)?;
+        assert_eq!(violations.iter().map(|v| v.key.0.clone()).collect::<Vec<_>>(), vec![b"key00".to_vec(), b"key02".to_vec()]);
+        assert_eq!(next, Some(("cf".to_string(), b"key03".to_vec())));
+
+        // limit 同样分页
+        let (violations, next) = client.audit_page(None, &[CheckKind::EmptyValue], Some(("cf", b"key03")), 1)?;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(next, Some(("cf".to_string(), b"key05".to_vec())));
+
+        // audit 自动续查，分帧的响应拼回完整的列表
+        let all = client.audit(None, &[CheckKind::EmptyValue])?;
+        let expected: Vec<Vec<u8>> = (0..20).step_by(2).map(|i| format!("key{:02}", i).into_bytes()).collect();
+        assert_eq!(all.into_iter().map(|v| v.key.0).collect::<Vec<_>>(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_violations_split_into_chunks() {
+        let violations: Vec<Violation> = (0..5)
+            .map(|i| Violation { cf: "cf".to_string(), key: Bytes(vec![b'k', i]), failed: vec![CheckKind::EmptyValue], value_len: 0 })
+            .collect();
+        let response = Response::Violations { violations, next: Some(("cf".to_string(), Bytes(b"k9".to_vec()))), examined_limit_reached: true };
+        let expected = serde_json::to_string(&response).unwrap();
+        let frames = response.into_chunks(8);
+        assert!(frames.len() > 1);
+
+        let mut assembled: Option<Response> = None;
+        for frame in frames {
+            let Response::Chunk { part, .. } = frame else { panic!("expected a chunk") };
+            match &mut assembled {
+                Some(response) => response.append(*part).unwrap(),
+                None => assembled = Some(*part),
+            }
+        }
+        assert_eq!(serde_json::to_string(&assembled.unwrap()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_fix_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        seed(client)?;
+
+        let report = client.fix_invalid(Some("users"), &CHECKS, FixPolicy::Delete)?;
+        assert_eq!(report.fixed.len(), 4);
+        assert!(report.skipped.is_empty());
+        assert!(client.audit(Some("users"), &CHECKS)?.is_empty());
+        assert_eq!(client.get_bytes("users", b"big")?, None);
+        assert_eq!(client.get_bytes("users", b"ok")?, Some(b"fine".to_vec()));
+        // 其他列族不受影响
+        assert_eq!(client.audit(Some("logs"), &CHECKS)?.len(), 1);
+
+        // 删除在重启后仍然生效
+        client.flush()?;
+        server.restart()?;
+        assert!(server.client().audit(Some("users"), &CHECKS)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fix_truncate_only_repairs_long_values() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        seed(client)?;
+        client.expire("users", "big", Duration::from_secs(3600))?;
+
+        let report = client.fix_invalid(None, &CHECKS, FixPolicy::Truncate)?;
+        assert_eq!(summary(&report.fixed), vec![("users".to_string(), b"big".to_vec(), vec![CheckKind::MaxValueLen(64)])]);
+        assert_eq!(report.skipped.len(), 4);
+        assert_eq!(client.get_bytes("users", b"big")?, Some(vec![b'x'; 64]));
+        // 截断保留原来的过期时间
+        assert!(client.ttl("users", "big")?.is_some());
+        // 无法截断的条目原样保留
+        assert_eq!(client.get_bytes("users", b"empty")?, Some(Vec::new()));
+        assert_eq!(client.audit(None, &CHECKS)?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fix_quarantine_moves_entries() {
+        let storage = Arc::new(StandaloneStorage::in_memory());
+        let api = RawKeyValueApi::new(Arc::clone(&storage));
+        let mut session = api.new_session();
+        storage.write(vec![Modify::new_put("users".to_string(), b"\xff".to_vec(), b"v".to_vec())]).unwrap();
+        storage.write(vec![Modify::new_put("users".to_string(), b"ok".to_vec(), b"v".to_vec())]).unwrap();
+
+        let cmd = Command::FixInvalid { cf: None, checks: vec![CheckKind::Utf8Key], policy: FixPolicy::Quarantine };
+        let Response::Fixed(report) = api.handle_command(&mut session, cmd) else {
+            panic!("expected Fixed");
+        };
+        assert_eq!(report.fixed.len(), 1);
+
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("users", b"\xff").unwrap(), None);
+        let quarantined = EncodedKey::encode("users", b"\xff");
+        assert_eq!(reader.get_cf(storage::QUARANTINE_CF, quarantined.as_bytes()).unwrap(), Some(b"v".to_vec()));
+
+        // 不指定列族时不检查内部列族，隔离的条目不会再次报告
+        let cmd = Command::Audit { cf: None, checks: vec![CheckKind::Utf8Key], start: None, limit: 10 };
+        let response = api.handle_command(&mut session, cmd);
+        assert!(matches!(&response, Response::Violations { violations, next: None, .. } if violations.is_empty()), "{:?}", response);
+    }
+
+    #[test]
+    fn test_audit_requires_admin() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { admin_token: Some("secret".to_string()), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let mut client = server.connect()?;
+        assert!(client.audit(None, &CHECKS).is_err());
+        assert!(client.fix_invalid(None, &CHECKS, FixPolicy::Delete).is_err());
+        client.admin_auth("secret")?;
+        assert!(client.audit(None, &CHECKS)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use tinykv_rs::storage;
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::Modify;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Arc;
+
+/// 按线程统计存活字节数及其峰值的分配器
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+fn track(delta: isize) {
+    let _ = LIVE.try_with(|live| {
+        let now = live.get() + delta;
+        live.set(now);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        track(layout.size() as isize);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        track(-(layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// 执行 f 期间相对起点的存活字节峰值
+fn peak_during<R>(f: impl FnOnce() -> R) -> (R, isize) {
+    let base = LIVE.with(Cell::get);
+    PEAK.with(|peak| peak.set(base));
+    let result = f();
+    (result, PEAK.with(Cell::get) - base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRIES: usize = 20_000;
+    const VALUE_SIZE: usize = 256;
+
+    fn large_storage() -> Arc<storage::StandaloneStorage> {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        let batch = (0..ENTRIES)
+            .map(|i| Modify::new_put("big".to_string(), format!("key{:06}", i).into_bytes(), vec![b'v'; VALUE_SIZE]))
+            .collect();
+        storage.write(batch).unwrap();
+        storage.write(vec![Modify::new_put("other".to_string(), b"k".to_vec(), b"v".to_vec())]).unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_iter_cf_streams_with_bounded_memory() {
+        let storage = large_storage();
+        let reader = storage.reader().unwrap();
+
+        let ((count, bytes), peak) = peak_during(|| {
+            reader
+                .iter_cf("big", b"", None)
+                .unwrap()
+                .fold((0, 0), |(count, bytes), (k, v)| (count + 1, bytes + k.len() + v.len()))
+        });
+        assert_eq!(count, ENTRIES);
+        assert!(bytes > ENTRIES * VALUE_SIZE);
+        // 全量遍历时同一时刻只存在常数个条目的拷贝
+        assert!(peak < 16 * 1024, "peak live bytes {} while iterating", peak);
+
+        // 收集结果的扫描需要与结果集成正比的内存
+        let (results, peak) = peak_during(|| reader.scan_cf("big", b"", None, usize::MAX, None).unwrap());
+        assert_eq!(results.len(), ENTRIES);
+        assert!(peak > (ENTRIES * VALUE_SIZE) as isize);
+    }
+
+    #[test]
+    fn test_iter_cf_respects_bounds() {
+        let storage = large_storage();
+        let reader = storage.reader().unwrap();
+
+        let keys: Vec<Vec<u8>> = reader
+            .iter_cf("big", b"key000010", Some(b"key000013"))
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![b"key000010".to_vec(), b"key000011".to_vec(), b"key000012".to_vec()]);
+
+        // 不会越过列族边界，逆序范围为空
+        assert_eq!(reader.iter_cf("other", b"", None).unwrap().count(), 1);
+        assert_eq!(reader.iter_cf("big", b"key2", Some(b"key1")).unwrap().count(), 0);
+        drop(reader);
+
+        let api = RawKeyValueApi::new(storage);
+        assert_eq!(api.raw_scan("big", b"", None, 5).unwrap().len(), 5);
+    }
+}
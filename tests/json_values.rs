@@ -0,0 +1,91 @@
+use tinykv_rs::client::{DecodeErrors, KvError};
+use tinykv_rs::testing::TestServer;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Address {
+    city: String,
+    zip: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    age: u32,
+    tags: Vec<String>,
+    address: Address,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, age: u32) -> User {
+        User {
+            name: name.to_string(),
+            age,
+            tags: vec!["a".to_string(), "b c".to_string()],
+            address: Address { city: "Hangzhou".to_string(), zip: None },
+        }
+    }
+
+    fn decode_error<'a>(e: &'a (dyn std::error::Error + 'static)) -> Option<&'a KvError> {
+        e.downcast_ref::<KvError>().filter(|e| matches!(e, KvError::Deserialize { .. }))
+    }
+
+    #[test]
+    fn test_json_round_trip_and_typed_errors() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+
+        let alice = user("alice", 30);
+        client.put_json("users", "u1", &alice)?;
+        assert_eq!(client.get_json::<User>("users", "u1")?, Some(alice));
+        assert_eq!(client.get_json::<User>("users", "missing")?, None);
+
+        // 类型不匹配时的错误带有出错的键
+        client.put("users", "bad", "{\"name\": 1}")?;
+        let err = client.get_json::<User>("users", "bad").unwrap_err();
+        match decode_error(err.as_ref()) {
+            Some(KvError::Deserialize { key, .. }) => assert_eq!(key, "bad"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(client.get_json::<Address>("users", "u1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_api_is_binary_safe() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+
+        let value = [0u8, 0xff, 0xfe, b'\n', 0x80];
+        client.put_bytes("bin", &[1, 2, 0xff], &value)?;
+        assert_eq!(client.get_bytes("bin", &[1, 2, 0xff])?, Some(value.to_vec()));
+        assert_eq!(client.scan_bytes("bin", &[], None, 10)?, vec![(vec![1, 2, 0xff], value.to_vec())]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_json_fail_fast_or_skip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.put_json("users", "u1", &user("alice", 30))?;
+        client.put("users", "u2", "not json")?;
+        client.put_json("users", "u3", &user("carol", 41))?;
+
+        let err = client.scan_json::<User>("users", "", None, 10, DecodeErrors::Fail).unwrap_err();
+        match decode_error(err.as_ref()) {
+            Some(KvError::Deserialize { key, .. }) => assert_eq!(key, "u2"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let scan = client.scan_json::<User>("users", "", None, 10, DecodeErrors::Skip)?;
+        let names: Vec<(&str, &str)> = scan.entries.iter().map(|(k, u)| (k.as_str(), u.name.as_str())).collect();
+        assert_eq!(names, vec![("u1", "alice"), ("u3", "carol")]);
+        assert_eq!(scan.errors.len(), 1);
+        assert!(matches!(&scan.errors[0], KvError::Deserialize { key, .. } if key == "u2"));
+        Ok(())
+    }
+}
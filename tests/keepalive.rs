@@ -0,0 +1,99 @@
+use tinykv_rs::errorlog::ErrorCategory;
+use tinykv_rs::keepalive;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::testing::TestServer;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 等到服务器关闭连接，返回等待的时间；超过 limit 时失败
+    fn wait_closed(stream: &mut TcpStream, limit: Duration) -> Duration {
+        let started = Instant::now();
+        stream.set_read_timeout(Some(limit)).unwrap();
+        let mut buf = [0u8; 64];
+        match stream.read(&mut buf) {
+            Ok(0) => {}
+            Ok(n) => panic!("unexpected {} bytes from the server", n),
+            Err(e) => assert!(
+                !matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut),
+                "connection still open after {:?}",
+                limit
+            ),
+        }
+        started.elapsed()
+    }
+
+    #[test]
+    fn test_half_open_connection_is_reaped() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_blocked_read: Some(Duration::from_millis(200)), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+
+        // 发出半条命令后不再读写，服务端的连接线程阻塞在读上
+        let mut paused = TcpStream::connect(server.addr())?;
+        paused.write_all(br#"{"type":"Get","cf":"#)?;
+        let waited = wait_closed(&mut paused, Duration::from_secs(5));
+        assert!(waited >= Duration::from_millis(100), "{:?}", waited);
+
+        // 服务器的其他连接和统计不受影响
+        let mut admin = server.connect()?;
+        assert!(admin.connections_reaped()? >= 1);
+        assert!(admin.clients()?.len() <= 2);
+        let errors = admin.recent_errors(10)?;
+        assert!(errors.iter().any(|e| e.category == ErrorCategory::Connection && e.message.contains("blocked in read")), "{:?}", errors);
+        Ok(())
+    }
+
+    #[test]
+    fn test_active_and_subscribed_connections_are_kept() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_blocked_read: Some(Duration::from_millis(300)), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let mut subscriber = server.connect()?.subscribe(&["news"])?;
+        let mut busy = server.connect()?;
+
+        // 不断发送命令的连接每次都重新计时
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(800) {
+            busy.put("cf", "k", "v")?;
+            thread::sleep(Duration::from_millis(50));
+        }
+        // 推送模式的连接不在等待命令，不会被断开
+        assert_eq!(busy.publish("news", b"still here")?, 1);
+        assert_eq!(subscriber.next().unwrap().payload.0, b"still here");
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_timeout_closes_silent_connections() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { idle_timeout: Some(Duration::from_millis(200)), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let mut silent = TcpStream::connect(server.addr())?;
+        wait_closed(&mut silent, Duration::from_secs(5));
+
+        // 读超时不计入被断开的连接数
+        assert_eq!(server.connect()?.connections_reaped()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keepalive_is_enabled_on_client_connections() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { tcp_keepalive: Some(Duration::from_secs(30)), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+
+        let stream = TcpStream::connect(server.addr())?;
+        assert!(!keepalive::keepalive_enabled(&stream)?);
+        keepalive::set_keepalive(&stream, Duration::from_secs(10))?;
+        assert_eq!(keepalive::keepalive_enabled(&stream)?, cfg!(unix));
+
+        let mut client = server.connect()?;
+        client.set_keepalive(Duration::from_secs(10))?;
+        client.put("cf", "k", "v")?;
+        assert_eq!(client.get("cf", "k")?, Some("v".to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::server::{KvServer, ServerConfig};
+use tinykv_rs::storage::{self, FileSystem, LoadingReads, OsFileSystem, StorageOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 读取文件时阻塞到 release 被调用，模拟很慢的加载
+#[derive(Debug, Default)]
+struct GatedFs {
+    released: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl GatedFs {
+    fn release(&self) {
+        *self.released.lock().unwrap() = true;
+        self.cond.notify_all();
+    }
+}
+
+impl FileSystem for GatedFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.create_dir_all(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        OsFileSystem.write_file(path, data, sync)
+    }
+
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        OsFileSystem.append_file(path, data, sync)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        OsFileSystem.rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.remove_file(path)
+    }
+
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.sync_dir(path)
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut released = self.released.lock().unwrap();
+        while !*released {
+            released = self.cond.wait(released).unwrap();
+        }
+        OsFileSystem.read_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(path: &Path) {
+        let storage = storage::StandaloneStorage::open(path).unwrap();
+        let batch = vec![
+            Modify::new_put("cf".into(), b"k1".to_vec(), b"v1".to_vec()),
+            Modify::new_put("cf".into(), b"k2".to_vec(), b"v2".to_vec()),
+        ];
+        storage.write(batch).unwrap();
+        storage.flush().unwrap();
+    }
+
+    fn lazy(fs: &Arc<GatedFs>, reads: LoadingReads) -> StorageOptions {
+        StorageOptions { fs: fs.clone(), lazy_load: Some(reads), ..StorageOptions::default() }
+    }
+
+    #[test]
+    fn test_reads_fail_while_loading_and_writes_are_merged() {
+        let path = temp_dir("lazy_load_fail");
+        seed(&path);
+        let fs = Arc::new(GatedFs::default());
+        let storage = Arc::new(storage::StandaloneStorage::open_with_options(&path, lazy(&fs, LoadingReads::Fail)).unwrap());
+        let loader = storage.start_loader().unwrap();
+        assert!(storage.start_loader().is_none());
+
+        let err = storage.reader().err().unwrap();
+        assert!(err.starts_with("Loading"), "{}", err);
+        let status = storage.load_status().unwrap();
+        assert!(!status.loaded);
+        assert!(status.total_bytes > 0);
+        assert_eq!(status.loaded_bytes, 0);
+
+        // 加载期间的写入暂存，加载完成后覆盖加载的数据
+        storage.write(vec![Modify::new_put("cf".into(), b"k1".to_vec(), b"new".to_vec())]).unwrap();
+        storage.write(vec![Modify::new_delete("cf".into(), b"k2".to_vec())]).unwrap();
+        assert_eq!(storage.load_status().unwrap().pending_writes, 2);
+
+        fs.release();
+        loader.join().unwrap();
+        let status = storage.load_status().unwrap();
+        assert!(status.loaded);
+        assert_eq!(status.loaded_bytes, status.total_bytes);
+        assert_eq!(status.pending_writes, 0);
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("cf", b"k1").unwrap(), Some(b"new".to_vec()));
+        assert_eq!(reader.get_cf("cf", b"k2").unwrap(), None);
+        assert!(storage.take_recovery_report().unwrap().is_some());
+
+        // 合并的写入和其他写入一样会被持久化
+        storage.flush().unwrap();
+        drop(reader);
+        drop(storage);
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(storage.reader().unwrap().get_cf("cf", b"k1").unwrap(), Some(b"new".to_vec()));
+        drop(storage);
+    }
+
+    #[test]
+    fn test_server_accepts_connections_and_reads_wait_for_load() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("lazy_load_wait");
+        seed(&path);
+        let fs = Arc::new(GatedFs::default());
+        let config = ServerConfig {
+            data_path: Some(path.to_path_buf()),
+            storage_options: lazy(&fs, LoadingReads::Wait),
+            ..ServerConfig::default()
+        };
+        let handle = KvServer::with_config(config)?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+        let mut client = KvClient::connect(&addr)?;
+        let status = client.load_status()?.unwrap();
+        assert!(!status.loaded);
+
+        let reader = thread::spawn(move || KvClient::connect(&addr).unwrap().get("cf", "k1").unwrap());
+        thread::sleep(Duration::from_millis(100));
+        assert!(!reader.is_finished());
+
+        fs.release();
+        assert_eq!(reader.join().unwrap(), Some("v1".to_string()));
+        assert_eq!(client.load_status()?, None);
+        assert_eq!(client.get("cf", "k2")?, Some("v2".to_string()));
+
+        drop(handle);
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Command, Modify, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big_batch(round: usize) -> Vec<Modify> {
+        (0..2000).map(|i| Modify::new_put("cf".to_string(), format!("k{}", i).into_bytes(), format!("v{}", round).into_bytes())).collect()
+    }
+
+    #[test]
+    fn test_uncontended_acquisitions_are_counted_without_waits() {
+        let storage = storage::StandaloneStorage::in_memory();
+        storage.write(big_batch(0)).unwrap();
+        let reader = storage.reader().unwrap();
+        reader.get_cf("cf", b"k1").unwrap();
+
+        let stats = storage.lock_stats();
+        assert!(stats.write_lock_acquisitions >= 1 && stats.read_lock_acquisitions >= 1, "{:?}", stats);
+        assert_eq!((stats.read_lock_waits, stats.write_lock_waits, stats.lock_wait_p99_us), (0, 0, 0));
+        assert_eq!(storage::take_thread_lock_wait(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_contended_workload_reports_waits() {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        let config = ServerConfig { lock_wait_warn_threshold: Some(Duration::from_secs(60)), ..ServerConfig::default() };
+        let api = Arc::new(RawKeyValueApi::with_config(Arc::clone(&storage), Arc::new(config)));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let writer = {
+            let storage = Arc::clone(&storage);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut round = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    storage.write(big_batch(round)).unwrap();
+                    round += 1;
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let api = Arc::clone(&api);
+                thread::spawn(move || {
+                    let mut session = api.new_session();
+                    for _ in 0..2000 {
+                        let get = Command::Get { cf: "cf".to_string(), key: b"k1".to_vec() };
+                        assert!(matches!(api.handle_command(&mut session, get), Response::Value(_)));
+                        let put = Command::Put { cf: "cf".to_string(), key: b"own".to_vec(), value: b"v".to_vec() };
+                        assert!(matches!(api.handle_command(&mut session, put), Response::Ok));
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        stop.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+
+        let stats = storage.lock_stats();
+        assert!(stats.read_lock_waits + stats.write_lock_waits > 0, "{:?}", stats);
+        assert!(stats.read_wait.max.max(stats.write_wait.max) > 0, "{:?}", stats);
+        assert!(stats.lock_wait_p99_us > 0, "{:?}", stats);
+        assert!(stats.write_lock_acquisitions >= 8000, "{:?}", stats);
+
+        // Info 报告同样的统计，ResetStats 清零
+        let mut session = api.new_session();
+        match api.handle_command(&mut session, Command::InfoSummary) {
+            Response::Info { locks, .. } => assert!(locks.read_lock_waits + locks.write_lock_waits > 0),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(matches!(api.handle_command(&mut session, Command::ResetStats), Response::Ok));
+        let stats = storage.lock_stats();
+        assert_eq!((stats.read_lock_waits, stats.write_lock_waits, stats.lock_wait_p99_us), (0, 0, 0));
+    }
+}
@@ -0,0 +1,58 @@
+use tinykv_rs::lockfile::{self, LockOwner, LOCK_FILE};
+use tinykv_rs::storage::{StandaloneStorage, StorageOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+use std::process;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_open_of_same_path_fails() {
+        let path = temp_dir("lock_twice");
+        let first = StandaloneStorage::open(&path).unwrap();
+        let owner = lockfile::read_lock_owner(&path).unwrap();
+        assert_eq!(owner.pid, process::id());
+
+        let err = StandaloneStorage::open(&path).err().unwrap();
+        assert!(err.starts_with(&format!("DataDirLocked: pid {}, acquired_at {}", owner.pid, owner.acquired_at_ms)), "{}", err);
+
+        // 释放后可以再次打开，锁文件保留但内容被清空
+        drop(first);
+        assert_eq!(lockfile::read_lock_owner(&path), None);
+        let second = StandaloneStorage::open(&path).unwrap();
+        drop(second);
+    }
+
+    #[test]
+    fn test_stale_lock_from_exited_process_is_taken_over() {
+        let path = temp_dir("lock_stale");
+        let mut child = process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        let stale = LockOwner { pid: dead_pid, acquired_at_ms: 1 };
+        fs::write(path.join(LOCK_FILE), serde_json::to_vec(&stale).unwrap()).unwrap();
+
+        let storage = StandaloneStorage::open(&path).unwrap();
+        assert_eq!(lockfile::read_lock_owner(&path).unwrap().pid, process::id());
+        drop(storage);
+    }
+
+    #[test]
+    fn test_force_unlock() {
+        let path = temp_dir("lock_force");
+        let _held = StandaloneStorage::open(&path).unwrap();
+        let options = StorageOptions { force_unlock: true, ..StorageOptions::default() };
+        let forced = StandaloneStorage::open_with_options(&path, options).unwrap();
+        drop(forced);
+    }
+
+    #[test]
+    fn test_memory_mode_takes_no_lock() {
+        let _a = StandaloneStorage::in_memory();
+        let _b = StandaloneStorage::in_memory();
+        let _c = StandaloneStorage::default();
+    }
+}
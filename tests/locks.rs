@@ -0,0 +1,151 @@
+use tinykv_rs::client::{KvClient, LockGuard};
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::{Command, Response};
+use tinykv_rs::server::{KvServer, ServerConfig};
+use tinykv_rs::storage;
+use tinykv_rs::testing::{temp_dir, wait_until, TestServer};
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_clock(path: Option<&Path>, clock: &Arc<MockClock>) -> storage::StandaloneStorage {
+        let options = storage::StorageOptions { clock: clock.clone(), ..storage::StorageOptions::default() };
+        match path {
+            Some(path) => storage::StandaloneStorage::open_with_options(path, options).unwrap(),
+            None => storage::StandaloneStorage::in_memory_with_options(options),
+        }
+    }
+
+    fn lock_entries(storage: &storage::StandaloneStorage) -> usize {
+        storage.reader().unwrap().scan_cf(storage::LOCKS_CF, b"", None, 100, None).unwrap().len()
+    }
+
+    #[test]
+    fn test_contention_between_clients() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+        let mut first = KvClient::connect(&addr)?;
+        let mut second = KvClient::connect(&addr)?;
+
+        let token = first.acquire_lock("job", Duration::from_secs(10))?.expect("first acquire");
+        assert_eq!(second.acquire_lock("job", Duration::from_secs(10))?, None);
+        // 不同名字的锁互不影响
+        assert!(second.acquire_lock("other", Duration::from_secs(10))?.is_some());
+
+        first.release_lock("job", token)?;
+        let next = second.acquire_lock("job", Duration::from_secs(10))?.expect("acquire after release");
+        assert!(next > token);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_token_release_rejected() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = Session::default();
+
+        let token = match api.handle_command(&mut session, Command::LockAcquire { name: "job".to_string(), ttl_ms: 10_000 }) {
+            Response::LockToken(Some(token)) => token,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        for cmd in [
+            Command::LockRelease { name: "job".to_string(), token: token + 1 },
+            Command::LockRenew { name: "job".to_string(), token: token + 1, ttl_ms: 10_000 },
+            Command::LockRelease { name: "missing".to_string(), token },
+        ] {
+            match api.handle_command(&mut session, cmd) {
+                Response::Error(e) => assert!(e.contains("LockNotHeld"), "{}", e),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+
+        // 锁仍由原持有者持有
+        assert!(matches!(
+            api.handle_command(&mut session, Command::LockAcquire { name: "job".to_string(), ttl_ms: 10_000 }),
+            Response::LockToken(None)
+        ));
+        assert!(matches!(api.handle_command(&mut session, Command::LockRelease { name: "job".to_string(), token }), Response::Ok));
+    }
+
+    #[test]
+    fn test_expired_lock_is_reclaimed() {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let storage = with_clock(None, &clock);
+        let token = storage.lock_acquire("job", 30).unwrap().unwrap();
+        assert_eq!(storage.lock_acquire("job", 30).unwrap(), None);
+        clock.advance(Duration::from_millis(30));
+
+        // 过期后续约失败，其他人可以直接获取
+        assert!(storage.lock_renew("job", token, 30).is_err());
+        let next = storage.lock_acquire("job", 30).unwrap().unwrap();
+        assert_ne!(next, token);
+        assert!(storage.lock_renew("job", token, 30).is_err());
+
+        storage.lock_acquire("abandoned", 10_000).unwrap().unwrap();
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(storage.sweep_expired_locks().unwrap(), 1);
+        assert_eq!(lock_entries(&storage), 1);
+    }
+
+    #[test]
+    fn test_sweeper_thread_removes_abandoned_locks() {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let storage = Arc::new(with_clock(None, &clock));
+        let _sweeper = storage.start_lock_sweeper(Duration::from_millis(10));
+        storage.lock_acquire("job", 20).unwrap().unwrap();
+        assert_eq!(lock_entries(&storage), 1);
+
+        // 推进时钟后锁已过期，清理线程在下一次检查时删除它
+        clock.advance(Duration::from_millis(20));
+        wait_until(|| lock_entries(&storage) == 0);
+    }
+
+    #[test]
+    fn test_tokens_increase_across_restart() {
+        let path = temp_dir("locks_restart");
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let storage = with_clock(Some(&path), &clock);
+        let token = storage.lock_acquire("job", 10_000).unwrap().unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+        // 令牌以打开时的毫秒时间戳为起点
+        clock.advance(Duration::from_millis(1));
+
+        let reopened = with_clock(Some(&path), &clock);
+        assert_eq!(reopened.lock_acquire("job", 10_000).unwrap(), None);
+        reopened.lock_release("job", token).unwrap();
+        assert!(reopened.lock_acquire("job", 10_000).unwrap().unwrap() > token);
+    }
+
+    #[test]
+    fn test_guard_renews_and_releases_on_drop() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let options = storage::StorageOptions { clock: clock.clone(), ..storage::StorageOptions::default() };
+        let server = TestServer::start_with_config(ServerConfig { storage_options: options, ..ServerConfig::default() })?;
+        let mut other = server.connect()?;
+
+        let guard = LockGuard::acquire(server.addr(), "job", Duration::from_millis(90))?.expect("guard acquire");
+        assert!(LockGuard::acquire(server.addr(), "job", Duration::from_millis(90))?.is_none());
+
+        // 每次把时钟推进到租约到期之前，等后台线程续约后再推进，累计超过几个租约周期
+        let renewals = |client: &mut KvClient| -> Result<u64, Box<dyn std::error::Error>> {
+            Ok(client.latency()?.get("LockRenew").map_or(0, |l| l.count))
+        };
+        for _ in 0..4 {
+            let before = renewals(&mut other)?;
+            clock.advance(Duration::from_millis(60));
+            wait_until(|| renewals(&mut other).unwrap() > before);
+        }
+        assert!(guard.is_held());
+        assert_eq!(other.acquire_lock("job", Duration::from_secs(10))?, None);
+
+        drop(guard);
+        assert!(other.acquire_lock("job", Duration::from_secs(10))?.is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage::{self, FileSystem, MaintenanceOperation, MaintenancePhase, OsFileSystem, StorageOptions};
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// fail 为 true 时所有写入都失败
+#[derive(Debug, Default)]
+struct FailingFs {
+    fail: AtomicBool,
+}
+
+impl FailingFs {
+    fn check(&self) -> io::Result<()> {
+        match self.fail.load(Ordering::SeqCst) {
+            true => Err(io::Error::other("disk full")),
+            false => Ok(()),
+        }
+    }
+}
+
+impl FileSystem for FailingFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.create_dir_all(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        self.check()?;
+        OsFileSystem.write_file(path, data, sync)
+    }
+
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        self.check()?;
+        OsFileSystem.append_file(path, data, sync)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        OsFileSystem.rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.remove_file(path)
+    }
+
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.sync_dir(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_keys(storage: &storage::StandaloneStorage, n: usize) {
+        let batch = (0..n).map(|i| Modify::new_put("cf".into(), format!("k{}", i).into_bytes(), b"v".to_vec())).collect();
+        storage.write(batch).unwrap();
+    }
+
+    #[test]
+    fn test_flush_and_compact_report_progress() {
+        let path = temp_dir("maintenance_progress");
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(storage.maintenance_status(), None);
+
+        put_keys(&storage, 10);
+        storage.flush().unwrap();
+        let status = storage.maintenance_status().unwrap();
+        assert_eq!(status.operation, MaintenanceOperation::Flush);
+        assert_eq!((status.processed_entries, status.total_entries), (10, 10));
+        assert!(!status.running());
+        assert_eq!(status.error, None);
+
+        put_keys(&storage, 15);
+        storage.compact().unwrap();
+        let status = storage.maintenance_status().unwrap();
+        assert_eq!(status.operation, MaintenanceOperation::Compact);
+        assert_eq!((status.processed_entries, status.total_entries), (15, 15));
+        assert_eq!(status.phase, MaintenancePhase::Done);
+
+        drop(storage);
+    }
+
+    #[test]
+    fn test_failure_is_kept_until_next_operation() {
+        let path = temp_dir("maintenance_failure");
+        let fs = Arc::new(FailingFs::default());
+        let options = StorageOptions { fs: fs.clone(), ..StorageOptions::default() };
+        let storage = storage::StandaloneStorage::open_with_options(&path, options).unwrap();
+        put_keys(&storage, 3);
+
+        fs.fail.store(true, Ordering::SeqCst);
+        assert!(storage.flush().is_err());
+        let status = storage.maintenance_status().unwrap();
+        assert!(!status.running());
+        assert!(status.error.as_deref().unwrap().contains("disk full"), "{:?}", status.error);
+        // 失败后状态不变，直到下一次操作开始
+        assert_eq!(storage.maintenance_status(), Some(status));
+
+        fs.fail.store(false, Ordering::SeqCst);
+        storage.compact().unwrap();
+        let status = storage.maintenance_status().unwrap();
+        assert_eq!((status.operation, status.error), (MaintenanceOperation::Compact, None));
+
+        drop(storage);
+    }
+
+    #[test]
+    fn test_info_includes_maintenance_status() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        assert_eq!(client.maintenance_status()?, None);
+
+        client.put("cf", "k", "v")?;
+        client.compact()?;
+        let status = client.maintenance_status()?.unwrap();
+        assert_eq!(status.operation, MaintenanceOperation::Compact);
+        assert!(!status.running());
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use tinykv_rs::storage::{self, StorageOptions};
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Command, Modify, Response};
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(storage: &storage::StandaloneStorage, key: &str, value_len: usize) -> Result<(), String> {
+        storage.write(vec![Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), vec![b'x'; value_len])])
+    }
+
+    fn delete(storage: &storage::StandaloneStorage, key: &str) -> Result<(), String> {
+        storage.write(vec![Modify::new_delete("cf".to_string(), key.as_bytes().to_vec())])
+    }
+
+    #[test]
+    fn test_memory_usage_tracks_overwrites() {
+        let storage = storage::StandaloneStorage::in_memory();
+        assert_eq!(storage.memory_usage().unwrap(), 0);
+
+        put(&storage, "k", 100).unwrap();
+        let one_entry = storage.memory_usage().unwrap();
+        assert!(one_entry >= "cf_k".len() + 100);
+
+        // 用不同大小的值覆盖同一个键：只计算差值，不重复计算条目开销
+        put(&storage, "k", 1000).unwrap();
+        assert_eq!(storage.memory_usage().unwrap(), one_entry + 900);
+        put(&storage, "k", 10).unwrap();
+        assert_eq!(storage.memory_usage().unwrap(), one_entry - 90);
+
+        put(&storage, "other", 10).unwrap();
+        delete(&storage, "k").unwrap();
+        delete(&storage, "missing").unwrap();
+        assert_eq!(storage.memory_usage().unwrap(), one_entry - 90 + 4);
+
+        delete(&storage, "other").unwrap();
+        assert_eq!(storage.memory_usage().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_writes_rejected_over_budget() {
+        let options = StorageOptions { max_memory_bytes: Some(1000), ..StorageOptions::default() };
+        let storage = storage::StandaloneStorage::in_memory_with_options(options);
+
+        put(&storage, "a", 400).unwrap();
+        put(&storage, "b", 400).unwrap();
+        let err = put(&storage, "c", 400).unwrap_err();
+        assert!(err.starts_with("OutOfMemoryBudget"), "{}", err);
+
+        // 超出预算后仍然可以缩小已有的值或删除
+        put(&storage, "a", 10).unwrap();
+        put(&storage, "c", 300).unwrap();
+        delete(&storage, "b").unwrap();
+        put(&storage, "d", 400).unwrap();
+        assert!(storage.memory_usage().unwrap() <= 1000);
+    }
+
+    #[test]
+    fn test_info_reports_memory() {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        put(&storage, "k", 100).unwrap();
+        let expected = storage.memory_usage().unwrap();
+
+        let api = RawKeyValueApi::new(storage);
+        match api.handle_command(&mut api.new_session(), Command::Info) {
+            Response::Info { memory_bytes, .. } => assert_eq!(memory_bytes, expected),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+}
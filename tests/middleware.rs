@@ -0,0 +1,110 @@
+use tinykv_rs::protocol::{Command, Response};
+use tinykv_rs::server::{ConnContext, Middleware, ServerConfig};
+use tinykv_rs::testing::TestServer;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 记录每个命令经过 before / after 时看到的信息
+#[derive(Debug, Default)]
+struct Recorder {
+    calls: Mutex<Vec<String>>,
+}
+
+impl Recorder {
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().iter().filter(|c| !c.contains("Hello")).cloned().collect()
+    }
+}
+
+impl Middleware for Recorder {
+    fn before(&self, ctx: &ConnContext, cmd: &Command) -> Result<(), Box<Response>> {
+        self.calls.lock().unwrap().push(format!("before {} db={}", cmd.kind(), ctx.db));
+        Ok(())
+    }
+
+    fn after(&self, ctx: &ConnContext, cmd: &Command, response: &Response, elapsed: Duration) {
+        assert!(elapsed < Duration::from_secs(5));
+        let outcome = if matches!(response, Response::Error(_)) { "error" } else { "ok" };
+        self.calls.lock().unwrap().push(format!("after {} db={} {}", cmd.kind(), ctx.db, outcome));
+    }
+}
+
+/// 拒绝对 protected 列族的删除
+#[derive(Debug)]
+struct Protect;
+
+impl Middleware for Protect {
+    fn before(&self, _ctx: &ConnContext, cmd: &Command) -> Result<(), Box<Response>> {
+        match cmd {
+            Command::Delete { cf, .. } if cf == "protected" => Err(Box::new(Response::Error("Forbidden: protected".into()))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(middlewares: Vec<Arc<dyn Middleware>>) -> TestServer {
+        TestServer::start_with_config(ServerConfig { middlewares, ..ServerConfig::default() }).unwrap()
+    }
+
+    #[test]
+    fn test_middlewares_wrap_every_command() -> Result<(), Box<dyn std::error::Error>> {
+        let recorder = Arc::new(Recorder::default());
+        let mut server = start(vec![recorder.clone()]);
+        let client = server.client();
+        client.put("cf", "k", "v")?;
+        client.use_db("other")?;
+        assert_eq!(client.get("cf", "k")?, None);
+
+        assert_eq!(
+            recorder.calls(),
+            vec![
+                "before Put db=default",
+                "after Put db=default ok",
+                "before UseDb db=default",
+                "after UseDb db=default ok",
+                "before Get db=other",
+                "after Get db=other ok",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_before_can_reject_commands() -> Result<(), Box<dyn std::error::Error>> {
+        let recorder = Arc::new(Recorder::default());
+        let mut server = start(vec![Arc::new(Protect), recorder.clone()]);
+        let client = server.client();
+        client.put("protected", "k", "v")?;
+
+        let err = client.delete("protected", "k").unwrap_err();
+        assert!(err.to_string().contains("Forbidden"), "{}", err);
+        assert_eq!(client.get("protected", "k")?, Some("v".to_string()));
+        client.delete("other", "k")?;
+
+        // 被拒绝的命令不再经过后面的 before，但所有中间件都能在 after 中看到拒绝的响应
+        let calls = recorder.calls();
+        assert_eq!(calls.iter().filter(|c| c.starts_with("before Delete")).count(), 1);
+        assert!(calls.contains(&"after Delete db=default error".to_string()), "{:?}", calls);
+        assert!(calls.contains(&"after Delete db=default ok".to_string()), "{:?}", calls);
+        Ok(())
+    }
+
+    #[test]
+    fn test_middlewares_see_the_default_cf() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = start(vec![Arc::new(Protect)]);
+        let client = server.client();
+        client.use_cf("protected")?;
+        client.put("", "k", "v")?;
+
+        // 空的列族名在中间件之前替换为默认列族，Protect 照常拒绝
+        let err = client.delete("", "k").unwrap_err();
+        assert!(err.to_string().contains("Forbidden"), "{}", err);
+        assert_eq!(client.get("protected", "k")?, Some("v".to_string()));
+        Ok(())
+    }
+}
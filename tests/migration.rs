@@ -0,0 +1,138 @@
+use tinykv_rs::migration::{self, FORMAT_VERSION, FORMAT_VERSION_FILE, MIGRATIONS};
+use tinykv_rs::storage::{self, RecoveryReport};
+use tinykv_rs::testing::{temp_dir, TempDir};
+
+use std::fs;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把 tests/fixtures 下的旧格式目录复制到新的临时目录
+    fn copy_fixture(fixture: &str, name: &str) -> TempDir {
+        let dir = temp_dir(name);
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture);
+        for entry in fs::read_dir(source).unwrap() {
+            let entry = entry.unwrap();
+            fs::copy(entry.path(), dir.join(entry.file_name())).unwrap();
+        }
+        dir
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != "LOCK")
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn get(storage: &storage::StandaloneStorage, cf: &str, key: &[u8]) -> Option<Vec<u8>> {
+        storage.reader().unwrap().get_cf(cf, key).unwrap()
+    }
+
+    #[test]
+    fn test_every_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len() as u32, FORMAT_VERSION - 1);
+    }
+
+    #[test]
+    fn test_migrate_v1_legacy_snapshot() {
+        let dir = copy_fixture("format-v1-snapshot", "migration_snapshot");
+        assert_eq!(migration::read_version(&dir).unwrap(), Some(1));
+
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&dir).unwrap();
+        assert_eq!(report.migrations, vec!["v1_to_v2".to_string()]);
+        assert_eq!(report.snapshot_entries, 3);
+        assert_eq!(get(&storage, "users", b"u1"), Some(b"alice".to_vec()));
+        assert_eq!(get(&storage, "users", b"a_b"), Some(b"underscore".to_vec()));
+        assert!(storage.ttl("orders", b"o1").unwrap().is_some());
+        assert_eq!(files(&dir), vec!["FORMAT_VERSION", "MANIFEST", "base-000001.json"]);
+        assert_eq!(fs::read_to_string(dir.join(FORMAT_VERSION_FILE)).unwrap().trim(), FORMAT_VERSION.to_string());
+        drop(storage);
+
+        // 再次打开时不需要迁移
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&dir).unwrap();
+        assert!(report.migrations.is_empty());
+        assert_eq!(get(&storage, "orders", b"o1"), Some(b"pending".to_vec()));
+        drop(storage);
+    }
+
+    #[test]
+    fn test_migrate_v1_segments_keeps_torn_tail() {
+        let dir = copy_fixture("format-v1-segments", "migration_segments");
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&dir).unwrap();
+        assert_eq!(
+            report,
+            RecoveryReport {
+                snapshot_entries: 2,
+                wal_records_replayed: 1,
+                corrupt_records_skipped: 1,
+                last_sequence: 2,
+                expired_entries_dropped: 0,
+                migrations: vec!["v1_to_v2".to_string()],
+            }
+        );
+        assert_eq!(get(&storage, "users", b"u1"), Some(b"bob".to_vec()));
+        assert_eq!(get(&storage, "users", b"a_b"), Some(b"underscore".to_vec()));
+        assert_eq!(get(&storage, storage::LOCKS_CF, b"job"), Some(vec![0u8; 16]));
+        let quarantined = tinykv_rs::api::EncodedKey::encode("users", b"bad");
+        assert_eq!(get(&storage, storage::QUARANTINE_CF, quarantined.as_bytes()), Some(b"x".to_vec()));
+        drop(storage);
+    }
+
+    #[test]
+    fn test_newer_format_is_rejected() {
+        let dir = copy_fixture("format-v1-snapshot", "migration_newer");
+        fs::write(dir.join(FORMAT_VERSION_FILE), format!("{}\n", FORMAT_VERSION + 1)).unwrap();
+        let err = storage::StandaloneStorage::open(&dir).err().unwrap();
+        assert!(err.starts_with("UnsupportedFormat"), "{}", err);
+        assert_eq!(files(&dir), vec!["FORMAT_VERSION", "data.json"]);
+    }
+
+    #[test]
+    fn test_failed_migration_leaves_directory_untouched() {
+        let dir = copy_fixture("format-v1-segments", "migration_failed");
+        fs::write(dir.join("base-000001.json"), "{ not json").unwrap();
+        let before: Vec<(String, Vec<u8>)> = files(&dir).into_iter().map(|f| (f.clone(), fs::read(dir.join(&f)).unwrap())).collect();
+
+        let err = storage::StandaloneStorage::open(&dir).err().unwrap();
+        assert!(err.starts_with("MigrationFailed: v1_to_v2"), "{}", err);
+        let after: Vec<(String, Vec<u8>)> = files(&dir).into_iter().map(|f| (f.clone(), fs::read(dir.join(&f)).unwrap())).collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_interrupted_migration_is_resumed_or_discarded() {
+        // 提交前崩溃：丢弃临时目录，重新迁移
+        let dir = copy_fixture("format-v1-snapshot", "migration_uncommitted");
+        fs::create_dir(dir.join("migrate.tmp")).unwrap();
+        fs::write(dir.join("migrate.tmp").join("data.json"), "partial").unwrap();
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&dir).unwrap();
+        assert_eq!(report.migrations, vec!["v1_to_v2".to_string()]);
+        assert_eq!(get(&storage, "users", b"u1"), Some(b"alice".to_vec()));
+        drop(storage);
+        assert_eq!(files(&dir), vec!["FORMAT_VERSION", "MANIFEST", "base-000001.json"]);
+
+        // 提交后、替换到一半时崩溃：打开时完成替换
+        let migrated = copy_fixture("format-v1-snapshot", "migration_committed_source");
+        drop(storage::StandaloneStorage::open(&migrated).unwrap());
+        let dir = copy_fixture("format-v1-snapshot", "migration_committed");
+        let done = dir.join("migrate.done");
+        fs::create_dir(&done).unwrap();
+        for name in ["FORMAT_VERSION", "MANIFEST", "base-000001.json"] {
+            fs::copy(migrated.join(name), done.join(name)).unwrap();
+        }
+        fs::write(done.join("FILES"), "FORMAT_VERSION\nMANIFEST\nbase-000001.json").unwrap();
+        fs::rename(done.join("MANIFEST"), dir.join("MANIFEST")).unwrap();
+
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&dir).unwrap();
+        assert!(report.migrations.is_empty());
+        assert_eq!(get(&storage, "users", b"a_b"), Some(b"underscore".to_vec()));
+        drop(storage);
+        assert_eq!(files(&dir), vec!["FORMAT_VERSION", "MANIFEST", "base-000001.json"]);
+    }
+}
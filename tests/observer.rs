@@ -0,0 +1,127 @@
+use tinykv_rs::errorlog::ErrorCategory;
+use tinykv_rs::observer::{ChannelObserver, WriteObserver};
+use tinykv_rs::protocol::{Modify, ModifyOp};
+use tinykv_rs::storage::StandaloneStorage;
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 一次通知：序号和批次中的键
+type Commit = (u64, Vec<String>);
+
+/// 记录每次通知
+#[derive(Clone, Default)]
+struct Recorder {
+    commits: Arc<Mutex<Vec<Commit>>>,
+}
+
+impl WriteObserver for Recorder {
+    fn on_commit(&mut self, seq: u64, batch: &[Modify]) {
+        let keys = batch.iter().map(|m| String::from_utf8_lossy(&m.key).into_owned()).collect();
+        self.commits.lock().unwrap().push((seq, keys));
+    }
+}
+
+/// 在回调中读取存储，持有写锁时调用会死锁
+struct ReadBack {
+    storage: Arc<StandaloneStorage>,
+    seen: Sender<Option<Vec<u8>>>,
+}
+
+impl WriteObserver for ReadBack {
+    fn on_commit(&mut self, _seq: u64, batch: &[Modify]) {
+        let value = self.storage.reader().unwrap().get_cf(&batch[0].cf, &batch[0].key).unwrap();
+        self.seen.send(value).unwrap();
+    }
+}
+
+/// 第 n 次通知时 panic
+struct PanicAt(u64);
+
+impl WriteObserver for PanicAt {
+    fn on_commit(&mut self, seq: u64, _batch: &[Modify]) {
+        if seq == self.0 {
+            panic!("observer failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(key: &str, value: &str) -> Modify {
+        Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_observers_see_commits_in_order() {
+        let storage = Arc::new(StandaloneStorage::in_memory());
+        let recorder = Recorder::default();
+        storage.register_observer(Box::new(recorder.clone()));
+
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let storage = Arc::clone(&storage);
+                thread::spawn(move || {
+                    for i in 0..50 {
+                        storage.write(vec![put("shared", &format!("{}-{}", t, i)), put(&format!("t{}", t), "x")]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let commits = recorder.commits.lock().unwrap().clone();
+        assert_eq!(commits.iter().map(|(seq, _)| *seq).collect::<Vec<_>>(), (1..=200).collect::<Vec<_>>());
+        assert!(commits.iter().all(|(_, keys)| keys.len() == 2 && keys[0] == "shared"));
+        drop(commits);
+
+        // 原子操作提交的批次同样通知，空批次（rename 到自身）不通知
+        storage.rename("cf", b"t0", b"moved", false).unwrap();
+        storage.rename("cf", b"moved", b"moved", false).unwrap();
+        let commits = recorder.commits.lock().unwrap().clone();
+        assert_eq!(commits.last().unwrap(), &(201, vec!["moved".to_string(), "t0".to_string()]));
+        assert_eq!(commits.len(), 201);
+    }
+
+    #[test]
+    fn test_observers_run_after_the_write_lock_is_released() {
+        let storage = Arc::new(StandaloneStorage::in_memory());
+        let (seen, values) = mpsc::channel();
+        storage.register_observer(Box::new(ReadBack { storage: Arc::clone(&storage), seen }));
+
+        let writer = {
+            let storage = Arc::clone(&storage);
+            thread::spawn(move || storage.write(vec![put("k", "v1")]).unwrap())
+        };
+        // 回调能读到本次提交的值
+        assert_eq!(values.recv_timeout(Duration::from_secs(5)).unwrap(), Some(b"v1".to_vec()));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_panicking_observer_is_disabled() {
+        let storage = StandaloneStorage::in_memory();
+        let (sender, commits) = mpsc::channel();
+        storage.register_observer(Box::new(PanicAt(2)));
+        storage.register_observer(Box::new(ChannelObserver::new(sender)));
+
+        for i in 0..4 {
+            storage.write(vec![put(&format!("k{}", i), "v")]).unwrap();
+        }
+        // 其他观察者和存储都不受影响
+        let received: Vec<(u64, Vec<Modify>)> = commits.try_iter().collect();
+        assert_eq!(received.iter().map(|(seq, _)| *seq).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(matches!(received[3].1[0].op, ModifyOp::Put));
+        assert_eq!(storage.reader().unwrap().get_cf("cf", b"k3").unwrap(), Some(b"v".to_vec()));
+
+        let events = storage.error_log().recent(10);
+        assert_eq!(events.iter().filter(|e| e.category == ErrorCategory::Observer).count(), 1);
+        assert!(events[0].message.contains("observer failed"), "{:?}", events);
+    }
+}
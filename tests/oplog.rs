@@ -0,0 +1,199 @@
+use tinykv_rs::acl::{CfPermission, Principal};
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::oplog::{self, OplogConfig, OplogEntry, OplogReader};
+use tinykv_rs::protocol::{Bytes, Command, Modify, ModifyOp, Response};
+use tinykv_rs::rotation::RotationPolicy;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{StandaloneStorage, StorageOptions};
+use tinykv_rs::testing::{temp_dir, TestServer};
+use tinykv_rs::testing::datagen::Rng;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_with_oplog(config: OplogConfig, clock: Arc<MockClock>) -> StandaloneStorage {
+        StandaloneStorage::in_memory_with_options(StorageOptions { oplog: Some(config), clock, ..StorageOptions::default() })
+    }
+
+    fn read_all(path: &Path) -> Vec<OplogEntry> {
+        OplogReader::open(path).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    fn put(cf: &str, key: &str, value: &str) -> Modify {
+        Modify::new_put(cf.to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    /// 整个存储的内容，按列族和键排序
+    fn contents(storage: Arc<StandaloneStorage>) -> Vec<(String, Bytes, Bytes)> {
+        let api = RawKeyValueApi::new(storage);
+        let mut session = api.new_session();
+        match api.handle_command(&mut session, Command::ScanAll { start: None, limit: 1_000_000 }) {
+            Response::CfValues { entries, next: None, .. } => entries,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_records_each_committed_batch() {
+        let dir = temp_dir("oplog_records");
+        let path = dir.join("oplog");
+        let clock = Arc::new(MockClock::new(1_000));
+        let storage = storage_with_oplog(OplogConfig::new(&path), clock.clone());
+
+        storage.write(vec![put("users", "u1", "a"), put("users", "u2", "b")]).unwrap();
+        clock.advance(Duration::from_millis(500));
+        {
+            let _principal = oplog::enter_principal(Some("orders-svc".to_string()));
+            storage.write(vec![Modify::new_delete("users".to_string(), b"u1".to_vec())]).unwrap();
+        }
+        storage.write(vec![put("logs", "l1", "c")]).unwrap();
+
+        let entries = read_all(&path);
+        let summary: Vec<(u64, u64, Option<&str>, usize)> =
+            entries.iter().map(|e| (e.seq, e.timestamp_ms, e.principal.as_deref(), e.ops.len())).collect();
+        assert_eq!(summary, vec![(1, 1_000, None, 2), (2, 1_500, Some("orders-svc"), 1), (3, 1_500, None, 1)]);
+        assert!(matches!(entries[1].ops[0].op, ModifyOp::Delete));
+        assert_eq!(entries[1].ops[0].key, b"u1");
+        assert_eq!(storage.oplog().unwrap().next_seq().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_replay_reconstructs_the_final_state() {
+        let dir = temp_dir("oplog_replay");
+        let path = dir.join("oplog");
+        // 小文件频繁轮转，重放要跨越所有文件
+        let config = OplogConfig { path: path.clone(), rotation: RotationPolicy { max_bytes: 4096, ..RotationPolicy::default() } };
+        let original = Arc::new(storage_with_oplog(config, Arc::new(MockClock::new(1_000))));
+
+        // 在少量键上反复覆盖和删除，最终状态依赖于操作的顺序
+        let mut rng = Rng::new(7);
+        for i in 0..500 {
+            let ops = (0..1 + rng.below(4))
+                .map(|_| {
+                    let cf = ["users", "orders", "logs"][rng.below(3) as usize].to_string();
+                    let key = format!("k{}", rng.below(40)).into_bytes();
+                    match rng.below(4) {
+                        0 => Modify::new_delete(cf, key),
+                        _ => Modify::new_put(cf, key, format!("v{}", i).into_bytes()),
+                    }
+                })
+                .collect();
+            original.write(ops).unwrap();
+        }
+        assert!(fs::read_dir(&dir).unwrap().count() > 2);
+
+        let replayed = Arc::new(StandaloneStorage::in_memory());
+        assert_eq!(oplog::replay(OplogReader::open(&path).unwrap(), &replayed).unwrap(), 500);
+        let expected = contents(original);
+        assert!(!expected.is_empty());
+        assert_eq!(contents(replayed), expected);
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_retention_prunes_files_and_reopen_continues_the_sequence() {
+        let dir = temp_dir("oplog_retain");
+        let path = dir.join("oplog");
+        let clock = Arc::new(MockClock::new(1_000));
+        let policy = RotationPolicy { max_age: Some(Duration::from_secs(3600)), retain: Some(2), ..RotationPolicy::default() };
+        let config = OplogConfig { path: path.clone(), rotation: policy };
+        let storage = storage_with_oplog(config.clone(), clock.clone());
+        for i in 0..6 {
+            storage.write(vec![put("cf", &format!("k{}", i), "v")]).unwrap();
+            clock.advance(Duration::from_secs(7200));
+        }
+        assert_eq!(files(&dir), vec!["oplog", "oplog.4", "oplog.5"]);
+        let stats = storage.oplog().unwrap().stats().unwrap();
+        assert_eq!((stats.name.as_str(), stats.files, stats.rotations, stats.pruned_files), ("oplog", 3, 5, 3));
+        // 被删除的记录不再能读到，剩下的序号连续
+        assert_eq!(read_all(&path).iter().map(|e| e.seq).collect::<Vec<_>>(), vec![4, 5, 6]);
+        let (entries, next) = storage.oplog().unwrap().range(1, Some(5), 10).unwrap();
+        assert_eq!((entries.iter().map(|e| e.seq).collect::<Vec<_>>(), next), (vec![4, 5], None));
+        drop(storage);
+
+        // 崩溃留下不完整的最后一行：读取时忽略，重新打开时截掉
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"seq":7,"timestamp_ms":"#).unwrap();
+        drop(file);
+        assert_eq!(read_all(&path).len(), 3);
+        let storage = storage_with_oplog(config.clone(), clock.clone());
+        storage.write(vec![put("cf", "after-crash", "v")]).unwrap();
+        // 当前文件已超过 max_age，这次写入先轮转并删除 oplog.4
+        assert_eq!(files(&dir), vec!["oplog", "oplog.5", "oplog.6"]);
+        assert_eq!(read_all(&path).iter().map(|e| e.seq).collect::<Vec<_>>(), vec![5, 6, 7]);
+        drop(storage);
+
+        // 轮转后还没写入就崩溃，当前文件不存在时序号从最后一个轮转文件接上
+        fs::rename(&path, dir.join("oplog.9")).unwrap();
+        let storage = storage_with_oplog(config, clock);
+        storage.write(vec![put("cf", "after-rotation", "v")]).unwrap();
+        drop(storage);
+        assert_eq!(read_all(&path).iter().map(|e| e.seq).collect::<Vec<_>>(), vec![5, 6, 7, 8]);
+    }
+
+    fn oplog_server(dir: &Path) -> Result<TestServer, Box<dyn std::error::Error>> {
+        let mut config = ServerConfig {
+            admin_token: Some("root".to_string()),
+            principals: vec![Principal::new("orders-svc", "orders-token", vec![CfPermission::new("orders", true, true, false)])],
+            // OplogRange 的响应分成多帧发送
+            response_chunk_bytes: Some(4096),
+            ..ServerConfig::default()
+        };
+        config.storage_options.oplog = Some(OplogConfig::new(dir.join("oplog")));
+        TestServer::start_with_config(config)
+    }
+
+    #[test]
+    fn test_oplog_range_over_the_server() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = temp_dir("oplog_server");
+        let server = oplog_server(&dir)?;
+        let mut writer = server.connect()?;
+        writer.auth("orders-svc", "orders-token")?;
+        for i in 0..oplog::OPLOG_RANGE_LIMIT + 200 {
+            writer.put("orders", &format!("o{:04}", i), "pending")?;
+        }
+        let error = writer.oplog_range(1, None).unwrap_err().to_string();
+        assert!(error.contains("PermissionDenied"), "{}", error);
+
+        let mut admin = server.connect()?;
+        admin.admin_auth("root")?;
+        admin.put("orders", "o0000", "shipped")?;
+        let (page, next) = admin.oplog_range_page(1, None)?;
+        assert_eq!((page.len(), next), (oplog::OPLOG_RANGE_LIMIT, Some(oplog::OPLOG_RANGE_LIMIT as u64 + 1)));
+
+        let entries = admin.oplog_range(1, None)?;
+        assert_eq!(entries.len(), oplog::OPLOG_RANGE_LIMIT + 201);
+        assert!(entries.iter().enumerate().all(|(i, e)| e.seq == i as u64 + 1));
+        assert_eq!(entries[0].principal.as_deref(), Some("orders-svc"));
+        let last = entries.last().unwrap();
+        assert_eq!((last.principal.as_deref(), last.ops[0].value.as_slice()), (Some("admin"), &b"shipped"[..]));
+
+        let seqs: Vec<u64> = admin.oplog_range(10, Some(12))?.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![10, 11, 12]);
+        assert!(admin.oplog_range(5_000, None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_oplog_range_without_an_oplog() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start_with_config(ServerConfig { admin_token: Some("root".to_string()), ..ServerConfig::default() })?;
+        let client = server.client();
+        client.admin_auth("root")?;
+        let error = client.oplog_range(1, None).unwrap_err().to_string();
+        assert!(error.contains("OplogDisabled"), "{}", error);
+        Ok(())
+    }
+}
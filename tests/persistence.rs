@@ -0,0 +1,237 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::errorlog::ErrorCategory;
+use tinykv_rs::protocol::{Modify, Warning};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{self, FileSystem, OsFileSystem, StandaloneStorage, StorageOptions};
+use tinykv_rs::testing::{temp_dir, wait_until, TestServer};
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// 打开 full 时写入只写进一半就返回磁盘已满，模拟写到一半空间耗尽
+#[derive(Debug, Default)]
+struct FullDiskFs {
+    full: AtomicBool,
+}
+
+impl FullDiskFs {
+    fn set_full(&self, full: bool) {
+        self.full.store(full, Ordering::SeqCst);
+    }
+
+    fn is_full(&self) -> bool {
+        self.full.load(Ordering::SeqCst)
+    }
+
+    fn full_error() -> io::Error {
+        io::Error::new(io::ErrorKind::StorageFull, "No space left on device")
+    }
+}
+
+impl FileSystem for FullDiskFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.create_dir_all(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        if self.is_full() {
+            fs::write(path, &data[..data.len() / 2])?;
+            return Err(Self::full_error());
+        }
+        OsFileSystem.write_file(path, data, sync)
+    }
+
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        if self.is_full() {
+            fs::OpenOptions::new().append(true).open(path)?.write_all(&data[..data.len() / 2])?;
+            return Err(Self::full_error());
+        }
+        OsFileSystem.append_file(path, data, sync)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        OsFileSystem.rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.remove_file(path)
+    }
+
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.sync_dir(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(disk: &Arc<FullDiskFs>, clock: &Arc<MockClock>, segment_max_bytes: u64) -> StorageOptions {
+        StorageOptions {
+            fs: disk.clone(),
+            clock: clock.clone(),
+            segment_max_bytes,
+            ..StorageOptions::default()
+        }
+    }
+
+    fn put(storage: &StandaloneStorage, key: &str, value: &str) {
+        storage.write(vec![Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn get(storage: &StandaloneStorage, key: &str) -> Option<String> {
+        storage.reader().unwrap().get_cf("cf", key.as_bytes()).unwrap().map(|v| String::from_utf8(v).unwrap())
+    }
+
+    fn segment_files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("segment-"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_disk_full_keeps_previous_data() {
+        let path = temp_dir("persistence_keeps_previous");
+        let (disk, clock) = (Arc::new(FullDiskFs::default()), Arc::new(MockClock::new(1_000)));
+        // 每次刷盘都切换到新的段文件，覆盖写新文件和追加两条路径
+        for segment_max_bytes in [1, 1024 * 1024] {
+            let _ = fs::remove_dir_all(&path);
+            let storage = StandaloneStorage::open_with_options(&path, options(&disk, &clock, segment_max_bytes)).unwrap();
+            put(&storage, "a", "1");
+            storage.flush().unwrap();
+            let segments = segment_files(&path);
+
+            disk.set_full(true);
+            put(&storage, "a", "2");
+            put(&storage, "b", "2");
+            let error = storage.flush().unwrap_err();
+            assert!(error.starts_with("DiskFull: "), "{}", error);
+            let status = storage.persistence_status();
+            assert!(status.degraded && status.disk_full, "{:?}", status);
+            assert_eq!(status.consecutive_failures, 1);
+            assert_eq!(status.last_error.as_deref(), Some(error.as_str()));
+            // 写入仍然在内存中可读，写了一半的新段文件被删除
+            assert_eq!(get(&storage, "b"), Some("2".to_string()));
+            if segment_max_bytes == 1 {
+                assert_eq!(segment_files(&path), segments);
+            }
+            drop(storage);
+            disk.set_full(false);
+
+            let reopened = StandaloneStorage::open_with_options(&path, options(&disk, &clock, segment_max_bytes)).unwrap();
+            assert_eq!((get(&reopened, "a"), get(&reopened, "b")), (Some("1".to_string()), None));
+            assert!(!reopened.persistence_status().degraded);
+        }
+    }
+
+    #[test]
+    fn test_retry_backoff_and_recovery() {
+        let path = temp_dir("persistence_backoff");
+        let (disk, clock) = (Arc::new(FullDiskFs::default()), Arc::new(MockClock::new(1_000)));
+        let storage = StandaloneStorage::open_with_options(&path, options(&disk, &clock, 1024 * 1024)).unwrap();
+        assert!(storage.flush_retry_due());
+
+        disk.set_full(true);
+        put(&storage, "a", "1");
+        storage.flush().unwrap_err();
+        let status = storage.persistence_status();
+        assert_eq!((status.since_ms, status.next_retry_at_ms), (1_000, 1_100));
+        assert!(!storage.flush_retry_due());
+        clock.advance(Duration::from_millis(100));
+        assert!(storage.flush_retry_due());
+
+        // 每次失败退避时间翻倍
+        storage.flush().unwrap_err();
+        let status = storage.persistence_status();
+        assert_eq!((status.since_ms, status.next_retry_at_ms, status.consecutive_failures), (1_000, 1_300, 2));
+
+        disk.set_full(false);
+        storage.flush().unwrap();
+        assert_eq!(storage.persistence_status(), storage::PersistenceStatus::default());
+
+        // 进入降级状态只记录一次，每次失败的刷盘各有一条
+        let events = storage.error_log().recent(10);
+        let degraded: Vec<_> = events.iter().filter(|e| e.message.starts_with("Persistence degraded: DiskFull")).collect();
+        assert_eq!(degraded.len(), 1, "{:?}", events);
+        assert!(degraded.iter().all(|e| e.category == ErrorCategory::Flush));
+        assert_eq!(events.iter().filter(|e| e.message.starts_with("DiskFull")).count(), 2, "{:?}", events);
+
+        drop(storage);
+        let reopened = StandaloneStorage::open_with_options(&path, options(&disk, &clock, 1024 * 1024)).unwrap();
+        assert_eq!(get(&reopened, "a"), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_degraded_state_is_reported_and_retried() -> Result<(), Box<dyn std::error::Error>> {
+        let disk = Arc::new(FullDiskFs::default());
+        let config = ServerConfig {
+            storage_options: StorageOptions { fs: disk.clone(), ..StorageOptions::default() },
+            ..ServerConfig::default()
+        };
+        let server = TestServer::start_with_config(config)?;
+        let mut client = server.connect()?;
+        client.enable_envelope()?;
+        client.put("cf", "a", "1")?;
+        client.take_warnings();
+
+        disk.set_full(true);
+        let error = client.flush().unwrap_err().to_string();
+        assert!(error.contains("DiskFull"), "{}", error);
+        let status = client.persistence_status()?;
+        assert!(status.degraded && status.disk_full, "{:?}", status);
+
+        // 每个写命令都带警告，读命令不带
+        client.put("cf", "b", "2")?;
+        let warnings = client.take_warnings();
+        assert!(matches!(&warnings[..], [Warning::PersistenceDegraded { error }] if error.contains("DiskFull")), "{:?}", warnings);
+        assert_eq!(client.get("cf", "b")?, Some("2".to_string()));
+        assert!(client.take_warnings().is_empty());
+        let errors = client.recent_errors(10)?;
+        assert!(errors.iter().any(|e| e.category == ErrorCategory::Flush && e.message.starts_with("Persistence degraded")), "{:?}", errors);
+
+        // 空间释放后后台重试刷盘，不需要新的写入或手动刷盘
+        disk.set_full(false);
+        wait_until(|| !client.persistence_status().unwrap().degraded);
+        client.put("cf", "c", "3")?;
+        assert!(client.take_warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_writes_when_degraded() -> Result<(), Box<dyn std::error::Error>> {
+        let disk = Arc::new(FullDiskFs::default());
+        let config = ServerConfig {
+            storage_options: StorageOptions { fs: disk.clone(), ..StorageOptions::default() },
+            fail_writes_when_degraded: true,
+            ..ServerConfig::default()
+        };
+        let mut server = TestServer::start_with_config(config)?;
+        let mut client = server.connect()?;
+        client.put("cf", "a", "1")?;
+        disk.set_full(true);
+        assert!(client.flush().is_err());
+
+        let error = client.put("cf", "a", "2").unwrap_err().to_string();
+        assert!(error.contains("PersistenceDegraded"), "{}", error);
+        assert!(client.delete("cf", "a").is_err());
+        // 读取和刷盘不受影响
+        assert_eq!(client.get("cf", "a")?, Some("1".to_string()));
+        assert!(client.flush().is_err());
+
+        disk.set_full(false);
+        client.flush()?;
+        client.put("cf", "b", "2")?;
+        server.restart()?;
+        let mut client = server.connect()?;
+        assert_eq!(client.get("cf", "a")?, Some("1".to_string()));
+        Ok(())
+    }
+}
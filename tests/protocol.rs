@@ -0,0 +1,46 @@
+use tinykv_rs::common;
+use tinykv_rs::protocol::{BatchMode, Bytes, Command, Modify, Response};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constructors_match_variants() {
+        let scan = Command::new_scan("users".into(), b"a".to_vec(), Some(b"m".to_vec()), 10);
+        assert_eq!(
+            serde_json::to_value(&scan).unwrap(),
+            serde_json::to_value(Command::Scan {
+                cf: "users".into(),
+                start_key: b"a".to_vec(),
+                end_key: Some(b"m".to_vec()),
+                limit: 10,
+                filter: None,
+                start_bound: None,
+                end_bound: None,
+            })
+            .unwrap()
+        );
+        assert_eq!(Command::new_get("users".into(), b"k".to_vec()).kind(), "Get");
+        assert_eq!(Command::new_put("users".into(), b"k".to_vec(), b"v".to_vec()).keys(), vec![b"k".as_slice()]);
+        assert!(!Command::new_delete("users".into(), b"k".to_vec()).is_read_only());
+        assert!(!Command::Publish { channel: "news".into(), payload: b"x".to_vec() }.is_read_only());
+        let batch = Command::new_batch(vec![Modify::new_delete("users".into(), b"k".to_vec())], BatchMode::BestEffort);
+        assert!(matches!(batch, Command::Batch { ref ops, mode: BatchMode::BestEffort } if ops.len() == 1));
+
+        assert!(matches!(Response::new_value(Some(Vec::new())), Response::Value(Some(Bytes(v))) if v.is_empty()));
+        assert!(matches!(Response::new_value(None), Response::Value(None)));
+        assert!(matches!(Response::new_error("UnknownCf: x"), Response::Error(e) if e == "UnknownCf: x"));
+    }
+
+    #[test]
+    fn test_common_reexports_new_modules() {
+        // 旧路径下的类型与新模块中的是同一个类型
+        let cmd: common::Command = Command::new_get("users".into(), b"k".to_vec());
+        let response: Response = common::Response::Ok;
+        assert!(matches!(cmd, common::Command::Get { .. }));
+        assert!(matches!(response, Response::Ok));
+        assert_eq!(common::DEFAULT_DB, tinykv_rs::protocol::DEFAULT_DB);
+        let _: fn(_) -> common::RawKeyValueApi = tinykv_rs::api::RawKeyValueApi::new;
+    }
+}
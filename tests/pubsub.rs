@@ -0,0 +1,118 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Bytes, Command, Response};
+use tinykv_rs::pubsub::PubSub;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_fan_out_to_all_subscribers() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let mut first = server.connect()?.subscribe(&["news"])?;
+        let mut second = server.connect()?.subscribe(&["news", "sports"])?;
+
+        let publisher = server.client();
+        assert_eq!(publisher.publish("news", b"hello")?, 2);
+        assert_eq!(publisher.publish("sports", b"goal")?, 1);
+        assert_eq!(publisher.publish("weather", b"rain")?, 0);
+        assert_eq!(publisher.publish("news", b"bye")?, 2);
+
+        let message = first.next().unwrap();
+        assert_eq!((message.channel.as_str(), message.payload, message.dropped), ("news", Bytes(b"hello".to_vec()), 0));
+        assert_eq!(first.next().unwrap().payload, Bytes(b"bye".to_vec()));
+        let received: Vec<(String, Vec<u8>)> = second.by_ref().take(3).map(|m| (m.channel, m.payload.0)).collect();
+        assert_eq!(
+            received,
+            vec![
+                ("news".to_string(), b"hello".to_vec()),
+                ("sports".to_string(), b"goal".to_vec()),
+                ("news".to_string(), b"bye".to_vec()),
+            ]
+        );
+
+        // 订阅后的连接不再接受命令，发布者的连接不受影响
+        assert_eq!(publisher.get("cf", "k")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_slow_consumer_drops_are_counted_and_reported() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { pubsub_queue_capacity: Some(4), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let subscriber = server.connect()?.subscribe(&["events"])?;
+
+        // 订阅者暂时不读取，发送缓冲区填满后消息在队列中堆积并被丢弃，发布者不会等待
+        let publisher = server.client();
+        let payload = vec![b'x'; 16 * 1024];
+        let published = 400;
+        let mut delivered = 0;
+        for _ in 0..published {
+            delivered += publisher.publish("events", &payload)?;
+        }
+        assert!(delivered < published, "nothing was dropped");
+
+        // 开始读取后队列腾出空位，结束标记才能放入
+        let consumer = thread::spawn(move || {
+            let (mut received, mut dropped) = (0, 0);
+            for message in subscriber {
+                received += 1;
+                dropped += message.dropped;
+                if message.payload.0 == b"end" {
+                    break;
+                }
+            }
+            (received, dropped)
+        });
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            if publisher.publish("events", b"end")? == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (received, dropped) = consumer.join().unwrap();
+        assert!(dropped > 0);
+        assert_eq!(received + dropped as usize, published + attempts);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dropped_subscription_is_removed() {
+        let hub = Arc::new(PubSub::new(2));
+        let subscription = hub.subscribe(&["a".to_string(), "b".to_string(), "a".to_string()]);
+        assert_eq!(subscription.channels(), ["a".to_string(), "b".to_string()]);
+        assert_eq!(hub.publish("a", b"1"), 1);
+        assert_eq!(hub.publish("a", b"2"), 1);
+        assert_eq!(hub.publish("a", b"3"), 0);
+
+        let message = subscription.recv_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!((message.payload, message.dropped), (Bytes(b"1".to_vec()), 1));
+        assert_eq!(subscription.recv_timeout(Duration::from_millis(10)).unwrap().dropped, 0);
+        assert!(subscription.recv_timeout(Duration::from_millis(10)).is_none());
+
+        let stats = hub.stats();
+        assert_eq!((stats.channels, stats.subscribers, stats.published, stats.delivered, stats.dropped), (2, 1, 3, 2, 1));
+        drop(subscription);
+        assert_eq!(hub.publish("a", b"4"), 0);
+        assert_eq!((hub.stats().channels, hub.stats().subscribers), (0, 0));
+    }
+
+    #[test]
+    fn test_subscribe_requires_a_channel() {
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::in_memory()));
+        let mut session = api.new_session();
+        let response = api.handle_command(&mut session, Command::Subscribe { channels: Vec::new() });
+        assert!(matches!(response, Response::Error(_)), "{:?}", response);
+        let response = api.handle_command(&mut session, Command::Publish { channel: "a".to_string(), payload: b"x".to_vec() });
+        assert!(matches!(response, Response::Count(0)), "{:?}", response);
+    }
+}
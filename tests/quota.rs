@@ -0,0 +1,118 @@
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{self, CfOptions, CfUsage};
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(cf: &str, key: &str, value: &str) -> Modify {
+        Modify::new_put(cf.to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    fn delete(cf: &str, key: &str) -> Modify {
+        Modify::new_delete(cf.to_string(), key.as_bytes().to_vec())
+    }
+
+    fn with_quota(cf: &str, max_keys: Option<usize>, max_bytes: Option<usize>) -> storage::StandaloneStorage {
+        let storage = storage::StandaloneStorage::in_memory();
+        storage.set_cf_options(cf, CfOptions { max_keys, max_bytes, ..CfOptions::default() }).unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_max_keys() {
+        let storage = with_quota("team", Some(2), None);
+        storage.write(vec![put("team", "a", "1"), put("team", "b", "2")]).unwrap();
+
+        let err = storage.write(vec![put("team", "c", "3")]).unwrap_err();
+        assert!(err.starts_with("QuotaExceeded: column family team max_keys 2"), "{}", err);
+
+        // 覆盖已有的键不增加键数，其他列族不受影响
+        storage.write(vec![put("team", "a", "updated")]).unwrap();
+        storage.write(vec![put("other", "c", "3")]).unwrap();
+
+        // 同一批次先删后增，净增为 0
+        storage.write(vec![delete("team", "a"), put("team", "c", "3")]).unwrap();
+        assert_eq!(storage.cf_usage().unwrap()[1].1.keys, 2);
+    }
+
+    #[test]
+    fn test_max_bytes_and_deletes_always_succeed() {
+        let storage = with_quota("team", None, Some(200));
+        storage.write(vec![put("team", "a", "x")]).unwrap();
+        let used = storage.cf_usage().unwrap()[0].1.bytes;
+
+        let err = storage.write(vec![put("team", "b", &"x".repeat(200))]).unwrap_err();
+        assert!(err.contains("max_bytes 200"), "{}", err);
+        assert!(err.contains(&format!("{} in use", used)), "{}", err);
+
+        // 配额调低到用量以下后，删除和缩小值仍然允许
+        storage.set_cf_quota("team", None, Some(1)).unwrap();
+        assert!(storage.write(vec![put("team", "a", "xx")]).is_err());
+        storage.write(vec![put("team", "a", "")]).unwrap();
+        storage.write(vec![delete("team", "a")]).unwrap();
+        assert_eq!(storage.cf_usage().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_mixed_batch_is_all_or_nothing() {
+        let storage = with_quota("small", Some(1), None);
+        let err = storage
+            .write(vec![put("big", "k", "v"), put("small", "a", "1"), put("small", "b", "2")])
+            .unwrap_err();
+        assert!(err.contains("small"), "{}", err);
+
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("big", b"k").unwrap(), None);
+        assert_eq!(reader.get_cf("small", b"a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_usage_is_maintained_incrementally() {
+        let path = temp_dir("quota_usage");
+
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        storage.set_cf_quota("a", Some(10), Some(10_000)).unwrap();
+        storage.write(vec![put("a", "1", "one"), put("a", "2", "two"), put("b", "1", "x")]).unwrap();
+        storage.write(vec![put("a", "1", "uno"), delete("a", "2"), delete("b", "1")]).unwrap();
+        let usage = storage.cf_usage().unwrap();
+        let bytes = storage.memory_usage().unwrap();
+        assert_eq!(usage, vec![("a".to_string(), CfUsage { keys: 1, bytes, logical_bytes: bytes })]);
+        storage.flush().unwrap();
+        drop(storage);
+
+        // 重新打开后用量和配额都恢复
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(storage.cf_usage().unwrap(), usage);
+        assert_eq!(storage.cf_options("a").unwrap().max_keys, Some(10));
+    }
+
+    #[test]
+    fn test_quota_over_the_wire() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { admin_token: Some("secret".to_string()), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let mut admin = server.connect()?;
+        admin.admin_auth("secret")?;
+        let client = server.client();
+
+        // 设置配额需要管理权限
+        assert!(client.set_cf_quota("team", Some(1), None).is_err());
+        admin.set_cf_quota("team", Some(1), None)?;
+
+        client.put("team", "a", "1")?;
+        let err = client.put("team", "b", "2").unwrap_err();
+        assert!(err.to_string().starts_with("QuotaExceeded"), "{}", err);
+
+        let info = client.cf_info()?;
+        assert_eq!((info[0].name.as_str(), info[0].keys, info[0].max_keys), ("team", 1, Some(1)));
+        assert!(info[0].bytes > 0);
+
+        // 运行时调高配额
+        admin.set_cf_quota("team", Some(2), None)?;
+        client.put("team", "b", "2")?;
+        client.delete("team", "a")?;
+        Ok(())
+    }
+}
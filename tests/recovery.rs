@@ -0,0 +1,148 @@
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{Command, Modify, Response};
+use tinykv_rs::storage::{self, RecoveryReport, StorageOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(storage: &storage::StandaloneStorage, key: &str, value: &str) {
+        storage.write(vec![Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn get(storage: &storage::StandaloneStorage, key: &str) -> Option<String> {
+        storage
+            .reader()
+            .unwrap()
+            .get_cf("cf", key.as_bytes())
+            .unwrap()
+            .map(|v| String::from_utf8(v).unwrap())
+    }
+
+    /// 写入三次刷盘，每次一个键，全部落在同一个段文件中
+    fn three_flushes(path: &Path) -> PathBuf {
+        let storage = storage::StandaloneStorage::open(path).unwrap();
+        for key in ["a", "b", "c"] {
+            put(&storage, key, key);
+            storage.flush().unwrap();
+        }
+        drop(storage);
+
+        let mut segments: Vec<PathBuf> = fs::read_dir(path)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.file_name().unwrap().to_string_lossy().starts_with("segment-"))
+            .collect();
+        assert_eq!(segments.len(), 1);
+        segments.pop().unwrap()
+    }
+
+    fn rewrite_line(segment: &Path, index: usize, content: &str) {
+        let text = fs::read_to_string(segment).unwrap();
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        lines[index] = content.to_string();
+        fs::write(segment, lines.join("\n") + "\n").unwrap();
+    }
+
+    fn open_salvage(path: &Path) -> Result<storage::StandaloneStorage, String> {
+        storage::StandaloneStorage::open_with_options(path, StorageOptions { salvage: true, ..StorageOptions::default() })
+    }
+
+    #[test]
+    fn test_clean_recovery_report() {
+        let path = temp_dir("recovery_clean");
+        three_flushes(&path);
+
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&path).unwrap();
+        assert_eq!(
+            report,
+            RecoveryReport { snapshot_entries: 0, wal_records_replayed: 3, corrupt_records_skipped: 0, last_sequence: 3, expired_entries_dropped: 0, migrations: Vec::new() }
+        );
+
+        // 整理后序号保存在基础快照中，之后的记录继续递增
+        storage.compact().unwrap();
+        put(&storage, "d", "d");
+        storage.flush().unwrap();
+        drop(storage);
+
+        let (_, report) = storage::StandaloneStorage::open_with_report(&path).unwrap();
+        assert_eq!(
+            report,
+            RecoveryReport { snapshot_entries: 3, wal_records_replayed: 1, corrupt_records_skipped: 0, last_sequence: 4, expired_entries_dropped: 0, migrations: Vec::new() }
+        );
+
+        let (_, empty) = storage::StandaloneStorage::open_with_report(temp_dir("recovery_empty")).unwrap();
+        assert_eq!(empty, RecoveryReport::default());
+    }
+
+    #[test]
+    fn test_torn_trailing_record_is_dropped() {
+        let path = temp_dir("recovery_torn");
+        let segment = three_flushes(&path);
+        fs::OpenOptions::new().append(true).open(&segment).unwrap().write_all(b"{\"sequence\":4,\"keys\":[{\"ke").unwrap();
+
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&path).unwrap();
+        assert_eq!(report.wal_records_replayed, 3);
+        assert_eq!(report.corrupt_records_skipped, 1);
+        assert_eq!(report.last_sequence, 3);
+        assert_eq!(get(&storage, "c"), Some("c".to_string()));
+
+        // 之后的刷盘不再追加到残缺的文件，重新打开时数据完整
+        put(&storage, "d", "d");
+        storage.flush().unwrap();
+        drop(storage);
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&path).unwrap();
+        assert_eq!((report.wal_records_replayed, report.last_sequence), (4, 4));
+        assert_eq!(get(&storage, "d"), Some("d".to_string()));
+    }
+
+    #[test]
+    fn test_garbage_last_record_is_dropped() {
+        let path = temp_dir("recovery_garbage_tail");
+        let segment = three_flushes(&path);
+        rewrite_line(&segment, 2, "not a record");
+
+        let (storage, report) = storage::StandaloneStorage::open_with_report(&path).unwrap();
+        assert_eq!((report.wal_records_replayed, report.corrupt_records_skipped), (2, 1));
+        assert_eq!(get(&storage, "b"), Some("b".to_string()));
+        assert_eq!(get(&storage, "c"), None);
+    }
+
+    #[test]
+    fn test_corruption_in_the_middle_requires_salvage() {
+        let path = temp_dir("recovery_middle");
+        let segment = three_flushes(&path);
+        rewrite_line(&segment, 1, "{\"keys\": [broken");
+
+        let err = storage::StandaloneStorage::open(&path).err().expect("open should fail");
+        assert!(err.contains("Corrupt record 2 of 3"), "{}", err);
+        assert!(err.contains("salvage"), "{}", err);
+
+        let storage = open_salvage(&path).unwrap();
+        assert_eq!(get(&storage, "a"), Some("a".to_string()));
+        assert_eq!(get(&storage, "b"), None);
+        assert_eq!(get(&storage, "c"), Some("c".to_string()));
+        let report = storage.take_recovery_report().unwrap().unwrap();
+        assert_eq!((report.wal_records_replayed, report.corrupt_records_skipped, report.last_sequence), (2, 1, 3));
+    }
+
+    #[test]
+    fn test_report_in_first_info_response() {
+        let path = temp_dir("recovery_info");
+        three_flushes(&path);
+        let api = RawKeyValueApi::new(Arc::new(storage::StandaloneStorage::open(&path).unwrap()));
+        let mut session = Session::default();
+
+        match api.handle_command(&mut session, Command::Info) {
+            Response::Info { recovery: Some(report), .. } => assert_eq!(report.wal_records_replayed, 3),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(matches!(api.handle_command(&mut session, Command::Info), Response::Info { recovery: None, .. }));
+    }
+}
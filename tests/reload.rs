@@ -0,0 +1,133 @@
+use tinykv_rs::observer::{ChannelObserver, WriteObserver};
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage;
+use tinykv_rs::testing::temp_dir;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// 记录收到的重新加载通知
+#[derive(Clone, Default)]
+struct ReloadRecorder {
+    reloads: Arc<Mutex<Vec<u64>>>,
+}
+
+impl WriteObserver for ReloadRecorder {
+    fn on_commit(&mut self, _seq: u64, _batch: &[Modify]) {}
+
+    fn on_reload(&mut self, seq: u64) {
+        self.reloads.lock().unwrap().push(seq);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(storage: &storage::StandaloneStorage, key: &str, value: &str) {
+        storage.write(vec![Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn get(storage: &storage::StandaloneStorage, key: &str) -> Option<String> {
+        storage.reader().unwrap().get_cf("cf", key.as_bytes()).unwrap().map(|v| String::from_utf8(v).unwrap())
+    }
+
+    #[test]
+    fn test_reload_refuses_unflushed_writes() {
+        let path = temp_dir("reload_dirty");
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        put(&storage, "a", "1");
+        storage.flush().unwrap();
+        put(&storage, "a", "2");
+
+        let e = storage.reload(false).unwrap_err();
+        assert!(e.starts_with("UnflushedWrites"), "{}", e);
+        assert_eq!(get(&storage, "a"), Some("2".to_string()));
+
+        // 刷盘后可以重新加载，数据不变
+        storage.flush().unwrap();
+        storage.reload(false).unwrap();
+        assert_eq!(get(&storage, "a"), Some("2".to_string()));
+        assert_eq!(storage.flush_info().unwrap().dirty, 0);
+    }
+
+    #[test]
+    fn test_forced_reload_discards_unflushed_writes() {
+        let path = temp_dir("reload_force");
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        storage.register_observer(Box::new(ChannelObserver::new(sender)));
+        let recorder = ReloadRecorder::default();
+        storage.register_observer(Box::new(recorder.clone()));
+        put(&storage, "a", "1");
+        storage.flush().unwrap();
+        put(&storage, "a", "2");
+        put(&storage, "b", "2");
+
+        let report = storage.reload(true).unwrap();
+        assert_eq!(report.wal_records_replayed, 1);
+        assert_eq!(get(&storage, "a"), Some("1".to_string()));
+        assert_eq!(get(&storage, "b"), None);
+        assert_eq!(storage.total_keys(), 1);
+        assert_eq!(storage.flush_info().unwrap().dirty, 0);
+
+        // 观察者收到重新加载通知，序号是之前最后一次提交的序号
+        assert_eq!(*recorder.reloads.lock().unwrap(), vec![3]);
+        let seqs: Vec<(u64, usize)> = receiver.try_iter().map(|(seq, batch)| (seq, batch.len())).collect();
+        assert_eq!(seqs, vec![(1, 1), (2, 1), (3, 1), (3, 0)]);
+
+        // 丢弃的写入不会在之后的刷盘中写出
+        put(&storage, "c", "3");
+        storage.flush().unwrap();
+        drop(storage);
+        let storage = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!((get(&storage, "a"), get(&storage, "b"), get(&storage, "c")), (Some("1".to_string()), None, Some("3".to_string())));
+    }
+
+    #[test]
+    fn test_reload_requires_persistence() {
+        let storage = storage::StandaloneStorage::in_memory();
+        let e = storage.reload(true).unwrap_err();
+        assert!(e.starts_with("NoPersistencePath"), "{}", e);
+    }
+
+    #[test]
+    fn test_readers_see_consistent_snapshots_during_reload() {
+        let path = temp_dir("reload_snapshot");
+        let storage = Arc::new(storage::StandaloneStorage::open(&path).unwrap());
+        let pair = |value: &str| {
+            vec![
+                Modify::new_put("cf".to_string(), b"x".to_vec(), value.as_bytes().to_vec()),
+                Modify::new_put("cf".to_string(), b"y".to_vec(), value.as_bytes().to_vec()),
+            ]
+        };
+        storage.write(pair("disk")).unwrap();
+        storage.flush().unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let storage = Arc::clone(&storage);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut scans = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let pairs = storage.reader().unwrap().scan_cf("cf", b"", None, 10, None).unwrap();
+                    // 同一批次写入的两个键总是一起出现、值相同
+                    assert_eq!(pairs.len(), 2);
+                    assert_eq!(pairs[0].1, pairs[1].1);
+                    scans += 1;
+                }
+                scans
+            })
+        };
+        for round in 0..200 {
+            storage.write(pair(&format!("memory{}", round))).unwrap();
+            storage.reload(true).unwrap();
+        }
+        stop.store(true, Ordering::Relaxed);
+        assert!(reader.join().unwrap() > 0);
+        assert_eq!(get(&storage, "x"), Some("disk".to_string()));
+    }
+}
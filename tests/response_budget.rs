@@ -0,0 +1,162 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{self, Bytes, Command, Modify, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 十个键 k0..k9，每个条目的大小相同
+    fn entries() -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..10).map(|i| (format!("k{}", i).into_bytes(), vec![b'a'; 10])).collect()
+    }
+
+    fn entry_size() -> usize {
+        let (key, value) = &entries()[0];
+        protocol::entry_wire_size(key, value)
+    }
+
+    fn api(max_response_bytes: Option<usize>) -> RawKeyValueApi {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        let ops = entries().into_iter().map(|(k, v)| Modify::new_put("users".to_string(), k, v)).collect();
+        storage.write(ops).unwrap();
+        let config = ServerConfig { max_response_bytes, ..ServerConfig::default() };
+        RawKeyValueApi::with_config(storage, Arc::new(config))
+    }
+
+    fn scan(limit: usize) -> Command {
+        Command::new_scan("users".to_string(), Vec::new(), None, limit)
+    }
+
+    fn keys(values: &[(Bytes, Bytes)]) -> Vec<String> {
+        values.iter().map(|(Bytes(k), _)| String::from_utf8_lossy(k).into_owned()).collect()
+    }
+
+    #[test]
+    fn test_entry_wire_size_matches_json() {
+        let samples: Vec<(Vec<u8>, Vec<u8>)> = vec![
+            (Vec::new(), Vec::new()),
+            (b"k".to_vec(), Vec::new()),
+            (vec![0, 9, 10, 99, 100, 255], vec![1]),
+            ((0..=255).collect(), b"value".to_vec()),
+        ];
+        for (key, value) in samples {
+            let json = serde_json::to_vec(&(Bytes(key.clone()), Bytes(value.clone()))).unwrap();
+            assert_eq!(protocol::entry_wire_size(&key, &value), json.len(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_scan_stops_exactly_on_budget() {
+        for (budget, expected) in [(3 * entry_size(), 3), (3 * entry_size() - 1, 2)] {
+            let api = api(Some(budget));
+            let mut session = api.new_session();
+            session.features = vec!["truncation".to_string()];
+            let response = api.handle_command(&mut session, scan(100));
+            let Response::TruncatedValues { values, next: Bytes(next), .. } = response else {
+                panic!("unexpected {:?}", response)
+            };
+            assert_eq!(values.len(), expected, "budget {}", budget);
+            assert_eq!(next, format!("k{}", expected).into_bytes());
+        }
+
+        // 恰好装下全部条目时不截断
+        let api = api(Some(10 * entry_size()));
+        let mut session = api.new_session();
+        session.features = vec!["truncation".to_string()];
+        let response = api.handle_command(&mut session, scan(100));
+        let Response::Values(values) = response else { panic!("unexpected {:?}", response) };
+        assert_eq!(values.len(), 10);
+    }
+
+    #[test]
+    fn test_oversized_entry_is_still_returned() {
+        let api = api(Some(1));
+        let (values, next) = api
+            .raw_scan_bounded("users", std::ops::Bound::Unbounded, std::ops::Bound::Unbounded, 100, None, Some(1))
+            .unwrap();
+        assert_eq!(values, entries()[..1].to_vec());
+        assert_eq!(next, Some(b"k1".to_vec()));
+    }
+
+    #[test]
+    fn test_truncation_requires_the_feature() {
+        let api = api(Some(3 * entry_size()));
+        let mut session = api.new_session();
+        let response = api.handle_command(&mut session, scan(100));
+        assert!(matches!(&response, Response::Error(e) if e.starts_with("ResponseTooLarge")), "{:?}", response);
+
+        // limit 内的结果没有超出上限，旧客户端照常收到 Values
+        let response = api.handle_command(&mut session, scan(3));
+        let Response::Values(values) = response else { panic!("unexpected {:?}", response) };
+        assert_eq!(keys(&values), vec!["k0", "k1", "k2"]);
+    }
+
+    #[test]
+    fn test_client_surfaces_truncation() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_response_bytes: Some(4 * entry_size()), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        let ops = entries().into_iter().map(|(k, v)| Modify::new_put("users".to_string(), k, v)).collect();
+        assert!(client.write_batch(ops)?.is_applied());
+
+        let page = client.scan_builder("users").run_page()?;
+        assert!(!page.complete);
+        assert_eq!(page.items, entries()[..4].to_vec());
+        assert_eq!(page.cursor, Some(b"k4".to_vec()));
+
+        // 从续扫位置逐页读完整个范围
+        let mut all = page.items;
+        let mut cursor = page.cursor;
+        while let Some(from) = cursor {
+            let page = client.scan_builder("users").from_inclusive(from).run_page()?;
+            all.extend(page.items);
+            cursor = page.cursor;
+        }
+        assert_eq!(all, entries());
+
+        // 不分页的接口自动续扫，limit 按整个结果计数
+        assert_eq!(client.scan_bytes("users", b"", None, 100)?, entries());
+        assert_eq!(client.scan_bytes("users", b"k2", None, 6)?, entries()[2..8].to_vec());
+        assert_eq!(client.scan_builder("users").to_exclusive("k9").run_bytes()?, entries()[..9].to_vec());
+        assert_eq!(client.scan("users", "", Some("k5"), 100)?.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_all_marks_truncated_pages() -> Result<(), Box<dyn std::error::Error>> {
+        // ScanAll 的条目还带列族名
+        let budget = 3 * (entry_size() + "users".len() + 3);
+        let config = ServerConfig { max_response_bytes: Some(budget), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        let ops = entries().into_iter().map(|(k, v)| Modify::new_put("users".to_string(), k, v)).collect();
+        assert!(client.write_batch(ops)?.is_applied());
+
+        let page = client.scan_all(None, 100)?;
+        assert!(page.truncated);
+        assert_eq!(page.entries.len(), 3);
+        assert_eq!(page.next, Some(("users".to_string(), "k3".to_string())));
+
+        let page = client.scan_all(None, 2)?;
+        assert!(!page.truncated);
+        assert_eq!(page.entries.len(), 2);
+
+        let mut total = 0;
+        let mut start: Option<(String, String)> = None;
+        loop {
+            let page = client.scan_all(start.as_ref().map(|(cf, k)| (cf.as_str(), k.as_str())), 100)?;
+            total += page.entries.len();
+            match page.next {
+                Some(next) => start = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(total, 10);
+        Ok(())
+    }
+}
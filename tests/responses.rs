@@ -0,0 +1,108 @@
+use tinykv_rs::client::{KvClient, KvError};
+use tinykv_rs::protocol::{self, Bytes, Command, Response};
+use tinykv_rs::testing::TestServer;
+
+use std::io::Write;
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 完成握手后对每个命令都回复同一个响应的服务器
+    fn canned_server(response: Response) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let reply = serde_json::to_vec(&response).unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut pending = Vec::new();
+            while let Ok(Some(cmd)) = protocol::read_message::<Command, _>(&mut stream, &mut pending) {
+                let hello = Response::Hello {
+                    server_version: protocol::PROTOCOL_VERSION,
+                    accepted_features: protocol::FEATURES.iter().map(|f| f.to_string()).collect(),
+                };
+                let bytes = match cmd {
+                    Command::Hello { .. } => serde_json::to_vec(&hello).unwrap(),
+                    _ => reply.clone(),
+                };
+                stream.write_all(&bytes).unwrap();
+            }
+        });
+        addr
+    }
+
+    /// 把结果归类：成功的值、服务端错误码或协议错误
+    fn outcome<T: std::fmt::Debug>(result: Result<T, Box<dyn std::error::Error>>) -> String {
+        match result {
+            Ok(value) => format!("ok {:?}", value),
+            Err(e) => match e.downcast_ref::<KvError>() {
+                Some(KvError::Server { code, .. }) => format!("server {:?}", code),
+                Some(KvError::Protocol(_)) => "protocol".to_string(),
+                _ => format!("other {}", e),
+            },
+        }
+    }
+
+    #[test]
+    fn test_every_response_variant_is_decoded_explicitly() {
+        let value = |v: &str| Response::Value(Some(Bytes(v.as_bytes().to_vec())));
+        // (响应, get, get_bytes, get_set, put)
+        let cases = vec![
+            (value("v"), r#"ok Some("v")"#, "ok Some([118])", r#"ok Some("v")"#, "protocol"),
+            (value(""), r#"ok Some("")"#, "ok Some([])", r#"ok Some("")"#, "protocol"),
+            (Response::Value(None), "ok None", "ok None", "ok None", "protocol"),
+            (Response::NotFound, "ok None", "ok None", "protocol", "protocol"),
+            (Response::Ok, "protocol", "protocol", "protocol", "ok ()"),
+            (Response::Values(Vec::new()), "protocol", "protocol", "protocol", "protocol"),
+            (Response::LockToken(Some(1)), "protocol", "protocol", "protocol", "protocol"),
+            (
+                Response::Error("UnknownCf: missing".into()),
+                r#"server Some("UnknownCf")"#,
+                r#"server Some("UnknownCf")"#,
+                r#"server Some("UnknownCf")"#,
+                r#"server Some("UnknownCf")"#,
+            ),
+            (Response::Error("Cannot drop the default database".into()), "server None", "server None", "server None", "server None"),
+        ];
+
+        for (response, get, get_bytes, get_set, put) in cases {
+            let label = format!("{:?}", response);
+            let mut client = KvClient::connect(&canned_server(response)).unwrap();
+            assert_eq!(outcome(client.get("cf", "k")), get, "get <- {}", label);
+            assert_eq!(outcome(client.get_bytes("cf", b"k")), get_bytes, "get_bytes <- {}", label);
+            assert_eq!(outcome(client.get_set("cf", "k", "v")), get_set, "get_set <- {}", label);
+            assert_eq!(outcome(client.put("cf", "k", "v")), put, "put <- {}", label);
+        }
+    }
+
+    #[test]
+    fn test_server_errors_keep_code_and_message() {
+        let err = KvError::server("QuotaExceeded: column family users max_keys 1".to_string());
+        assert_eq!(err.code(), Some("QuotaExceeded"));
+        assert_eq!(err.to_string(), "QuotaExceeded: column family users max_keys 1");
+        let err = KvError::server("Invalid key: empty".to_string());
+        assert_eq!(err, KvError::Server { code: None, message: "Invalid key: empty".to_string() });
+    }
+
+    #[test]
+    fn test_absent_key_and_empty_value_are_distinct() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        assert!(client.negotiated_features().contains(&"not-found".to_string()));
+        client.put_bytes("cf", b"empty", b"")?;
+        assert_eq!(client.get_bytes("cf", b"empty")?, Some(Vec::new()));
+        assert_eq!(client.get("cf", "empty")?, Some(String::new()));
+        assert_eq!(client.get_bytes("cf", b"absent")?, None);
+
+        // 服务端错误以 KvError::Server 返回
+        let err = client.expire("cf", "absent", Duration::from_secs(1)).unwrap_err();
+        assert_eq!(err.downcast_ref::<KvError>().and_then(KvError::code), Some("KeyNotFound"));
+        let err = client.drop_db("default").unwrap_err();
+        assert!(matches!(err.downcast_ref::<KvError>(), Some(KvError::Server { code: None, .. })), "{}", err);
+        Ok(())
+    }
+}
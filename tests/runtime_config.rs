@@ -0,0 +1,129 @@
+use tinykv_rs::protocol::{Modify, ValueFilter};
+use tinykv_rs::runtime_config::{ConfigError, RuntimeConfig, DYNAMIC_KEYS, STATIC_KEYS};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{FlushPolicy, StandaloneStorage, StorageOptions};
+use tinykv_rs::testing::{temp_dir, wait_until, TestServer};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> Vec<u8> {
+        format!("k{:03}", i).into_bytes()
+    }
+
+    #[test]
+    fn test_scan_cap_changes_without_reconnecting() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        for i in 0..100 {
+            client.put_bytes("logs", &key(i), if i % 10 == 0 { b"match" } else { b"other" })?;
+        }
+        let filter = || ValueFilter::Prefix(b"match".to_vec());
+
+        let page = client.scan_builder("logs").filter(filter()).run_bounded()?;
+        assert!(page.complete && !page.examined_limit_reached);
+        assert_eq!(page.items.len(), 10);
+
+        // 同一个连接上的下一个请求就按新的上限处理
+        client.set_config("max_scan_examined", "25")?;
+        let page = client.scan_builder("logs").filter(filter()).run_bounded()?;
+        assert!(page.examined_limit_reached && !page.complete);
+        assert_eq!(page.items.len(), 3);
+        assert_eq!(page.cursor, Some(key(25)));
+
+        client.set_config("max_scan_examined", "none")?;
+        let page = client.scan_builder("logs").filter(filter()).run_bounded()?;
+        assert!(page.complete);
+        assert_eq!(page.items.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_config_lists_every_key() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_response_bytes: Some(4096), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        client.set_config("strict_cf_mode", "on")?;
+
+        let entries = client.get_config()?;
+        let value = |key: &str| entries.iter().find(|e| e.key == key).map(|e| (e.value.as_str(), e.dynamic));
+        assert_eq!(value("max_response_bytes"), Some(("4096", true)));
+        assert_eq!(value("max_scan_examined"), Some(("none", true)));
+        assert_eq!(value("strict_cf_mode"), Some(("true", true)));
+        assert_eq!(value("listen_addrs"), Some((server.addr().to_string().as_str(), false)));
+        for key in DYNAMIC_KEYS.iter().chain(STATIC_KEYS) {
+            assert!(value(key).is_some(), "missing {}", key);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_scan_examined: Some(100), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+
+        let error = client.set_config("no_such_key", "1").unwrap_err().to_string();
+        assert!(error.contains("UnknownConfig"), "{}", error);
+        let error = client.set_config("data_path", "/tmp/elsewhere").unwrap_err().to_string();
+        assert!(error.contains("StaticConfig"), "{}", error);
+        for value in ["0", "-1", "lots"] {
+            let error = client.set_config("max_scan_examined", value).unwrap_err().to_string();
+            assert!(error.contains("InvalidConfig"), "{}: {}", value, error);
+        }
+        let error = client.set_config("strict_cf_mode", "maybe").unwrap_err().to_string();
+        assert!(error.contains("InvalidConfig"), "{}", error);
+        // 没有配置后台刷盘时不能修改刷盘间隔
+        let error = client.set_config("flush_interval_ms", "100").unwrap_err().to_string();
+        assert!(error.contains("NoFlushPolicy"), "{}", error);
+
+        // 失败的修改不影响原来的值
+        let entries = client.get_config()?;
+        assert!(entries.iter().any(|e| e.key == "max_scan_examined" && e.value == "100"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_commands_require_admin() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { admin_token: Some("secret".to_string()), ..ServerConfig::default() };
+        let server = TestServer::start_with_config(config)?;
+        let mut client = server.connect()?;
+        assert!(client.set_config("max_scan_examined", "10").is_err());
+        assert!(client.get_config().is_err());
+
+        client.admin_auth("secret")?;
+        client.set_config("max_scan_examined", "10")?;
+        assert!(client.get_config()?.iter().any(|e| e.key == "max_scan_examined" && e.value == "10"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_flusher_picks_up_new_interval() {
+        let path = temp_dir("runtime_config_flush");
+        let options = StorageOptions {
+            flush_policy: Some(FlushPolicy { interval: Some(Duration::from_secs(3600)), ..FlushPolicy::default() }),
+            ..StorageOptions::default()
+        };
+        let storage = Arc::new(StandaloneStorage::open_with_options(&path, options).unwrap());
+        let scheduler = storage.start_flush_scheduler();
+        let runtime = RuntimeConfig::new(&ServerConfig::default(), Arc::clone(&storage));
+        storage.write(vec![Modify::new_put("cf".to_string(), b"a".to_vec(), b"1".to_vec())]).unwrap();
+
+        assert_eq!(runtime.set("flush_interval_ms", "20"), Ok("3600000".to_string()));
+        wait_until(|| storage.flush_info().unwrap().flushes > 0);
+        assert_eq!(storage.flush_info().unwrap().dirty, 0);
+
+        assert_eq!(
+            runtime.set("flush_interval_ms", "0"),
+            Err(ConfigError::Invalid {
+                key: "flush_interval_ms".to_string(),
+                reason: "must be greater than 0, use 'none' to remove the limit".to_string(),
+            })
+        );
+        drop(scheduler);
+    }
+}
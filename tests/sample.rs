@@ -0,0 +1,89 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage::{self, StorageOptions};
+use tinykv_rs::testing::TestServer;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(samples: &[storage::KeySample]) -> Vec<(&str, &[u8])> {
+        samples.iter().map(|s| (s.cf.as_str(), s.key.0.as_slice())).collect()
+    }
+
+    #[test]
+    fn test_sample_edge_cases() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(0));
+        let options = StorageOptions { clock: clock.clone(), ..StorageOptions::default() };
+        let storage = storage::StandaloneStorage::in_memory_with_options(options);
+        let cfs = vec!["a".to_string(), "b".to_string()];
+        assert!(storage.sample_keys(&cfs, 10)?.is_empty());
+
+        storage.write(vec![
+            Modify::new_put("a".into(), b"k1".to_vec(), b"v".to_vec()),
+            Modify::new_put("a".into(), b"k2".to_vec(), b"vvv".to_vec()),
+            Modify::new_put("b".into(), b"k3".to_vec(), b"vv".to_vec()),
+            Modify::new_put("c".into(), b"k4".to_vec(), b"v".to_vec()),
+        ])?;
+        storage.expire("a", b"k2", Duration::from_secs(10))?;
+
+        // count 大于键数时返回所有键，不包括其他列族
+        let samples = storage.sample_keys(&cfs, 100)?;
+        assert_eq!(keys(&samples), vec![("a", &b"k1"[..]), ("a", b"k2"), ("b", b"k3")]);
+        assert_eq!(samples[1].value_size, 3);
+        assert_eq!(samples[1].ttl_ms, Some(10_000));
+        assert_eq!(samples[0].ttl_ms, None);
+        assert!(storage.sample_keys(&cfs, 0)?.is_empty());
+
+        // 过期的键不会被抽到
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(keys(&storage.sample_keys(&cfs, 100)?), vec![("a", &b"k1"[..]), ("b", b"k3")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_is_spread_over_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let storage = storage::StandaloneStorage::in_memory();
+        let batch = (0..8).map(|i| Modify::new_put("cf".into(), vec![i], b"v".to_vec())).collect();
+        storage.write(batch)?;
+
+        let mut hits: BTreeMap<Vec<u8>, usize> = BTreeMap::new();
+        for _ in 0..400 {
+            let samples = storage.sample_keys(&["cf".to_string()], 2)?;
+            assert_eq!(samples.len(), 2);
+            assert_ne!(samples[0].key, samples[1].key);
+            for s in samples {
+                *hits.entry(s.key.0).or_default() += 1;
+            }
+        }
+        // 每个键的期望次数为 100
+        assert_eq!(hits.len(), 8);
+        assert!(hits.values().all(|&n| (40..=160).contains(&n)), "{:?}", hits);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_command_is_scoped_to_database() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.put("users", "u1", "alice")?;
+        client.put("orders", "o1", "x")?;
+        client.acquire_lock("job", Duration::from_secs(60))?;
+        client.use_db("other")?;
+        client.put("users", "u2", "bob")?;
+        client.use_db("default")?;
+
+        let samples = client.sample(None, 10)?;
+        assert_eq!(keys(&samples), vec![("orders", &b"o1"[..]), ("users", b"u1")]);
+        assert_eq!(samples[1].value_size, 5);
+
+        let samples = client.sample(Some("users"), 10)?;
+        assert_eq!(keys(&samples), vec![("users", &b"u1"[..])]);
+        assert!(client.sample(Some("missing"), 10)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+use tinykv_rs::storage;
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{Bytes, Command, Modify, Response, ScanBound, ValueFilter};
+use tinykv_rs::testing::TestServer;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::{Arc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_with(pairs: &[(&str, &str)]) -> RawKeyValueApi {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        let batch = pairs
+            .iter()
+            .map(|(k, v)| Modify::new_put("logs".to_string(), k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect();
+        storage.write(batch).unwrap();
+        RawKeyValueApi::new(storage)
+    }
+
+    fn keys(api: &RawKeyValueApi, limit: usize, filter: ValueFilter) -> Vec<String> {
+        api.raw_scan_filtered("logs", b"", None, limit, Some(&filter))
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| String::from_utf8(k).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_filter_variants() {
+        let api = api_with(&[("a", "ERROR disk"), ("b", "INFO ok"), ("c", "WARN disk full"), ("d", "")]);
+
+        assert_eq!(keys(&api, 10, ValueFilter::Prefix(b"ERROR".to_vec())), vec!["a"]);
+        assert_eq!(keys(&api, 10, ValueFilter::Contains(b"disk".to_vec())), vec!["a", "c"]);
+        assert_eq!(keys(&api, 10, ValueFilter::Contains(Vec::new())).len(), 4);
+        assert_eq!(keys(&api, 10, ValueFilter::SizeAtLeast(10)), vec!["a", "c"]);
+        assert_eq!(keys(&api, 10, ValueFilter::SizeAtMost(7)), vec!["b", "d"]);
+    }
+
+    #[test]
+    fn test_scan_limit_counts_matches() {
+        let pairs: Vec<(String, String)> = (0..20)
+            .map(|i| (format!("k{:02}", i), if i % 5 == 0 { "hit".to_string() } else { "miss".to_string() }))
+            .collect();
+        let refs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let api = api_with(&refs);
+
+        // 跳过的条目不占用 limit
+        assert_eq!(keys(&api, 3, ValueFilter::Prefix(b"hit".to_vec())), vec!["k00", "k05", "k10"]);
+        assert_eq!(keys(&api, 10, ValueFilter::Prefix(b"hit".to_vec())).len(), 4);
+    }
+
+    #[test]
+    fn test_scan_command_with_filter() {
+        let api = api_with(&[("a", "x1"), ("b", "y2"), ("c", "x3")]);
+        let mut session = Session::default();
+
+        let cmd = Command::Scan {
+            cf: "logs".to_string(),
+            start_key: Vec::new(),
+            end_key: None,
+            limit: 10,
+            filter: Some(ValueFilter::Prefix(b"x".to_vec())),
+            start_bound: None,
+            end_bound: None,
+        };
+        match api.handle_command(&mut session, cmd) {
+            Response::Values(values) => assert_eq!(values.len(), 2),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // 不带 filter 的旧请求仍然可以解析
+        let json = r#"{"type":"Scan","cf":"logs","start_key":[],"end_key":null,"limit":10}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(cmd, Command::Scan { filter: None, .. }));
+    }
+
+    fn range_keys(api: &RawKeyValueApi, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Vec<String> {
+        api.raw_scan_range("logs", start, end, 100, None)
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| String::from_utf8(k).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_range_bounds() {
+        let api = api_with(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4")]);
+        let (b, c): (&[u8], &[u8]) = (b"b", b"c");
+
+        assert_eq!(range_keys(&api, Bound::Included(b), Bound::Included(c)), vec!["b", "c"]);
+        assert_eq!(range_keys(&api, Bound::Excluded(b), Bound::Excluded(b"d")), vec!["c"]);
+        assert_eq!(range_keys(&api, Bound::Excluded(b), Bound::Unbounded), vec!["c", "d"]);
+        assert_eq!(range_keys(&api, Bound::Unbounded, Bound::Included(b)), vec!["a", "b"]);
+        assert_eq!(range_keys(&api, Bound::Unbounded, Bound::Unbounded).len(), 4);
+
+        // 两端相等时只有都包含才返回该键，其余组合为空而不是 panic
+        assert_eq!(range_keys(&api, Bound::Included(b), Bound::Included(b)), vec!["b"]);
+        assert!(range_keys(&api, Bound::Included(b), Bound::Excluded(b)).is_empty());
+        assert!(range_keys(&api, Bound::Excluded(b), Bound::Included(b)).is_empty());
+        assert!(range_keys(&api, Bound::Excluded(b), Bound::Excluded(b)).is_empty());
+        assert!(range_keys(&api, Bound::Included(c), Bound::Included(b)).is_empty());
+
+        // 排除的起点不存在时从下一个键开始
+        assert_eq!(range_keys(&api, Bound::Excluded(b"bb"), Bound::Unbounded), vec!["c", "d"]);
+    }
+
+    #[test]
+    fn test_scan_command_bounds_override_keys() {
+        let api = api_with(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let mut session = Session::default();
+
+        let cmd = Command::Scan {
+            cf: "logs".to_string(),
+            start_key: b"a".to_vec(),
+            end_key: Some(b"b".to_vec()),
+            limit: 10,
+            filter: None,
+            start_bound: Some(ScanBound::Excluded(b"a".to_vec())),
+            end_bound: Some(ScanBound::Included(b"c".to_vec())),
+        };
+        let json = serde_json::to_string(&cmd).unwrap();
+        match api.handle_command(&mut session, serde_json::from_str(&json).unwrap()) {
+            Response::Values(values) => {
+                let keys: Vec<&[u8]> = values.iter().map(|(k, _)| k.0.as_slice()).collect();
+                assert_eq!(keys, vec![b"b".as_slice(), b"c"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // 只给出新字段的请求也能解析
+        let json = r#"{"type":"Scan","cf":"logs","limit":10,"start_bound":"Unbounded","end_bound":{"Excluded":[98]}}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(cmd, Command::Scan { end_bound: Some(ScanBound::Excluded(_)), .. }));
+    }
+
+    #[test]
+    fn test_scan_builder() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        for key in ["k1", "k2", "k3", "k4"] {
+            client.put("cf", key, "v")?;
+        }
+
+        let keys = |pairs: Vec<(String, String)>| pairs.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys(client.scan_builder("cf").from_inclusive("k2").to_inclusive("k3").run()?), vec!["k2", "k3"]);
+        assert_eq!(keys(client.scan_builder("cf").from_exclusive("k2").limit(1).run()?), vec!["k3"]);
+        assert_eq!(keys(client.scan_builder("cf").to_exclusive("k2").run()?), vec!["k1"]);
+        assert_eq!(client.scan_builder("cf").run()?.len(), 4);
+        assert!(client.scan_builder("cf").from_exclusive("k2").to_inclusive("k2").run()?.is_empty());
+        Ok(())
+    }
+
+    fn adjacent_cfs() -> storage::StandaloneStorage {
+        let storage = storage::StandaloneStorage::in_memory();
+        let mut batch = Vec::new();
+        // "`" 与 "a0" 紧挨着列族 a 的编码键空间的两侧
+        for (cf, key) in [
+            ("`", "z"),
+            ("a", ""),
+            ("a", "m"),
+            ("a", "\u{10ffff}"),
+            ("a0", ""),
+            ("a0", "k"),
+            ("a_", "k"),
+            ("b", "a"),
+        ] {
+            batch.push(Modify::new_put(cf.to_string(), key.as_bytes().to_vec(), format!("{}/{}", cf, key).into_bytes()));
+        }
+        storage.write(batch).unwrap();
+        storage
+    }
+
+    fn scan_values(storage: &storage::StandaloneStorage, cf: &str, end: Option<&[u8]>, limit: usize) -> Vec<String> {
+        storage
+            .reader()
+            .unwrap()
+            .scan_cf(cf, b"", end, limit, None)
+            .unwrap()
+            .into_iter()
+            .map(|(_, v)| String::from_utf8(v).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_stays_inside_adjacent_cfs() {
+        let storage = adjacent_cfs();
+
+        // 列族 a_ 的键不会出现在列族 a 的扫描中
+        assert_eq!(scan_values(&storage, "a", None, 100), vec!["a/", "a/m", "a/\u{10ffff}"]);
+        assert_eq!(scan_values(&storage, "a_", None, 100), vec!["a_/k"]);
+        assert_eq!(scan_values(&storage, "a0", None, 100), vec!["a0/", "a0/k"]);
+        assert_eq!(scan_values(&storage, "`", None, 100), vec!["`/z"]);
+        assert!(scan_values(&storage, "aa", None, 100).is_empty());
+
+        // 终点落在最后一个键之后仍不越界
+        assert_eq!(scan_values(&storage, "a", Some(&[0xff, 0xff]), 100).len(), 3);
+        assert_eq!(scan_values(&storage, "a", Some(b"m"), 100), vec!["a/"]);
+    }
+
+    #[test]
+    fn test_scan_limit_at_cf_boundary() {
+        let storage = adjacent_cfs();
+        assert_eq!(scan_values(&storage, "a", None, 2), vec!["a/", "a/m"]);
+        assert!(scan_values(&storage, "a", None, 0).is_empty());
+
+        // 过滤掉的条目不计数，但遍历仍在列族内结束
+        let reader = storage.reader().unwrap();
+        let filter = ValueFilter::Prefix(b"a0".to_vec());
+        assert!(reader.scan_cf("a", b"", None, 10, Some(&filter)).unwrap().is_empty());
+        let keys: Vec<Vec<u8>> = reader.iter_cf("a", b"n", None).unwrap().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!["\u{10ffff}".as_bytes().to_vec()]);
+    }
+
+    fn cf_api() -> RawKeyValueApi {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        let mut batch = Vec::new();
+        for (cf, keys) in [("b", vec!["1", "2"]), ("a", vec!["1", "2"]), ("aB", vec!["1"]), ("c", vec!["1", "2", "3"])] {
+            for key in keys {
+                batch.push(Modify::new_put(cf.to_string(), key.as_bytes().to_vec(), format!("{}{}", cf, key).into_bytes()));
+            }
+        }
+        storage.write(batch).unwrap();
+        RawKeyValueApi::new(storage)
+    }
+
+    fn page(api: &RawKeyValueApi, start: Option<(&str, &[u8])>, limit: usize) -> (Vec<String>, Option<(String, Vec<u8>)>) {
+        let (entries, next) = api.raw_scan_all("default", start, limit).unwrap();
+        let entries = entries
+            .into_iter()
+            .map(|(cf, k, v)| {
+                assert_eq!(v, [cf.as_bytes(), &k].concat());
+                format!("{}/{}", cf, String::from_utf8(k).unwrap())
+            })
+            .collect();
+        (entries, next)
+    }
+
+    #[test]
+    fn test_scan_all_ordered_by_cf_and_key() {
+        let api = cf_api();
+        let (all, next) = page(&api, None, 100);
+        assert_eq!(all, vec!["a/1", "a/2", "aB/1", "b/1", "b/2", "c/1", "c/2", "c/3"]);
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn test_scan_all_pages_on_cf_boundaries() {
+        let api = cf_api();
+
+        // 第一页恰好在列族 a 的末尾结束，游标指向下一个列族的第一个键
+        let (first, next) = page(&api, None, 2);
+        assert_eq!(first, vec!["a/1", "a/2"]);
+        assert_eq!(next, Some(("aB".to_string(), b"1".to_vec())));
+
+        let (cf, key) = next.unwrap();
+        let (second, next) = page(&api, Some((&cf, &key)), 3);
+        assert_eq!(second, vec!["aB/1", "b/1", "b/2"]);
+        assert_eq!(next, Some(("c".to_string(), b"1".to_vec())));
+
+        // 最后一页恰好取完所有条目时没有下一页
+        let (cf, key) = next.unwrap();
+        let (last, next) = page(&api, Some((&cf, &key)), 3);
+        assert_eq!(last, vec!["c/1", "c/2", "c/3"]);
+        assert_eq!(next, None);
+
+        // 起点可以落在不存在的列族或键上
+        assert_eq!(page(&api, Some(("ab", b"")), 1).0, vec!["b/1"]);
+        assert_eq!(page(&api, Some(("c", b"10")), 10).0, vec!["c/2", "c/3"]);
+    }
+
+    #[test]
+    fn test_scan_all_stays_in_session_database() {
+        let api = cf_api();
+        let mut session = Session::default();
+        api.handle_command(&mut session, Command::UseDb { name: "app".to_string() });
+        api.handle_command(&mut session, Command::Put { cf: "z".to_string(), key: b"k".to_vec(), value: b"v".to_vec() });
+
+        match api.handle_command(&mut session, Command::ScanAll { start: None, limit: 10 }) {
+            Response::CfValues { entries, next, .. } => {
+                assert_eq!(entries, vec![("z".to_string(), Bytes(b"k".to_vec()), Bytes(b"v".to_vec()))]);
+                assert_eq!(next, None);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_iter_cf_resumes_after_disconnect() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let mut admin = server.connect()?;
+        let expected: BTreeMap<Vec<u8>, Vec<u8>> =
+            (0..95).map(|i| (format!("k{:03}", i).into_bytes(), format!("v{}", i).into_bytes())).collect();
+        for (key, value) in &expected {
+            admin.put_bytes("logs", key, value)?;
+        }
+        admin.put("other", "k000", "x")?;
+
+        let client = server.client();
+        assert_eq!(client.iter_cf("logs", 10).collect_to_map()?, expected);
+        assert_eq!(client.iter_cf("logs", 95).count(), 95);
+        assert_eq!(client.iter_cf("empty", 10).count(), 0);
+
+        // 读到一半时服务端断开连接，迭代器重新连接后从断点继续
+        let mut seen = Vec::new();
+        let mut iter = client.iter_cf("logs", 10);
+        for entry in iter.by_ref().take(25) {
+            seen.push(entry?);
+        }
+        let scanning = admin.clients()?.into_iter().find(|c| c.last_command.as_deref() == Some("Scan")).unwrap();
+        admin.kill_client(scanning.id)?;
+        for entry in iter {
+            seen.push(entry?);
+        }
+        assert_eq!(seen, expected.into_iter().collect::<Vec<_>>());
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::protocol::{Bytes, Command, Modify, Response, ValueFilter};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage;
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> Vec<u8> {
+        format!("k{:07}", i).into_bytes()
+    }
+
+    /// count 个键，下标是 step 的倍数的值为 "match"，其余为 "other"
+    fn populate(storage: &storage::StandaloneStorage, count: usize, step: usize) {
+        for chunk_start in (0..count).step_by(100_000) {
+            let ops = (chunk_start..(chunk_start + 100_000).min(count))
+                .map(|i| {
+                    let value: &[u8] = if step > 0 && i % step == 0 { b"match" } else { b"other" };
+                    Modify::new_put("logs".to_string(), key(i), value.to_vec())
+                })
+                .collect();
+            storage.write(ops).unwrap();
+        }
+    }
+
+    fn filtered_scan(start: Vec<u8>, limit: usize) -> Command {
+        Command::Scan {
+            cf: "logs".to_string(),
+            start_key: start,
+            end_key: None,
+            limit,
+            filter: Some(ValueFilter::Prefix(b"match".to_vec())),
+            start_bound: None,
+            end_bound: None,
+        }
+    }
+
+    #[test]
+    fn test_filter_without_matches_stops_at_the_examined_limit() {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        populate(&storage, 1_000_000, 0);
+        let config = ServerConfig { max_scan_examined: Some(10_000), ..ServerConfig::default() };
+        let api = RawKeyValueApi::with_config(storage, Arc::new(config));
+        let mut session = api.new_session();
+        session.features = vec!["truncation".to_string()];
+
+        let started = Instant::now();
+        let response = api.handle_command(&mut session, filtered_scan(Vec::new(), 10));
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+        match response {
+            Response::TruncatedValues { values, next: Bytes(next), examined_limit_reached } => {
+                assert!(values.is_empty());
+                assert!(examined_limit_reached);
+                assert_eq!(next, key(10_000));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // 没有协商 truncation 的连接收到错误
+        let mut old_session = api.new_session();
+        let response = api.handle_command(&mut old_session, filtered_scan(Vec::new(), 10));
+        assert!(matches!(&response, Response::Error(e) if e.starts_with("ExaminedLimitReached")), "{:?}", response);
+    }
+
+    #[test]
+    fn test_scan_within_the_limit_is_unaffected() {
+        let storage = Arc::new(storage::StandaloneStorage::in_memory());
+        populate(&storage, 100, 10);
+        let config = ServerConfig { max_scan_examined: Some(1000), ..ServerConfig::default() };
+        let api = RawKeyValueApi::with_config(storage, Arc::new(config));
+        let mut session = api.new_session();
+        let response = api.handle_command(&mut session, filtered_scan(Vec::new(), 100));
+        let Response::Values(values) = response else { panic!("unexpected {:?}", response) };
+        assert_eq!(values.len(), 10);
+
+        // 凑够 limit 时不再检查后面的条目，也不报告截断
+        let page = api
+            .raw_scan_limited("logs", std::ops::Bound::Unbounded, std::ops::Bound::Unbounded, 3, None, None, Some(3))
+            .unwrap();
+        assert_eq!((page.pairs.len(), page.next, page.examined_limit_reached), (3, None, false));
+    }
+
+    #[test]
+    fn test_client_continues_from_the_cursor() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { max_scan_examined: Some(25), ..ServerConfig::default() };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        for i in 0..100 {
+            client.put_bytes("logs", &key(i), if i % 10 == 0 { b"match" } else { b"other" })?;
+        }
+        let expected: Vec<Vec<u8>> = (0..100).step_by(10).map(key).collect();
+
+        // 只检查到上限就停下，返回已匹配的条目和续扫位置
+        let page = client.scan_builder("logs").filter(ValueFilter::Prefix(b"match".to_vec())).run_bounded()?;
+        assert!(page.examined_limit_reached && !page.complete);
+        assert_eq!(page.items.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>(), expected[..3].to_vec());
+        assert_eq!(page.cursor, Some(key(25)));
+
+        // 调用方从续扫位置继续，最终得到全部匹配的条目
+        let mut found: Vec<Vec<u8>> = page.items.into_iter().map(|(k, _)| k).collect();
+        let mut cursor = page.cursor;
+        while let Some(from) = cursor {
+            let page = client.scan_builder("logs").from_inclusive(from).filter(ValueFilter::Prefix(b"match".to_vec())).run_bounded()?;
+            found.extend(page.items.into_iter().map(|(k, _)| k));
+            cursor = page.cursor;
+        }
+        assert_eq!(found, expected);
+
+        // run_bytes 自动续扫
+        let all = client.scan_builder("logs").filter(ValueFilter::Prefix(b"match".to_vec())).limit(5).run_bytes()?;
+        assert_eq!(all.into_iter().map(|(k, _)| k).collect::<Vec<_>>(), expected[..5].to_vec());
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+use tinykv_rs::storage::{self, CfOptions, Durability, FileSystem, OsFileSystem, StorageOptions};
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::lockfile::LOCK_FILE;
+use tinykv_rs::migration::FORMAT_VERSION_FILE;
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// 在执行若干次文件操作后模拟进程崩溃：当次写入只落盘一半，之后的操作全部失败
+#[derive(Debug)]
+struct CrashingFs {
+    remaining: AtomicUsize,
+    crashed: AtomicBool,
+}
+
+impl CrashingFs {
+    fn after(ops: usize) -> Arc<Self> {
+        Arc::new(CrashingFs { remaining: AtomicUsize::new(ops), crashed: AtomicBool::new(false) })
+    }
+
+    fn crashed(&self) -> bool {
+        self.crashed.load(Ordering::SeqCst)
+    }
+
+    /// 本次操作是否应当失败
+    fn crash_now(&self) -> bool {
+        if self.crashed() {
+            return true;
+        }
+        if self.remaining.load(Ordering::SeqCst) == 0 {
+            self.crashed.store(true, Ordering::SeqCst);
+            return true;
+        }
+        self.remaining.fetch_sub(1, Ordering::SeqCst);
+        false
+    }
+
+    fn crash_error() -> io::Error {
+        io::Error::other("simulated crash")
+    }
+}
+
+impl FileSystem for CrashingFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        OsFileSystem.create_dir_all(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        if self.crash_now() {
+            fs::write(path, &data[..data.len() / 2])?;
+            return Err(Self::crash_error());
+        }
+        OsFileSystem.write_file(path, data, sync)
+    }
+
+    fn append_file(&self, path: &Path, data: &[u8], sync: bool) -> io::Result<()> {
+        if self.crash_now() {
+            fs::OpenOptions::new().append(true).open(path)?.write_all(&data[..data.len() / 2])?;
+            return Err(Self::crash_error());
+        }
+        OsFileSystem.append_file(path, data, sync)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if self.crash_now() {
+            return Err(Self::crash_error());
+        }
+        OsFileSystem.rename(from, to)
+    }
+
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        if self.crash_now() {
+            return Err(Self::crash_error());
+        }
+        OsFileSystem.sync_dir(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        if self.crash_now() {
+            return Err(Self::crash_error());
+        }
+        OsFileSystem.remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_with(path: &Path, fs: Arc<dyn FileSystem>, segment_max_bytes: u64) -> storage::StandaloneStorage {
+        open_rotating(path, fs, segment_max_bytes, None)
+    }
+
+    fn open_rotating(
+        path: &Path,
+        fs: Arc<dyn FileSystem>,
+        segment_max_bytes: u64,
+        segment_max_age: Option<Duration>,
+    ) -> storage::StandaloneStorage {
+        let options = StorageOptions {
+            durability: Durability::Fsync,
+            fs,
+            segment_max_bytes,
+            segment_max_age,
+            ..StorageOptions::default()
+        };
+        storage::StandaloneStorage::open_with_options(path, options).unwrap()
+    }
+
+    fn open(path: &Path) -> storage::StandaloneStorage {
+        open_with(path, Arc::new(OsFileSystem), 1024 * 1024)
+    }
+
+    fn put(storage: &storage::StandaloneStorage, key: &str, value: &str) {
+        storage.write(vec![Modify::new_put("cf".to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn delete(storage: &storage::StandaloneStorage, key: &str) {
+        storage.write(vec![Modify::new_delete("cf".to_string(), key.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn get(storage: &storage::StandaloneStorage, key: &str) -> Option<String> {
+        storage
+            .reader()
+            .unwrap()
+            .get_cf("cf", key.as_bytes())
+            .unwrap()
+            .map(|v| String::from_utf8(v).unwrap())
+    }
+
+    /// 数据目录中的存储文件，不含锁文件
+    fn files(path: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(path)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != LOCK_FILE && name != FORMAT_VERSION_FILE)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_flush_writes_only_changed_keys() {
+        let path = temp_dir("segments_delta");
+        let storage = open(&path);
+        storage.set_cf_options("cf", CfOptions { keep_versions: 2, ..CfOptions::default() }).unwrap();
+        for i in 0..500 {
+            put(&storage, &format!("key{:03}", i), "initial value");
+        }
+        let full = storage.flush().unwrap().bytes_written;
+
+        put(&storage, "key007", "changed");
+        delete(&storage, "key008");
+        let delta = storage.flush().unwrap().bytes_written;
+        assert!(delta * 20 < full, "delta flush wrote {} of {} bytes", delta, full);
+
+        // 没有修改时不写入
+        assert_eq!(storage.flush().unwrap().bytes_written, 0);
+        let history = storage.reader().unwrap().history_cf("cf", b"key007", 10).unwrap();
+        drop(storage);
+
+        let reopened = open(&path);
+        assert_eq!(get(&reopened, "key000"), Some("initial value".to_string()));
+        assert_eq!(get(&reopened, "key007"), Some("changed".to_string()));
+        assert_eq!(get(&reopened, "key008"), None);
+        // 版本历史和列族选项随段文件一起恢复
+        assert_eq!(reopened.reader().unwrap().history_cf("cf", b"key007", 10).unwrap(), history);
+        put(&reopened, "key007", "again");
+        assert_eq!(reopened.reader().unwrap().history_cf("cf", b"key007", 10).unwrap().len(), history.len() + 1);
+    }
+
+    #[test]
+    fn test_segments_rotate_and_replay_in_order() {
+        let path = temp_dir("segments_rotate");
+        let storage = open_with(&path, Arc::new(OsFileSystem), 64);
+        for round in 0..5 {
+            put(&storage, "k", &format!("v{}", round));
+            put(&storage, &format!("only{}", round), "x");
+            storage.flush().unwrap();
+        }
+        drop(storage);
+
+        let segments: Vec<String> = files(&path).into_iter().filter(|f| f.starts_with("segment-")).collect();
+        assert_eq!(segments.len(), 5);
+
+        let reopened = open(&path);
+        assert_eq!(get(&reopened, "k"), Some("v4".to_string()));
+        for round in 0..5 {
+            assert_eq!(get(&reopened, &format!("only{}", round)), Some("x".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_segments_rotate_by_age() {
+        let path = temp_dir("segments_rotate_age");
+        let clock = Arc::new(MockClock::new(1_000));
+        let options = StorageOptions {
+            segment_max_age: Some(Duration::from_secs(60)),
+            clock: clock.clone(),
+            ..StorageOptions::default()
+        };
+        let storage = storage::StandaloneStorage::open_with_options(&path, options.clone()).unwrap();
+        for round in 0..3 {
+            put(&storage, "k", &format!("v{}", round));
+            storage.flush().unwrap();
+            put(&storage, &format!("only{}", round), "x");
+            storage.flush().unwrap();
+            clock.advance(Duration::from_secs(120));
+        }
+        let stats = storage.wal_stats().unwrap();
+        assert_eq!((stats.files, stats.rotations, stats.pruned_files), (3, 2, 0));
+        let on_disk: u64 = files(&path)
+            .iter()
+            .filter(|f| f.starts_with("segment-"))
+            .map(|f| fs::metadata(path.join(f)).unwrap().len())
+            .sum();
+        assert_eq!(stats.total_bytes, on_disk);
+
+        // 整理之后旧的段文件全部删除，只剩基础快照
+        storage.compact().unwrap();
+        let stats = storage.wal_stats().unwrap();
+        assert_eq!((stats.files, stats.active_bytes, stats.pruned_files), (1, 0, 3));
+        drop(storage);
+
+        let reopened = storage::StandaloneStorage::open_with_options(&path, options).unwrap();
+        assert_eq!(get(&reopened, "k"), Some("v2".to_string()));
+        assert_eq!(get(&reopened, "only0"), Some("x".to_string()));
+    }
+
+    #[test]
+    fn test_compact_merges_segments_into_base() {
+        let path = temp_dir("segments_compact");
+        let storage = open_with(&path, Arc::new(OsFileSystem), 64);
+        for i in 0..4 {
+            put(&storage, &format!("k{}", i), "v");
+            storage.flush().unwrap();
+        }
+        delete(&storage, "k0");
+        storage.compact().unwrap();
+        assert_eq!(files(&path), vec!["MANIFEST", "base-000005.json"]);
+        assert_eq!(storage.flush_info().unwrap().dirty, 0);
+
+        // 整理之后的刷盘写入新的段文件
+        put(&storage, "k4", "v");
+        storage.flush().unwrap();
+        drop(storage);
+
+        let reopened = open(&path);
+        assert_eq!(get(&reopened, "k0"), None);
+        assert_eq!(get(&reopened, "k3"), Some("v".to_string()));
+        assert_eq!(get(&reopened, "k4"), Some("v".to_string()));
+    }
+
+    #[test]
+    fn test_legacy_snapshot_is_used_as_base() {
+        let path = temp_dir("segments_legacy");
+        let legacy = serde_json::json!({ "entries": [[b"cf_old".to_vec(), b"value".to_vec()]] });
+        fs::write(path.join("data.json"), legacy.to_string()).unwrap();
+
+        let storage = open(&path);
+        assert_eq!(get(&storage, "old"), Some("value".to_string()));
+        put(&storage, "new", "value");
+        storage.flush().unwrap();
+        drop(storage);
+
+        let storage = open(&path);
+        assert_eq!(get(&storage, "old"), Some("value".to_string()));
+        assert_eq!(get(&storage, "new"), Some("value".to_string()));
+
+        storage.compact().unwrap();
+        assert!(!path.join("data.json").exists());
+        drop(storage);
+        assert_eq!(get(&open(&path), "old"), Some("value".to_string()));
+    }
+
+    /// 在刷盘的每一步崩溃后，重新打开的存储要么是刷盘前的状态，要么是刷盘后的状态
+    fn crash_during_flush(name: &str, segment_max_bytes: u64, segment_max_age: Option<Duration>) {
+        let open_with = |path: &Path, fs: Arc<dyn FileSystem>, max_bytes| open_rotating(path, fs, max_bytes, segment_max_age);
+        for ops in 0.. {
+            let path = temp_dir(&format!("{}_{}", name, ops));
+            let storage = open_with(&path, Arc::new(OsFileSystem), segment_max_bytes);
+            put(&storage, "a", "1");
+            storage.flush().unwrap();
+            drop(storage);
+
+            let crashing = CrashingFs::after(ops);
+            let storage = open_with(&path, crashing.clone(), segment_max_bytes);
+            put(&storage, "a", "2");
+            put(&storage, "b", "2");
+            let result = storage.flush();
+            drop(storage);
+
+            let reopened = open_with(&path, Arc::new(OsFileSystem), segment_max_bytes);
+            let state = (get(&reopened, "a"), get(&reopened, "b"));
+            let before = (Some("1".to_string()), None);
+            let after = (Some("2".to_string()), Some("2".to_string()));
+            match result {
+                Ok(_) => assert_eq!(state, after, "crash after {} ops", ops),
+                Err(_) => assert!(state == before || state == after, "crash after {} ops: {:?}", ops, state),
+            }
+
+            // 崩溃留下的文件不影响之后的写入
+            put(&reopened, "c", "3");
+            reopened.flush().unwrap();
+            drop(reopened);
+            let reopened = open(&path);
+            assert_eq!((get(&reopened, "a"), get(&reopened, "b")), state);
+            assert_eq!(get(&reopened, "c"), Some("3".to_string()));
+
+            let _ = fs::remove_dir_all(&path);
+            if !crashing.crashed() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_crash_while_appending_to_segment() {
+        crash_during_flush("segments_crash_append", 1024 * 1024, None);
+    }
+
+    #[test]
+    fn test_crash_while_rotating_segment() {
+        crash_during_flush("segments_crash_rotate", 1, None);
+    }
+
+    #[test]
+    fn test_crash_while_rotating_segment_by_age() {
+        // 时长为 0 时每次刷盘都按时间切换到新的段文件
+        crash_during_flush("segments_crash_rotate_age", 1024 * 1024, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_crash_during_compaction() {
+        for ops in 0.. {
+            let path = temp_dir(&format!("segments_crash_compact_{}", ops));
+            let storage = open_with(&path, Arc::new(OsFileSystem), 1);
+            put(&storage, "a", "1");
+            put(&storage, "b", "1");
+            storage.flush().unwrap();
+            delete(&storage, "b");
+            storage.flush().unwrap();
+            drop(storage);
+
+            let crashing = CrashingFs::after(ops);
+            let storage = open_with(&path, crashing.clone(), 1);
+            put(&storage, "c", "3");
+            let result = storage.compact();
+            drop(storage);
+
+            let reopened = open(&path);
+            assert_eq!(get(&reopened, "a"), Some("1".to_string()), "crash after {} ops", ops);
+            assert_eq!(get(&reopened, "b"), None, "crash after {} ops", ops);
+            if result.is_ok() {
+                assert_eq!(get(&reopened, "c"), Some("3".to_string()), "crash after {} ops", ops);
+            }
+
+            // 下一次整理清理掉崩溃留下的文件
+            put(&reopened, "d", "4");
+            reopened.compact().unwrap();
+            let remaining = files(&path);
+            assert_eq!(remaining.len(), 2, "{:?}", remaining);
+            assert!(remaining[1].starts_with("base-"));
+            drop(reopened);
+            assert_eq!(get(&open(&path), "d"), Some("4".to_string()));
+
+            let _ = fs::remove_dir_all(&path);
+            if !crashing.crashed() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::{self, Command, Response};
+use tinykv_rs::server::KvServer;
+
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在后台线程启动内存模式的服务器，返回监听地址
+    fn spawn_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Arc::new(KvServer::in_memory().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let server = Arc::clone(&server);
+                let stream = stream.unwrap();
+                thread::spawn(move || {
+                    let _ = server.serve_connection(stream);
+                });
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn test_command_split_across_reads() -> Result<(), Box<dyn std::error::Error>> {
+        let addr = spawn_server();
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+
+        let cmd = Command::Put {
+            cf: "default".to_string(),
+            key: b"split".to_vec(),
+            value: b"value".to_vec(),
+        };
+        for byte in serde_json::to_vec(&cmd)? {
+            stream.write_all(&[byte])?;
+            stream.flush()?;
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let mut pending = Vec::new();
+        let response: Option<Response> = protocol::read_message(&mut stream, &mut pending)?;
+        assert!(matches!(response, Some(Response::Ok)));
+
+        let mut client = KvClient::connect(&addr.to_string())?;
+        assert_eq!(client.get("default", "split")?, Some("value".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_scan_response() -> Result<(), Box<dyn std::error::Error>> {
+        let addr = spawn_server();
+        let mut client = KvClient::connect(&addr.to_string())?;
+
+        let value = "x".repeat(1024);
+        for i in 0..100 {
+            client.put("big", &format!("key{:03}", i), &value)?;
+        }
+
+        let results = client.scan("big", "", None, 1000)?;
+        assert_eq!(results.len(), 100);
+        assert!(results.iter().all(|(_, v)| v.len() == 1024));
+
+        // 连接在大响应之后仍可继续使用
+        assert_eq!(client.get("big", "key042")?, Some(value));
+        Ok(())
+    }
+}
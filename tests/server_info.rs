@@ -0,0 +1,74 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::group_commit::GroupCommitConfig;
+use tinykv_rs::migration;
+use tinykv_rs::protocol::{self, Command, Response};
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{Durability, StandaloneStorage, StorageOptions};
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_info_reflects_config() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            storage_options: StorageOptions { durability: Durability::Fsync, max_memory_bytes: Some(1 << 20), ..StorageOptions::default() },
+            admin_token: Some("secret".to_string()),
+            group_commit: Some(GroupCommitConfig::default()),
+            max_response_bytes: Some(4096),
+            idle_timeout: Some(Duration::from_secs(30)),
+            ..ServerConfig::default()
+        };
+        let server = TestServer::start_with_config(config)?;
+        // 不需要管理权限
+        let info = server.connect()?.server_info()?;
+
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.git_hash.as_deref(), protocol::GIT_HASH);
+        assert_eq!((info.protocol_version, info.format_version), (protocol::PROTOCOL_VERSION, migration::FORMAT_VERSION));
+        assert_eq!(info.pid, std::process::id());
+        assert_eq!(info.listen_addrs, vec![server.addr().to_string()]);
+        let data_path = server.data_path().canonicalize()?;
+        assert_eq!(info.data_path, Some(data_path.display().to_string()));
+        assert_eq!(info.durability, Durability::Fsync);
+        // 默认开启的后台整理同样列出
+        assert_eq!(info.features, vec!["admin-auth", "group-commit", "auto-compaction"]);
+        assert!(info.protocol_features.iter().any(|f| f == "envelope"));
+        assert_eq!(info.limits.max_memory_bytes, Some(1 << 20));
+        assert_eq!(info.limits.max_response_bytes, Some(4096));
+        assert_eq!(info.limits.max_scan_examined, None);
+        assert_eq!(info.limits.idle_timeout_ms, Some(30_000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_banner_format() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let banner = server.connect()?.server_info()?.to_string();
+        let first = banner.lines().next().unwrap();
+        assert!(first.starts_with(&format!("tinykv {}", env!("CARGO_PKG_VERSION"))), "{}", banner);
+        assert!(banner.contains(&format!("  listen:            {}\n", server.addr())), "{}", banner);
+        assert!(banner.contains("  features:          auto-compaction\n"), "{}", banner);
+        assert!(banner.contains("  max response:      -\n"), "{}", banner);
+        assert!(!banner.ends_with('\n'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_info_without_listener() {
+        let api = RawKeyValueApi::new(Arc::new(StandaloneStorage::in_memory()));
+        let mut session = api.new_session();
+        let Response::ServerInfo(info) = api.handle_command(&mut session, Command::ServerInfo) else {
+            panic!("expected ServerInfo");
+        };
+        assert!(info.listen_addrs.is_empty());
+        assert_eq!(info.data_path, None);
+        assert!(!info.background_flush);
+        assert!(info.to_string().contains("  data path:         (in-memory)\n"));
+        assert_eq!(*info, api.server_info());
+    }
+}
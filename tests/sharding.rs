@@ -0,0 +1,153 @@
+use tinykv_rs::api::EncodedKey;
+use tinykv_rs::client::ShardedClient;
+use tinykv_rs::protocol::stable_hash;
+use tinykv_rs::testing::TestServer;
+
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(n: usize) -> Vec<TestServer> {
+        (0..n).map(|_| TestServer::start().unwrap()).collect()
+    }
+
+    fn keys(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("k{:04}", i)).collect()
+    }
+
+    fn owners(client: &ShardedClient, keys: &[String]) -> BTreeMap<String, String> {
+        keys.iter().map(|k| (k.clone(), client.node_for("cf", k).unwrap().to_string())).collect()
+    }
+
+    #[test]
+    fn test_routing_and_fan_out() -> Result<(), Box<dyn std::error::Error>> {
+        let mut servers = start(3);
+        let addrs: Vec<String> = servers.iter().map(|s| s.addr().to_string()).collect();
+        let mut client = ShardedClient::new(&addrs.iter().map(String::as_str).collect::<Vec<_>>())?;
+
+        let keys = keys(300);
+        for key in &keys {
+            client.put("cf", key, &format!("v-{}", key))?;
+        }
+
+        // 每个键只写到负责它的节点，三个节点都分到了键
+        for server in &mut servers {
+            let stored = server.client().scan("cf", "", None, usize::MAX)?;
+            assert!(!stored.is_empty());
+            assert!(stored.iter().all(|(k, _)| client.node_for("cf", k) == Some(server.addr())));
+        }
+
+        assert_eq!(client.get("cf", "k0042")?, Some("v-k0042".to_string()));
+        let values = client.multi_get("cf", &["k0001", "missing", "k0299", "k0001"])?;
+        assert_eq!(
+            values,
+            vec![Some("v-k0001".to_string()), None, Some("v-k0299".to_string()), Some("v-k0001".to_string())]
+        );
+
+        client.delete("cf", "k0001")?;
+        assert_eq!(client.get("cf", "k0001")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_merges_shards_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        let servers = start(3);
+        let addrs: Vec<&str> = servers.iter().map(|s| s.addr()).collect();
+        let mut client = ShardedClient::new(&addrs)?;
+        let keys = keys(200);
+        for key in &keys {
+            client.put("cf", key, key)?;
+        }
+        client.put("other", "k0100", "other cf")?;
+
+        let page = client.scan("cf", "k0050", Some("k0150"), 40)?;
+        let expected: Vec<(String, String)> = keys[50..90].iter().map(|k| (k.clone(), k.clone())).collect();
+        assert_eq!(page, expected);
+
+        let all = client.scan("cf", "", None, 1000)?;
+        assert_eq!(all.len(), 200);
+        assert!(all.windows(2).all(|w| w[0].0 < w[1].0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_membership_changes_move_minimal_ranges() -> Result<(), Box<dyn std::error::Error>> {
+        let servers = start(4);
+        let addrs: Vec<&str> = servers.iter().map(|s| s.addr()).collect();
+        let mut client = ShardedClient::new(&addrs[..3])?;
+        let keys = keys(2000);
+        let before = owners(&client, &keys);
+
+        // 同样的节点集合得到同样的路由
+        assert_eq!(owners(&ShardedClient::new(&addrs[..3])?, &keys), before);
+
+        // 新节点只接管键，其他节点之间不交换键
+        client.add_node(addrs[3])?;
+        let after_add = owners(&client, &keys);
+        let moved: Vec<&String> = keys.iter().filter(|k| before[*k] != after_add[*k]).collect();
+        assert!(moved.iter().all(|k| after_add[*k] == addrs[3]));
+        assert!(moved.len() > 2000 / 10 && moved.len() < 2000 / 2, "{} keys moved", moved.len());
+
+        // 移除节点只影响它负责的键
+        client.remove_node(addrs[0])?;
+        let after_remove = owners(&client, &keys);
+        for key in &keys {
+            if after_add[key] != addrs[0] {
+                assert_eq!(after_remove[key], after_add[key]);
+            }
+        }
+        assert!(client.remove_node(addrs[0]).is_err());
+        assert!(client.add_node(addrs[1]).is_err());
+        assert_eq!(client.nodes().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_prefers_current_owner_after_rebalance() -> Result<(), Box<dyn std::error::Error>> {
+        let servers = start(2);
+        let mut client = ShardedClient::new(&[servers[0].addr()])?;
+        for key in keys(50) {
+            client.put("cf", &key, "old")?;
+        }
+
+        // 加入节点后部分键改由新节点负责，旧节点上的副本被新值覆盖
+        client.add_node(servers[1].addr())?;
+        for key in keys(50) {
+            client.put("cf", &key, "new")?;
+        }
+        let all = client.scan("cf", "", None, 100)?;
+        assert_eq!(all.len(), 50);
+        assert!(all.iter().all(|(_, v)| v == "new"));
+
+        assert!(ShardedClient::new(&[])?.get("cf", "k").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stable_hash_golden_values() {
+        // 这些值固定下来以后不能改变，否则已有数据在 ShardedClient 中的归属会变化
+        assert_eq!(stable_hash(b""), 0xf52a15e9a9b5e89b);
+        assert_eq!(stable_hash(b"a"), 0x02c0bdbf481420f8);
+        assert_eq!(stable_hash(b"tinykv"), 0xb958ec2d7b3d385d);
+        assert_eq!(EncodedKey::encode("users", b"alice").stable_hash(), 0xbcbd40f00d622b0d);
+        assert_eq!(EncodedKey::encode("cf", b"").stable_hash(), 0xf20375aeeca3a276);
+    }
+
+    #[test]
+    fn test_server_key_hash_matches_client() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        assert_eq!(client.key_hash("users", b"alice")?, 0xbcbd40f00d622b0d);
+        for key in keys(20) {
+            let expected = EncodedKey::encode("cf", key.as_bytes()).stable_hash();
+            assert_eq!(client.key_hash("cf", key.as_bytes())?, expected);
+        }
+
+        // 其他数据库中的列族名带数据库前缀，哈希随之不同
+        client.use_db("tenant")?;
+        assert_ne!(client.key_hash("users", b"alice")?, 0xbcbd40f00d622b0d);
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::{KvServer, ServerConfig, ShutdownOptions};
+use tinykv_rs::storage;
+use tinykv_rs::testing::temp_dir;
+
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutdown_drains_connections_and_flushes() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("shutdown");
+        let handle = KvServer::new(&path)?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+
+        let mut client = KvClient::connect(&addr)?;
+        client.put("default", "k", "v")?;
+
+        let report = handle.shutdown(ShutdownOptions {
+            drain_timeout: Duration::from_secs(2),
+            flush: true,
+        })?;
+        assert_eq!(report.drained, 1);
+        assert_eq!(report.forced, 0);
+        assert!(report.flush.unwrap().bytes_written > 0);
+
+        // 已建立的连接被关闭，新连接不再被服务
+        assert!(client.get("default", "k").is_err());
+        let refused = KvClient::connect(&addr).and_then(|mut c| c.get("default", "k"));
+        assert!(refused.is_err());
+
+        let reopened = storage::StandaloneStorage::open(&path)?;
+        assert_eq!(reopened.reader()?.get_cf("default", b"k")?, Some(b"v".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_without_flush() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("shutdown_no_flush");
+        let handle = KvServer::new(&path)?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+        client.put("default", "k", "v")?;
+
+        let report = handle.shutdown(ShutdownOptions { flush: false, ..ShutdownOptions::default() })?;
+        assert!(report.flush.is_none());
+        assert!(!path.join("MANIFEST").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_shutdown_command() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("shutdown_remote");
+        let handle = KvServer::new(&path)?.start_background("127.0.0.1:0")?;
+        let addr = handle.local_addr().to_string();
+
+        let mut idle = KvClient::connect(&addr)?;
+        let mut client = KvClient::connect(&addr)?;
+        client.put("default", "k", "v")?;
+        client.shutdown(true)?;
+
+        // 监听线程在刷盘完成后退出
+        handle.wait()?;
+        assert!(idle.get("default", "k").is_err());
+        assert!(KvClient::connect(&addr).is_err());
+
+        let reopened = storage::StandaloneStorage::open(&path)?;
+        assert_eq!(reopened.reader()?.get_cf("default", b"k")?, Some(b"v".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_shutdown_requires_admin() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig { admin_token: Some("secret".to_string()), ..ServerConfig::default() };
+        let handle = KvServer::with_config(config)?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+        assert!(client.shutdown(false).is_err());
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        client.admin_auth("secret")?;
+        client.shutdown(false)?;
+        handle.wait()?;
+        Ok(())
+    }
+}
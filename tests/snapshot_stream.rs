@@ -0,0 +1,97 @@
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage;
+use tinykv_rs::testing::temp_dir;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 记录开启统计期间单次分配的最大字节数
+struct PeakAlloc;
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static LARGEST: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if TRACKING.load(Ordering::Relaxed) {
+            LARGEST.fetch_max(layout.size(), Ordering::Relaxed);
+        }
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if TRACKING.load(Ordering::Relaxed) {
+            LARGEST.fetch_max(new_size, Ordering::Relaxed);
+        }
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 统计是全局的，测试依次执行
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    const KEYS: usize = 256;
+    const VALUE_LEN: usize = 4096;
+
+    /// f 执行期间单次分配的最大字节数
+    fn largest_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        LARGEST.store(0, Ordering::SeqCst);
+        TRACKING.store(true, Ordering::SeqCst);
+        let result = f();
+        TRACKING.store(false, Ordering::SeqCst);
+        (result, LARGEST.load(Ordering::SeqCst))
+    }
+
+    fn fill(path: &Path) -> storage::StandaloneStorage {
+        let storage = storage::StandaloneStorage::open(path).unwrap();
+        let ops = (0..KEYS)
+            .map(|i| Modify::new_put("docs".to_string(), format!("k{:04}", i).into_bytes(), vec![i as u8; VALUE_LEN]))
+            .collect();
+        storage.write(ops).unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_base_snapshot_is_written_without_buffering_the_file() {
+        let _serial = SERIAL.lock().unwrap();
+        let path = temp_dir("snapshot_stream_save");
+        let storage = fill(&path);
+
+        let (result, largest) = largest_allocation(|| storage.compact());
+        result.unwrap();
+        let dataset = KEYS * VALUE_LEN;
+        let file_bytes = storage.flush_info().unwrap().last_bytes as usize;
+        assert!(file_bytes > dataset, "{} bytes on disk", file_bytes);
+        assert!(largest < dataset / 4, "largest allocation {} for a {} byte dataset", largest, dataset);
+    }
+
+    #[test]
+    fn test_base_snapshot_is_parsed_while_reading() {
+        let _serial = SERIAL.lock().unwrap();
+        let path = temp_dir("snapshot_stream_load");
+        let storage = fill(&path);
+        storage.compact().unwrap();
+        drop(storage);
+
+        let (storage, largest) = largest_allocation(|| storage::StandaloneStorage::open(&path).unwrap());
+        let dataset = KEYS * VALUE_LEN;
+        assert!(largest < dataset / 4, "largest allocation {} for a {} byte dataset", largest, dataset);
+
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.get_cf("docs", b"k0042").unwrap(), Some(vec![42; VALUE_LEN]));
+        assert_eq!(storage.get_stats().unwrap().0, KEYS);
+    }
+}
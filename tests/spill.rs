@@ -0,0 +1,205 @@
+use tinykv_rs::api::RawKeyValueApi;
+use tinykv_rs::blob::{self, SpillStats};
+use tinykv_rs::protocol::{Command, Modify, Response};
+use tinykv_rs::storage::{self, CfOptions, EvictionPolicy, StorageOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spill_options() -> StorageOptions {
+        StorageOptions { spill_threshold: Some(100), checksums: true, ..StorageOptions::default() }
+    }
+
+    fn value(len: usize, fill: u8) -> Vec<u8> {
+        vec![fill; len]
+    }
+
+    fn put(storage: &storage::StandaloneStorage, key: &str, value: Vec<u8>) {
+        storage.write(vec![Modify::new_put("docs".to_string(), key.as_bytes().to_vec(), value)]).unwrap();
+    }
+
+    fn get(storage: &storage::StandaloneStorage, key: &str) -> Option<Vec<u8>> {
+        storage.reader().unwrap().get_cf("docs", key.as_bytes()).unwrap()
+    }
+
+    fn blob_files(path: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(path.join(blob::BLOB_DIR))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_values_are_read_from_both_tiers() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("spill_tiers");
+        let storage = Arc::new(storage::StandaloneStorage::open_with_options(&path, spill_options())?);
+        put(&storage, "big", value(1000, b'b'));
+        put(&storage, "small", value(10, b's'));
+
+        assert_eq!(get(&storage, "big"), Some(value(1000, b'b')));
+        assert_eq!(get(&storage, "small"), Some(value(10, b's')));
+        assert_eq!(storage.spill_stats()?, SpillStats { values: 1, disk_bytes: 1000, files: 1, ..SpillStats::default() });
+        // 磁盘层的值不计入内存占用
+        assert!(storage.memory_usage()? < 200, "{}", storage.memory_usage()?);
+
+        let api = RawKeyValueApi::new(Arc::clone(&storage));
+        let mut session = api.new_session();
+        let response = api.handle_command(&mut session, Command::new_scan("docs".to_string(), Vec::new(), None, 10));
+        let Response::Values(values) = response else { panic!("unexpected {:?}", response) };
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].1.0, value(1000, b'b'));
+        match api.handle_command(&mut session, Command::Info) {
+            Response::Info { spill, .. } => assert_eq!(spill.values, 1),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_deletes_and_compaction_reclaim_blob_files() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("spill_reclaim");
+        let storage = storage::StandaloneStorage::open_with_options(&path, spill_options())?;
+        for i in 0..4 {
+            put(&storage, &format!("k{}", i), value(500, b'0' + i));
+        }
+        storage.write(vec![
+            Modify::new_delete("docs".to_string(), b"k0".to_vec()),
+            Modify::new_delete("docs".to_string(), b"k1".to_vec()),
+        ])?;
+        put(&storage, "k2", value(50, b'x'));
+
+        let stats = storage.spill_stats()?;
+        assert_eq!((stats.values, stats.disk_bytes, stats.dead_bytes), (1, 500, 1500));
+
+        // 整理把仍在使用的值移到新文件，旧文件随之删除
+        storage.compact()?;
+        let stats = storage.spill_stats()?;
+        assert_eq!(stats, SpillStats { values: 1, disk_bytes: 500, files: 1, dead_bytes: 0, reclaimed_files: 1 });
+        assert_eq!(blob_files(&path).len(), 1);
+        assert_eq!(get(&storage, "k2"), Some(value(50, b'x')));
+        assert_eq!(get(&storage, "k3"), Some(value(500, b'3')));
+
+        storage.write(vec![Modify::new_delete("docs".to_string(), b"k3".to_vec())])?;
+        assert_eq!(storage.spill_stats()?.values, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deletes_do_not_read_spilled_values() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("spill_delete");
+        let storage = storage::StandaloneStorage::open_with_options(&path, spill_options())?;
+        put(&storage, "big", value(500, b'b'));
+        put(&storage, "other", value(500, b'o'));
+        // 清空 blob 文件，之后读取磁盘层的值都会失败
+        let file = path.join(blob::BLOB_DIR).join(&blob_files(&path)[0]);
+        fs::write(&file, b"")?;
+
+        // 删除不读取旧值，只把占用的空间记为可回收
+        storage.write(vec![Modify::new_delete("docs".to_string(), b"big".to_vec())])?;
+        assert_eq!(get(&storage, "big"), None);
+        let stats = storage.spill_stats()?;
+        assert_eq!((stats.values, stats.disk_bytes, stats.dead_bytes), (1, 500, 500));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_values_fail_without_panicking() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("spill_unreadable");
+        let storage = storage::StandaloneStorage::open_with_options(&path, spill_options())?;
+        storage.set_cf_options("docs", CfOptions { keep_versions: 2, ..CfOptions::default() })?;
+        put(&storage, "big", value(500, b'b'));
+        put(&storage, "small", value(10, b's'));
+        let file = path.join(blob::BLOB_DIR).join(&blob_files(&path)[0]);
+        fs::write(&file, b"")?;
+
+        assert!(storage.reader()?.get_cf("docs", b"big").is_err());
+        assert!(storage.flush().is_err());
+        assert!(storage.compact().is_err());
+        // 重建值索引失败时保留原来的选项
+        assert!(storage.set_cf_options("docs", CfOptions { keep_versions: 2, index_values: true, ..CfOptions::default() }).is_err());
+        assert!(!storage.cf_options("docs")?.index_values);
+
+        // 覆盖要把旧值记入历史，读取失败时整个批次不生效
+        let batch = vec![
+            Modify::new_put("docs".to_string(), b"small".to_vec(), value(10, b'n')),
+            Modify::new_put("docs".to_string(), b"big".to_vec(), value(10, b'n')),
+        ];
+        assert!(storage.write(batch).is_err());
+        assert_eq!(get(&storage, "small"), Some(value(10, b's')));
+
+        // 关闭版本记录后删除不需要旧值，刷盘恢复正常
+        storage.set_cf_options("docs", CfOptions::default())?;
+        storage.write(vec![Modify::new_delete("docs".to_string(), b"big".to_vec())])?;
+        storage.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovery_ignores_blob_files_left_by_a_crash() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("spill_recovery");
+        let storage = storage::StandaloneStorage::open_with_options(&path, spill_options())?;
+        put(&storage, "flushed", value(300, b'f'));
+        storage.flush()?;
+        put(&storage, "unflushed", value(300, b'u'));
+        // 模拟进程崩溃：不释放目录锁，留下未刷盘的值和写了一半的 blob 文件
+        std::mem::forget(storage);
+        fs::write(path.join(blob::BLOB_DIR).join("00000007.blob"), b"torn")?;
+
+        let options = StorageOptions { force_unlock: true, ..spill_options() };
+        let storage = storage::StandaloneStorage::open_with_options(&path, options)?;
+        assert_eq!(get(&storage, "flushed"), Some(value(300, b'f')));
+        assert_eq!(get(&storage, "unflushed"), None);
+        // 加载时重新写出需要的值，旧文件全部清除
+        assert_eq!(blob_files(&path), vec!["00000000.blob".to_string()]);
+        assert_eq!(storage.spill_stats()?.disk_bytes, 300);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spill_policy_moves_cold_values_to_disk() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_dir("spill_policy");
+        let options = StorageOptions {
+            max_memory_bytes: Some(20_000),
+            eviction: EvictionPolicy::Spill,
+            ..StorageOptions::default()
+        };
+        let storage = storage::StandaloneStorage::open_with_options(&path, options)?;
+        put(&storage, "hot", value(1000, b'h'));
+        for i in 0..100 {
+            put(&storage, &format!("cold{:03}", i), value(1000, b'c'));
+            assert!(get(&storage, "hot").is_some());
+            assert!(storage.memory_usage()? <= 20_000);
+        }
+
+        // 没有键被删除，移到磁盘的值照常读取
+        assert_eq!(storage.get_stats()?.0, 101);
+        assert_eq!(storage.evicted_keys()?, 0);
+        assert!(storage.spill_stats()?.values > 80);
+        assert_eq!(get(&storage, "hot"), Some(value(1000, b'h')));
+        for i in 0..100 {
+            assert_eq!(get(&storage, &format!("cold{:03}", i)), Some(value(1000, b'c')));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_storage_keeps_values_in_memory() {
+        let options = StorageOptions { eviction: EvictionPolicy::Spill, max_memory_bytes: Some(2000), ..spill_options() };
+        let storage = storage::StandaloneStorage::in_memory_with_options(options);
+        put(&storage, "big", value(1000, b'b'));
+        assert_eq!(get(&storage, "big"), Some(value(1000, b'b')));
+        assert_eq!(storage.spill_stats().unwrap(), SpillStats::default());
+
+        // 没有磁盘层时 Spill 与 None 相同，超出预算的写入被拒绝
+        let err = storage.write(vec![Modify::new_put("docs".to_string(), b"more".to_vec(), value(1000, b'm'))]).unwrap_err();
+        assert!(err.contains("OutOfMemoryBudget"), "{}", err);
+    }
+}
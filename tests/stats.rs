@@ -0,0 +1,100 @@
+use tinykv_rs::api::EncodedKey;
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::{Modify, ModifyOp};
+use tinykv_rs::storage::{StandaloneStorage, StorageOptions};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CFS: [&str; 3] = ["a", "b", "c"];
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+    }
+
+    fn options(clock: &Arc<MockClock>) -> StorageOptions {
+        StorageOptions { compress_threshold: Some(64), clock: clock.clone(), ..StorageOptions::default() }
+    }
+
+    fn random_op(rng: &mut Rng) -> Modify {
+        let cf = CFS[rng.below(3) as usize].to_string();
+        let key = format!("k{}", rng.below(20)).into_bytes();
+        match rng.below(3) {
+            // 一部分值足够长且可压缩
+            0 | 1 => Modify::new_put(cf, key, vec![b'x'; rng.below(200) as usize]),
+            _ => Modify::new_delete(cf, key),
+        }
+    }
+
+    /// 把模型的内容写入新的存储，它的统计相当于对现有数据的一次完整重算
+    fn assert_matches_recount(storage: &StandaloneStorage, model: &BTreeMap<(String, Vec<u8>), Vec<u8>>) {
+        let fresh = StandaloneStorage::in_memory_with_options(options(&Arc::new(MockClock::new(0))));
+        let batch = model.iter().map(|((cf, k), v)| Modify::new_put(cf.clone(), k.clone(), v.clone())).collect();
+        fresh.write(batch).unwrap();
+
+        assert_eq!(storage.total_keys(), model.len());
+        assert_eq!(storage.get_stats().unwrap(), fresh.get_stats().unwrap());
+        assert_eq!(storage.cf_usage().unwrap(), fresh.cf_usage().unwrap());
+        assert_eq!(storage.memory_usage().unwrap(), fresh.memory_usage().unwrap());
+        assert_eq!(storage.compression_stats().unwrap(), fresh.compression_stats().unwrap());
+    }
+
+    #[test]
+    fn test_incremental_stats_match_recount() {
+        let clock = Arc::new(MockClock::new(0));
+        let storage = StandaloneStorage::in_memory_with_options(options(&clock));
+        let mut model: BTreeMap<(String, Vec<u8>), Vec<u8>> = BTreeMap::new();
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for step in 0..500 {
+            match rng.below(10) {
+                // 单条或成批写入，包括覆盖和删除不存在的键
+                0..=6 => {
+                    let batch: Vec<Modify> = (0..1 + rng.below(4)).map(|_| random_op(&mut rng)).collect();
+                    for m in &batch {
+                        let entry = (m.cf.clone(), m.key.clone());
+                        match m.op {
+                            ModifyOp::Put => model.insert(entry, m.value.clone()),
+                            ModifyOp::Delete => model.remove(&entry),
+                        };
+                    }
+                    storage.write(batch).unwrap();
+                }
+                // 按前缀删除
+                7 => {
+                    let cf = CFS[rng.below(3) as usize];
+                    let prefix = format!("k{}", rng.below(3));
+                    storage.delete_prefix(EncodedKey::encode(cf, prefix.as_bytes()).as_bytes()).unwrap();
+                    model.retain(|(c, k), _| !(c == cf && k.starts_with(prefix.as_bytes())));
+                }
+                // 过期后由清理删除
+                _ => {
+                    let Some(((cf, key), _)) = model.iter().nth(rng.below(model.len().max(1) as u64) as usize) else {
+                        continue;
+                    };
+                    let (cf, key) = (cf.clone(), key.clone());
+                    storage.expire(&cf, &key, Duration::from_millis(1)).unwrap();
+                    clock.advance(Duration::from_millis(1));
+                    assert_eq!(storage.purge_expired().unwrap(), 1);
+                    model.remove(&(cf, key));
+                }
+            }
+            if step % 25 == 0 {
+                assert_matches_recount(&storage, &model);
+            }
+        }
+        assert_matches_recount(&storage, &model);
+    }
+}
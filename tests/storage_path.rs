@@ -0,0 +1,51 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::server::KvServer;
+use tinykv_rs::storage::StandaloneStorage;
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_creates_and_normalizes_directory() {
+        let base = temp_dir("storage_path");
+        let nested = base.join("a").join("b");
+
+        // 目录在打开时创建，而不是等到第一次刷盘
+        let storage = StandaloneStorage::open(&nested).unwrap();
+        assert!(nested.is_dir());
+        let canonical = fs::canonicalize(&nested).unwrap();
+        assert_eq!(storage.path(), Some(canonical.as_path()));
+        storage.write(vec![Modify::new_put("cf".into(), b"k".to_vec(), b"v".to_vec())]).unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        // 带 .. 和结尾分隔符的路径指向同一个目录
+        let messy = format!("{}/../b/", nested.display());
+        let reopened = StandaloneStorage::open(&messy).unwrap();
+        assert_eq!(reopened.path(), Some(canonical.as_path()));
+        assert_eq!(reopened.reader().unwrap().get_cf("cf", b"k").unwrap(), Some(b"v".to_vec()));
+        drop(reopened);
+    }
+
+    #[test]
+    fn test_in_memory_mode_is_explicit() -> Result<(), Box<dyn std::error::Error>> {
+        let err = StandaloneStorage::open("").err().unwrap();
+        assert!(err.starts_with("InvalidPath"), "{}", err);
+
+        let storage = StandaloneStorage::in_memory();
+        assert_eq!(storage.path(), None);
+        let err = storage.flush().unwrap_err();
+        assert!(err.starts_with("NoPersistencePath"), "{}", err);
+
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+        client.put("cf", "k", "v")?;
+        assert!(client.flush().unwrap_err().to_string().contains("NoPersistencePath"));
+        Ok(())
+    }
+}
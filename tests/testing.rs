@@ -0,0 +1,57 @@
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::testing::TestServer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_end_to_end_over_tcp() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+
+        client.put("default", "key1", "value1")?;
+        assert_eq!(client.get("default", "key1")?, Some("value1".to_string()));
+        client.put("cf1", "key", "value1")?;
+        client.put("cf2", "key", "value2")?;
+        assert_eq!(client.get("cf2", "key")?, Some("value2".to_string()));
+        assert_eq!(client.scan("cf1", "", None, 10)?, vec![("key".to_string(), "value1".to_string())]);
+        client.delete("default", "key1")?;
+        assert_eq!(client.get("default", "key1")?, None);
+
+        // 其他连接看到同样的数据
+        let mut other = server.connect()?;
+        assert_eq!(other.get("cf1", "key")?, Some("value1".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_servers_are_isolated_and_cleaned_up() -> Result<(), Box<dyn std::error::Error>> {
+        let mut first = TestServer::start()?;
+        let mut second = TestServer::start()?;
+        assert_ne!(first.addr(), second.addr());
+        assert_ne!(first.data_path(), second.data_path());
+
+        first.client().put("default", "k", "v")?;
+        assert_eq!(second.client().get("default", "k")?, None);
+
+        let path = first.data_path().to_path_buf();
+        first.client().flush()?;
+        assert!(path.exists());
+        drop(first);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_keeps_data_and_config() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start_with_config(ServerConfig { strict_cf_mode: true, ..ServerConfig::default() })?;
+        server.client().create_cf("users", None)?;
+        server.client().put("users", "k", "v")?;
+
+        server.restart()?;
+        assert_eq!(server.client().get("users", "k")?, Some("v".to_string()));
+        assert!(server.client().put("other", "k", "v").is_err());
+        Ok(())
+    }
+}
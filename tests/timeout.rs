@@ -0,0 +1,86 @@
+use tinykv_rs::client::{KvClient, KvError};
+use tinykv_rs::protocol::{self, Command, Response};
+use tinykv_rs::server::KvServer;
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 只回应握手、之后从不响应的服务器，连接保持打开直到客户端关闭
+    fn silent_server() -> (String, mpsc::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (stop, stopped) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            listener.set_nonblocking(true).unwrap();
+            while stopped.try_recv().is_err() {
+                if let Ok((stream, _)) = listener.accept() {
+                    stream.set_nonblocking(false).unwrap();
+                    thread::spawn(move || answer_hello_only(stream));
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+        (addr, stop)
+    }
+
+    fn answer_hello_only(mut stream: TcpStream) {
+        let mut pending = Vec::new();
+        while let Ok(Some(cmd)) = protocol::read_message::<Command, _>(&mut stream, &mut pending) {
+            if let Command::Hello { .. } = cmd {
+                let hello = Response::Hello { server_version: protocol::PROTOCOL_VERSION, accepted_features: Vec::new() };
+                stream.write_all(&serde_json::to_vec(&hello).unwrap()).unwrap();
+            }
+        }
+    }
+
+    fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+        e.downcast_ref::<KvError>() == Some(&KvError::Timeout)
+    }
+
+    #[test]
+    fn test_request_times_out_against_silent_server() {
+        let (addr, _stop) = silent_server();
+        let mut client = KvClient::connect_with_timeout(&addr, Duration::from_millis(100)).unwrap();
+
+        let started = Instant::now();
+        let err = client.get("default", "k").unwrap_err();
+        assert!(is_timeout(err.as_ref()), "unexpected error: {}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // 损坏的连接被丢弃并重新建立，而不是继续读取上一个请求的残留响应
+        let err = client.get("default", "k").unwrap_err();
+        assert!(is_timeout(err.as_ref()), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_set_timeout_on_existing_connection() {
+        let (addr, _stop) = silent_server();
+        let mut client = KvClient::connect(&addr).unwrap();
+        client.set_timeout(Duration::from_millis(50)).unwrap();
+
+        let err = client.put("default", "k", "v").unwrap_err();
+        assert!(is_timeout(err.as_ref()), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_timeout_client_and_detached_stream() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = KvServer::in_memory()?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect_with_timeout(&handle.local_addr().to_string(), Duration::from_secs(5))?;
+        client.put("default", "k", "v")?;
+        assert_eq!(client.get("default", "k")?, Some("v".to_string()));
+
+        let mut detached = KvClient::from_stream(std::io::empty());
+        assert!(detached.set_timeout(Duration::from_millis(10)).is_err());
+        assert!(detached.get("default", "k").is_err());
+        let err = detached.get("default", "k").unwrap_err();
+        assert_eq!(err.downcast_ref::<KvError>(), Some(&KvError::Broken));
+        Ok(())
+    }
+}
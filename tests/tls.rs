@@ -0,0 +1,105 @@
+#![cfg(feature = "tls")]
+
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::{KvServer, ServerConfig, ServerHandle, ShutdownOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 为 localhost 和 127.0.0.1 生成自签名证书，返回 (证书路径, 私钥路径)
+    fn self_signed(dir: &Path) -> (PathBuf, PathBuf) {
+        let names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let certified = rcgen::generate_simple_self_signed(names).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        fs::write(&cert, certified.cert.pem()).unwrap();
+        fs::write(&key, certified.key_pair.serialize_pem()).unwrap();
+        (cert, key)
+    }
+
+    fn start(cert: &Path, key: &Path) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            tls_cert_path: Some(cert.to_path_buf()),
+            tls_key_path: Some(key.to_path_buf()),
+            ..ServerConfig::default()
+        };
+        KvServer::with_config(config)?.start_background("127.0.0.1:0")
+    }
+
+    #[test]
+    fn test_commands_round_trip_over_tls() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = temp_dir("tls_round_trip");
+        let (cert, key) = self_signed(&dir);
+        let handle = start(&cert, &key)?;
+        let addr = handle.local_addr().to_string();
+
+        let mut client = KvClient::connect_tls(&addr, &cert, "localhost")?;
+        assert!(!client.negotiated_features().is_empty());
+        client.put("users", "u1", "Alice")?;
+        assert_eq!(client.get("users", "u1")?, Some("Alice".to_string()));
+        client.set_timeout(Duration::from_secs(5))?;
+        assert_eq!(client.scan("users", "", None, 10)?, vec![("u1".to_string(), "Alice".to_string())]);
+
+        // 按 IP 地址验证，数据在另一个连接上可见
+        let mut other = KvClient::connect_tls(&addr, &cert, "127.0.0.1")?;
+        assert_eq!(other.get("users", "u1")?, Some("Alice".to_string()));
+        other.delete("users", "u1")?;
+        assert_eq!(client.get("users", "u1")?, None);
+
+        drop((client, other));
+        handle.shutdown(ShutdownOptions { drain_timeout: Duration::from_secs(1), flush: false })?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_untrusted_or_plain_connections_fail() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = temp_dir("tls_untrusted");
+        let (cert, key) = self_signed(&dir);
+        let handle = start(&cert, &key)?;
+        let addr = handle.local_addr().to_string();
+
+        // 证书不属于该名字
+        assert!(KvClient::connect_tls(&addr, &cert, "example.com").is_err());
+        // 只信任另一张自签名证书
+        let other_dir = dir.join("other");
+        fs::create_dir_all(&other_dir)?;
+        let (other_cert, _) = self_signed(&other_dir);
+        assert!(KvClient::connect_tls(&addr, &other_cert, "localhost").is_err());
+
+        // 明文客户端读不到明文响应
+        let mut plain = TcpStream::connect(&addr)?;
+        plain.set_read_timeout(Some(Duration::from_secs(5)))?;
+        plain.write_all(br#"{"type":"Info"}"#)?;
+        plain.shutdown(Shutdown::Write)?;
+        let mut response = Vec::new();
+        let _ = plain.read_to_end(&mut response);
+        assert!(!String::from_utf8_lossy(&response).contains("total_keys"));
+
+        // 服务器仍然可用
+        KvClient::connect_tls(&addr, &cert, "localhost")?.put("cf", "k", "v")?;
+        handle.shutdown(ShutdownOptions { drain_timeout: Duration::from_secs(1), flush: false })?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cert_and_key_must_be_set_together() {
+        let dir = temp_dir("tls_config");
+        let (cert, _) = self_signed(&dir);
+        let config = ServerConfig { tls_cert_path: Some(cert), ..ServerConfig::default() };
+        assert!(KvServer::with_config(config).is_err());
+        let missing = ServerConfig {
+            tls_cert_path: Some(dir.join("missing.pem")),
+            tls_key_path: Some(dir.join("missing.key")),
+            ..ServerConfig::default()
+        };
+        let error = KvServer::with_config(missing).err().unwrap();
+        assert!(error.contains("missing.pem"), "{}", error);
+    }
+}
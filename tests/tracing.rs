@@ -0,0 +1,111 @@
+#![cfg(feature = "tracing")]
+
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::KvServer;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 捕获到的 span：名称、父 span 的名称和字段
+    #[derive(Debug, Clone)]
+    struct Captured {
+        id: u64,
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: BTreeMap<String, String>,
+    }
+
+    impl Captured {
+        fn field(&self, name: &str) -> Option<&str> {
+            self.fields.get(name).map(String::as_str)
+        }
+    }
+
+    struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    /// 按创建顺序记录 span 及之后填入的字段
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<Captured>>>);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let parent = ctx.span(id).and_then(|span| span.parent()).map(|parent| parent.name());
+            let mut span = Captured { id: id.into_u64(), name: attrs.metadata().name(), parent, fields: BTreeMap::new() };
+            attrs.record(&mut Fields(&mut span.fields));
+            self.0.lock().unwrap().push(span);
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            let mut spans = self.0.lock().unwrap();
+            // id 在 span 关闭后可以复用，取最近创建的那个
+            if let Some(span) = spans.iter_mut().rev().find(|s| s.id == id.into_u64()) {
+                values.record(&mut Fields(&mut span.fields));
+            }
+        }
+    }
+
+    #[test]
+    fn test_connection_and_command_spans() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?.to_string();
+        let server = Arc::new(KvServer::in_memory()?);
+        let capture = Capture::default();
+
+        let subscriber = Registry::default().with(capture.clone());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            tracing::subscriber::with_default(subscriber, || server.serve_connection(stream).unwrap());
+        });
+
+        let mut client = KvClient::connect(&addr)?;
+        client.put("users", "u1", "Alice")?;
+        assert_eq!(client.get("users", "u1")?, Some("Alice".to_string()));
+        assert!(client.create_cf("users", None).is_err());
+        drop(client);
+        handle.join().unwrap();
+
+        let spans = capture.0.lock().unwrap().clone();
+        let connections: Vec<&Captured> = spans.iter().filter(|s| s.name == "connection").collect();
+        assert_eq!(connections.len(), 1, "{:?}", spans);
+        assert!(connections[0].field("conn_id").is_some() && connections[0].field("peer_addr").is_some());
+
+        let commands: Vec<&Captured> = spans.iter().filter(|s| s.name == "command").collect();
+        assert!(commands.iter().all(|s| s.parent == Some("connection")), "{:?}", commands);
+        let kinds: Vec<&str> = commands.iter().filter_map(|s| s.field("cmd")).collect();
+        assert_eq!(kinds, vec!["Hello", "Put", "Get", "CreateCf"]);
+
+        let put = commands[1];
+        assert_eq!((put.field("cf"), put.field("key_len"), put.field("status"), put.field("code")), (Some("users"), Some("2"), Some("ok"), None));
+        for command in &commands {
+            let exec: u64 = command.field("exec_us").expect("exec_us").parse()?;
+            let lock_wait: u64 = command.field("lock_wait_us").expect("lock_wait_us").parse()?;
+            assert!(lock_wait <= exec, "{:?}", command);
+        }
+        let failed = commands[3];
+        assert_eq!((failed.field("status"), failed.field("code")), (Some("error"), Some("CfExists")));
+        Ok(())
+    }
+}
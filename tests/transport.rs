@@ -0,0 +1,59 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::server::KvServer;
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+/// 对字节做异或的包装流，模拟 TLS 这类会改写线上字节的传输层
+struct XorStream {
+    inner: TcpStream,
+    mask: u8,
+}
+
+impl Read for XorStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for b in &mut buf[..n] {
+            *b ^= self.mask;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for XorStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let masked: Vec<u8> = buf.iter().map(|b| b ^ self.mask).collect();
+        self.inner.write(&masked)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_commands_over_wrapped_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let server = Arc::new(KvServer::in_memory()?);
+
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        server.serve_connection(XorStream { inner: stream, mask: 0x5a }).unwrap();
+    });
+
+    let stream = TcpStream::connect(addr)?;
+    let mut client = KvClient::from_stream(XorStream { inner: stream, mask: 0x5a });
+
+    client.put("default", "name", "Alice")?;
+    assert_eq!(client.get("default", "name")?, Some("Alice".to_string()));
+
+    client.delete("default", "name")?;
+    assert_eq!(client.get("default", "name")?, None);
+
+    drop(client);
+    handle.join().unwrap();
+    Ok(())
+}
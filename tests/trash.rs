@@ -0,0 +1,147 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::server::ServerConfig;
+use tinykv_rs::storage::{self, StorageOptions, TrashEntry};
+use tinykv_rs::testing::TestServer;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_trash(retention: Duration) -> Result<TestServer, Box<dyn std::error::Error>> {
+        with_trash_and_clock(retention, Arc::new(MockClock::new(1_000_000)))
+    }
+
+    fn with_trash_and_clock(retention: Duration, clock: Arc<MockClock>) -> Result<TestServer, Box<dyn std::error::Error>> {
+        TestServer::start_with_config(ServerConfig {
+            trash_retention: Some(retention),
+            storage_options: StorageOptions { clock, ..StorageOptions::default() },
+            ..ServerConfig::default()
+        })
+    }
+
+    fn trashed(entries: &[TrashEntry]) -> Vec<(&str, &[u8], &[u8])> {
+        entries.iter().map(|e| (e.cf.as_str(), e.key.0.as_slice(), e.value.0.as_slice())).collect()
+    }
+
+    #[test]
+    fn test_delete_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let mut server = with_trash_and_clock(Duration::from_secs(3600), clock.clone())?;
+        let client = server.client();
+
+        client.put("users", "u1", "first")?;
+        client.delete("users", "u1")?;
+        clock.advance(Duration::from_millis(1));
+        client.put("users", "u1", "second")?;
+        client.delete("users", "u1")?;
+        // 删除不存在的键不进入回收站
+        client.delete("users", "missing")?;
+        assert_eq!(client.get("users", "u1")?, None);
+
+        let entries = client.scan_trash(Some("users"), 10)?;
+        assert_eq!(trashed(&entries), vec![("users", &b"u1"[..], &b"first"[..]), ("users", &b"u1"[..], &b"second"[..])]);
+        assert!(entries[0].deleted_at_ms < entries[1].deleted_at_ms);
+
+        // 恢复最近一次删除的值，再次恢复得到更早的值
+        client.restore_key("users", "u1", false)?;
+        assert_eq!(client.get("users", "u1")?, Some("second".to_string()));
+        let err = client.restore_key("users", "u1", false).unwrap_err();
+        assert!(err.to_string().starts_with("KeyExists"), "{}", err);
+        client.restore_key("users", "u1", true)?;
+        assert_eq!(client.get("users", "u1")?, Some("first".to_string()));
+
+        let err = client.restore_key("users", "u1", true).unwrap_err();
+        assert!(err.to_string().starts_with("NotInTrash"), "{}", err);
+        assert!(client.scan_trash(None, 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trash_is_scoped_to_the_database() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = with_trash(Duration::from_secs(3600))?;
+        let mut app = server.connect()?;
+        app.use_db("app")?;
+        app.put("users", "k", "app value")?;
+        app.delete("users", "k")?;
+        server.client().put("users", "k", "default value")?;
+        server.client().delete("users", "k")?;
+
+        assert_eq!(trashed(&app.scan_trash(None, 10)?), vec![("users", &b"k"[..], &b"app value"[..])]);
+        app.restore_key("users", "k", false)?;
+        assert_eq!(app.get("users", "k")?, Some("app value".to_string()));
+        assert_eq!(server.client().scan_trash(None, 10)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_db_moves_entries_to_trash() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = with_trash(Duration::from_secs(3600))?;
+        let mut app = server.connect()?;
+        app.use_db("app")?;
+        app.put("a", "1", "one")?;
+        app.put("b", "2", "two")?;
+
+        server.client().drop_db("app")?;
+        assert_eq!(app.get("a", "1")?, None);
+        assert_eq!(
+            trashed(&app.scan_trash(None, 10)?),
+            vec![("a", &b"1"[..], &b"one"[..]), ("b", &b"2"[..], &b"two"[..])]
+        );
+        app.restore_key("b", "2", false)?;
+        assert_eq!(app.get("b", "2")?, Some("two".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retention_expiry() {
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let options = StorageOptions { clock: clock.clone(), ..StorageOptions::default() };
+        let storage = Arc::new(storage::StandaloneStorage::in_memory_with_options(options));
+        storage.write(vec![tinykv_rs::protocol::Modify::new_put("cf".into(), b"k".to_vec(), b"v".to_vec())]).unwrap();
+        storage.soft_delete("cf", b"k").unwrap();
+        assert_eq!(storage.purge_trash(Some(Duration::from_secs(3600))).unwrap(), 0);
+
+        // 推进时钟超过保留期，清理线程在下一次检查时删除条目
+        let _sweeper = storage.start_trash_sweeper(Duration::from_millis(20), Duration::from_millis(10));
+        clock.advance(Duration::from_millis(21));
+        for _ in 0..100 {
+            if storage.trash_entries(None).unwrap().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(storage.trash_entries(None).unwrap().is_empty());
+        assert!(storage.restore_key("cf", b"k", false).unwrap_err().starts_with("NotInTrash"));
+    }
+
+    #[test]
+    fn test_purge_and_disabled_trash() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            trash_retention: Some(Duration::from_secs(3600)),
+            admin_token: Some("s".into()),
+            ..ServerConfig::default()
+        };
+        let mut server = TestServer::start_with_config(config)?;
+        let client = server.client();
+        client.put("cf", "k", "v")?;
+        client.delete("cf", "k")?;
+
+        assert!(client.purge_trash(true).is_err());
+        client.admin_auth("s")?;
+        assert_eq!(client.purge_trash(false)?, 0);
+        assert_eq!(client.purge_trash(true)?, 1);
+        assert!(client.scan_trash(None, 10)?.is_empty());
+
+        // 未开启回收站时删除直接生效
+        let mut plain = TestServer::start()?;
+        plain.client().put("cf", "k", "v")?;
+        plain.client().delete("cf", "k")?;
+        assert!(plain.client().scan_trash(None, 10)?.is_empty());
+        assert!(plain.client().restore_key("cf", "k", false).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use tinykv_rs::clock::MockClock;
+use tinykv_rs::protocol::Modify;
+use tinykv_rs::storage::{self, StorageOptions};
+use tinykv_rs::testing::{temp_dir, TestServer};
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(path: &Path, clock: &Arc<MockClock>) -> storage::StandaloneStorage {
+        let options = StorageOptions { clock: clock.clone(), ..StorageOptions::default() };
+        storage::StandaloneStorage::open_with_options(path, options).unwrap()
+    }
+
+    fn put(storage: &storage::StandaloneStorage, key: &str) {
+        storage.write(vec![Modify::new_put("cf".into(), key.as_bytes().to_vec(), b"v".to_vec())]).unwrap();
+    }
+
+    fn get(storage: &storage::StandaloneStorage, key: &str) -> Option<Vec<u8>> {
+        storage.reader().unwrap().get_cf("cf", key.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_expiration_survives_flush_and_reload() {
+        let path = temp_dir("ttl_reload");
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let storage = open(&path, &clock);
+
+        // short 和 long 进入基础快照，segment 只在段文件中
+        put(&storage, "short");
+        put(&storage, "long");
+        put(&storage, "plain");
+        storage.expire("cf", b"short", Duration::from_secs(10)).unwrap();
+        storage.expire("cf", b"long", Duration::from_secs(100)).unwrap();
+        storage.compact().unwrap();
+        put(&storage, "segment");
+        storage.expire("cf", b"segment", Duration::from_secs(20)).unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        clock.advance(Duration::from_secs(50));
+        let storage = open(&path, &clock);
+        let report = storage.take_recovery_report().unwrap().unwrap();
+        assert_eq!(report.expired_entries_dropped, 2);
+        assert_eq!(get(&storage, "short"), None);
+        assert_eq!(get(&storage, "segment"), None);
+        assert_eq!(get(&storage, "long"), Some(b"v".to_vec()));
+        assert_eq!(storage.ttl("cf", b"long").unwrap(), Some(Duration::from_secs(50)));
+        assert_eq!(storage.ttl("cf", b"plain").unwrap(), None);
+        assert!(storage.ttl("cf", b"short").unwrap_err().starts_with("KeyNotFound"));
+
+        // 过期后读取不到，清理前仍占着条目
+        clock.advance(Duration::from_secs(50));
+        assert_eq!(get(&storage, "long"), None);
+        assert!(storage.reader().unwrap().scan_cf("cf", b"", None, 10, None).unwrap().iter().all(|(k, _)| k == b"plain"));
+        assert_eq!(storage.purge_expired().unwrap(), 1);
+        storage.flush().unwrap();
+        drop(storage);
+
+        // 清理结果已刷盘，重新打开时没有需要丢弃的键
+        let storage = open(&path, &clock);
+        assert_eq!(storage.take_recovery_report().unwrap().unwrap().expired_entries_dropped, 0);
+        assert_eq!(storage.get_stats().unwrap().0, 1);
+        drop(storage);
+    }
+
+    #[test]
+    fn test_write_clears_expiration() {
+        let clock = Arc::new(MockClock::new(0));
+        let storage = storage::StandaloneStorage::in_memory_with_options(StorageOptions {
+            clock: clock.clone(),
+            ..StorageOptions::default()
+        });
+        put(&storage, "k");
+        storage.expire("cf", b"k", Duration::from_secs(1)).unwrap();
+        put(&storage, "k");
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(get(&storage, "k"), Some(b"v".to_vec()));
+        assert!(storage.expire("cf", b"missing", Duration::from_secs(1)).unwrap_err().starts_with("KeyNotFound"));
+
+        // 写路径先删除过期的键，读改写命令看不到它们
+        storage.expire("cf", b"k", Duration::from_secs(1)).unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(storage.get_del("cf", b"k").unwrap(), None);
+    }
+
+    #[test]
+    fn test_rename_and_copy_keep_expiration() {
+        let path = temp_dir("ttl_rename");
+        let clock = Arc::new(MockClock::new(1_000_000));
+        let storage = open(&path, &clock);
+        put(&storage, "a");
+        storage.expire("cf", b"a", Duration::from_secs(100)).unwrap();
+        storage.rename("cf", b"a", b"b", false).unwrap();
+        storage.copy("cf", b"b", b"c", false).unwrap();
+        assert!(storage.ttl("cf", b"a").unwrap_err().starts_with("KeyNotFound"));
+        assert_eq!(storage.ttl("cf", b"b").unwrap(), Some(Duration::from_secs(100)));
+        assert_eq!(storage.ttl("cf", b"c").unwrap(), Some(Duration::from_secs(100)));
+
+        // 没有过期时间的源键覆盖目标键时，目标键原来的过期时间被清除
+        put(&storage, "plain");
+        storage.copy("cf", b"plain", b"c", true).unwrap();
+        assert_eq!(storage.ttl("cf", b"c").unwrap(), None);
+
+        storage.flush().unwrap();
+        drop(storage);
+        clock.advance(Duration::from_secs(100));
+        let storage = open(&path, &clock);
+        assert_eq!(get(&storage, "b"), None);
+        assert_eq!(get(&storage, "c"), Some(b"v".to_vec()));
+    }
+
+    #[test]
+    fn test_expire_and_ttl_commands() -> Result<(), Box<dyn std::error::Error>> {
+        let mut server = TestServer::start()?;
+        let client = server.client();
+        client.put("sessions", "s1", "data")?;
+        assert_eq!(client.ttl("sessions", "s1")?, None);
+
+        client.expire("sessions", "s1", Duration::from_secs(60))?;
+        let ttl = client.ttl("sessions", "s1")?.unwrap();
+        assert!(ttl > Duration::from_secs(59) && ttl <= Duration::from_secs(60), "{:?}", ttl);
+
+        client.expire("sessions", "s1", Duration::ZERO)?;
+        assert_eq!(client.get("sessions", "s1")?, None);
+        let err = client.ttl("sessions", "s1").unwrap_err();
+        assert!(err.to_string().starts_with("KeyNotFound"), "{}", err);
+        Ok(())
+    }
+}
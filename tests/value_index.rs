@@ -0,0 +1,138 @@
+use tinykv_rs::client::KvClient;
+use tinykv_rs::api::{RawKeyValueApi, Session};
+use tinykv_rs::protocol::{self, Command, Modify, Response};
+use tinykv_rs::server::{KvServer, ServerConfig};
+use tinykv_rs::storage::{self, CfOptions, StorageOptions};
+use tinykv_rs::testing::temp_dir;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed() -> CfOptions {
+        CfOptions { index_values: true, ..CfOptions::default() }
+    }
+
+    fn open(path: Option<&Path>) -> storage::StandaloneStorage {
+        let options = StorageOptions {
+            cf_options: HashMap::from([("users".to_string(), indexed())]),
+            ..StorageOptions::default()
+        };
+        match path {
+            Some(path) => storage::StandaloneStorage::open_with_options(path, options).unwrap(),
+            None => storage::StandaloneStorage::in_memory_with_options(options),
+        }
+    }
+
+    fn put(storage: &storage::StandaloneStorage, cf: &str, key: &str, value: &str) {
+        storage.write(vec![Modify::new_put(cf.to_string(), key.as_bytes().to_vec(), value.as_bytes().to_vec())]).unwrap();
+    }
+
+    fn find(storage: &storage::StandaloneStorage, value: &str) -> Vec<String> {
+        storage
+            .reader()
+            .unwrap()
+            .find_by_value_cf("users", value.as_bytes(), 100)
+            .unwrap()
+            .into_iter()
+            .map(|k| String::from_utf8(k).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_index_follows_overwrite_and_delete() {
+        let storage = open(None);
+        put(&storage, "users", "u1", "active");
+        put(&storage, "users", "u2", "active");
+        put(&storage, "users", "u3", "banned");
+        assert_eq!(find(&storage, "active"), vec!["u1", "u2"]);
+
+        // 覆盖时从旧值的列表中移除
+        put(&storage, "users", "u1", "banned");
+        assert_eq!(find(&storage, "active"), vec!["u2"]);
+        assert_eq!(find(&storage, "banned"), vec!["u1", "u3"]);
+
+        storage.write(vec![Modify::new_delete("users".to_string(), b"u3".to_vec())]).unwrap();
+        assert_eq!(find(&storage, "banned"), vec!["u1"]);
+        assert!(find(&storage, "missing").is_empty());
+
+        let reader = storage.reader().unwrap();
+        assert_eq!(reader.find_by_value_cf("users", b"banned", 0).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_index_rebuilt_after_reload() {
+        let path = temp_dir("value_index_reload");
+        let storage = open(Some(&path));
+        put(&storage, "users", "u1", "active");
+        put(&storage, "users", "u2", "active");
+        storage.flush().unwrap();
+        put(&storage, "users", "u2", "banned");
+        storage.flush().unwrap();
+        drop(storage);
+
+        let reopened = open(Some(&path));
+        assert_eq!(find(&reopened, "active"), vec!["u1"]);
+        assert_eq!(find(&reopened, "banned"), vec!["u2"]);
+        drop(reopened);
+
+        // 选项随数据持久化，不传入选项时索引同样被重建
+        let reopened = storage::StandaloneStorage::open(&path).unwrap();
+        assert_eq!(find(&reopened, "banned"), vec!["u2"]);
+    }
+
+    #[test]
+    fn test_enable_and_disable_index_on_existing_data() {
+        let storage = storage::StandaloneStorage::in_memory();
+        put(&storage, "users", "u1", "active");
+        let err = storage.reader().unwrap().find_by_value_cf("users", b"active", 10).unwrap_err();
+        assert!(err.contains("not enabled"), "{}", err);
+
+        storage.set_cf_options("users", indexed()).unwrap();
+        assert_eq!(find(&storage, "active"), vec!["u1"]);
+
+        storage.set_cf_options("users", CfOptions::default()).unwrap();
+        assert!(storage.reader().unwrap().find_by_value_cf("users", b"active", 10).is_err());
+    }
+
+    #[test]
+    fn test_find_by_value_command() {
+        let api = RawKeyValueApi::new(Arc::new(open(None)));
+        let mut session = Session::default();
+        let put = |cf: &str| Command::Put { cf: cf.to_string(), key: b"k".to_vec(), value: b"v".to_vec() };
+        api.handle_command(&mut session, put("users"));
+        api.handle_command(&mut session, put("orders"));
+
+        let find = |cf: &str| Command::FindByValue { cf: cf.to_string(), value: b"v".to_vec(), limit: 10 };
+        match api.handle_command(&mut session, find("users")) {
+            Response::Keys(keys) => assert_eq!(keys, vec![protocol::Bytes(b"k".to_vec())]),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(matches!(api.handle_command(&mut session, find("orders")), Response::Error(_)));
+    }
+
+    #[test]
+    fn test_find_by_value_over_client() -> Result<(), Box<dyn std::error::Error>> {
+        let config = ServerConfig {
+            storage_options: StorageOptions {
+                cf_options: HashMap::from([("users".to_string(), indexed())]),
+                ..StorageOptions::default()
+            },
+            ..ServerConfig::default()
+        };
+        let handle = KvServer::with_config(config)?.start_background("127.0.0.1:0")?;
+        let mut client = KvClient::connect(&handle.local_addr().to_string())?;
+
+        for (key, value) in [("u1", "active"), ("u2", "banned"), ("u3", "active")] {
+            client.put("users", key, value)?;
+        }
+        assert_eq!(client.find_by_value("users", "active", 10)?, vec!["u1", "u3"]);
+        assert_eq!(client.find_by_value("users", "active", 1)?, vec!["u1"]);
+        assert!(client.find_by_value("default", "active", 10).is_err());
+        Ok(())
+    }
+}